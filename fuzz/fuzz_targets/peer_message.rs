@@ -0,0 +1,30 @@
+#![no_main]
+
+use bittorrent_client::peer::extension::{
+    EXTENDED_HANDSHAKE_ID, EXTENDED_MESSAGE_ID, ExtendedHandshake,
+};
+use bittorrent_client::peer::value::PeerMessage;
+use libfuzzer_sys::fuzz_target;
+
+// Reads messages off the input until it runs out or stops making sense. Whatever parses
+// must encode back to the frame it came from, and extended handshakes go through their
+// parser too.
+fuzz_target!(|data: &[u8]| {
+    let mut stream = data;
+    while let Ok(message) = PeerMessage::read_peer_message(&mut stream) {
+        let bytes = message.to_bytes();
+        assert_eq!(
+            PeerMessage::read_peer_message(&mut bytes.as_slice()).unwrap(),
+            message
+        );
+        if let PeerMessage::Unknown {
+            id: EXTENDED_MESSAGE_ID,
+            payload,
+        } = &message
+        {
+            if payload.first() == Some(&EXTENDED_HANDSHAKE_ID) {
+                let _ = ExtendedHandshake::from_payload(&payload[1..]);
+            }
+        }
+    }
+});
@@ -1,29 +1,104 @@
 use std::fmt;
 
+// Bytes of input shown after the offset in a Position.
+const CONTEXT_LENGTH: usize = 16;
+
+// Where in the input a parse failed, with the bytes found there so the offending spot
+// can be recognised without dumping the rest of a multi-megabyte torrent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub offset: usize,
+    pub context: String,
+}
+
+impl Position {
+    pub fn in_input(input: &[u8], offset: usize) -> Position {
+        let offset = offset.min(input.len());
+        let end = input.len().min(offset + CONTEXT_LENGTH);
+        let mut context = input[offset..end].escape_ascii().to_string();
+        if end < input.len() {
+            context.push_str("...");
+        }
+        Position { offset, context }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at byte {} near \"{}\"", self.offset, self.context)
+    }
+}
+
+// The parser fills in the position of the failing token; errors raised on values that
+// are already decoded have none.
 #[derive(Debug)]
 pub enum BencodeError {
-    InvalidInteger(String),
-    InvalidString(String),
-    InvalidList(String),
-    InvalidDict(String),
-    UnexpectedEof,
+    InvalidInteger(String, Option<Position>),
+    InvalidString(String, Option<Position>),
+    InvalidList(String, Option<Position>),
+    InvalidDict(String, Option<Position>),
+    UnexpectedEof(Option<Position>),
+    UnexpectedByte(u8, Option<Position>),
     MissingKey(String),
+    MissingIndex(usize),
+    InvalidPath(String),
+    LimitExceeded(String, Option<Position>),
     WrongType { expected: String, found: String },
 }
 
+impl BencodeError {
+    pub fn position(&self) -> Option<&Position> {
+        match self {
+            BencodeError::InvalidInteger(_, position)
+            | BencodeError::InvalidString(_, position)
+            | BencodeError::InvalidList(_, position)
+            | BencodeError::InvalidDict(_, position)
+            | BencodeError::UnexpectedEof(position)
+            | BencodeError::UnexpectedByte(_, position)
+            | BencodeError::LimitExceeded(_, position) => position.as_ref(),
+            _ => None,
+        }
+    }
+
+    // Sets the position unless one is already known, which is always the more precise.
+    pub(crate) fn at(mut self, input: &[u8], offset: usize) -> BencodeError {
+        if let BencodeError::InvalidInteger(_, position @ None)
+        | BencodeError::InvalidString(_, position @ None)
+        | BencodeError::InvalidList(_, position @ None)
+        | BencodeError::InvalidDict(_, position @ None)
+        | BencodeError::UnexpectedEof(position @ None)
+        | BencodeError::UnexpectedByte(_, position @ None)
+        | BencodeError::LimitExceeded(_, position @ None) = &mut self
+        {
+            *position = Some(Position::in_input(input, offset));
+        }
+        self
+    }
+}
+
 impl std::fmt::Display for BencodeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            BencodeError::UnexpectedEof => write!(f, "Unexpected EOF"),
-            BencodeError::InvalidInteger(msg) => write!(f, "Invalid Integer: {}", msg),
-            BencodeError::InvalidString(msg) => write!(f, "Invalid String: {}", msg),
-            BencodeError::InvalidList(msg) => write!(f, "Invalid List: {}", msg),
-            BencodeError::InvalidDict(msg) => write!(f, "Invalid Dict: {}", msg),
-            BencodeError::MissingKey(msg) => write!(f, "Missing Key: {}", msg),
+            BencodeError::UnexpectedEof(_) => write!(f, "Unexpected EOF")?,
+            BencodeError::UnexpectedByte(byte, _) => {
+                write!(f, "Unexpected byte: {}", byte.escape_ascii())?
+            }
+            BencodeError::InvalidInteger(msg, _) => write!(f, "Invalid Integer: {}", msg)?,
+            BencodeError::InvalidString(msg, _) => write!(f, "Invalid String: {}", msg)?,
+            BencodeError::InvalidList(msg, _) => write!(f, "Invalid List: {}", msg)?,
+            BencodeError::InvalidDict(msg, _) => write!(f, "Invalid Dict: {}", msg)?,
+            BencodeError::MissingKey(msg) => write!(f, "Missing Key: {}", msg)?,
+            BencodeError::MissingIndex(index) => write!(f, "Missing Index: {}", index)?,
+            BencodeError::InvalidPath(path) => write!(f, "Invalid Path: {}", path)?,
+            BencodeError::LimitExceeded(msg, _) => write!(f, "Limit Exceeded: {}", msg)?,
             BencodeError::WrongType { expected, found } => {
-                write!(f, "Wrong type, \nExpected:{} Found:{}", expected, found)
+                write!(f, "Wrong type, \nExpected:{} Found:{}", expected, found)?
             }
         }
+        match self.position() {
+            Some(position) => write!(f, " {}", position),
+            None => Ok(()),
+        }
     }
 }
 impl std::error::Error for BencodeError {}
@@ -0,0 +1,128 @@
+use super::errors::BencodeError;
+use super::value::BencodeValue;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+// How byte strings are written as JSON text. Either way a byte string becomes a one-key
+// object, {"$base64": "..."} or {"$hex": "..."}, so it reads back as bytes rather than
+// as a text string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BytesEncoding {
+    #[default]
+    Base64,
+    Hex,
+}
+
+impl BytesEncoding {
+    fn key(self) -> &'static str {
+        match self {
+            BytesEncoding::Base64 => "$base64",
+            BytesEncoding::Hex => "$hex",
+        }
+    }
+}
+
+pub fn to_json(value: &BencodeValue, encoding: BytesEncoding) -> Value {
+    match value {
+        BencodeValue::Integer(i) => Value::Number((*i).into()),
+        BencodeValue::String(s) => Value::String(s.clone()),
+        BencodeValue::Bytes(b) => {
+            let text = match encoding {
+                BytesEncoding::Base64 => STANDARD.encode(b),
+                BytesEncoding::Hex => b.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            };
+            let mut object = Map::new();
+            object.insert(encoding.key().to_string(), Value::String(text));
+            Value::Object(object)
+        }
+        BencodeValue::List(l) => Value::Array(l.iter().map(|v| to_json(v, encoding)).collect()),
+        BencodeValue::Dictionary(d) => {
+            let mut keys: Vec<&String> = d.keys().collect();
+            keys.sort();
+            Value::Object(
+                keys.into_iter()
+                    .map(|key| (key.clone(), to_json(&d[key], encoding)))
+                    .collect(),
+            )
+        }
+    }
+}
+
+// The reverse of to_json. Byte strings are accepted in either encoding. JSON has no
+// bencode equivalent for floats, booleans or null, so those are refused.
+pub fn from_json(value: &Value) -> Result<BencodeValue, BencodeError> {
+    match value {
+        Value::Number(n) => n.as_i64().map(BencodeValue::Integer).ok_or_else(|| {
+            BencodeError::InvalidInteger(format!("{} is not a 64-bit integer", n), None)
+        }),
+        Value::String(s) => Ok(BencodeValue::String(s.clone())),
+        Value::Array(items) => Ok(BencodeValue::List(
+            items.iter().map(from_json).collect::<Result<_, _>>()?,
+        )),
+        Value::Object(object) => {
+            if let Some(bytes) = encoded_bytes(object)? {
+                return Ok(BencodeValue::Bytes(bytes));
+            }
+            let dict = object
+                .iter()
+                .map(|(key, v)| Ok((key.clone(), from_json(v)?)))
+                .collect::<Result<HashMap<_, _>, BencodeError>>()?;
+            Ok(BencodeValue::Dictionary(dict))
+        }
+        Value::Bool(_) | Value::Null => Err(BencodeError::WrongType {
+            expected: "Number/String/Array/Object".into(),
+            found: if value.is_null() { "Null" } else { "Bool" }.into(),
+        }),
+    }
+}
+
+fn encoded_bytes(object: &Map<String, Value>) -> Result<Option<Vec<u8>>, BencodeError> {
+    if object.len() != 1 {
+        return Ok(None);
+    }
+    let (key, value) = object.iter().next().unwrap();
+    let Value::String(text) = value else {
+        return Ok(None);
+    };
+    if key == BytesEncoding::Base64.key() {
+        STANDARD
+            .decode(text)
+            .map(Some)
+            .map_err(|e| BencodeError::InvalidString(format!("Bad base64: {}", e), None))
+    } else if key == BytesEncoding::Hex.key() {
+        decode_hex(text).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>, BencodeError> {
+    let bad = || BencodeError::InvalidString(format!("Bad hex: {}", text), None);
+    if !text.len().is_multiple_of(2) {
+        return Err(bad());
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            text.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(bad)
+        })
+        .collect()
+}
+
+impl BencodeValue {
+    pub fn to_json(&self, encoding: BytesEncoding) -> Value {
+        to_json(self, encoding)
+    }
+}
+
+impl TryFrom<&Value> for BencodeValue {
+    type Error = BencodeError;
+
+    fn try_from(value: &Value) -> Result<BencodeValue, BencodeError> {
+        from_json(value)
+    }
+}
@@ -0,0 +1,33 @@
+// Bounds on what a single parse may do, so hostile input (a tracker response, a metadata
+// piece from a peer) can't make us recurse or allocate without limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    // Lists and dicts nested inside each other.
+    pub max_depth: usize,
+    // Largest length prefix a single string may declare.
+    pub max_string_length: usize,
+    // Sum of all decoded string lengths in the value.
+    pub max_total_size: usize,
+}
+
+impl Default for ParseLimits {
+    // Roomy enough for .torrent files of very large torrents.
+    fn default() -> Self {
+        ParseLimits {
+            max_depth: 128,
+            max_string_length: 64 << 20,
+            max_total_size: 128 << 20,
+        }
+    }
+}
+
+impl ParseLimits {
+    // For messages from the network, which are never legitimately large.
+    pub fn strict() -> ParseLimits {
+        ParseLimits {
+            max_depth: 32,
+            max_string_length: 1 << 20,
+            max_total_size: 4 << 20,
+        }
+    }
+}
@@ -1,5 +1,10 @@
 pub mod encoder;
 pub mod errors;
 pub mod helper;
+pub mod json;
+pub mod limits;
+pub mod options;
 pub mod parser;
+pub mod pretty;
+pub mod query;
 pub mod value;
@@ -0,0 +1,32 @@
+use super::limits::ParseLimits;
+
+// How a BencodeParser treats input. Lenient parsing takes what real-world encoders
+// produce: integers and lengths with leading zeros, -0, a '+' sign, and dict keys out of
+// order or repeated (the last one wins). Strict parsing holds to the spec and rejects all
+// of that, for checking that a file is well-formed rather than just usable.
+//
+// This is separate from ParseLimits::strict(), which bounds what untrusted input may
+// cost; either set of limits can be combined with either mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParserOptions {
+    pub limits: ParseLimits,
+    pub strict: bool,
+}
+
+impl ParserOptions {
+    pub fn strict() -> ParserOptions {
+        ParserOptions {
+            strict: true,
+            ..ParserOptions::default()
+        }
+    }
+
+    pub fn lenient() -> ParserOptions {
+        ParserOptions::default()
+    }
+
+    pub fn limits(mut self, limits: ParseLimits) -> ParserOptions {
+        self.limits = limits;
+        self
+    }
+}
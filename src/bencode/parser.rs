@@ -1,143 +1,357 @@
-use super::errors::BencodeError;
+use super::errors::{BencodeError, Position};
+use super::limits::ParseLimits;
+use super::options::ParserOptions;
 use super::value::BencodeValue;
+use std::collections::HashMap;
 
+// Parses leniently with ParseLimits::default(). Input from the network should go through
+// a BencodeParser with ParseLimits::strict() instead.
 pub fn parse_value(input: &[u8]) -> Result<(BencodeValue, &[u8]), BencodeError> {
-    if input.is_empty() {
-        return Err(BencodeError::UnexpectedEof);
+    BencodeParser::default().parse(input)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BencodeParser {
+    options: ParserOptions,
+}
+
+// Lists: Lists are encoded as an 'l' followed by their elements (also bencoded) followed by an 'e'.
+// For example l4:spam4:eggse corresponds to ['spam', 'eggs'].
+//
+// Dictionaries are encoded as a 'd' followed by a list of alternating
+// keys and their corresponding values followed by an 'e'.
+// For example, d3:cow3:moo4:spam4:eggse corresponds to
+// {'cow': 'moo', 'spam': 'eggs'} and d4:spaml1:a1:bee corresponds to
+// {'spam': ['a', 'b']}.
+// Keys must be strings and appear in sorted order (sorted as raw strings, not alphanumerics).
+enum Frame {
+    List(Vec<BencodeValue>),
+    // The key is set once read and taken again when its value arrives. The last key read
+    // is kept for checking order in strict mode.
+    Dict(
+        HashMap<String, BencodeValue>,
+        Option<String>,
+        Option<String>,
+    ),
+}
+
+impl Frame {
+    // A dict can't end between a key and its value.
+    fn can_close(&self) -> bool {
+        !matches!(self, Frame::Dict(_, Some(_), _))
     }
 
-    match input[0] {
-        b'i' => parse_int(input),
-        b'l' => parse_list(input),
-        b'd' => parse_dict(input),
-        b'0'..=b'9' => parse_string(input),
-        _ => Err(BencodeError::WrongType {
-            expected: "String/List/Integer/Dictionary".into(),
-            found: format!("Unknown byte: {}", input[0]),
-        }),
+    fn into_value(self) -> BencodeValue {
+        match self {
+            Frame::List(values) => BencodeValue::List(values),
+            Frame::Dict(dict, ..) => BencodeValue::Dictionary(dict),
+        }
     }
 }
+
+// Running totals for one parse, checked against the limits.
+struct ParseState {
+    depth: usize,
+    decoded: usize,
+}
+
+impl ParseState {
+    fn new() -> ParseState {
+        ParseState {
+            depth: 0,
+            decoded: 0,
+        }
+    }
+}
+
+impl BencodeParser {
+    pub fn new(limits: ParseLimits) -> BencodeParser {
+        BencodeParser::with_options(ParserOptions::lenient().limits(limits))
+    }
+
+    pub fn with_options(options: ParserOptions) -> BencodeParser {
+        BencodeParser { options }
+    }
+
+    pub fn limits(&self) -> &ParseLimits {
+        &self.options.limits
+    }
+
+    pub fn options(&self) -> &ParserOptions {
+        &self.options
+    }
+
+    pub fn parse<'a>(&self, input: &'a [u8]) -> Result<(BencodeValue, &'a [u8]), BencodeError> {
+        self.value(&mut ParseState::new(), input)
+    }
+
+    // Nested lists and dicts are kept on an explicit stack rather than the call stack, so
+    // deep nesting costs heap, not stack frames. Every remainder is a suffix of `input`,
+    // so the offset of the token being read is what has been consumed so far.
+    fn value<'a>(
+        &self,
+        state: &mut ParseState,
+        input: &'a [u8],
+    ) -> Result<(BencodeValue, &'a [u8]), BencodeError> {
+        let mut stack: Vec<Frame> = Vec::new();
+        let mut rest = input;
+
+        loop {
+            let offset = input.len() - rest.len();
+            let here = |error: BencodeError| error.at(input, offset);
+
+            if let Some(Frame::Dict(_, key @ None, last)) = stack.last_mut()
+                && !rest.starts_with(b"e")
+            {
+                if rest.is_empty() {
+                    return Err(here(BencodeError::InvalidDict(
+                        "Missing ending 'e'".into(),
+                        None,
+                    )));
+                }
+                let (parsed, remaining) = self.string(state, rest).map_err(here)?;
+                let parsed = parsed.as_string().map_err(|_| {
+                    here(BencodeError::InvalidDict("Key is not UTF-8".into(), None))
+                })?;
+                if self.options.strict {
+                    check_key_order(last.as_deref(), parsed).map_err(here)?;
+                    *last = Some(parsed.to_string());
+                }
+                *key = Some(parsed.to_string());
+                rest = remaining;
+                continue;
+            }
+
+            let value = match rest.first() {
+                None => {
+                    return Err(here(match stack.last() {
+                        Some(Frame::List(_)) => {
+                            BencodeError::InvalidList("Missing ending 'e'".into(), None)
+                        }
+                        Some(Frame::Dict(..)) => {
+                            BencodeError::InvalidDict("Missing ending 'e'".into(), None)
+                        }
+                        None => BencodeError::UnexpectedEof(None),
+                    }));
+                }
+                Some(b'e') if stack.last().is_some_and(Frame::can_close) => {
+                    rest = &rest[1..];
+                    state.depth -= 1;
+                    stack.pop().map(Frame::into_value).unwrap()
+                }
+                Some(b'l') => {
+                    self.enter(state).map_err(here)?;
+                    stack.push(Frame::List(Vec::new()));
+                    rest = &rest[1..];
+                    continue;
+                }
+                Some(b'd') => {
+                    self.enter(state).map_err(here)?;
+                    stack.push(Frame::Dict(HashMap::new(), None, None));
+                    rest = &rest[1..];
+                    continue;
+                }
+                Some(b'i') => {
+                    let (parsed, remaining) = int(rest, self.options.strict).map_err(here)?;
+                    rest = remaining;
+                    parsed
+                }
+                Some(b'0'..=b'9') => {
+                    let (parsed, remaining) = self.string(state, rest).map_err(here)?;
+                    rest = remaining;
+                    parsed
+                }
+                Some(&byte) => return Err(here(BencodeError::UnexpectedByte(byte, None))),
+            };
+
+            match stack.last_mut() {
+                None => return Ok((value, rest)),
+                Some(Frame::List(values)) => values.push(value),
+                Some(Frame::Dict(dict, key, _)) => {
+                    if let Some(key) = key.take() {
+                        dict.insert(key, value);
+                    }
+                }
+            }
+        }
+    }
+
+    // Strings: Strings are length-prefixed base ten followed by a colon and the string.
+    // For example 4:spam corresponds to 'spam'.
+    // The length is read only as far as its digits go, so a missing colon is reported
+    // where it should have been rather than wherever the next one happens to be.
+    fn string<'a>(
+        &self,
+        state: &mut ParseState,
+        input: &'a [u8],
+    ) -> Result<(BencodeValue, &'a [u8]), BencodeError> {
+        let colon_pos = input
+            .iter()
+            .position(|b| !b.is_ascii_digit())
+            .unwrap_or(input.len());
+        if input.get(colon_pos) != Some(&b':') {
+            return Err(BencodeError::InvalidString(
+                "Missing ':' after length".into(),
+                None,
+            ));
+        }
+
+        // Only ASCII digits were taken, so this is valid UTF-8.
+        let len_str = std::str::from_utf8(&input[..colon_pos]).unwrap_or_default();
+        if self.options.strict && len_str.len() > 1 && len_str.starts_with('0') {
+            return Err(BencodeError::InvalidString(
+                "Length has a leading zero".into(),
+                None,
+            ));
+        }
+        let len = len_str.parse::<usize>().map_err(|_| {
+            BencodeError::InvalidInteger(format!("Bad string length {:.20}", len_str), None)
+        })?;
+
+        let limits = &self.options.limits;
+        if len > limits.max_string_length {
+            return Err(BencodeError::LimitExceeded(
+                format!(
+                    "String of {} bytes exceeds {}",
+                    len, limits.max_string_length
+                ),
+                None,
+            ));
+        }
+        state.decoded += len;
+        if state.decoded > limits.max_total_size {
+            return Err(BencodeError::LimitExceeded(
+                format!("Decoded size exceeds {} bytes", limits.max_total_size),
+                None,
+            ));
+        }
+
+        let start = colon_pos + 1;
+        let end = start + len;
+
+        if input.len() < end {
+            return Err(BencodeError::InvalidString(
+                format!(
+                    "{} byte string runs {} bytes past the end of input",
+                    len,
+                    end - input.len()
+                ),
+                None,
+            ));
+        }
+
+        let bytes = &input[start..end];
+
+        let value = match std::str::from_utf8(bytes) {
+            Ok(s) => BencodeValue::String(s.to_string()),
+            Err(_) => BencodeValue::Bytes(bytes.to_vec()),
+        };
+        let rest = &input[end..];
+
+        Ok((value, rest))
+    }
+
+    fn enter(&self, state: &mut ParseState) -> Result<(), BencodeError> {
+        state.depth += 1;
+        if state.depth > self.options.limits.max_depth {
+            return Err(BencodeError::LimitExceeded(
+                format!("Nesting deeper than {}", self.options.limits.max_depth),
+                None,
+            ));
+        }
+        Ok(())
+    }
+}
+
 // Integers are represented by an 'i' followed by the number in base 10 followed by an 'e'.
 // For example i3e corresponds to 3 and i-3e corresponds to -3.
 // Integers have no size limitation. i-0e is invalid.
 // All encodings with a leading zero, such as i03e,
 // are invalid, other than i0e, which of course corresponds to 0.
+// A lone integer is always checked strictly; leniency comes with a BencodeParser.
 pub fn parse_int(input: &[u8]) -> Result<(BencodeValue, &[u8]), BencodeError> {
+    int(input, true).map_err(|error| error.at(input, 0))
+}
+
+fn int(input: &[u8], strict: bool) -> Result<(BencodeValue, &[u8]), BencodeError> {
     if !input.starts_with(b"i") {
-        return Err(BencodeError::InvalidInteger("Missing 'i'".to_string()));
+        return Err(BencodeError::InvalidInteger(
+            "Missing 'i'".to_string(),
+            None,
+        ));
     }
 
     let end = input
         .iter()
         .position(|&b| b == b'e')
-        .ok_or_else(|| BencodeError::InvalidInteger("Missing 'e'".into()))?;
+        .ok_or_else(|| BencodeError::InvalidInteger("Missing 'e'".into(), None))?;
 
     let num_str = std::str::from_utf8(&input[1..end])
-        .map_err(|_| BencodeError::InvalidInteger("Invalid UTF-8 in number".into()))?;
+        .map_err(|_| BencodeError::InvalidInteger("Invalid UTF-8 in number".into(), None))?;
 
-    if (num_str.starts_with("0") && num_str.len() > 1) || num_str.starts_with("-0") {
+    if strict && ((num_str.starts_with("0") && num_str.len() > 1) || num_str.starts_with("-0")) {
         return Err(BencodeError::InvalidInteger(
             "Integer starts with 0 or -0".to_string(),
+            None,
+        ));
+    }
+    if strict && num_str.starts_with('+') {
+        return Err(BencodeError::InvalidInteger(
+            "Integer has a '+' sign".to_string(),
+            None,
         ));
     }
 
-    let value = num_str
-        .parse::<i64>()
-        .map_err(|_| BencodeError::InvalidInteger(format!("Cannot parse: {}", num_str)))?;
+    // The digits are in the position's context; a stray 'e' far off could make them long.
+    let value = num_str.parse::<i64>().map_err(|_| {
+        BencodeError::InvalidInteger(format!("Cannot parse {} bytes as i64", num_str.len()), None)
+    })?;
 
     Ok((BencodeValue::Integer(value), &input[end + 1..]))
 }
 
-// Strings: Strings are length-prefixed base ten followed by a colon and the string.
-// For example 4:spam corresponds to 'spam'.
-pub fn parse_string(input: &[u8]) -> Result<(BencodeValue, &[u8]), BencodeError> {
-    let colon_pos = input
-        .iter()
-        .position(|&b| b == b':')
-        .ok_or_else(|| BencodeError::InvalidString("Missing ':'".into()))?;
-
-    let len_str = std::str::from_utf8(&input[..colon_pos])
-        .map_err(|_| BencodeError::InvalidInteger("Invalid UTF-8 in number".into()))?;
-    let len = len_str
-        .parse::<usize>()
-        .map_err(|_| BencodeError::InvalidInteger(format!("Cannot parse: {}", len_str)))?;
-
-    let start = colon_pos + 1;
-    let end = start + len;
-
-    if input.len() < end {
-        return Err(BencodeError::InvalidString(
-            "String length exceeds input length".into(),
+// Keys must be unique and in byte order.
+fn check_key_order(last: Option<&str>, key: &str) -> Result<(), BencodeError> {
+    let Some(last) = last else {
+        return Ok(());
+    };
+    if key.as_bytes() == last.as_bytes() {
+        return Err(BencodeError::InvalidDict(
+            format!("Duplicate key {:.40}", key),
+            None,
         ));
     }
+    if key.as_bytes() < last.as_bytes() {
+        return Err(BencodeError::InvalidDict(
+            format!("Key {:.40} comes after {:.40}", key, last),
+            None,
+        ));
+    }
+    Ok(())
+}
 
-    let bytes = &input[start..end];
-
-    let value = match std::str::from_utf8(bytes) {
-        Ok(s) => BencodeValue::String(s.to_string()),
-        Err(_) => BencodeValue::Bytes(bytes.to_vec()),
-    };
-    let rest = &input[end..];
-
-    Ok((value, rest))
+pub fn parse_string(input: &[u8]) -> Result<(BencodeValue, &[u8]), BencodeError> {
+    BencodeParser::default()
+        .string(&mut ParseState::new(), input)
+        .map_err(|error| error.at(input, 0))
 }
 
-// Lists: Lists are encoded as an 'l' followed by their elements (also bencoded) followed by an 'e'.
-// For example l4:spam4:eggse corresponds to ['spam', 'eggs'].
 pub fn parse_list(input: &[u8]) -> Result<(BencodeValue, &[u8]), BencodeError> {
     if !input.starts_with(b"l") {
-        return Err(BencodeError::InvalidList(format!(
-            "Input does not start with 'l': {}",
-            input[0]
-        )));
-    }
-
-    let mut values = Vec::new();
-    let mut rest = &input[1..];
-
-    while !rest.is_empty() && !rest.starts_with(b"e") {
-        let (value, remaining) = parse_value(rest)?;
-        values.push(value);
-        rest = remaining;
-    }
-
-    if !rest.starts_with(b"e") {
-        return Err(BencodeError::InvalidList("Missing ending 'e'".into()));
+        return Err(BencodeError::InvalidList(
+            "Input does not start with 'l'".into(),
+            Some(Position::in_input(input, 0)),
+        ));
     }
-
-    Ok((BencodeValue::List(values), &rest[1..]))
+    parse_value(input)
 }
 
-// Dictionaries are encoded as a 'd' followed by a list of alternating
-// keys and their corresponding values followed by an 'e'.
-// For example, d3:cow3:moo4:spam4:eggse corresponds to
-// {'cow': 'moo', 'spam': 'eggs'} and d4:spaml1:a1:bee corresponds to
-// {'spam': ['a', 'b']}.
-// Keys must be strings and appear in sorted order (sorted as raw strings, not alphanumerics).
 pub fn parse_dict(input: &[u8]) -> Result<(BencodeValue, &[u8]), BencodeError> {
     if !input.starts_with(b"d") {
-        return Err(BencodeError::InvalidDict(format!(
-            "Input does not start with 'd': {}",
-            input[0]
-        )));
-    }
-
-    let mut dict = std::collections::HashMap::new();
-    let mut rest = &input[1..];
-
-    while !rest.is_empty() && !rest.starts_with(b"e") {
-        let (key, remaining) = parse_string(rest)?;
-        rest = remaining;
-        let key_str = key.as_string()?;
-
-        let (value, remaining) = parse_value(rest)?;
-        dict.insert(key_str.to_string(), value);
-        rest = remaining;
-    }
-
-    if !rest.starts_with(b"e") {
-        return Err(BencodeError::InvalidDict("Missing ending 'e'".into()));
+        return Err(BencodeError::InvalidDict(
+            "Input does not start with 'd'".into(),
+            Some(Position::in_input(input, 0)),
+        ));
     }
-
-    Ok((BencodeValue::Dictionary(dict), &rest[1..]))
+    parse_value(input)
 }
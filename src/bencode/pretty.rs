@@ -0,0 +1,134 @@
+use super::value::BencodeValue;
+use std::fmt::Write;
+
+// How much of a value pretty() shows. Anything cut short says how much was left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrettyOptions {
+    // Spaces per nesting level.
+    pub indent: usize,
+    // Leading bytes of a byte string shown as hex.
+    pub byte_preview: usize,
+    // Characters of a text string shown.
+    pub max_string_length: usize,
+    // Items of a list or dict shown.
+    pub max_items: usize,
+    // Lists and dicts nested deeper than this are collapsed.
+    pub max_depth: usize,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        PrettyOptions {
+            indent: 2,
+            byte_preview: 16,
+            max_string_length: 200,
+            max_items: 50,
+            max_depth: 32,
+        }
+    }
+}
+
+impl PrettyOptions {
+    // Shows everything, for values known to be small.
+    pub fn full() -> PrettyOptions {
+        PrettyOptions {
+            byte_preview: usize::MAX,
+            max_string_length: usize::MAX,
+            max_items: usize::MAX,
+            ..PrettyOptions::default()
+        }
+    }
+}
+
+// Indented, one entry per line, with dict keys sorted as they are on the wire. Byte
+// strings show as their length and a hex preview, e.g. <20 bytes 0a1b2c...>.
+pub fn pretty(value: &BencodeValue, options: &PrettyOptions) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, options, 0);
+    out
+}
+
+impl BencodeValue {
+    pub fn pretty(&self) -> String {
+        pretty(self, &PrettyOptions::default())
+    }
+}
+
+fn write_value(out: &mut String, value: &BencodeValue, options: &PrettyOptions, depth: usize) {
+    match value {
+        BencodeValue::Integer(i) => {
+            let _ = write!(out, "{}", i);
+        }
+        BencodeValue::String(s) => {
+            let shown: String = s.chars().take(options.max_string_length).collect();
+            let _ = write!(out, "{:?}", shown);
+            let hidden = s.chars().count() - shown.chars().count();
+            if hidden > 0 {
+                let _ = write!(out, "... ({} more chars)", hidden);
+            }
+        }
+        BencodeValue::Bytes(b) => {
+            let _ = write!(out, "<{} bytes ", b.len());
+            for byte in b.iter().take(options.byte_preview) {
+                let _ = write!(out, "{:02x}", byte);
+            }
+            if b.len() > options.byte_preview {
+                out.push_str("...");
+            }
+            out.push('>');
+        }
+        BencodeValue::List(list) => {
+            let items: Vec<(Option<&str>, &BencodeValue)> =
+                list.iter().map(|item| (None, item)).collect();
+            write_container(out, ('[', ']'), &items, options, depth);
+        }
+        BencodeValue::Dictionary(dict) => {
+            let mut items: Vec<(Option<&str>, &BencodeValue)> = dict
+                .iter()
+                .map(|(key, item)| (Some(key.as_str()), item))
+                .collect();
+            items.sort_by_key(|(key, _)| *key);
+            write_container(out, ('{', '}'), &items, options, depth);
+        }
+    }
+}
+
+fn write_container(
+    out: &mut String,
+    (open, close): (char, char),
+    items: &[(Option<&str>, &BencodeValue)],
+    options: &PrettyOptions,
+    depth: usize,
+) {
+    out.push(open);
+    if items.is_empty() {
+        out.push(close);
+        return;
+    }
+    if depth >= options.max_depth {
+        let _ = write!(out, "... {} items{}", items.len(), close);
+        return;
+    }
+
+    let inner = " ".repeat(options.indent * (depth + 1));
+    for (key, item) in items.iter().take(options.max_items) {
+        out.push('\n');
+        out.push_str(&inner);
+        if let Some(key) = key {
+            let _ = write!(out, "{:?}: ", key);
+        }
+        write_value(out, item, options, depth + 1);
+        out.push(',');
+    }
+    if items.len() > options.max_items {
+        let _ = write!(
+            out,
+            "\n{}... {} more",
+            inner,
+            items.len() - options.max_items
+        );
+    }
+    out.push('\n');
+    out.push_str(&" ".repeat(options.indent * depth));
+    out.push(close);
+}
@@ -0,0 +1,83 @@
+use super::errors::BencodeError;
+use super::value::BencodeValue;
+
+// Shortcuts for digging into nested values without a get_dict(get_dict(...)) ladder:
+//
+//     value.get_path("info.files[0].length")?.as_int()?
+//     value.dict("info")?.list("files")?.index(0)?.int("length")?
+//
+// Path segments are dict keys separated by '.', each optionally followed by list
+// indices in brackets. Keys that contain '.' or '[' can't be reached by path; use the
+// typed accessors for those.
+impl BencodeValue {
+    pub fn get_path(&self, path: &str) -> Result<&BencodeValue, BencodeError> {
+        let mut current = self;
+        for segment in path.split('.') {
+            let (key, mut indices) = match segment.find('[') {
+                Some(bracket) => segment.split_at(bracket),
+                None => (segment, ""),
+            };
+
+            if !key.is_empty() {
+                current = current.get(key)?;
+            }
+            while !indices.is_empty() {
+                let close = indices
+                    .find(']')
+                    .filter(|_| indices.starts_with('['))
+                    .ok_or_else(|| BencodeError::InvalidPath(path.to_string()))?;
+                let index = indices[1..close]
+                    .parse()
+                    .map_err(|_| BencodeError::InvalidPath(path.to_string()))?;
+                current = current.index(index)?;
+                indices = &indices[close + 1..];
+            }
+        }
+        Ok(current)
+    }
+
+    pub fn get(&self, key: &str) -> Result<&BencodeValue, BencodeError> {
+        self.as_dict()?
+            .get(key)
+            .ok_or_else(|| BencodeError::MissingKey(key.to_string()))
+    }
+
+    pub fn index(&self, index: usize) -> Result<&BencodeValue, BencodeError> {
+        self.as_list()?
+            .get(index)
+            .ok_or(BencodeError::MissingIndex(index))
+    }
+
+    pub fn dict(&self, key: &str) -> Result<&BencodeValue, BencodeError> {
+        let value = self.get(key)?;
+        value.as_dict()?;
+        Ok(value)
+    }
+
+    pub fn list(&self, key: &str) -> Result<&BencodeValue, BencodeError> {
+        let value = self.get(key)?;
+        value.as_list()?;
+        Ok(value)
+    }
+
+    pub fn int(&self, key: &str) -> Result<i64, BencodeError> {
+        self.get(key)?.as_int().copied()
+    }
+
+    pub fn string(&self, key: &str) -> Result<&str, BencodeError> {
+        self.get(key)?.as_string()
+    }
+
+    // Raw bytes of a byte string. Unlike as_bytes, also accepts strings the parser
+    // decoded as UTF-8, since binary data can happen to be valid UTF-8.
+    pub fn bytes(&self, key: &str) -> Result<&[u8], BencodeError> {
+        match self.get(key)? {
+            BencodeValue::Bytes(b) => Ok(b),
+            BencodeValue::String(s) => Ok(s.as_bytes()),
+            other => Err(BencodeError::WrongType {
+                expected: "Bytes".into(),
+                found: other.type_name().into(),
+            }),
+        }
+    }
+}
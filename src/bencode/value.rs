@@ -10,6 +10,26 @@ pub enum BencodeValue {
     Dictionary(std::collections::HashMap<String, BencodeValue>),
 }
 
+// The derived drop would recurse once per nesting level, which overflows the stack on
+// values the iterative parser can build. Children are moved onto a worklist instead.
+impl Drop for BencodeValue {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        take_children(self, &mut pending);
+        while let Some(mut value) = pending.pop() {
+            take_children(&mut value, &mut pending);
+        }
+    }
+}
+
+fn take_children(value: &mut BencodeValue, pending: &mut Vec<BencodeValue>) {
+    match value {
+        BencodeValue::List(l) => pending.append(l),
+        BencodeValue::Dictionary(d) => pending.extend(d.drain().map(|(_, v)| v)),
+        _ => {}
+    }
+}
+
 impl std::fmt::Display for BencodeValue {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -1,6 +1,13 @@
 pub mod bencode;
 pub mod peer;
-// pub mod peer_handshake;
-pub mod peer_message;
+pub mod piece;
+pub mod prelude;
+pub mod retry;
+pub mod session;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod storage;
 pub mod torrent;
 pub mod tracker;
+pub mod units;
+pub mod webseed;
@@ -0,0 +1,67 @@
+// Names the client behind a peer id, for showing next to a peer's address. Most clients
+// use the Azureus style "-XXvvvv-" (two-letter code, four version characters); old
+// Mainline uses "Mv-v-v--". Anything else is reported as unknown.
+
+const AZUREUS_CODES: [(&[u8; 2], &str); 18] = [
+    (b"AZ", "Vuze"),
+    (b"BC", "BitComet"),
+    (b"BI", "BiglyBT"),
+    (b"BT", "BitTorrent"),
+    (b"DE", "Deluge"),
+    (b"FD", "Free Download Manager"),
+    (b"KT", "KTorrent"),
+    (b"LT", "libtorrent"),
+    (b"lt", "libTorrent (rakshasa)"),
+    (b"PI", "PicoTorrent"),
+    (b"qB", "qBittorrent"),
+    (b"RS", "bittorrent-client"),
+    (b"SD", "Thunder"),
+    (b"TL", "Tribler"),
+    (b"TR", "Transmission"),
+    (b"UT", "\u{b5}Torrent"),
+    (b"UM", "\u{b5}Torrent Mac"),
+    (b"WW", "WebTorrent"),
+];
+
+pub fn client_name(peer_id: &[u8; 20]) -> Option<String> {
+    azureus(peer_id).or_else(|| mainline(peer_id))
+}
+
+fn azureus(peer_id: &[u8; 20]) -> Option<String> {
+    if peer_id[0] != b'-' || peer_id[7] != b'-' {
+        return None;
+    }
+    let code = &peer_id[1..3];
+    let name = AZUREUS_CODES
+        .iter()
+        .find(|(known, _)| &known[..] == code)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| String::from_utf8_lossy(code).into_owned());
+    let version: Vec<String> = peer_id[3..7]
+        .iter()
+        .map(|&c| version_digit(c))
+        .collect::<Option<_>>()?;
+    Some(format!("{} {}", name, version.join(".")))
+}
+
+// Digits, with letters standing for 10 and up as some clients do.
+fn version_digit(c: u8) -> Option<String> {
+    match c {
+        b'0'..=b'9' => Some((c - b'0').to_string()),
+        b'A'..=b'Z' => Some((c - b'A' + 10).to_string()),
+        b'a'..=b'z' => Some((c - b'a' + 10).to_string()),
+        _ => None,
+    }
+}
+
+fn mainline(peer_id: &[u8; 20]) -> Option<String> {
+    if peer_id[0] != b'M' {
+        return None;
+    }
+    let text = std::str::from_utf8(&peer_id[1..8]).ok()?;
+    let version: Vec<&str> = text.trim_end_matches('-').split('-').collect();
+    let numeric = version
+        .iter()
+        .all(|part| !part.is_empty() && part.bytes().all(|c| c.is_ascii_digit()));
+    (numeric && version.len() == 3).then(|| format!("Mainline {}", version.join(".")))
+}
@@ -0,0 +1,43 @@
+use super::error::PeerMessageError;
+use super::framing::decode_message;
+use super::value::PeerMessage;
+use bytes::{BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+// Peer wire framing for tokio_util's FramedRead and FramedWrite, so async code can read
+// and write the crate's PeerMessages directly.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PeerMessageCodec;
+
+impl Decoder for PeerMessageCodec {
+    type Item = PeerMessage;
+    type Error = PeerMessageError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<PeerMessage>, PeerMessageError> {
+        decode_message(src)
+    }
+}
+
+impl Encoder<PeerMessage> for PeerMessageCodec {
+    type Error = PeerMessageError;
+
+    fn encode(&mut self, item: PeerMessage, dst: &mut BytesMut) -> Result<(), PeerMessageError> {
+        // Blocks go straight into the output buffer rather than through to_bytes.
+        if let PeerMessage::Piece {
+            index,
+            begin,
+            block,
+        } = &item
+        {
+            dst.reserve(13 + block.len());
+            dst.put_u32(block.len() as u32 + 9);
+            dst.put_u8(7);
+            dst.put_u32(*index);
+            dst.put_u32(*begin);
+            dst.extend_from_slice(block);
+            return Ok(());
+        }
+        dst.extend_from_slice(&item.to_bytes());
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use super::transport::PeerStream;
+use super::value::Handshake;
+use std::net::SocketAddr;
+
+// A peer we are connected to and have completed the handshake with.
+// Both sides start out choking and not interested.
+#[derive(Debug)]
+pub struct PeerConnection {
+    pub addr: SocketAddr,
+    pub stream: Box<dyn PeerStream>,
+    pub remote: Handshake,
+    pub am_choking: bool,
+    pub am_interested: bool,
+    pub peer_choking: bool,
+    pub peer_interested: bool,
+    // Whether the peer connected to us rather than the other way round.
+    pub incoming: bool,
+}
+
+impl PeerConnection {
+    pub fn new(
+        addr: SocketAddr,
+        stream: impl PeerStream + 'static,
+        remote: Handshake,
+    ) -> PeerConnection {
+        PeerConnection {
+            addr,
+            stream: Box::new(stream),
+            remote,
+            am_choking: true,
+            am_interested: false,
+            peer_choking: true,
+            peer_interested: false,
+            incoming: false,
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use super::connection::PeerConnection;
+use super::error::PeerHandshakeError;
+use super::pex::{PexFlags, PexPeer};
+use super::reserved::ReservedBits;
+use super::socks::{self, Socks5Proxy};
+use super::value::{Handshake, PeerTimeouts};
+use crate::retry::RetryPolicy;
+use crate::tracker::value::Peer;
+use std::collections::VecDeque;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+
+#[derive(Debug, Clone)]
+pub struct DialTarget {
+    pub ip: IpAddr,
+    pub port: u16,
+    // Alternate port a peer advertised for obfuscated connections (e.g. via PEX flags).
+    pub obfuscated_port: Option<u16>,
+    // Hints from PEX; tracker peers come without any.
+    pub flags: PexFlags,
+}
+
+impl From<&Peer> for DialTarget {
+    fn from(peer: &Peer) -> Self {
+        DialTarget {
+            ip: peer.ip,
+            port: peer.port,
+            obfuscated_port: None,
+            flags: PexFlags::default(),
+        }
+    }
+}
+
+impl From<&PexPeer> for DialTarget {
+    fn from(peer: &PexPeer) -> Self {
+        DialTarget {
+            ip: IpAddr::V4(*peer.addr.ip()),
+            port: peer.addr.port(),
+            obfuscated_port: None,
+            flags: peer.flags,
+        }
+    }
+}
+
+impl DialTarget {
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        let mut addrs = vec![SocketAddr::new(self.ip, self.port)];
+        if let Some(port) = self.obfuscated_port.filter(|&p| p != self.port) {
+            addrs.push(SocketAddr::new(self.ip, port));
+        }
+        addrs
+    }
+
+    // Lower is dialed earlier. Seeds are what a downloader wants most and are useless to a
+    // seed; peers someone else reached directly are likelier to accept us, while those
+    // advertising holepunch support tend to sit behind a NAT.
+    pub fn dial_rank(&self, we_are_seed: bool) -> u8 {
+        let mut rank = 0;
+        if self.flags.contains(PexFlags::SEED) == we_are_seed {
+            rank += 4;
+        }
+        if !self.flags.contains(PexFlags::REACHABLE) {
+            rank += 2;
+        }
+        if self.flags.contains(PexFlags::SUPPORTS_HOLEPUNCH) {
+            rank += 1;
+        }
+        rank
+    }
+}
+
+// Stable, so candidates with equal hints keep the order they were discovered in.
+pub fn prioritize(targets: &mut [DialTarget], we_are_seed: bool) {
+    targets.sort_by_key(|target| target.dial_rank(we_are_seed));
+}
+
+// Which address family to dial first when a swarm hands out peers on both.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FamilyPreference {
+    #[default]
+    Either,
+    Ipv4,
+    Ipv6,
+}
+
+impl FamilyPreference {
+    pub fn prefers(&self, ip: IpAddr) -> bool {
+        match self {
+            FamilyPreference::Either => true,
+            FamilyPreference::Ipv4 => ip.is_ipv4(),
+            FamilyPreference::Ipv6 => ip.is_ipv6(),
+        }
+    }
+
+    // Stable like prioritize, so each family keeps its own order.
+    pub fn sort(&self, addrs: &mut [SocketAddr]) {
+        addrs.sort_by_key(|addr| !self.prefers(addr.ip()));
+    }
+}
+
+// Many tracker-returned peers sit behind NATs or are briefly overloaded, so a single
+// blocking connect gives up on them far too early. The connector retries with
+// exponential backoff and dials every known port of a peer at the same time, keeping
+// whichever connection comes up first.
+#[derive(Debug, Clone)]
+pub struct PeerConnector {
+    policy: RetryPolicy,
+    timeouts: PeerTimeouts,
+    proxy: Option<Socks5Proxy>,
+    reserved: ReservedBits,
+}
+
+impl PeerConnector {
+    pub fn new(policy: RetryPolicy, timeouts: PeerTimeouts) -> PeerConnector {
+        PeerConnector {
+            policy,
+            timeouts,
+            proxy: None,
+            reserved: ReservedBits::FAST | ReservedBits::V2,
+        }
+    }
+
+    pub fn with_proxy(mut self, proxy: Socks5Proxy) -> PeerConnector {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    // Capabilities sent in our handshakes; fast and v2 unless set otherwise.
+    pub fn with_reserved(mut self, reserved: ReservedBits) -> PeerConnector {
+        self.reserved = reserved;
+        self
+    }
+
+    pub fn connect(&self, target: &DialTarget) -> Result<TcpStream, PeerHandshakeError> {
+        self.open(target).map(|(_, stream)| stream)
+    }
+
+    // The stream and the address it reached. With a proxy the stream's own peer address
+    // is the proxy's.
+    fn open(&self, target: &DialTarget) -> Result<(SocketAddr, TcpStream), PeerHandshakeError> {
+        let mut attempt = 0;
+        loop {
+            match self.open_simultaneously(&target.addrs()) {
+                Ok((addr, stream)) => {
+                    stream.set_read_timeout(Some(self.timeouts.read))?;
+                    stream.set_write_timeout(Some(self.timeouts.write))?;
+                    return Ok((addr, stream));
+                }
+                Err(err) => {
+                    attempt += 1;
+                    if attempt >= self.policy.max_attempts {
+                        return Err(err.into());
+                    }
+                    thread::sleep(self.policy.jittered(attempt));
+                }
+            }
+        }
+    }
+
+    pub fn connect_and_handshake(
+        &self,
+        target: &DialTarget,
+        info_hash: &[u8; 20],
+        own_peer_id: &[u8; 20],
+    ) -> Result<PeerConnection, PeerHandshakeError> {
+        let (addr, mut stream) = self.open(target)?;
+        let remote = Handshake::perform_handshake(
+            &mut stream,
+            info_hash,
+            own_peer_id,
+            self.reserved,
+            self.timeouts.handshake,
+        )?;
+
+        Ok(PeerConnection::new(addr, stream, remote))
+    }
+
+    // Dials all targets with at most `max_parallel` connects and handshakes in flight.
+    // Connections are delivered through the returned channel in the order their
+    // handshakes complete; the channel closes once every target has been tried.
+    pub fn dial_all(
+        &self,
+        targets: Vec<DialTarget>,
+        info_hash: [u8; 20],
+        own_peer_id: [u8; 20],
+        max_parallel: usize,
+    ) -> mpsc::Receiver<PeerConnection> {
+        let (sender, receiver) = mpsc::channel();
+        let workers = max_parallel.max(1).min(targets.len());
+        let queue = Arc::new(Mutex::new(VecDeque::from(targets)));
+
+        for _ in 0..workers {
+            let connector = self.clone();
+            let queue = Arc::clone(&queue);
+            let sender = sender.clone();
+
+            thread::spawn(move || {
+                loop {
+                    let Some(target) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+                    if let Ok(connection) =
+                        connector.connect_and_handshake(&target, &info_hash, &own_peer_id)
+                        && sender.send(connection).is_err()
+                    {
+                        // Nobody is listening anymore.
+                        break;
+                    }
+                }
+            });
+        }
+
+        receiver
+    }
+
+    fn open_simultaneously(
+        &self,
+        addrs: &[SocketAddr],
+    ) -> std::io::Result<(SocketAddr, TcpStream)> {
+        let (sender, receiver) = mpsc::channel();
+
+        for &addr in addrs {
+            let sender = sender.clone();
+            let proxy = self.proxy.clone();
+            let timeout = self.timeouts.connect;
+            thread::spawn(move || {
+                let result = socks::connect(proxy.as_ref(), addr, timeout);
+                let _ = sender.send(result.map(|stream| (addr, stream)));
+            });
+        }
+        drop(sender);
+
+        let mut last_err = None;
+        for result in receiver {
+            match result {
+                Ok(opened) => return Ok(opened),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| std::io::Error::other("no address to dial")))
+    }
+}
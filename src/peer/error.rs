@@ -32,6 +32,7 @@ impl Error for HandshakeError {}
 pub enum PeerHandshakeError {
     HandshakeError(HandshakeError),
     IOError(std::io::Error),
+    Timeout,
 }
 
 impl fmt::Display for PeerHandshakeError {
@@ -39,6 +40,7 @@ impl fmt::Display for PeerHandshakeError {
         match self {
             Self::HandshakeError(h) => write!(f, "{}", h),
             Self::IOError(i) => write!(f, "{}", i),
+            Self::Timeout => write!(f, "Timed out waiting for peer"),
         }
     }
 }
@@ -47,7 +49,12 @@ impl Error for PeerHandshakeError {}
 
 impl From<std::io::Error> for PeerHandshakeError {
     fn from(err: std::io::Error) -> Self {
-        PeerHandshakeError::IOError(err)
+        match err.kind() {
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => {
+                PeerHandshakeError::Timeout
+            }
+            _ => PeerHandshakeError::IOError(err),
+        }
     }
 }
 
@@ -56,3 +63,73 @@ impl From<HandshakeError> for PeerHandshakeError {
         PeerHandshakeError::HandshakeError(err)
     }
 }
+
+#[derive(Debug)]
+pub enum PeerMessageError {
+    IOError(std::io::Error),
+    InvalidPayloadLength { id: u8, length: usize },
+    MessageTooLong(u32),
+}
+
+impl fmt::Display for PeerMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IOError(i) => write!(f, "{}", i),
+            Self::InvalidPayloadLength { id, length } => {
+                write!(f, "Invalid payload length {} for message id {}", length, id)
+            }
+            Self::MessageTooLong(length) => {
+                write!(f, "Message length {} exceeds the limit", length)
+            }
+        }
+    }
+}
+
+impl Error for PeerMessageError {}
+
+impl From<std::io::Error> for PeerMessageError {
+    fn from(value: std::io::Error) -> Self {
+        PeerMessageError::IOError(value)
+    }
+}
+
+#[derive(Debug)]
+pub enum SocksError {
+    UnexpectedVersion(u8),
+    NoAcceptableMethod,
+    AuthenticationFailed,
+    CredentialsTooLong,
+    HostnameTooLong,
+    // Reply code of a refused CONNECT.
+    ConnectFailed(u8),
+    UnknownAddressType(u8),
+}
+
+impl fmt::Display for SocksError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnexpectedVersion(version) => {
+                write!(f, "Proxy answered with SOCKS version {}", version)
+            }
+            Self::NoAcceptableMethod => write!(f, "Proxy accepts none of our auth methods"),
+            Self::AuthenticationFailed => write!(f, "Proxy refused the credentials"),
+            Self::CredentialsTooLong => {
+                write!(f, "Proxy username and password are limited to 255 bytes")
+            }
+            Self::HostnameTooLong => write!(f, "Hostnames are limited to 255 bytes"),
+            Self::ConnectFailed(code) => write!(f, "Proxy couldn't connect (reply {})", code),
+            Self::UnknownAddressType(kind) => {
+                write!(f, "Proxy replied with unknown address type {}", kind)
+            }
+        }
+    }
+}
+
+impl Error for SocksError {}
+
+// Connections through a proxy fail like any other connection to their callers.
+impl From<SocksError> for std::io::Error {
+    fn from(err: SocksError) -> Self {
+        std::io::Error::other(err)
+    }
+}
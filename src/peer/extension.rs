@@ -0,0 +1,95 @@
+use crate::bencode::errors::BencodeError;
+use crate::bencode::limits::ParseLimits;
+use crate::bencode::parser::BencodeParser;
+use crate::bencode::value::BencodeValue;
+use crate::peer::value::PeerMessage;
+use bytes::Bytes;
+use std::collections::HashMap;
+
+// Message id 20 is reserved for the extension protocol (BEP 10); extended id 0 within it
+// is the handshake.
+pub const EXTENDED_MESSAGE_ID: u8 = 20;
+pub const EXTENDED_HANDSHAKE_ID: u8 = 0;
+
+// Payload of the extended handshake. `port` is the "p" field: the port the sender accepts
+// connections on, which may differ from the one the connection came from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtendedHandshake {
+    // Extension name -> the id the sender wants us to use for it. Id 0 disables it.
+    pub extensions: HashMap<String, u8>,
+    pub port: Option<u16>,
+    pub client: Option<String>,
+    // Requests the sender lets us keep queued with it.
+    pub reqq: Option<u32>,
+}
+
+impl ExtendedHandshake {
+    pub fn to_bencode(&self) -> BencodeValue {
+        let m = self
+            .extensions
+            .iter()
+            .map(|(name, &id)| (name.clone(), BencodeValue::Integer(id as i64)))
+            .collect();
+
+        let mut dict = HashMap::new();
+        dict.insert("m".to_string(), BencodeValue::Dictionary(m));
+        if let Some(port) = self.port {
+            dict.insert("p".to_string(), BencodeValue::Integer(port as i64));
+        }
+        if let Some(client) = &self.client {
+            dict.insert("v".to_string(), BencodeValue::String(client.clone()));
+        }
+        if let Some(reqq) = self.reqq {
+            dict.insert("reqq".to_string(), BencodeValue::Integer(reqq as i64));
+        }
+        BencodeValue::Dictionary(dict)
+    }
+
+    pub fn to_message(&self) -> PeerMessage {
+        PeerMessage::Extended {
+            id: EXTENDED_HANDSHAKE_ID,
+            payload: Bytes::from(self.to_bencode().encode()),
+        }
+    }
+
+    // Parses the payload of an extended handshake message, i.e. what follows the extended
+    // message id. It comes straight from the peer, so the strict limits apply.
+    pub fn from_payload(payload: &[u8]) -> Result<ExtendedHandshake, BencodeError> {
+        let (value, _) = BencodeParser::new(ParseLimits::strict()).parse(payload)?;
+        ExtendedHandshake::from_bencode(&value)
+    }
+
+    // Unknown keys are ignored, as are extension ids and ports that are out of range.
+    pub fn from_bencode(value: &BencodeValue) -> Result<ExtendedHandshake, BencodeError> {
+        let dict = value.as_dict()?;
+
+        let mut extensions = HashMap::new();
+        if let Some(m) = dict.get("m") {
+            for (name, id) in m.as_dict()? {
+                if let Ok(id) = u8::try_from(*id.as_int()?) {
+                    extensions.insert(name.clone(), id);
+                }
+            }
+        }
+
+        let port = match dict.get("p") {
+            Some(p) => u16::try_from(*p.as_int()?).ok().filter(|&p| p != 0),
+            None => None,
+        };
+        let client = dict
+            .get("v")
+            .and_then(|v| v.as_string().ok())
+            .map(str::to_string);
+        let reqq = match dict.get("reqq") {
+            Some(reqq) => u32::try_from(*reqq.as_int()?).ok().filter(|&r| r != 0),
+            None => None,
+        };
+
+        Ok(ExtendedHandshake {
+            extensions,
+            port,
+            client,
+            reqq,
+        })
+    }
+}
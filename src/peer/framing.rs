@@ -0,0 +1,82 @@
+use super::error::PeerMessageError;
+use super::value::{MAX_MESSAGE_LENGTH, PeerMessage};
+use bytes::{Buf, BytesMut};
+use std::io::{ErrorKind, Read};
+
+// Bytes asked of the reader per read.
+const READ_CHUNK: usize = 16 * 1024;
+
+// Takes one whole message off the front of `buffer`, or returns None and leaves the buffer
+// alone if it doesn't hold one yet. Oversized length prefixes are refused before anything
+// is reserved for them.
+pub fn decode_message(buffer: &mut BytesMut) -> Result<Option<PeerMessage>, PeerMessageError> {
+    let Some(prefix) = buffer.get(..4) else {
+        return Ok(None);
+    };
+    let length = u32::from_be_bytes(prefix.try_into().unwrap());
+    if length > MAX_MESSAGE_LENGTH {
+        return Err(PeerMessageError::MessageTooLong(length));
+    }
+    let frame_length = 4 + length as usize;
+    if buffer.len() < frame_length {
+        buffer.reserve(frame_length - buffer.len());
+        return Ok(None);
+    }
+
+    buffer.advance(4);
+    if length == 0 {
+        return Ok(Some(PeerMessage::KeepAlive));
+    }
+    let mut frame = buffer.split_to(length as usize).freeze();
+    let payload = frame.split_off(1);
+    PeerMessage::parse_frame(frame[0], payload).map(Some)
+}
+
+// Length-prefixed framing over a reader that may hand out a message in pieces, such as a
+// non-blocking socket. Whatever has arrived is kept until a whole message is in.
+#[derive(Debug, Default)]
+pub struct MessageDecoder {
+    buffer: BytesMut,
+}
+
+impl MessageDecoder {
+    pub fn new() -> MessageDecoder {
+        MessageDecoder::default()
+    }
+
+    // Adds bytes received some other way.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    // Bytes received but not yet handed out as messages.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn decode(&mut self) -> Result<Option<PeerMessage>, PeerMessageError> {
+        decode_message(&mut self.buffer)
+    }
+
+    // The next message, reading from `reader` until one is complete. Returns None when a
+    // non-blocking reader has nothing more for now; the partial message is kept for the
+    // next call. The reader closing mid-message or between messages is UnexpectedEof.
+    pub fn read_message(
+        &mut self,
+        reader: &mut impl Read,
+    ) -> Result<Option<PeerMessage>, PeerMessageError> {
+        let mut chunk = [0u8; READ_CHUNK];
+        loop {
+            if let Some(message) = self.decode()? {
+                return Ok(Some(message));
+            }
+            match reader.read(&mut chunk) {
+                Ok(0) => return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into()),
+                Ok(read) => self.feed(&chunk[..read]),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(None),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
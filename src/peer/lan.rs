@@ -0,0 +1,13 @@
+use std::net::IpAddr;
+
+// Addresses that can only be on the local network: RFC 1918 and link-local for IPv4,
+// unique local (fc00::/7) and link-local for IPv6. Loopback isn't included; that's us.
+pub fn is_lan(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_link_local(),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_lan(IpAddr::V4(v4)),
+            None => ip.is_unique_local() || ip.is_unicast_link_local(),
+        },
+    }
+}
@@ -1,2 +1,14 @@
+pub mod client_id;
+pub mod codec;
+pub(crate) mod connection;
+pub mod connector;
 pub mod error;
+pub mod extension;
+pub mod framing;
+pub mod lan;
+pub mod mse;
+pub mod pex;
+pub mod reserved;
+pub mod socks;
+pub mod transport;
 pub mod value;
@@ -0,0 +1,29 @@
+use sha1::{Digest, Sha1};
+
+// Message Stream Encryption never sends the info hash in the clear. The initiator sends
+// HASH('req2', SKEY) xor HASH('req3', S), where SKEY is the info hash and S the
+// Diffie-Hellman shared secret, and the receiver has to find the torrent it refers to.
+
+pub fn req2_hash(info_hash: &[u8; 20]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(b"req2");
+    hasher.update(info_hash);
+    hasher.finalize().into()
+}
+
+pub fn req3_hash(shared_secret: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(b"req3");
+    hasher.update(shared_secret);
+    hasher.finalize().into()
+}
+
+// Recovers HASH('req2', SKEY) from the value received on the wire, ready for lookup.
+pub fn unmask_req2(received: &[u8; 20], shared_secret: &[u8]) -> [u8; 20] {
+    let mask = req3_hash(shared_secret);
+    let mut unmasked = [0u8; 20];
+    for (out, (a, b)) in unmasked.iter_mut().zip(received.iter().zip(mask.iter())) {
+        *out = a ^ b;
+    }
+    unmasked
+}
@@ -0,0 +1,122 @@
+use crate::bencode::errors::BencodeError;
+use crate::bencode::value::BencodeValue;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+// One byte per peer in the "added.f" list of a ut_pex message (BEP 11).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PexFlags(pub u8);
+
+impl PexFlags {
+    pub const PREFERS_ENCRYPTION: PexFlags = PexFlags(0x01);
+    // The peer is a seed or partial seed and will only upload.
+    pub const SEED: PexFlags = PexFlags(0x02);
+    pub const SUPPORTS_UTP: PexFlags = PexFlags(0x04);
+    pub const SUPPORTS_HOLEPUNCH: PexFlags = PexFlags(0x08);
+    // The sender connected to this peer itself, so it is known to accept connections.
+    pub const REACHABLE: PexFlags = PexFlags(0x10);
+
+    pub fn contains(&self, other: PexFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: PexFlags) {
+        self.0 |= other.0;
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PexPeer {
+    pub addr: SocketAddrV4,
+    pub flags: PexFlags,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PexMessage {
+    pub added: Vec<PexPeer>,
+    pub dropped: Vec<SocketAddrV4>,
+}
+
+impl PexMessage {
+    // "added.f" may be missing or shorter than "added"; those peers simply get no flags.
+    pub fn from_bencode(value: &BencodeValue) -> Result<PexMessage, BencodeError> {
+        let dict = value.as_dict()?;
+        let added = compact_field(dict, "added")?;
+        let flags = compact_field(dict, "added.f")?;
+
+        let added = decode_compact(added)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, addr)| PexPeer {
+                addr,
+                flags: PexFlags(flags.get(i).copied().unwrap_or(0)),
+            })
+            .collect();
+        let dropped = decode_compact(compact_field(dict, "dropped")?)?;
+
+        Ok(PexMessage { added, dropped })
+    }
+
+    pub fn to_bencode(&self) -> BencodeValue {
+        let mut added = Vec::with_capacity(self.added.len() * 6);
+        let mut flags = Vec::with_capacity(self.added.len());
+        for peer in &self.added {
+            added.extend_from_slice(&encode_compact(&peer.addr));
+            flags.push(peer.flags.bits());
+        }
+        let dropped = self.dropped.iter().flat_map(encode_compact).collect();
+
+        let mut dict = HashMap::new();
+        dict.insert("added".to_string(), BencodeValue::Bytes(added));
+        dict.insert("added.f".to_string(), BencodeValue::Bytes(flags));
+        dict.insert("dropped".to_string(), BencodeValue::Bytes(dropped));
+        BencodeValue::Dictionary(dict)
+    }
+}
+
+// The parser hands back byte strings that happen to be valid UTF-8 as String values.
+fn compact_field<'a>(
+    dict: &'a HashMap<String, BencodeValue>,
+    key: &str,
+) -> Result<&'a [u8], BencodeError> {
+    match dict.get(key) {
+        None => Ok(&[]),
+        Some(BencodeValue::Bytes(b)) => Ok(b),
+        Some(BencodeValue::String(s)) => Ok(s.as_bytes()),
+        Some(other) => Err(BencodeError::WrongType {
+            expected: "Bytes".to_string(),
+            found: other.type_name().to_string(),
+        }),
+    }
+}
+
+fn decode_compact(bytes: &[u8]) -> Result<Vec<SocketAddrV4>, BencodeError> {
+    if !bytes.len().is_multiple_of(6) {
+        return Err(BencodeError::InvalidString(
+            format!(
+                "Compact peer list length {} is not a multiple of 6",
+                bytes.len()
+            ),
+            None,
+        ));
+    }
+
+    Ok(bytes
+        .chunks_exact(6)
+        .map(|chunk| {
+            let ip = Ipv4Addr::new(chunk[0], chunk[1], chunk[2], chunk[3]);
+            SocketAddrV4::new(ip, u16::from_be_bytes([chunk[4], chunk[5]]))
+        })
+        .collect())
+}
+
+fn encode_compact(addr: &SocketAddrV4) -> [u8; 6] {
+    let mut bytes = [0u8; 6];
+    bytes[..4].copy_from_slice(&addr.ip().octets());
+    bytes[4..].copy_from_slice(&addr.port().to_be_bytes());
+    bytes
+}
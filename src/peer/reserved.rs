@@ -0,0 +1,127 @@
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign};
+
+// The eight reserved handshake bytes, read as a set of capabilities. Each side sets the
+// bits for what it supports; an extension is on for a connection only when both did.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ReservedBits([u8; 8]);
+
+impl ReservedBits {
+    // BEP 10, the extended handshake and everything sent through it.
+    pub const EXTENSION_PROTOCOL: ReservedBits = ReservedBits::bit(5, 0x10);
+    // BEP 6: HaveAll, HaveNone, rejects, suggestions and allowed-fast pieces.
+    pub const FAST: ReservedBits = ReservedBits::bit(7, 0x04);
+    // BEP 5: the peer runs a DHT node and takes Port messages.
+    pub const DHT: ReservedBits = ReservedBits::bit(7, 0x01);
+    // BEP 52: the peer takes part in the v2 hash exchange.
+    pub const V2: ReservedBits = ReservedBits::bit(7, 0x10);
+
+    const NAMED: [(ReservedBits, &'static str); 4] = [
+        (ReservedBits::EXTENSION_PROTOCOL, "extension protocol"),
+        (ReservedBits::FAST, "fast"),
+        (ReservedBits::DHT, "dht"),
+        (ReservedBits::V2, "v2"),
+    ];
+
+    const fn bit(byte: usize, mask: u8) -> ReservedBits {
+        let mut bytes = [0u8; 8];
+        bytes[byte] = mask;
+        ReservedBits(bytes)
+    }
+
+    pub const fn empty() -> ReservedBits {
+        ReservedBits([0; 8])
+    }
+
+    pub const fn from_bytes(bytes: [u8; 8]) -> ReservedBits {
+        ReservedBits(bytes)
+    }
+
+    pub const fn to_bytes(self) -> [u8; 8] {
+        self.0
+    }
+
+    // True if every bit of `other` is set here.
+    pub fn contains(self, other: ReservedBits) -> bool {
+        self & other == other
+    }
+
+    pub fn insert(&mut self, other: ReservedBits) {
+        *self |= other;
+    }
+
+    pub fn remove(&mut self, other: ReservedBits) {
+        for (byte, mask) in self.0.iter_mut().zip(other.0) {
+            *byte &= !mask;
+        }
+    }
+
+    pub fn supports_extension_protocol(self) -> bool {
+        self.contains(ReservedBits::EXTENSION_PROTOCOL)
+    }
+
+    pub fn supports_fast(self) -> bool {
+        self.contains(ReservedBits::FAST)
+    }
+
+    pub fn supports_dht(self) -> bool {
+        self.contains(ReservedBits::DHT)
+    }
+
+    pub fn supports_v2(self) -> bool {
+        self.contains(ReservedBits::V2)
+    }
+
+    // Set bits this crate has no name for, such as other clients' private extensions.
+    pub fn unknown(self) -> ReservedBits {
+        let mut rest = self;
+        for (bits, _) in ReservedBits::NAMED {
+            rest.remove(bits);
+        }
+        rest
+    }
+}
+
+impl BitOr for ReservedBits {
+    type Output = ReservedBits;
+
+    fn bitor(mut self, other: ReservedBits) -> ReservedBits {
+        self |= other;
+        self
+    }
+}
+
+impl BitOrAssign for ReservedBits {
+    fn bitor_assign(&mut self, other: ReservedBits) {
+        for (byte, mask) in self.0.iter_mut().zip(other.0) {
+            *byte |= mask;
+        }
+    }
+}
+
+impl BitAnd for ReservedBits {
+    type Output = ReservedBits;
+
+    fn bitand(mut self, other: ReservedBits) -> ReservedBits {
+        for (byte, mask) in self.0.iter_mut().zip(other.0) {
+            *byte &= mask;
+        }
+        self
+    }
+}
+
+// Named flags, then any unknown bits as hex, e.g. ReservedBits(fast | v2 | 0000000000100000).
+impl fmt::Debug for ReservedBits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts: Vec<String> = ReservedBits::NAMED
+            .iter()
+            .filter(|(bits, _)| self.contains(*bits))
+            .map(|(_, name)| name.to_string())
+            .collect();
+        let unknown = self.unknown();
+        if unknown != ReservedBits::empty() {
+            parts.push(unknown.0.iter().map(|b| format!("{:02x}", b)).collect());
+        }
+        write!(f, "ReservedBits({})", parts.join(" | "))
+    }
+}
@@ -0,0 +1,203 @@
+use super::error::SocksError;
+use crate::tracker::value::TrackerRequest;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0x00;
+const USERNAME_PASSWORD: u8 = 0x02;
+const CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+// A SOCKS5 proxy (RFC 1928) that outgoing connections are made through, with optional
+// username/password authentication (RFC 1929).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socks5Proxy {
+    // host:port of the proxy itself, resolved locally.
+    pub addr: String,
+    pub credentials: Option<ProxyCredentials>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct ProxyCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+// Keeps the password out of logs and debug dumps.
+impl fmt::Debug for ProxyCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProxyCredentials")
+            .field("username", &self.username)
+            .field("password", &"<hidden>")
+            .finish()
+    }
+}
+
+// Where the proxy should connect to. Hostnames are resolved by the proxy.
+enum Destination<'a> {
+    Addr(SocketAddr),
+    Host(&'a str, u16),
+}
+
+impl Socks5Proxy {
+    pub fn new(addr: impl Into<String>) -> Socks5Proxy {
+        Socks5Proxy {
+            addr: addr.into(),
+            credentials: None,
+        }
+    }
+
+    pub fn with_credentials(mut self, username: &str, password: &str) -> Socks5Proxy {
+        self.credentials = Some(ProxyCredentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        });
+        self
+    }
+
+    // A socks5h:// URL for HTTP clients; the h has the proxy resolve hostnames too.
+    pub fn url(&self) -> String {
+        match &self.credentials {
+            Some(credentials) => format!(
+                "socks5h://{}:{}@{}",
+                TrackerRequest::url_encode_bytes(credentials.username.as_bytes()),
+                TrackerRequest::url_encode_bytes(credentials.password.as_bytes()),
+                self.addr
+            ),
+            None => format!("socks5h://{}", self.addr),
+        }
+    }
+
+    // Opens a connection to `target` through the proxy. `timeout` applies to reaching the
+    // proxy and to each read and write while negotiating; the stream is handed back
+    // without read or write timeouts.
+    pub fn connect(&self, target: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+        self.open(Destination::Addr(target), timeout)
+    }
+
+    pub fn connect_host(&self, host: &str, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+        self.open(Destination::Host(host, port), timeout)
+    }
+
+    fn open(&self, destination: Destination, timeout: Duration) -> io::Result<TcpStream> {
+        let mut last_err = io::Error::other("Proxy address didn't resolve");
+        for addr in self.addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(mut stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    self.negotiate(&mut stream, &destination)?;
+                    stream.set_read_timeout(None)?;
+                    stream.set_write_timeout(None)?;
+                    return Ok(stream);
+                }
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+
+    fn negotiate(&self, stream: &mut TcpStream, destination: &Destination) -> io::Result<()> {
+        let method = if self.credentials.is_some() {
+            USERNAME_PASSWORD
+        } else {
+            NO_AUTH
+        };
+        stream.write_all(&[VERSION, 1, method])?;
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply)?;
+        if reply[0] != VERSION {
+            return Err(SocksError::UnexpectedVersion(reply[0]).into());
+        }
+        if reply[1] != method {
+            return Err(SocksError::NoAcceptableMethod.into());
+        }
+        if let Some(credentials) = &self.credentials {
+            authenticate(stream, credentials)?;
+        }
+
+        let mut request = vec![VERSION, CONNECT, 0];
+        match destination {
+            Destination::Addr(addr) => {
+                match addr.ip() {
+                    IpAddr::V4(ip) => {
+                        request.push(ATYP_IPV4);
+                        request.extend_from_slice(&ip.octets());
+                    }
+                    IpAddr::V6(ip) => {
+                        request.push(ATYP_IPV6);
+                        request.extend_from_slice(&ip.octets());
+                    }
+                }
+                request.extend_from_slice(&addr.port().to_be_bytes());
+            }
+            Destination::Host(host, port) => {
+                let length = u8::try_from(host.len()).map_err(|_| SocksError::HostnameTooLong)?;
+                request.extend_from_slice(&[ATYP_DOMAIN, length]);
+                request.extend_from_slice(host.as_bytes());
+                request.extend_from_slice(&port.to_be_bytes());
+            }
+        }
+        stream.write_all(&request)?;
+
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply)?;
+        if reply[0] != VERSION {
+            return Err(SocksError::UnexpectedVersion(reply[0]).into());
+        }
+        if reply[1] != 0 {
+            return Err(SocksError::ConnectFailed(reply[1]).into());
+        }
+        // The address the proxy bound for us isn't of any use, but has to be read past.
+        let bound = match reply[3] {
+            ATYP_IPV4 => 4,
+            ATYP_IPV6 => 16,
+            ATYP_DOMAIN => {
+                let mut length = [0u8; 1];
+                stream.read_exact(&mut length)?;
+                length[0] as usize
+            }
+            other => return Err(SocksError::UnknownAddressType(other).into()),
+        };
+        let mut skipped = vec![0u8; bound + 2];
+        stream.read_exact(&mut skipped)?;
+        Ok(())
+    }
+}
+
+fn authenticate(stream: &mut TcpStream, credentials: &ProxyCredentials) -> io::Result<()> {
+    let username = credentials.username.as_bytes();
+    let password = credentials.password.as_bytes();
+    if username.len() > 255 || password.len() > 255 {
+        return Err(SocksError::CredentialsTooLong.into());
+    }
+    let mut request = vec![1, username.len() as u8];
+    request.extend_from_slice(username);
+    request.push(password.len() as u8);
+    request.extend_from_slice(password);
+    stream.write_all(&request)?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply)?;
+    if reply[1] != 0 {
+        return Err(SocksError::AuthenticationFailed.into());
+    }
+    Ok(())
+}
+
+// Connects directly, or through `proxy` when there is one.
+pub fn connect(
+    proxy: Option<&Socks5Proxy>,
+    addr: SocketAddr,
+    timeout: Duration,
+) -> io::Result<TcpStream> {
+    match proxy {
+        Some(proxy) => proxy.connect(addr, timeout),
+        None => TcpStream::connect_timeout(&addr, timeout),
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt::Debug;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+// The byte stream a peer connection runs over. TCP is what the session dials and
+// accepts; anything else, such as an in-memory pipe for simulations, can be handed to
+// Session::add_peer_stream. Timeouts behave as on TcpStream: a read that waits too long
+// fails with WouldBlock or TimedOut.
+pub trait PeerStream: Read + Write + Send + Debug {
+    fn read_timeout(&self) -> io::Result<Option<Duration>>;
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    fn write_timeout(&self) -> io::Result<Option<Duration>>;
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    // Waits for data like a read, but leaves it in the stream.
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+impl PeerStream for TcpStream {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        TcpStream::read_timeout(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        TcpStream::write_timeout(self)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_write_timeout(self, timeout)
+    }
+
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        TcpStream::peek(self, buf)
+    }
+}
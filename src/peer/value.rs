@@ -1,28 +1,89 @@
-use super::error::{HandshakeError, PeerHandshakeError};
-use std::io::{Read, Write};
-use std::net::TcpStream;
+use super::error::{HandshakeError, PeerHandshakeError, PeerMessageError};
+use super::extension::EXTENDED_MESSAGE_ID;
+use super::reserved::ReservedBits;
+use super::transport::PeerStream;
+use bytes::Bytes;
+use std::io::{IoSlice, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PeerTimeouts {
+    pub connect: Duration,
+    // Peers send a keep-alive at least every two minutes, so reads can wait that long.
+    pub read: Duration,
+    pub write: Duration,
+    pub handshake: Duration,
+}
+
+impl Default for PeerTimeouts {
+    fn default() -> Self {
+        PeerTimeouts {
+            connect: Duration::from_secs(10),
+            read: Duration::from_secs(130),
+            write: Duration::from_secs(30),
+            handshake: Duration::from_secs(20),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Handshake {
     // - Length byte (1 byte): Always 19 (the length of the protocol string)
     // - Protocol string (19 bytes): Always "BitTorrent protocol"
-    // - Reserved bytes (8 bytes): All zeros, reserved for future extensions
+    // - Reserved bytes (8 bytes): Extension bits, see ReservedBits
     // - Info hash (20 bytes): The SHA1 hash of the torrent's info section
     // - Peer ID (20 bytes): A unique identifier for your client
     pub length: u8,
     pub protocol: [u8; 19],
-    pub reserved: [u8; 8],
+    pub reserved: ReservedBits,
     pub info_hash: [u8; 20],
     pub peer_id: [u8; 20],
 }
 
+// Longest message we accept. The length prefix comes from the peer, so it's checked
+// before anything is allocated; 1 MiB leaves room for the bitfield of 8M pieces.
+pub const MAX_MESSAGE_LENGTH: u32 = 1 << 20;
+// A Piece frame carrying a 16 KiB block.
+const FRAME_PREALLOCATION: u32 = 9 + 16 * 1024;
+// Longest block we serve to a Request. Clients ask for 16 KiB; some go up to 128 KiB.
+pub const MAX_REQUEST_LENGTH: u32 = 128 * 1024;
+
 impl Handshake {
+    pub fn new(info_hash: [u8; 20], peer_id: [u8; 20], reserved: ReservedBits) -> Handshake {
+        Handshake {
+            length: 19,
+            protocol: *b"BitTorrent protocol",
+            reserved,
+            info_hash,
+            peer_id,
+        }
+    }
+
+    // Both sides advertise it, so for a remote handshake this says whether the fast
+    // extension is on for the connection.
+    pub fn supports_fast(&self) -> bool {
+        self.reserved.supports_fast()
+    }
+
+    pub fn supports_dht(&self) -> bool {
+        self.reserved.supports_dht()
+    }
+
+    pub fn supports_v2(&self) -> bool {
+        self.reserved.supports_v2()
+    }
+
+    pub fn supports_extension_protocol(&self) -> bool {
+        self.reserved.supports_extension_protocol()
+    }
+
     pub fn to_bytes(&self) -> [u8; 68] {
         let mut bytes: [u8; 68] = [0; 68];
 
         bytes[0] = self.length;
         bytes[1..20].copy_from_slice(&self.protocol);
-        bytes[20..28].copy_from_slice(&self.reserved);
+        bytes[20..28].copy_from_slice(&self.reserved.to_bytes());
         bytes[28..48].copy_from_slice(&self.info_hash);
         bytes[48..68].copy_from_slice(&self.peer_id);
 
@@ -46,8 +107,7 @@ impl Handshake {
             return Err(HandshakeError::InvalidProtocolString);
         }
 
-        let mut reserved = [0u8; 8];
-        reserved.copy_from_slice(&bytes[20..28]);
+        let reserved = ReservedBits::from_bytes(bytes[20..28].try_into().unwrap());
 
         let mut info_hash = [0u8; 20];
         info_hash.copy_from_slice(&bytes[28..48]);
@@ -72,27 +132,47 @@ impl Handshake {
         })
     }
 
-    fn connect_to_peer(
+    pub fn connect_to_peer(
         peer: &crate::tracker::value::Peer,
+        timeouts: &PeerTimeouts,
     ) -> Result<TcpStream, PeerHandshakeError> {
-        let addr = format!("{}:{}", peer.ip, peer.port);
-        let stream = TcpStream::connect(addr)?;
+        let addr = SocketAddr::from((peer.ip, peer.port));
+        let stream = TcpStream::connect_timeout(&addr, timeouts.connect)?;
+        stream.set_read_timeout(Some(timeouts.read))?;
+        stream.set_write_timeout(Some(timeouts.write))?;
 
         Ok(stream)
     }
 
     pub fn perform_handshake(
-        stream: &mut TcpStream,
+        stream: &mut (impl PeerStream + ?Sized),
         info_hash: &[u8; 20],
         own_peer_id: &[u8; 20],
+        reserved: ReservedBits,
+        timeout: Duration,
     ) -> Result<Handshake, PeerHandshakeError> {
-        let request_handshake = Handshake {
-            length: 19,
-            protocol: *b"BitTorrent protocol",
-            reserved: [0u8; 8],
-            info_hash: *info_hash,
-            peer_id: *own_peer_id,
-        };
+        // The handshake gets its own, usually shorter, deadline. The stream's regular
+        // timeouts are restored afterwards if possible; the handshake's outcome matters
+        // more than a failure to do so.
+        let read_timeout = stream.read_timeout()?;
+        let write_timeout = stream.write_timeout()?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let result = Self::exchange_handshake(stream, info_hash, own_peer_id, reserved);
+
+        let _ = stream.set_read_timeout(read_timeout);
+        let _ = stream.set_write_timeout(write_timeout);
+        result
+    }
+
+    fn exchange_handshake(
+        stream: &mut (impl PeerStream + ?Sized),
+        info_hash: &[u8; 20],
+        own_peer_id: &[u8; 20],
+        reserved: ReservedBits,
+    ) -> Result<Handshake, PeerHandshakeError> {
+        let request_handshake = Handshake::new(*info_hash, *own_peer_id, reserved);
 
         stream.write_all(&request_handshake.to_bytes())?;
 
@@ -103,9 +183,47 @@ impl Handshake {
 
         Ok(response_handshake)
     }
-}
 
+    // Incoming side of the handshake: the remote speaks first, and we only answer if
+    // `own_peer_id` gives us a peer id for the info hash it asked for. Timeouts are handled
+    // as in perform_handshake.
+    pub fn accept_handshake(
+        stream: &mut (impl PeerStream + ?Sized),
+        own_peer_id: impl Fn(&[u8; 20]) -> Option<[u8; 20]>,
+        reserved: ReservedBits,
+        timeout: Duration,
+    ) -> Result<Handshake, PeerHandshakeError> {
+        let read_timeout = stream.read_timeout()?;
+        let write_timeout = stream.write_timeout()?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let result = Self::answer_handshake(stream, own_peer_id, reserved);
+
+        let _ = stream.set_read_timeout(read_timeout);
+        let _ = stream.set_write_timeout(write_timeout);
+        result
+    }
+
+    fn answer_handshake(
+        stream: &mut (impl PeerStream + ?Sized),
+        own_peer_id: impl Fn(&[u8; 20]) -> Option<[u8; 20]>,
+        reserved: ReservedBits,
+    ) -> Result<Handshake, PeerHandshakeError> {
+        let mut request_buf = [0u8; 68];
+        stream.read_exact(&mut request_buf)?;
+
+        let mut info_hash = [0u8; 20];
+        info_hash.copy_from_slice(&request_buf[28..48]);
+        let Some(own_peer_id) = own_peer_id(&info_hash) else {
+            return Err(HandshakeError::InfoHashMismatch.into());
+        };
 
+        let remote = Handshake::from_bytes(&request_buf, &info_hash, &own_peer_id)?;
+        stream.write_all(&Handshake::new(info_hash, own_peer_id, reserved).to_bytes())?;
+        Ok(remote)
+    }
+}
 
 // This data is recieved in stages , first the length, then message_id and payload
 //  1. Length Prefix (4 bytes): This is a 4-byte number (u32) that tells you the length of the
@@ -114,6 +232,7 @@ impl Handshake {
 //     is a bitfield message.
 //  3. Payload (variable size): The actual data for the message. This can be empty. Its size is
 //     Length - 1
+#[derive(Debug, Clone, PartialEq)]
 pub enum PeerMessage {
     KeepAlive,
     Choke,
@@ -123,7 +242,7 @@ pub enum PeerMessage {
     Have {
         piece_index: u32,
     },
-    Bitfield(Vec<u8>),
+    Bitfield(Bytes),
     Request {
         index: u32,
         begin: u32,
@@ -132,63 +251,408 @@ pub enum PeerMessage {
     Piece {
         index: u32,
         begin: u32,
-        block: Vec<u8>,
+        block: Bytes,
     },
     Cancel {
         index: u32,
         begin: u32,
         length: u32,
     },
+    // UDP port of the sender's DHT node (BEP 5).
+    Port {
+        listen_port: u16,
+    },
+    // Fast extension (BEP 6) messages.
+    SuggestPiece {
+        piece_index: u32,
+    },
+    HaveAll,
+    HaveNone,
+    RejectRequest {
+        index: u32,
+        begin: u32,
+        length: u32,
+    },
+    // A piece the peer will serve us even while choking us.
+    AllowedFast {
+        piece_index: u32,
+    },
+    // BEP 52 hash exchange for v2 files. `base_layer` counts up from the 16 KiB leaves and
+    // `index` and `length` pick a run of hashes in it; `proof_layers` asks for the uncle
+    // hashes that check the run against the file's pieces root.
+    HashRequest {
+        pieces_root: [u8; 32],
+        base_layer: u32,
+        index: u32,
+        length: u32,
+        proof_layers: u32,
+    },
+    // The run asked for, followed by its uncle hashes from the bottom up.
+    Hashes {
+        pieces_root: [u8; 32],
+        base_layer: u32,
+        index: u32,
+        length: u32,
+        proof_layers: u32,
+        hashes: Vec<[u8; 32]>,
+    },
+    HashReject {
+        pieces_root: [u8; 32],
+        base_layer: u32,
+        index: u32,
+        length: u32,
+        proof_layers: u32,
+    },
+    // Extension protocol (BEP 10) message. `id` is the extended message id, 0 being the
+    // extended handshake, and `payload` whatever follows it.
+    Extended {
+        id: u8,
+        payload: Bytes,
+    },
     Unknown {
         id: u8,
-        payload: Vec<u8>,
+        payload: Bytes,
     },
 }
 
-pub enum PeerMessageError {
-    IOError(std::io::Error),
+fn read_u32(payload: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(payload[at..at + 4].try_into().unwrap())
 }
 
-impl From<std::io::Error> for PeerMessageError{
-    fn from(value: std::io::Error) -> Self {
-        PeerMessageError::IOError(value)
-    }
+// Pieces root, base layer, index, length and proof layers, shared by the hash messages.
+const HASH_HEADER_LENGTH: usize = 48;
+
+type HashHeader = ([u8; 32], u32, u32, u32, u32);
+
+fn read_hash_header(payload: &[u8]) -> HashHeader {
+    (
+        payload[..32].try_into().unwrap(),
+        read_u32(payload, 32),
+        read_u32(payload, 36),
+        read_u32(payload, 40),
+        read_u32(payload, 44),
+    )
 }
 
 impl PeerMessage {
-    pub fn read_peer_message(stream: &mut TcpStream) -> Result<PeerMessage, PeerMessageError> {
-        let mut len_bytes = [0u8;4];
+    pub fn read_peer_message(stream: &mut impl Read) -> Result<PeerMessage, PeerMessageError> {
+        let mut len_bytes = [0u8; 4];
         stream.read_exact(&mut len_bytes)?;
 
         let message_len = u32::from_be_bytes(len_bytes);
 
-        if message_len == 0{
+        if message_len == 0 {
             return Ok(PeerMessage::KeepAlive);
         }
+        if message_len > MAX_MESSAGE_LENGTH {
+            return Err(PeerMessageError::MessageTooLong(message_len));
+        }
+
+        // Room for a whole block frame up front; anything longer grows with what actually
+        // arrives rather than with what the prefix claims.
+        let mut frame = Vec::with_capacity(message_len.min(FRAME_PREALLOCATION) as usize);
+        stream.take(message_len as u64).read_to_end(&mut frame)?;
+        if frame.len() < message_len as usize {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+
+        // Payloads such as Piece blocks are slices of the frame, not copies.
+        let mut frame = Bytes::from(frame);
+        let message_id = frame[0];
+        PeerMessage::parse_frame(message_id, frame.split_off(1))
+    }
 
-        let mut payload_buffer = vec![0u8;message_len as usize];
-        stream.read_exact(&mut payload_buffer)?;
+    // Builds a message from its id and payload, i.e. a frame without the length prefix.
+    pub fn from_frame(message_id: u8, payload: &[u8]) -> Result<PeerMessage, PeerMessageError> {
+        PeerMessage::parse_frame(message_id, Bytes::copy_from_slice(payload))
+    }
+
+    pub(crate) fn parse_frame(
+        message_id: u8,
+        payload: Bytes,
+    ) -> Result<PeerMessage, PeerMessageError> {
+        let expect_len = |len: usize| {
+            if payload.len() == len {
+                Ok(())
+            } else {
+                Err(PeerMessageError::InvalidPayloadLength {
+                    id: message_id,
+                    length: payload.len(),
+                })
+            }
+        };
 
-        let message_id = payload_buffer[0];
-        let payload = &payload_buffer[1..];
+        match message_id {
+            0 => expect_len(0).map(|_| PeerMessage::Choke),
+            1 => expect_len(0).map(|_| PeerMessage::Unchoke),
+            2 => expect_len(0).map(|_| PeerMessage::Interested),
+            3 => expect_len(0).map(|_| PeerMessage::NotInterested),
+            4 => expect_len(4).map(|_| PeerMessage::Have {
+                piece_index: read_u32(&payload, 0),
+            }),
+            5 => Ok(PeerMessage::Bitfield(payload)),
+            6 => expect_len(12).map(|_| PeerMessage::Request {
+                index: read_u32(&payload, 0),
+                begin: read_u32(&payload, 4),
+                length: read_u32(&payload, 8),
+            }),
+            7 => {
+                if payload.len() < 8 {
+                    return Err(PeerMessageError::InvalidPayloadLength {
+                        id: message_id,
+                        length: payload.len(),
+                    });
+                }
+                Ok(PeerMessage::Piece {
+                    index: read_u32(&payload, 0),
+                    begin: read_u32(&payload, 4),
+                    block: payload.slice(8..),
+                })
+            }
+            8 => expect_len(12).map(|_| PeerMessage::Cancel {
+                index: read_u32(&payload, 0),
+                begin: read_u32(&payload, 4),
+                length: read_u32(&payload, 8),
+            }),
+            9 => expect_len(2).map(|_| PeerMessage::Port {
+                listen_port: u16::from_be_bytes([payload[0], payload[1]]),
+            }),
+            13 => expect_len(4).map(|_| PeerMessage::SuggestPiece {
+                piece_index: read_u32(&payload, 0),
+            }),
+            14 => expect_len(0).map(|_| PeerMessage::HaveAll),
+            15 => expect_len(0).map(|_| PeerMessage::HaveNone),
+            16 => expect_len(12).map(|_| PeerMessage::RejectRequest {
+                index: read_u32(&payload, 0),
+                begin: read_u32(&payload, 4),
+                length: read_u32(&payload, 8),
+            }),
+            17 => expect_len(4).map(|_| PeerMessage::AllowedFast {
+                piece_index: read_u32(&payload, 0),
+            }),
+            EXTENDED_MESSAGE_ID => match payload.first() {
+                Some(&id) => Ok(PeerMessage::Extended {
+                    id,
+                    payload: payload.slice(1..),
+                }),
+                None => Err(PeerMessageError::InvalidPayloadLength {
+                    id: message_id,
+                    length: 0,
+                }),
+            },
+            21 => expect_len(HASH_HEADER_LENGTH).map(|_| {
+                let (pieces_root, base_layer, index, length, proof_layers) =
+                    read_hash_header(&payload);
+                PeerMessage::HashRequest {
+                    pieces_root,
+                    base_layer,
+                    index,
+                    length,
+                    proof_layers,
+                }
+            }),
+            22 => {
+                if payload.len() < HASH_HEADER_LENGTH
+                    || !(payload.len() - HASH_HEADER_LENGTH).is_multiple_of(32)
+                {
+                    return Err(PeerMessageError::InvalidPayloadLength {
+                        id: message_id,
+                        length: payload.len(),
+                    });
+                }
+                let (pieces_root, base_layer, index, length, proof_layers) =
+                    read_hash_header(&payload);
+                Ok(PeerMessage::Hashes {
+                    pieces_root,
+                    base_layer,
+                    index,
+                    length,
+                    proof_layers,
+                    hashes: payload[HASH_HEADER_LENGTH..]
+                        .chunks(32)
+                        .map(|hash| hash.try_into().unwrap())
+                        .collect(),
+                })
+            }
+            23 => expect_len(HASH_HEADER_LENGTH).map(|_| {
+                let (pieces_root, base_layer, index, length, proof_layers) =
+                    read_hash_header(&payload);
+                PeerMessage::HashReject {
+                    pieces_root,
+                    base_layer,
+                    index,
+                    length,
+                    proof_layers,
+                }
+            }),
+            _ => Ok(PeerMessage::Unknown {
+                id: message_id,
+                payload,
+            }),
+        }
+    }
 
+    pub fn id(&self) -> Option<u8> {
+        match self {
+            PeerMessage::KeepAlive => None,
+            PeerMessage::Choke => Some(0),
+            PeerMessage::Unchoke => Some(1),
+            PeerMessage::Interested => Some(2),
+            PeerMessage::NotInterested => Some(3),
+            PeerMessage::Have { .. } => Some(4),
+            PeerMessage::Bitfield(_) => Some(5),
+            PeerMessage::Request { .. } => Some(6),
+            PeerMessage::Piece { .. } => Some(7),
+            PeerMessage::Cancel { .. } => Some(8),
+            PeerMessage::Port { .. } => Some(9),
+            PeerMessage::SuggestPiece { .. } => Some(13),
+            PeerMessage::HaveAll => Some(14),
+            PeerMessage::HaveNone => Some(15),
+            PeerMessage::RejectRequest { .. } => Some(16),
+            PeerMessage::AllowedFast { .. } => Some(17),
+            PeerMessage::Extended { .. } => Some(EXTENDED_MESSAGE_ID),
+            PeerMessage::HashRequest { .. } => Some(21),
+            PeerMessage::Hashes { .. } => Some(22),
+            PeerMessage::HashReject { .. } => Some(23),
+            PeerMessage::Unknown { id, .. } => Some(*id),
+        }
+    }
 
-        match message_id{
-            0 => {
-                return Ok(PeerMessage::Choke)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match self {
+            PeerMessage::KeepAlive
+            | PeerMessage::Choke
+            | PeerMessage::Unchoke
+            | PeerMessage::Interested
+            | PeerMessage::NotInterested
+            | PeerMessage::HaveAll
+            | PeerMessage::HaveNone => {}
+            PeerMessage::Have { piece_index }
+            | PeerMessage::SuggestPiece { piece_index }
+            | PeerMessage::AllowedFast { piece_index } => {
+                payload.extend_from_slice(&piece_index.to_be_bytes())
+            }
+            PeerMessage::Bitfield(bits) => payload.extend_from_slice(bits),
+            PeerMessage::Port { listen_port } => {
+                payload.extend_from_slice(&listen_port.to_be_bytes())
+            }
+            PeerMessage::Request {
+                index,
+                begin,
+                length,
+            }
+            | PeerMessage::Cancel {
+                index,
+                begin,
+                length,
+            }
+            | PeerMessage::RejectRequest {
+                index,
+                begin,
+                length,
+            } => {
+                payload.extend_from_slice(&index.to_be_bytes());
+                payload.extend_from_slice(&begin.to_be_bytes());
+                payload.extend_from_slice(&length.to_be_bytes());
+            }
+            PeerMessage::Piece {
+                index,
+                begin,
+                block,
+            } => {
+                payload.extend_from_slice(&index.to_be_bytes());
+                payload.extend_from_slice(&begin.to_be_bytes());
+                payload.extend_from_slice(block);
             }
-            2 => {
-                return Ok(PeerMessage::Interested)
+            PeerMessage::HashRequest {
+                pieces_root,
+                base_layer,
+                index,
+                length,
+                proof_layers,
             }
-            5 => {
-                return Ok(PeerMessage::Bitfield(payload.to_vec()))
+            | PeerMessage::HashReject {
+                pieces_root,
+                base_layer,
+                index,
+                length,
+                proof_layers,
+            } => {
+                payload.extend_from_slice(pieces_root);
+                for field in [base_layer, index, length, proof_layers] {
+                    payload.extend_from_slice(&field.to_be_bytes());
+                }
             }
-            // 7 => {
-            //     return Ok(PeerMessage::Piece { index: (), begin: (), block: () })
-            // }
-            _ => {
-                return Ok(PeerMessage::Unknown { id: message_id, payload: payload.to_vec()})
+            PeerMessage::Hashes {
+                pieces_root,
+                base_layer,
+                index,
+                length,
+                proof_layers,
+                hashes,
+            } => {
+                payload.extend_from_slice(pieces_root);
+                for field in [base_layer, index, length, proof_layers] {
+                    payload.extend_from_slice(&field.to_be_bytes());
+                }
+                payload.extend(hashes.iter().flatten());
             }
+            PeerMessage::Extended { id, payload: p } => {
+                payload.push(*id);
+                payload.extend_from_slice(p);
+            }
+            PeerMessage::Unknown { payload: p, .. } => payload.extend_from_slice(p),
+        }
+
+        let Some(id) = self.id() else {
+            return vec![0u8; 4];
+        };
+
+        let mut bytes = Vec::with_capacity(5 + payload.len());
+        bytes.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+        bytes.push(id);
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+
+    pub fn write_peer_message(&self, stream: &mut impl Write) -> Result<(), PeerMessageError> {
+        if let PeerMessage::Piece {
+            index,
+            begin,
+            block,
+        } = self
+        {
+            return write_piece(stream, *index, *begin, block);
+        }
+        stream.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+}
+
+// Sends a Piece message straight from `block`: the header and the block go out in one
+// vectored write instead of being copied into a message buffer first.
+pub fn write_piece(
+    stream: &mut impl Write,
+    index: u32,
+    begin: u32,
+    block: &[u8],
+) -> Result<(), PeerMessageError> {
+    let mut header = [0u8; 13];
+    header[..4].copy_from_slice(&(block.len() as u32 + 9).to_be_bytes());
+    header[4] = 7;
+    header[5..9].copy_from_slice(&index.to_be_bytes());
+    header[9..].copy_from_slice(&begin.to_be_bytes());
+
+    let mut slices = [IoSlice::new(&header), IoSlice::new(block)];
+    let mut slices = &mut slices[..];
+    while !slices.is_empty() {
+        match stream.write_vectored(slices) {
+            Ok(0) => return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into()),
+            Ok(written) => IoSlice::advance_slices(&mut slices, written),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
         }
     }
+    Ok(())
 }
@@ -0,0 +1,73 @@
+// Bitfield as sent in the peer wire protocol: the high bit of the first byte
+// corresponds to piece index 0. Spare bits at the end are zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitfield {
+    bytes: Vec<u8>,
+    num_pieces: usize,
+}
+
+impl Bitfield {
+    pub fn new(num_pieces: usize) -> Bitfield {
+        Bitfield {
+            bytes: vec![0u8; num_pieces.div_ceil(8)],
+            num_pieces,
+        }
+    }
+
+    pub fn full(num_pieces: usize) -> Bitfield {
+        let mut bitfield = Bitfield::new(num_pieces);
+        for index in 0..num_pieces {
+            bitfield.set(index);
+        }
+        bitfield
+    }
+
+    pub fn from_bytes(bytes: &[u8], num_pieces: usize) -> Bitfield {
+        let mut bitfield = Bitfield::new(num_pieces);
+        for index in 0..num_pieces {
+            if bytes
+                .get(index / 8)
+                .is_some_and(|byte| byte & (0x80 >> (index % 8)) != 0)
+            {
+                bitfield.set(index);
+            }
+        }
+        bitfield
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_pieces
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_pieces == 0
+    }
+
+    pub fn has(&self, index: usize) -> bool {
+        index < self.num_pieces && self.bytes[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+
+    pub fn set(&mut self, index: usize) {
+        if index < self.num_pieces {
+            self.bytes[index / 8] |= 0x80 >> (index % 8);
+        }
+    }
+
+    pub fn clear(&mut self, index: usize) {
+        if index < self.num_pieces {
+            self.bytes[index / 8] &= !(0x80 >> (index % 8));
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.bytes.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.count() == self.num_pieces
+    }
+}
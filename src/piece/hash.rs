@@ -0,0 +1,47 @@
+use sha1::{Digest, Sha1};
+use sha1collisiondetection::Sha1CD;
+use std::error::Error;
+use std::fmt;
+
+// Plain SHA-1 is what v1 torrents are built on, but identical-prefix collisions are
+// practical, so a malicious swarm could serve a crafted piece that still matches. The
+// collision-detecting implementation spots the disturbance vectors such attacks rely on,
+// but hashes noticeably slower, so it's opt-in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sha1Mode {
+    #[default]
+    Fast,
+    CollisionDetection,
+}
+
+// Which hash a piece was checked against. Hybrid torrents carry both SHA-1 piece hashes
+// and BEP 52 SHA-256 trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashVersion {
+    V1,
+    V2,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollisionDetected;
+
+impl fmt::Display for CollisionDetected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SHA-1 collision attack detected")
+    }
+}
+
+impl Error for CollisionDetected {}
+
+pub fn sha1(data: &[u8], mode: Sha1Mode) -> Result<[u8; 20], CollisionDetected> {
+    match mode {
+        Sha1Mode::Fast => Ok(Sha1::digest(data).into()),
+        Sha1Mode::CollisionDetection => {
+            // safe_hash(false) keeps the real SHA-1 output; we only want the detection.
+            let mut hasher = Sha1CD::configure().safe_hash(false).build();
+            hasher.update(data);
+            let digest = hasher.finalize_cd().map_err(|_| CollisionDetected)?;
+            Ok(digest.into())
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use super::merkle::{self, BLOCK_SIZE};
+use crate::torrent::value::TorrentMetaInfo;
+use std::collections::{HashMap, HashSet};
+
+// Most hashes asked for in one hash request; peers aren't required to serve more.
+pub const MAX_HASHES: usize = 512;
+
+// A run of piece layer hashes to ask a peer for, with enough proof layers to check it
+// against the file's pieces root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashRun {
+    pub pieces_root: [u8; 32],
+    pub base_layer: u32,
+    pub index: u32,
+    pub length: u32,
+    pub proof_layers: u32,
+}
+
+#[derive(Debug)]
+struct PieceLayer {
+    // One per piece of the file, then pad hashes up to a power of two.
+    hashes: Vec<Option<[u8; 32]>>,
+    pad: [u8; 32],
+    // Runs asked for and not answered yet, by first index.
+    requested: HashSet<usize>,
+}
+
+impl PieceLayer {
+    fn width(&self) -> usize {
+        self.hashes.len()
+    }
+
+    fn is_complete(&self) -> bool {
+        self.hashes.iter().all(Option::is_some)
+    }
+
+    fn run_length(&self) -> usize {
+        self.width().min(MAX_HASHES)
+    }
+}
+
+// The piece layer of each v2 file longer than a piece, keyed by pieces root: the layer of
+// its Merkle tree with one hash per piece, which is what pieces are checked against. A
+// .torrent may carry them; otherwise they're fetched from peers with hash requests and
+// every run received is checked against the pieces root before it's used.
+#[derive(Debug)]
+pub struct PieceLayers {
+    // Height of the piece layers above the 16 KiB leaves.
+    base_layer: u32,
+    layers: HashMap<[u8; 32], PieceLayer>,
+}
+
+impl PieceLayers {
+    pub fn new(meta: &TorrentMetaInfo) -> PieceLayers {
+        let piece_length = meta.info.piece_length;
+        let blocks_per_piece = piece_length / BLOCK_SIZE;
+        let mut layers = HashMap::new();
+        if blocks_per_piece.is_power_of_two() && piece_length.is_multiple_of(BLOCK_SIZE) {
+            let pad = merkle::pad_hash(blocks_per_piece);
+            for file in meta
+                .v2_files
+                .iter()
+                .filter(|file| file.length > piece_length)
+            {
+                let leaves = file.length.div_ceil(BLOCK_SIZE).next_power_of_two();
+                let pieces = file.length.div_ceil(piece_length);
+                let mut hashes = vec![None; leaves / blocks_per_piece];
+                hashes[pieces..].fill(Some(pad));
+                // A layer that came with the .torrent was checked against the pieces root
+                // when it was parsed.
+                if file.piece_layer.len() == pieces {
+                    for (slot, hash) in hashes.iter_mut().zip(&file.piece_layer) {
+                        *slot = Some(*hash);
+                    }
+                }
+                let layer = PieceLayer {
+                    hashes,
+                    pad,
+                    requested: HashSet::new(),
+                };
+                layers.insert(file.pieces_root, layer);
+            }
+        }
+        PieceLayers {
+            base_layer: blocks_per_piece.trailing_zeros(),
+            layers,
+        }
+    }
+
+    // The `n`th piece hash of the file with this pieces root, if known.
+    pub fn hash(&self, pieces_root: &[u8; 32], n: usize) -> Option<[u8; 32]> {
+        *self.layers.get(pieces_root)?.hashes.get(n)?
+    }
+
+    // Files whose piece layer is still incomplete.
+    pub fn missing(&self) -> usize {
+        self.layers
+            .values()
+            .filter(|layer| !layer.is_complete())
+            .count()
+    }
+
+    // Picks a run nobody has been asked for yet and marks it requested.
+    pub fn next_request(&mut self) -> Option<HashRun> {
+        let base_layer = self.base_layer;
+        self.layers.iter_mut().find_map(|(root, layer)| {
+            let length = layer.run_length();
+            let index = (0..layer.width()).step_by(length).find(|&index| {
+                !layer.requested.contains(&index)
+                    && layer.hashes[index..index + length]
+                        .iter()
+                        .any(Option::is_none)
+            })?;
+            layer.requested.insert(index);
+            Some(HashRun {
+                pieces_root: *root,
+                base_layer,
+                index: index as u32,
+                length: length as u32,
+                proof_layers: layer.width().trailing_zeros(),
+            })
+        })
+    }
+
+    // The run wasn't answered, say because the peer rejected it or went away.
+    pub fn cancel(&mut self, pieces_root: &[u8; 32], index: u32) {
+        if let Some(layer) = self.layers.get_mut(pieces_root) {
+            layer.requested.remove(&(index as usize));
+        }
+    }
+
+    // Takes a Hashes message: the run followed by its uncle hashes. Returns true if they
+    // add up to the pieces root and were kept.
+    pub fn add_hashes(&mut self, run: HashRun, hashes: &[[u8; 32]]) -> bool {
+        let Some(layer) = self.layers.get_mut(&run.pieces_root) else {
+            return false;
+        };
+        let (index, length) = (run.index as usize, run.length as usize);
+        layer.requested.remove(&index);
+        if run.base_layer != self.base_layer || hashes.len() < length {
+            return false;
+        }
+        let (run_hashes, uncles) = hashes.split_at(length);
+        if !merkle::verify_proof(&run.pieces_root, layer.width(), index, run_hashes, uncles) {
+            return false;
+        }
+        for (slot, hash) in layer.hashes[index..].iter_mut().zip(run_hashes) {
+            *slot = Some(*hash);
+        }
+        true
+    }
+
+    // Answers a peer's hash request from a complete layer: the run and as many uncle hashes
+    // as it asked for. None means the request gets a HashReject.
+    pub fn answer(&self, run: HashRun) -> Option<Vec<[u8; 32]>> {
+        let layer = self.layers.get(&run.pieces_root)?;
+        let (index, length) = (run.index as usize, run.length as usize);
+        let valid = run.base_layer == self.base_layer
+            && layer.is_complete()
+            && length.is_power_of_two()
+            && length <= MAX_HASHES
+            && index.is_multiple_of(length)
+            && index + length <= layer.width();
+        if !valid {
+            return None;
+        }
+        let full: Vec<[u8; 32]> = layer.hashes.iter().flatten().copied().collect();
+        let mut hashes = full[index..index + length].to_vec();
+        // Layers the run's own hashes can rebuild don't get proof hashes.
+        let wanted = (run.proof_layers as usize).saturating_sub(length.trailing_zeros() as usize);
+        let uncles = merkle::proof(&full, layer.width(), layer.pad, index, length);
+        hashes.extend(uncles.into_iter().take(wanted));
+        Some(hashes)
+    }
+}
@@ -0,0 +1,104 @@
+use sha2::{Digest, Sha256};
+
+// BEP 52 hashes files as a binary Merkle tree over 16 KiB blocks. Leaves past the end of
+// the file are all-zero hashes, so every tree is a full power of two wide.
+pub const BLOCK_SIZE: usize = 16 * 1024;
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+pub fn block_hashes(data: &[u8]) -> Vec<[u8; 32]> {
+    data.chunks(BLOCK_SIZE).map(sha256).collect()
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn parent_layer(layer: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    layer
+        .chunks(2)
+        .map(|pair| hash_pair(&pair[0], &pair[1]))
+        .collect()
+}
+
+// Root of a tree `width` leaves wide (a power of two), padded with zero hashes.
+pub fn root(leaves: &[[u8; 32]], width: usize) -> [u8; 32] {
+    root_with_padding(leaves, width, [0; 32])
+}
+
+// Same as root, for a layer higher up the tree. Its padding is `pad`, the root of an
+// all-zero subtree reaching down to the leaves (see pad_hash).
+pub fn root_with_padding(layer: &[[u8; 32]], width: usize, pad: [u8; 32]) -> [u8; 32] {
+    let mut layer = layer.to_vec();
+    layer.resize(width.max(1), pad);
+    while layer.len() > 1 {
+        layer = parent_layer(&layer);
+    }
+    layer[0]
+}
+
+// Root of a subtree over `leaves` zero hashes.
+pub fn pad_hash(leaves: usize) -> [u8; 32] {
+    root(&[], leaves)
+}
+
+// Uncle hashes proving the run of `length` nodes starting at `index` in `layer`, padded to
+// `width` with `pad`: one per level from the run's subtree root up to the tree's root,
+// bottom first. `length` is a power of two and `index` a multiple of it.
+pub fn proof(
+    layer: &[[u8; 32]],
+    width: usize,
+    pad: [u8; 32],
+    index: usize,
+    length: usize,
+) -> Vec<[u8; 32]> {
+    let mut layer = layer.to_vec();
+    layer.resize(width, pad);
+    while layer.len() > width / length {
+        layer = parent_layer(&layer);
+    }
+    let mut position = index / length;
+    let mut uncles = Vec::new();
+    while layer.len() > 1 {
+        uncles.push(layer[position ^ 1]);
+        layer = parent_layer(&layer);
+        position /= 2;
+    }
+    uncles
+}
+
+// Checks a run of nodes from a layer `width` wide against the tree's `root`, given the
+// uncle hashes from proof.
+pub fn verify_proof(
+    root: &[u8; 32],
+    width: usize,
+    index: usize,
+    hashes: &[[u8; 32]],
+    uncles: &[[u8; 32]],
+) -> bool {
+    let length = hashes.len();
+    if !length.is_power_of_two()
+        || !width.is_power_of_two()
+        || index + length > width
+        || !index.is_multiple_of(length)
+        || (width / length).trailing_zeros() as usize != uncles.len()
+    {
+        return false;
+    }
+    let mut node = root_with_padding(hashes, length, [0; 32]);
+    let mut position = index / length;
+    for uncle in uncles {
+        node = if position.is_multiple_of(2) {
+            hash_pair(&node, uncle)
+        } else {
+            hash_pair(uncle, &node)
+        };
+        position /= 2;
+    }
+    node == *root
+}
@@ -0,0 +1,7 @@
+pub mod availability;
+pub mod bitfield;
+pub mod hash;
+pub mod layers;
+pub mod merkle;
+pub mod picker;
+pub mod strategy;
@@ -0,0 +1,269 @@
+use super::availability::Availability;
+use super::bitfield::Bitfield;
+use super::strategy::{PickStrategy, PickView, RarestFirst, Sequential};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickerStrictness {
+    // Switch to seed-friendly picking on its own when the swarm only has one seed.
+    Auto,
+    RarestFirst,
+    SeedFriendly,
+}
+
+// Rarest-first spreads requests all over the torrent, which is what we want in a healthy
+// swarm. When a single seed holds the only copy of the pieces we're missing, it just makes
+// the seed's disk seek around. In seed-friendly mode each peer is asked for pieces in index
+// order, continuing from where we left off with that peer.
+// A strategy set with set_strategy replaces both, and the strictness no longer matters.
+pub struct PiecePicker {
+    have: Bitfield,
+    // Pieces of files that aren't skipped; the others are never picked.
+    wanted: Bitfield,
+    pending: Vec<bool>,
+    availability: Availability,
+    strictness: PickerStrictness,
+    rarest: RarestFirst,
+    sequential: Sequential,
+    strategy: Option<Box<dyn PickStrategy>>,
+    // Peers that sent a copy of the piece that failed its hash check. They're only asked
+    // for it again when they have nothing else we want.
+    avoid: HashMap<usize, HashSet<SocketAddr>>,
+}
+
+impl PiecePicker {
+    pub fn new(num_pieces: usize) -> PiecePicker {
+        PiecePicker {
+            have: Bitfield::new(num_pieces),
+            wanted: Bitfield::full(num_pieces),
+            pending: vec![false; num_pieces],
+            availability: Availability::new(num_pieces),
+            strictness: PickerStrictness::Auto,
+            rarest: RarestFirst,
+            sequential: Sequential::default(),
+            strategy: None,
+            avoid: HashMap::new(),
+        }
+    }
+
+    pub fn strictness(&self) -> PickerStrictness {
+        self.strictness
+    }
+
+    pub fn set_strictness(&mut self, strictness: PickerStrictness) {
+        self.strictness = strictness;
+    }
+
+    pub fn set_strategy(&mut self, strategy: Box<dyn PickStrategy>) {
+        self.strategy = Some(strategy);
+    }
+
+    // Back to the built-in strategies and the strictness setting.
+    pub fn clear_strategy(&mut self) {
+        self.strategy = None;
+    }
+
+    pub fn has_custom_strategy(&self) -> bool {
+        self.strategy.is_some()
+    }
+
+    pub fn have(&self) -> &Bitfield {
+        &self.have
+    }
+
+    pub fn wanted(&self) -> &Bitfield {
+        &self.wanted
+    }
+
+    pub fn set_wanted(&mut self, wanted: Bitfield) {
+        if wanted.len() == self.wanted.len() {
+            self.wanted = wanted;
+        }
+    }
+
+    pub fn availability(&self) -> &Availability {
+        &self.availability
+    }
+
+    pub fn add_peer_bitfield(&mut self, bitfield: &Bitfield) {
+        self.availability.add_bitfield(bitfield);
+    }
+
+    // For a peer that sends its whole bitfield again: what it reported before stops
+    // counting.
+    pub fn replace_peer_bitfield(&mut self, old: &Bitfield, new: &Bitfield) {
+        self.availability.remove_bitfield(old);
+        self.availability.add_bitfield(new);
+    }
+
+    pub fn remove_peer(&mut self, peer: SocketAddr, bitfield: &Bitfield) {
+        self.availability.remove_bitfield(bitfield);
+        self.sequential.on_peer_removed(peer);
+        if let Some(strategy) = &mut self.strategy {
+            strategy.on_peer_removed(peer);
+        }
+    }
+
+    // `bitfield` is the peer's bitfield *after* the Have was applied.
+    pub fn add_have(&mut self, index: usize, bitfield: &Bitfield) {
+        self.availability.add_have(index, bitfield);
+        if let Some(strategy) = &mut self.strategy {
+            strategy.on_have(index);
+        }
+    }
+
+    pub fn on_choke(&mut self, peer: SocketAddr) {
+        if let Some(strategy) = &mut self.strategy {
+            strategy.on_choke(peer);
+        }
+    }
+
+    // True when exactly one seed exists and nobody else has any of the pieces we still need.
+    pub fn is_solo_seed_swarm(&self) -> bool {
+        self.availability.seeds() == 1
+            && self
+                .availability
+                .counts()
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !self.have.has(*index))
+                .all(|(_, count)| *count <= 1)
+    }
+
+    pub fn is_seed_friendly(&self) -> bool {
+        match self.strictness {
+            PickerStrictness::Auto => self.is_solo_seed_swarm(),
+            PickerStrictness::RarestFirst => false,
+            PickerStrictness::SeedFriendly => true,
+        }
+    }
+
+    pub fn pick(&mut self, peer: SocketAddr, peer_bitfield: &Bitfield) -> Option<usize> {
+        self.pick_up_to(peer, peer_bitfield, 1).pop()
+    }
+
+    // Claims up to `n` pieces for `peer`, in the order they should be requested. Pieces
+    // this peer got wrong before are only offered to the strategy when there's nothing
+    // else.
+    pub fn pick_up_to(
+        &mut self,
+        peer: SocketAddr,
+        peer_bitfield: &Bitfield,
+        n: usize,
+    ) -> Vec<usize> {
+        let mut candidates = Bitfield::new(self.have.len());
+        let mut avoided = Bitfield::new(self.have.len());
+        for index in (0..self.have.len()).filter(|&index| self.is_wanted(index, peer_bitfield)) {
+            if self.is_avoided(index, peer) {
+                avoided.set(index);
+            } else {
+                candidates.set(index);
+            }
+        }
+        if candidates.count() == 0 {
+            candidates = avoided;
+        }
+        if candidates.count() == 0 || n == 0 {
+            return Vec::new();
+        }
+
+        let seed_friendly = self.is_seed_friendly();
+        let view = PickView {
+            have: &self.have,
+            availability: &self.availability,
+        };
+        let strategy: &mut dyn PickStrategy = match &mut self.strategy {
+            Some(strategy) => strategy.as_mut(),
+            None if seed_friendly => &mut self.sequential,
+            None => &mut self.rarest,
+        };
+        let picked = strategy.next_pieces(&view, peer, &candidates, n);
+        // Strategies may be outside code; only what was offered is claimed, and only once.
+        let mut claimed = Vec::new();
+        for index in picked {
+            if claimed.len() == n {
+                break;
+            }
+            if candidates.has(index) && !self.pending[index] {
+                self.pending[index] = true;
+                claimed.push(index);
+            }
+        }
+        claimed
+    }
+
+    // Endgame: once every piece we still want has been claimed, a peer may be handed one
+    // that another peer is already downloading, so the last few pieces don't wait on the
+    // slowest peer. Returns None while anything is still unclaimed.
+    pub fn pick_endgame(&self, peer: SocketAddr, peer_bitfield: &Bitfield) -> Option<usize> {
+        let missing = |index: usize| self.wanted.has(index) && !self.have.has(index);
+        if (0..self.have.len()).any(|index| missing(index) && !self.pending[index]) {
+            return None;
+        }
+        (0..self.have.len())
+            .filter(|&index| missing(index) && peer_bitfield.has(index))
+            .filter(|&index| !self.is_avoided(index, peer))
+            .min_by_key(|&index| (self.availability.count(index), index))
+    }
+
+    // Claims up to `max_pieces` adjacent pieces, starting at the first one nobody has
+    // claimed yet. For sources that have everything and prefer sequential reads, like web
+    // seeds.
+    pub fn pick_run(&mut self, max_pieces: usize) -> Option<Range<usize>> {
+        let start = (0..self.have.len()).find(|&index| self.is_unclaimed(index))?;
+        let end = (start..self.have.len())
+            .take(max_pieces.max(1))
+            .take_while(|&index| self.is_unclaimed(index))
+            .last()?
+            + 1;
+
+        for pending in &mut self.pending[start..end] {
+            *pending = true;
+        }
+        Some(start..end)
+    }
+
+    pub fn on_piece_done(&mut self, index: usize) {
+        self.have.set(index);
+        self.avoid.remove(&index);
+        if let Some(pending) = self.pending.get_mut(index) {
+            *pending = false;
+        }
+        if let Some(strategy) = &mut self.strategy {
+            strategy.on_piece_done(index);
+        }
+    }
+
+    // A piece we had turned out bad on disk; it has to be downloaded again.
+    pub fn on_piece_lost(&mut self, index: usize) {
+        self.have.clear(index);
+    }
+
+    // The piece failed verification or its peer went away; make it pickable again.
+    pub fn abandon(&mut self, index: usize) {
+        if let Some(pending) = self.pending.get_mut(index) {
+            *pending = false;
+        }
+    }
+
+    // The piece failed its hash check; prefer other peers than `peers` for the next try.
+    pub fn avoid(&mut self, index: usize, peers: impl IntoIterator<Item = SocketAddr>) {
+        self.avoid.entry(index).or_default().extend(peers);
+    }
+
+    fn is_avoided(&self, index: usize, peer: SocketAddr) -> bool {
+        self.avoid
+            .get(&index)
+            .is_some_and(|peers| peers.contains(&peer))
+    }
+
+    fn is_unclaimed(&self, index: usize) -> bool {
+        self.wanted.has(index) && !self.have.has(index) && !self.pending[index]
+    }
+
+    fn is_wanted(&self, index: usize, peer_bitfield: &Bitfield) -> bool {
+        self.is_unclaimed(index) && peer_bitfield.has(index)
+    }
+}
@@ -0,0 +1,111 @@
+use super::availability::Availability;
+use super::bitfield::Bitfield;
+use rand::seq::IteratorRandom;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+// What a strategy gets to look at besides the candidates: which pieces we have and how
+// common each one is among connected peers.
+pub struct PickView<'a> {
+    pub have: &'a Bitfield,
+    pub availability: &'a Availability,
+}
+
+// Decides the order pieces are downloaded in. PiecePicker does the bookkeeping (what we
+// have, want and are already fetching, which peer sent a bad copy, endgame) and hands the
+// strategy only pieces that are fine to request, so a strategy is just an ordering plus
+// whatever state it keeps from the events below.
+pub trait PickStrategy: Send {
+    // Up to `n` of the `candidates`, best first. The candidates are pieces `peer` has
+    // that we want and nobody is fetching; an empty result leaves the peer idle.
+    fn next_pieces(
+        &mut self,
+        view: &PickView,
+        peer: SocketAddr,
+        candidates: &Bitfield,
+        n: usize,
+    ) -> Vec<usize>;
+
+    // A peer announced a piece. Availability is already updated.
+    fn on_have(&mut self, _index: usize) {}
+
+    fn on_piece_done(&mut self, _index: usize) {}
+
+    // `peer` choked us; whatever it was sending is parked for other peers.
+    fn on_choke(&mut self, _peer: SocketAddr) {}
+
+    fn on_peer_removed(&mut self, _peer: SocketAddr) {}
+}
+
+fn indices(candidates: &Bitfield) -> impl Iterator<Item = usize> + '_ {
+    (0..candidates.len()).filter(|&index| candidates.has(index))
+}
+
+// The least common pieces first, ties broken by index, so copies spread through the
+// swarm as fast as possible.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RarestFirst;
+
+impl PickStrategy for RarestFirst {
+    fn next_pieces(
+        &mut self,
+        view: &PickView,
+        _peer: SocketAddr,
+        candidates: &Bitfield,
+        n: usize,
+    ) -> Vec<usize> {
+        let mut pieces: Vec<usize> = indices(candidates).collect();
+        pieces.sort_by_key(|&index| (view.availability.count(index), index));
+        pieces.truncate(n);
+        pieces
+    }
+}
+
+// Index order, each peer continuing after the last piece it was asked for. Easy on a
+// lone seed's disk, and what streaming wants.
+#[derive(Debug, Clone, Default)]
+pub struct Sequential {
+    cursors: HashMap<SocketAddr, usize>,
+}
+
+impl PickStrategy for Sequential {
+    fn next_pieces(
+        &mut self,
+        _view: &PickView,
+        peer: SocketAddr,
+        candidates: &Bitfield,
+        n: usize,
+    ) -> Vec<usize> {
+        let start = self.cursors.get(&peer).copied().unwrap_or(0);
+        let pieces: Vec<usize> = (start..candidates.len())
+            .chain(0..start)
+            .filter(|&index| candidates.has(index))
+            .take(n)
+            .collect();
+        if let Some(last) = pieces.last() {
+            self.cursors.insert(peer, last + 1);
+        }
+        pieces
+    }
+
+    fn on_peer_removed(&mut self, peer: SocketAddr) {
+        self.cursors.remove(&peer);
+    }
+}
+
+// Uniformly random; gets a newcomer some complete pieces to trade quickly without every
+// peer converging on the same rare ones.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Random;
+
+impl PickStrategy for Random {
+    fn next_pieces(
+        &mut self,
+        _view: &PickView,
+        _peer: SocketAddr,
+        candidates: &Bitfield,
+        n: usize,
+    ) -> Vec<usize> {
+        indices(candidates).choose_multiple(&mut rand::rng(), n)
+    }
+}
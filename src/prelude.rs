@@ -0,0 +1,12 @@
+// The types most programs need to run a session: `use bittorrent_client::prelude::*;`.
+// Magnet links parse, but metadata isn't fetched from peers yet, so torrents are still
+// added from full metainfo.
+pub use crate::session::config::SessionConfig;
+pub use crate::session::engine::Session;
+pub use crate::session::error::SessionError;
+pub use crate::session::event::SessionEvent;
+pub use crate::session::handle::{TorrentHandle, TorrentState};
+pub use crate::session::torrent::Torrent;
+pub use crate::torrent::magnet::MagnetLink;
+pub use crate::torrent::parser::parse_torrent_file;
+pub use crate::torrent::value::TorrentMetaInfo;
@@ -0,0 +1,113 @@
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+// How often and how soon to try again after something failed: peer connections, tracker
+// announces, web seeds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // Fraction of each delay that is random, 0.0 to 1.0, so that many torrents failing
+    // together don't all retry in the same instant.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Delay before the given retry (1 = first retry), doubling each time up to max_backoff.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    // backoff(retry) shortened by a random part of up to `jitter` of it; never longer.
+    pub fn jittered(&self, retry: u32) -> Duration {
+        self.spread(self.backoff(retry))
+    }
+
+    fn spread(&self, delay: Duration) -> Duration {
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+        delay.mul_f64(1.0 - rand::rng().random_range(0.0..=jitter))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    // Working, or failed fewer than max_attempts times in a row. A failure only delays
+    // the next try.
+    Closed,
+    // Failed max_attempts times in a row; tried again only after max_backoff.
+    Open,
+    // Open, but max_backoff has passed: the next try decides. Success closes the
+    // breaker, failure opens it for another max_backoff.
+    HalfOpen,
+}
+
+// Failure bookkeeping for one remote resource, e.g. a tracker, under a RetryPolicy. A
+// single failure never writes the resource off; it only pushes the next try back.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new() -> CircuitBreaker {
+        CircuitBreaker::default()
+    }
+
+    // Consecutive failures since the last success.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    pub fn retry_at(&self) -> Option<Instant> {
+        self.retry_at
+    }
+
+    pub fn state(&self, policy: &RetryPolicy, now: Instant) -> BreakerState {
+        if self.failures < policy.max_attempts {
+            BreakerState::Closed
+        } else if self.allows(now) {
+            BreakerState::HalfOpen
+        } else {
+            BreakerState::Open
+        }
+    }
+
+    // Whether the resource may be tried at `now`.
+    pub fn allows(&self, now: Instant) -> bool {
+        self.retry_at.is_none_or(|at| now >= at)
+    }
+
+    pub fn on_success(&mut self) {
+        self.failures = 0;
+        self.retry_at = None;
+    }
+
+    pub fn on_failure(&mut self, policy: &RetryPolicy, now: Instant) {
+        self.failures = self.failures.saturating_add(1);
+        let delay = if self.failures >= policy.max_attempts {
+            policy.spread(policy.max_backoff)
+        } else {
+            policy.jittered(self.failures)
+        };
+        self.retry_at = Some(now + delay);
+    }
+}
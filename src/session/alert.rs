@@ -0,0 +1,125 @@
+use super::event::SessionEvent;
+use super::history::StateChange;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+// Something a user may want to know about after the fact. Ids increase by one per alert,
+// so a UI can ask for what it hasn't seen yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub id: u64,
+    pub at: SystemTime,
+    pub severity: Severity,
+    // The torrent it's about, if any.
+    pub info_hash: Option<[u8; 20]>,
+    pub message: String,
+}
+
+// The session's most recent alerts. Unlike the event bus this keeps them whether or not
+// anyone is subscribed, for UIs that attach later; the oldest are dropped past capacity.
+pub struct AlertLog {
+    alerts: Mutex<VecDeque<Alert>>,
+    capacity: usize,
+}
+
+impl AlertLog {
+    pub fn new(capacity: usize) -> AlertLog {
+        AlertLog {
+            alerts: Mutex::new(VecDeque::new()),
+            capacity,
+        }
+    }
+
+    pub fn push(&self, severity: Severity, info_hash: Option<[u8; 20]>, message: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut alerts = self.alerts.lock().unwrap();
+        let id = alerts.back().map_or(0, |last| last.id + 1);
+        if alerts.len() == self.capacity {
+            alerts.pop_front();
+        }
+        alerts.push_back(Alert {
+            id,
+            at: SystemTime::now(),
+            severity,
+            info_hash,
+            message,
+        });
+    }
+
+    // Alerts of at least `severity`, oldest first.
+    pub fn alerts(&self, severity: Severity) -> Vec<Alert> {
+        let alerts = self.alerts.lock().unwrap();
+        let matching = alerts.iter().filter(|alert| alert.severity >= severity);
+        matching.cloned().collect()
+    }
+
+    // Alerts newer than the one with id `seen`, oldest first.
+    pub fn alerts_since(&self, seen: u64) -> Vec<Alert> {
+        let alerts = self.alerts.lock().unwrap();
+        let newer = alerts.iter().filter(|alert| alert.id > seen);
+        newer.cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        let mut alerts = self.alerts.lock().unwrap();
+        // Keeps the last one so ids carry on where they were.
+        let len = alerts.len();
+        alerts.drain(..len.saturating_sub(1));
+    }
+
+    // Messages leave out the torrent's name; `info_hash` says which one it's about.
+    pub(crate) fn push_change(&self, info_hash: [u8; 20], change: &StateChange) {
+        let (severity, message) = match change {
+            StateChange::Completed => (Severity::Info, "Download completed".to_string()),
+            StateChange::TrackerError { url, error } => (
+                Severity::Warning,
+                format!("Announce to {} failed: {}", url, error),
+            ),
+            StateChange::InsufficientSpace { needed, available } => (
+                Severity::Error,
+                format!("Paused, {} needed but {} available", needed, available),
+            ),
+            StateChange::Error(error) => (Severity::Error, error.clone()),
+            _ => return,
+        };
+        self.push(severity, Some(info_hash), message);
+    }
+
+    pub(crate) fn push_event(&self, event: &SessionEvent) {
+        let (severity, info_hash, message) = match event {
+            SessionEvent::SeedingGoalReached { info_hash, goal } => (
+                Severity::Info,
+                info_hash,
+                format!("Seeding goal reached: {:?}", goal),
+            ),
+            SessionEvent::Md5Mismatch { info_hash, path } => (
+                Severity::Warning,
+                info_hash,
+                format!("{} doesn't match its md5sum", path.display()),
+            ),
+            SessionEvent::WebSeedDisabled { info_hash, url } => (
+                Severity::Warning,
+                info_hash,
+                format!("Web seed {} disabled after bad pieces", url),
+            ),
+            SessionEvent::Moved { info_hash, to, .. } => (
+                Severity::Info,
+                info_hash,
+                format!("Moved to {}", to.display()),
+            ),
+            // MoveFailed also stops the torrent with an error, which is alerted on.
+            _ => return,
+        };
+        self.push(severity, Some(*info_hash), message);
+    }
+}
@@ -0,0 +1,24 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+// Labels for a peer address, such as "country" => "NL" or "asn" => "AS1136". Keys are up
+// to the annotator.
+pub type PeerLabels = BTreeMap<String, String>;
+
+// Looks up extra information about peer addresses, e.g. from a GeoIP or ASN database the
+// user has. The session asks once per connection, on that connection's own thread, so a
+// slow lookup only holds up its own peer. Implementations that hit the disk or network
+// should cache.
+pub trait PeerAnnotator: Send + Sync {
+    fn annotate(&self, ip: IpAddr) -> PeerLabels;
+}
+
+// What a session uses until it's given an annotator: no labels for anyone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoAnnotations;
+
+impl PeerAnnotator for NoAnnotations {
+    fn annotate(&self, _ip: IpAddr) -> PeerLabels {
+        PeerLabels::new()
+    }
+}
@@ -0,0 +1,270 @@
+use crate::units::Rate;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Session-wide transfer rates. None is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    pub download: Option<Rate>,
+    pub upload: Option<Rate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ];
+}
+
+// Limits that apply from start_hour up to end_hour on the given days. A rule whose end is
+// at or before its start runs past midnight, e.g. 22 to 6; the part after midnight still
+// belongs to the day it started on. No days means every day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleRule {
+    pub days: Vec<Weekday>,
+    pub start_hour: u8,
+    pub end_hour: u8,
+    pub limits: RateLimits,
+}
+
+impl ScheduleRule {
+    fn matches(&self, day: Weekday, hour: u8) -> bool {
+        let runs_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        if self.start_hour < self.end_hour {
+            return runs_on(day) && (self.start_hour..self.end_hour).contains(&hour);
+        }
+        let yesterday = Weekday::ALL[(day as usize + 6) % 7];
+        (runs_on(day) && hour >= self.start_hour) || (runs_on(yesterday) && hour < self.end_hour)
+    }
+}
+
+// Alternative rate limits by time of day. Hours are taken at utc_offset_minutes (e.g. 60
+// for CET), as there's no portable way of asking for the machine's time zone. The first
+// matching rule wins; outside all of them SessionConfig::rate_limits applies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BandwidthSchedule {
+    pub utc_offset_minutes: i32,
+    pub rules: Vec<ScheduleRule>,
+}
+
+impl BandwidthSchedule {
+    pub fn limits_at(&self, time: SystemTime, default: RateLimits) -> RateLimits {
+        let since_epoch = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let local = (since_epoch as i64 + self.utc_offset_minutes as i64 * 60).max(0) as u64;
+        // 1970-01-01 was a Thursday.
+        let day = Weekday::ALL[((local / 86400 + 3) % 7) as usize];
+        let hour = (local % 86400 / 3600) as u8;
+
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(day, hour))
+            .map_or(default, |rule| rule.limits)
+    }
+}
+
+// Token bucket holding up to a second's worth of bytes. A transfer larger than what's
+// left runs the bucket into debt, which later callers wait out.
+pub struct RateLimiter {
+    state: Mutex<Bucket>,
+    // Bytes ever asked for, limited or not.
+    acquired: AtomicU64,
+}
+
+struct Bucket {
+    rate: Option<u64>,
+    tokens: f64,
+    refilled: Instant,
+}
+
+// Longest single sleep, so a rate change is noticed by callers already waiting.
+const MAX_WAIT: Duration = Duration::from_millis(100);
+
+impl RateLimiter {
+    pub fn new(rate: Option<u64>) -> RateLimiter {
+        RateLimiter {
+            state: Mutex::new(Bucket {
+                rate,
+                tokens: rate.unwrap_or(0) as f64,
+                refilled: Instant::now(),
+            }),
+            acquired: AtomicU64::new(0),
+        }
+    }
+
+    pub fn acquired(&self) -> u64 {
+        self.acquired.load(Ordering::Relaxed)
+    }
+
+    pub fn rate(&self) -> Option<u64> {
+        self.state.lock().unwrap().rate
+    }
+
+    pub fn set_rate(&self, rate: Option<u64>) {
+        let mut bucket = self.state.lock().unwrap();
+        if bucket.rate != rate {
+            bucket.rate = rate;
+            bucket.tokens = bucket.tokens.min(rate.unwrap_or(0) as f64);
+            bucket.refilled = Instant::now();
+        }
+    }
+
+    // Blocks until `bytes` may be transferred.
+    pub fn acquire(&self, bytes: usize) {
+        self.acquired.fetch_add(bytes as u64, Ordering::Relaxed);
+        loop {
+            let wait = {
+                let mut bucket = self.state.lock().unwrap();
+                let Some(rate) = bucket.rate else {
+                    return;
+                };
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * rate as f64).min(rate as f64);
+                bucket.refilled = now;
+                if bucket.tokens >= 0.0 {
+                    bucket.tokens -= bytes as f64;
+                    return;
+                }
+                Duration::from_secs_f64(-bucket.tokens / rate.max(1) as f64)
+            };
+            thread::sleep(wait.min(MAX_WAIT));
+        }
+    }
+}
+
+// A pair of limiters, for the session as a whole or for one torrent's share of it.
+pub(crate) struct Bandwidth {
+    pub(crate) download: RateLimiter,
+    pub(crate) upload: RateLimiter,
+    // When `sample_usage` last ran and the byte counts it saw.
+    sampled: Mutex<(Instant, u64, u64)>,
+}
+
+impl Bandwidth {
+    pub(crate) fn new(limits: RateLimits) -> Bandwidth {
+        Bandwidth {
+            download: RateLimiter::new(limits.download.map(|rate| rate.0)),
+            upload: RateLimiter::new(limits.upload.map(|rate| rate.0)),
+            sampled: Mutex::new((Instant::now(), 0, 0)),
+        }
+    }
+
+    // Bytes per second downloaded and uploaded since the last call.
+    pub(crate) fn sample_usage(&self) -> (u64, u64) {
+        let now = Instant::now();
+        let (download, upload) = (self.download.acquired(), self.upload.acquired());
+        let mut sampled = self.sampled.lock().unwrap();
+        let (at, last_download, last_upload) = *sampled;
+        *sampled = (now, download, upload);
+        let secs = now.duration_since(at).as_secs_f64().max(0.001);
+        let rate = |bytes: u64| (bytes as f64 / secs) as u64;
+        (
+            rate(download.wrapping_sub(last_download)),
+            rate(upload.wrapping_sub(last_upload)),
+        )
+    }
+
+    pub(crate) fn limits(&self) -> RateLimits {
+        RateLimits {
+            download: self.download.rate().map(Rate),
+            upload: self.upload.rate().map(Rate),
+        }
+    }
+
+    pub(crate) fn apply(&self, limits: RateLimits) {
+        self.download.set_rate(limits.download.map(|rate| rate.0));
+        self.upload.set_rate(limits.upload.map(|rate| rate.0));
+    }
+}
+
+// Weighs a torrent's claim on the session's bandwidth when torrents compete for it: a high
+// priority torrent gets twice the share of a normal one, which gets twice that of a low.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TorrentPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl TorrentPriority {
+    pub fn weight(&self) -> u64 {
+        match self {
+            TorrentPriority::Low => 1,
+            TorrentPriority::Normal => 2,
+            TorrentPriority::High => 4,
+        }
+    }
+}
+
+// Splits `rate` between torrents by priority, given the rate each has used lately.
+// Torrents using well under their share keep what they use and the rest is split again
+// among the others, so idle torrents don't hold bandwidth back. No torrent gets less than
+// its weighted share of the whole rate, so one that picks up speed can reclaim its share
+// on the next split; the session limiter keeps the total in check meanwhile.
+pub fn weighted_shares(rate: u64, torrents: &[(TorrentPriority, u64)]) -> Vec<u64> {
+    let total_weight: u64 = torrents.iter().map(|(priority, _)| priority.weight()).sum();
+    let mut shares: Vec<Option<u64>> = vec![None; torrents.len()];
+    let mut remaining = rate;
+    loop {
+        let open_weight: u64 = torrents
+            .iter()
+            .zip(&shares)
+            .filter(|(_, share)| share.is_none())
+            .map(|((priority, _), _)| priority.weight())
+            .sum();
+        if open_weight == 0 {
+            break;
+        }
+
+        let level = remaining;
+        let mut settled = false;
+        for ((priority, used), share) in torrents.iter().zip(&mut shares) {
+            let fair = part(level, priority.weight(), open_weight);
+            if share.is_none() && *used < fair * 9 / 10 {
+                *share = Some(*used);
+                remaining -= used;
+                settled = true;
+            }
+        }
+        if !settled {
+            for ((priority, _), share) in torrents.iter().zip(&mut shares) {
+                share.get_or_insert(part(level, priority.weight(), open_weight));
+            }
+        }
+    }
+
+    torrents
+        .iter()
+        .zip(shares)
+        .map(|((priority, _), share)| {
+            let floor = part(rate, priority.weight(), total_weight);
+            share.unwrap_or(0).max(floor)
+        })
+        .collect()
+}
+
+fn part(rate: u64, weight: u64, total_weight: u64) -> u64 {
+    (rate as u128 * weight as u128 / total_weight as u128) as u64
+}
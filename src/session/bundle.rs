@@ -0,0 +1,73 @@
+use super::error::SessionError;
+use super::resume::ResumeData;
+use crate::bencode::options::ParserOptions;
+use crate::bencode::parser::parse_value;
+use crate::bencode::value::BencodeValue;
+use crate::torrent::parser::parse_torrent_bytes;
+use crate::torrent::value::TorrentMetaInfo;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+const METAINFO_FILE: &str = "metainfo.torrent";
+const RESUME_FILE: &str = "resume";
+const STATS_FILE: &str = "stats";
+
+// Everything needed to carry on seeding a torrent in another session, possibly on another
+// machine: the metainfo, which pieces we have and the transfer totals so far. Stored as a
+// directory of bencoded files; the data itself is moved separately.
+#[derive(Debug, Clone)]
+pub struct TorrentBundle {
+    pub meta: TorrentMetaInfo,
+    pub resume: ResumeData,
+    pub downloaded: u64,
+    pub uploaded: u64,
+}
+
+impl TorrentBundle {
+    pub fn save(&self, dir: &Path) -> Result<(), SessionError> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(METAINFO_FILE), self.meta.to_bytes())?;
+        self.resume.save(&dir.join(RESUME_FILE))?;
+
+        let mut stats = HashMap::new();
+        stats.insert(
+            "downloaded".to_string(),
+            BencodeValue::Integer(self.downloaded as i64),
+        );
+        stats.insert(
+            "uploaded".to_string(),
+            BencodeValue::Integer(self.uploaded as i64),
+        );
+        fs::write(
+            dir.join(STATS_FILE),
+            BencodeValue::Dictionary(stats).encode(),
+        )?;
+        Ok(())
+    }
+
+    pub fn load(dir: &Path) -> Result<TorrentBundle, SessionError> {
+        let invalid = |err: &dyn std::fmt::Display| SessionError::InvalidBundle(err.to_string());
+
+        let data = fs::read(dir.join(METAINFO_FILE))?;
+        let meta = parse_torrent_bytes(&data, ParserOptions::lenient()).map_err(|e| invalid(&e))?;
+
+        let resume = ResumeData::load(&dir.join(RESUME_FILE))
+            .ok_or_else(|| invalid(&"unreadable resume data"))?;
+        if resume.info_hash != meta.info_hash() {
+            return Err(invalid(&"resume data is for another torrent"));
+        }
+
+        let data = fs::read(dir.join(STATS_FILE))?;
+        let (stats, _) = parse_value(&data).map_err(|e| invalid(&e))?;
+        let downloaded = stats.int("downloaded").map_err(|e| invalid(&e))?;
+        let uploaded = stats.int("uploaded").map_err(|e| invalid(&e))?;
+
+        Ok(TorrentBundle {
+            meta,
+            resume,
+            downloaded: downloaded.max(0) as u64,
+            uploaded: uploaded.max(0) as u64,
+        })
+    }
+}
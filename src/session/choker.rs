@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+// How upload slots are handed out once a torrent is complete. While downloading, slots go
+// to the peers we download from fastest, reciprocating, plus one optimistic unchoke.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SeedChoker {
+    // Peers that take data from us fastest keep their slots, plus one optimistic unchoke.
+    #[default]
+    FastestUpload,
+    // Slots rotate every round to whoever has waited longest, spreading the data around.
+    RoundRobin,
+}
+
+#[derive(Debug, Default)]
+struct ChokePeer {
+    interested: bool,
+    // Bytes each way since the last round.
+    downloaded: u64,
+    uploaded: u64,
+    unchoked: bool,
+    // Round the peer last got a slot in; 0 if it never did.
+    last_unchoked: u64,
+}
+
+// Decides which of a torrent's peers we upload to. Peer connections report their traffic
+// and interest here and pick up the outcome of each round; the session's tick runs the
+// rounds.
+#[derive(Debug, Default)]
+pub struct Choker {
+    peers: HashMap<SocketAddr, ChokePeer>,
+    round: u64,
+    last_round: Option<Instant>,
+}
+
+impl Choker {
+    pub fn add_peer(&mut self, addr: SocketAddr) {
+        self.peers.entry(addr).or_default();
+    }
+
+    pub fn remove_peer(&mut self, addr: SocketAddr) {
+        self.peers.remove(&addr);
+    }
+
+    pub fn set_interested(&mut self, addr: SocketAddr, interested: bool) {
+        if let Some(peer) = self.peers.get_mut(&addr) {
+            peer.interested = interested;
+        }
+    }
+
+    pub fn record_download(&mut self, addr: SocketAddr, bytes: u64) {
+        if let Some(peer) = self.peers.get_mut(&addr) {
+            peer.downloaded += bytes;
+        }
+    }
+
+    pub fn record_upload(&mut self, addr: SocketAddr, bytes: u64) {
+        if let Some(peer) = self.peers.get_mut(&addr) {
+            peer.uploaded += bytes;
+        }
+    }
+
+    pub fn is_unchoked(&self, addr: SocketAddr) -> bool {
+        self.peers.get(&addr).is_some_and(|peer| peer.unchoked)
+    }
+
+    pub fn unchoked(&self) -> Vec<SocketAddr> {
+        let mut unchoked: Vec<_> = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| peer.unchoked)
+            .map(|(&addr, _)| addr)
+            .collect();
+        unchoked.sort();
+        unchoked
+    }
+
+    pub fn is_due(&self, now: Instant, interval: Duration) -> bool {
+        self.last_round
+            .is_none_or(|last| now.duration_since(last) >= interval)
+    }
+
+    // Hands out `slots` unchokes among the interested peers and starts counting traffic
+    // afresh.
+    pub fn rechoke(&mut self, now: Instant, slots: usize, seeding: bool, seed_choker: SeedChoker) {
+        self.round += 1;
+        self.last_round = Some(now);
+
+        let mut candidates: Vec<(SocketAddr, &ChokePeer)> = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| peer.interested)
+            .map(|(&addr, peer)| (addr, peer))
+            .collect();
+        // Ties go to the lower address so rounds are reproducible.
+        candidates.sort_by_key(|&(addr, _)| addr);
+
+        let chosen = match (seeding, seed_choker) {
+            (true, SeedChoker::RoundRobin) => {
+                longest_waiting(&mut candidates);
+                candidates.truncate(slots);
+                candidates.into_iter().map(|(addr, _)| addr).collect()
+            }
+            (true, SeedChoker::FastestUpload) => {
+                fastest_plus_optimistic(candidates, slots, |peer| peer.uploaded)
+            }
+            (false, _) => fastest_plus_optimistic(candidates, slots, |peer| peer.downloaded),
+        };
+
+        for (addr, peer) in &mut self.peers {
+            peer.unchoked = chosen.contains(addr);
+            if peer.unchoked {
+                peer.last_unchoked = self.round;
+            }
+            peer.downloaded = 0;
+            peer.uploaded = 0;
+        }
+    }
+}
+
+// Choked peers first, then whoever got a slot longest ago.
+fn longest_waiting(candidates: &mut [(SocketAddr, &ChokePeer)]) {
+    candidates.sort_by_key(|&(_, peer)| (peer.unchoked, peer.last_unchoked));
+}
+
+// All slots but one go to the highest `rate`. The last is an optimistic unchoke, giving a
+// peer that hasn't had the chance to show its rate one.
+fn fastest_plus_optimistic(
+    mut candidates: Vec<(SocketAddr, &ChokePeer)>,
+    slots: usize,
+    rate: impl Fn(&ChokePeer) -> u64,
+) -> Vec<SocketAddr> {
+    if slots == 0 {
+        return Vec::new();
+    }
+    candidates.sort_by_key(|&(_, peer)| std::cmp::Reverse(rate(peer)));
+    let regular = (slots - 1).min(candidates.len());
+    let mut chosen: Vec<_> = candidates.drain(..regular).map(|(addr, _)| addr).collect();
+    longest_waiting(&mut candidates);
+    chosen.extend(candidates.first().map(|&(addr, _)| addr));
+    chosen
+}
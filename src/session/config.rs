@@ -0,0 +1,202 @@
+use super::bandwidth::{BandwidthSchedule, RateLimits};
+use super::choker::SeedChoker;
+use super::emulation::ClientPreset;
+use super::event::OverflowPolicy;
+use super::pipeline::PipelineDepth;
+use super::seeding::SeedingGoals;
+use crate::peer::connector::FamilyPreference;
+use crate::peer::socks::Socks5Proxy;
+use crate::peer::value::PeerTimeouts;
+use crate::piece::hash::Sha1Mode;
+use crate::retry::RetryPolicy;
+use crate::tracker::filter::TrackerFilter;
+use crate::units::ByteSize;
+use crate::webseed::policy::WebSeedPolicy;
+use crate::webseed::url_seed::UrlSeedLimits;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    pub listen_addr: SocketAddr,
+    // Port other peers should connect to when it differs from the bound one, e.g. behind a
+    // VPN that forwards some other external port to us. Used everywhere we advertise a port.
+    pub announce_port: Option<u16>,
+    // How often each active torrent asks the session's DhtClient for peers and announces
+    // itself through it.
+    pub dht_announce_interval: Duration,
+    pub timeouts: PeerTimeouts,
+    // Bytes asked for per block request. Most clients refuse requests over 16 KiB; peers
+    // we know better of may take up to peer::value::MAX_REQUEST_LENGTH.
+    pub block_size: u32,
+    // Number of block requests kept outstanding per peer. Links with a lot of latency need
+    // more of them to stay busy, which the adaptive setting works out per peer.
+    pub pipeline_depth: PipelineDepth,
+    // Peers per torrent that may transfer data at the same time.
+    pub max_active_peers: usize,
+    // Idle connections kept per torrent on top of the active ones; further peers are dropped.
+    pub max_standby_peers: usize,
+    // Lets peers on the local network (see peer::lan) connect regardless of the two peer
+    // limits above, so transfers between one's own machines aren't held back.
+    pub lan_peers_exempt: bool,
+    // How long after dialing a discovered peer we're willing to dial it again.
+    pub reconnect_interval: Duration,
+    // Interested peers we upload to at once, per torrent. Every choke_interval the slots
+    // are handed out again: while downloading to the peers giving us the most data, once
+    // complete as seed_choker says. None unchokes every interested active peer.
+    pub upload_slots: Option<usize>,
+    pub seed_choker: SeedChoker,
+    pub choke_interval: Duration,
+    pub keep_alive_interval: Duration,
+    // Caps on peer traffic across all torrents. The schedule swaps in other limits at
+    // certain hours; the tick loop re-evaluates it.
+    pub rate_limits: RateLimits,
+    pub bandwidth_schedule: BandwidthSchedule,
+    // UDP port of a DHT node running next to the session, sent in Port messages to peers
+    // that have a DHT node of their own.
+    pub dht_port: Option<u16>,
+    // Gives away as little as possible about who we are: a fresh random peer id per torrent
+    // with no client prefix, no client name in the extended handshake, lazy bitfields and
+    // no address hints to trackers.
+    pub privacy_mode: bool,
+    // Makes the peer id, extended handshake, keep-alives and bitfields look like those of
+    // another client. Privacy mode takes precedence over it.
+    pub client_preset: ClientPreset,
+    // Once every missing piece is being downloaded, lets idle peers download them too.
+    // Whoever finishes a piece second cancels the requests it still has out for it.
+    pub endgame: bool,
+    // Keeps requesting pieces a fast extension peer allows us while it chokes us.
+    pub honor_allowed_fast: bool,
+    // Piece and info hash verification; CollisionDetection is safer but slower.
+    pub sha1_mode: Sha1Mode,
+    // Peers found to have sent bad blocks for this many pieces are disconnected and turned
+    // away. None never bans.
+    pub max_hash_failures: Option<u32>,
+    // Pieces failing their hash check are written here with the peer behind each block,
+    // for offline analysis. Off by default; a torrent can override it.
+    pub forensics_dir: Option<PathBuf>,
+    // Downloads are kept here until complete and then moved below their save directory.
+    pub incomplete_dir: Option<PathBuf>,
+    // Appends ".!bt" to files in the incomplete directory.
+    pub part_suffix: bool,
+    // Checks files against their md5sum, if the torrent has any, once a download completes.
+    pub verify_md5: bool,
+    // Times DNS resolution, connecting and the response of each announce separately, at
+    // the price of an extra connection per announce. Otherwise only the total is kept in
+    // the announce history.
+    pub time_announces: bool,
+    // Longest an announce or scrape may take, redirects included.
+    pub tracker_timeout: Duration,
+    // Backoff between failed announces to a tracker. After max_attempts failures in a
+    // row it's only tried every max_backoff; stopped announces go out regardless.
+    pub tracker_retry: RetryPolicy,
+    // Trackers on denied domains, outside the allow list or with a scheme we can't
+    // announce over are skipped; their status in Torrent::trackers says why.
+    pub tracker_filter: TrackerFilter,
+    // Longest a stopped announce may take. On shutdown the session waits this long at most
+    // for all of them together.
+    pub stopped_announce_timeout: Duration,
+    // Announces over both IPv4 and IPv6 to trackers that have addresses in both, so peers
+    // of either family are found. Timed announces always use one connection.
+    pub dual_stack_announce: bool,
+    // Peers of this family are dialed first when an announce returns both.
+    pub prefer_family: FamilyPreference,
+    // Outgoing peer connections, tracker requests and web seed downloads go through this
+    // proxy, which also resolves their hostnames. Dual stack and timed announces don't apply then, as
+    // the proxy picks the address. Incoming connections are unaffected.
+    pub proxy: Option<Socks5Proxy>,
+    pub web_seeds: WebSeedPolicy,
+    pub web_seed_limits: UrlSeedLimits,
+    // Where the session keeps its lock file and per-torrent resume data. Without one every
+    // torrent is fully rechecked when added.
+    pub state_dir: Option<PathBuf>,
+    // With a DHT port and a state directory, the DHT node id and known nodes are saved
+    // there on shutdown and loaded at startup. Nodes not heard of for this long are
+    // dropped on load.
+    pub dht_node_max_age: Duration,
+    // How long written data may sit in the OS cache before reaching the disk. After an
+    // unclean shutdown, pieces completed this close to the last resume save are rehashed.
+    pub flush_window: Duration,
+    // Torrents allowed to download or seed at once; the rest wait in the queue. None means
+    // no limit.
+    pub max_active_downloads: Option<usize>,
+    pub max_active_seeds: Option<usize>,
+    // How often running downloads are checked for enough free space at their destination,
+    // and those paused for the lack of it for whether there's room again.
+    pub space_check_interval: Duration,
+    // Applies to every torrent that doesn't set its own goals.
+    pub seeding_goals: SeedingGoals,
+    // How often the session re-evaluates seeding goals and the queue and moves completed
+    // downloads out of the incomplete directory.
+    pub tick_interval: Duration,
+    // Bytes of whole pieces kept in memory for uploads, shared by all torrents; 0 reads
+    // every block from disk.
+    pub read_cache_size: ByteSize,
+    // Events each subscriber may have unread before `event_overflow` kicks in.
+    pub event_capacity: usize,
+    pub event_overflow: OverflowPolicy,
+    // Alerts the session keeps for later; the oldest go first. 0 keeps none.
+    pub alert_capacity: usize,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            listen_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 6881)),
+            announce_port: None,
+            dht_announce_interval: Duration::from_secs(15 * 60),
+            timeouts: PeerTimeouts::default(),
+            block_size: 16 * 1024,
+            pipeline_depth: PipelineDepth::default(),
+            max_active_peers: 30,
+            max_standby_peers: 5,
+            lan_peers_exempt: false,
+            reconnect_interval: Duration::from_secs(60),
+            upload_slots: None,
+            seed_choker: SeedChoker::FastestUpload,
+            choke_interval: Duration::from_secs(10),
+            keep_alive_interval: Duration::from_secs(90),
+            rate_limits: RateLimits::default(),
+            bandwidth_schedule: BandwidthSchedule::default(),
+            dht_port: None,
+            privacy_mode: false,
+            client_preset: ClientPreset::Native,
+            endgame: true,
+            honor_allowed_fast: true,
+            sha1_mode: Sha1Mode::Fast,
+            max_hash_failures: Some(3),
+            forensics_dir: None,
+            incomplete_dir: None,
+            part_suffix: false,
+            verify_md5: false,
+            time_announces: false,
+            tracker_timeout: Duration::from_secs(30),
+            tracker_retry: RetryPolicy {
+                max_attempts: 5,
+                initial_backoff: Duration::from_secs(15),
+                max_backoff: Duration::from_secs(30 * 60),
+                jitter: 0.2,
+            },
+            tracker_filter: TrackerFilter::default(),
+            stopped_announce_timeout: Duration::from_secs(5),
+            dual_stack_announce: true,
+            prefer_family: FamilyPreference::Either,
+            proxy: None,
+            web_seeds: WebSeedPolicy::default(),
+            web_seed_limits: UrlSeedLimits::default(),
+            state_dir: None,
+            dht_node_max_age: Duration::from_secs(24 * 60 * 60),
+            flush_window: Duration::from_secs(30),
+            max_active_downloads: Some(3),
+            max_active_seeds: Some(5),
+            space_check_interval: Duration::from_secs(30),
+            seeding_goals: SeedingGoals::default(),
+            tick_interval: Duration::from_secs(1),
+            read_cache_size: ByteSize(32 * 1024 * 1024),
+            event_capacity: 1024,
+            event_overflow: OverflowPolicy::DropOldest,
+            alert_capacity: 1000,
+        }
+    }
+}
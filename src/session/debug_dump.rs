@@ -0,0 +1,168 @@
+use super::engine::Session;
+use super::error::hex;
+use super::event::SessionEvent;
+use super::resume::unix_time;
+use super::snapshot::PeerState;
+use super::torrent::Torrent;
+use std::fmt::Write;
+use std::time::SystemTime;
+
+// Plain text meant to be attached to bug reports, so it favours completeness over
+// brevity. Times are unix seconds. Writing to a String can't fail, hence the unwraps.
+pub(crate) fn render(session: &Session, recent: &[(SystemTime, SessionEvent)]) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "bittorrent-client {} debug dump",
+        env!("CARGO_PKG_VERSION")
+    )
+    .unwrap();
+    writeln!(out, "time: {}", unix_time(SystemTime::now())).unwrap();
+    writeln!(out, "listening on: {}", session.local_addr()).unwrap();
+    writeln!(
+        out,
+        "peer id: {}",
+        String::from_utf8_lossy(&session.peer_id())
+    )
+    .unwrap();
+    writeln!(out, "unclean shutdown: {}", session.unclean_shutdown()).unwrap();
+    writeln!(out, "rate limits: {:?}", session.rate_limits()).unwrap();
+    writeln!(out, "read cache: {:?}", session.read_cache_stats()).unwrap();
+
+    writeln!(out, "\n== config\n{:#?}", session.config()).unwrap();
+
+    for torrent in session.torrents() {
+        let position = session.queue_position(&torrent.info_hash());
+        write_torrent(&mut out, &torrent, position);
+    }
+
+    writeln!(out, "\n== recent events").unwrap();
+    for (at, event) in recent {
+        writeln!(out, "{} {:?}", unix_time(*at), event).unwrap();
+    }
+    out
+}
+
+fn write_torrent(out: &mut String, torrent: &Torrent, queue_position: Option<usize>) {
+    let meta = torrent.meta();
+    writeln!(
+        out,
+        "\n== torrent {} {}",
+        hex(&torrent.info_hash()),
+        torrent.name()
+    )
+    .unwrap();
+    writeln!(out, "state: {:?}", torrent.state()).unwrap();
+    writeln!(
+        out,
+        "paused: {}, queued: {}, queue position: {:?}",
+        torrent.is_paused(),
+        torrent.is_queued(),
+        queue_position
+    )
+    .unwrap();
+    writeln!(out, "schedule: {:?}", torrent.schedule()).unwrap();
+    writeln!(
+        out,
+        "downloaded: {}, uploaded: {}, left: {}, ratio: {:.3}",
+        torrent.downloaded(),
+        torrent.uploaded(),
+        torrent.left(),
+        torrent.ratio()
+    )
+    .unwrap();
+
+    let picker = torrent.picker();
+    writeln!(
+        out,
+        "picker: {}/{} pieces, {} wanted, strictness {:?}, custom strategy {}, solo seed swarm {}",
+        picker.have().count(),
+        meta.num_pieces(),
+        picker.wanted().count(),
+        picker.strictness(),
+        picker.has_custom_strategy(),
+        picker.is_solo_seed_swarm()
+    )
+    .unwrap();
+    let availability = picker.availability();
+    writeln!(
+        out,
+        "availability: {:.3} distributed copies, {} seeds, rarest piece on {} peers",
+        availability.distributed_copies(),
+        availability.seeds(),
+        availability.counts().iter().min().copied().unwrap_or(0)
+    )
+    .unwrap();
+    drop(picker);
+
+    let mut sources: Vec<_> = torrent.peer_source_counts().into_iter().collect();
+    sources.sort();
+    writeln!(
+        out,
+        "known peers: {}, by source {:?}",
+        torrent.known_peers(),
+        sources
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "peers (flags: c/C we/they choke, i/I we/they are interested):"
+    )
+    .unwrap();
+    for peer in torrent.peer_states() {
+        write_peer(out, &peer);
+    }
+
+    writeln!(out, "history:").unwrap();
+    for entry in torrent.history() {
+        writeln!(out, "  {} {:?}", unix_time(entry.at), entry.change).unwrap();
+    }
+
+    writeln!(out, "trackers:").unwrap();
+    for tracker in torrent.trackers() {
+        writeln!(
+            out,
+            "  {} group {} enabled {} seeders {:?} leechers {:?} interval {:?}",
+            tracker.url,
+            tracker.group,
+            tracker.enabled,
+            tracker.seeders,
+            tracker.leechers,
+            tracker.interval
+        )
+        .unwrap();
+        for record in &tracker.history {
+            writeln!(
+                out,
+                "    {} {:?} {}",
+                unix_time(record.at),
+                record.timings,
+                record.error.as_deref().unwrap_or("ok")
+            )
+            .unwrap();
+        }
+    }
+}
+
+fn write_peer(out: &mut String, peer: &PeerState) {
+    let flags: String = [
+        (peer.am_choking, 'c'),
+        (peer.peer_choking, 'C'),
+        (peer.am_interested, 'i'),
+        (peer.peer_interested, 'I'),
+    ]
+    .iter()
+    .map(|&(set, flag)| if set { flag } else { '.' })
+    .collect();
+    writeln!(
+        out,
+        "  {} {} {:?} {} fast {} pieces {}",
+        peer.addr,
+        String::from_utf8_lossy(&peer.peer_id),
+        peer.slot,
+        flags,
+        peer.fast,
+        peer.pieces
+    )
+    .unwrap();
+}
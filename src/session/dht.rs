@@ -0,0 +1,16 @@
+use std::net::SocketAddr;
+
+// The DHT node running next to the session, as the session drives it. This crate speaks
+// no KRPC itself; whoever runs the node implements this on top of it. Lookups run on a
+// thread of their own per torrent, so the calls may block on the network.
+pub trait DhtClient: Send + Sync {
+    // Called once when the session is given the client, with the node id to use and the
+    // nodes to fill a routing table from.
+    fn bootstrap(&self, node_id: [u8; 20], nodes: &[SocketAddr]);
+
+    // Peers for the torrent, as found by get_peers queries.
+    fn get_peers(&self, info_hash: &[u8; 20]) -> Vec<SocketAddr>;
+
+    // Tells the nodes closest to the torrent that we take connections on `port`.
+    fn announce_peer(&self, info_hash: &[u8; 20], port: u16);
+}
@@ -0,0 +1,166 @@
+use super::node_id::{NodeIdCheck, check_node_id, is_exempt, is_secure_node_id, secure_node_id};
+use super::resume::unix_time;
+use crate::bencode::errors::BencodeError;
+use crate::bencode::parser::parse_value;
+use crate::bencode::value::BencodeValue;
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+// What a DHT node next to the session needs to skip bootstrapping from scratch: the node
+// id it used last time and the nodes it knew about, with when each was last heard of.
+// This crate has no routing table of its own; the nodes are the ones peers announced in
+// Port messages, plus whatever the DHT node reports through Session::add_dht_nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DhtState {
+    pub node_id: [u8; 20],
+    // Seconds since the Unix epoch, like every time below.
+    pub saved_at: u64,
+    pub nodes: Vec<DhtNode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DhtNode {
+    pub addr: SocketAddr,
+    pub last_seen: u64,
+    // The node's id, if the DHT node reported it; Port messages don't carry one.
+    pub id: Option<[u8; 20]>,
+}
+
+impl DhtNode {
+    pub fn id_check(&self) -> NodeIdCheck {
+        check_node_id(self.id.as_ref(), self.addr.ip())
+    }
+}
+
+impl DhtState {
+    // A fresh random node id and no nodes.
+    pub fn new() -> DhtState {
+        DhtState {
+            node_id: rand::rng().random(),
+            saved_at: unix_time(SystemTime::now()),
+            nodes: Vec::new(),
+        }
+    }
+
+    // Drops nodes not heard of within `max_age` of `now`. The node id is kept however old
+    // the state: other nodes may still have us in their tables under it.
+    pub fn prune(&mut self, now: SystemTime, max_age: Duration) {
+        let cutoff = unix_time(now).saturating_sub(max_age.as_secs());
+        self.nodes.retain(|node| node.last_seen >= cutoff);
+    }
+
+    // Adds or refreshes nodes, keeping the later sighting and the latest known id for
+    // each address. Nodes whose ids pass BEP 42 come first, those that fail it last; each
+    // group most recently seen first.
+    pub fn merge(&mut self, nodes: impl IntoIterator<Item = DhtNode>) {
+        let mut by_addr: HashMap<SocketAddr, DhtNode> =
+            (self.nodes.iter()).map(|node| (node.addr, *node)).collect();
+        for node in nodes {
+            let known = by_addr.entry(node.addr).or_insert(node);
+            if node.last_seen >= known.last_seen {
+                known.last_seen = node.last_seen;
+                known.id = node.id.or(known.id);
+            } else {
+                known.id = known.id.or(node.id);
+            }
+        }
+        self.nodes = by_addr.into_values().collect();
+        self.nodes.sort_by_key(|node| {
+            let trust = match node.id_check() {
+                NodeIdCheck::Valid | NodeIdCheck::Exempt => 0,
+                NodeIdCheck::Unknown => 1,
+                NodeIdCheck::Invalid => 2,
+            };
+            (trust, std::cmp::Reverse(node.last_seen), node.addr)
+        });
+    }
+
+    // A new BEP 42 id for `ip`, unless the current one already fits it.
+    pub fn secure_for(&mut self, ip: IpAddr) -> bool {
+        if is_exempt(ip) || is_secure_node_id(&self.node_id, ip) {
+            return false;
+        }
+        self.node_id = secure_node_id(ip);
+        true
+    }
+
+    pub fn to_bencode(&self) -> BencodeValue {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut entry = vec![
+                    BencodeValue::String(node.addr.to_string()),
+                    BencodeValue::Integer(node.last_seen as i64),
+                ];
+                entry.extend(node.id.map(|id| BencodeValue::Bytes(id.to_vec())));
+                BencodeValue::List(entry)
+            })
+            .collect();
+        let mut dict = HashMap::new();
+        dict.insert(
+            "node id".to_string(),
+            BencodeValue::Bytes(self.node_id.to_vec()),
+        );
+        dict.insert(
+            "saved".to_string(),
+            BencodeValue::Integer(self.saved_at as i64),
+        );
+        dict.insert("nodes".to_string(), BencodeValue::List(nodes));
+        BencodeValue::Dictionary(dict)
+    }
+
+    pub fn from_bencode(value: &BencodeValue) -> Result<DhtState, BencodeError> {
+        let node_id = value
+            .bytes("node id")?
+            .try_into()
+            .map_err(|_| BencodeError::InvalidString("node id must be 20 bytes".into(), None))?;
+        let mut nodes = Vec::new();
+        for entry in value.list("nodes")?.as_list()? {
+            let addr = entry.index(0)?.as_string()?;
+            let addr = addr.parse().map_err(|_| {
+                BencodeError::InvalidString(format!("Bad node address {}", addr), None)
+            })?;
+            let last_seen = *entry.index(1)?.as_int()?;
+            let id = match entry.index(2) {
+                Ok(BencodeValue::Bytes(id)) => id.as_slice().try_into().ok(),
+                _ => None,
+            };
+            nodes.push(DhtNode {
+                addr,
+                last_seen: last_seen.max(0) as u64,
+                id,
+            });
+        }
+        Ok(DhtState {
+            node_id,
+            saved_at: value.int("saved")?.max(0) as u64,
+            nodes,
+        })
+    }
+
+    // Unreadable or corrupt files count as missing.
+    pub fn load(path: &Path) -> Option<DhtState> {
+        let data = fs::read(path).ok()?;
+        let (value, _) = parse_value(&data).ok()?;
+        DhtState::from_bencode(&value).ok()
+    }
+
+    // Through a temporary file, as with resume data.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temp = path.with_extension("dht.tmp");
+        fs::write(&temp, self.to_bencode().encode())?;
+        fs::rename(&temp, path)
+    }
+}
+
+impl Default for DhtState {
+    fn default() -> DhtState {
+        DhtState::new()
+    }
+}
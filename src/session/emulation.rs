@@ -0,0 +1,113 @@
+use crate::tracker::value::TrackerRequest;
+use rand::Rng;
+use std::time::Duration;
+
+// Wire-level behaviour of a mainstream client, for testing against trackers and peers that
+// fingerprint clients. Native is this client as it normally behaves.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClientPreset {
+    #[default]
+    Native,
+    QBittorrent,
+    Transmission,
+    Deluge,
+    UTorrent,
+}
+
+// How a client fills the twelve bytes after its peer id prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PeerIdSuffix {
+    RandomBytes,
+    // Digits and lowercase letters only.
+    Alphanumeric,
+}
+
+struct ClientProfile {
+    peer_id_prefix: &'static [u8; 8],
+    suffix: PeerIdSuffix,
+    client_name: &'static str,
+    // Requests the client lets a peer queue with it, advertised as "reqq".
+    reqq: u32,
+    keep_alive_interval: Duration,
+    lazy_bitfield: bool,
+}
+
+impl ClientPreset {
+    fn profile(self) -> Option<ClientProfile> {
+        let profile = match self {
+            ClientPreset::Native => return None,
+            ClientPreset::QBittorrent => ClientProfile {
+                peer_id_prefix: b"-qB4630-",
+                suffix: PeerIdSuffix::Alphanumeric,
+                client_name: "qBittorrent/4.6.3",
+                reqq: 500,
+                keep_alive_interval: Duration::from_secs(120),
+                lazy_bitfield: false,
+            },
+            ClientPreset::Transmission => ClientProfile {
+                peer_id_prefix: b"-TR4050-",
+                suffix: PeerIdSuffix::Alphanumeric,
+                client_name: "Transmission 4.0.5",
+                reqq: 512,
+                keep_alive_interval: Duration::from_secs(100),
+                lazy_bitfield: false,
+            },
+            ClientPreset::Deluge => ClientProfile {
+                peer_id_prefix: b"-DE211s-",
+                suffix: PeerIdSuffix::Alphanumeric,
+                client_name: "Deluge 2.1.1",
+                reqq: 500,
+                keep_alive_interval: Duration::from_secs(120),
+                lazy_bitfield: false,
+            },
+            ClientPreset::UTorrent => ClientProfile {
+                peer_id_prefix: b"-UT355S-",
+                suffix: PeerIdSuffix::RandomBytes,
+                client_name: "\u{b5}Torrent 3.5.5",
+                reqq: 255,
+                keep_alive_interval: Duration::from_secs(120),
+                lazy_bitfield: true,
+            },
+        };
+        Some(profile)
+    }
+
+    pub fn generate_peer_id(self) -> [u8; 20] {
+        let Some(profile) = self.profile() else {
+            return TrackerRequest::generate_peer_id();
+        };
+        const ALPHANUMERIC: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        let mut rng = rand::rng();
+        let mut peer_id = [0u8; 20];
+        peer_id[..8].copy_from_slice(profile.peer_id_prefix);
+        for byte in &mut peer_id[8..] {
+            *byte = match profile.suffix {
+                PeerIdSuffix::RandomBytes => rng.random(),
+                PeerIdSuffix::Alphanumeric => ALPHANUMERIC[rng.random_range(0..ALPHANUMERIC.len())],
+            };
+        }
+        peer_id
+    }
+
+    // The "v" field of the extended handshake.
+    pub fn client_name(self) -> String {
+        self.profile().map_or_else(
+            || concat!("bittorrent-client ", env!("CARGO_PKG_VERSION")).to_string(),
+            |profile| profile.client_name.to_string(),
+        )
+    }
+
+    // Native leaves "reqq" out of the extended handshake.
+    pub fn reqq(self) -> Option<u32> {
+        self.profile().map(|profile| profile.reqq)
+    }
+
+    // Native uses SessionConfig::keep_alive_interval.
+    pub fn keep_alive_interval(self) -> Option<Duration> {
+        self.profile().map(|profile| profile.keep_alive_interval)
+    }
+
+    pub fn lazy_bitfield(self) -> bool {
+        self.profile().is_some_and(|profile| profile.lazy_bitfield)
+    }
+}
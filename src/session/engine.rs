@@ -0,0 +1,1382 @@
+use super::alert::{Alert, AlertLog, Severity};
+use super::annotate::{NoAnnotations, PeerAnnotator};
+use super::bandwidth::{Bandwidth, RateLimits, weighted_shares};
+use super::bundle::TorrentBundle;
+use super::config::SessionConfig;
+use super::debug_dump;
+use super::dht::DhtClient;
+use super::dht_state::{DhtNode, DhtState};
+use super::error::{SessionError, hex};
+use super::event::{EventBus, EventReceiver, SessionEvent};
+use super::handle::TorrentHandle;
+use super::history::StateChange;
+use super::lock::SessionLock;
+use super::metrics;
+use super::peer_pool::PeerSource;
+use super::peer_task::run_peer;
+use super::queue::TorrentQueue;
+use super::resume::{ResumeData, unix_time};
+use super::seeding::GoalAction;
+use super::snapshot::{AnnounceRecord, TrackerStatus};
+use super::torrent::Torrent;
+use crate::peer::connection::PeerConnection;
+use crate::peer::extension::ExtendedHandshake;
+use crate::peer::mse;
+use crate::peer::reserved::ReservedBits;
+use crate::peer::socks;
+use crate::peer::transport::PeerStream;
+use crate::peer::value::Handshake;
+use crate::storage::backend::StorageBackend;
+use crate::storage::read_cache::{CacheStats, ReadCache};
+use crate::torrent::value::TorrentMetaInfo;
+use crate::tracker::client::TrackerClient;
+use crate::tracker::error::TrackerError;
+use crate::tracker::filter::is_web_tracker;
+use crate::tracker::value::{AnnounceTimings, Event, ScrapeStats, TrackerRequest, TrackerResponse};
+use crate::units::{ByteSize, Rate};
+use crate::webseed::url_seed::{PieceRun, UrlSeed};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{Span, debug, info, info_span, warn};
+
+const PART_SUFFIX: &str = ".!bt";
+
+// Everything the background threads need. They only keep a Weak to it, so dropping the
+// Session lets them exit.
+pub(crate) struct Shared {
+    config: SessionConfig,
+    peer_id: [u8; 20],
+    // Per-torrent peer ids, used instead of peer_id in privacy mode.
+    torrent_peer_ids: Mutex<HashMap<[u8; 20], [u8; 20]>>,
+    tracker_key: String,
+    local_addr: SocketAddr,
+    torrents: Mutex<HashMap<[u8; 20], Arc<Torrent>>>,
+    // SHA1("req2" + info hash) -> info hash, for routing incoming encrypted connections.
+    obfuscated_hashes: Mutex<HashMap<[u8; 20], [u8; 20]>>,
+    queue: Mutex<TorrentQueue>,
+    // Downloads whose files get checked against their md5sums once complete.
+    md5_pending: Mutex<HashSet<[u8; 20]>>,
+    events: EventBus,
+    alerts: Arc<AlertLog>,
+    // Totals from each torrent's last Progress event.
+    reported_progress: Mutex<HashMap<[u8; 20], (ByteSize, ByteSize)>>,
+    bandwidth: Bandwidth,
+    read_cache: ReadCache,
+    annotator: RwLock<Arc<dyn PeerAnnotator>>,
+    dht_client: RwLock<Option<Arc<dyn DhtClient>>>,
+    // Node id and nodes heard of in earlier sessions or reported by the DHT node since.
+    dht: Mutex<DhtState>,
+    space_checked: Mutex<Instant>,
+    span: Span,
+    // Whether the previous session using the same state directory crashed.
+    unclean_shutdown: bool,
+    // Declared last so it is released only after the final resume data is saved.
+    _lock: Option<SessionLock>,
+}
+
+impl Shared {
+    fn peer_id_for(&self, info_hash: &[u8; 20]) -> [u8; 20] {
+        if !self.config.privacy_mode {
+            return self.peer_id;
+        }
+        *self
+            .torrent_peer_ids
+            .lock()
+            .unwrap()
+            .entry(*info_hash)
+            .or_insert_with(TrackerRequest::generate_anonymous_peer_id)
+    }
+
+    fn advertised_port(&self) -> u16 {
+        self.config.announce_port.unwrap_or(self.local_addr.port())
+    }
+
+    // What our handshakes advertise. DHT only when there is a node to give peers the port
+    // of.
+    fn reserved_bits(&self) -> ReservedBits {
+        let mut reserved = ReservedBits::FAST | ReservedBits::V2 | ReservedBits::EXTENSION_PROTOCOL;
+        if self.config.dht_port.is_some() {
+            reserved.insert(ReservedBits::DHT);
+        }
+        reserved
+    }
+
+    // No extensions are implemented yet, so only the port, client name and queue depth are
+    // filled in.
+    fn extended_handshake(&self) -> ExtendedHandshake {
+        ExtendedHandshake {
+            extensions: HashMap::new(),
+            port: Some(self.advertised_port()),
+            client: (!self.config.privacy_mode).then(|| self.config.client_preset.client_name()),
+            reqq: self.config.client_preset.reqq(),
+        }
+    }
+
+    fn annotator(&self) -> Arc<dyn PeerAnnotator> {
+        Arc::clone(&self.annotator.read().unwrap())
+    }
+
+    fn tracker_request(&self, torrent: &Torrent, event: Option<Event>) -> TrackerRequest {
+        let transferred = torrent.transfer().since_started();
+        TrackerRequest {
+            announce_url: torrent.meta().announce.clone(),
+            info_hash: torrent.info_hash(),
+            peer_id: self.peer_id_for(&torrent.info_hash()),
+            ip: None,
+            port: self.advertised_port(),
+            uploaded: transferred.uploaded.as_u64(),
+            downloaded: transferred.downloaded.as_u64(),
+            left: torrent.left().as_u64(),
+            compact: false,
+            no_peer_id: true,
+            event,
+            numwant: Some(torrent.peers_wanted(&self.config)),
+            key: Some(self.tracker_key.clone()),
+            tracker_id: torrent
+                .trackers()
+                .into_iter()
+                .find(|tracker| tracker.url == torrent.meta().announce)
+                .and_then(|tracker| tracker.tracker_id),
+        }
+    }
+
+    // Stopped announces for every torrent that told its trackers it started, all at once
+    // and each cut off after stopped_announce_timeout, so exiting takes that long at most.
+    fn announce_shutdown(&self) {
+        let timeout = self.config.stopped_announce_timeout;
+        let mut announces = Vec::new();
+        for torrent in self.torrents.lock().unwrap().values() {
+            if !torrent.lifecycle().begin_stop() {
+                continue;
+            }
+            for tracker in torrent.trackers().into_iter().filter(|t| t.enabled) {
+                if self.config.tracker_filter.check(&tracker.url).is_err() {
+                    continue;
+                }
+                let mut request = self.tracker_request(torrent, Some(Event::Stopped));
+                request.announce_url = tracker.url;
+                request.tracker_id = tracker.tracker_id;
+                let proxy = self.config.proxy.clone();
+                announces.push(thread::spawn(move || {
+                    let _ = TrackerClient::query_tracker_within(&request, proxy.as_ref(), timeout);
+                }));
+            }
+            torrent
+                .lifecycle()
+                .on_announced(Some(Event::Stopped), false);
+        }
+        for announce in announces {
+            let _ = announce.join();
+        }
+    }
+
+    fn dht_path(&self) -> Option<PathBuf> {
+        self.config.dht_port?;
+        Some(self.config.state_dir.as_ref()?.join("dht.state"))
+    }
+
+    // The saved DHT state, if it is there and readable, minus nodes gone quiet for too
+    // long. Otherwise a new node id.
+    fn load_dht_state(config: &SessionConfig) -> DhtState {
+        let saved = (config.dht_port.and(config.state_dir.as_ref()))
+            .and_then(|dir| DhtState::load(&dir.join("dht.state")));
+        let Some(mut state) = saved else {
+            return DhtState::new();
+        };
+        state.prune(SystemTime::now(), config.dht_node_max_age);
+        state
+    }
+
+    fn dht_state(&self) -> DhtState {
+        let mut state = self.dht.lock().unwrap().clone();
+        for torrent in self.torrents.lock().unwrap().values() {
+            state.merge(torrent.dht_node_sightings());
+        }
+        state.saved_at = unix_time(SystemTime::now());
+        state
+    }
+
+    fn resume_path(&self, info_hash: &[u8; 20]) -> Option<PathBuf> {
+        let dir = self.config.state_dir.as_ref()?;
+        Some(dir.join(format!("{}.resume", hex(info_hash))))
+    }
+
+    fn emit(&self, event: SessionEvent) {
+        self.alerts.push_event(&event);
+        self.events.emit(event);
+    }
+
+    fn save_resume_data(&self, torrent: &Torrent) {
+        if let Some(path) = self.resume_path(&torrent.info_hash()) {
+            let _ = torrent.resume_data(self.config.flush_window).save(&path);
+        }
+    }
+}
+
+// A clean shutdown: everything is saved and the lock file removed.
+impl Drop for Shared {
+    fn drop(&mut self) {
+        for torrent in self.torrents.lock().unwrap().values() {
+            self.save_resume_data(torrent);
+        }
+        if let Some(path) = self.dht_path() {
+            let _ = self.dht_state().save(&path);
+        }
+        self.announce_shutdown();
+
+        // The accept thread sits in accept() until someone connects. A connection of our
+        // own lets it find the session gone and close the listening socket.
+        let mut wake = self.local_addr;
+        if wake.ip().is_unspecified() {
+            wake.set_ip(match wake {
+                SocketAddr::V4(_) => IpAddr::from(Ipv4Addr::LOCALHOST),
+                SocketAddr::V6(_) => IpAddr::from(Ipv6Addr::LOCALHOST),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&wake, Duration::from_secs(1));
+    }
+}
+
+// Owns the listening socket and every torrent added to it. Each peer connection, incoming
+// or outgoing, runs on its own thread; a tick thread applies the seeding goals and keeps
+// the number of running torrents within the configured limits.
+pub struct Session {
+    shared: Arc<Shared>,
+}
+
+impl Session {
+    pub fn new(config: SessionConfig) -> Result<Session, SessionError> {
+        let listener = TcpListener::bind(config.listen_addr)?;
+        let local_addr = listener.local_addr()?;
+        let tick_interval = config.tick_interval;
+        let (lock, unclean_shutdown) = match &config.state_dir {
+            Some(dir) => {
+                let (lock, stale) = SessionLock::acquire(dir)?;
+                (Some(lock), stale)
+            }
+            None => (None, false),
+        };
+
+        let limits = config
+            .bandwidth_schedule
+            .limits_at(SystemTime::now(), config.rate_limits);
+        let peer_id = config.client_preset.generate_peer_id();
+        let events = EventBus::new(config.event_capacity, config.event_overflow);
+        let read_cache_size = config.read_cache_size.0;
+        let alert_capacity = config.alert_capacity;
+        let dht = Shared::load_dht_state(&config);
+        let shared = Arc::new(Shared {
+            config,
+            peer_id,
+            torrent_peer_ids: Mutex::new(HashMap::new()),
+            tracker_key: TrackerRequest::generate_key(),
+            local_addr,
+            torrents: Mutex::new(HashMap::new()),
+            obfuscated_hashes: Mutex::new(HashMap::new()),
+            queue: Mutex::new(TorrentQueue::default()),
+            md5_pending: Mutex::new(HashSet::new()),
+            events,
+            alerts: Arc::new(AlertLog::new(alert_capacity)),
+            reported_progress: Mutex::new(HashMap::new()),
+            bandwidth: Bandwidth::new(limits),
+            read_cache: ReadCache::new(read_cache_size),
+            annotator: RwLock::new(Arc::new(NoAnnotations)),
+            dht_client: RwLock::new(None),
+            dht: Mutex::new(dht),
+            space_checked: Mutex::new(Instant::now()),
+            span: info_span!("session", addr = %local_addr),
+            unclean_shutdown,
+            _lock: lock,
+        });
+
+        let weak = Arc::downgrade(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let Some(shared) = weak.upgrade() else {
+                    break;
+                };
+                thread::spawn(move || {
+                    if let Err(err) = Self::handle_incoming(stream, &shared) {
+                        shared.span.in_scope(|| {
+                            debug!(error = %err, "incoming connection failed");
+                        });
+                    }
+                });
+            }
+        });
+
+        let weak = Arc::downgrade(&shared);
+        thread::spawn(move || {
+            loop {
+                thread::sleep(tick_interval);
+                let Some(shared) = weak.upgrade() else {
+                    break;
+                };
+                let span = shared.span.clone();
+                span.in_scope(|| Session { shared }.tick());
+            }
+        });
+
+        Ok(Session { shared })
+    }
+
+    // The rate limits in effect right now, as picked by the bandwidth schedule.
+    pub fn rate_limits(&self) -> RateLimits {
+        self.shared.bandwidth.limits()
+    }
+
+    pub fn config(&self) -> &SessionConfig {
+        &self.shared.config
+    }
+
+    pub fn peer_id(&self) -> [u8; 20] {
+        self.shared.peer_id
+    }
+
+    // The peer id peers and trackers of one torrent see.
+    pub fn peer_id_for(&self, info_hash: &[u8; 20]) -> [u8; 20] {
+        self.shared.peer_id_for(info_hash)
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.shared.local_addr
+    }
+
+    // The port we tell trackers and other peers about.
+    pub fn advertised_port(&self) -> u16 {
+        self.shared.advertised_port()
+    }
+
+    // What we send peers that speak the extension protocol.
+    pub fn extended_handshake(&self) -> ExtendedHandshake {
+        self.shared.extended_handshake()
+    }
+
+    pub fn read_cache_stats(&self) -> CacheStats {
+        self.shared.read_cache.stats()
+    }
+
+    // Hands the DHT node to the session, which bootstraps it from the saved state and from
+    // then on looks up and announces its active public torrents through it.
+    pub fn set_dht_client(&self, client: Arc<dyn DhtClient>) {
+        client.bootstrap(self.dht_node_id(), &self.dht_nodes());
+        *self.shared.dht_client.write().unwrap() = Some(client);
+    }
+
+    // Labels connections made from now on with what `annotator` says about the peer's
+    // address; they show up in Torrent::peers(). Existing connections keep their labels.
+    pub fn set_peer_annotator(&self, annotator: Arc<dyn PeerAnnotator>) {
+        *self.shared.annotator.write().unwrap() = annotator;
+    }
+
+    // The id a DHT node next to the session should use, kept across sessions.
+    pub fn dht_node_id(&self) -> [u8; 20] {
+        self.shared.dht.lock().unwrap().node_id
+    }
+
+    // Nodes to bootstrap a DHT routing table from, most recently heard of first: those
+    // saved last time, those our peers announced and those added through add_dht_nodes.
+    pub fn dht_nodes(&self) -> Vec<SocketAddr> {
+        let state = self.shared.dht_state();
+        state.nodes.iter().map(|node| node.addr).collect()
+    }
+
+    // For the DHT node to report nodes it found good, so they're saved for next time.
+    pub fn add_dht_nodes(&self, nodes: impl IntoIterator<Item = SocketAddr>) {
+        let last_seen = unix_time(SystemTime::now());
+        (self.shared.dht.lock().unwrap()).merge(nodes.into_iter().map(|addr| DhtNode {
+            addr,
+            last_seen,
+            id: None,
+        }));
+    }
+
+    // The same for nodes whose ids are known, so they're ranked by BEP 42.
+    pub fn add_dht_nodes_with_ids(&self, nodes: impl IntoIterator<Item = (SocketAddr, [u8; 20])>) {
+        let last_seen = unix_time(SystemTime::now());
+        (self.shared.dht.lock().unwrap()).merge(nodes.into_iter().map(|(addr, id)| DhtNode {
+            addr,
+            last_seen,
+            id: Some(id),
+        }));
+    }
+
+    // Our address as others see it. With a public address the DHT node id is replaced
+    // by one that satisfies BEP 42 for it, unless it already does. Returns whether the id
+    // changed. Trackers reporting our address call this on their own.
+    pub fn set_external_ip(&self, ip: IpAddr) -> bool {
+        self.shared.dht.lock().unwrap().secure_for(ip)
+    }
+
+    // Everything that would be saved on shutdown, as of now.
+    pub fn dht_state(&self) -> DhtState {
+        self.shared.dht_state()
+    }
+
+    // Everything the session knows about itself, as text for a bug report.
+    pub fn debug_dump(&self) -> String {
+        debug_dump::render(self, &self.shared.events.recent())
+    }
+
+    pub fn write_debug_dump(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.debug_dump())
+    }
+
+    pub fn unclean_shutdown(&self) -> bool {
+        self.shared.unclean_shutdown
+    }
+
+    pub fn subscribe(&self) -> EventReceiver {
+        self.shared.events.subscribe()
+    }
+
+    // Recent alerts of at least `severity`, oldest first, whether or not anyone was
+    // subscribed when they happened.
+    pub fn alerts(&self, severity: Severity) -> Vec<Alert> {
+        self.shared.alerts.alerts(severity)
+    }
+
+    // Alerts after the one with id `seen`, for polling.
+    pub fn alerts_since(&self, seen: u64) -> Vec<Alert> {
+        self.shared.alerts.alerts_since(seen)
+    }
+
+    pub fn clear_alerts(&self) {
+        self.shared.alerts.clear();
+    }
+
+    // Adds a torrent whose data lives (or will live) below `save_dir`. Existing data is
+    // checked before the torrent is returned.
+    pub fn add_torrent(
+        &self,
+        meta: TorrentMetaInfo,
+        save_dir: &Path,
+    ) -> Result<Arc<Torrent>, SessionError> {
+        let resume = self
+            .shared
+            .resume_path(&meta.info_hash())
+            .and_then(|path| ResumeData::load(&path));
+        meta.info.check_paths().map_err(SessionError::UnsafePath)?;
+        let torrent = Torrent::new(meta, save_dir, self.shared.config.sha1_mode);
+        self.insert_torrent(torrent, resume, self.shared.unclean_shutdown)
+    }
+
+    // Adds a torrent whose pieces are read from and written to `backend` instead of files.
+    // The backend must be as large as the torrent; what it already holds is checked like
+    // data on disk.
+    pub fn add_torrent_with_backend(
+        &self,
+        meta: TorrentMetaInfo,
+        backend: Box<dyn StorageBackend>,
+    ) -> Result<Arc<Torrent>, SessionError> {
+        let resume = self
+            .shared
+            .resume_path(&meta.info_hash())
+            .and_then(|path| ResumeData::load(&path));
+        let torrent = Torrent::with_backend(meta, backend, self.shared.config.sha1_mode)?;
+        self.insert_torrent(torrent, resume, self.shared.unclean_shutdown)
+    }
+
+    // Writes what another session needs to take over a torrent into `dir`; see
+    // import_torrent.
+    pub fn export_torrent(&self, info_hash: &[u8; 20], dir: &Path) -> Result<(), SessionError> {
+        let torrent = self
+            .torrent(info_hash)
+            .ok_or(SessionError::UnknownTorrent(*info_hash))?;
+        TorrentBundle {
+            meta: torrent.meta().clone(),
+            resume: torrent.resume_data(self.shared.config.flush_window),
+            downloaded: torrent.downloaded().as_u64(),
+            uploaded: torrent.uploaded().as_u64(),
+        }
+        .save(dir)
+    }
+
+    // Adds a torrent exported by export_torrent, with its data (copied over separately)
+    // below `save_dir`. Pieces the bundle lists are trusted as long as their data is there,
+    // and the transfer totals carry on from where they were.
+    pub fn import_torrent(
+        &self,
+        dir: &Path,
+        save_dir: &Path,
+    ) -> Result<Arc<Torrent>, SessionError> {
+        let bundle = TorrentBundle::load(dir)?;
+        let torrent = Torrent::new(bundle.meta, save_dir, self.shared.config.sha1_mode);
+        let torrent = self.insert_torrent(torrent, Some(bundle.resume), false)?;
+        torrent.restore_totals(bundle.downloaded, bundle.uploaded);
+        Ok(torrent)
+    }
+
+    fn insert_torrent(
+        &self,
+        torrent: Torrent,
+        resume: Option<ResumeData>,
+        unclean: bool,
+    ) -> Result<Arc<Torrent>, SessionError> {
+        torrent
+            .meta()
+            .checked_info_hash(self.shared.config.sha1_mode)?;
+        let info_hash = torrent.info_hash();
+
+        if self
+            .shared
+            .torrents
+            .lock()
+            .unwrap()
+            .contains_key(&info_hash)
+        {
+            return Err(SessionError::DuplicateTorrent(info_hash));
+        }
+        torrent.set_alert_log(Arc::clone(&self.shared.alerts));
+        torrent.record(StateChange::Added);
+
+        torrent.set_block_size(self.shared.config.block_size);
+        torrent.set_forensics_dir(self.shared.config.forensics_dir.clone());
+
+        // Data that's already complete at the destination is seeded from there.
+        if let Some(incomplete_dir) = &self.shared.config.incomplete_dir
+            && !torrent.has_custom_backend()
+            && !torrent.storage().is_in_place()
+        {
+            let suffix = self.shared.config.part_suffix.then_some(PART_SUFFIX);
+            torrent.storage().stage(incomplete_dir, suffix);
+        }
+
+        // Resume data saves hashing everything again; after a crash only the pieces that
+        // may not have been flushed are checked.
+        let restored = resume.is_some_and(|resume| {
+            torrent.restore(&resume, unclean, self.shared.config.flush_window)
+        });
+        if !restored {
+            torrent.check_files();
+        }
+        torrent.check_space();
+        self.shared.save_resume_data(&torrent);
+        for url in &torrent.meta().url_list {
+            let limits = self.shared.config.web_seed_limits.clone();
+            if let Ok(seed) = UrlSeed::with_proxy(url, limits, self.shared.config.proxy.as_ref()) {
+                torrent.add_web_seed(seed);
+            }
+        }
+        let torrent = Arc::new(torrent);
+        self.shared
+            .torrents
+            .lock()
+            .unwrap()
+            .insert(info_hash, Arc::clone(&torrent));
+        self.shared
+            .obfuscated_hashes
+            .lock()
+            .unwrap()
+            .insert(mse::req2_hash(&info_hash), info_hash);
+        self.shared.queue.lock().unwrap().push(info_hash);
+        self.update_queue();
+
+        let has_md5sums = !torrent.has_custom_backend()
+            && torrent.storage().files().iter().any(|f| f.md5sum.is_some());
+        if self.shared.config.verify_md5 && has_md5sums && !torrent.is_complete() {
+            self.shared.md5_pending.lock().unwrap().insert(info_hash);
+        }
+
+        self.shared.emit(SessionEvent::TorrentAdded { info_hash });
+        Ok(torrent)
+    }
+
+    // Removing a torrent also pauses it, so its peer tasks shut down. Trackers that had
+    // heard from it get a stopped announce, sent from its own thread so a slow tracker
+    // doesn't hold up the caller. With `delete_data` its files go as well, as far as
+    // FileStorage::remove_all deletes them. A custom storage backend is flushed instead;
+    // its data is left to whoever owns it.
+    pub fn remove_torrent(
+        &self,
+        info_hash: &[u8; 20],
+        delete_data: bool,
+    ) -> Result<Arc<Torrent>, SessionError> {
+        if let Some(path) = self.shared.resume_path(info_hash) {
+            let _ = std::fs::remove_file(path);
+        }
+        self.shared
+            .obfuscated_hashes
+            .lock()
+            .unwrap()
+            .remove(&mse::req2_hash(info_hash));
+        self.shared.queue.lock().unwrap().remove(info_hash);
+        self.shared.md5_pending.lock().unwrap().remove(info_hash);
+        self.shared.read_cache.remove_torrent(info_hash);
+        (self.shared.reported_progress.lock().unwrap()).remove(info_hash);
+        self.shared
+            .torrent_peer_ids
+            .lock()
+            .unwrap()
+            .remove(info_hash);
+        let torrent = (self.shared.torrents.lock().unwrap())
+            .remove(info_hash)
+            .ok_or(SessionError::UnknownTorrent(*info_hash))?;
+
+        torrent.pause();
+        self.update_queue();
+        self.announce_stopped(&torrent);
+
+        let custom = torrent.has_custom_backend();
+        let deleted = match (custom, delete_data) {
+            (true, _) => torrent.backend().flush(),
+            (false, true) => torrent.storage().remove_all(),
+            (false, false) => Ok(()),
+        };
+        self.shared.emit(SessionEvent::TorrentRemoved {
+            info_hash: *info_hash,
+            data_deleted: delete_data && !custom && deleted.is_ok(),
+        });
+        deleted?;
+        Ok(torrent)
+    }
+
+    // Finds the torrent an MSE-encrypted incoming peer asked for by its unmasked
+    // HASH('req2', info hash).
+    pub fn torrent_by_obfuscated_hash(&self, req2_hash: &[u8; 20]) -> Option<Arc<Torrent>> {
+        let info_hash = *self
+            .shared
+            .obfuscated_hashes
+            .lock()
+            .unwrap()
+            .get(req2_hash)?;
+        self.torrent(&info_hash)
+    }
+
+    pub fn torrent(&self, info_hash: &[u8; 20]) -> Option<Arc<Torrent>> {
+        self.shared.torrents.lock().unwrap().get(info_hash).cloned()
+    }
+
+    pub fn handle(&self, info_hash: &[u8; 20]) -> Option<TorrentHandle> {
+        let torrent = self.torrent(info_hash)?;
+        Some(TorrentHandle::new(torrent, Arc::downgrade(&self.shared)))
+    }
+
+    pub fn handles(&self) -> Vec<TorrentHandle> {
+        self.torrents()
+            .into_iter()
+            .map(|torrent| TorrentHandle::new(torrent, Arc::downgrade(&self.shared)))
+            .collect()
+    }
+
+    // The session behind a handle, while it's still around.
+    pub(crate) fn from_weak(shared: &Weak<Shared>) -> Option<Session> {
+        shared.upgrade().map(|shared| Session { shared })
+    }
+
+    pub fn torrents(&self) -> Vec<Arc<Torrent>> {
+        let torrents = self.shared.torrents.lock().unwrap();
+        torrents.values().cloned().collect()
+    }
+
+    pub fn queue_position(&self, info_hash: &[u8; 20]) -> Option<usize> {
+        self.shared.queue.lock().unwrap().position(info_hash)
+    }
+
+    // Moves a torrent within the queue (0 = front) and starts or queues torrents to match.
+    pub fn set_queue_position(&self, info_hash: &[u8; 20], position: usize) -> bool {
+        let moved = self
+            .shared
+            .queue
+            .lock()
+            .unwrap()
+            .move_to(info_hash, position);
+        if moved {
+            self.update_queue();
+        }
+        moved
+    }
+
+    // Walks the queue front to back and lets torrents run until the download and seed
+    // limits are used up. Paused torrents don't take a slot, so pausing or finishing one
+    // promotes the next in line.
+    pub fn update_queue(&self) {
+        let config = &self.shared.config;
+        let queue = self.shared.queue.lock().unwrap();
+        let torrents = self.shared.torrents.lock().unwrap();
+        let mut downloads = 0;
+        let mut seeds = 0;
+
+        for torrent in queue.iter().filter_map(|hash| torrents.get(hash)) {
+            if torrent.is_paused() {
+                continue;
+            }
+            let (running, limit) = if torrent.is_complete() {
+                (&mut seeds, config.max_active_seeds)
+            } else {
+                (&mut downloads, config.max_active_downloads)
+            };
+
+            let queued = limit.is_some_and(|limit| *running >= limit);
+            if !queued {
+                *running += 1;
+            }
+            torrent.set_queued(queued);
+        }
+    }
+
+    // Connects to a peer for the given torrent in the background.
+    // Dials the peer right away, however recently it was last tried.
+    pub fn add_peer(&self, info_hash: &[u8; 20], addr: SocketAddr) -> Result<(), SessionError> {
+        let torrent = self
+            .torrent(info_hash)
+            .ok_or(SessionError::UnknownTorrent(*info_hash))?;
+        let mut pool = torrent.peer_pool();
+        pool.add(addr, PeerSource::Manual);
+        pool.try_dial(addr, Instant::now(), Duration::ZERO);
+        drop(pool);
+        self.dial(torrent, addr);
+        Ok(())
+    }
+
+    // For peers from trackers, DHT, PEX or LSD. The peer goes into the torrent's pool and
+    // is dialed unless it's connected already or was tried within reconnect_interval.
+    // Returns whether it was dialed.
+    pub fn add_discovered_peer(
+        &self,
+        info_hash: &[u8; 20],
+        addr: SocketAddr,
+        source: PeerSource,
+    ) -> Result<bool, SessionError> {
+        let torrent = self
+            .torrent(info_hash)
+            .ok_or(SessionError::UnknownTorrent(*info_hash))?;
+        let connected = torrent.peer_states().iter().any(|peer| peer.addr == addr);
+        let mut pool = torrent.peer_pool();
+        pool.add(addr, source);
+        let dial = !connected
+            && pool.try_dial(addr, Instant::now(), self.shared.config.reconnect_interval);
+        drop(pool);
+        if dial {
+            self.dial(torrent, addr);
+        }
+        Ok(dial)
+    }
+
+    // Runs a peer connection over `stream` rather than a socket the session opens, e.g. an
+    // in-memory pipe to a simulated peer. We handshake first, as when dialing; `addr` is
+    // what the peer is known by from then on.
+    pub fn add_peer_stream(
+        &self,
+        info_hash: &[u8; 20],
+        addr: SocketAddr,
+        stream: impl PeerStream + 'static,
+    ) -> Result<(), SessionError> {
+        let torrent = self
+            .torrent(info_hash)
+            .ok_or(SessionError::UnknownTorrent(*info_hash))?;
+        torrent.peer_pool().add(addr, PeerSource::Manual);
+        let shared = Arc::clone(&self.shared);
+
+        thread::spawn(move || {
+            if let Err(err) = Self::run_outgoing(addr, stream, &torrent, &shared) {
+                debug!(parent: torrent.span(), %addr, error = %err, "peer stream closed");
+            }
+        });
+        Ok(())
+    }
+
+    fn dial(&self, torrent: Arc<Torrent>, addr: SocketAddr) {
+        let shared = Arc::clone(&self.shared);
+
+        thread::spawn(move || {
+            if let Err(err) = Self::handle_outgoing(addr, &torrent, &shared) {
+                debug!(parent: torrent.span(), %addr, error = %err, "couldn't connect");
+            }
+        });
+    }
+
+    pub fn tracker_request(&self, torrent: &Torrent, event: Option<Event>) -> TrackerRequest {
+        self.shared.tracker_request(torrent, event)
+    }
+
+    // Announces the torrent to every tracker in an enabled group and connects to the peers
+    // they hand out. Returns the first successful response, or the last error if every
+    // tracker failed. Without an event, the torrent's lifecycle picks one: started on the
+    // first announce, completed once after the download finishes.
+    pub fn announce(
+        &self,
+        torrent: &Torrent,
+        event: Option<Event>,
+    ) -> Result<TrackerResponse, Box<dyn std::error::Error>> {
+        let event = event.or_else(|| torrent.lifecycle().next_event());
+        if event == Some(Event::Started) {
+            torrent.transfer().mark_started();
+        }
+        let result = self.announce_event(torrent, event);
+        (torrent.lifecycle()).on_announced(event, result.is_ok());
+        result
+    }
+
+    fn announce_event(
+        &self,
+        torrent: &Torrent,
+        event: Option<Event>,
+    ) -> Result<TrackerResponse, Box<dyn std::error::Error>> {
+        let mut first = None;
+        let mut last_error: Box<dyn std::error::Error> = "No enabled trackers".into();
+        let stopping = event == Some(Event::Stopped);
+        for tracker in torrent.trackers().into_iter().filter(|t| t.enabled) {
+            if let Err(err) = self.filter_tracker(torrent, &tracker.url) {
+                last_error = err.into();
+                continue;
+            }
+            // Trackers that failed recently are left alone until their backoff is over.
+            if !stopping && !tracker.breaker.allows(Instant::now()) {
+                let wait = (tracker.breaker.retry_at()).map_or(Duration::ZERO, |at| {
+                    at.saturating_duration_since(Instant::now())
+                });
+                last_error = format!(
+                    "Tracker {} is backing off for {}s",
+                    tracker.url,
+                    wait.as_secs()
+                )
+                .into();
+                continue;
+            }
+            let mut request = self.tracker_request(torrent, event);
+            request.announce_url = tracker.url.clone();
+            request.tracker_id = tracker.tracker_id;
+
+            let config = &self.shared.config;
+            let timeout = if stopping {
+                config.stopped_announce_timeout
+            } else {
+                config.tracker_timeout
+            };
+            // Stopped, proxied and WebTorrent announces skip the extras and just go out once.
+            let plain = stopping || config.proxy.is_some() || is_web_tracker(&tracker.url);
+            let (result, timings) = if config.time_announces && !plain {
+                TrackerClient::query_tracker_timed(&request, timeout)
+            } else {
+                let started = Instant::now();
+                let result = if config.dual_stack_announce && !plain {
+                    TrackerClient::query_tracker_dual_within(&request, timeout)
+                } else {
+                    TrackerClient::query_tracker_within(&request, config.proxy.as_ref(), timeout)
+                };
+                let timings = AnnounceTimings {
+                    total: Some(started.elapsed()),
+                    ..AnnounceTimings::default()
+                };
+                (result, timings)
+            };
+            torrent.record_announce_attempt(
+                &tracker.url,
+                AnnounceRecord {
+                    at: SystemTime::now(),
+                    timings,
+                    error: result.as_ref().err().map(|err| err.to_string()),
+                },
+            );
+            let response = match result {
+                Ok(response) => response,
+                Err(err) => {
+                    torrent.record_tracker_failure(&tracker.url, &config.tracker_retry);
+                    warn!(parent: torrent.span(), url = %tracker.url, error = %err, ?timings, "announce failed");
+                    metrics::announce_error();
+                    last_error = err;
+                    continue;
+                }
+            };
+            info!(
+                parent: torrent.span(),
+                url = %tracker.url,
+                peers = response.peers.len(),
+                "announced"
+            );
+            torrent.record_announce(&tracker.url, &response);
+            if let Some(ip) = response.external_ip {
+                self.set_external_ip(ip);
+            }
+            if let Some(warning) = &response.warning_message {
+                self.shared.alerts.push(
+                    Severity::Warning,
+                    Some(torrent.info_hash()),
+                    format!("Tracker {} warns: {}", tracker.url, warning),
+                );
+            }
+            let mut addrs: Vec<SocketAddr> = response
+                .peers
+                .iter()
+                .map(|peer| SocketAddr::new(peer.ip, peer.port))
+                .collect();
+            self.shared.config.prefer_family.sort(&mut addrs);
+            // Peers handed out in answer to a stopped announce are of no use.
+            if event == Some(Event::Stopped) {
+                addrs.clear();
+            }
+            for addr in addrs {
+                self.add_discovered_peer(&torrent.info_hash(), addr, PeerSource::Tracker)?;
+            }
+            first.get_or_insert(response);
+        }
+        first.ok_or(last_error)
+    }
+
+    // Trackers the session can't or mustn't talk to are marked as such and skipped, without
+    // counting as failures.
+    fn filter_tracker(&self, torrent: &Torrent, url: &str) -> Result<(), TrackerError> {
+        let err = match self.shared.config.tracker_filter.check(url) {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        let status = match err {
+            TrackerError::Blocked(_) => TrackerStatus::Blocked,
+            _ => TrackerStatus::Unsupported,
+        };
+        torrent.set_tracker_status(url, status);
+        debug!(parent: torrent.span(), url, error = %err, "tracker skipped");
+        Err(err)
+    }
+
+    // Scrapes every tracker in an enabled group that supports it; the counts end up in
+    // Torrent::trackers. Returns the first tracker's stats, or the last error.
+    pub fn scrape(&self, torrent: &Torrent) -> Result<ScrapeStats, Box<dyn std::error::Error>> {
+        let mut first = None;
+        let mut last_error: Box<dyn std::error::Error> = "No enabled trackers".into();
+        for tracker in torrent.trackers().into_iter().filter(|t| t.enabled) {
+            if let Err(err) = self.filter_tracker(torrent, &tracker.url) {
+                last_error = err.into();
+                continue;
+            }
+            // WebTorrent trackers' counts come with each announce.
+            if is_web_tracker(&tracker.url) {
+                continue;
+            }
+            let config = &self.shared.config;
+            let result = TrackerClient::scrape_within(
+                &tracker.url,
+                &torrent.info_hash(),
+                config.proxy.as_ref(),
+                config.tracker_timeout,
+            );
+            match result {
+                Ok(stats) => {
+                    torrent.record_scrape(&tracker.url, &stats);
+                    first.get_or_insert(stats);
+                }
+                Err(err) => {
+                    warn!(parent: torrent.span(), url = %tracker.url, error = %err, "scrape failed");
+                    last_error = err;
+                }
+            }
+        }
+        first.ok_or(last_error)
+    }
+
+    // Tells the trackers in the background, if they were told the torrent started.
+    fn announce_stopped(&self, torrent: &Arc<Torrent>) {
+        if !torrent.lifecycle().begin_stop() {
+            return;
+        }
+        let session = Session {
+            shared: Arc::clone(&self.shared),
+        };
+        let torrent = Arc::clone(torrent);
+        thread::spawn(move || {
+            if let Err(err) = session.announce(&torrent, Some(Event::Stopped)) {
+                debug!(parent: torrent.span(), error = %err, "stopped announce failed");
+            }
+        });
+    }
+
+    // Paused and queued torrents, whichever way they got there, say goodbye to their
+    // trackers.
+    fn announce_stops(&self) {
+        for torrent in self.torrents() {
+            if !torrent.is_active() {
+                self.announce_stopped(&torrent);
+            }
+        }
+    }
+
+    // Runs once per tick_interval on the session's tick thread. Public so callers driving
+    // the session themselves can apply these policies right away.
+    pub fn tick(&self) {
+        self.apply_bandwidth_schedule();
+        self.share_bandwidth();
+        self.check_disk_space();
+        self.apply_torrent_schedules();
+        self.rechoke();
+        self.save_resume_data();
+        self.run_web_seeds();
+        self.verify_completed_md5();
+        self.move_completed();
+        self.apply_seeding_goals();
+        self.update_queue();
+        self.announce_stops();
+        self.lookup_dht_peers();
+        self.report_progress();
+    }
+
+    // Private torrents keep to their trackers (BEP 27). Each lookup gets a thread, as the
+    // DHT takes a few round trips to answer.
+    fn lookup_dht_peers(&self) {
+        let Some(client) = self.shared.dht_client.read().unwrap().clone() else {
+            return;
+        };
+        let now = Instant::now();
+        for torrent in self.torrents() {
+            if !torrent.is_active() {
+                torrent.forget_dht_lookup();
+                continue;
+            }
+            if torrent.meta().info.is_private()
+                || !torrent.begin_dht_lookup(now, self.shared.config.dht_announce_interval)
+            {
+                continue;
+            }
+
+            let client = Arc::clone(&client);
+            let session = Session {
+                shared: Arc::clone(&self.shared),
+            };
+            thread::spawn(move || {
+                let info_hash = torrent.info_hash();
+                let peers = client.get_peers(&info_hash);
+                client.announce_peer(&info_hash, session.advertised_port());
+                debug!(parent: torrent.span(), peers = peers.len(), "DHT lookup");
+                for addr in peers {
+                    let _ = session.add_discovered_peer(&info_hash, addr, PeerSource::Dht);
+                }
+            });
+        }
+    }
+
+    fn rechoke(&self) {
+        let config = &self.shared.config;
+        let Some(slots) = config.upload_slots else {
+            return;
+        };
+        let now = Instant::now();
+        for torrent in self.torrents() {
+            let mut choker = torrent.choker();
+            if choker.is_due(now, config.choke_interval) {
+                choker.rechoke(now, slots, torrent.is_complete(), config.seed_choker);
+            }
+        }
+    }
+
+    fn report_progress(&self) {
+        for torrent in self.torrents() {
+            let info_hash = torrent.info_hash();
+            let totals = (torrent.downloaded(), torrent.uploaded());
+            let previous =
+                (self.shared.reported_progress.lock().unwrap()).insert(info_hash, totals);
+            if previous.unwrap_or_default() != totals {
+                self.shared.emit(SessionEvent::Progress {
+                    info_hash,
+                    downloaded: totals.0,
+                    uploaded: totals.1,
+                    progress: torrent.progress(),
+                });
+            }
+        }
+    }
+
+    fn apply_torrent_schedules(&self) {
+        let now = SystemTime::now();
+        for torrent in self.torrents() {
+            for action in torrent.apply_schedule(now) {
+                info!(parent: torrent.span(), ?action, "scheduled");
+                self.shared.emit(SessionEvent::Scheduled {
+                    info_hash: torrent.info_hash(),
+                    action,
+                });
+            }
+        }
+    }
+
+    fn apply_bandwidth_schedule(&self) {
+        let config = &self.shared.config;
+        let limits = config
+            .bandwidth_schedule
+            .limits_at(SystemTime::now(), config.rate_limits);
+        if limits != self.shared.bandwidth.limits() {
+            info!(parent: &self.shared.span, ?limits, "rate limits changed");
+            self.shared.bandwidth.apply(limits);
+        }
+    }
+
+    // Splits the session's rate limits between active torrents by priority and recent use.
+    fn share_bandwidth(&self) {
+        let limits = self.shared.bandwidth.limits();
+        let torrents: Vec<_> = self
+            .torrents()
+            .into_iter()
+            .filter(|torrent| torrent.is_active())
+            .collect();
+        let usage: Vec<(u64, u64)> = torrents
+            .iter()
+            .map(|torrent| torrent.bandwidth().sample_usage())
+            .collect();
+        let split = |rate: Option<Rate>, used: fn(&(u64, u64)) -> u64| -> Vec<Option<Rate>> {
+            let Some(rate) = rate else {
+                return vec![None; torrents.len()];
+            };
+            let demands: Vec<_> = torrents
+                .iter()
+                .zip(&usage)
+                .map(|(torrent, usage)| (torrent.priority(), used(usage)))
+                .collect();
+            weighted_shares(rate.0, &demands)
+                .into_iter()
+                .map(|share| Some(Rate(share)))
+                .collect()
+        };
+        let downloads = split(limits.download, |usage| usage.0);
+        let uploads = split(limits.upload, |usage| usage.1);
+        for ((torrent, download), upload) in torrents.iter().zip(downloads).zip(uploads) {
+            torrent.bandwidth().apply(RateLimits { download, upload });
+        }
+    }
+
+    fn check_disk_space(&self) {
+        let mut checked = self.shared.space_checked.lock().unwrap();
+        if checked.elapsed() < self.shared.config.space_check_interval {
+            return;
+        }
+        *checked = Instant::now();
+        drop(checked);
+
+        for torrent in self.torrents() {
+            if torrent.lacks_space() {
+                // Checks again before starting.
+                torrent.resume();
+            } else if !torrent.is_paused() && !torrent.is_complete() {
+                torrent.check_space();
+            }
+        }
+    }
+
+    fn save_resume_data(&self) {
+        for torrent in self.torrents() {
+            if torrent.take_resume_dirty() {
+                self.shared.save_resume_data(&torrent);
+            }
+        }
+    }
+
+    // Hands each usable web seed the next run of unclaimed pieces, if the policy says the
+    // swarm needs help. Runs download on their own threads.
+    fn run_web_seeds(&self) {
+        for torrent in self.torrents() {
+            let peer_rate = torrent.sample_peer_rate();
+            if !torrent.is_active()
+                || torrent.is_complete()
+                || !torrent.downloads()
+                || !self.shared.config.web_seeds.should_use(peer_rate)
+            {
+                continue;
+            }
+
+            for seed in torrent.web_seeds() {
+                if !seed.is_available() {
+                    continue;
+                }
+                let Some(pieces) = torrent.picker().pick_run(seed.max_run_pieces(&torrent)) else {
+                    break;
+                };
+                let run = PieceRun {
+                    first: pieces.start,
+                    count: pieces.len(),
+                };
+
+                let torrent = Arc::clone(&torrent);
+                let shared = Arc::clone(&self.shared);
+                thread::spawn(move || {
+                    let was_disabled = seed.is_disabled();
+                    let _ = seed.download_run(&torrent, &run);
+                    if !was_disabled && seed.is_disabled() {
+                        shared.emit(SessionEvent::WebSeedDisabled {
+                            info_hash: torrent.info_hash(),
+                            url: seed.url().to_string(),
+                        });
+                    }
+                });
+            }
+        }
+    }
+
+    // Hashing whole files takes a while, so each check runs on its own thread.
+    fn verify_completed_md5(&self) {
+        let completed: Vec<Arc<Torrent>> = {
+            let mut pending = self.shared.md5_pending.lock().unwrap();
+            let completed: Vec<_> = self
+                .torrents()
+                .into_iter()
+                .filter(|t| pending.contains(&t.info_hash()) && t.is_complete())
+                .collect();
+            for torrent in &completed {
+                pending.remove(&torrent.info_hash());
+            }
+            completed
+        };
+
+        for torrent in completed {
+            let shared = Arc::clone(&self.shared);
+            thread::spawn(move || {
+                for path in torrent.check_md5sums() {
+                    shared.emit(SessionEvent::Md5Mismatch {
+                        info_hash: torrent.info_hash(),
+                        path,
+                    });
+                }
+            });
+        }
+    }
+
+    fn move_completed(&self) {
+        for torrent in self.torrents() {
+            let storage = torrent.storage();
+            if torrent.is_paused() || !torrent.is_complete() || !storage.is_staged() {
+                continue;
+            }
+
+            let info_hash = torrent.info_hash();
+            let from = storage.current_root();
+            let event = match storage.finalize() {
+                Ok(()) => {
+                    let to = storage.root().to_path_buf();
+                    torrent.record(StateChange::Moved { to: to.clone() });
+                    SessionEvent::Moved {
+                        info_hash,
+                        from,
+                        to,
+                    }
+                }
+                Err(err) => {
+                    torrent.set_error(format!("Moving the finished download failed: {}", err));
+                    SessionEvent::MoveFailed {
+                        info_hash,
+                        error: err.to_string(),
+                    }
+                }
+            };
+            self.shared.emit(event);
+        }
+    }
+
+    fn apply_seeding_goals(&self) {
+        for torrent in self.torrents() {
+            if !torrent.is_active() {
+                continue;
+            }
+            let (Some(seeding_for), Some(idle_for)) = (torrent.seeding_time(), torrent.idle_time())
+            else {
+                continue;
+            };
+
+            let goals = torrent
+                .seeding_goals()
+                .unwrap_or_else(|| self.shared.config.seeding_goals.clone());
+            let Some(goal) = goals.reached(torrent.ratio(), seeding_for, idle_for) else {
+                continue;
+            };
+
+            let info_hash = torrent.info_hash();
+            self.shared
+                .emit(SessionEvent::SeedingGoalReached { info_hash, goal });
+            match goals.action {
+                GoalAction::Pause => torrent.pause(),
+                GoalAction::Remove => {
+                    let _ = self.remove_torrent(&info_hash, false);
+                }
+            }
+        }
+    }
+
+    fn handle_outgoing(
+        addr: SocketAddr,
+        torrent: &Torrent,
+        shared: &Shared,
+    ) -> Result<(), SessionError> {
+        if !torrent.is_active() {
+            return Ok(());
+        }
+
+        let timeouts = &shared.config.timeouts;
+        let stream = socks::connect(shared.config.proxy.as_ref(), addr, timeouts.connect)?;
+        stream.set_nodelay(true)?;
+        Self::run_outgoing(addr, stream, torrent, shared)
+    }
+
+    fn run_outgoing(
+        addr: SocketAddr,
+        mut stream: impl PeerStream + 'static,
+        torrent: &Torrent,
+        shared: &Shared,
+    ) -> Result<(), SessionError> {
+        if !torrent.is_active() {
+            return Ok(());
+        }
+
+        let timeouts = &shared.config.timeouts;
+        stream.set_read_timeout(Some(timeouts.read))?;
+        stream.set_write_timeout(Some(timeouts.write))?;
+
+        let remote = Handshake::perform_handshake(
+            &mut stream,
+            &torrent.info_hash(),
+            &shared.peer_id_for(&torrent.info_hash()),
+            shared.reserved_bits(),
+            timeouts.handshake,
+        )?;
+
+        let mut connection = PeerConnection::new(addr, stream, remote);
+        run_peer(
+            torrent,
+            &mut connection,
+            &shared.config,
+            &shared.bandwidth,
+            &shared.read_cache,
+            &*shared.annotator(),
+            &shared.extended_handshake(),
+        )
+    }
+
+    fn handle_incoming(mut stream: TcpStream, shared: &Shared) -> Result<(), SessionError> {
+        let timeouts = &shared.config.timeouts;
+        let addr = stream.peer_addr()?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(timeouts.read))?;
+        stream.set_write_timeout(Some(timeouts.write))?;
+
+        // Paused and queued torrents are treated as unknown so the handshake is refused.
+        let torrents = &shared.torrents;
+        let remote = Handshake::accept_handshake(
+            &mut stream,
+            |info_hash| {
+                let torrents = torrents.lock().unwrap();
+                let known = torrents.get(info_hash).is_some_and(|t| t.is_active());
+                known.then(|| shared.peer_id_for(info_hash))
+            },
+            shared.reserved_bits(),
+            timeouts.handshake,
+        )?;
+
+        let torrent = torrents
+            .lock()
+            .unwrap()
+            .get(&remote.info_hash)
+            .cloned()
+            .ok_or(SessionError::UnknownTorrent(remote.info_hash))?;
+
+        let mut connection = PeerConnection::new(addr, stream, remote);
+        connection.incoming = true;
+        run_peer(
+            &torrent,
+            &mut connection,
+            &shared.config,
+            &shared.bandwidth,
+            &shared.read_cache,
+            &*shared.annotator(),
+            &shared.extended_handshake(),
+        )
+    }
+}
@@ -0,0 +1,86 @@
+use crate::peer::error::{PeerHandshakeError, PeerMessageError};
+use crate::piece::hash::CollisionDetected;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum SessionError {
+    IOError(std::io::Error),
+    Handshake(PeerHandshakeError),
+    Message(PeerMessageError),
+    UnknownTorrent([u8; 20]),
+    DuplicateTorrent([u8; 20]),
+    HashCollision,
+    // Download-only mode was asked of a private torrent.
+    PrivateTorrent([u8; 20]),
+    InvalidBundle(String),
+    // A file of the torrent would land outside its save directory.
+    UnsafePath(String),
+    // pid is None when the lock file names no readable owner.
+    Locked { path: PathBuf, pid: Option<u32> },
+    SessionClosed,
+}
+
+pub(crate) fn hex(hash: &[u8; 20]) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IOError(i) => write!(f, "{}", i),
+            Self::Handshake(h) => write!(f, "{}", h),
+            Self::Message(m) => write!(f, "{}", m),
+            Self::UnknownTorrent(hash) => write!(f, "Unknown torrent: {}", hex(hash)),
+            Self::DuplicateTorrent(hash) => write!(f, "Torrent already added: {}", hex(hash)),
+            Self::HashCollision => write!(f, "Info dict is a SHA-1 collision attack"),
+            Self::PrivateTorrent(hash) => {
+                write!(f, "Private torrent has to keep uploading: {}", hex(hash))
+            }
+            Self::InvalidBundle(msg) => write!(f, "Invalid torrent bundle: {}", msg),
+            Self::UnsafePath(msg) => write!(f, "{}", msg),
+            Self::Locked {
+                path,
+                pid: Some(pid),
+            } => write!(
+                f,
+                "State directory is in use by process {} (remove {} if that process is not this client)",
+                pid,
+                path.display()
+            ),
+            Self::Locked { path, pid: None } => write!(
+                f,
+                "State directory is in use (remove {} if no client is running on it)",
+                path.display()
+            ),
+            Self::SessionClosed => write!(f, "The session has been dropped"),
+        }
+    }
+}
+
+impl Error for SessionError {}
+
+impl From<std::io::Error> for SessionError {
+    fn from(err: std::io::Error) -> Self {
+        SessionError::IOError(err)
+    }
+}
+
+impl From<PeerHandshakeError> for SessionError {
+    fn from(err: PeerHandshakeError) -> Self {
+        SessionError::Handshake(err)
+    }
+}
+
+impl From<PeerMessageError> for SessionError {
+    fn from(err: PeerMessageError) -> Self {
+        SessionError::Message(err)
+    }
+}
+
+impl From<CollisionDetected> for SessionError {
+    fn from(_: CollisionDetected) -> Self {
+        SessionError::HashCollision
+    }
+}
@@ -0,0 +1,277 @@
+use super::schedule::ScheduledAction;
+use super::seeding::SeedingGoal;
+use crate::units::ByteSize;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    TorrentAdded {
+        info_hash: [u8; 20],
+    },
+    TorrentRemoved {
+        info_hash: [u8; 20],
+        // Whether its files were deleted along with it.
+        data_deleted: bool,
+    },
+    SeedingGoalReached {
+        info_hash: [u8; 20],
+        goal: SeedingGoal,
+    },
+    // A completed file didn't match the md5sum in its torrent. Pieces passed their SHA-1
+    // check, so the torrent itself is likely wrong, but the file shouldn't be trusted.
+    Md5Mismatch {
+        info_hash: [u8; 20],
+        path: PathBuf,
+    },
+    // The web seed kept sending pieces that failed verification and won't be used again.
+    WebSeedDisabled {
+        info_hash: [u8; 20],
+        url: String,
+    },
+    // A completed download left the incomplete directory.
+    Moved {
+        info_hash: [u8; 20],
+        from: PathBuf,
+        to: PathBuf,
+    },
+    // The torrent is paused afterwards; resuming it retries the move.
+    MoveFailed {
+        info_hash: [u8; 20],
+        error: String,
+    },
+    // The torrent's schedule started or stopped it.
+    Scheduled {
+        info_hash: [u8; 20],
+        action: ScheduledAction,
+    },
+    // Sent by the session's tick when a torrent's totals changed since the last one.
+    Progress {
+        info_hash: [u8; 20],
+        downloaded: ByteSize,
+        uploaded: ByteSize,
+        progress: f64,
+    },
+}
+
+// Events the bus keeps around for debug dumps.
+pub const RECENT_EVENTS: usize = 50;
+
+// What happens when a subscriber has `SessionConfig::event_capacity` events it hasn't read
+// and another one comes in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    // The subscriber loses its oldest unread event.
+    #[default]
+    DropOldest,
+    // Progress events replace the unread one for the same torrent whether or not the queue
+    // is full, so a slow subscriber only sees the latest. Beyond that, as DropOldest.
+    Coalesce,
+    // emit waits until the subscriber catches up, stalling whoever emitted, the session's
+    // tick included. Only for subscribers that are sure to keep reading.
+    Block,
+}
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<SessionEvent>,
+    dropped: u64,
+    receiver_gone: bool,
+    bus_gone: bool,
+}
+
+struct Channel {
+    queue: Mutex<Queue>,
+    // Signalled when an event is queued and when the bus goes away.
+    ready: Condvar,
+    // Signalled when the receiver takes an event out or goes away.
+    space: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+impl Channel {
+    // Returns false once the receiver is gone.
+    fn send(&self, event: SessionEvent) -> bool {
+        let mut queue = self.queue.lock().unwrap();
+        if self.policy == OverflowPolicy::Coalesce
+            && let Some(queued) = queue
+                .events
+                .iter_mut()
+                .find(|queued| supersedes(&event, queued))
+        {
+            *queued = event;
+            return !queue.receiver_gone;
+        }
+
+        while queue.events.len() >= self.capacity && !queue.receiver_gone {
+            if self.policy == OverflowPolicy::Block {
+                queue = self.space.wait(queue).unwrap();
+            } else {
+                queue.events.pop_front();
+                queue.dropped += 1;
+            }
+        }
+        if queue.receiver_gone {
+            return false;
+        }
+        queue.events.push_back(event);
+        self.ready.notify_one();
+        true
+    }
+
+    fn take(&self, mut queue: MutexGuard<'_, Queue>) -> Option<SessionEvent> {
+        let event = queue.events.pop_front()?;
+        self.space.notify_all();
+        Some(event)
+    }
+}
+
+// Whether `event` makes the unread `queued` one redundant.
+fn supersedes(event: &SessionEvent, queued: &SessionEvent) -> bool {
+    matches!(
+        (event, queued),
+        (SessionEvent::Progress { info_hash: a, .. }, SessionEvent::Progress { info_hash: b, .. })
+            if a == b
+    )
+}
+
+// One subscriber's end of the bus. Mirrors std::sync::mpsc::Receiver; receiving fails once
+// the session is gone and everything queued has been read.
+pub struct EventReceiver {
+    channel: Arc<Channel>,
+}
+
+impl EventReceiver {
+    pub fn recv(&self) -> Result<SessionEvent, RecvError> {
+        let mut queue = self.channel.queue.lock().unwrap();
+        while queue.events.is_empty() {
+            if queue.bus_gone {
+                return Err(RecvError);
+            }
+            queue = self.channel.ready.wait(queue).unwrap();
+        }
+        self.channel.take(queue).ok_or(RecvError)
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<SessionEvent, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut queue = self.channel.queue.lock().unwrap();
+        while queue.events.is_empty() {
+            if queue.bus_gone {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            queue = self
+                .channel
+                .ready
+                .wait_timeout(queue, deadline - now)
+                .unwrap()
+                .0;
+        }
+        self.channel.take(queue).ok_or(RecvTimeoutError::Timeout)
+    }
+
+    pub fn try_recv(&self) -> Result<SessionEvent, TryRecvError> {
+        let queue = self.channel.queue.lock().unwrap();
+        let bus_gone = queue.bus_gone;
+        self.channel.take(queue).ok_or(if bus_gone {
+            TryRecvError::Disconnected
+        } else {
+            TryRecvError::Empty
+        })
+    }
+
+    // Everything queued right now, without waiting for more.
+    pub fn try_iter(&self) -> impl Iterator<Item = SessionEvent> + '_ {
+        std::iter::from_fn(|| self.try_recv().ok())
+    }
+
+    // Events this subscriber lost to the overflow policy. Coalesced ones don't count.
+    pub fn dropped(&self) -> u64 {
+        self.channel.queue.lock().unwrap().dropped
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.channel.queue.lock().unwrap().receiver_gone = true;
+        self.channel.space.notify_all();
+    }
+}
+
+// Fans events out to every subscriber, each with a queue of its own. Subscribers that
+// dropped their receiver are forgotten on the next emit.
+pub struct EventBus {
+    subscribers: Mutex<Vec<Arc<Channel>>>,
+    recent: Mutex<VecDeque<(SystemTime, SessionEvent)>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> EventBus {
+        EventBus {
+            subscribers: Mutex::new(Vec::new()),
+            recent: Mutex::new(VecDeque::new()),
+            capacity: capacity.max(1),
+            policy,
+        }
+    }
+
+    pub fn subscribe(&self) -> EventReceiver {
+        let channel = Arc::new(Channel {
+            queue: Mutex::new(Queue::default()),
+            ready: Condvar::new(),
+            space: Condvar::new(),
+            capacity: self.capacity,
+            policy: self.policy,
+        });
+        self.subscribers.lock().unwrap().push(channel.clone());
+        EventReceiver { channel }
+    }
+
+    pub fn emit(&self, event: SessionEvent) {
+        tracing::debug!(?event, "session event");
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == RECENT_EVENTS {
+            recent.pop_front();
+        }
+        recent.push_back((SystemTime::now(), event.clone()));
+        drop(recent);
+
+        // A Block subscriber can keep send() waiting, so the list is copied out rather than
+        // held locked; otherwise subscribing or emitting elsewhere would wait along with it.
+        let subscribers = self.subscribers.lock().unwrap().clone();
+        let dead: Vec<Arc<Channel>> = subscribers
+            .into_iter()
+            .filter(|subscriber| !subscriber.send(event.clone()))
+            .collect();
+        if !dead.is_empty() {
+            self.subscribers
+                .lock()
+                .unwrap()
+                .retain(|subscriber| !dead.iter().any(|gone| Arc::ptr_eq(gone, subscriber)));
+        }
+    }
+
+    // The latest events, oldest first.
+    pub fn recent(&self) -> Vec<(SystemTime, SessionEvent)> {
+        self.recent.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl Drop for EventBus {
+    fn drop(&mut self) {
+        for subscriber in self.subscribers.lock().unwrap().iter() {
+            subscriber.queue.lock().unwrap().bus_gone = true;
+            subscriber.ready.notify_all();
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use super::error::hex;
+use super::resume::unix_time;
+use sha1::{Digest, Sha1};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// A piece that failed its hash check, as written out for offline analysis.
+pub(crate) struct FailedPiece<'a> {
+    pub(crate) info_hash: [u8; 20],
+    pub(crate) index: usize,
+    pub(crate) expected: Option<[u8; 20]>,
+    pub(crate) data: &'a [u8],
+    pub(crate) block_size: u32,
+    // Per block: the peer that sent it and that peer's id, if it's still connected.
+    pub(crate) sources: Vec<(SocketAddr, Option<[u8; 20]>)>,
+}
+
+// Writes the piece's blocks to `<dir>/<info hash>/piece-<index>-<time>/block-<n>.bin` with a
+// manifest.txt next to them listing each block's hash and sender. Comparing the blocks with
+// a good copy tells a lying peer from corruption on our side: blocks that differ and came
+// from one peer point at it, a piece that matches a good copy points at the disk or at how
+// we put it together.
+pub(crate) fn write_failed_piece(dir: &Path, piece: &FailedPiece) -> io::Result<PathBuf> {
+    let now = SystemTime::now();
+    let parent = dir.join(hex(&piece.info_hash));
+    fs::create_dir_all(&parent)?;
+    // The same piece can fail more than once a second.
+    let mut path = parent.join(format!("piece-{}-{}", piece.index, unix_time(now)));
+    let mut attempt = 1;
+    while let Err(err) = fs::create_dir(&path) {
+        if err.kind() != io::ErrorKind::AlreadyExists {
+            return Err(err);
+        }
+        attempt += 1;
+        path = parent.join(format!(
+            "piece-{}-{}-{}",
+            piece.index,
+            unix_time(now),
+            attempt
+        ));
+    }
+
+    let mut manifest = String::new();
+    let sha1 = |data: &[u8]| hex(&Sha1::digest(data).into());
+    writeln!(manifest, "piece: {}", piece.index).unwrap();
+    writeln!(manifest, "size: {}", piece.data.len()).unwrap();
+    if let Some(expected) = piece.expected {
+        writeln!(manifest, "expected sha1: {}", hex(&expected)).unwrap();
+    }
+    writeln!(manifest, "actual sha1: {}", sha1(piece.data)).unwrap();
+    writeln!(manifest, "time: {}", unix_time(now)).unwrap();
+    writeln!(manifest, "blocks (offset length sha1 peer peer-id):").unwrap();
+
+    for (n, block) in piece.data.chunks(piece.block_size as usize).enumerate() {
+        fs::write(path.join(format!("block-{}.bin", n)), block)?;
+        let (peer, peer_id) = match piece.sources.get(n) {
+            Some((addr, Some(id))) => (addr.to_string(), String::from_utf8_lossy(id).into_owned()),
+            Some((addr, None)) => (addr.to_string(), "unknown".to_string()),
+            None => ("unknown".to_string(), "unknown".to_string()),
+        };
+        writeln!(
+            manifest,
+            "{} {} {} {} {}",
+            n * piece.block_size as usize,
+            block.len(),
+            sha1(block),
+            peer,
+            peer_id
+        )
+        .unwrap();
+    }
+    fs::write(path.join("manifest.txt"), manifest)?;
+    Ok(path)
+}
@@ -0,0 +1,159 @@
+use super::bandwidth::TorrentPriority;
+use super::engine::{Session, Shared};
+use super::error::SessionError;
+use super::history::HistoryEntry;
+use super::priority::FilePriority;
+use super::stream::TorrentStream;
+use super::torrent::Torrent;
+use crate::tracker::value::TrackerResponse;
+use crate::units::ByteSize;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Weak};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TorrentState {
+    // Hashing the files already on disk.
+    CheckingFiles,
+    // Fetching the info dict from peers. Torrents are only added from full metainfo so
+    // far, so none are in this state yet.
+    DownloadingMetadata,
+    Downloading,
+    Seeding,
+    // Waiting for a slot in the session's queue.
+    Queued,
+    Paused,
+    // Paused because the destination hasn't room for the rest of the download. The session
+    // resumes the torrent once it has.
+    InsufficientSpace {
+        needed: ByteSize,
+        available: ByteSize,
+    },
+    // Stopped over something like a failed move; resuming tries again.
+    Error(String),
+}
+
+// Which ways a torrent moves data. Switchable at any time; peer connections catch up the
+// next time they hear from their peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransferMode {
+    #[default]
+    Normal,
+    // Serves the pieces we have but never asks peers or web seeds for more.
+    UploadOnly,
+    // Keeps peers choked and refuses their requests. Not allowed for private torrents,
+    // whose trackers count that as hit and run.
+    DownloadOnly,
+}
+
+// What library users hold on to for a torrent: its state and progress, and the controls
+// that need the session as well as the torrent. The handle doesn't keep the session alive;
+// once it's dropped those controls fail with SessionError::SessionClosed.
+#[derive(Clone)]
+pub struct TorrentHandle {
+    torrent: Arc<Torrent>,
+    session: Weak<Shared>,
+}
+
+impl TorrentHandle {
+    pub(crate) fn new(torrent: Arc<Torrent>, session: Weak<Shared>) -> TorrentHandle {
+        TorrentHandle { torrent, session }
+    }
+
+    fn session(&self) -> Result<Session, SessionError> {
+        Session::from_weak(&self.session).ok_or(SessionError::SessionClosed)
+    }
+
+    // Everything not covered by the handle itself.
+    pub fn torrent(&self) -> &Arc<Torrent> {
+        &self.torrent
+    }
+
+    pub fn info_hash(&self) -> [u8; 20] {
+        self.torrent.info_hash()
+    }
+
+    pub fn name(&self) -> &str {
+        self.torrent.name()
+    }
+
+    pub fn state(&self) -> TorrentState {
+        self.torrent.state()
+    }
+
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.torrent.history()
+    }
+
+    // Fraction of the torrent downloaded, from 0.0 to 1.0.
+    pub fn progress(&self) -> f64 {
+        self.torrent.progress()
+    }
+
+    // The torrent's data from the start, as it gets verified.
+    pub fn stream(&self) -> TorrentStream {
+        TorrentStream::new(Arc::clone(&self.torrent))
+    }
+
+    pub fn pause(&self) {
+        self.torrent.pause();
+    }
+
+    pub fn resume(&self) {
+        self.torrent.resume();
+    }
+
+    pub fn set_file_priority(&self, file: usize, priority: FilePriority) -> std::io::Result<()> {
+        self.torrent.set_file_priority(file, priority)
+    }
+
+    pub fn rename_file(&self, index: usize, new_path: &Path) -> std::io::Result<()> {
+        self.torrent.rename_file(index, new_path)
+    }
+
+    pub fn rename_root(&self, new_name: &str) -> std::io::Result<()> {
+        self.torrent.rename_root(new_name)
+    }
+
+    pub fn set_priority(&self, priority: TorrentPriority) {
+        self.torrent.set_priority(priority);
+    }
+
+    pub fn set_transfer_mode(&self, mode: TransferMode) -> Result<(), SessionError> {
+        self.torrent.set_transfer_mode(mode)
+    }
+
+    pub fn force_recheck(&self) {
+        self.torrent.check_files();
+    }
+
+    pub fn recheck_file(&self, index: usize) -> std::io::Result<Vec<usize>> {
+        self.torrent.recheck_file(index)
+    }
+
+    pub fn announce(&self) -> Result<TrackerResponse, Box<dyn std::error::Error>> {
+        self.session()?.announce(&self.torrent, None)
+    }
+
+    pub fn add_peer(&self, addr: SocketAddr) -> Result<(), SessionError> {
+        self.session()?.add_peer(&self.info_hash(), addr)
+    }
+
+    pub fn queue_position(&self) -> Option<usize> {
+        self.session().ok()?.queue_position(&self.info_hash())
+    }
+
+    pub fn set_queue_position(&self, position: usize) -> Result<bool, SessionError> {
+        Ok(self
+            .session()?
+            .set_queue_position(&self.info_hash(), position))
+    }
+
+    // Takes the torrent out of the session. Its files stay on disk unless `delete_data`.
+    pub fn remove(self, delete_data: bool) -> Result<(), SessionError> {
+        let info_hash = self.info_hash();
+        self.session()?
+            .remove_torrent(&info_hash, delete_data)
+            .map(|_| ())
+    }
+}
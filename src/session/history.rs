@@ -0,0 +1,40 @@
+use crate::units::ByteSize;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// Entries a torrent keeps; older ones are dropped.
+pub const TORRENT_HISTORY: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateChange {
+    Added,
+    // Files on disk were hashed; `have` of `pieces` checked out.
+    Checked {
+        have: usize,
+        pieces: usize,
+    },
+    Started,
+    Stopped,
+    // The last piece came in.
+    Completed,
+    TrackerError {
+        url: String,
+        error: String,
+    },
+    Moved {
+        to: PathBuf,
+    },
+    InsufficientSpace {
+        needed: ByteSize,
+        available: ByteSize,
+    },
+    Error(String),
+}
+
+// A torrent's significant state changes with when they happened, oldest first, like the
+// per-torrent log of desktop clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub at: SystemTime,
+    pub change: StateChange,
+}
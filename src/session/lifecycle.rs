@@ -0,0 +1,75 @@
+use crate::tracker::value::Event;
+
+// Which events a torrent's announces still owe its trackers. The first announce says
+// "started"; "completed" goes out once, the first time a download finishes here (never for
+// a torrent that was complete when added); "stopped" follows a "started" when the torrent
+// is paused, queued, removed or the session shuts down. After a stop the next announce
+// starts over.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnnounceLifecycle {
+    started: bool,
+    stopping: bool,
+    completed: Completion,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Completion {
+    #[default]
+    NotDue,
+    Due,
+    Sent,
+}
+
+impl AnnounceLifecycle {
+    // The event for an announce nobody asked a particular event of.
+    pub fn next_event(&self) -> Option<Event> {
+        if !self.started {
+            Some(Event::Started)
+        } else if self.completed == Completion::Due {
+            Some(Event::Completed)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    pub fn completed_sent(&self) -> bool {
+        self.completed == Completion::Sent
+    }
+
+    // The download just finished. Only the first time counts.
+    pub fn on_download_complete(&mut self) {
+        if self.completed == Completion::NotDue {
+            self.completed = Completion::Due;
+        }
+    }
+
+    // Records an announce. Stopped counts even if no tracker answered: there won't be
+    // another try.
+    pub fn on_announced(&mut self, event: Option<Event>, succeeded: bool) {
+        match event {
+            Some(Event::Stopped) => {
+                self.started = false;
+                self.stopping = false;
+            }
+            _ if !succeeded => {}
+            Some(Event::Started) => self.started = true,
+            Some(Event::Completed) => {
+                self.started = true;
+                self.completed = Completion::Sent;
+            }
+            None => {}
+        }
+    }
+
+    // Whether a stopped announce should go out now. True once per start, so the paths
+    // that stop a torrent don't send it twice.
+    pub fn begin_stop(&mut self) -> bool {
+        let send = self.started && !self.stopping;
+        self.stopping |= send;
+        send
+    }
+}
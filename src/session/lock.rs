@@ -0,0 +1,121 @@
+use super::error::SessionError;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Marks a state directory as in use. The file is removed again when the lock is dropped,
+// so finding one at startup means either another client is running on the same
+// directory, or the previous session never shut down cleanly (crash, kill -9, power
+// loss) and data written shortly before may not have reached the disk. The PID written
+// into the file tells the two apart.
+#[derive(Debug)]
+pub struct SessionLock {
+    path: PathBuf,
+}
+
+impl SessionLock {
+    // Returns the lock and whether a stale one was found. Fails with SessionError::Locked
+    // if the process named in an existing lock file is still alive, or if the file names
+    // no process at all.
+    pub fn acquire(state_dir: &Path) -> Result<(SessionLock, bool), SessionError> {
+        fs::create_dir_all(state_dir)?;
+        let path = state_dir.join("session.lock");
+        let mut stale = false;
+
+        // The PID goes into a file of our own first and is linked into place whole, so
+        // nobody ever sees a lock file without its owner.
+        static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+        let attempt = format!(
+            "session.lock.{}.{}",
+            std::process::id(),
+            ATTEMPTS.fetch_add(1, Ordering::Relaxed)
+        );
+        let pending = state_dir.join(&attempt);
+        let mut file = fs::File::create(&pending)?;
+        writeln!(file, "{}", std::process::id())?;
+        file.sync_all()?;
+        drop(file);
+
+        let result = loop {
+            match fs::hard_link(&pending, &path) {
+                Ok(()) => break Ok((SessionLock { path }, stale)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => break Err(e.into()),
+            }
+
+            // A lock we can't read is someone else's still being written, or one we
+            // can't judge; either way it isn't ours to break.
+            let Some(pid) = Self::owner(&path) else {
+                break Err(SessionError::Locked { path, pid: None });
+            };
+            if process_alive(pid) {
+                break Err(SessionError::Locked {
+                    path,
+                    pid: Some(pid),
+                });
+            }
+            stale = true;
+            // Another acquirer may have broken the same stale lock and put its own in place
+            // since we read it. Moving the file aside first lets us check which one we got
+            // before throwing it away.
+            let aside = state_dir.join(format!("{}.stale", attempt));
+            match fs::rename(&path, &aside) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => break Err(e.into()),
+            }
+            if Self::owner(&aside) != Some(pid) {
+                let _ = fs::hard_link(&aside, &path);
+            }
+            let _ = fs::remove_file(&aside);
+        };
+        let _ = fs::remove_file(&pending);
+        result
+    }
+
+    // The PID recorded in a lock file, if it can be read.
+    pub fn owner(path: &Path) -> Option<u32> {
+        let mut contents = String::new();
+        fs::File::open(path)
+            .ok()?
+            .read_to_string(&mut contents)
+            .ok()?;
+        contents.trim().parse().ok()
+    }
+
+    #[cfg(feature = "sim")]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Our own PID counts as alive: another Session in this process holds the directory.
+// Where liveness can't be determined the lock is treated as stale, as before.
+fn process_alive(pid: u32) -> bool {
+    pid == std::process::id() || signalable(pid)
+}
+
+#[cfg(unix)]
+fn signalable(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks that the process exists. EPERM means it does but belongs to
+    // another user.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn signalable(_pid: u32) -> bool {
+    false
+}
@@ -0,0 +1,61 @@
+// Session-wide counters and gauges for running the client as a daemon. They go through
+// the `metrics` facade when the "metrics" feature is enabled, so whichever recorder the
+// application installs (metrics-exporter-prometheus serves them as Prometheus text) picks
+// them up. Without the feature every call here compiles to nothing.
+
+pub const DOWNLOADED_BYTES: &str = "bittorrent_downloaded_bytes_total";
+pub const UPLOADED_BYTES: &str = "bittorrent_uploaded_bytes_total";
+pub const PIECES_VERIFIED: &str = "bittorrent_pieces_verified_total";
+pub const HASH_FAILURES: &str = "bittorrent_hash_failures_total";
+pub const ANNOUNCE_ERRORS: &str = "bittorrent_announce_errors_total";
+pub const CONNECTED_PEERS: &str = "bittorrent_connected_peers";
+
+pub(crate) fn add_downloaded(bytes: u64) {
+    counter(DOWNLOADED_BYTES, bytes);
+}
+
+pub(crate) fn add_uploaded(bytes: u64) {
+    counter(UPLOADED_BYTES, bytes);
+}
+
+pub(crate) fn piece_verified() {
+    counter(PIECES_VERIFIED, 1);
+}
+
+pub(crate) fn hash_failure() {
+    counter(HASH_FAILURES, 1);
+}
+
+pub(crate) fn announce_error() {
+    counter(ANNOUNCE_ERRORS, 1);
+}
+
+// Counts a peer connection as connected for as long as the guard lives.
+pub(crate) struct ConnectedPeer(());
+
+impl ConnectedPeer {
+    pub(crate) fn new() -> ConnectedPeer {
+        gauge(CONNECTED_PEERS, 1.0);
+        ConnectedPeer(())
+    }
+}
+
+impl Drop for ConnectedPeer {
+    fn drop(&mut self) {
+        gauge(CONNECTED_PEERS, -1.0);
+    }
+}
+
+fn counter(name: &'static str, value: u64) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(name).increment(value);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (name, value);
+}
+
+fn gauge(name: &'static str, delta: f64) {
+    #[cfg(feature = "metrics")]
+    metrics::gauge!(name).increment(delta);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (name, delta);
+}
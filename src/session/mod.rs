@@ -0,0 +1,34 @@
+pub mod alert;
+pub mod annotate;
+pub mod bandwidth;
+pub mod bundle;
+pub mod choker;
+pub mod config;
+mod debug_dump;
+pub mod dht;
+pub mod dht_state;
+pub mod emulation;
+pub mod engine;
+pub mod error;
+pub mod event;
+mod forensics;
+pub mod handle;
+pub mod history;
+pub mod lifecycle;
+pub(crate) mod lock;
+pub mod metrics;
+pub mod node_id;
+pub mod peer_pool;
+mod peer_task;
+pub mod pipeline;
+pub mod priority;
+pub(crate) mod quarantine;
+pub(crate) mod queue;
+pub mod resume;
+pub mod schedule;
+pub mod seeding;
+pub mod slots;
+pub mod snapshot;
+pub mod stream;
+pub mod torrent;
+pub mod transfer;
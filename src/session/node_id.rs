@@ -0,0 +1,91 @@
+use rand::Rng;
+use std::net::IpAddr;
+
+// BEP 42: a DHT node id starts with 21 bits derived from the node's external address, so
+// a node can't pick its place in the keyspace (and sit next to the torrents it wants to
+// poison) without controlling addresses there. The last byte holds the random number the
+// prefix was derived with.
+
+const V4_MASK: [u8; 4] = [0x03, 0x0f, 0x3f, 0xff];
+const V6_MASK: [u8; 8] = [0x01, 0x03, 0x07, 0x0f, 0x1f, 0x3f, 0x7f, 0xff];
+
+// What BEP 42 says about a remote node's id, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NodeIdCheck {
+    // The prefix matches the address.
+    Valid,
+    // Local and private addresses are exempt from the rule.
+    Exempt,
+    // We haven't seen the node's id.
+    Unknown,
+    // The id doesn't belong to the address: unlikely to be routed to properly, possibly
+    // an attacker.
+    Invalid,
+}
+
+impl NodeIdCheck {
+    // Whether the node is fit for a routing table.
+    pub fn is_acceptable(self) -> bool {
+        self != NodeIdCheck::Invalid
+    }
+}
+
+// A new id for a node reachable at `ip`.
+pub fn secure_node_id(ip: IpAddr) -> [u8; 20] {
+    let mut rng = rand::rng();
+    let mut id: [u8; 20] = rng.random();
+    let prefix = id_prefix(ip, id[19]);
+    id[0] = prefix[0];
+    id[1] = prefix[1];
+    id[2] = (prefix[2] & 0xf8) | (id[2] & 0x07);
+    id
+}
+
+pub fn is_secure_node_id(id: &[u8; 20], ip: IpAddr) -> bool {
+    let prefix = id_prefix(ip, id[19]);
+    id[0] == prefix[0] && id[1] == prefix[1] && id[2] & 0xf8 == prefix[2] & 0xf8
+}
+
+pub fn check_node_id(id: Option<&[u8; 20]>, ip: IpAddr) -> NodeIdCheck {
+    if is_exempt(ip) {
+        return NodeIdCheck::Exempt;
+    }
+    match id {
+        None => NodeIdCheck::Unknown,
+        Some(id) if is_secure_node_id(id, ip) => NodeIdCheck::Valid,
+        Some(_) => NodeIdCheck::Invalid,
+    }
+}
+
+// Loopback, private and link-local addresses, whose owners can't be told apart anyway.
+pub fn is_exempt(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        IpAddr::V6(ip) => ip.is_loopback() || ip.is_unique_local() || ip.is_unicast_link_local(),
+    }
+}
+
+// CRC32-C of the masked address with the low three bits of `rand` in its top bits.
+fn id_prefix(ip: IpAddr, rand: u8) -> [u8; 4] {
+    let r = rand & 0x07;
+    let crc = match ip {
+        IpAddr::V4(ip) => {
+            let mut bytes = ip.octets();
+            for (byte, mask) in bytes.iter_mut().zip(V4_MASK) {
+                *byte &= mask;
+            }
+            bytes[0] |= r << 5;
+            crc32c::crc32c(&bytes)
+        }
+        IpAddr::V6(ip) => {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&ip.octets()[..8]);
+            for (byte, mask) in bytes.iter_mut().zip(V6_MASK) {
+                *byte &= mask;
+            }
+            bytes[0] |= r << 5;
+            crc32c::crc32c(&bytes)
+        }
+    };
+    crc.to_be_bytes()
+}
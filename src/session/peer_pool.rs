@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PeerSource {
+    Tracker,
+    Dht,
+    Pex,
+    // Local service discovery (BEP 14).
+    Lsd,
+    // Added through Session::add_peer.
+    Manual,
+}
+
+#[derive(Debug, Default)]
+struct KnownPeer {
+    sources: Vec<PeerSource>,
+    last_attempt: Option<Instant>,
+}
+
+// Every address a torrent has heard of, whichever source it came from. Trackers hand out
+// mostly the same peers on each announce and DHT, PEX and LSD repeat them again, so the
+// pool keeps one entry per address and holds back reconnects that come too soon.
+#[derive(Debug, Default)]
+pub struct PeerPool {
+    peers: HashMap<SocketAddr, KnownPeer>,
+}
+
+impl PeerPool {
+    // Returns true if the address wasn't known before.
+    pub fn add(&mut self, addr: SocketAddr, source: PeerSource) -> bool {
+        let addr = canonical(addr);
+        let is_new = !self.peers.contains_key(&addr);
+        let peer = self.peers.entry(addr).or_default();
+        if !peer.sources.contains(&source) {
+            peer.sources.push(source);
+            peer.sources.sort();
+        }
+        is_new
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    pub fn contains(&self, addr: SocketAddr) -> bool {
+        self.peers.contains_key(&canonical(addr))
+    }
+
+    pub fn sources(&self, addr: SocketAddr) -> Vec<PeerSource> {
+        self.peers
+            .get(&canonical(addr))
+            .map_or_else(Vec::new, |peer| peer.sources.clone())
+    }
+
+    // How many known peers each source told us about. A peer heard of from two sources
+    // counts for both.
+    pub fn source_counts(&self) -> HashMap<PeerSource, usize> {
+        let mut counts = HashMap::new();
+        for source in self.peers.values().flat_map(|peer| &peer.sources) {
+            *counts.entry(*source).or_default() += 1;
+        }
+        counts
+    }
+
+    // Whether `interval` has passed since the last attempt on `addr`. If so, the attempt is
+    // recorded as made now. Unknown addresses are never dialed.
+    pub fn try_dial(&mut self, addr: SocketAddr, now: Instant, interval: Duration) -> bool {
+        let Some(peer) = self.peers.get_mut(&canonical(addr)) else {
+            return false;
+        };
+        let due = peer
+            .last_attempt
+            .is_none_or(|last| now.duration_since(last) >= interval);
+        if due {
+            peer.last_attempt = Some(now);
+        }
+        due
+    }
+}
+
+// An IPv4 peer reported as an IPv4-mapped IPv6 address is still the same peer.
+fn canonical(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(addr.ip().to_canonical(), addr.port())
+}
@@ -0,0 +1,889 @@
+use super::annotate::{PeerAnnotator, PeerLabels};
+use super::bandwidth::Bandwidth;
+use super::config::SessionConfig;
+use super::error::SessionError;
+use super::metrics::ConnectedPeer;
+use super::pipeline::PipelineEstimator;
+use super::slots::SlotKind;
+use super::snapshot::PeerState;
+use super::torrent::Torrent;
+use crate::peer::connection::PeerConnection;
+use crate::peer::extension::ExtendedHandshake;
+use crate::peer::lan::is_lan;
+use crate::peer::value::{MAX_REQUEST_LENGTH, PeerMessage, write_piece};
+use crate::piece::bitfield::Bitfield;
+use crate::piece::layers::HashRun;
+use crate::storage::read_cache::ReadCache;
+use crate::units::ByteSize;
+use bytes::Bytes;
+use rand::seq::IteratorRandom;
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tracing::{debug, info_span, trace};
+
+// Block size of torrents the session hasn't set one for.
+pub const BLOCK_SIZE: u32 = 16 * 1024;
+
+// How often a standby connection checks whether an active slot has freed up.
+const STANDBY_POLL: Duration = Duration::from_millis(500);
+
+// How often an active connection looks at the choker's verdict while the peer is quiet.
+const CHOKE_POLL: Duration = Duration::from_millis(250);
+
+// Pieces left out of a lazy bitfield.
+const LAZY_PIECES: usize = 4;
+
+// Hash requests kept outstanding per peer while piece layers are missing.
+const MAX_HASH_REQUESTS: usize = 2;
+
+// A piece being assembled block by block. When its peer chokes us or goes away, the
+// blocks received so far are parked with the torrent and whoever picks the piece next
+// carries on from there.
+pub(crate) struct PieceDownload {
+    index: usize,
+    data: Vec<u8>,
+    block_size: u32,
+    // The peer each block came from, once received.
+    sources: Vec<Option<SocketAddr>>,
+    // When each block with a request in flight was asked for.
+    requested: Vec<Option<Instant>>,
+}
+
+impl PieceDownload {
+    fn new(index: usize, size: usize, block_size: u32) -> PieceDownload {
+        let blocks = size.div_ceil(block_size as usize);
+        PieceDownload {
+            index,
+            data: vec![0u8; size],
+            block_size,
+            sources: vec![None; blocks],
+            requested: vec![None; blocks],
+        }
+    }
+
+    pub(crate) fn index(&self) -> usize {
+        self.index
+    }
+
+    pub(crate) fn received_bytes(&self) -> usize {
+        (0..self.sources.len())
+            .filter(|&block| self.sources[block].is_some())
+            .map(|block| self.block_length(block) as usize)
+            .sum()
+    }
+
+    pub(crate) fn unrequest_all(&mut self) {
+        self.requested.fill(None);
+    }
+
+    fn block_length(&self, block: usize) -> u32 {
+        self.block_size
+            .min(self.data.len() as u32 - self.block_begin(block))
+    }
+
+    fn block_begin(&self, block: usize) -> u32 {
+        block as u32 * self.block_size
+    }
+
+    // The block a Piece message refers to, if it's one we'd have asked for.
+    fn block_at(&self, begin: u32, length: usize) -> Option<usize> {
+        let block = (begin / self.block_size) as usize;
+        (begin.is_multiple_of(self.block_size)
+            && block < self.sources.len()
+            && self.block_length(block) as usize == length)
+            .then_some(block)
+    }
+
+    fn outstanding(&self) -> usize {
+        self.requested.iter().flatten().count()
+    }
+
+    fn next_missing(&self) -> Option<usize> {
+        (0..self.sources.len())
+            .find(|&block| self.sources[block].is_none() && self.requested[block].is_none())
+    }
+
+    fn is_complete(&self) -> bool {
+        self.sources.iter().all(Option::is_some)
+    }
+}
+
+// Drives a single handshaken connection until either side goes away or both of us are
+// seeds. Downloads one piece at a time from the peer, keeping as many block requests in
+// flight as the pipeline depth calls for, and serves the peer's requests while we're not choking it.
+pub(crate) fn run_peer(
+    torrent: &Torrent,
+    connection: &mut PeerConnection,
+    config: &SessionConfig,
+    bandwidth: &Bandwidth,
+    read_cache: &ReadCache,
+    annotator: &dyn PeerAnnotator,
+    extended_handshake: &ExtendedHandshake,
+) -> Result<(), SessionError> {
+    let _span = info_span!(
+        parent: torrent.span(),
+        "peer",
+        addr = %connection.addr,
+        fast = connection.remote.supports_fast(),
+    )
+    .entered();
+    if !torrent.is_active() || is_banned(torrent, connection, config) {
+        debug!("not connecting, torrent inactive or peer banned");
+        return Ok(());
+    }
+
+    let slot = if config.lan_peers_exempt && is_lan(connection.addr.ip()) {
+        Some(torrent.slots().acquire_lan())
+    } else {
+        torrent
+            .slots()
+            .acquire(config.max_active_peers, config.max_standby_peers)
+    };
+    let Some(slot) = slot else {
+        // Full on both active and standby connections.
+        debug!("no free connection slot");
+        return Ok(());
+    };
+
+    let labels = annotator.annotate(connection.addr.ip());
+    let mut task = PeerTask {
+        torrent,
+        config,
+        bandwidth,
+        read_cache,
+        extended_handshake,
+        slot,
+        fast: connection.remote.supports_fast(),
+        allowed_fast: HashSet::new(),
+        priority_epoch: torrent.priority_epoch(),
+        peer_bitfield: Bitfield::new(torrent.meta().num_pieces()),
+        download: None,
+        pipeline: PipelineEstimator::default(),
+        hash_requests: Vec::new(),
+        hashes_rejected: false,
+        downloaded: 0,
+        uploaded: 0,
+        labels,
+    };
+
+    if slot == SlotKind::Active {
+        torrent.choker().add_peer(connection.addr);
+    }
+    debug!(slot = ?task.slot, "connected");
+    let _connected = ConnectedPeer::new();
+    let result = task.run(connection);
+    if let Err(err) = &result {
+        debug!(error = %err, "connection closed");
+    }
+
+    torrent.slots().release(task.slot);
+    torrent.choker().remove_peer(connection.addr);
+    torrent.remove_peer_state(connection.addr);
+    torrent
+        .picker()
+        .remove_peer(connection.addr, &task.peer_bitfield);
+    if let Some(download) = task.download.take() {
+        torrent.park_download(download);
+    }
+    for run in &task.hash_requests {
+        torrent.piece_layers().cancel(&run.pieces_root, run.index);
+    }
+
+    result
+}
+
+fn is_banned(torrent: &Torrent, connection: &PeerConnection, config: &SessionConfig) -> bool {
+    config
+        .max_hash_failures
+        .is_some_and(|max| torrent.hash_failures(connection.addr.ip()) >= max)
+}
+
+// Leaves a few random pieces out of our bitfield, to be sent as Have messages right after
+// it, so that a seed's bitfield doesn't give it away as one.
+fn lazy_bitfield(mut have: Bitfield) -> (Bitfield, Vec<usize>) {
+    let withheld = (0..have.len())
+        .filter(|&index| have.has(index))
+        .choose_multiple(&mut rand::rng(), LAZY_PIECES);
+    for &index in &withheld {
+        have.clear(index);
+    }
+    (have, withheld)
+}
+
+// Waits up to `wait` for the peer to send something. Returns true when a message can be
+// read.
+fn peek_message(connection: &mut PeerConnection, wait: Duration) -> Result<bool, SessionError> {
+    let read_timeout = connection.stream.read_timeout()?;
+    connection.stream.set_read_timeout(Some(wait))?;
+    let peeked = connection.stream.peek(&mut [0u8; 1]);
+    connection.stream.set_read_timeout(read_timeout)?;
+
+    match peeked {
+        Ok(0) => Err(std::io::Error::from(ErrorKind::UnexpectedEof).into()),
+        Ok(_) => Ok(true),
+        Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+struct PeerTask<'a> {
+    torrent: &'a Torrent,
+    config: &'a SessionConfig,
+    bandwidth: &'a Bandwidth,
+    read_cache: &'a ReadCache,
+    // Sent to peers that speak the extension protocol.
+    extended_handshake: &'a ExtendedHandshake,
+    slot: SlotKind,
+    // Whether the fast extension is on for this connection.
+    fast: bool,
+    // Pieces the peer lets us download while it chokes us.
+    allowed_fast: HashSet<usize>,
+    // The torrent's priority epoch as of our last look at the file priorities.
+    priority_epoch: u64,
+    peer_bitfield: Bitfield,
+    download: Option<PieceDownload>,
+    pipeline: PipelineEstimator,
+    hash_requests: Vec<HashRun>,
+    // Set once the peer rejects a hash request; it isn't asked again.
+    hashes_rejected: bool,
+    // Bytes exchanged over this connection.
+    downloaded: u64,
+    uploaded: u64,
+    // From the session's PeerAnnotator when the connection was made.
+    labels: PeerLabels,
+}
+
+impl PeerTask<'_> {
+    fn run(&mut self, connection: &mut PeerConnection) -> Result<(), SessionError> {
+        let (have, withheld) =
+            if self.config.privacy_mode || self.config.client_preset.lazy_bitfield() {
+                lazy_bitfield(self.torrent.bitfield())
+            } else {
+                (self.torrent.bitfield(), Vec::new())
+            };
+
+        // With the fast extension exactly one of these has to open the conversation.
+        let opening = if self.fast && have.is_complete() {
+            Some(PeerMessage::HaveAll)
+        } else if have.count() > 0 {
+            Some(PeerMessage::Bitfield(Bytes::copy_from_slice(
+                have.as_bytes(),
+            )))
+        } else if self.fast {
+            Some(PeerMessage::HaveNone)
+        } else {
+            None
+        };
+        if let Some(message) = opening {
+            message.write_peer_message(&mut connection.stream)?;
+        }
+        if connection.remote.supports_extension_protocol() {
+            self.extended_handshake
+                .to_message()
+                .write_peer_message(&mut connection.stream)?;
+        }
+        if let Some(listen_port) = self.config.dht_port
+            && connection.remote.supports_dht()
+        {
+            PeerMessage::Port { listen_port }.write_peer_message(&mut connection.stream)?;
+        }
+        for index in withheld {
+            PeerMessage::Have {
+                piece_index: index as u32,
+            }
+            .write_peer_message(&mut connection.stream)?;
+        }
+        self.request_hashes(connection)?;
+
+        let keep_alive_interval = (self.config.client_preset)
+            .keep_alive_interval()
+            .unwrap_or(self.config.keep_alive_interval);
+        let mut last_keep_alive = Instant::now();
+        let mut last_message = Instant::now();
+        loop {
+            if !self.torrent.is_active() || is_banned(self.torrent, connection, self.config) {
+                return Ok(());
+            }
+            self.torrent.update_peer_state(self.peer_state(connection));
+
+            // No connection blocks on the peer: keep-alives have to go out while it's quiet,
+            // and with upload slots the choker may change its mind at any time.
+            let standby = self.slot == SlotKind::Standby;
+            let ready = if standby {
+                self.wait_in_standby(connection)?
+            } else {
+                if self.config.upload_slots.is_some() && self.slot == SlotKind::Active {
+                    self.apply_choke(connection)?;
+                }
+                peek_message(connection, CHOKE_POLL)?
+            };
+            if !ready {
+                // A peer in standby has no reason to talk, so its silence there doesn't count.
+                if standby {
+                    last_message = Instant::now();
+                } else if last_message.elapsed() >= self.config.timeouts.read {
+                    return Err(std::io::Error::from(ErrorKind::TimedOut).into());
+                }
+                if last_keep_alive.elapsed() >= keep_alive_interval {
+                    PeerMessage::KeepAlive.write_peer_message(&mut connection.stream)?;
+                    last_keep_alive = Instant::now();
+                }
+                continue;
+            }
+
+            let message = PeerMessage::read_peer_message(&mut connection.stream)?;
+            last_message = Instant::now();
+            self.handle_message(connection, message)?;
+            if self.drop_finished(connection)? {
+                self.update_interest(connection)?;
+                self.request_more(connection)?;
+            }
+            if self.priority_epoch != self.torrent.priority_epoch() {
+                self.apply_priorities(connection)?;
+            }
+
+            if self.torrent.is_complete() && self.peer_bitfield.is_complete() {
+                return Ok(());
+            }
+        }
+    }
+
+    // Serves a block through the read cache, which loads the whole piece on a miss. Cached
+    // blocks are written straight out of the cache.
+    fn send_block(
+        &self,
+        connection: &mut PeerConnection,
+        index: u32,
+        begin: u32,
+        length: u32,
+    ) -> Result<(), SessionError> {
+        let backend = self.torrent.backend();
+        if self.read_cache.capacity() == 0 {
+            let block = backend.read_block(index as usize, begin, length)?;
+            write_piece(&mut connection.stream, index, begin, &block)?;
+            return Ok(());
+        }
+        let piece =
+            self.read_cache
+                .get_or_load(self.torrent.info_hash(), index as usize, || {
+                    let size = self.torrent.meta().piece_size(index as usize);
+                    backend.read_block(index as usize, 0, size as u32)
+                })?;
+        let start = begin as usize;
+        let block = piece.get(start..start + length as usize).ok_or_else(|| {
+            std::io::Error::new(ErrorKind::InvalidInput, "Block exceeds piece size")
+        })?;
+        write_piece(&mut connection.stream, index, begin, block)?;
+        Ok(())
+    }
+
+    fn peer_state(&self, connection: &PeerConnection) -> PeerState {
+        PeerState {
+            addr: connection.addr,
+            peer_id: connection.remote.peer_id,
+            slot: self.slot,
+            fast: self.fast,
+            am_choking: connection.am_choking,
+            am_interested: connection.am_interested,
+            peer_choking: connection.peer_choking,
+            peer_interested: connection.peer_interested,
+            pieces: self.peer_bitfield.count(),
+            incoming: connection.incoming,
+            downloaded: ByteSize(self.downloaded),
+            uploaded: ByteSize(self.uploaded),
+            labels: self.labels.clone(),
+            requests: self.download.as_ref().map_or(0, |download| {
+                (download.requested.iter())
+                    .filter(|requested| requested.is_some())
+                    .count()
+            }),
+        }
+    }
+
+    // Waits up to STANDBY_POLL for the peer to send something, promoting the connection to
+    // an active slot if one frees up meanwhile. Returns true when a message can be read.
+    fn wait_in_standby(&mut self, connection: &mut PeerConnection) -> Result<bool, SessionError> {
+        if self.torrent.slots().promote(self.config.max_active_peers) {
+            self.slot = SlotKind::Active;
+            let mut choker = self.torrent.choker();
+            choker.add_peer(connection.addr);
+            choker.set_interested(connection.addr, connection.peer_interested);
+            drop(choker);
+            if self.config.upload_slots.is_none()
+                && connection.peer_interested
+                && connection.am_choking
+                && self.torrent.uploads()
+            {
+                connection.am_choking = false;
+                PeerMessage::Unchoke.write_peer_message(&mut connection.stream)?;
+            }
+            self.update_interest(connection)?;
+            return Ok(false);
+        }
+
+        peek_message(connection, STANDBY_POLL)
+    }
+
+    // Chokes or unchokes the peer to match the choker. LAN peers aren't subject to it.
+    fn apply_choke(&mut self, connection: &mut PeerConnection) -> Result<(), SessionError> {
+        let unchoke = self.torrent.uploads() && self.torrent.choker().is_unchoked(connection.addr);
+        if unchoke && connection.am_choking {
+            connection.am_choking = false;
+            PeerMessage::Unchoke.write_peer_message(&mut connection.stream)?;
+        } else if !unchoke && !connection.am_choking {
+            connection.am_choking = true;
+            PeerMessage::Choke.write_peer_message(&mut connection.stream)?;
+        }
+        Ok(())
+    }
+
+    fn handle_message(
+        &mut self,
+        connection: &mut PeerConnection,
+        message: PeerMessage,
+    ) -> Result<(), SessionError> {
+        match message {
+            PeerMessage::KeepAlive
+            | PeerMessage::Cancel { .. }
+            | PeerMessage::SuggestPiece { .. }
+            | PeerMessage::HaveNone
+            | PeerMessage::Extended { .. }
+            | PeerMessage::Unknown { .. } => {}
+            PeerMessage::HashRequest {
+                pieces_root,
+                base_layer,
+                index,
+                length,
+                proof_layers,
+            } => {
+                let run = HashRun {
+                    pieces_root,
+                    base_layer,
+                    index,
+                    length,
+                    proof_layers,
+                };
+                let answer = self.torrent.piece_layers().answer(run);
+                let reply = match answer {
+                    Some(hashes) => PeerMessage::Hashes {
+                        pieces_root,
+                        base_layer,
+                        index,
+                        length,
+                        proof_layers,
+                        hashes,
+                    },
+                    None => PeerMessage::HashReject {
+                        pieces_root,
+                        base_layer,
+                        index,
+                        length,
+                        proof_layers,
+                    },
+                };
+                reply.write_peer_message(&mut connection.stream)?;
+            }
+            PeerMessage::Hashes {
+                pieces_root,
+                base_layer,
+                index,
+                length,
+                proof_layers,
+                hashes,
+            } => {
+                let run = HashRun {
+                    pieces_root,
+                    base_layer,
+                    index,
+                    length,
+                    proof_layers,
+                };
+                if self.take_hash_request(&pieces_root, index) {
+                    if !self.torrent.piece_layers().add_hashes(run, &hashes) {
+                        debug!(index, "hashes don't match the pieces root");
+                        self.hashes_rejected = true;
+                    }
+                    self.request_hashes(connection)?;
+                }
+            }
+            PeerMessage::HashReject {
+                pieces_root, index, ..
+            } => {
+                if self.take_hash_request(&pieces_root, index) {
+                    self.torrent.piece_layers().cancel(&pieces_root, index);
+                    self.hashes_rejected = true;
+                }
+            }
+            PeerMessage::Port { listen_port } => {
+                if listen_port != 0 {
+                    let node = SocketAddr::new(connection.addr.ip(), listen_port);
+                    self.torrent.add_dht_node(node);
+                }
+            }
+            PeerMessage::Choke => {
+                trace!("choked");
+                connection.peer_choking = true;
+                self.on_choke();
+                self.torrent.picker().on_choke(connection.addr);
+            }
+            PeerMessage::Unchoke => {
+                trace!("unchoked");
+                connection.peer_choking = false;
+                self.request_more(connection)?;
+            }
+            PeerMessage::Interested => {
+                connection.peer_interested = true;
+                self.torrent.choker().set_interested(connection.addr, true);
+                self.unchoke_if_free(connection)?;
+            }
+            PeerMessage::NotInterested => {
+                connection.peer_interested = false;
+                self.torrent.choker().set_interested(connection.addr, false);
+            }
+            PeerMessage::Have { piece_index } => {
+                let index = piece_index as usize;
+                // A Have past the last piece would count the peer as a seed.
+                if index < self.peer_bitfield.len() && !self.peer_bitfield.has(index) {
+                    self.peer_bitfield.set(index);
+                    self.torrent.picker().add_have(index, &self.peer_bitfield);
+                }
+                self.update_interest(connection)?;
+            }
+            PeerMessage::Bitfield(bytes) => {
+                let bitfield = Bitfield::from_bytes(&bytes, self.peer_bitfield.len());
+                self.replace_peer_bitfield(bitfield);
+                self.update_interest(connection)?;
+            }
+            PeerMessage::HaveAll => {
+                self.replace_peer_bitfield(Bitfield::full(self.peer_bitfield.len()));
+                self.update_interest(connection)?;
+            }
+            PeerMessage::AllowedFast { piece_index } => {
+                // The set is only as large as the torrent, whatever the peer sends.
+                if self.config.honor_allowed_fast
+                    && (piece_index as usize) < self.peer_bitfield.len()
+                {
+                    self.allowed_fast.insert(piece_index as usize);
+                    self.request_more(connection)?;
+                }
+            }
+            PeerMessage::RejectRequest { index, .. } => {
+                self.on_reject(connection, index as usize);
+                self.request_more(connection)?;
+            }
+            PeerMessage::Request {
+                index,
+                begin,
+                length,
+            } => {
+                // A block may not run past the end of its piece, whichever piece that is.
+                let within_piece = begin as u64 + length as u64
+                    <= self.torrent.meta().piece_size(index as usize) as u64;
+                let servable = length > 0
+                    && length <= MAX_REQUEST_LENGTH
+                    && within_piece
+                    && self.torrent.uploads();
+                if servable && !connection.am_choking && self.torrent.has_piece(index as usize) {
+                    self.torrent.bandwidth().upload.acquire(length as usize);
+                    self.bandwidth.upload.acquire(length as usize);
+                    self.send_block(connection, index, begin, length)?;
+                    self.torrent.add_uploaded(length as u64);
+                    self.uploaded += length as u64;
+                    (self.torrent.choker()).record_upload(connection.addr, length as u64);
+                } else if self.fast {
+                    PeerMessage::RejectRequest {
+                        index,
+                        begin,
+                        length,
+                    }
+                    .write_peer_message(&mut connection.stream)?;
+                }
+            }
+            PeerMessage::Piece {
+                index,
+                begin,
+                block,
+            } => {
+                self.torrent.bandwidth().download.acquire(block.len());
+                self.bandwidth.download.acquire(block.len());
+                self.receive_block(connection, index as usize, begin, &block)?;
+                self.request_more(connection)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Allowed-fast pieces carry on through a choke. Anything else is parked for other
+    // peers to finish; without the fast extension the choke dropped our requests anyway,
+    // and with it the peer will only send rejects for them.
+    fn on_choke(&mut self) {
+        let Some(download) = self.download.take() else {
+            return;
+        };
+        if self.fast && self.allowed_fast.contains(&download.index) {
+            self.download = Some(download);
+            return;
+        }
+        self.torrent.park_download(download);
+    }
+
+    // The peer won't give us the piece after all. It's left for other peers, and this one
+    // is asked for it again only when it has nothing else we want.
+    fn on_reject(&mut self, connection: &PeerConnection, index: usize) {
+        let Some(download) = self.download.take_if(|d| d.index == index) else {
+            return;
+        };
+        self.allowed_fast.remove(&index);
+        self.torrent.picker().avoid(index, [connection.addr]);
+        self.torrent.park_download(download);
+    }
+
+    // Unchokes an interested peer unless the choker or the transfer mode has a say.
+    fn unchoke_if_free(&mut self, connection: &mut PeerConnection) -> Result<(), SessionError> {
+        let choker_decides = self.config.upload_slots.is_some() && self.slot != SlotKind::Lan;
+        if !choker_decides
+            && connection.peer_interested
+            && connection.am_choking
+            && self.slot.transfers()
+            && self.torrent.uploads()
+        {
+            connection.am_choking = false;
+            PeerMessage::Unchoke.write_peer_message(&mut connection.stream)?;
+        }
+        Ok(())
+    }
+
+    // File priorities or the transfer mode changed. Outstanding requests for a piece we no
+    // longer want are cancelled, our interest follows what's left, and the peer is choked
+    // or unchoked as the mode now says.
+    fn apply_priorities(&mut self, connection: &mut PeerConnection) -> Result<(), SessionError> {
+        self.priority_epoch = self.torrent.priority_epoch();
+        let torrent = self.torrent;
+        let unwanted = |d: &mut PieceDownload| !torrent.downloads() || !torrent.is_wanted(d.index);
+        if let Some(download) = self.download.take_if(unwanted) {
+            self.cancel_requests(connection, &download)?;
+            self.torrent.park_download(download);
+        }
+        if !torrent.uploads() && !connection.am_choking {
+            connection.am_choking = true;
+            PeerMessage::Choke.write_peer_message(&mut connection.stream)?;
+        } else {
+            self.unchoke_if_free(connection)?;
+        }
+        self.update_interest(connection)?;
+        self.request_more(connection)
+    }
+
+    // In endgame another peer may finish our piece first. It's dropped, and the blocks
+    // still requested are cancelled so the peer doesn't spend upload on them. Returns true
+    // if there was such a piece.
+    fn drop_finished(&mut self, connection: &mut PeerConnection) -> Result<bool, SessionError> {
+        let torrent = self.torrent;
+        let Some(download) = self.download.take_if(|d| torrent.has_piece(d.index)) else {
+            return Ok(false);
+        };
+        trace!(index = download.index, "piece finished by another peer");
+        self.cancel_requests(connection, &download)?;
+        Ok(true)
+    }
+
+    fn cancel_requests(
+        &self,
+        connection: &mut PeerConnection,
+        download: &PieceDownload,
+    ) -> Result<(), SessionError> {
+        let mut cancelled = 0;
+        for block in (0..download.requested.len()).filter(|&b| download.requested[b].is_some()) {
+            PeerMessage::Cancel {
+                index: download.index as u32,
+                begin: download.block_begin(block),
+                length: download.block_length(block),
+            }
+            .write_peer_message(&mut connection.stream)?;
+            cancelled += 1;
+        }
+        self.torrent.add_cancelled_requests(cancelled);
+        Ok(())
+    }
+
+    fn receive_block(
+        &mut self,
+        connection: &mut PeerConnection,
+        index: usize,
+        begin: u32,
+        block: &[u8],
+    ) -> Result<(), SessionError> {
+        if self.torrent.has_piece(index) {
+            return Ok(());
+        }
+        let Some(download) = self.download.as_mut().filter(|d| d.index == index) else {
+            return Ok(());
+        };
+        let Some(block_index) = download.block_at(begin, block.len()) else {
+            return Ok(());
+        };
+        if download.sources[block_index].is_some() {
+            return Ok(());
+        }
+
+        let start = begin as usize;
+        download.data[start..start + block.len()].copy_from_slice(block);
+        download.sources[block_index] = Some(connection.addr);
+        if let Some(requested_at) = download.requested[block_index].take() {
+            self.pipeline
+                .on_block(block.len(), requested_at, Instant::now());
+        }
+        self.torrent.add_downloaded(block.len() as u64);
+        self.downloaded += block.len() as u64;
+        (self.torrent.choker()).record_download(connection.addr, block.len() as u64);
+
+        if !download.is_complete() {
+            return Ok(());
+        }
+
+        let download = self.download.take().unwrap();
+        let sources: Vec<SocketAddr> = download.sources.iter().flatten().copied().collect();
+        if self
+            .torrent
+            .store_piece_from(index, &download.data, &sources)?
+        {
+            PeerMessage::Have {
+                piece_index: index as u32,
+            }
+            .write_peer_message(&mut connection.stream)?;
+        } else {
+            self.torrent.picker().abandon(index);
+        }
+
+        self.update_interest(connection)
+    }
+
+    // Asks a v2 capable peer for piece layers we lack, a few runs at a time.
+    fn request_hashes(&mut self, connection: &mut PeerConnection) -> Result<(), SessionError> {
+        if !connection.remote.supports_v2() || self.hashes_rejected {
+            return Ok(());
+        }
+        while self.hash_requests.len() < MAX_HASH_REQUESTS {
+            let Some(run) = self.torrent.piece_layers().next_request() else {
+                break;
+            };
+            PeerMessage::HashRequest {
+                pieces_root: run.pieces_root,
+                base_layer: run.base_layer,
+                index: run.index,
+                length: run.length,
+                proof_layers: run.proof_layers,
+            }
+            .write_peer_message(&mut connection.stream)?;
+            self.hash_requests.push(run);
+        }
+        Ok(())
+    }
+
+    // Whether we asked this peer for the run, forgetting the request if so.
+    fn take_hash_request(&mut self, pieces_root: &[u8; 32], index: u32) -> bool {
+        let position = self
+            .hash_requests
+            .iter()
+            .position(|run| run.pieces_root == *pieces_root && run.index == index);
+        position
+            .map(|position| self.hash_requests.remove(position))
+            .is_some()
+    }
+
+    fn request_more(&mut self, connection: &mut PeerConnection) -> Result<(), SessionError> {
+        if !connection.am_interested {
+            return Ok(());
+        }
+        self.drop_finished(connection)?;
+
+        if self.download.is_none() {
+            // While choked, only allowed-fast pieces are on offer.
+            let candidates = if connection.peer_choking {
+                let mut allowed = Bitfield::new(self.peer_bitfield.len());
+                for &index in &self.allowed_fast {
+                    if self.peer_bitfield.has(index) {
+                        allowed.set(index);
+                    }
+                }
+                allowed
+            } else {
+                self.peer_bitfield.clone()
+            };
+            let mut picker = self.torrent.picker();
+            let picked = picker.pick(connection.addr, &candidates).or_else(|| {
+                (self.config.endgame)
+                    .then(|| picker.pick_endgame(connection.addr, &candidates))
+                    .flatten()
+            });
+            drop(picker);
+            let Some(index) = picked else {
+                return Ok(());
+            };
+            let download = self.torrent.take_parked(index).unwrap_or_else(|| {
+                let size = self.torrent.meta().piece_size(index);
+                PieceDownload::new(index, size, self.torrent.block_size())
+            });
+            self.download = Some(download);
+        }
+
+        let download = self.download.as_mut().unwrap();
+        if connection.peer_choking && !self.allowed_fast.contains(&download.index) {
+            return Ok(());
+        }
+        let depth = self
+            .pipeline
+            .depth(self.config.pipeline_depth, download.block_size);
+        while download.outstanding() < depth {
+            let Some(block) = download.next_missing() else {
+                break;
+            };
+            PeerMessage::Request {
+                index: download.index as u32,
+                begin: download.block_begin(block),
+                length: download.block_length(block),
+            }
+            .write_peer_message(&mut connection.stream)?;
+            download.requested[block] = Some(Instant::now());
+        }
+
+        Ok(())
+    }
+
+    // A Bitfield or HaveAll is meant to come once, right after the handshake. One sent
+    // again takes the place of the first in the swarm's availability instead of adding to
+    // it.
+    fn replace_peer_bitfield(&mut self, bitfield: Bitfield) {
+        (self.torrent.picker()).replace_peer_bitfield(&self.peer_bitfield, &bitfield);
+        self.peer_bitfield = bitfield;
+    }
+
+    fn update_interest(&mut self, connection: &mut PeerConnection) -> Result<(), SessionError> {
+        if self.slot == SlotKind::Standby {
+            return Ok(());
+        }
+
+        let picker = self.torrent.picker();
+        let wanted = self.torrent.downloads()
+            && (0..self.peer_bitfield.len()).any(|index| {
+                self.peer_bitfield.has(index)
+                    && picker.wanted().has(index)
+                    && !picker.have().has(index)
+            });
+        drop(picker);
+
+        if wanted && !connection.am_interested {
+            connection.am_interested = true;
+            PeerMessage::Interested.write_peer_message(&mut connection.stream)?;
+            self.request_more(connection)?;
+        } else if !wanted && connection.am_interested {
+            connection.am_interested = false;
+            PeerMessage::NotInterested.write_peer_message(&mut connection.stream)?;
+        }
+
+        Ok(())
+    }
+}
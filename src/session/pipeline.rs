@@ -0,0 +1,76 @@
+use std::time::{Duration, Instant};
+
+// Download rate is averaged over windows of this length.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineDepth {
+    // Always this many block requests in flight.
+    Fixed(usize),
+    // Twice the peer's bandwidth-delay product in blocks, kept between `min` and `max`. A
+    // shallow pipe caps the measured rate at depth blocks per round trip, so covering the
+    // product exactly would never grow; doubling it does until the link is the bottleneck.
+    Adaptive { min: usize, max: usize },
+}
+
+impl Default for PipelineDepth {
+    fn default() -> Self {
+        PipelineDepth::Adaptive { min: 5, max: 128 }
+    }
+}
+
+// What a connection has measured of its peer for sizing the request pipeline.
+#[derive(Debug, Default)]
+pub struct PipelineEstimator {
+    // The quickest a block has come back after its request, which leaves out most of the
+    // time spent queued behind earlier requests.
+    min_rtt: Option<Duration>,
+    // Smoothed download rate in bytes per second.
+    rate: f64,
+    window_start: Option<Instant>,
+    window_bytes: u64,
+}
+
+impl PipelineEstimator {
+    pub fn on_block(&mut self, length: usize, requested_at: Instant, now: Instant) {
+        let rtt = now.saturating_duration_since(requested_at);
+        self.min_rtt = Some(self.min_rtt.map_or(rtt, |min| min.min(rtt)));
+
+        let start = *self.window_start.get_or_insert(requested_at);
+        self.window_bytes += length as u64;
+        let elapsed = now.saturating_duration_since(start);
+        if elapsed >= RATE_WINDOW {
+            let rate = self.window_bytes as f64 / elapsed.as_secs_f64();
+            self.rate = if self.rate == 0.0 {
+                rate
+            } else {
+                (self.rate + rate) / 2.0
+            };
+            self.window_start = Some(now);
+            self.window_bytes = 0;
+        }
+    }
+
+    pub fn min_rtt(&self) -> Option<Duration> {
+        self.min_rtt
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    // Block requests to keep outstanding with this peer.
+    pub fn depth(&self, policy: PipelineDepth, block_size: u32) -> usize {
+        match policy {
+            PipelineDepth::Fixed(depth) => depth.max(1),
+            PipelineDepth::Adaptive { min, max } => {
+                let min = min.max(1);
+                let Some(rtt) = self.min_rtt else {
+                    return min;
+                };
+                let bdp = self.rate * rtt.as_secs_f64() / block_size as f64;
+                ((2.0 * bdp).ceil() as usize).clamp(min, max.max(min))
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use crate::piece::bitfield::Bitfield;
+use crate::storage::file_storage::FileStorage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilePriority {
+    // Not downloaded, and removed from disk if none of its pieces are complete.
+    Skip,
+    Normal,
+}
+
+// Pieces holding at least one byte of a file we want. Pieces shared between a skipped and
+// a wanted file are still downloaded whole.
+pub fn wanted_pieces(storage: &FileStorage, priorities: &[FilePriority]) -> Bitfield {
+    let num_pieces = storage.total_size().div_ceil(storage.piece_length()) as usize;
+    let mut wanted = Bitfield::new(num_pieces);
+    for (entry, priority) in storage.files().iter().zip(priorities) {
+        if *priority != FilePriority::Skip {
+            for index in storage.pieces_of(entry) {
+                wanted.set(index);
+            }
+        }
+    }
+    wanted
+}
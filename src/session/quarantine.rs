@@ -0,0 +1,76 @@
+use super::peer_task::BLOCK_SIZE;
+use sha1::{Digest, Sha1};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+
+// One failed copy of a piece: each block's hash and who sent it.
+type FailedCopy = Vec<([u8; 20], SocketAddr)>;
+
+// Blocks of pieces that failed their hash check, kept as the hash of each block and the
+// peer that sent it. A failed piece alone doesn't say which block was bad; once a good
+// copy arrives, the blocks that differ from it point at the peers that lied.
+#[derive(Debug)]
+pub struct Quarantine {
+    pieces: HashMap<usize, Vec<FailedCopy>>,
+    block_size: u32,
+}
+
+impl Default for Quarantine {
+    fn default() -> Self {
+        Quarantine::new(BLOCK_SIZE)
+    }
+}
+
+fn block_hashes(data: &[u8], block_size: u32) -> impl Iterator<Item = [u8; 20]> + '_ {
+    data.chunks(block_size as usize)
+        .map(|block| Sha1::digest(block).into())
+}
+
+impl Quarantine {
+    // Pieces are split into blocks of `block_size` bytes, as they were requested.
+    pub fn new(block_size: u32) -> Quarantine {
+        Quarantine {
+            pieces: HashMap::new(),
+            block_size,
+        }
+    }
+
+    // `sources` holds the peer that sent each block of `data`, in order.
+    pub fn add_failed(&mut self, index: usize, data: &[u8], sources: &[SocketAddr]) {
+        let blocks = block_hashes(data, self.block_size)
+            .zip(sources.iter().copied())
+            .collect();
+        self.pieces.entry(index).or_default().push(blocks);
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.pieces.contains_key(&index)
+    }
+
+    // Everyone who contributed to a failed copy of the piece.
+    #[cfg(feature = "sim")]
+    pub fn suspects(&self, index: usize) -> HashSet<SocketAddr> {
+        self.pieces
+            .get(&index)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|(_, source)| *source)
+            .collect()
+    }
+
+    // Compares the failed copies against the verified `data` and forgets the piece.
+    // Returns the peers that sent at least one block that differed.
+    pub fn resolve(&mut self, index: usize, data: &[u8]) -> HashSet<SocketAddr> {
+        let Some(attempts) = self.pieces.remove(&index) else {
+            return HashSet::new();
+        };
+        let good: Vec<[u8; 20]> = block_hashes(data, self.block_size).collect();
+        attempts
+            .iter()
+            .flat_map(|blocks| blocks.iter().zip(&good))
+            .filter(|((hash, _), good)| hash != *good)
+            .map(|((_, source), _)| *source)
+            .collect()
+    }
+}
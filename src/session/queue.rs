@@ -0,0 +1,36 @@
+// Order in which torrents get to run. Torrents are appended as they are added; the
+// session starts them front to back until the active limits are hit.
+#[derive(Debug, Default)]
+pub struct TorrentQueue {
+    order: Vec<[u8; 20]>,
+}
+
+impl TorrentQueue {
+    pub fn push(&mut self, info_hash: [u8; 20]) {
+        if !self.order.contains(&info_hash) {
+            self.order.push(info_hash);
+        }
+    }
+
+    pub fn remove(&mut self, info_hash: &[u8; 20]) {
+        self.order.retain(|hash| hash != info_hash);
+    }
+
+    pub fn position(&self, info_hash: &[u8; 20]) -> Option<usize> {
+        self.order.iter().position(|hash| hash == info_hash)
+    }
+
+    // Positions past the end move the torrent to the back. Returns false for unknown torrents.
+    pub fn move_to(&mut self, info_hash: &[u8; 20], position: usize) -> bool {
+        let Some(current) = self.position(info_hash) else {
+            return false;
+        };
+        let hash = self.order.remove(current);
+        self.order.insert(position.min(self.order.len()), hash);
+        true
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[u8; 20]> {
+        self.order.iter()
+    }
+}
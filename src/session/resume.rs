@@ -0,0 +1,181 @@
+use super::schedule::TorrentSchedule;
+use crate::bencode::errors::BencodeError;
+use crate::bencode::parser::parse_value;
+use crate::bencode::value::BencodeValue;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// What we remember about a torrent between sessions, so startup doesn't have to hash
+// everything on disk again. Pieces completed shortly before `saved_at` are listed in
+// `recent` with their completion time: after an unclean shutdown those may not have
+// been flushed and get verified again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResumeData {
+    pub info_hash: [u8; 20],
+    pub pieces: Vec<u8>,
+    // Seconds since the Unix epoch.
+    pub saved_at: u64,
+    pub recent: Vec<(usize, u64)>,
+    pub schedule: TorrentSchedule,
+    // The torrent's top directory as renamed by the user, and renamed files as (path in
+    // the torrent, path now), both below the download directory.
+    pub root_name: Option<PathBuf>,
+    pub renamed: Vec<(PathBuf, PathBuf)>,
+}
+
+fn path_to_bencode(path: &Path) -> BencodeValue {
+    BencodeValue::List(
+        path.iter()
+            .map(|part| BencodeValue::String(part.to_string_lossy().into_owned()))
+            .collect(),
+    )
+}
+
+fn path_from_bencode(value: &BencodeValue) -> Result<PathBuf, BencodeError> {
+    value
+        .as_list()?
+        .iter()
+        .map(|part| part.as_string().map(str::to_string))
+        .collect()
+}
+
+pub fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+impl ResumeData {
+    // Pieces completed within `window` of the save, i.e. possibly still in the page cache
+    // when the machine went down.
+    pub fn suspect_pieces(&self, window: Duration) -> Vec<usize> {
+        let cutoff = self.saved_at.saturating_sub(window.as_secs());
+        self.recent
+            .iter()
+            .filter(|(_, completed)| *completed >= cutoff)
+            .map(|(index, _)| *index)
+            .collect()
+    }
+
+    pub fn to_bencode(&self) -> BencodeValue {
+        let recent = self
+            .recent
+            .iter()
+            .map(|(index, completed)| {
+                BencodeValue::List(vec![
+                    BencodeValue::Integer(*index as i64),
+                    BencodeValue::Integer(*completed as i64),
+                ])
+            })
+            .collect();
+
+        let mut dict = HashMap::new();
+        dict.insert(
+            "info hash".to_string(),
+            BencodeValue::Bytes(self.info_hash.to_vec()),
+        );
+        dict.insert(
+            "pieces".to_string(),
+            BencodeValue::Bytes(self.pieces.clone()),
+        );
+        dict.insert(
+            "saved".to_string(),
+            BencodeValue::Integer(self.saved_at as i64),
+        );
+        dict.insert("recent".to_string(), BencodeValue::List(recent));
+        let times = [
+            ("start at", self.schedule.start_at),
+            ("stop at", self.schedule.stop_at),
+        ];
+        for (key, time) in times {
+            if let Some(time) = time {
+                dict.insert(
+                    key.to_string(),
+                    BencodeValue::Integer(unix_time(time) as i64),
+                );
+            }
+        }
+        if let Some(root) = &self.root_name {
+            dict.insert("root".to_string(), path_to_bencode(root));
+        }
+        if !self.renamed.is_empty() {
+            let renamed = self
+                .renamed
+                .iter()
+                .map(|(from, to)| {
+                    BencodeValue::List(vec![path_to_bencode(from), path_to_bencode(to)])
+                })
+                .collect();
+            dict.insert("renamed".to_string(), BencodeValue::List(renamed));
+        }
+        BencodeValue::Dictionary(dict)
+    }
+
+    pub fn from_bencode(value: &BencodeValue) -> Result<ResumeData, BencodeError> {
+        let info_hash = value
+            .bytes("info hash")?
+            .try_into()
+            .map_err(|_| BencodeError::InvalidString("info hash must be 20 bytes".into(), None))?;
+
+        let mut recent = Vec::new();
+        for entry in value.list("recent")?.as_list()? {
+            let index = *entry.index(0)?.as_int()?;
+            let completed = *entry.index(1)?.as_int()?;
+            recent.push((index as usize, completed as u64));
+        }
+
+        // Older resume files have no schedule.
+        let time = |key| {
+            value
+                .int(key)
+                .ok()
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64))
+        };
+        let schedule = TorrentSchedule {
+            start_at: time("start at"),
+            stop_at: time("stop at"),
+        };
+
+        // Nor do they have renames.
+        let root_name = match value.list("root") {
+            Ok(root) => Some(path_from_bencode(root)?),
+            Err(_) => None,
+        };
+        let mut renamed = Vec::new();
+        if let Ok(list) = value.list("renamed") {
+            for entry in list.as_list()? {
+                renamed.push((
+                    path_from_bencode(entry.index(0)?)?,
+                    path_from_bencode(entry.index(1)?)?,
+                ));
+            }
+        }
+
+        Ok(ResumeData {
+            info_hash,
+            pieces: value.bytes("pieces")?.to_vec(),
+            saved_at: value.int("saved")? as u64,
+            recent,
+            schedule,
+            root_name,
+            renamed,
+        })
+    }
+
+    // Unreadable or corrupt files count as missing; the caller falls back to a full check.
+    pub fn load(path: &Path) -> Option<ResumeData> {
+        let data = fs::read(path).ok()?;
+        let (value, _) = parse_value(&data).ok()?;
+        ResumeData::from_bencode(&value).ok()
+    }
+
+    // Written to a temporary file first, so a crash mid-save leaves the old file intact.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temp = path.with_extension("resume.tmp");
+        fs::write(&temp, self.to_bencode().encode())?;
+        fs::rename(&temp, path)
+    }
+}
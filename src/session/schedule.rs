@@ -0,0 +1,35 @@
+use std::time::SystemTime;
+
+// When the session should start or stop a torrent, e.g. to run a big download only at
+// night. Each time is cleared once the session has acted on it. Kept in the resume data,
+// so a schedule survives restarts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TorrentSchedule {
+    pub start_at: Option<SystemTime>,
+    pub stop_at: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduledAction {
+    Start,
+    Stop,
+}
+
+impl TorrentSchedule {
+    pub fn is_empty(&self) -> bool {
+        self.start_at.is_none() && self.stop_at.is_none()
+    }
+
+    // Takes out whatever is due at `now`. A start and a stop both due make for a torrent
+    // that ends up stopped.
+    pub fn take_due(&mut self, now: SystemTime) -> Vec<ScheduledAction> {
+        let mut due = Vec::new();
+        if self.start_at.take_if(|at| *at <= now).is_some() {
+            due.push(ScheduledAction::Start);
+        }
+        if self.stop_at.take_if(|at| *at <= now).is_some() {
+            due.push(ScheduledAction::Stop);
+        }
+        due
+    }
+}
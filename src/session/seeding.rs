@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedingGoal {
+    Ratio,
+    SeedTime,
+    IdleTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoalAction {
+    // Stop seeding but keep the torrent in the session.
+    Pause,
+    Remove,
+}
+
+// Limits after which a complete torrent stops seeding. Unset limits never trigger.
+// Idle time counts from the last byte uploaded (or from completion if nothing was).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedingGoals {
+    pub ratio: Option<f64>,
+    pub seed_time: Option<Duration>,
+    pub idle_time: Option<Duration>,
+    pub action: GoalAction,
+}
+
+impl Default for SeedingGoals {
+    fn default() -> Self {
+        SeedingGoals {
+            ratio: None,
+            seed_time: None,
+            idle_time: None,
+            action: GoalAction::Pause,
+        }
+    }
+}
+
+impl SeedingGoals {
+    pub fn reached(
+        &self,
+        ratio: f64,
+        seeding_for: Duration,
+        idle_for: Duration,
+    ) -> Option<SeedingGoal> {
+        if self.ratio.is_some_and(|goal| ratio >= goal) {
+            Some(SeedingGoal::Ratio)
+        } else if self.seed_time.is_some_and(|goal| seeding_for >= goal) {
+            Some(SeedingGoal::SeedTime)
+        } else if self.idle_time.is_some_and(|goal| idle_for >= goal) {
+            Some(SeedingGoal::IdleTime)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// Per-torrent connection slots. Active peers transfer data; standby peers are kept
+// connected, choked and uninterested, with only keep-alives going over the wire, so they
+// can take over an active slot without a new connect and handshake. LAN peers may be let
+// in on top of both limits.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotKind {
+    Active,
+    Standby,
+    // Transfers like an active peer but doesn't count against the limits.
+    Lan,
+}
+
+impl SlotKind {
+    pub fn transfers(self) -> bool {
+        self != SlotKind::Standby
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PeerSlots {
+    active: usize,
+    standby: usize,
+    lan: usize,
+}
+
+impl PeerSlots {
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn standby(&self) -> usize {
+        self.standby
+    }
+
+    pub fn lan(&self) -> usize {
+        self.lan
+    }
+
+    pub fn acquire_lan(&mut self) -> SlotKind {
+        self.lan += 1;
+        SlotKind::Lan
+    }
+
+    pub fn acquire(&mut self, max_active: usize, max_standby: usize) -> Option<SlotKind> {
+        if self.active < max_active {
+            self.active += 1;
+            Some(SlotKind::Active)
+        } else if self.standby < max_standby {
+            self.standby += 1;
+            Some(SlotKind::Standby)
+        } else {
+            None
+        }
+    }
+
+    // Moves a standby peer into a free active slot, if there is one.
+    pub fn promote(&mut self, max_active: usize) -> bool {
+        if self.standby > 0 && self.active < max_active {
+            self.standby -= 1;
+            self.active += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn release(&mut self, kind: SlotKind) {
+        match kind {
+            SlotKind::Active => self.active = self.active.saturating_sub(1),
+            SlotKind::Standby => self.standby = self.standby.saturating_sub(1),
+            SlotKind::Lan => self.lan = self.lan.saturating_sub(1),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use super::annotate::PeerLabels;
+use super::slots::SlotKind;
+use crate::retry::CircuitBreaker;
+use crate::tracker::value::AnnounceTimings;
+use crate::units::{ByteSize, Rate};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net;
+use std::time::SystemTime;
+
+// Remote UIs poll the session every second or so. Instead of shipping the full peer and
+// torrent lists every time, a SnapshotDiffer remembers the previous poll and only hands
+// out what was added, removed or changed since then.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerSnapshot {
+    pub ip: net::Ipv4Addr,
+    pub port: u16,
+    pub id: Option<Vec<u8>>,
+    pub choked: bool,
+    pub interested: bool,
+    pub downloaded: ByteSize,
+    pub uploaded: ByteSize,
+}
+
+// A connected peer as its connection last saw it, for diagnostics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerState {
+    pub addr: net::SocketAddr,
+    pub peer_id: [u8; 20],
+    pub slot: SlotKind,
+    // Whether the fast extension is on for the connection.
+    pub fast: bool,
+    pub am_choking: bool,
+    pub am_interested: bool,
+    pub peer_choking: bool,
+    pub peer_interested: bool,
+    // Pieces the peer has told us it has.
+    pub pieces: usize,
+    pub incoming: bool,
+    // Bytes of good blocks from the peer, and bytes we sent it.
+    pub downloaded: ByteSize,
+    pub uploaded: ByteSize,
+    // Our block requests to the peer still waiting for an answer.
+    pub requests: usize,
+    pub labels: PeerLabels,
+}
+
+// A connected peer as a UI lists it, from Torrent::peers().
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerInfo {
+    pub addr: net::SocketAddr,
+    // Guessed from the peer id; None if it follows no known convention.
+    pub client: Option<String>,
+    pub flags: PeerFlags,
+    // Share of the torrent's pieces the peer has, 0.0 to 1.0.
+    pub progress: f64,
+    // Averaged over the session's last tick.
+    pub download_rate: Rate,
+    pub upload_rate: Rate,
+    pub downloaded: ByteSize,
+    pub uploaded: ByteSize,
+    pub requests: usize,
+    // Whatever the session's PeerAnnotator said about the address, e.g. its country.
+    pub labels: PeerLabels,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PeerFlags {
+    pub am_choking: bool,
+    pub am_interested: bool,
+    pub peer_choking: bool,
+    pub peer_interested: bool,
+    // Peer connections are plain TCP for now, so this stays false.
+    pub encrypted: bool,
+    pub incoming: bool,
+    pub fast: bool,
+}
+
+// What the last announce to a tracker told us. Counts stay None until a tracker reports
+// them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackerSnapshot {
+    pub url: String,
+    pub group: String,
+    // Whether the tracker's group is enabled, i.e. whether it's announced to.
+    pub enabled: bool,
+    pub seeders: Option<u32>,
+    pub leechers: Option<u32>,
+    // Times the torrent was completed, as reported by a scrape.
+    pub downloaded: Option<u32>,
+    pub interval: Option<u32>,
+    pub min_interval: Option<u32>,
+    pub tracker_id: Option<String>,
+    // The latest announces, oldest first, up to ANNOUNCE_HISTORY of them.
+    pub history: Vec<AnnounceRecord>,
+    // Consecutive failed announces and when the tracker may be tried again.
+    pub breaker: CircuitBreaker,
+    pub status: TrackerStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrackerStatus {
+    #[default]
+    NotContacted,
+    Working,
+    // The last announce failed; see the history for why.
+    Failing,
+    // Skipped for a URL scheme the client can't announce over, or an unparsable URL.
+    Unsupported,
+    // Skipped because the session's tracker filter rules it out.
+    Blocked,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnounceRecord {
+    pub at: SystemTime,
+    pub timings: AnnounceTimings,
+    // Why the announce failed, if it did.
+    pub error: Option<String>,
+}
+
+pub const ANNOUNCE_HISTORY: usize = 10;
+
+// The group of the metainfo's own announce URL.
+pub const DEFAULT_TRACKER_GROUP: &str = "default";
+
+impl TrackerSnapshot {
+    pub fn new(url: &str, group: &str) -> TrackerSnapshot {
+        TrackerSnapshot {
+            url: url.to_string(),
+            group: group.to_string(),
+            enabled: true,
+            seeders: None,
+            leechers: None,
+            downloaded: None,
+            interval: None,
+            min_interval: None,
+            tracker_id: None,
+            history: Vec::new(),
+            breaker: CircuitBreaker::new(),
+            status: TrackerStatus::NotContacted,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TorrentSnapshot {
+    pub info_hash: [u8; 20],
+    pub name: String,
+    pub total_size: ByteSize,
+    pub downloaded: ByteSize,
+    // Included in `downloaded`.
+    pub web_seed_downloaded: ByteSize,
+    pub uploaded: ByteSize,
+    pub peers: usize,
+    pub trackers: Vec<TrackerSnapshot>,
+}
+
+// Swarm health as seen through our connected peers. Unlike the snapshots this isn't
+// diffed; a UI asks for it when it shows a torrent's details.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TorrentStats {
+    // Replicas of each piece among connected peers.
+    pub piece_availability: Vec<u32>,
+    pub distributed_copies: f64,
+    pub seeds: usize,
+}
+
+pub trait Snapshot: Clone + PartialEq {
+    type Key: Eq + Hash + Clone;
+
+    fn key(&self) -> Self::Key;
+}
+
+impl Snapshot for PeerSnapshot {
+    type Key = (net::Ipv4Addr, u16);
+
+    fn key(&self) -> Self::Key {
+        (self.ip, self.port)
+    }
+}
+
+impl Snapshot for TorrentSnapshot {
+    type Key = [u8; 20];
+
+    fn key(&self) -> Self::Key {
+        self.info_hash
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotDiff<T: Snapshot> {
+    pub added: Vec<T>,
+    pub removed: Vec<T::Key>,
+    pub changed: Vec<T>,
+}
+
+impl<T: Snapshot> SnapshotDiff<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+pub fn diff<T: Snapshot>(old: &[T], new: &[T]) -> SnapshotDiff<T> {
+    let previous: HashMap<T::Key, &T> = old.iter().map(|s| (s.key(), s)).collect();
+    let current: HashMap<T::Key, &T> = new.iter().map(|s| (s.key(), s)).collect();
+
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+
+    for snapshot in new {
+        match previous.get(&snapshot.key()) {
+            None => added.push(snapshot.clone()),
+            Some(prev) if *prev != snapshot => changed.push(snapshot.clone()),
+            Some(_) => {}
+        }
+    }
+
+    let removed = old
+        .iter()
+        .map(|s| s.key())
+        .filter(|key| !current.contains_key(key))
+        .collect();
+
+    SnapshotDiff {
+        added,
+        removed,
+        changed,
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SnapshotDiffer<T: Snapshot> {
+    previous: Vec<T>,
+}
+
+impl<T: Snapshot> SnapshotDiffer<T> {
+    pub fn new() -> Self {
+        SnapshotDiffer {
+            previous: Vec::new(),
+        }
+    }
+
+    // Returns the changes since the last poll and remembers `current` for the next one.
+    // The first poll reports every entry as added.
+    pub fn poll(&mut self, current: Vec<T>) -> SnapshotDiff<T> {
+        let result = diff(&self.previous, &current);
+        self.previous = current;
+        result
+    }
+
+    // Forget the previous poll, e.g. when a UI reconnects and needs a full snapshot.
+    pub fn reset(&mut self) {
+        self.previous.clear();
+    }
+}
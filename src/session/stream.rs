@@ -0,0 +1,77 @@
+use super::torrent::Torrent;
+use std::io;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+// Reads a torrent's byte space front to back as pieces get verified, whatever backend
+// they're stored in. A read at a piece that isn't there yet waits for it, so the reader
+// sets the pace only as far as the download allows; a Sequential pick strategy gets the
+// pieces in the order they're read. Nothing unverified is ever returned.
+pub struct TorrentStream {
+    torrent: Arc<Torrent>,
+    position: u64,
+    end: u64,
+}
+
+impl TorrentStream {
+    pub fn new(torrent: Arc<Torrent>) -> TorrentStream {
+        let end = torrent.meta().total_size() as u64;
+        TorrentStream::range(torrent, 0..end)
+    }
+
+    // Just `range` of the byte space, e.g. one file's span; cut off at the torrent's end.
+    pub fn range(torrent: Arc<Torrent>, range: Range<u64>) -> TorrentStream {
+        let end = range.end.min(torrent.meta().total_size() as u64);
+        TorrentStream {
+            torrent,
+            position: range.start.min(end),
+            end,
+        }
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.end - self.position
+    }
+}
+
+impl AsyncRead for TorrentStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.position >= this.end || buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let meta = this.torrent.meta();
+        let piece_length = meta.info.piece_length as u64;
+        let index = (this.position / piece_length) as usize;
+        if !this.torrent.has_piece(index) {
+            this.torrent.register_reader(cx.waker());
+            // The piece may have come in before the waker was registered.
+            if !this.torrent.has_piece(index) {
+                return Poll::Pending;
+            }
+        }
+
+        let begin = this.position - index as u64 * piece_length;
+        let length = (meta.piece_size(index) as u64 - begin)
+            .min(this.end - this.position)
+            .min(buf.remaining() as u64);
+        let data = this
+            .torrent
+            .backend()
+            .read_block(index, begin as u32, length as u32)?;
+        buf.put_slice(&data);
+        this.position += length;
+        Poll::Ready(Ok(()))
+    }
+}
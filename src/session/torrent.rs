@@ -0,0 +1,1369 @@
+use super::alert::AlertLog;
+use super::bandwidth::{Bandwidth, RateLimits, TorrentPriority};
+use super::choker::Choker;
+use super::config::SessionConfig;
+use super::dht_state::DhtNode;
+use super::error::{SessionError, hex};
+use super::forensics::{self, FailedPiece};
+use super::handle::{TorrentState, TransferMode};
+use super::history::{HistoryEntry, StateChange, TORRENT_HISTORY};
+use super::lifecycle::AnnounceLifecycle;
+use super::metrics;
+use super::peer_pool::{PeerPool, PeerSource};
+use super::peer_task::{BLOCK_SIZE, PieceDownload};
+use super::priority::{FilePriority, wanted_pieces};
+use super::quarantine::Quarantine;
+use super::resume::{ResumeData, unix_time};
+use super::schedule::{ScheduledAction, TorrentSchedule};
+use super::seeding::SeedingGoals;
+use super::slots::PeerSlots;
+use super::snapshot::{
+    ANNOUNCE_HISTORY, AnnounceRecord, DEFAULT_TRACKER_GROUP, PeerFlags, PeerInfo, PeerState,
+    TorrentSnapshot, TorrentStats, TrackerSnapshot, TrackerStatus,
+};
+use super::transfer::TransferCounters;
+use crate::peer::client_id::client_name;
+use crate::peer::value::MAX_REQUEST_LENGTH;
+use crate::piece::bitfield::Bitfield;
+use crate::piece::hash::{HashVersion, Sha1Mode, sha1};
+use crate::piece::layers::PieceLayers;
+use crate::piece::merkle;
+use crate::piece::picker::PiecePicker;
+use crate::piece::strategy::PickStrategy;
+use crate::retry::RetryPolicy;
+use crate::storage::backend::StorageBackend;
+use crate::storage::disk_space::available_space;
+use crate::storage::file_storage::FileStorage;
+use crate::torrent::value::{TorrentMetaInfo, V2File};
+use crate::tracker::value::{ScrapeStats, TrackerResponse};
+use crate::units::{ByteSize, Rate};
+use crate::webseed::url_seed::UrlSeed;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::task::Waker;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{Span, debug, info_span, warn};
+
+// A peer's byte counts at the last rate sample, and the rates worked out then.
+struct PeerSample {
+    at: Instant,
+    downloaded: u64,
+    uploaded: u64,
+    download_rate: Rate,
+    upload_rate: Rate,
+}
+
+pub struct Torrent {
+    meta: TorrentMetaInfo,
+    info_hash: [u8; 20],
+    hash_mode: Sha1Mode,
+    storage: FileStorage,
+    // Where piece data goes instead of the files in `storage`, which then only describes
+    // the layout.
+    backend: Option<Box<dyn StorageBackend>>,
+    picker: Mutex<PiecePicker>,
+    slots: Mutex<PeerSlots>,
+    choker: Mutex<Choker>,
+    peer_states: Mutex<HashMap<SocketAddr, PeerState>>,
+    peer_pool: Mutex<PeerPool>,
+    // In announce order. The `enabled` flags are kept in step with disabled_groups.
+    trackers: Mutex<Vec<TrackerSnapshot>>,
+    disabled_groups: Mutex<HashSet<String>>,
+    // When the torrent last looked itself up in the DHT. Cleared while it isn't active, so
+    // it does so again as soon as it's resumed.
+    dht_lookup: Mutex<Option<Instant>>,
+    transfer: TransferCounters,
+    // Block requests we took back with a Cancel, mostly for pieces finished elsewhere.
+    cancelled_requests: AtomicU64,
+    // When and at what peer byte count the rate was last sampled, and the result.
+    peer_rate: Mutex<(Instant, u64, Rate)>,
+    // The same per connected peer, both ways.
+    peer_samples: Mutex<HashMap<SocketAddr, PeerSample>>,
+    web_seeds: Mutex<Vec<Arc<UrlSeed>>>,
+    paused: AtomicBool,
+    queued: AtomicBool,
+    // Set while check_files runs.
+    checking: AtomicBool,
+    // What stopped the torrent, until it's resumed.
+    error: Mutex<Option<String>>,
+    // Bytes needed and available when the torrent was last paused for lack of space.
+    space_shortage: Mutex<Option<(ByteSize, ByteSize)>>,
+    seeding_goals: Mutex<Option<SeedingGoals>>,
+    schedule: Mutex<TorrentSchedule>,
+    history: Mutex<VecDeque<HistoryEntry>>,
+    seeding_since: Mutex<Option<Instant>>,
+    last_upload: Mutex<Option<Instant>>,
+    // Completion times of downloaded pieces, for spotting unflushed ones after a crash.
+    completed_at: Mutex<HashMap<usize, SystemTime>>,
+    // Which hash each piece we've verified was checked against. Pieces restored from
+    // resume data without hashing aren't listed.
+    verified_with: Mutex<HashMap<usize, HashVersion>>,
+    piece_layers: Mutex<PieceLayers>,
+    resume_dirty: AtomicBool,
+    lifecycle: Mutex<AnnounceLifecycle>,
+    quarantine: Mutex<Quarantine>,
+    // Size of the blocks pieces are requested in.
+    block_size: AtomicU32,
+    // Where pieces that fail their hash check are written out, if anywhere.
+    forensics_dir: Mutex<Option<PathBuf>>,
+    // Pieces each peer address sent bad blocks for, as found by the quarantine.
+    hash_failures: Mutex<HashMap<IpAddr, u32>>,
+    // Partly downloaded pieces no peer is working on, by index.
+    parked: Mutex<HashMap<usize, PieceDownload>>,
+    // Streams waiting for a piece to be verified. Every new piece wakes them all.
+    readers: Mutex<Vec<Waker>>,
+    file_priorities: Mutex<Vec<FilePriority>>,
+    // Bumped on every priority change, so peer tasks know to drop unwanted requests.
+    priority_epoch: AtomicU64,
+    // DHT nodes our peers told us about in Port messages.
+    // When each was last announced, for persisting them with the session's DHT state.
+    dht_nodes: Mutex<HashMap<SocketAddr, SystemTime>>,
+    priority: Mutex<TorrentPriority>,
+    transfer_mode: Mutex<TransferMode>,
+    // This torrent's share of the session's rate limits, split up by priority each tick.
+    bandwidth: Bandwidth,
+    // Where alert-worthy history entries are copied to, once the session has set it.
+    alerts: OnceLock<Arc<AlertLog>>,
+    // Parent of the spans of the torrent's peer connections.
+    span: Span,
+}
+
+impl Torrent {
+    pub fn new(meta: TorrentMetaInfo, save_dir: &Path, hash_mode: Sha1Mode) -> Torrent {
+        let info_hash = meta.info_hash();
+        let storage = FileStorage::new(save_dir, &meta);
+        let picker = Mutex::new(PiecePicker::new(meta.num_pieces()));
+        let piece_layers = Mutex::new(PieceLayers::new(&meta));
+        let file_priorities = Mutex::new(vec![FilePriority::Normal; storage.files().len()]);
+        let trackers = vec![TrackerSnapshot::new(&meta.announce, DEFAULT_TRACKER_GROUP)];
+        let span = info_span!("torrent", name = %meta.info.name, info_hash = %hex(&info_hash));
+
+        Torrent {
+            meta,
+            info_hash,
+            hash_mode,
+            storage,
+            backend: None,
+            picker,
+            slots: Mutex::new(PeerSlots::default()),
+            choker: Mutex::new(Choker::default()),
+            peer_states: Mutex::new(HashMap::new()),
+            peer_pool: Mutex::new(PeerPool::default()),
+            trackers: Mutex::new(trackers),
+            disabled_groups: Mutex::new(HashSet::new()),
+            dht_lookup: Mutex::new(None),
+            transfer: TransferCounters::default(),
+            cancelled_requests: AtomicU64::new(0),
+            peer_rate: Mutex::new((Instant::now(), 0, Rate(0))),
+            peer_samples: Mutex::new(HashMap::new()),
+            web_seeds: Mutex::new(Vec::new()),
+            paused: AtomicBool::new(false),
+            queued: AtomicBool::new(false),
+            checking: AtomicBool::new(false),
+            error: Mutex::new(None),
+            space_shortage: Mutex::new(None),
+            seeding_goals: Mutex::new(None),
+            schedule: Mutex::new(TorrentSchedule::default()),
+            history: Mutex::new(VecDeque::new()),
+            seeding_since: Mutex::new(None),
+            last_upload: Mutex::new(None),
+            completed_at: Mutex::new(HashMap::new()),
+            verified_with: Mutex::new(HashMap::new()),
+            piece_layers,
+            resume_dirty: AtomicBool::new(false),
+            lifecycle: Mutex::new(AnnounceLifecycle::default()),
+            quarantine: Mutex::new(Quarantine::default()),
+            block_size: AtomicU32::new(BLOCK_SIZE),
+            forensics_dir: Mutex::new(None),
+            hash_failures: Mutex::new(HashMap::new()),
+            parked: Mutex::new(HashMap::new()),
+            readers: Mutex::new(Vec::new()),
+            file_priorities,
+            priority_epoch: AtomicU64::new(0),
+            dht_nodes: Mutex::new(HashMap::new()),
+            priority: Mutex::new(TorrentPriority::default()),
+            transfer_mode: Mutex::new(TransferMode::default()),
+            bandwidth: Bandwidth::new(RateLimits::default()),
+            alerts: OnceLock::new(),
+            span,
+        }
+    }
+
+    // A torrent whose data is read and written through `backend` rather than files on
+    // disk. Staging, moving, symlinks, md5 checks and deleting data don't apply to it.
+    pub fn with_backend(
+        meta: TorrentMetaInfo,
+        backend: Box<dyn StorageBackend>,
+        hash_mode: Sha1Mode,
+    ) -> std::io::Result<Torrent> {
+        if backend.len() != meta.total_size() as u64 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Backend holds {} bytes, the torrent {}",
+                    backend.len(),
+                    meta.total_size()
+                ),
+            ));
+        }
+        let mut torrent = Torrent::new(meta, Path::new(""), hash_mode);
+        torrent.backend = Some(backend);
+        Ok(torrent)
+    }
+
+    pub fn meta(&self) -> &TorrentMetaInfo {
+        &self.meta
+    }
+
+    pub fn info_hash(&self) -> [u8; 20] {
+        self.info_hash
+    }
+
+    pub fn name(&self) -> &str {
+        &self.meta.info.name
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn storage(&self) -> &FileStorage {
+        &self.storage
+    }
+
+    // What piece data is read from and written to: the custom backend if there is one,
+    // the files otherwise.
+    pub fn backend(&self) -> &dyn StorageBackend {
+        self.backend.as_deref().unwrap_or(&self.storage)
+    }
+
+    pub fn has_custom_backend(&self) -> bool {
+        self.backend.is_some()
+    }
+
+    pub fn bitfield(&self) -> Bitfield {
+        self.picker().have().clone()
+    }
+
+    pub fn has_piece(&self, index: usize) -> bool {
+        self.picker().have().has(index)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.picker().have().is_complete()
+    }
+
+    pub fn progress(&self) -> f64 {
+        let have = self.picker().have().clone();
+        if have.is_empty() {
+            return 1.0;
+        }
+        have.count() as f64 / have.len() as f64
+    }
+
+    // Bytes of wanted pieces we don't have yet.
+    pub fn left(&self) -> ByteSize {
+        let picker = self.picker();
+        let (have, wanted) = (picker.have().clone(), picker.wanted().clone());
+        drop(picker);
+        (0..have.len())
+            .filter(|&index| wanted.has(index) && !have.has(index))
+            .map(|index| ByteSize(self.meta.piece_size(index) as u64))
+            .sum()
+    }
+
+    // Moves file `index` to `new_path`, relative to the download directory, on disk and
+    // for every later read and write. Works at any point, also mid-download; the new name
+    // is kept in resume data.
+    pub fn rename_file(&self, index: usize, new_path: &Path) -> std::io::Result<()> {
+        self.require_files()?;
+        let Some(entry) = self.storage.files().get(index) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("No file {}", index),
+            ));
+        };
+        self.storage.rename_file(entry, new_path)?;
+        self.resume_dirty.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // Renames the directory the torrent's files go in, or the file of a single-file
+    // torrent.
+    pub fn rename_root(&self, new_name: &str) -> std::io::Result<()> {
+        self.require_files()?;
+        self.storage.rename_root(new_name)?;
+        self.resume_dirty.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn file_priorities(&self) -> Vec<FilePriority> {
+        self.file_priorities.lock().unwrap().clone()
+    }
+
+    // Takes effect right away: peers stop being asked for pieces nobody wants any more,
+    // and a newly skipped file is deleted unless some of its pieces are already complete.
+    pub fn set_file_priority(&self, file: usize, priority: FilePriority) -> std::io::Result<()> {
+        let mut priorities = self.file_priorities.lock().unwrap();
+        let Some(current) = priorities.get_mut(file) else {
+            return Ok(());
+        };
+        if *current == priority {
+            return Ok(());
+        }
+        *current = priority;
+        let wanted = wanted_pieces(&self.storage, &priorities);
+        drop(priorities);
+
+        let mut picker = self.picker();
+        picker.set_wanted(wanted);
+        let entry = &self.storage.files()[file];
+        let started = self
+            .storage
+            .pieces_of(entry)
+            .any(|index| picker.have().has(index));
+        drop(picker);
+        self.priority_epoch.fetch_add(1, Ordering::Relaxed);
+
+        if priority == FilePriority::Skip && !started && !self.has_custom_backend() {
+            self.storage.remove_file(entry)?;
+        }
+        Ok(())
+    }
+
+    pub fn priority(&self) -> TorrentPriority {
+        *self.priority.lock().unwrap()
+    }
+
+    // Shifts bandwidth from the next split on, i.e. within a tick.
+    pub fn set_priority(&self, priority: TorrentPriority) {
+        *self.priority.lock().unwrap() = priority;
+    }
+
+    pub fn transfer_mode(&self) -> TransferMode {
+        *self.transfer_mode.lock().unwrap()
+    }
+
+    pub fn set_transfer_mode(&self, mode: TransferMode) -> Result<(), SessionError> {
+        if mode == TransferMode::DownloadOnly && self.meta.info.is_private() {
+            return Err(SessionError::PrivateTorrent(self.info_hash));
+        }
+        *self.transfer_mode.lock().unwrap() = mode;
+        // Peer tasks look at the mode where they look at file priorities.
+        self.priority_epoch.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub(crate) fn downloads(&self) -> bool {
+        self.transfer_mode() != TransferMode::UploadOnly
+    }
+
+    pub(crate) fn uploads(&self) -> bool {
+        self.transfer_mode() != TransferMode::DownloadOnly
+    }
+
+    // The rates this torrent may use as of the last split. Unlimited while the session is.
+    pub fn rate_share(&self) -> RateLimits {
+        self.bandwidth.limits()
+    }
+
+    pub(crate) fn bandwidth(&self) -> &Bandwidth {
+        &self.bandwidth
+    }
+
+    pub fn is_wanted(&self, index: usize) -> bool {
+        self.picker().wanted().has(index)
+    }
+
+    pub(crate) fn priority_epoch(&self) -> u64 {
+        self.priority_epoch.load(Ordering::Relaxed)
+    }
+
+    // Candidates for bootstrapping a DHT routing table.
+    pub fn dht_nodes(&self) -> Vec<SocketAddr> {
+        self.dht_nodes.lock().unwrap().keys().copied().collect()
+    }
+
+    pub(crate) fn dht_node_sightings(&self) -> Vec<DhtNode> {
+        (self.dht_nodes.lock().unwrap().iter())
+            .map(|(&addr, &seen)| DhtNode {
+                addr,
+                last_seen: unix_time(seen),
+                id: None,
+            })
+            .collect()
+    }
+
+    pub(crate) fn add_dht_node(&self, node: SocketAddr) {
+        self.dht_nodes
+            .lock()
+            .unwrap()
+            .insert(node, SystemTime::now());
+    }
+
+    pub fn active_peers(&self) -> usize {
+        self.slots().active()
+    }
+
+    pub fn standby_peers(&self) -> usize {
+        self.slots().standby()
+    }
+
+    // Connected LAN peers exempt from the peer limits; not included in the two above.
+    pub fn lan_peers(&self) -> usize {
+        self.slots().lan()
+    }
+
+    // How many more peers we'd connect to right now; used as numwant when announcing.
+    pub fn peers_wanted(&self, config: &SessionConfig) -> u32 {
+        let slots = self.slots();
+        let capacity = config.max_active_peers + config.max_standby_peers;
+        capacity.saturating_sub(slots.active() + slots.standby()) as u32
+    }
+
+    pub fn transfer(&self) -> &TransferCounters {
+        &self.transfer
+    }
+
+    pub fn downloaded(&self) -> ByteSize {
+        self.transfer.downloaded()
+    }
+
+    pub fn web_seed_downloaded(&self) -> ByteSize {
+        self.transfer.web_seed_downloaded()
+    }
+
+    pub fn cancelled_requests(&self) -> u64 {
+        self.cancelled_requests.load(Ordering::Relaxed)
+    }
+
+    pub fn peer_downloaded(&self) -> ByteSize {
+        self.downloaded() - self.web_seed_downloaded()
+    }
+
+    // Download rate from peers alone, as of the last sample_peer_rate call.
+    pub fn peer_rate(&self) -> Rate {
+        self.peer_rate.lock().unwrap().2
+    }
+
+    // Averages the peer download rate over the time since the previous call.
+    pub fn sample_peer_rate(&self) -> Rate {
+        let mut sample = self.peer_rate.lock().unwrap();
+        let now = Instant::now();
+        let bytes = self.peer_downloaded().as_u64();
+        let elapsed = now.duration_since(sample.0).as_secs_f64();
+        if elapsed > 0.0 {
+            let rate = (bytes.saturating_sub(sample.1) as f64 / elapsed) as u64;
+            *sample = (now, bytes, Rate(rate));
+        }
+        drop(sample);
+        self.sample_peer_rates(now);
+        self.peer_rate()
+    }
+
+    fn sample_peer_rates(&self, now: Instant) {
+        let states = self.peer_states.lock().unwrap();
+        let mut samples = self.peer_samples.lock().unwrap();
+        samples.retain(|addr, _| states.contains_key(addr));
+        for state in states.values() {
+            let (downloaded, uploaded) = (state.downloaded.as_u64(), state.uploaded.as_u64());
+            let sample = samples.entry(state.addr).or_insert(PeerSample {
+                at: now,
+                downloaded,
+                uploaded,
+                download_rate: Rate(0),
+                upload_rate: Rate(0),
+            });
+            let elapsed = now.duration_since(sample.at).as_secs_f64();
+            if elapsed > 0.0 {
+                let rate = |bytes: u64, before: u64| {
+                    Rate((bytes.saturating_sub(before) as f64 / elapsed) as u64)
+                };
+                sample.download_rate = rate(downloaded, sample.downloaded);
+                sample.upload_rate = rate(uploaded, sample.uploaded);
+                sample.at = now;
+                sample.downloaded = downloaded;
+                sample.uploaded = uploaded;
+            }
+        }
+    }
+
+    pub fn web_seeds(&self) -> Vec<Arc<UrlSeed>> {
+        self.web_seeds.lock().unwrap().clone()
+    }
+
+    pub fn add_web_seed(&self, seed: UrlSeed) {
+        self.web_seeds.lock().unwrap().push(Arc::new(seed));
+    }
+
+    pub fn snapshot(&self) -> TorrentSnapshot {
+        TorrentSnapshot {
+            info_hash: self.info_hash,
+            name: self.name().to_string(),
+            total_size: ByteSize(self.storage.total_size()),
+            downloaded: self.downloaded(),
+            web_seed_downloaded: self.web_seed_downloaded(),
+            uploaded: self.uploaded(),
+            peers: self.active_peers() + self.standby_peers() + self.lan_peers(),
+            trackers: self.trackers(),
+        }
+    }
+
+    pub fn stats(&self) -> TorrentStats {
+        let picker = self.picker();
+        let availability = picker.availability();
+        TorrentStats {
+            piece_availability: availability.counts().to_vec(),
+            distributed_copies: availability.distributed_copies(),
+            seeds: availability.seeds(),
+        }
+    }
+
+    pub fn trackers(&self) -> Vec<TrackerSnapshot> {
+        self.trackers.lock().unwrap().clone()
+    }
+
+    // Adds a tracker to `group`, which is created on first use. Returns false if the URL
+    // is already one of the torrent's trackers.
+    pub fn add_tracker(&self, url: &str, group: &str) -> bool {
+        let mut trackers = self.trackers.lock().unwrap();
+        if trackers.iter().any(|tracker| tracker.url == url) {
+            return false;
+        }
+        let mut tracker = TrackerSnapshot::new(url, group);
+        tracker.enabled = !self.disabled_groups.lock().unwrap().contains(group);
+        trackers.push(tracker);
+        true
+    }
+
+    // Trackers in a disabled group are skipped by Session::announce until it's enabled
+    // again. Groups without trackers can be toggled too, for trackers added later.
+    pub fn set_tracker_group_enabled(&self, group: &str, enabled: bool) {
+        let mut trackers = self.trackers.lock().unwrap();
+        let mut disabled_groups = self.disabled_groups.lock().unwrap();
+        if enabled {
+            disabled_groups.remove(group);
+        } else {
+            disabled_groups.insert(group.to_string());
+        }
+        for tracker in trackers.iter_mut().filter(|tracker| tracker.group == group) {
+            tracker.enabled = enabled;
+        }
+    }
+
+    pub fn is_tracker_group_enabled(&self, group: &str) -> bool {
+        !self.disabled_groups.lock().unwrap().contains(group)
+    }
+
+    pub fn uploaded(&self) -> ByteSize {
+        self.transfer.uploaded()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    // Paused torrents refuse new connections; running peer tasks wind down on their next
+    // message.
+    // A torrent paused for lack of space stays paused once there's room again.
+    pub fn pause(&self) {
+        *self.space_shortage.lock().unwrap() = None;
+        self.stop();
+    }
+
+    fn stop(&self) {
+        if !self.paused.swap(true, Ordering::Relaxed) {
+            self.record(StateChange::Stopped);
+        }
+    }
+
+    // Also clears an error, for another try. Stays paused if there isn't enough space.
+    pub fn resume(&self) {
+        *self.error.lock().unwrap() = None;
+        if !self.check_space() {
+            return;
+        }
+        if self.paused.swap(false, Ordering::Relaxed) {
+            self.record(StateChange::Started);
+        }
+    }
+
+    // Whether the destination has room for what's left to download. If it hasn't, the
+    // torrent is paused in the InsufficientSpace state. Space that can't be determined
+    // counts as enough.
+    pub fn check_space(&self) -> bool {
+        let needed = self.left();
+        let available = match available_space(&self.storage.current_root()) {
+            Ok(available) if !self.has_custom_backend() => ByteSize(available),
+            _ => ByteSize(u64::MAX),
+        };
+        if available >= needed {
+            *self.space_shortage.lock().unwrap() = None;
+            return true;
+        }
+
+        let previous = self
+            .space_shortage
+            .lock()
+            .unwrap()
+            .replace((needed, available));
+        if previous.is_none() {
+            warn!(parent: &self.span, %needed, %available, "not enough disk space");
+            self.record(StateChange::InsufficientSpace { needed, available });
+        }
+        self.stop();
+        false
+    }
+
+    pub(crate) fn lacks_space(&self) -> bool {
+        self.space_shortage.lock().unwrap().is_some()
+    }
+
+    // State changes, oldest first, up to TORRENT_HISTORY of them.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.history.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub(crate) fn set_alert_log(&self, alerts: Arc<AlertLog>) {
+        let _ = self.alerts.set(alerts);
+    }
+
+    pub(crate) fn record(&self, change: StateChange) {
+        if let Some(alerts) = self.alerts.get() {
+            alerts.push_change(self.info_hash, &change);
+        }
+        let mut history = self.history.lock().unwrap();
+        if history.len() == TORRENT_HISTORY {
+            history.pop_front();
+        }
+        history.push_back(HistoryEntry {
+            at: SystemTime::now(),
+            change,
+        });
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+
+    // Pauses the torrent over something that needs the user's attention.
+    pub(crate) fn set_error(&self, error: String) {
+        warn!(parent: &self.span, %error, "torrent stopped");
+        *self.error.lock().unwrap() = Some(error.clone());
+        self.record(StateChange::Error(error));
+        self.pause();
+    }
+
+    pub fn state(&self) -> TorrentState {
+        if let Some(error) = self.error() {
+            TorrentState::Error(error)
+        } else if let Some((needed, available)) = *self.space_shortage.lock().unwrap() {
+            TorrentState::InsufficientSpace { needed, available }
+        } else if self.checking.load(Ordering::Relaxed) {
+            TorrentState::CheckingFiles
+        } else if self.is_paused() {
+            TorrentState::Paused
+        } else if self.is_queued() {
+            TorrentState::Queued
+        } else if self.is_complete() {
+            TorrentState::Seeding
+        } else {
+            TorrentState::Downloading
+        }
+    }
+
+    // Waiting for an active download or seed slot in the session's queue.
+    pub fn is_queued(&self) -> bool {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    // Whether a DHT lookup is due, i.e. none ran within `interval`. If so, it's recorded as
+    // started now.
+    pub(crate) fn begin_dht_lookup(&self, now: Instant, interval: Duration) -> bool {
+        let mut last = self.dht_lookup.lock().unwrap();
+        let due = last.is_none_or(|last| now.duration_since(last) >= interval);
+        if due {
+            *last = Some(now);
+        }
+        due
+    }
+
+    pub(crate) fn forget_dht_lookup(&self) {
+        *self.dht_lookup.lock().unwrap() = None;
+    }
+
+    // Neither paused nor queued, i.e. allowed to have peer connections.
+    pub fn is_active(&self) -> bool {
+        !self.is_paused() && !self.is_queued()
+    }
+
+    // Per-torrent goals override the session-wide ones; None falls back to those.
+    pub fn seeding_goals(&self) -> Option<SeedingGoals> {
+        self.seeding_goals.lock().unwrap().clone()
+    }
+
+    pub fn set_seeding_goals(&self, goals: Option<SeedingGoals>) {
+        *self.seeding_goals.lock().unwrap() = goals;
+    }
+
+    pub fn schedule(&self) -> TorrentSchedule {
+        *self.schedule.lock().unwrap()
+    }
+
+    // A start in the future pauses the torrent until then; the session's tick resumes it.
+    pub fn set_schedule(&self, schedule: TorrentSchedule) {
+        if schedule.start_at.is_some_and(|at| at > SystemTime::now()) {
+            self.pause();
+        }
+        *self.schedule.lock().unwrap() = schedule;
+        self.resume_dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub fn schedule_start(&self, at: SystemTime) {
+        self.set_schedule(TorrentSchedule {
+            start_at: Some(at),
+            ..self.schedule()
+        });
+    }
+
+    pub fn schedule_stop(&self, at: SystemTime) {
+        self.set_schedule(TorrentSchedule {
+            stop_at: Some(at),
+            ..self.schedule()
+        });
+    }
+
+    pub fn schedule_stop_after(&self, duration: Duration) {
+        self.schedule_stop(SystemTime::now() + duration);
+    }
+
+    // Starts or stops the torrent for whatever in its schedule is due.
+    pub(crate) fn apply_schedule(&self, now: SystemTime) -> Vec<ScheduledAction> {
+        let due = self.schedule.lock().unwrap().take_due(now);
+        for action in &due {
+            match action {
+                ScheduledAction::Start => self.resume(),
+                ScheduledAction::Stop => self.pause(),
+            }
+        }
+        if !due.is_empty() {
+            self.resume_dirty.store(true, Ordering::Relaxed);
+        }
+        due
+    }
+
+    pub fn ratio(&self) -> f64 {
+        let total = self.meta.total_size();
+        if total == 0 {
+            return 0.0;
+        }
+        self.uploaded().as_u64() as f64 / total as f64
+    }
+
+    // Time since the torrent became complete, or None while still downloading.
+    pub fn seeding_time(&self) -> Option<Duration> {
+        self.seeding_since
+            .lock()
+            .unwrap()
+            .map(|since| since.elapsed())
+    }
+
+    // Time since we last uploaded anything while seeding.
+    pub fn idle_time(&self) -> Option<Duration> {
+        let since = (*self.last_upload.lock().unwrap()).or(*self.seeding_since.lock().unwrap());
+        since.map(|since| since.elapsed())
+    }
+
+    pub fn verify_piece(&self, index: usize, data: &[u8]) -> bool {
+        self.check_piece(index, data).is_some()
+    }
+
+    // Checks a piece against its v2 tree where the torrent has one, and against the v1
+    // SHA-1 otherwise. A v2 mismatch is final; there's no retrying with the weaker hash.
+    // A piece that triggers collision detection fails like any other bad data.
+    pub fn check_piece(&self, index: usize, data: &[u8]) -> Option<HashVersion> {
+        let layer_hash = |file: &V2File, n| self.piece_layers().hash(&file.pieces_root, n);
+        if let Some(piece) = self.meta.v2_piece_with(index, layer_hash) {
+            let file_data = data.get(..piece.length)?;
+            let root = merkle::root(&merkle::block_hashes(file_data), piece.leaves);
+            return (root == piece.hash).then_some(HashVersion::V2);
+        }
+        match sha1(data, self.hash_mode) {
+            Ok(hash) => {
+                (self.meta.info.pieces.get(index) == Some(&hash)).then_some(HashVersion::V1)
+            }
+            Err(_) => None,
+        }
+    }
+
+    // v2 files longer than a piece whose piece layer is still to be fetched from peers.
+    // Until then their pieces are checked against SHA-1.
+    pub fn missing_piece_layers(&self) -> usize {
+        self.piece_layers().missing()
+    }
+
+    pub(crate) fn piece_layers(&self) -> MutexGuard<'_, PieceLayers> {
+        self.piece_layers.lock().unwrap()
+    }
+
+    pub fn hash_failures(&self, ip: IpAddr) -> u32 {
+        self.hash_failures
+            .lock()
+            .unwrap()
+            .get(&ip)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn is_quarantined(&self, index: usize) -> bool {
+        self.quarantine.lock().unwrap().contains(index)
+    }
+
+    // Bytes received so far for a piece no peer is currently downloading.
+    pub fn parked_bytes(&self, index: usize) -> Option<usize> {
+        self.parked
+            .lock()
+            .unwrap()
+            .get(&index)
+            .map(PieceDownload::received_bytes)
+    }
+
+    // Hands a piece back to the picker, keeping whatever blocks have arrived so the next
+    // peer to pick it only fetches the rest.
+    pub(crate) fn park_download(&self, mut download: PieceDownload) {
+        let index = download.index();
+        // An endgame duplicate of a piece someone else already finished.
+        if self.has_piece(index) {
+            return;
+        }
+        download.unrequest_all();
+        if download.received_bytes() > 0 {
+            self.parked.lock().unwrap().insert(index, download);
+        }
+        self.picker().abandon(index);
+    }
+
+    pub(crate) fn take_parked(&self, index: usize) -> Option<PieceDownload> {
+        self.parked.lock().unwrap().remove(&index)
+    }
+
+    pub fn verified_with(&self, index: usize) -> Option<HashVersion> {
+        self.verified_with.lock().unwrap().get(&index).copied()
+    }
+
+    // Verifies a complete piece and, if it checks out, writes it and marks it as done.
+    pub fn store_piece(&self, index: usize, data: &[u8]) -> std::io::Result<bool> {
+        self.store_piece_from(index, data, &[])
+    }
+
+    // Same as store_piece for a piece assembled from peer blocks; `sources` holds the peer
+    // each block came from. A bad piece goes into quarantine and is downloaded again from
+    // someone else. Once a good copy is in, whoever sent blocks that differ from it gets a
+    // hash failure.
+    pub fn store_piece_from(
+        &self,
+        index: usize,
+        data: &[u8],
+        sources: &[SocketAddr],
+    ) -> std::io::Result<bool> {
+        let Some(version) = self.check_piece(index, data) else {
+            warn!(index, ?sources, "piece failed its hash check");
+            metrics::hash_failure();
+            if !sources.is_empty() {
+                self.quarantine
+                    .lock()
+                    .unwrap()
+                    .add_failed(index, data, sources);
+                self.picker().avoid(index, sources.iter().copied());
+            }
+            self.write_forensics(index, data, sources);
+            return Ok(false);
+        };
+        self.backend().write_block(index, 0, data)?;
+        debug!(index, ?version, "piece verified");
+        metrics::piece_verified();
+        self.parked.lock().unwrap().remove(&index);
+        let offenders = self.quarantine.lock().unwrap().resolve(index, data);
+        let mut hash_failures = self.hash_failures.lock().unwrap();
+        for peer in offenders {
+            warn!(index, %peer, "peer sent a bad block");
+            *hash_failures.entry(peer.ip()).or_default() += 1;
+        }
+        drop(hash_failures);
+        self.verified_with.lock().unwrap().insert(index, version);
+        self.picker().on_piece_done(index);
+        self.wake_readers();
+        self.completed_at
+            .lock()
+            .unwrap()
+            .insert(index, SystemTime::now());
+        self.resume_dirty.store(true, Ordering::Relaxed);
+        if self.mark_seeding_if_complete() {
+            self.lifecycle().on_download_complete();
+            self.record(StateChange::Completed);
+        }
+        Ok(true)
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.block_size.load(Ordering::Relaxed)
+    }
+
+    // Meant to be set before any piece is requested: pieces already quarantined are
+    // forgotten, as their blocks no longer line up.
+    pub fn set_block_size(&self, size: u32) {
+        let size = size.clamp(1, MAX_REQUEST_LENGTH);
+        self.block_size.store(size, Ordering::Relaxed);
+        *self.quarantine.lock().unwrap() = Quarantine::new(size);
+    }
+
+    pub fn forensics_dir(&self) -> Option<PathBuf> {
+        self.forensics_dir.lock().unwrap().clone()
+    }
+
+    // Failed pieces are kept below `dir` from now on, see forensics::write_failed_piece.
+    pub fn set_forensics_dir(&self, dir: Option<PathBuf>) {
+        *self.forensics_dir.lock().unwrap() = dir;
+    }
+
+    fn write_forensics(&self, index: usize, data: &[u8], sources: &[SocketAddr]) {
+        let Some(dir) = self.forensics_dir() else {
+            return;
+        };
+        let peer_ids: HashMap<SocketAddr, [u8; 20]> = self
+            .peer_states()
+            .into_iter()
+            .map(|peer| (peer.addr, peer.peer_id))
+            .collect();
+        let piece = FailedPiece {
+            info_hash: self.info_hash,
+            index,
+            expected: self.meta.info.pieces.get(index).copied(),
+            data,
+            block_size: self.block_size(),
+            sources: sources
+                .iter()
+                .map(|addr| (*addr, peer_ids.get(addr).copied()))
+                .collect(),
+        };
+        match forensics::write_failed_piece(&dir, &piece) {
+            Ok(path) => warn!(index, path = %path.display(), "failed piece written out"),
+            Err(err) => warn!(index, error = %err, "couldn't write out failed piece"),
+        }
+    }
+
+    // Hashes whatever is already on disk and marks the pieces that check out as done.
+    // Missing or short files simply leave their pieces unmarked.
+    pub fn check_files(&self) {
+        self.checking.store(true, Ordering::Relaxed);
+        for index in 0..self.meta.num_pieces() {
+            if self.check_piece_on_disk(index) {
+                self.picker().on_piece_done(index);
+            }
+        }
+        self.checking.store(false, Ordering::Relaxed);
+        self.wake_readers();
+        self.record(StateChange::Checked {
+            have: self.bitfield().count(),
+            pieces: self.meta.num_pieces(),
+        });
+        self.mark_seeding_if_complete();
+    }
+
+    // Hashes only the pieces overlapping file `index`, e.g. after the file was edited or
+    // replaced outside the client. Pieces that no longer match are marked missing and
+    // fetched again; pieces that now match count as done. Returns the indexes of the
+    // pieces that were lost.
+    pub fn recheck_file(&self, index: usize) -> std::io::Result<Vec<usize>> {
+        let Some(entry) = self.storage.files().get(index) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("No file {}", index),
+            ));
+        };
+        Ok(self.recheck_pieces(self.storage.pieces_of(entry)))
+    }
+
+    // The same for an arbitrary set of pieces. A piece still being downloaded that doesn't
+    // check out is left to its download.
+    pub fn recheck_pieces(&self, pieces: impl IntoIterator<Item = usize>) -> Vec<usize> {
+        self.checking.store(true, Ordering::Relaxed);
+        let mut lost = Vec::new();
+        for index in pieces
+            .into_iter()
+            .filter(|&index| index < self.meta.num_pieces())
+        {
+            if self.check_piece_on_disk(index) {
+                self.picker().on_piece_done(index);
+            } else if self.has_piece(index) {
+                self.picker().on_piece_lost(index);
+                self.verified_with.lock().unwrap().remove(&index);
+                self.completed_at.lock().unwrap().remove(&index);
+                lost.push(index);
+            }
+        }
+        self.checking.store(false, Ordering::Relaxed);
+        self.wake_readers();
+
+        if !lost.is_empty() {
+            warn!(pieces = ?lost, "pieces failed recheck");
+            *self.seeding_since.lock().unwrap() = None;
+            self.priority_epoch.fetch_add(1, Ordering::Relaxed);
+        }
+        self.resume_dirty.store(true, Ordering::Relaxed);
+        self.mark_seeding_if_complete();
+        lost
+    }
+
+    // Compares every file that carries an md5sum against its contents. Returns the paths
+    // of files that differ or couldn't be read.
+    pub fn check_md5sums(&self) -> Vec<PathBuf> {
+        if self.has_custom_backend() {
+            return Vec::new();
+        }
+        self.storage
+            .files()
+            .iter()
+            .filter(|entry| {
+                entry.md5sum.as_ref().is_some_and(|expected| {
+                    self.storage
+                        .file_md5(entry)
+                        .map_or(true, |actual| !actual.eq_ignore_ascii_case(expected))
+                })
+            })
+            .map(|entry| self.storage.file_path(entry))
+            .collect()
+    }
+
+    pub fn resume_data(&self, flush_window: Duration) -> ResumeData {
+        let now = SystemTime::now();
+        let mut completed_at = self.completed_at.lock().unwrap();
+        // Anything older than the window has been written back by now and needn't be kept.
+        completed_at
+            .retain(|_, at| now.duration_since(*at).unwrap_or(Duration::ZERO) <= flush_window);
+
+        let mut recent: Vec<(usize, u64)> = completed_at
+            .iter()
+            .map(|(index, at)| (*index, unix_time(*at)))
+            .collect();
+        recent.sort_unstable();
+
+        ResumeData {
+            info_hash: self.info_hash,
+            pieces: self.bitfield().as_bytes().to_vec(),
+            saved_at: unix_time(now),
+            recent,
+            schedule: self.schedule(),
+            root_name: Some(self.storage.root_name())
+                .filter(|root| root.as_os_str() != self.meta.info.name.as_str()),
+            renamed: self.storage.renamed(),
+        }
+    }
+
+    // Marks the pieces from resume data as done without hashing them, except those that
+    // may not have reached the disk before an unclean shutdown. Pieces whose files are
+    // missing or too short are left out. Returns false (and marks nothing) if the data
+    // doesn't belong to this torrent; the caller should run check_files instead.
+    pub fn restore(&self, resume: &ResumeData, unclean: bool, flush_window: Duration) -> bool {
+        let num_pieces = self.meta.num_pieces();
+        if resume.info_hash != self.info_hash || resume.pieces.len() != num_pieces.div_ceil(8) {
+            return false;
+        }
+        if !resume.schedule.is_empty() {
+            self.set_schedule(resume.schedule);
+        }
+        self.storage
+            .restore_names(resume.root_name.as_deref(), &resume.renamed);
+
+        let suspects = if unclean {
+            resume.suspect_pieces(flush_window)
+        } else {
+            Vec::new()
+        };
+        let have = Bitfield::from_bytes(&resume.pieces, num_pieces);
+        for index in (0..num_pieces).filter(|&index| have.has(index)) {
+            let offset = index as u64 * self.meta.info.piece_length as u64;
+            let length = self.meta.piece_size(index) as u64;
+            let trusted = if suspects.contains(&index) || self.has_custom_backend() {
+                self.check_piece_on_disk(index)
+            } else {
+                self.storage.is_range_present(offset, length)
+            };
+            if trusted {
+                self.picker().on_piece_done(index);
+            }
+        }
+        self.wake_readers();
+        self.mark_seeding_if_complete();
+        true
+    }
+
+    // Whether resume data changed since the last call.
+    pub(crate) fn take_resume_dirty(&self) -> bool {
+        self.resume_dirty.swap(false, Ordering::Relaxed)
+    }
+
+    // Marks the pieces set in `have` as done without looking at the disk, for verification
+    // results from a source we trust (a backup, another client's state). Pieces we already
+    // have stay. Returns false, marking nothing, if the bitfield is for a different number
+    // of pieces.
+    pub fn import_bitfield(&self, have: &Bitfield) -> bool {
+        if have.len() != self.meta.num_pieces() {
+            return false;
+        }
+        let mut picker = self.picker();
+        for index in (0..have.len()).filter(|&index| have.has(index)) {
+            picker.on_piece_done(index);
+        }
+        drop(picker);
+        self.wake_readers();
+        self.resume_dirty.store(true, Ordering::Relaxed);
+        self.mark_seeding_if_complete();
+        true
+    }
+
+    fn check_piece_on_disk(&self, index: usize) -> bool {
+        let version = self
+            .backend()
+            .read_block(index, 0, self.meta.piece_size(index) as u32)
+            .ok()
+            .and_then(|data| self.check_piece(index, &data));
+        let Some(version) = version else {
+            return false;
+        };
+        self.verified_with.lock().unwrap().insert(index, version);
+        true
+    }
+
+    // Returns true if the torrent just became complete.
+    fn mark_seeding_if_complete(&self) -> bool {
+        let mut seeding_since = self.seeding_since.lock().unwrap();
+        let completed = seeding_since.is_none() && self.is_complete();
+        if completed {
+            *seeding_since = Some(Instant::now());
+            if let Some(backend) = &self.backend {
+                if let Err(error) = backend.flush() {
+                    warn!(parent: &self.span, %error, "couldn't flush storage backend");
+                }
+            } else if let Err(error) = self.storage.create_symlinks() {
+                warn!(parent: &self.span, %error, "couldn't create symlinks");
+            }
+        }
+        completed
+    }
+
+    pub(crate) fn register_reader(&self, waker: &Waker) {
+        let mut readers = self.readers.lock().unwrap();
+        if !readers.iter().any(|reader| reader.will_wake(waker)) {
+            readers.push(waker.clone());
+        }
+    }
+
+    fn wake_readers(&self) {
+        let readers = std::mem::take(&mut *self.readers.lock().unwrap());
+        readers.into_iter().for_each(Waker::wake);
+    }
+
+    fn require_files(&self) -> std::io::Result<()> {
+        match self.has_custom_backend() {
+            true => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Torrent data isn't kept in files",
+            )),
+            false => Ok(()),
+        }
+    }
+
+    pub(crate) fn set_queued(&self, queued: bool) {
+        self.queued.store(queued, Ordering::Relaxed);
+    }
+
+    pub(crate) fn picker(&self) -> MutexGuard<'_, PiecePicker> {
+        self.picker.lock().unwrap()
+    }
+
+    // Downloads in the order `strategy` picks from now on, in place of rarest-first and
+    // seed-friendly ordering. Pieces already being fetched carry on.
+    pub fn set_pick_strategy(&self, strategy: Box<dyn PickStrategy>) {
+        self.picker().set_strategy(strategy);
+    }
+
+    pub fn clear_pick_strategy(&self) {
+        self.picker().clear_strategy();
+    }
+
+    // Where the torrent stands with its trackers: started, completion reported, stopping.
+    pub fn announce_lifecycle(&self) -> AnnounceLifecycle {
+        self.lifecycle().clone()
+    }
+
+    pub(crate) fn lifecycle(&self) -> MutexGuard<'_, AnnounceLifecycle> {
+        self.lifecycle.lock().unwrap()
+    }
+
+    pub(crate) fn slots(&self) -> MutexGuard<'_, PeerSlots> {
+        self.slots.lock().unwrap()
+    }
+
+    pub(crate) fn choker(&self) -> MutexGuard<'_, Choker> {
+        self.choker.lock().unwrap()
+    }
+
+    // Peers holding an upload slot as of the last choke round.
+    pub fn unchoked_peers(&self) -> Vec<SocketAddr> {
+        self.choker().unchoked()
+    }
+
+    // Connected peers, ordered by address.
+    pub fn peer_states(&self) -> Vec<PeerState> {
+        let mut peers: Vec<PeerState> =
+            self.peer_states.lock().unwrap().values().cloned().collect();
+        peers.sort_by_key(|peer| peer.addr);
+        peers
+    }
+
+    // Every connection with what a peer list shows: client, flags, progress, rates and
+    // outstanding requests. Rates are as of the session's last tick; ordered by address.
+    pub fn peers(&self) -> Vec<PeerInfo> {
+        let num_pieces = self.meta.num_pieces().max(1);
+        let samples = self.peer_samples.lock().unwrap();
+        self.peer_states()
+            .into_iter()
+            .map(|state| {
+                let sample = samples.get(&state.addr);
+                PeerInfo {
+                    addr: state.addr,
+                    client: client_name(&state.peer_id),
+                    flags: PeerFlags {
+                        am_choking: state.am_choking,
+                        am_interested: state.am_interested,
+                        peer_choking: state.peer_choking,
+                        peer_interested: state.peer_interested,
+                        encrypted: false,
+                        incoming: state.incoming,
+                        fast: state.fast,
+                    },
+                    progress: state.pieces as f64 / num_pieces as f64,
+                    download_rate: sample.map_or(Rate(0), |sample| sample.download_rate),
+                    upload_rate: sample.map_or(Rate(0), |sample| sample.upload_rate),
+                    downloaded: state.downloaded,
+                    uploaded: state.uploaded,
+                    requests: state.requests,
+                    labels: state.labels,
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn peer_pool(&self) -> MutexGuard<'_, PeerPool> {
+        self.peer_pool.lock().unwrap()
+    }
+
+    // Addresses heard of so far, connected or not.
+    pub fn known_peers(&self) -> usize {
+        self.peer_pool().len()
+    }
+
+    pub fn peer_source_counts(&self) -> HashMap<PeerSource, usize> {
+        self.peer_pool().source_counts()
+    }
+
+    pub(crate) fn update_peer_state(&self, state: PeerState) {
+        self.peer_states.lock().unwrap().insert(state.addr, state);
+    }
+
+    pub(crate) fn remove_peer_state(&self, addr: SocketAddr) {
+        self.peer_states.lock().unwrap().remove(&addr);
+    }
+
+    // Counts a tracker leaves out keep their previous value rather than going blank.
+    pub(crate) fn record_announce(&self, url: &str, response: &TrackerResponse) {
+        let mut trackers = self.trackers.lock().unwrap();
+        let Some(tracker) = trackers.iter_mut().find(|tracker| tracker.url == url) else {
+            return;
+        };
+        tracker.breaker.on_success();
+        tracker.status = TrackerStatus::Working;
+        tracker.interval = Some(response.interval);
+        tracker.min_interval = response.min_interval;
+        tracker.seeders = response.complete.or(tracker.seeders);
+        tracker.leechers = response.incomplete.or(tracker.leechers);
+        if response.tracker_id.is_some() {
+            tracker.tracker_id = response.tracker_id.clone();
+        }
+    }
+
+    pub(crate) fn record_tracker_failure(&self, url: &str, policy: &RetryPolicy) {
+        let mut trackers = self.trackers.lock().unwrap();
+        if let Some(tracker) = trackers.iter_mut().find(|tracker| tracker.url == url) {
+            tracker.breaker.on_failure(policy, Instant::now());
+            tracker.status = TrackerStatus::Failing;
+        }
+    }
+
+    pub(crate) fn set_tracker_status(&self, url: &str, status: TrackerStatus) {
+        let mut trackers = self.trackers.lock().unwrap();
+        if let Some(tracker) = trackers.iter_mut().find(|tracker| tracker.url == url) {
+            tracker.status = status;
+        }
+    }
+
+    pub(crate) fn record_announce_attempt(&self, url: &str, record: AnnounceRecord) {
+        let mut trackers = self.trackers.lock().unwrap();
+        let Some(tracker) = trackers.iter_mut().find(|tracker| tracker.url == url) else {
+            return;
+        };
+        if tracker.history.len() == ANNOUNCE_HISTORY {
+            tracker.history.remove(0);
+        }
+        let error = record.error.clone();
+        tracker.history.push(record);
+        drop(trackers);
+        if let Some(error) = error {
+            self.record(StateChange::TrackerError {
+                url: url.to_string(),
+                error,
+            });
+        }
+    }
+
+    pub(crate) fn record_scrape(&self, url: &str, stats: &ScrapeStats) {
+        let mut trackers = self.trackers.lock().unwrap();
+        if let Some(tracker) = trackers.iter_mut().find(|tracker| tracker.url == url) {
+            tracker.seeders = Some(stats.complete);
+            tracker.leechers = Some(stats.incomplete);
+            tracker.downloaded = Some(stats.downloaded);
+        }
+    }
+
+    // Totals carried over from another session, e.g. by Session::import_torrent.
+    pub(crate) fn restore_totals(&self, downloaded: u64, uploaded: u64) {
+        self.transfer.add_downloaded(downloaded);
+        self.transfer.add_uploaded(uploaded);
+    }
+
+    pub(crate) fn add_cancelled_requests(&self, requests: u64) {
+        self.cancelled_requests
+            .fetch_add(requests, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_downloaded(&self, bytes: u64) {
+        self.transfer.add_downloaded(bytes);
+        metrics::add_downloaded(bytes);
+    }
+
+    // Counts towards downloaded() as well.
+    pub(crate) fn add_web_seed_downloaded(&self, bytes: u64) {
+        self.transfer.add_web_seed_downloaded(bytes);
+        metrics::add_downloaded(bytes);
+    }
+
+    pub(crate) fn add_uploaded(&self, bytes: u64) {
+        self.transfer.add_uploaded(bytes);
+        metrics::add_uploaded(bytes);
+        *self.last_upload.lock().unwrap() = Some(Instant::now());
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::units::ByteSize;
+
+// Payload bytes a torrent has moved: piece data received from peers and web seeds, and
+// piece data sent. Handshakes, requests and other protocol overhead aren't counted.
+#[derive(Debug, Default)]
+pub struct TransferCounters {
+    downloaded: AtomicU64,
+    web_seed_downloaded: AtomicU64,
+    uploaded: AtomicU64,
+    // Totals when the last started announce went out.
+    started_at: Mutex<TransferTotals>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferTotals {
+    pub downloaded: ByteSize,
+    pub uploaded: ByteSize,
+}
+
+impl TransferCounters {
+    pub fn totals(&self) -> TransferTotals {
+        TransferTotals {
+            downloaded: self.downloaded(),
+            uploaded: self.uploaded(),
+        }
+    }
+
+    pub fn downloaded(&self) -> ByteSize {
+        ByteSize(self.downloaded.load(Ordering::Relaxed))
+    }
+
+    pub fn web_seed_downloaded(&self) -> ByteSize {
+        ByteSize(self.web_seed_downloaded.load(Ordering::Relaxed))
+    }
+
+    pub fn uploaded(&self) -> ByteSize {
+        ByteSize(self.uploaded.load(Ordering::Relaxed))
+    }
+
+    // What trackers are told: bytes moved since the torrent last announced started, so
+    // totals from earlier runs or imported from another client aren't reported twice.
+    pub fn since_started(&self) -> TransferTotals {
+        let started_at = *self.started_at.lock().unwrap();
+        let totals = self.totals();
+        TransferTotals {
+            downloaded: totals.downloaded - started_at.downloaded,
+            uploaded: totals.uploaded - started_at.uploaded,
+        }
+    }
+
+    pub(crate) fn mark_started(&self) {
+        *self.started_at.lock().unwrap() = self.totals();
+    }
+
+    pub(crate) fn add_downloaded(&self, bytes: u64) {
+        self.downloaded.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_web_seed_downloaded(&self, bytes: u64) {
+        self.add_downloaded(bytes);
+        self.web_seed_downloaded.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_uploaded(&self, bytes: u64) {
+        self.uploaded.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,3 @@
+// Engine parts that aren't public API, re-exported for tests that drive them directly.
+pub use crate::session::lock::SessionLock;
+pub use crate::session::quarantine::Quarantine;
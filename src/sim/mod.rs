@@ -0,0 +1,6 @@
+pub mod internals;
+pub mod network;
+pub mod peer;
+pub mod pipe;
+pub mod tracker;
+pub mod wait;
@@ -0,0 +1,37 @@
+use super::peer::{FakePeer, FakePeerHandle};
+use super::pipe::pipe;
+use crate::session::engine::Session;
+use crate::session::error::SessionError;
+use std::net::{Ipv4Addr, SocketAddr};
+
+// Connects fake peers to a session over in-memory pipes, so a swarm can be scripted
+// without sockets, trackers or timing-dependent port numbers. Peers get addresses from
+// 203.0.113.0/24 (TEST-NET-3) in the order they're connected, the same on every run.
+pub struct SimNetwork<'a> {
+    session: &'a Session,
+    connected: u8,
+}
+
+impl<'a> SimNetwork<'a> {
+    pub fn new(session: &'a Session) -> SimNetwork<'a> {
+        SimNetwork {
+            session,
+            connected: 0,
+        }
+    }
+
+    // The session dials `peer` for the torrent `info_hash`, as if it had been handed the
+    // peer's address.
+    pub fn connect(
+        &mut self,
+        info_hash: &[u8; 20],
+        peer: FakePeer,
+    ) -> Result<FakePeerHandle, SessionError> {
+        self.connected = self.connected.wrapping_add(1);
+        let addr = SocketAddr::from((Ipv4Addr::new(203, 0, 113, self.connected), 6881));
+        let (ours, theirs) = pipe();
+        let handle = peer.spawn(theirs, addr);
+        self.session.add_peer_stream(info_hash, addr, ours)?;
+        Ok(handle)
+    }
+}
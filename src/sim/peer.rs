@@ -0,0 +1,378 @@
+use super::pipe::{Hangup, PipeStream};
+use crate::peer::reserved::ReservedBits;
+use crate::peer::transport::PeerStream;
+use crate::peer::value::{Handshake, PeerMessage};
+use crate::piece::bitfield::Bitfield;
+use crate::torrent::value::TorrentMetaInfo;
+use bytes::Bytes;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const BLOCK: u32 = 16 * 1024;
+
+// What a fake peer saw of the engine, for tests to check against.
+#[derive(Debug, Clone, Default)]
+pub struct PeerLog {
+    // Block requests the engine sent, in order.
+    pub requests: Vec<(u32, u32, u32)>,
+    pub cancels: usize,
+    pub blocks_sent: usize,
+    pub blocks_received: usize,
+    // The engine's stance towards us as of its last message.
+    pub interested: bool,
+    pub choking: bool,
+    // Times the engine unchoked us.
+    pub unchokes: usize,
+    // Set once the handshake is through, and once the connection is gone.
+    pub connected: bool,
+    pub closed: bool,
+}
+
+// The log and a signal for whoever waits on it to change.
+#[derive(Debug, Default)]
+struct SharedLog {
+    log: Mutex<PeerLog>,
+    changed: Condvar,
+}
+
+impl SharedLog {
+    fn update(&self, change: impl FnOnce(&mut PeerLog)) {
+        change(&mut self.log.lock().unwrap());
+        self.changed.notify_all();
+    }
+}
+
+// A remote peer with scripted behaviour, run on its own thread against one end of a pipe.
+// By default it unchokes whoever is interested and answers every request for pieces it
+// has; the builder methods make it misbehave in the ways the engine has to cope with.
+#[derive(Debug, Clone)]
+pub struct FakePeer {
+    meta: TorrentMetaInfo,
+    data: Arc<[u8]>,
+    have: Bitfield,
+    corrupt: HashSet<usize>,
+    choke_after: Option<usize>,
+    stall_after: Option<usize>,
+    keep_alive: Option<Duration>,
+    leech: bool,
+    peer_id: [u8; 20],
+}
+
+impl FakePeer {
+    // Has every piece of `data`, the torrent's full contents.
+    pub fn seed(meta: &TorrentMetaInfo, data: &[u8]) -> FakePeer {
+        let mut have = Bitfield::new(meta.num_pieces());
+        (0..meta.num_pieces()).for_each(|index| have.set(index));
+        FakePeer {
+            meta: meta.clone(),
+            data: data.into(),
+            have,
+            corrupt: HashSet::new(),
+            choke_after: None,
+            stall_after: None,
+            keep_alive: None,
+            leech: false,
+            peer_id: *b"-SIM000-000000000000",
+        }
+    }
+
+    // Has nothing, and asks for blocks whenever the engine unchokes it.
+    pub fn leech(meta: &TorrentMetaInfo) -> FakePeer {
+        FakePeer {
+            have: Bitfield::new(meta.num_pieces()),
+            leech: true,
+            ..FakePeer::seed(meta, &[])
+        }
+    }
+
+    // Has only these pieces.
+    pub fn only(mut self, pieces: impl IntoIterator<Item = usize>) -> Self {
+        self.have = Bitfield::new(self.meta.num_pieces());
+        pieces.into_iter().for_each(|index| self.have.set(index));
+        self
+    }
+
+    // Sends blocks of `index` with their bytes flipped.
+    pub fn corrupt(mut self, index: usize) -> Self {
+        self.corrupt.insert(index);
+        self
+    }
+
+    // Chokes the engine after sending `blocks` blocks and never unchokes again. Zero
+    // means it never unchokes at all.
+    pub fn choke_after(mut self, blocks: usize) -> Self {
+        self.choke_after = Some(blocks);
+        self
+    }
+
+    // Stops answering requests after `blocks` blocks, keeping the connection open.
+    pub fn stall_after(mut self, blocks: usize) -> Self {
+        self.stall_after = Some(blocks);
+        self
+    }
+
+    // Sends a keep-alive whenever it has been quiet for `interval`. The engine only looks
+    // at bans and the like when it hears from a peer, so this speeds those up.
+    pub fn keep_alive(mut self, interval: Duration) -> Self {
+        self.keep_alive = Some(interval);
+        self
+    }
+
+    pub fn peer_id(mut self, peer_id: [u8; 20]) -> Self {
+        self.peer_id = peer_id;
+        self
+    }
+
+    // Answers the engine's handshake on `stream` and plays the script until the engine
+    // hangs up or the handle stops it.
+    pub fn spawn(self, stream: PipeStream, addr: SocketAddr) -> FakePeerHandle {
+        let log = Arc::new(SharedLog::default());
+        let hangup = stream.hangup();
+        let thread = {
+            let log = Arc::clone(&log);
+            thread::spawn(move || {
+                let mut run = Run {
+                    peer: self,
+                    stream,
+                    log: &log,
+                    engine_has: None,
+                    choking: true,
+                    sent: 0,
+                    wanted: Vec::new(),
+                };
+                let _ = run.play();
+                log.update(|log| log.closed = true);
+            })
+        };
+        FakePeerHandle {
+            addr,
+            log,
+            hangup,
+            thread: Some(thread),
+        }
+    }
+}
+
+// The test's side of a running fake peer. Dropping it hangs up on the engine.
+pub struct FakePeerHandle {
+    addr: SocketAddr,
+    log: Arc<SharedLog>,
+    hangup: Hangup,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FakePeerHandle {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn log(&self) -> PeerLog {
+        self.log.log.lock().unwrap().clone()
+    }
+
+    // Waits for the log to change until `done` holds, for at most `timeout`. Returns
+    // whether it did.
+    pub fn wait_for(&self, timeout: Duration, done: impl Fn(&PeerLog) -> bool) -> bool {
+        let log = self.log.log.lock().unwrap();
+        let (log, _) = (self.log.changed)
+            .wait_timeout_while(log, timeout, |log| !done(log))
+            .unwrap();
+        done(&log)
+    }
+
+    // Hangs up on the engine and waits for the peer's thread to finish.
+    pub fn disconnect(mut self) -> PeerLog {
+        self.hangup.hang_up();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.log()
+    }
+}
+
+impl Drop for FakePeerHandle {
+    fn drop(&mut self) {
+        self.hangup.hang_up();
+    }
+}
+
+struct Run<'a> {
+    peer: FakePeer,
+    stream: PipeStream,
+    log: &'a SharedLog,
+    // What the engine told us it has, once it has.
+    engine_has: Option<Bitfield>,
+    choking: bool,
+    sent: usize,
+    // Blocks a leech still has to ask for.
+    wanted: Vec<(u32, u32, u32)>,
+}
+
+impl Run<'_> {
+    fn play(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let info_hash = self.peer.meta.info_hash();
+        let peer_id = self.peer.peer_id;
+        Handshake::accept_handshake(
+            &mut self.stream,
+            |asked| (asked == &info_hash).then_some(peer_id),
+            ReservedBits::empty(),
+            Duration::from_secs(10),
+        )?;
+        self.log.update(|log| log.connected = true);
+
+        if self.peer.have.count() > 0 {
+            let bitfield = Bytes::copy_from_slice(self.peer.have.as_bytes());
+            PeerMessage::Bitfield(bitfield).write_peer_message(&mut self.stream)?;
+        }
+        if self.peer.leech {
+            PeerMessage::Interested.write_peer_message(&mut self.stream)?;
+        }
+
+        // Runs until the engine or the handle hangs up, which ends the wait below.
+        let mut last_keep_alive = Instant::now();
+        loop {
+            let until_keep_alive = (self.peer.keep_alive)
+                .map(|interval| interval.saturating_sub(last_keep_alive.elapsed()));
+            if until_keep_alive == Some(Duration::ZERO) {
+                PeerMessage::KeepAlive.write_peer_message(&mut self.stream)?;
+                last_keep_alive = Instant::now();
+                continue;
+            }
+            // Waiting with a timeout only while nothing has arrived keeps messages whole.
+            self.stream.set_read_timeout(until_keep_alive)?;
+            match self.stream.peek(&mut [0u8; 1]) {
+                Ok(0) => return Ok(()),
+                Ok(_) => {}
+                Err(_) => continue,
+            }
+            self.stream.set_read_timeout(None)?;
+            let message = PeerMessage::read_peer_message(&mut self.stream)?;
+            self.handle(message)?;
+        }
+    }
+
+    fn handle(&mut self, message: PeerMessage) -> Result<(), Box<dyn std::error::Error>> {
+        let num_pieces = self.peer.meta.num_pieces();
+        match message {
+            PeerMessage::Interested => {
+                self.log.update(|log| log.interested = true);
+                if self.choking && self.peer.choke_after != Some(0) {
+                    self.choking = false;
+                    PeerMessage::Unchoke.write_peer_message(&mut self.stream)?;
+                }
+            }
+            PeerMessage::NotInterested => self.log.update(|log| log.interested = false),
+            PeerMessage::Choke => self.log.update(|log| log.choking = true),
+            PeerMessage::Unchoke => {
+                self.log.update(|log| {
+                    log.choking = false;
+                    log.unchokes += 1;
+                });
+                self.request_more()?;
+            }
+            PeerMessage::Bitfield(bytes) => {
+                self.engine_has = Some(Bitfield::from_bytes(&bytes, num_pieces));
+            }
+            PeerMessage::Have { piece_index } => {
+                let has = self
+                    .engine_has
+                    .get_or_insert_with(|| Bitfield::new(num_pieces));
+                if (piece_index as usize) < num_pieces {
+                    has.set(piece_index as usize);
+                }
+            }
+            PeerMessage::Request {
+                index,
+                begin,
+                length,
+            } => {
+                self.log
+                    .update(|log| log.requests.push((index, begin, length)));
+                self.serve(index, begin, length)?;
+            }
+            PeerMessage::Cancel { .. } => self.log.update(|log| log.cancels += 1),
+            PeerMessage::Piece { .. } => {
+                self.log.update(|log| log.blocks_received += 1);
+                self.request_more()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn serve(&mut self, index: u32, begin: u32, length: u32) -> std::io::Result<()> {
+        let stalled = self
+            .peer
+            .stall_after
+            .is_some_and(|after| self.sent >= after);
+        if self.choking || stalled || !self.peer.have.has(index as usize) {
+            return Ok(());
+        }
+        let start = index as usize * self.peer.meta.info.piece_length + begin as usize;
+        let Some(data) = self.peer.data.get(start..start + length as usize) else {
+            return Ok(());
+        };
+        let mut block = data.to_vec();
+        if self.peer.corrupt.contains(&(index as usize)) {
+            block.iter_mut().for_each(|byte| *byte = !*byte);
+        }
+        PeerMessage::Piece {
+            index,
+            begin,
+            block: block.into(),
+        }
+        .write_peer_message(&mut self.stream)
+        .map_err(std::io::Error::other)?;
+        self.sent += 1;
+        self.log.update(|log| log.blocks_sent += 1);
+
+        if self
+            .peer
+            .choke_after
+            .is_some_and(|after| self.sent >= after)
+        {
+            self.choking = true;
+            PeerMessage::Choke
+                .write_peer_message(&mut self.stream)
+                .map_err(std::io::Error::other)?;
+        }
+        Ok(())
+    }
+
+    // A leech asks for one block at a time from the pieces the engine has.
+    fn request_more(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.peer.leech || self.log.log.lock().unwrap().choking {
+            return Ok(());
+        }
+        if self.wanted.is_empty() {
+            let Some(has) = &self.engine_has else {
+                return Ok(());
+            };
+            let meta = &self.peer.meta;
+            let Some(index) =
+                (0..meta.num_pieces()).find(|&index| has.has(index) && !self.peer.have.has(index))
+            else {
+                return Ok(());
+            };
+            self.peer.have.set(index);
+            let size = meta.piece_size(index) as u32;
+            self.wanted = (0..size)
+                .step_by(BLOCK as usize)
+                .map(|begin| (index as u32, begin, BLOCK.min(size - begin)))
+                .rev()
+                .collect();
+        }
+        if let Some((index, begin, length)) = self.wanted.pop() {
+            PeerMessage::Request {
+                index,
+                begin,
+                length,
+            }
+            .write_peer_message(&mut self.stream)?;
+        }
+        Ok(())
+    }
+}
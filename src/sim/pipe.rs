@@ -0,0 +1,163 @@
+use crate::peer::transport::PeerStream;
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+struct Buffer {
+    data: VecDeque<u8>,
+    // Set when either end is dropped: the reader sees EOF once the data is drained, the
+    // writer gets BrokenPipe.
+    closed: bool,
+}
+
+#[derive(Debug, Default)]
+struct Channel {
+    buffer: Mutex<Buffer>,
+    ready: Condvar,
+}
+
+impl Channel {
+    fn close(&self) {
+        self.buffer.lock().unwrap().closed = true;
+        self.ready.notify_all();
+    }
+}
+
+// One end of an in-memory duplex stream, standing in for a TCP connection. Writes never
+// block; reads wait for data up to the read timeout, like a socket's.
+#[derive(Debug)]
+pub struct PipeStream {
+    incoming: Arc<Channel>,
+    outgoing: Arc<Channel>,
+    read_timeout: Mutex<Option<Duration>>,
+    write_timeout: Mutex<Option<Duration>>,
+}
+
+// Closes a pipe from outside, as if its owner had dropped it. A read blocked on the pipe
+// returns straight away instead of waiting for data that won't come.
+#[derive(Debug, Clone)]
+pub struct Hangup {
+    incoming: Arc<Channel>,
+    outgoing: Arc<Channel>,
+}
+
+impl Hangup {
+    pub fn hang_up(&self) {
+        self.incoming.close();
+        self.outgoing.close();
+    }
+}
+
+// Two connected ends: what one writes the other reads.
+pub fn pipe() -> (PipeStream, PipeStream) {
+    let (a, b) = (Arc::new(Channel::default()), Arc::new(Channel::default()));
+    let end = |incoming: &Arc<Channel>, outgoing: &Arc<Channel>| PipeStream {
+        incoming: Arc::clone(incoming),
+        outgoing: Arc::clone(outgoing),
+        read_timeout: Mutex::new(None),
+        write_timeout: Mutex::new(None),
+    };
+    (end(&a, &b), end(&b, &a))
+}
+
+impl PipeStream {
+    pub fn hangup(&self) -> Hangup {
+        Hangup {
+            incoming: Arc::clone(&self.incoming),
+            outgoing: Arc::clone(&self.outgoing),
+        }
+    }
+
+    // Waits until there is data or the pipe is closed, then hands the buffer to `take`.
+    fn wait<T>(&self, take: impl FnOnce(&mut Buffer) -> T) -> io::Result<T> {
+        let deadline = self
+            .read_timeout
+            .lock()
+            .unwrap()
+            .map(|timeout| Instant::now() + timeout);
+        let mut buffer = self.incoming.buffer.lock().unwrap();
+        while buffer.data.is_empty() && !buffer.closed {
+            buffer = match deadline {
+                None => self.incoming.ready.wait(buffer).unwrap(),
+                Some(deadline) => {
+                    let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+                        return Err(ErrorKind::WouldBlock.into());
+                    };
+                    self.incoming.ready.wait_timeout(buffer, left).unwrap().0
+                }
+            };
+        }
+        Ok(take(&mut buffer))
+    }
+}
+
+impl Read for PipeStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.wait(|buffer| {
+            let n = buf.len().min(buffer.data.len());
+            for (slot, byte) in buf.iter_mut().zip(buffer.data.drain(..n)) {
+                *slot = byte;
+            }
+            n
+        })
+    }
+}
+
+impl Write for PipeStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut buffer = self.outgoing.buffer.lock().unwrap();
+        if buffer.closed {
+            return Err(ErrorKind::BrokenPipe.into());
+        }
+        buffer.data.extend(buf);
+        self.outgoing.ready.notify_all();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl PeerStream for PipeStream {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(*self.read_timeout.lock().unwrap())
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        *self.read_timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+
+    // Kept for callers that restore it; writes never wait.
+    fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(*self.write_timeout.lock().unwrap())
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        *self.write_timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.wait(|buffer| {
+            let n = buf.len().min(buffer.data.len());
+            for (slot, byte) in buf.iter_mut().zip(buffer.data.iter()) {
+                *slot = *byte;
+            }
+            n
+        })
+    }
+}
+
+impl Drop for PipeStream {
+    fn drop(&mut self) {
+        self.incoming.close();
+        self.outgoing.close();
+    }
+}
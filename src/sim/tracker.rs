@@ -0,0 +1,348 @@
+use crate::bencode::encoder::{encode, encode_bytes, encode_integer};
+use crate::bencode::value::BencodeValue;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// What the tracker answers with; changed at any time through MockTracker::update.
+#[derive(Debug, Clone)]
+pub struct MockReply {
+    pub interval: u32,
+    pub min_interval: Option<u32>,
+    pub complete: Option<u32>,
+    pub incomplete: Option<u32>,
+    // IPv4 peers go out in "peers", IPv6 ones in "peers6", compact or not as asked.
+    pub peers: Vec<SocketAddr>,
+    pub tracker_id: Option<String>,
+    pub warning: Option<String>,
+    // Turns every answer into a failure with this reason.
+    pub failure: Option<String>,
+    // Answers with this HTTP status and an empty body instead, e.g. 503.
+    pub status: Option<u16>,
+    // Held back this long before answering, to run into timeouts.
+    pub delay: Duration,
+}
+
+impl Default for MockReply {
+    fn default() -> Self {
+        MockReply {
+            interval: 1800,
+            min_interval: None,
+            complete: None,
+            incomplete: None,
+            peers: Vec::new(),
+            tracker_id: None,
+            warning: None,
+            failure: None,
+            status: None,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+// One announce as the tracker received it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockAnnounce {
+    pub info_hash: Vec<u8>,
+    pub peer_id: Vec<u8>,
+    pub port: u16,
+    pub uploaded: u64,
+    pub downloaded: u64,
+    pub left: u64,
+    pub event: Option<String>,
+    pub compact: bool,
+    pub numwant: Option<u32>,
+    pub tracker_id: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    reply: MockReply,
+    announces: Vec<MockAnnounce>,
+    scrapes: usize,
+}
+
+// An HTTP tracker on 127.0.0.1 that answers announces at /announce and scrapes at
+// /scrape from a scripted MockReply and records what it was sent. Only HTTP: the client
+// has no UDP tracker support to test against. Stops when dropped.
+pub struct MockTracker {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl MockTracker {
+    pub fn start() -> io::Result<MockTracker> {
+        MockTracker::with_reply(MockReply::default())
+    }
+
+    pub fn with_reply(reply: MockReply) -> io::Result<MockTracker> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State {
+            reply,
+            ..State::default()
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let (state, stop) = (Arc::clone(&state), Arc::clone(&stop));
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    // Each request on its own thread, so a delayed answer holds up no other.
+                    if let Ok(stream) = stream {
+                        let state = Arc::clone(&state);
+                        thread::spawn(move || {
+                            let _ = serve(stream, &state);
+                        });
+                    }
+                }
+            });
+        }
+        Ok(MockTracker { addr, state, stop })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn announce_url(&self) -> String {
+        format!("http://{}/announce", self.addr)
+    }
+
+    pub fn reply(&self) -> MockReply {
+        self.state.lock().unwrap().reply.clone()
+    }
+
+    pub fn update(&self, change: impl FnOnce(&mut MockReply)) {
+        change(&mut self.state.lock().unwrap().reply);
+    }
+
+    pub fn announces(&self) -> Vec<MockAnnounce> {
+        self.state.lock().unwrap().announces.clone()
+    }
+
+    pub fn scrapes(&self) -> usize {
+        self.state.lock().unwrap().scrapes
+    }
+}
+
+impl Drop for MockTracker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wakes the accept loop so it sees the flag.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn serve(stream: TcpStream, state: &Mutex<State>) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers say nothing the tracker cares about.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let target = request_line.split(' ').nth(1).unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = parse_query(query);
+    let (reply, body) = {
+        let mut state = state.lock().unwrap();
+        let body = match path {
+            "/announce" => {
+                let announce = announce_from(&query);
+                let compact = announce.compact;
+                state.announces.push(announce);
+                announce_body(&state.reply, compact)
+            }
+            "/scrape" => {
+                state.scrapes += 1;
+                scrape_body(&state.reply, query.get("info_hash"))
+            }
+            _ => return respond(&stream, 404, b""),
+        };
+        (state.reply.clone(), body)
+    };
+
+    thread::sleep(reply.delay);
+    match reply.status {
+        Some(status) => respond(&stream, status, b""),
+        None => respond(&stream, 200, &body),
+    }
+}
+
+fn respond(mut stream: &TcpStream, status: u16, body: &[u8]) -> io::Result<()> {
+    let header = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    );
+    stream.write_all(header.as_bytes())?;
+    stream.write_all(body)
+}
+
+fn parse_query(query: &str) -> HashMap<String, Vec<u8>> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.to_string(), percent_decode(value)))
+        .collect()
+}
+
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+fn announce_from(query: &HashMap<String, Vec<u8>>) -> MockAnnounce {
+    let text = |key: &str| {
+        query
+            .get(key)
+            .map(|value| String::from_utf8_lossy(value).into_owned())
+    };
+    let number = |key: &str| text(key).and_then(|value| value.parse::<u64>().ok());
+    MockAnnounce {
+        info_hash: query.get("info_hash").cloned().unwrap_or_default(),
+        peer_id: query.get("peer_id").cloned().unwrap_or_default(),
+        port: number("port").unwrap_or(0) as u16,
+        uploaded: number("uploaded").unwrap_or(0),
+        downloaded: number("downloaded").unwrap_or(0),
+        left: number("left").unwrap_or(0),
+        event: text("event"),
+        compact: text("compact").as_deref() == Some("1"),
+        numwant: number("numwant").map(|n| n as u32),
+        tracker_id: text("trackerid"),
+    }
+}
+
+fn announce_body(reply: &MockReply, compact: bool) -> Vec<u8> {
+    let mut dict = HashMap::new();
+    if let Some(reason) = &reply.failure {
+        dict.insert(
+            "failure reason".to_string(),
+            BencodeValue::String(reason.clone()),
+        );
+        return encode(&BencodeValue::Dictionary(dict));
+    }
+
+    let int = |n: u32| BencodeValue::Integer(n as i64);
+    dict.insert("interval".to_string(), int(reply.interval));
+    let counts = [
+        ("min interval", reply.min_interval),
+        ("complete", reply.complete),
+        ("incomplete", reply.incomplete),
+    ];
+    for (key, count) in counts {
+        if let Some(count) = count {
+            dict.insert(key.to_string(), int(count));
+        }
+    }
+    let strings = [
+        ("tracker id", &reply.tracker_id),
+        ("warning message", &reply.warning),
+    ];
+    for (key, value) in strings {
+        if let Some(value) = value {
+            dict.insert(key.to_string(), BencodeValue::String(value.clone()));
+        }
+    }
+
+    let (v4, v6): (Vec<SocketAddr>, Vec<SocketAddr>) =
+        reply.peers.iter().partition(|peer| peer.is_ipv4());
+    if compact {
+        dict.insert("peers".to_string(), BencodeValue::Bytes(compact_peers(&v4)));
+        if !v6.is_empty() {
+            dict.insert(
+                "peers6".to_string(),
+                BencodeValue::Bytes(compact_peers(&v6)),
+            );
+        }
+    } else {
+        let peers = v4
+            .iter()
+            .chain(&v6)
+            .map(|peer| {
+                let mut entry = HashMap::new();
+                entry.insert(
+                    "ip".to_string(),
+                    BencodeValue::String(peer.ip().to_string()),
+                );
+                entry.insert("port".to_string(), int(peer.port() as u32));
+                BencodeValue::Dictionary(entry)
+            })
+            .collect();
+        dict.insert("peers".to_string(), BencodeValue::List(peers));
+    }
+    encode(&BencodeValue::Dictionary(dict))
+}
+
+fn compact_peers(peers: &[SocketAddr]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for peer in peers {
+        match peer.ip() {
+            IpAddr::V4(ip) => bytes.extend(ip.octets()),
+            IpAddr::V6(ip) => bytes.extend(ip.octets()),
+        }
+        bytes.extend(peer.port().to_be_bytes());
+    }
+    bytes
+}
+
+// Info hashes key the "files" dict as raw bytes, so it is put together by hand.
+fn scrape_body(reply: &MockReply, info_hash: Option<&Vec<u8>>) -> Vec<u8> {
+    if let Some(reason) = &reply.failure {
+        let mut body = b"d".to_vec();
+        body.extend(encode_bytes(b"failure reason"));
+        body.extend(encode_bytes(reason.as_bytes()));
+        body.push(b'e');
+        return body;
+    }
+    let mut body = b"d".to_vec();
+    body.extend(encode_bytes(b"files"));
+    body.push(b'd');
+    if let Some(info_hash) = info_hash {
+        body.extend(encode_bytes(info_hash));
+        body.push(b'd');
+        let complete = reply.complete.unwrap_or(0) as i64;
+        let incomplete = reply.incomplete.unwrap_or(0) as i64;
+        body.extend(encode_bytes(b"complete"));
+        body.extend(encode_integer(complete));
+        body.extend(encode_bytes(b"downloaded"));
+        body.extend(encode_integer(complete));
+        body.extend(encode_bytes(b"incomplete"));
+        body.extend(encode_integer(incomplete));
+        body.push(b'e');
+    }
+    body.extend(b"ee");
+    body
+}
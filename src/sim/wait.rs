@@ -0,0 +1,32 @@
+use crate::session::torrent::Torrent;
+use std::sync::Arc;
+use std::task::{Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Blocks until `done` holds for the torrent. It's checked again whenever the torrent's
+// pieces change, the same signal stream readers wait on, so nothing depends on how often
+// it's looked at. Gives up after `limit` and returns false.
+pub fn until(torrent: &Torrent, limit: Duration, done: impl Fn(&Torrent) -> bool) -> bool {
+    let deadline = Instant::now() + limit;
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    loop {
+        // Registered before looking, so a change in between still unparks us.
+        torrent.register_reader(&waker);
+        if done(torrent) {
+            return true;
+        }
+        let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+            return false;
+        };
+        thread::park_timeout(left);
+    }
+}
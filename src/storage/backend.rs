@@ -0,0 +1,98 @@
+use super::file_storage::FileStorage;
+use crate::torrent::value::TorrentMetaInfo;
+use std::io;
+use std::sync::Mutex;
+
+// Where a torrent's bytes end up. Blocks are addressed by piece and offset within it in
+// the torrent's concatenated byte space; the file layout stays the torrent's business, so
+// a backend is free to keep the data as one object, in memory or feed it somewhere else.
+// Only pieces that passed their hash check are written, each in one call.
+pub trait StorageBackend: Send + Sync {
+    // A block that can't be read counts as missing when the torrent is checked.
+    fn read_block(&self, index: usize, begin: u32, length: u32) -> io::Result<Vec<u8>>;
+
+    fn write_block(&self, index: usize, begin: u32, data: &[u8]) -> io::Result<()>;
+
+    // Called once the torrent is complete and when it's removed from the session.
+    fn flush(&self) -> io::Result<()>;
+
+    // Size of the byte space; must equal the torrent's total size.
+    fn len(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl StorageBackend for FileStorage {
+    fn read_block(&self, index: usize, begin: u32, length: u32) -> io::Result<Vec<u8>> {
+        FileStorage::read_block(self, index, begin, length)
+    }
+
+    fn write_block(&self, index: usize, begin: u32, data: &[u8]) -> io::Result<()> {
+        FileStorage::write_block(self, index, begin, data)
+    }
+
+    // Every write goes through a file handle of its own, closed right after.
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        self.total_size()
+    }
+}
+
+// Keeps the whole torrent in one buffer. Handy for tests and small torrents that are
+// passed on rather than saved; bytes never written read as zeros.
+#[derive(Debug)]
+pub struct MemoryStorage {
+    piece_length: u64,
+    data: Mutex<Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new(meta: &TorrentMetaInfo) -> MemoryStorage {
+        MemoryStorage {
+            piece_length: meta.info.piece_length as u64,
+            data: Mutex::new(vec![0; meta.total_size()]),
+        }
+    }
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.data.lock().unwrap().clone()
+    }
+
+    fn range(&self, index: usize, begin: u32, length: usize) -> io::Result<std::ops::Range<usize>> {
+        let start = index as u64 * self.piece_length + begin as u64;
+        let end = start + length as u64;
+        if end > self.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Block exceeds torrent size",
+            ));
+        }
+        Ok(start as usize..end as usize)
+    }
+}
+
+impl StorageBackend for MemoryStorage {
+    fn read_block(&self, index: usize, begin: u32, length: u32) -> io::Result<Vec<u8>> {
+        let range = self.range(index, begin, length as usize)?;
+        Ok(self.data.lock().unwrap()[range].to_vec())
+    }
+
+    fn write_block(&self, index: usize, begin: u32, data: &[u8]) -> io::Result<()> {
+        let range = self.range(index, begin, data.len())?;
+        self.data.lock().unwrap()[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        self.data.lock().unwrap().len() as u64
+    }
+}
@@ -0,0 +1,589 @@
+use crate::torrent::value::{FileAttributes, FilesInfo, TorrentMetaInfo};
+use md5::{Digest, Md5};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    pub length: u64,
+    // Position of the file's first byte in the torrent's concatenated byte space.
+    pub offset: u64,
+    pub md5sum: Option<String>,
+    // A BEP 47 pad file: takes up piece space, reads as zeros, never touches the disk.
+    pub pad: bool,
+    pub executable: bool,
+    // For a BEP 47 symlink, its target below the torrent's top directory. Symlinks hold
+    // no data and are made once the download is complete.
+    pub symlink: Option<PathBuf>,
+}
+
+// Where the files currently are. While staged in an incomplete directory every file name
+// may carry a suffix such as ".!bt".
+#[derive(Debug, Clone)]
+struct Location {
+    root: PathBuf,
+    suffix: Option<String>,
+}
+
+// Paths the user gave files in place of the torrent's own, keyed by the torrent's path.
+// `root` is what the torrent's top directory (or its only file) is called now.
+#[derive(Debug, Clone)]
+struct Names {
+    root: PathBuf,
+    renamed: HashMap<PathBuf, PathBuf>,
+}
+
+// Maps the torrent's byte space onto the files below `root`. A single-file torrent is
+// stored as `root/name`, a multi-file torrent as `root/name/<path...>`.
+#[derive(Debug)]
+pub struct FileStorage {
+    root: PathBuf,
+    location: RwLock<Location>,
+    names: RwLock<Names>,
+    files: Vec<FileEntry>,
+    piece_length: u64,
+    total_size: u64,
+}
+
+impl FileEntry {
+    // Whether the file is backed by bytes on disk, i.e. neither padding nor a symlink.
+    pub fn holds_data(&self) -> bool {
+        !self.pad && self.symlink.is_none()
+    }
+}
+
+impl FileStorage {
+    pub fn new(root: &Path, torrent: &TorrentMetaInfo) -> FileStorage {
+        let mut files = Vec::new();
+        let mut offset = 0u64;
+
+        match &torrent.info.files_info {
+            FilesInfo::SingleFile { length, md5sum } => {
+                files.push(FileEntry {
+                    path: PathBuf::from(&torrent.info.name),
+                    length: *length as u64,
+                    offset,
+                    md5sum: md5sum.clone(),
+                    pad: false,
+                    executable: FileAttributes::of(&torrent.info.extras).executable,
+                    symlink: None,
+                });
+            }
+            FilesInfo::MultiFile { files: entries } => {
+                for entry in entries {
+                    let mut path = PathBuf::from(&torrent.info.name);
+                    path.extend(&entry.path);
+                    files.push(FileEntry {
+                        path,
+                        length: entry.length as u64,
+                        offset,
+                        md5sum: entry.md5sum.clone(),
+                        pad: entry.is_padding(),
+                        executable: entry.attributes().executable,
+                        symlink: entry.symlink_path().map(PathBuf::from_iter),
+                    });
+                    offset += entry.length as u64;
+                }
+            }
+        }
+
+        FileStorage {
+            root: root.to_path_buf(),
+            location: RwLock::new(Location {
+                root: root.to_path_buf(),
+                suffix: None,
+            }),
+            names: RwLock::new(Names {
+                root: PathBuf::from(&torrent.info.name),
+                renamed: HashMap::new(),
+            }),
+            files,
+            piece_length: torrent.info.piece_length as u64,
+            total_size: torrent.total_size() as u64,
+        }
+    }
+
+    // The final destination, regardless of where the files are staged right now.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn current_root(&self) -> PathBuf {
+        self.location.read().unwrap().root.clone()
+    }
+
+    // Full path of a file as it currently sits on disk.
+    pub fn current_path(&self, entry: &FileEntry) -> PathBuf {
+        let location = self.location.read().unwrap();
+        Self::path_in(&location, &self.file_path(entry))
+    }
+
+    // The file's path below the root: the torrent's own unless it has been renamed.
+    pub fn file_path(&self, entry: &FileEntry) -> PathBuf {
+        let names = self.names.read().unwrap();
+        names
+            .renamed
+            .get(&entry.path)
+            .unwrap_or(&entry.path)
+            .clone()
+    }
+
+    // What the torrent's top directory, or its only file, is called now.
+    pub fn root_name(&self) -> PathBuf {
+        self.names.read().unwrap().root.clone()
+    }
+
+    // Every renamed file as (torrent's path, path now), for saving with resume data.
+    pub fn renamed(&self) -> Vec<(PathBuf, PathBuf)> {
+        let names = self.names.read().unwrap();
+        let mut renamed: Vec<_> = names
+            .renamed
+            .iter()
+            .map(|(from, to)| (from.clone(), to.clone()))
+            .collect();
+        renamed.sort();
+        renamed
+    }
+
+    // Gives a file a new path below the root, moving it on disk if it's there already.
+    // The path must be relative and plain, with no "..", and mustn't belong to another
+    // file of the torrent or to anything already on disk.
+    pub fn rename_file(&self, entry: &FileEntry, new_path: &Path) -> io::Result<()> {
+        if !Self::is_plain(new_path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "New path must be relative, without \"..\"",
+            ));
+        }
+        let location = self.location.read().unwrap();
+        let mut names = self.names.write().unwrap();
+        self.move_file(&location, &mut names, entry, new_path.to_path_buf())
+    }
+
+    // Renames the torrent's top directory (the file itself for a single-file torrent).
+    // Files that were renamed out of that directory stay where they are.
+    pub fn rename_root(&self, new_name: &str) -> io::Result<()> {
+        let new_root = PathBuf::from(new_name);
+        if new_root.components().count() != 1 || !Self::is_plain(&new_root) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "New name must be a single path component",
+            ));
+        }
+        let location = self.location.read().unwrap();
+        let mut names = self.names.write().unwrap();
+        let old_root = names.root.clone();
+        if old_root == new_root {
+            return Ok(());
+        }
+        for entry in &self.files {
+            let current = names.renamed.get(&entry.path).unwrap_or(&entry.path);
+            if let Ok(rest) = current.strip_prefix(&old_root) {
+                let moved = new_root.join(rest);
+                self.move_file(&location, &mut names, entry, moved)?;
+            }
+        }
+        names.root = new_root;
+        Ok(())
+    }
+
+    // Puts back names saved with resume data, without touching the disk: the files were
+    // moved when the renames happened. Entries for paths the torrent doesn't have are
+    // ignored, as are unsafe ones.
+    pub fn restore_names(&self, root: Option<&Path>, renamed: &[(PathBuf, PathBuf)]) {
+        let mut names = self.names.write().unwrap();
+        if let Some(root) =
+            root.filter(|root| root.components().count() == 1 && Self::is_plain(root))
+        {
+            names.root = root.to_path_buf();
+        }
+        for (from, to) in renamed {
+            let known = self.files.iter().any(|entry| &entry.path == from);
+            if known && Self::is_plain(to) {
+                names.renamed.insert(from.clone(), to.clone());
+            }
+        }
+    }
+
+    pub fn is_staged(&self) -> bool {
+        let location = self.location.read().unwrap();
+        location.root != self.root || location.suffix.is_some()
+    }
+
+    // Whether every file already exists at the final destination with its full length.
+    pub fn is_in_place(&self) -> bool {
+        self.files
+            .iter()
+            .filter(|entry| entry.holds_data())
+            .all(|entry| {
+                fs::metadata(self.root.join(self.file_path(entry)))
+                    .is_ok_and(|meta| meta.len() == entry.length)
+            })
+    }
+
+    // Whether the files backing a range exist and are long enough to hold it. A cheap
+    // sanity check before trusting resume data; says nothing about the contents.
+    pub fn is_range_present(&self, offset: u64, length: u64) -> bool {
+        self.spans(offset, length)
+            .iter()
+            .filter(|(entry, _, _)| !entry.pad)
+            .all(|(entry, file_offset, range)| {
+                fs::metadata(self.current_path(entry))
+                    .is_ok_and(|meta| meta.len() >= file_offset + *range as u64)
+            })
+    }
+
+    // Keeps the files below `incomplete_root` (with `suffix` appended to each name) until
+    // finalize() is called. Doesn't touch anything on disk.
+    pub fn stage(&self, incomplete_root: &Path, suffix: Option<&str>) {
+        *self.location.write().unwrap() = Location {
+            root: incomplete_root.to_path_buf(),
+            suffix: suffix.map(str::to_string),
+        };
+    }
+
+    // Moves staged files to the final destination and drops the suffix. Renames are atomic
+    // when both directories are on the same filesystem; otherwise each file is copied and
+    // the original removed. Reads and writes wait until the move is done.
+    pub fn finalize(&self) -> io::Result<()> {
+        let mut location = self.location.write().unwrap();
+        let destination = Location {
+            root: self.root.clone(),
+            suffix: None,
+        };
+
+        for entry in self.files.iter().filter(|entry| !entry.pad) {
+            let path = self.file_path(entry);
+            let from = Self::path_in(&location, &path);
+            // Links are made again at the destination, below.
+            if entry.symlink.is_some() {
+                if fs::symlink_metadata(&from).is_ok_and(|meta| meta.is_symlink()) {
+                    fs::remove_file(&from)?;
+                }
+                continue;
+            }
+            let to = Self::path_in(&destination, &path);
+            if from == to {
+                continue;
+            }
+            if !from.exists() && entry.length == 0 {
+                continue;
+            }
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if fs::rename(&from, &to).is_err() {
+                fs::copy(&from, &to)?;
+                fs::remove_file(&from)?;
+            }
+            Self::remove_empty_parents(&from, &location.root);
+        }
+
+        *location = destination;
+        drop(location);
+        self.create_symlinks()
+    }
+
+    // Makes the torrent's BEP 47 symlinks where its files are now. Each points relative to
+    // itself, so moving the whole download keeps it working. Links already there are
+    // replaced; a real file in the way is left alone, as is any link whose path or target
+    // would leave the torrent's directory. Only done on Unix.
+    pub fn create_symlinks(&self) -> io::Result<()> {
+        for entry in &self.files {
+            let Some(target) = &entry.symlink else {
+                continue;
+            };
+            let path = self.file_path(entry);
+            if !Self::is_plain(&path) || !Self::is_plain(target) {
+                continue;
+            }
+            let link = self.current_path(entry);
+            match fs::symlink_metadata(&link) {
+                Ok(meta) if meta.is_symlink() => fs::remove_file(&link)?,
+                Ok(_) => continue,
+                Err(_) => {}
+            }
+            if let Some(parent) = link.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Up from the link's directory to the torrent's top one, then down to the target.
+            let depth = path.components().count().saturating_sub(2);
+            let mut relative: PathBuf = (0..depth).map(|_| Component::ParentDir).collect();
+            relative.push(target);
+            symlink(&relative, &link)?;
+        }
+        Ok(())
+    }
+
+    pub fn files(&self) -> &[FileEntry] {
+        &self.files
+    }
+
+    pub fn piece_length(&self) -> u64 {
+        self.piece_length
+    }
+
+    // Indexes of the pieces holding any of the file's bytes; empty for empty files and
+    // padding, which don't make any piece worth having.
+    pub fn pieces_of(&self, entry: &FileEntry) -> Range<usize> {
+        if entry.length == 0 || entry.pad {
+            return 0..0;
+        }
+        let first = entry.offset / self.piece_length;
+        let end = (entry.offset + entry.length).div_ceil(self.piece_length);
+        first as usize..end as usize
+    }
+
+    // Deletes a file from wherever it currently is, and any directories that leaves
+    // empty. A file that doesn't exist is fine.
+    pub fn remove_file(&self, entry: &FileEntry) -> io::Result<()> {
+        if entry.pad {
+            return Ok(());
+        }
+        let path = self.current_path(entry);
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        Self::remove_empty_parents(&path, &self.current_root());
+        Ok(())
+    }
+
+    // Deletes every file of the torrent and the directories that leaves empty. Nothing
+    // else goes: directories still holding other files stay, and a path that would reach
+    // outside the root, like one with "..", is skipped.
+    pub fn remove_all(&self) -> io::Result<()> {
+        for entry in &self.files {
+            if Self::is_plain(&self.file_path(entry)) {
+                self.remove_file(entry)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    // Reads `length` bytes starting at `offset` in the torrent's byte space.
+    pub fn read(&self, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![0u8; length];
+        let mut done = 0usize;
+
+        for (entry, file_offset, range) in self.spans(offset, length as u64) {
+            if entry.pad {
+                done += range;
+                continue;
+            }
+            let mut file = File::open(self.current_path(entry))?;
+            file.seek(SeekFrom::Start(file_offset))?;
+            file.read_exact(&mut buffer[done..done + range])?;
+            done += range;
+        }
+
+        Ok(buffer)
+    }
+
+    pub fn write(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let mut done = 0usize;
+
+        for (entry, file_offset, range) in self.spans(offset, data.len() as u64) {
+            if entry.pad {
+                done += range;
+                continue;
+            }
+            let mut file = self.open_for_write(entry)?;
+            file.seek(SeekFrom::Start(file_offset))?;
+            file.write_all(&data[done..done + range])?;
+            done += range;
+        }
+
+        Ok(())
+    }
+
+    // Hex MD5 of a file as it is on disk, read in chunks so large files don't end up in
+    // memory.
+    pub fn file_md5(&self, entry: &FileEntry) -> io::Result<String> {
+        let mut file = File::open(self.current_path(entry))?;
+        let mut hasher = Md5::new();
+        let mut buffer = vec![0u8; 1 << 16];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+
+    pub fn read_block(&self, index: usize, begin: u32, length: u32) -> io::Result<Vec<u8>> {
+        if begin as u64 + length as u64 > self.piece_length {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Block exceeds piece size",
+            ));
+        }
+        let offset = index as u64 * self.piece_length + begin as u64;
+        if offset + length as u64 > self.total_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Block exceeds torrent size",
+            ));
+        }
+        self.read(offset, length as usize)
+    }
+
+    pub fn write_block(&self, index: usize, begin: u32, data: &[u8]) -> io::Result<()> {
+        let offset = index as u64 * self.piece_length + begin as u64;
+        if offset + data.len() as u64 > self.total_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Block exceeds torrent size",
+            ));
+        }
+        self.write(offset, data)
+    }
+
+    fn open_for_write(&self, entry: &FileEntry) -> io::Result<File> {
+        let path = self.current_path(entry);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let created = !path.exists();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if created && entry.executable {
+            make_executable(&file)?;
+        }
+        Ok(file)
+    }
+
+    // Moves one file to `new_path` (below the root) wherever it's staged, then records the
+    // name. The caller holds the names lock, so other lookups of the file's path wait.
+    fn move_file(
+        &self,
+        location: &Location,
+        names: &mut Names,
+        entry: &FileEntry,
+        new_path: PathBuf,
+    ) -> io::Result<()> {
+        let current = names
+            .renamed
+            .get(&entry.path)
+            .unwrap_or(&entry.path)
+            .clone();
+        if current == new_path {
+            return Ok(());
+        }
+        let taken = self.files.iter().any(|other| {
+            other.path != entry.path
+                && names.renamed.get(&other.path).unwrap_or(&other.path) == &new_path
+        });
+        let from = Self::path_in(location, &current);
+        let to = Self::path_in(location, &new_path);
+        if taken || to.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is already in use", new_path.display()),
+            ));
+        }
+        if from.exists() {
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if fs::rename(&from, &to).is_err() {
+                fs::copy(&from, &to)?;
+                fs::remove_file(&from)?;
+            }
+            Self::remove_empty_parents(&from, &location.root);
+        }
+        if new_path == entry.path {
+            names.renamed.remove(&entry.path);
+        } else {
+            names.renamed.insert(entry.path.clone(), new_path);
+        }
+        Ok(())
+    }
+
+    // Relative, non-empty and made only of ordinary names, so it stays below the root.
+    fn is_plain(path: &Path) -> bool {
+        path.components().next().is_some()
+            && path
+                .components()
+                .all(|part| matches!(part, Component::Normal(_)))
+    }
+
+    fn path_in(location: &Location, relative: &Path) -> PathBuf {
+        let mut path = location.root.join(relative).into_os_string();
+        if let Some(suffix) = &location.suffix {
+            path.push(suffix);
+        }
+        PathBuf::from(path)
+    }
+
+    // Cleans up directories a move left empty, stopping at `root`. Failures are harmless.
+    fn remove_empty_parents(path: &Path, root: &Path) {
+        let mut dir = path.parent();
+        while let Some(current) = dir {
+            if current == root || !current.starts_with(root) || fs::remove_dir(current).is_err() {
+                break;
+            }
+            dir = current.parent();
+        }
+    }
+
+    // Splits a range of the torrent's byte space into (file, offset in file, length) parts.
+    pub fn spans(&self, offset: u64, length: u64) -> Vec<(&FileEntry, u64, usize)> {
+        let end = offset + length;
+        self.files
+            .iter()
+            .filter(|entry| entry.offset < end && entry.offset + entry.length > offset)
+            .map(|entry| {
+                let start = offset.max(entry.offset);
+                let stop = end.min(entry.offset + entry.length);
+                (entry, start - entry.offset, (stop - start) as usize)
+            })
+            .collect()
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// Executable for whoever may read it, as `chmod +x` would do.
+#[cfg(unix)]
+fn make_executable(file: &File) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = file.metadata()?.permissions();
+    let mode = permissions.mode();
+    permissions.set_mode(mode | ((mode & 0o444) >> 2));
+    file.set_permissions(permissions)
+}
+
+#[cfg(not(unix))]
+fn make_executable(_file: &File) -> io::Result<()> {
+    Ok(())
+}
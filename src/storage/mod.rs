@@ -0,0 +1,4 @@
+pub mod backend;
+pub mod disk_space;
+pub mod file_storage;
+pub mod read_cache;
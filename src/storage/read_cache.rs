@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub cached_bytes: u64,
+}
+
+#[derive(Debug)]
+struct CachedPiece {
+    data: Arc<Vec<u8>>,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    // Keyed by info hash and piece index.
+    pieces: HashMap<([u8; 20], usize), CachedPiece>,
+    // Bumped on every lookup, so the smallest last_used is the least recently used piece.
+    clock: u64,
+    stats: CacheStats,
+}
+
+// Whole verified pieces kept in memory for serving uploads, least recently used out first.
+// Peers of a popular torrent tend to ask for the same pieces, and a piece read once serves
+// all of its blocks. Shared by every torrent in a session; a capacity of 0 turns it off.
+#[derive(Debug)]
+pub struct ReadCache {
+    capacity: u64,
+    inner: Mutex<Inner>,
+}
+
+impl ReadCache {
+    pub fn new(capacity: u64) -> ReadCache {
+        ReadCache {
+            capacity,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.inner.lock().unwrap().stats
+    }
+
+    // The piece's data, from memory if it's cached and from `load` otherwise. The lock isn't
+    // held while loading, so a slow disk doesn't hold up peers hitting the cache.
+    pub fn get_or_load(
+        &self,
+        info_hash: [u8; 20],
+        index: usize,
+        load: impl FnOnce() -> io::Result<Vec<u8>>,
+    ) -> io::Result<Arc<Vec<u8>>> {
+        let key = (info_hash, index);
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.clock += 1;
+            let clock = inner.clock;
+            if let Some(piece) = inner.pieces.get_mut(&key) {
+                piece.last_used = clock;
+                let data = Arc::clone(&piece.data);
+                inner.stats.hits += 1;
+                return Ok(data);
+            }
+            inner.stats.misses += 1;
+        }
+
+        let data = Arc::new(load()?);
+        let size = data.len() as u64;
+        if size == 0 || size > self.capacity {
+            return Ok(data);
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        while inner.stats.cached_bytes + size > self.capacity {
+            let Some(oldest) = inner
+                .pieces
+                .iter()
+                .min_by_key(|(_, piece)| piece.last_used)
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+            let evicted = inner.pieces.remove(&oldest).unwrap();
+            inner.stats.cached_bytes -= evicted.data.len() as u64;
+        }
+        let last_used = inner.clock;
+        let previous = inner.pieces.insert(
+            key,
+            CachedPiece {
+                data: Arc::clone(&data),
+                last_used,
+            },
+        );
+        // Another peer may have loaded the same piece meanwhile.
+        let replaced = previous.map_or(0, |piece| piece.data.len() as u64);
+        inner.stats.cached_bytes = inner.stats.cached_bytes + size - replaced;
+        Ok(data)
+    }
+
+    // Drops every piece of a torrent, e.g. once it's removed from the session.
+    pub fn remove_torrent(&self, info_hash: &[u8; 20]) {
+        let mut inner = self.inner.lock().unwrap();
+        let mut freed = 0;
+        inner.pieces.retain(|(hash, _), piece| {
+            let keep = hash != info_hash;
+            if !keep {
+                freed += piece.data.len() as u64;
+            }
+            keep
+        });
+        inner.stats.cached_bytes -= freed;
+    }
+}
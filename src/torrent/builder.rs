@@ -0,0 +1,354 @@
+use super::value::{File, FilesInfo, Info, TorrentMetaInfo};
+use crate::bencode::value::BencodeValue;
+use crate::units::{ByteSize, Rate};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+pub const DEFAULT_PIECE_LENGTH: usize = 256 * 1024;
+
+// Where hashing stands, handed to the progress callback after every piece.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HashProgress {
+    pub pieces_hashed: usize,
+    pub total_pieces: usize,
+    pub bytes_hashed: ByteSize,
+    pub total_bytes: ByteSize,
+    // Average since hashing started.
+    pub rate: Rate,
+}
+
+// Makes a v1 torrent of a file, or of a directory with everything below it. Pieces are
+// read and hashed by a pool of worker threads, one piece per worker at a time, so memory
+// stays at threads * piece_length however large the content is.
+#[derive(Debug, Clone)]
+pub struct TorrentBuilder {
+    path: PathBuf,
+    announce: String,
+    piece_length: usize,
+    threads: usize,
+    comment: Option<String>,
+    created_by: Option<String>,
+    private: bool,
+    url_list: Vec<String>,
+    pad_files: bool,
+}
+
+// One file of the content and where it starts in the torrent's byte stream.
+struct Entry {
+    source: PathBuf,
+    path: Vec<String>,
+    offset: u64,
+    length: u64,
+    pad: bool,
+}
+
+impl TorrentBuilder {
+    pub fn new(path: impl Into<PathBuf>) -> TorrentBuilder {
+        TorrentBuilder {
+            path: path.into(),
+            announce: String::new(),
+            piece_length: DEFAULT_PIECE_LENGTH,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            comment: None,
+            created_by: None,
+            private: false,
+            url_list: Vec::new(),
+            pad_files: false,
+        }
+    }
+
+    pub fn announce(mut self, url: &str) -> TorrentBuilder {
+        self.announce = url.to_string();
+        self
+    }
+
+    pub fn piece_length(mut self, piece_length: usize) -> TorrentBuilder {
+        self.piece_length = piece_length;
+        self
+    }
+
+    // Worker threads hashing pieces; all available cores unless set.
+    pub fn threads(mut self, threads: usize) -> TorrentBuilder {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn comment(mut self, comment: &str) -> TorrentBuilder {
+        self.comment = Some(comment.to_string());
+        self
+    }
+
+    pub fn created_by(mut self, created_by: &str) -> TorrentBuilder {
+        self.created_by = Some(created_by.to_string());
+        self
+    }
+
+    pub fn private(mut self, private: bool) -> TorrentBuilder {
+        self.private = private;
+        self
+    }
+
+    pub fn url_list(mut self, urls: Vec<String>) -> TorrentBuilder {
+        self.url_list = urls;
+        self
+    }
+
+    // Follows each file that doesn't end on a piece boundary with a BEP 47 pad file, so
+    // every file starts a piece of its own, as hybrid torrents require.
+    pub fn pad_files(mut self, pad_files: bool) -> TorrentBuilder {
+        self.pad_files = pad_files;
+        self
+    }
+
+    pub fn build(&self) -> Result<TorrentMetaInfo, Box<dyn Error>> {
+        self.build_with_progress(|_| {})
+    }
+
+    // Calls `progress` on the calling thread after each hashed piece. Pieces finish in
+    // whatever order the workers get to them; the counts only ever go up.
+    pub fn build_with_progress(
+        &self,
+        mut progress: impl FnMut(HashProgress),
+    ) -> Result<TorrentMetaInfo, Box<dyn Error>> {
+        if !self.piece_length.is_power_of_two() || self.piece_length < 16 * 1024 {
+            return Err(format!(
+                "Piece length must be a power of two of at least 16 KiB, not {}",
+                self.piece_length
+            )
+            .into());
+        }
+        let name = self
+            .path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or("Content path has no usable name")?
+            .to_string();
+        let single = fs::metadata(&self.path)?.is_file();
+        let entries = if single {
+            let length = fs::metadata(&self.path)?.len();
+            vec![Entry {
+                source: self.path.clone(),
+                path: vec![name.clone()],
+                offset: 0,
+                length,
+                pad: false,
+            }]
+        } else {
+            let mut entries = list_files(&self.path)?;
+            if self.pad_files {
+                entries = pad(entries, self.piece_length as u64);
+            }
+            entries
+        };
+        if entries.is_empty() {
+            return Err("Nothing to make a torrent of".into());
+        }
+
+        let total = entries.last().map_or(0, |e| e.offset + e.length);
+        let pieces = self.hash_pieces(&entries, total, &mut progress)?;
+        let files_info = if single {
+            FilesInfo::SingleFile {
+                length: total as usize,
+                md5sum: None,
+            }
+        } else {
+            FilesInfo::MultiFile {
+                files: entries
+                    .into_iter()
+                    .map(|entry| {
+                        if entry.pad {
+                            return File::padding(entry.length as usize);
+                        }
+                        File {
+                            length: entry.length as usize,
+                            path: entry.path,
+                            md5sum: None,
+                            extras: HashMap::new(),
+                        }
+                    })
+                    .collect(),
+            }
+        };
+        let mut extras = HashMap::new();
+        if self.private {
+            extras.insert("private".to_string(), BencodeValue::Integer(1));
+        }
+        Ok(TorrentMetaInfo {
+            url_list: self.url_list.clone(),
+            comment: self.comment.clone(),
+            created_by: self.created_by.clone(),
+            ..TorrentMetaInfo::new(
+                self.announce.clone(),
+                Info {
+                    name,
+                    piece_length: self.piece_length,
+                    pieces,
+                    files_info,
+                    extras,
+                },
+            )
+        })
+    }
+
+    fn hash_pieces(
+        &self,
+        entries: &[Entry],
+        total: u64,
+        progress: &mut impl FnMut(HashProgress),
+    ) -> Result<Vec<[u8; 20]>, Box<dyn Error>> {
+        let piece_length = self.piece_length as u64;
+        let total_pieces = total.div_ceil(piece_length) as usize;
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let (results, received) = mpsc::channel();
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(total_pieces.max(1)) {
+                let results = results.clone();
+                let (next, failed) = (&next, &failed);
+                scope.spawn(move || {
+                    let mut buffer = Vec::with_capacity(piece_length as usize);
+                    while !failed.load(Ordering::Relaxed) {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= total_pieces {
+                            break;
+                        }
+                        let start = index as u64 * piece_length;
+                        let length = piece_length.min(total - start);
+                        let result = read_range(entries, start, length, &mut buffer)
+                            .map(|()| (index, Sha1::digest(&buffer).into(), length));
+                        if result.is_err() {
+                            failed.store(true, Ordering::Relaxed);
+                        }
+                        if results.send(result).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(results);
+
+            let started = Instant::now();
+            let mut pieces = vec![[0u8; 20]; total_pieces];
+            let mut bytes = 0;
+            for (done, result) in received.into_iter().enumerate() {
+                let (index, hash, length) = result?;
+                pieces[index] = hash;
+                bytes += length;
+                let elapsed = started.elapsed().as_secs_f64();
+                progress(HashProgress {
+                    pieces_hashed: done + 1,
+                    total_pieces,
+                    bytes_hashed: ByteSize(bytes),
+                    total_bytes: ByteSize(total),
+                    rate: Rate(if elapsed > 0.0 {
+                        (bytes as f64 / elapsed) as u64
+                    } else {
+                        0
+                    }),
+                });
+            }
+            Ok(pieces)
+        })
+    }
+}
+
+// Every file below `root`, in path order so the same directory always makes the same
+// torrent.
+fn list_files(root: &Path) -> io::Result<Vec<Entry>> {
+    let mut found = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), Vec::new())];
+    while let Some((dir, prefix)) = pending.pop() {
+        for item in fs::read_dir(&dir)? {
+            let item = item?;
+            let name = item.file_name().to_string_lossy().into_owned();
+            let mut path: Vec<String> = prefix.clone();
+            path.push(name);
+            let metadata = fs::metadata(item.path())?;
+            if metadata.is_dir() {
+                pending.push((item.path(), path));
+            } else {
+                found.push((path, item.path(), metadata.len()));
+            }
+        }
+    }
+    found.sort();
+
+    let mut offset = 0;
+    Ok(found
+        .into_iter()
+        .map(|(path, source, length)| {
+            let entry = Entry {
+                source,
+                path,
+                offset,
+                length,
+                pad: false,
+            };
+            offset += length;
+            entry
+        })
+        .collect())
+}
+
+// Inserts pad files between `entries`, moving the files after them along. Nothing
+// follows the last file, which may end wherever.
+fn pad(entries: Vec<Entry>, piece_length: u64) -> Vec<Entry> {
+    let count = entries.len();
+    let mut padded = Vec::new();
+    let mut offset = 0;
+    for (n, mut entry) in entries.into_iter().enumerate() {
+        entry.offset = offset;
+        offset += entry.length;
+        padded.push(entry);
+        let gap = offset.next_multiple_of(piece_length) - offset;
+        if n + 1 < count && gap > 0 {
+            padded.push(Entry {
+                source: PathBuf::new(),
+                path: Vec::new(),
+                offset,
+                length: gap,
+                pad: true,
+            });
+            offset += gap;
+        }
+    }
+    padded
+}
+
+// Reads `length` bytes of the content starting at `start`, across file boundaries.
+fn read_range(entries: &[Entry], start: u64, length: u64, buffer: &mut Vec<u8>) -> io::Result<()> {
+    buffer.clear();
+    let end = start + length;
+    for entry in entries {
+        let (from, to) = (
+            start.max(entry.offset),
+            end.min(entry.offset + entry.length),
+        );
+        if from >= to {
+            continue;
+        }
+        if entry.pad {
+            buffer.resize(buffer.len() + (to - from) as usize, 0);
+            continue;
+        }
+        let mut file = fs::File::open(&entry.source)?;
+        file.seek(SeekFrom::Start(from - entry.offset))?;
+        let read = file.take(to - from).read_to_end(buffer)?;
+        if read as u64 != to - from {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{} changed while hashing", entry.source.display()),
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use sha1::{Digest, Sha1};
+
+use crate::bencode::encoder::{encode, encode_string};
+use crate::bencode::parser::parse_value;
+use crate::bencode::value::BencodeValue;
+
+// A .torrent file opened for changing its top-level keys: trackers, web seeds, comment and
+// the like. The info dict is kept as the exact bytes it was read as and written back
+// untouched, so the edited torrent has the same info hash and is the same swarm.
+#[derive(Debug, Clone)]
+pub struct TorrentEditor {
+    info: Vec<u8>,
+    // Every top-level key but "info"; sorted, as bencoded dicts have to be.
+    fields: BTreeMap<String, BencodeValue>,
+}
+
+impl TorrentEditor {
+    pub fn open(path: impl AsRef<Path>) -> Result<TorrentEditor, Box<dyn Error>> {
+        TorrentEditor::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(input: &[u8]) -> Result<TorrentEditor, Box<dyn Error>> {
+        let Some(mut rest) = input.strip_prefix(b"d") else {
+            return Err("Torrent file isn't a dictionary".into());
+        };
+        let mut info = None;
+        let mut fields = BTreeMap::new();
+        // Walked entry by entry rather than parsed whole, to get at the raw bytes of each
+        // value.
+        while !rest.starts_with(b"e") {
+            let (key, after_key) = parse_value(rest)?;
+            let key = key.as_string()?.to_string();
+            let (value, after_value) = parse_value(after_key)?;
+            if key == "info" {
+                value.as_dict()?;
+                info = Some(after_key[..after_key.len() - after_value.len()].to_vec());
+            } else {
+                fields.insert(key, value);
+            }
+            rest = after_value;
+        }
+        let info = info.ok_or("Torrent file has no info dictionary")?;
+        Ok(TorrentEditor { info, fields })
+    }
+
+    pub fn info_hash(&self) -> [u8; 20] {
+        Sha1::digest(&self.info).into()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = b"d".to_vec();
+        let mut info = Some(&self.info);
+        for (key, value) in &self.fields {
+            if key.as_str() > "info"
+                && let Some(info) = info.take()
+            {
+                result.extend(encode_string("info"));
+                result.extend_from_slice(info);
+            }
+            result.extend(encode_string(key));
+            result.extend(encode(value));
+        }
+        if let Some(info) = info {
+            result.extend(encode_string("info"));
+            result.extend_from_slice(info);
+        }
+        result.push(b'e');
+        result
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&BencodeValue> {
+        self.fields.get(key)
+    }
+
+    // Sets any top-level key but "info", including ones this client doesn't know of.
+    pub fn set(&mut self, key: &str, value: BencodeValue) -> Result<(), Box<dyn Error>> {
+        if key == "info" {
+            return Err("The info dictionary can't be edited".into());
+        }
+        self.fields.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    // Strips a top-level key, e.g. a tracker's own tags. The info dict can't be removed.
+    pub fn remove(&mut self, key: &str) -> Option<BencodeValue> {
+        self.fields.remove(key)
+    }
+
+    pub fn announce(&self) -> Option<&str> {
+        self.string("announce")
+    }
+
+    pub fn set_announce(&mut self, url: &str) {
+        self.set_string("announce", Some(url));
+    }
+
+    // BEP 12 tiers, each tried in order. Entries that aren't strings are skipped.
+    pub fn announce_list(&self) -> Vec<Vec<String>> {
+        let Some(BencodeValue::List(tiers)) = self.fields.get("announce-list") else {
+            return Vec::new();
+        };
+        tiers
+            .iter()
+            .filter_map(|tier| tier.as_list().ok())
+            .map(|tier| {
+                tier.iter()
+                    .filter_map(|url| url.as_string().ok().map(String::from))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Empty tiers are dropped, and an empty list removes the key.
+    pub fn set_announce_list(&mut self, tiers: Vec<Vec<String>>) {
+        let tiers: Vec<BencodeValue> = tiers
+            .into_iter()
+            .filter(|tier| !tier.is_empty())
+            .map(|tier| BencodeValue::List(tier.into_iter().map(BencodeValue::String).collect()))
+            .collect();
+        if tiers.is_empty() {
+            self.fields.remove("announce-list");
+        } else {
+            self.fields
+                .insert("announce-list".to_string(), BencodeValue::List(tiers));
+        }
+    }
+
+    // Adds the tracker as a tier of its own after the existing ones. Clients that read
+    // "announce-list" ignore "announce", so the current announce URL becomes the first
+    // tier when the list is created; a torrent without any tracker gets it as its announce.
+    pub fn add_tracker(&mut self, url: &str) {
+        let mut tiers = self.announce_list();
+        if self.announce() == Some(url) || tiers.iter().flatten().any(|known| known == url) {
+            return;
+        }
+        match self.announce() {
+            None if tiers.is_empty() => {
+                self.set_announce(url);
+                return;
+            }
+            Some(announce) if tiers.is_empty() => tiers.push(vec![announce.to_string()]),
+            _ => {}
+        }
+        tiers.push(vec![url.to_string()]);
+        self.set_announce_list(tiers);
+    }
+
+    // Takes the tracker out of every tier. If it was the announce URL, the first tracker
+    // left takes its place.
+    pub fn remove_tracker(&mut self, url: &str) {
+        let tiers: Vec<Vec<String>> = self
+            .announce_list()
+            .into_iter()
+            .map(|tier| tier.into_iter().filter(|known| known != url).collect())
+            .collect();
+        self.set_announce_list(tiers);
+        if self.announce() == Some(url) {
+            let next = self.announce_list().into_iter().flatten().next();
+            self.set_string("announce", next.as_deref());
+        }
+    }
+
+    // Replaces every tracker: the first one becomes the announce URL and each gets a tier.
+    pub fn replace_trackers(&mut self, urls: &[&str]) {
+        self.set_string("announce", urls.first().copied());
+        let tiers = if urls.len() > 1 {
+            urls.iter().map(|url| vec![url.to_string()]).collect()
+        } else {
+            Vec::new()
+        };
+        self.set_announce_list(tiers);
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.string("comment")
+    }
+
+    pub fn set_comment(&mut self, comment: Option<&str>) {
+        self.set_string("comment", comment);
+    }
+
+    pub fn created_by(&self) -> Option<&str> {
+        self.string("created by")
+    }
+
+    pub fn set_created_by(&mut self, created_by: Option<&str>) {
+        self.set_string("created by", created_by);
+    }
+
+    // Seconds since the unix epoch.
+    pub fn creation_date(&self) -> Option<i64> {
+        self.fields
+            .get("creation date")
+            .and_then(|date| date.as_int().ok())
+            .copied()
+    }
+
+    pub fn set_creation_date(&mut self, date: Option<i64>) {
+        match date {
+            Some(date) => {
+                self.fields
+                    .insert("creation date".to_string(), BencodeValue::Integer(date));
+            }
+            None => {
+                self.fields.remove("creation date");
+            }
+        }
+    }
+
+    // BEP 19 web seeds. A single URL may be stored as a plain string.
+    pub fn url_list(&self) -> Vec<String> {
+        match self.fields.get("url-list") {
+            Some(BencodeValue::String(url)) => vec![url.clone()],
+            Some(BencodeValue::List(urls)) => urls
+                .iter()
+                .filter_map(|url| url.as_string().ok().map(String::from))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn set_url_list(&mut self, urls: Vec<String>) {
+        if urls.is_empty() {
+            self.fields.remove("url-list");
+        } else {
+            let urls = urls.into_iter().map(BencodeValue::String).collect();
+            self.fields
+                .insert("url-list".to_string(), BencodeValue::List(urls));
+        }
+    }
+
+    fn string(&self, key: &str) -> Option<&str> {
+        self.fields
+            .get(key)
+            .and_then(|value| value.as_string().ok())
+    }
+
+    fn set_string(&mut self, key: &str, value: Option<&str>) {
+        match value {
+            Some(value) => {
+                let value = BencodeValue::String(value.to_string());
+                self.fields.insert(key.to_string(), value);
+            }
+            None => {
+                self.fields.remove(key);
+            }
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use super::value::TorrentMetaInfo;
+use crate::tracker::value::TrackerRequest;
+use std::error::Error;
+
+// The multihash prefix of a SHA-256 digest: function 0x12, length 0x20.
+const SHA256_MULTIHASH: [u8; 2] = [0x12, 0x20];
+
+// Which metainfo a magnet asks for, by the exact topics it carries: "btih" names a v1
+// info hash, "btmh" a v2 one (BEP 52), and a hybrid torrent's magnet has both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MagnetVersion {
+    V1,
+    V2,
+    Hybrid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MagnetLink {
+    pub info_hash: Option<[u8; 20]>,
+    pub info_hash_v2: Option<[u8; 32]>,
+    pub display_name: Option<String>,
+    pub trackers: Vec<String>,
+    pub web_seeds: Vec<String>,
+    // "x.pe" peer addresses, as host:port.
+    pub peers: Vec<String>,
+    pub length: Option<u64>,
+}
+
+impl MagnetLink {
+    // Accepts btih in hex or base32 and btmh as a hex SHA-256 multihash; several exact
+    // topics (xt, xt.1, ...) may appear. Parameters we don't know are ignored.
+    pub fn parse(uri: &str) -> Result<MagnetLink, Box<dyn Error>> {
+        let query = uri.strip_prefix("magnet:?").ok_or("Not a magnet link")?;
+        let mut magnet = MagnetLink::default();
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)?;
+            match key {
+                "dn" => magnet.display_name = Some(value),
+                "tr" => magnet.trackers.push(value),
+                "ws" => magnet.web_seeds.push(value),
+                "x.pe" => magnet.peers.push(value),
+                "xl" => magnet.length = value.parse().ok(),
+                _ if key == "xt" || key.starts_with("xt.") => magnet.add_topic(&value)?,
+                _ => {}
+            }
+        }
+
+        if magnet.info_hash.is_none() && magnet.info_hash_v2.is_none() {
+            return Err("Magnet link has no btih or btmh exact topic".into());
+        }
+        Ok(magnet)
+    }
+
+    fn add_topic(&mut self, topic: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(hash) = topic.strip_prefix("urn:btih:") {
+            let bytes = match hash.len() {
+                40 => decode_hex(hash),
+                32 => decode_base32(hash),
+                _ => None,
+            };
+            let hash = bytes
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| format!("Bad btih {}", hash))?;
+            self.info_hash = Some(hash);
+        } else if let Some(multihash) = topic.strip_prefix("urn:btmh:") {
+            let bytes = decode_hex(multihash).ok_or_else(|| format!("Bad btmh {}", multihash))?;
+            let digest = bytes
+                .strip_prefix(&SHA256_MULTIHASH[..])
+                .ok_or("Only SHA-256 btmh multihashes are supported")?;
+            let hash = digest
+                .try_into()
+                .map_err(|_| format!("Bad btmh {}", multihash))?;
+            self.info_hash_v2 = Some(hash);
+        }
+        // Other urns, such as ed2k or sha1 of a single file, aren't for us.
+        Ok(())
+    }
+
+    pub fn version(&self) -> MagnetVersion {
+        match (self.info_hash, self.info_hash_v2) {
+            (Some(_), Some(_)) => MagnetVersion::Hybrid,
+            (None, Some(_)) => MagnetVersion::V2,
+            _ => MagnetVersion::V1,
+        }
+    }
+
+    // The 20 bytes peers and trackers know the swarm by: the v1 hash where there is one,
+    // otherwise the v2 hash truncated, as BEP 52 has it.
+    pub fn swarm_hash(&self) -> [u8; 20] {
+        if let Some(hash) = self.info_hash {
+            return hash;
+        }
+        let mut hash = [0u8; 20];
+        hash.copy_from_slice(&self.info_hash_v2.unwrap_or_default()[..20]);
+        hash
+    }
+
+    // Whether fetched metainfo is the torrent the magnet names. Every hash the magnet
+    // carries has to match, so a hybrid magnet takes nothing less than the hybrid torrent.
+    pub fn matches(&self, meta: &TorrentMetaInfo) -> bool {
+        let v1 = self.info_hash.is_none_or(|hash| hash == meta.info_hash());
+        let v2 = (self.info_hash_v2).is_none_or(|hash| hash == meta.info_hash_v2());
+        v1 && v2
+    }
+
+    pub fn to_uri(&self) -> String {
+        let hex = |bytes: &[u8]| -> String { bytes.iter().map(|b| format!("{:02x}", b)).collect() };
+        let mut params = Vec::new();
+        if let Some(hash) = &self.info_hash {
+            params.push(format!("xt=urn:btih:{}", hex(hash)));
+        }
+        if let Some(hash) = &self.info_hash_v2 {
+            params.push(format!(
+                "xt=urn:btmh:{}{}",
+                hex(&SHA256_MULTIHASH),
+                hex(hash)
+            ));
+        }
+        let encode = |text: &str| TrackerRequest::url_encode_bytes(text.as_bytes());
+        if let Some(name) = &self.display_name {
+            params.push(format!("dn={}", encode(name)));
+        }
+        if let Some(length) = self.length {
+            params.push(format!("xl={}", length));
+        }
+        params.extend(
+            self.trackers
+                .iter()
+                .map(|url| format!("tr={}", encode(url))),
+        );
+        params.extend(
+            self.web_seeds
+                .iter()
+                .map(|url| format!("ws={}", encode(url))),
+        );
+        params.extend(
+            self.peers
+                .iter()
+                .map(|peer| format!("x.pe={}", encode(peer))),
+        );
+        format!("magnet:?{}", params.join("&"))
+    }
+}
+
+// %XX escapes, and '+' for a space as form encoding has it.
+fn percent_decode(text: &str) -> Result<String, Box<dyn Error>> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let byte = text
+                    .get(i + 1..i + 3)
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or_else(|| format!("Bad percent escape in {}", text))?;
+                decoded.push(byte);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Ok(String::from_utf8(decoded)?)
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// RFC 4648 base32 without padding, as older magnets write btih.
+fn decode_base32(text: &str) -> Option<Vec<u8>> {
+    let mut bits = 0u64;
+    let mut count = 0;
+    let mut bytes = Vec::new();
+    for c in text.bytes() {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        bits = (bits << 5) | value as u64;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Some(bytes)
+}
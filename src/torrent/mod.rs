@@ -1,2 +1,5 @@
+pub mod builder;
+pub mod edit;
+pub mod magnet;
 pub mod parser;
 pub mod value;
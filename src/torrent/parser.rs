@@ -1,77 +1,311 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
 
-use crate::bencode::helper::{get_bytes, get_dict, get_int, get_list, get_string};
-use crate::bencode::parser::parse_value;
+use crate::bencode::options::ParserOptions;
+use crate::bencode::parser::BencodeParser;
 use crate::bencode::value::BencodeValue;
+use crate::piece::merkle::{self, BLOCK_SIZE};
 
-use super::value::{File, FilesInfo, Info, TorrentMetaInfo};
+use super::value::{File, FilesInfo, Info, TorrentMetaInfo, V2File};
 
+// Lenient, so torrents from sloppy encoders still download.
 pub fn parse_torrent_file(path: &str) -> Result<TorrentMetaInfo, Box<dyn Error>> {
+    parse_torrent_file_with(path, ParserOptions::lenient())
+}
+
+// ParserOptions::strict() makes this a check that the file is valid bencode by the spec.
+pub fn parse_torrent_file_with(
+    path: &str,
+    options: ParserOptions,
+) -> Result<TorrentMetaInfo, Box<dyn Error>> {
     let contents = fs::read(path)?;
-    let (bencode_value, _) = parse_value(&contents)?;
-    torrent_from_bencode(&bencode_value)
+    parse_torrent_bytes(&contents, options)
+}
+
+// Unlike torrent_from_bencode, keeps the info dict as the bytes it was read from, so the
+// info hash is the one the rest of the swarm computes even for non-canonical encodings.
+pub fn parse_torrent_bytes(
+    input: &[u8],
+    options: ParserOptions,
+) -> Result<TorrentMetaInfo, Box<dyn Error>> {
+    let parser = BencodeParser::with_options(options);
+    let (document, piece_layers) = take_piece_layers(&parser, input);
+    let (bencode_value, _) = parser.parse(&document)?;
+    let mut meta = torrent_from_bencode(&bencode_value)?;
+    meta.raw_info = Some(raw_info(&parser, &document)?.to_vec());
+    add_piece_layers(&mut meta, &piece_layers);
+    Ok(meta)
+}
+
+// Raw pieces root -> the concatenated hashes of its piece layer.
+type RawPieceLayers = HashMap<Vec<u8>, Vec<u8>>;
+
+// "piece layers" is keyed by raw pieces roots, which the parser won't take as dictionary
+// keys, so the entry is cut out of the document and read here. When anything about it
+// looks off the document is left whole, for the parser to report.
+fn take_piece_layers<'a>(
+    parser: &BencodeParser,
+    input: &'a [u8],
+) -> (Cow<'a, [u8]>, RawPieceLayers) {
+    let Some(mut rest) = input.strip_prefix(b"d") else {
+        return (Cow::Borrowed(input), HashMap::new());
+    };
+    while !rest.is_empty() && !rest.starts_with(b"e") {
+        let Ok((key, after_key)) = parser.parse(rest) else {
+            break;
+        };
+        if key.as_string().is_ok_and(|key| key == "piece layers")
+            && let Some((layers, after_value)) = byte_string_dict(parser, after_key)
+        {
+            let start = input.len() - rest.len();
+            let end = input.len() - after_value.len();
+            let document = [&input[..start], &input[end..]].concat();
+            return (Cow::Owned(document), layers);
+        }
+        let Ok((_, after_value)) = parser.parse(after_key) else {
+            break;
+        };
+        rest = after_value;
+    }
+    (Cow::Borrowed(input), HashMap::new())
+}
+
+// A dict whose keys and values are all strings, with whatever follows it.
+fn byte_string_dict<'a>(
+    parser: &BencodeParser,
+    input: &'a [u8],
+) -> Option<(RawPieceLayers, &'a [u8])> {
+    let mut rest = input.strip_prefix(b"d")?;
+    let mut dict = HashMap::new();
+    while !rest.starts_with(b"e") {
+        let (key, after_key) = byte_string(parser, rest)?;
+        let (value, after_value) = byte_string(parser, after_key)?;
+        dict.insert(key, value);
+        rest = after_value;
+    }
+    Some((dict, &rest[1..]))
+}
+
+fn byte_string<'a>(parser: &BencodeParser, input: &'a [u8]) -> Option<(Vec<u8>, &'a [u8])> {
+    let (value, rest) = parser.parse(input).ok()?;
+    let bytes = match &value {
+        BencodeValue::Bytes(bytes) => bytes.clone(),
+        BencodeValue::String(string) => string.as_bytes().to_vec(),
+        _ => return None,
+    };
+    Some((bytes, rest))
+}
+
+// Gives each v2 file longer than a piece its layer from "piece layers". A layer that
+// doesn't add up to the file's pieces root is left out and fetched from peers instead.
+fn add_piece_layers(meta: &mut TorrentMetaInfo, piece_layers: &RawPieceLayers) {
+    let piece_length = meta.info.piece_length;
+    let blocks_per_piece = piece_length / BLOCK_SIZE;
+    if !blocks_per_piece.is_power_of_two() || !piece_length.is_multiple_of(BLOCK_SIZE) {
+        return;
+    }
+    let pad = merkle::pad_hash(blocks_per_piece);
+    for file in meta
+        .v2_files
+        .iter_mut()
+        .filter(|file| file.length > piece_length)
+    {
+        let Some(layer) = piece_layers.get(file.pieces_root.as_slice()) else {
+            continue;
+        };
+        let hashes: Vec<[u8; 32]> = layer
+            .chunks_exact(32)
+            .map(|hash| hash.try_into().unwrap())
+            .collect();
+        let width = file.length.div_ceil(BLOCK_SIZE).next_power_of_two() / blocks_per_piece;
+        if layer.len() == 32 * file.length.div_ceil(piece_length)
+            && merkle::root_with_padding(&hashes, width, pad) == file.pieces_root
+        {
+            file.piece_layer = hashes;
+        }
+    }
 }
 
-fn get_files_info(dict: &HashMap<String, BencodeValue>) -> Result<FilesInfo, Box<dyn Error>> {
+// Finds the "info" value among the top-level entries. Each entry is parsed on its own
+// to learn where it ends; the parse before already checked the whole is valid.
+fn raw_info<'a>(parser: &BencodeParser, input: &'a [u8]) -> Result<&'a [u8], Box<dyn Error>> {
+    let mut rest = input
+        .strip_prefix(b"d")
+        .ok_or("Torrent file isn't a dictionary")?;
+    let mut info = None;
+    while !rest.is_empty() && !rest.starts_with(b"e") {
+        let (key, after_key) = parser.parse(rest)?;
+        let (_, after_value) = parser.parse(after_key)?;
+        // With repeated keys the last one counts, as in the parsed dict.
+        if key.as_string()? == "info" {
+            info = Some(&after_key[..after_key.len() - after_value.len()]);
+        }
+        rest = after_value;
+    }
+    Ok(info.ok_or("Torrent file has no info dictionary")?)
+}
+
+fn get_files_info(info: &BencodeValue) -> Result<FilesInfo, Box<dyn Error>> {
     // There is also a key 'length' or a key 'files', but not both or neither.
     // If length is present then the download represents a single file,
     // otherwise it represents a set of files which go in a directory structure.
-    let has_length = dict.contains_key("length");
-    let has_files = dict.contains_key("files");
+    let has_length = info.get("length").is_ok();
+    let has_files = info.get("files").is_ok();
 
     match (has_length, has_files) {
         (true, false) => {
-            let length = get_int(dict, "length")? as usize;
-            Ok(FilesInfo::SingleFile { length })
+            let length = info.int("length")? as usize;
+            let md5sum = info.string("md5sum").ok().map(String::from);
+            Ok(FilesInfo::SingleFile { length, md5sum })
         }
         (false, true) => {
-            let files = parse_files_list(dict)?;
+            let files = parse_files_list(info)?;
             Ok(FilesInfo::MultiFile { files })
         }
         _ => Err("Must have exactly one of 'length' or 'files'".into()),
     }
 }
 
-pub fn parse_files_list(dict: &HashMap<String, BencodeValue>) -> Result<Vec<File>, Box<dyn Error>> {
-    get_list(dict, "files")?
+pub fn parse_files_list(info: &BencodeValue) -> Result<Vec<File>, Box<dyn Error>> {
+    info.list("files")?
+        .as_list()?
         .iter()
-        .map(|file_value| {
-            let file_dict = file_value.as_dict()?;
-            let length = get_int(file_dict, "length")? as usize;
-            let path = get_list(file_dict, "path")?
+        .map(|file| {
+            let length = file.int("length")? as usize;
+            let path = file
+                .list("path")?
+                .as_list()?
                 .iter()
                 .map(|p| p.as_string().map(String::from))
                 .collect::<Result<Vec<_>, _>>()?;
-            Ok(File { length, path })
+            let md5sum = file.string("md5sum").ok().map(String::from);
+            Ok(File {
+                length,
+                path,
+                md5sum,
+                extras: extras(file, &["length", "path", "md5sum"]),
+            })
         })
         .collect()
 }
 
-pub fn torrent_from_bencode(input: &BencodeValue) -> Result<TorrentMetaInfo, Box<dyn Error>> {
-    let bencode_dict = input.as_dict()?;
+// Collects the URLs of an optional list, skipping entries that aren't strings. BEP 19
+// also allows "url-list" to be a single URL.
+fn url_list(input: &BencodeValue, key: &str) -> Vec<String> {
+    match input.get(key) {
+        Ok(BencodeValue::String(url)) => vec![url.clone()],
+        Ok(BencodeValue::List(list)) => list
+            .iter()
+            .filter_map(|url| url.as_string().ok().map(String::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Every entry of `dict` but the `known` keys.
+fn extras(dict: &BencodeValue, known: &[&str]) -> HashMap<String, BencodeValue> {
+    let Ok(dict) = dict.as_dict() else {
+        return HashMap::new();
+    };
+    dict.iter()
+        .filter(|(key, _)| !known.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
 
-    let announce = get_string(bencode_dict, "announce")?;
-    let info_dict = get_dict(bencode_dict, "info")?;
+fn optional_string(input: &BencodeValue, key: &str) -> Option<String> {
+    input.string(key).ok().map(String::from)
+}
+
+// Hybrid torrents describe the same files a second time in a BEP 52 "file tree": nested
+// dicts keyed by path component, with each file's length and "pieces root" under an empty
+// key. Files are matched up by path and placed at their v1 offsets; pad files and files
+// missing from the tree get no v2 hashes.
+//
+// Piece layers for files longer than a piece live in the top-level "piece layers" dict,
+// which only parse_torrent_bytes can read (see take_piece_layers).
+fn v2_files(info: &BencodeValue, name: &str, files_info: &FilesInfo) -> Vec<V2File> {
+    let Ok(tree) = info.dict("file tree") else {
+        return Vec::new();
+    };
+
+    let files: Vec<(Vec<String>, usize)> = match files_info {
+        FilesInfo::SingleFile { length, .. } => vec![(vec![name.to_string()], *length)],
+        FilesInfo::MultiFile { files } => files
+            .iter()
+            .map(|file| (file.path.clone(), file.length))
+            .collect(),
+    };
+
+    let mut v2_files = Vec::new();
+    let mut offset = 0;
+    for (path, length) in files {
+        let node = path
+            .iter()
+            .try_fold(tree, |node, component| node.get(component))
+            .and_then(|node| node.dict(""));
+        if let Ok(node) = node
+            && let Ok(root) = node.bytes("pieces root")
+            && let Ok(pieces_root) = root.try_into()
+            && node.int("length").ok() == Some(length as i64)
+        {
+            v2_files.push(V2File {
+                offset,
+                length,
+                pieces_root,
+                piece_layer: Vec::new(),
+            });
+        }
+        offset += length;
+    }
+    v2_files
+}
+
+pub fn torrent_from_bencode(input: &BencodeValue) -> Result<TorrentMetaInfo, Box<dyn Error>> {
+    let announce = input.string("announce")?.to_string();
+    let info = input.dict("info")?;
 
-    let name = get_string(info_dict, "name")?;
-    let piece_length: usize = get_int(info_dict, "piece length")? as usize;
-    let pieces_bytes = get_bytes(info_dict, "pieces")?;
-    let pieces: Vec<[u8; 20]> = pieces_bytes
+    let name = info.string("name")?.to_string();
+    let piece_length = info.int("piece length")? as usize;
+    let pieces: Vec<[u8; 20]> = info
+        .bytes("pieces")?
         .chunks(20)
         .map(|chunk| chunk.try_into().map_err(|_| "Invalid piece length"))
         .collect::<Result<Vec<_>, _>>()?;
 
-    let files_info = get_files_info(info_dict)?;
+    let files_info = get_files_info(info)?;
+    let v2_files = v2_files(info, &name, &files_info);
 
-    Ok(TorrentMetaInfo {
+    let meta = TorrentMetaInfo {
         announce,
         info: Info {
             name,
             piece_length,
             pieces,
             files_info,
+            extras: extras(
+                info,
+                &[
+                    "name",
+                    "piece length",
+                    "pieces",
+                    "length",
+                    "md5sum",
+                    "files",
+                ],
+            ),
         },
-    })
+        http_seeds: url_list(input, "httpseeds"),
+        url_list: url_list(input, "url-list"),
+        v2_files,
+        comment: optional_string(input, "comment"),
+        created_by: optional_string(input, "created by"),
+        creation_date: input.int("creation date").ok(),
+        encoding: optional_string(input, "encoding"),
+        raw_info: None,
+    };
+    meta.info.check_paths()?;
+    Ok(meta)
 }
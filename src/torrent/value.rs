@@ -1,26 +1,185 @@
+use crate::bencode::encoder;
 use crate::bencode::value::BencodeValue;
-use std::collections::HashMap;
+use crate::piece::merkle::BLOCK_SIZE;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path};
 
+#[derive(Debug, Clone)]
 pub struct File {
     pub length: usize,
     pub path: Vec<String>,
+    // Optional hex MD5 of the file's contents, as some older torrents carry.
+    pub md5sum: Option<String>,
+    // Keys of the file's dict not modelled above, e.g. BEP 47 "attr".
+    pub extras: HashMap<String, BencodeValue>,
 }
 
+#[derive(Debug, Clone)]
 pub enum FilesInfo {
-    SingleFile { length: usize },
-    MultiFile { files: Vec<File> },
+    SingleFile {
+        length: usize,
+        md5sum: Option<String>,
+    },
+    MultiFile {
+        files: Vec<File>,
+    },
 }
 
+#[derive(Debug, Clone)]
 pub struct Info {
     pub name: String,
     pub piece_length: usize,
     pub pieces: Vec<[u8; 20]>,
     pub files_info: FilesInfo,
+    // Keys of the info dict not modelled above, such as "private", "source" or a hybrid
+    // torrent's "file tree". The info hash covers them too, so they're kept as parsed and
+    // written back.
+    pub extras: HashMap<String, BencodeValue>,
 }
 
+// The BEP 47 "attr" flags of a file. Letters this crate doesn't know are ignored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileAttributes {
+    // Zeros that push the next file onto a piece boundary. Part of the piece space, never
+    // of the download.
+    pub padding: bool,
+    // No data of its own; a link to the file at "symlink path".
+    pub symlink: bool,
+    pub executable: bool,
+    pub hidden: bool,
+}
+
+impl FileAttributes {
+    pub fn parse(attr: &[u8]) -> FileAttributes {
+        FileAttributes {
+            padding: attr.contains(&b'p'),
+            symlink: attr.contains(&b'l'),
+            executable: attr.contains(&b'x'),
+            hidden: attr.contains(&b'h'),
+        }
+    }
+
+    // From the "attr" key of a file dict, or of the info dict of a single-file torrent.
+    pub fn of(extras: &HashMap<String, BencodeValue>) -> FileAttributes {
+        match extras.get("attr") {
+            Some(BencodeValue::String(attr)) => FileAttributes::parse(attr.as_bytes()),
+            Some(BencodeValue::Bytes(attr)) => FileAttributes::parse(attr),
+            _ => FileAttributes::default(),
+        }
+    }
+}
+
+impl File {
+    pub fn attributes(&self) -> FileAttributes {
+        FileAttributes::of(&self.extras)
+    }
+
+    pub fn is_padding(&self) -> bool {
+        self.attributes().padding
+    }
+
+    // Where a symlink points, as path components below the torrent's top directory.
+    // None unless the file is flagged as one and says where to.
+    pub fn symlink_path(&self) -> Option<Vec<String>> {
+        if !self.attributes().symlink {
+            return None;
+        }
+        let BencodeValue::List(parts) = self.extras.get("symlink path")? else {
+            return None;
+        };
+        parts
+            .iter()
+            .map(|part| part.as_string().ok().map(str::to_string))
+            .collect()
+    }
+
+    // A pad file of `length` zeros, named the way BEP 47 suggests.
+    pub fn padding(length: usize) -> File {
+        File {
+            length,
+            path: vec![".pad".to_string(), length.to_string()],
+            md5sum: None,
+            extras: HashMap::from([("attr".to_string(), BencodeValue::String("p".into()))]),
+        }
+    }
+}
+
+impl Info {
+    // BEP 27: peers only come from the torrent's trackers, which usually also expect
+    // everyone to keep seeding.
+    pub fn is_private(&self) -> bool {
+        matches!(self.extras.get("private"), Some(BencodeValue::Integer(1)))
+    }
+
+    // The name and every file path element end up joined below the save directory, so each
+    // has to be one plain component: no "..", no root, no separators and not empty.
+    pub fn check_paths(&self) -> Result<(), String> {
+        let mut parts = vec![&self.name];
+        if let FilesInfo::MultiFile { files } = &self.files_info {
+            for file in files {
+                if file.path.is_empty() {
+                    return Err("File with an empty path".to_string());
+                }
+                parts.extend(&file.path);
+            }
+        }
+        match parts.into_iter().find(|part| !is_plain_component(part)) {
+            Some(part) => Err(format!("Unsafe path component: {:?}", part)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn is_plain_component(part: &str) -> bool {
+    let mut components = Path::new(part).components();
+    !part.contains(['/', '\\'])
+        && matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+}
+
+// BEP 52 hashes of one file in a hybrid torrent. `offset` is where the file starts in the
+// v1 piece space, which pad files keep on a piece boundary.
+#[derive(Debug, Clone)]
+pub struct V2File {
+    pub offset: usize,
+    pub length: usize,
+    pub pieces_root: [u8; 32],
+    // Empty for files no longer than a piece, whose root covers them whole.
+    pub piece_layer: Vec<[u8; 32]>,
+}
+
+// A v1 piece seen from the v2 side: the hash its file data must match, how many bytes of
+// file data it holds (the rest is padding) and how many leaves its tree has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct V2Piece {
+    pub hash: [u8; 32],
+    pub length: usize,
+    pub leaves: usize,
+}
+
+#[derive(Debug, Clone)]
 pub struct TorrentMetaInfo {
     pub announce: String,
     pub info: Info,
+    // BEP 17 HTTP seed URLs ("httpseeds").
+    pub http_seeds: Vec<String>,
+    // BEP 19 web seed URLs ("url-list"), plain HTTP servers hosting the files.
+    pub url_list: Vec<String>,
+    // Per-file v2 hashes of a hybrid torrent; empty for v1-only ones.
+    pub v2_files: Vec<V2File>,
+    // Optional creation metadata from the top level of the file.
+    pub comment: Option<String>,
+    pub created_by: Option<String>,
+    // Seconds since the unix epoch.
+    pub creation_date: Option<i64>,
+    // Character set the strings were written in; UTF-8 when missing.
+    pub encoding: Option<String>,
+    // The info dict exactly as it appeared in the parsed file. The info hash is taken over
+    // these bytes, so it matches the swarm's even when the encoder was sloppy. None for
+    // torrents built in memory, whose `info` is encoded instead; set it to None after
+    // changing `info` of a parsed torrent.
+    pub raw_info: Option<Vec<u8>>,
 }
 
 pub trait ToBencode {
@@ -29,7 +188,7 @@ pub trait ToBencode {
 
 impl ToBencode for Info {
     fn to_bencode_value(&self) -> BencodeValue {
-        let mut dict = HashMap::new();
+        let mut dict = self.extras.clone();
 
         dict.insert("name".to_string(), BencodeValue::String(self.name.clone()));
         dict.insert(
@@ -45,8 +204,11 @@ impl ToBencode for Info {
         dict.insert("pieces".to_string(), BencodeValue::Bytes(pieces_bytes));
 
         match &self.files_info {
-            FilesInfo::SingleFile { length } => {
+            FilesInfo::SingleFile { length, md5sum } => {
                 dict.insert("length".to_string(), BencodeValue::Integer(*length as i64));
+                if let Some(md5sum) = md5sum {
+                    dict.insert("md5sum".to_string(), BencodeValue::String(md5sum.clone()));
+                }
             }
             FilesInfo::MultiFile { files } => {
                 let files_list: Vec<BencodeValue> =
@@ -61,7 +223,7 @@ impl ToBencode for Info {
 
 impl ToBencode for File {
     fn to_bencode_value(&self) -> BencodeValue {
-        let mut dict = HashMap::new();
+        let mut dict = self.extras.clone();
 
         dict.insert(
             "length".to_string(),
@@ -74,24 +236,132 @@ impl ToBencode for File {
             .map(|s| BencodeValue::String(s.clone()))
             .collect();
         dict.insert("path".to_string(), BencodeValue::List(path_list));
+        if let Some(md5sum) = &self.md5sum {
+            dict.insert("md5sum".to_string(), BencodeValue::String(md5sum.clone()));
+        }
+
+        BencodeValue::Dictionary(dict)
+    }
+}
+
+impl ToBencode for TorrentMetaInfo {
+    fn to_bencode_value(&self) -> BencodeValue {
+        let mut dict = HashMap::new();
+
+        dict.insert(
+            "announce".to_string(),
+            BencodeValue::String(self.announce.clone()),
+        );
+        dict.insert("info".to_string(), self.info.to_bencode_value());
+
+        if !self.http_seeds.is_empty() {
+            let seeds = self
+                .http_seeds
+                .iter()
+                .map(|url| BencodeValue::String(url.clone()))
+                .collect();
+            dict.insert("httpseeds".to_string(), BencodeValue::List(seeds));
+        }
+
+        if !self.url_list.is_empty() {
+            let urls = self
+                .url_list
+                .iter()
+                .map(|url| BencodeValue::String(url.clone()))
+                .collect();
+            dict.insert("url-list".to_string(), BencodeValue::List(urls));
+        }
+
+        let strings = [
+            ("comment", &self.comment),
+            ("created by", &self.created_by),
+            ("encoding", &self.encoding),
+        ];
+        for (key, value) in strings {
+            if let Some(value) = value {
+                dict.insert(key.to_string(), BencodeValue::String(value.clone()));
+            }
+        }
+        if let Some(date) = self.creation_date {
+            dict.insert("creation date".to_string(), BencodeValue::Integer(date));
+        }
 
         BencodeValue::Dictionary(dict)
     }
 }
 
 impl TorrentMetaInfo {
+    // A torrent with just a tracker and an info dict; the optional top-level keys start
+    // out empty.
+    pub fn new(announce: String, info: Info) -> TorrentMetaInfo {
+        TorrentMetaInfo {
+            announce,
+            info,
+            http_seeds: Vec::new(),
+            url_list: Vec::new(),
+            v2_files: Vec::new(),
+            comment: None,
+            created_by: None,
+            creation_date: None,
+            encoding: None,
+            raw_info: None,
+        }
+    }
+
+    // The bencoded info dict the info hashes are taken over.
+    pub fn info_bytes(&self) -> Cow<'_, [u8]> {
+        match &self.raw_info {
+            Some(raw) => Cow::Borrowed(raw),
+            None => Cow::Owned(encoder::encode(&self.info.to_bencode_value())),
+        }
+    }
+
     pub fn info_hash(&self) -> [u8; 20] {
-        use crate::bencode::encoder;
         use sha1::{Digest, Sha1};
 
-        let info_bencode = self.info.to_bencode_value();
-        let bencode_bytes = encoder::encode(&info_bencode);
-
         let mut hasher = Sha1::new();
-        hasher.update(&bencode_bytes);
+        hasher.update(self.info_bytes());
         hasher.finalize().into()
     }
 
+    // SHA-256 of the info dict, which identifies a v2 or hybrid torrent (BEP 52). Not
+    // meaningful for a v1-only torrent.
+    pub fn info_hash_v2(&self) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+
+        Sha256::digest(self.info_bytes()).into()
+    }
+
+    // Same as info_hash, but refuses info dicts crafted to collide when collision
+    // detection is enabled.
+    pub fn checked_info_hash(
+        &self,
+        mode: crate::piece::hash::Sha1Mode,
+    ) -> Result<[u8; 20], crate::piece::hash::CollisionDetected> {
+        crate::piece::hash::sha1(&self.info_bytes(), mode)
+    }
+
+    // The whole torrent bencoded, with the info dict from info_bytes so that reading it
+    // back gives the same info hash.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut entries = BTreeMap::new();
+        if let BencodeValue::Dictionary(dict) = &self.to_bencode_value() {
+            entries.extend(
+                dict.iter()
+                    .map(|(key, value)| (key.clone(), encoder::encode(value))),
+            );
+        }
+        entries.insert("info".to_string(), self.info_bytes().into_owned());
+
+        let mut bytes = b"d".to_vec();
+        for (key, value) in entries {
+            bytes.extend(encoder::encode_string(&key));
+            bytes.extend(value);
+        }
+        bytes.push(b'e');
+        bytes
+    }
+
     // pub fn info_hash_urlencoded(&self) -> String {
     //     self.info_hash()
     //         .iter()
@@ -101,7 +371,7 @@ impl TorrentMetaInfo {
 
     pub fn total_size(&self) -> usize {
         match &self.info.files_info {
-            FilesInfo::SingleFile { length } => *length,
+            FilesInfo::SingleFile { length, .. } => *length,
             FilesInfo::MultiFile { files } => files.iter().map(|f| f.length).sum(),
         }
     }
@@ -109,4 +379,50 @@ impl TorrentMetaInfo {
     pub fn num_pieces(&self) -> usize {
         self.info.pieces.len()
     }
+
+    // The v2 hash covering piece `index`, if the torrent is hybrid and the piece starts on
+    // a v2 piece boundary of a file we have hashes for.
+    pub fn v2_piece(&self, index: usize) -> Option<V2Piece> {
+        self.v2_piece_with(index, |file, n| file.piece_layer.get(n).copied())
+    }
+
+    // Same as v2_piece, with `layer_hash` looking up the `n`th piece layer hash of a file
+    // longer than a piece, e.g. in layers fetched from peers.
+    pub fn v2_piece_with(
+        &self,
+        index: usize,
+        layer_hash: impl FnOnce(&V2File, usize) -> Option<[u8; 32]>,
+    ) -> Option<V2Piece> {
+        let piece_length = self.info.piece_length;
+        let start = index * piece_length;
+        let file = self
+            .v2_files
+            .iter()
+            .find(|file| file.offset <= start && start < file.offset + file.length)?;
+        let local = start - file.offset;
+        if !local.is_multiple_of(piece_length) {
+            return None;
+        }
+
+        if file.length <= piece_length {
+            return Some(V2Piece {
+                hash: file.pieces_root,
+                length: file.length,
+                leaves: file.length.div_ceil(BLOCK_SIZE).next_power_of_two(),
+            });
+        }
+        Some(V2Piece {
+            hash: layer_hash(file, local / piece_length)?,
+            length: piece_length.min(file.length - local),
+            leaves: piece_length / BLOCK_SIZE,
+        })
+    }
+
+    // Every piece is piece_length long except the last one, which holds whatever is left.
+    pub fn piece_size(&self, index: usize) -> usize {
+        let start = index * self.info.piece_length;
+        self.info
+            .piece_length
+            .min(self.total_size().saturating_sub(start))
+    }
 }
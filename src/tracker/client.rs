@@ -1,8 +1,25 @@
-use super::value::{Peer, TrackerRequest, TrackerResponse};
-use crate::bencode::helper::{get_bytes, get_int, get_list, get_string};
-use crate::bencode::parser::parse_value;
+use super::error::TrackerError;
+use super::value::{
+    AnnounceTimings, Peer, ScrapeResponse, ScrapeStats, TrackerRequest, TrackerResponse,
+    append_query, scrape_url,
+};
+#[cfg(feature = "webtorrent")]
+use super::{filter::is_web_tracker, websocket::WebTrackerClient};
+use crate::bencode::limits::ParseLimits;
+use crate::bencode::parser::{BencodeParser, parse_string};
 use crate::bencode::value::BencodeValue;
+use crate::peer::socks::Socks5Proxy;
 use std::error::Error;
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Per address tried while timing the connection to a tracker.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// For a whole request, redirects included, unless the caller picks another.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+// Enough for http to https plus a move or two; more is a loop.
+pub const MAX_REDIRECTS: usize = 5;
 
 pub struct TrackerClient;
 
@@ -10,43 +27,398 @@ impl TrackerClient {
     pub fn query_tracker(
         request: &TrackerRequest,
     ) -> Result<TrackerResponse, Box<dyn std::error::Error>> {
-        let client = reqwest::blocking::Client::builder().build()?;
-        let url = request.build_url();
+        Self::query_tracker_within(request, None, DEFAULT_TIMEOUT)
+    }
+
+    // Announces through a SOCKS5 proxy, which resolves the tracker's hostname as well, so
+    // no DNS query leaves this host either.
+    pub fn query_tracker_via(
+        request: &TrackerRequest,
+        proxy: &Socks5Proxy,
+    ) -> Result<TrackerResponse, Box<dyn Error>> {
+        Self::query_tracker_within(request, Some(proxy), DEFAULT_TIMEOUT)
+    }
+
+    // Gives up after `timeout` in all, e.g. the session's tracker_timeout, or the shorter
+    // stopped_announce_timeout for announces that mustn't hold up shutdown.
+    // WebTorrent trackers are asked too, with the webtorrent feature; they can't go
+    // through the proxy.
+    pub fn query_tracker_within(
+        request: &TrackerRequest,
+        proxy: Option<&Socks5Proxy>,
+        timeout: Duration,
+    ) -> Result<TrackerResponse, Box<dyn Error>> {
+        #[cfg(feature = "webtorrent")]
+        if is_web_tracker(&request.announce_url) {
+            if proxy.is_some() {
+                return Err("WebTorrent trackers can't be reached through a SOCKS5 proxy".into());
+            }
+            return WebTrackerClient::announce(request, timeout, Duration::ZERO)
+                .map(|announce| announce.response);
+        }
+        let client = http_client(proxy, timeout)?;
+        parse_tracker_response(&fetch(&client, request.build_url())?)
+    }
+
+    // Announces over IPv4 and IPv6 at the same time when the tracker's host has addresses
+    // in both families, so the tracker learns both of our addresses and hands out peers
+    // from both, and merges the two responses. Falls back to a single announce for IP
+    // literals and single-family hosts. Fails only if both announces do.
+    pub fn query_tracker_dual(request: &TrackerRequest) -> Result<TrackerResponse, Box<dyn Error>> {
+        Self::query_tracker_dual_within(request, DEFAULT_TIMEOUT)
+    }
+
+    pub fn query_tracker_dual_within(
+        request: &TrackerRequest,
+        timeout: Duration,
+    ) -> Result<TrackerResponse, Box<dyn Error>> {
+        let url = reqwest::Url::parse(&request.build_url())?;
+        let Some(host) = url.domain() else {
+            return Self::query_tracker_within(request, None, timeout);
+        };
+        let port = url
+            .port_or_known_default()
+            .ok_or("Tracker URL has no port")?;
+        let (v6, v4): (Vec<SocketAddr>, Vec<SocketAddr>) = (host, port)
+            .to_socket_addrs()?
+            .partition(SocketAddr::is_ipv6);
+        if v4.is_empty() || v6.is_empty() {
+            return Self::query_tracker_within(request, None, timeout);
+        }
+
+        // Box<dyn Error> isn't Send, so the other family's error comes back as a string.
+        let (over_v4, over_v6) = thread::scope(|scope| {
+            let over_v6 = scope.spawn(|| {
+                Self::query_pinned(&url, host, &v6, timeout).map_err(|err| err.to_string())
+            });
+            let over_v4 = Self::query_pinned(&url, host, &v4, timeout);
+            let over_v6 = over_v6
+                .join()
+                .unwrap_or_else(|_| Err("IPv6 announce panicked".to_string()));
+            (over_v4, over_v6)
+        });
+        match (over_v4, over_v6) {
+            (Ok(v4), Ok(v6)) => Ok(merge_responses(v4, v6)),
+            (Ok(response), Err(_)) => Ok(response),
+            (Err(_), Ok(response)) => Ok(response),
+            (Err(err), Err(_)) => Err(err),
+        }
+    }
+
+    // Sends the announce to `addrs` only, whatever else the host resolves to.
+    fn query_pinned(
+        url: &reqwest::Url,
+        host: &str,
+        addrs: &[SocketAddr],
+        timeout: Duration,
+    ) -> Result<TrackerResponse, Box<dyn Error>> {
+        let client = client_builder(timeout)
+            .resolve_to_addrs(host, addrs)
+            .build()?;
+        parse_tracker_response(&fetch(&client, url.clone())?)
+    }
 
-        let response = client.get(&url).send()?;
-        let response_bytes = response.bytes()?;
+    // Same as query_tracker, but resolves the tracker's host and connects to it on its own
+    // first so that each step can be timed. That costs an extra connection to the tracker;
+    // the request then goes to the address that answered. The timings are returned
+    // whether or not the announce succeeded.
+    pub fn query_tracker_timed(
+        request: &TrackerRequest,
+        timeout: Duration,
+    ) -> (Result<TrackerResponse, Box<dyn Error>>, AnnounceTimings) {
+        let mut timings = AnnounceTimings::default();
+        let started = Instant::now();
+        let result = Self::timed(request, timeout, &mut timings);
+        timings.total = Some(started.elapsed());
+        (result, timings)
+    }
+
+    fn timed(
+        request: &TrackerRequest,
+        timeout: Duration,
+        timings: &mut AnnounceTimings,
+    ) -> Result<TrackerResponse, Box<dyn Error>> {
+        let url = reqwest::Url::parse(&request.build_url())?;
+        let host = url.host_str().ok_or("Tracker URL has no host")?.to_string();
+        let port = url
+            .port_or_known_default()
+            .ok_or("Tracker URL has no port")?;
+
+        let started = Instant::now();
+        let resolved = (host.as_str(), port).to_socket_addrs();
+        timings.dns = Some(started.elapsed());
+        let addrs = interleave_families(resolved?.collect());
+
+        let started = Instant::now();
+        let addr = addrs
+            .into_iter()
+            .find(|addr| TcpStream::connect_timeout(addr, CONNECT_TIMEOUT).is_ok())
+            .ok_or("Couldn't connect to the tracker")?;
+        timings.connect = Some(started.elapsed());
+
+        let client = client_builder(timeout).resolve(&host, addr).build()?;
+        let started = Instant::now();
+        let response_bytes = fetch(&client, url)?;
+        timings.response = Some(started.elapsed());
 
         parse_tracker_response(&response_bytes)
     }
+
+    // Scrapes a single torrent. Fails for trackers without a scrape URL.
+    pub fn scrape(
+        announce_url: &str,
+        info_hash: &[u8; 20],
+    ) -> Result<ScrapeStats, Box<dyn std::error::Error>> {
+        Self::scrape_within(announce_url, info_hash, None, DEFAULT_TIMEOUT)
+    }
+
+    pub fn scrape_via(
+        announce_url: &str,
+        info_hash: &[u8; 20],
+        proxy: &Socks5Proxy,
+    ) -> Result<ScrapeStats, Box<dyn Error>> {
+        Self::scrape_within(announce_url, info_hash, Some(proxy), DEFAULT_TIMEOUT)
+    }
+
+    pub fn scrape_within(
+        announce_url: &str,
+        info_hash: &[u8; 20],
+        proxy: Option<&Socks5Proxy>,
+        timeout: Duration,
+    ) -> Result<ScrapeStats, Box<dyn Error>> {
+        let base = scrape_url(announce_url).ok_or("Tracker doesn't support scraping")?;
+        let url = append_query(
+            &base,
+            &[format!(
+                "info_hash={}",
+                TrackerRequest::url_encode_bytes(info_hash)
+            )],
+        );
+
+        let client = http_client(proxy, timeout)?;
+        let response = parse_scrape_response(&fetch(&client, url)?)?;
+        response
+            .files
+            .get(info_hash)
+            .copied()
+            .ok_or_else(|| "Torrent missing from scrape response".into())
+    }
+}
+
+// Every tracker request goes through a client built here: redirects capped, gzip and
+// deflate bodies unpacked (reqwest asks for them and decodes what comes back).
+fn client_builder(timeout: Duration) -> reqwest::blocking::ClientBuilder {
+    reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+}
+
+// Web seeds are fetched through this client as well, so they honour the proxy too.
+pub(crate) fn http_client(
+    proxy: Option<&Socks5Proxy>,
+    timeout: Duration,
+) -> reqwest::Result<reqwest::blocking::Client> {
+    let mut builder = client_builder(timeout);
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy.url())?);
+    }
+    builder.build()
 }
 
-fn parse_tracker_response(data: &[u8]) -> Result<TrackerResponse, Box<dyn Error>> {
-    let (bencode_value, _) = parse_value(data)?;
-    let dict = bencode_value.as_dict()?;
+// The body of a 2xx answer. Other statuses, redirect loops and timeouts come back as
+// TrackerErrors rather than as whatever reqwest made of them.
+fn fetch(
+    client: &reqwest::blocking::Client,
+    url: impl reqwest::IntoUrl,
+) -> Result<bytes::Bytes, Box<dyn Error>> {
+    let response = client.get(url).send().map_err(http_error)?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(TrackerError::HttpStatus(status.as_u16()).into());
+    }
+    response.bytes().map_err(http_error)
+}
 
-    let interval = get_int(dict, "interval")? as u32;
+fn http_error(err: reqwest::Error) -> Box<dyn Error> {
+    if err.is_redirect() {
+        TrackerError::TooManyRedirects(MAX_REDIRECTS).into()
+    } else if err.is_timeout() {
+        TrackerError::Timeout.into()
+    } else {
+        err.into()
+    }
+}
 
-    let peers_value = get_list(dict, "peers")?;
-    let peers = parse_peers(peers_value)?;
+// Alternates between address families, starting with the resolver's first choice, so a
+// host whose IPv6 addresses are unreachable doesn't have to time out on all of them first.
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let prefer_v6 = first.is_ipv6();
+    let (mut preferred, mut other): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == prefer_v6);
+    preferred.reverse();
+    other.reverse();
 
-    Ok(TrackerResponse { interval, peers })
+    let mut interleaved = Vec::new();
+    while let Some(addr) = preferred.pop() {
+        interleaved.push(addr);
+        interleaved.extend(other.pop());
+    }
+    interleaved.extend(other.into_iter().rev());
+    interleaved
 }
 
-fn parse_peers(peers_data: &Vec<BencodeValue>) -> Result<Vec<Peer>, Box<dyn Error>> {
-    use std::net::Ipv4Addr;
-    let mut peers = Vec::new();
+pub fn parse_tracker_response(data: &[u8]) -> Result<TrackerResponse, Box<dyn Error>> {
+    let (response, _) = BencodeParser::new(ParseLimits::strict()).parse(data)?;
+    // A failed announce carries nothing else that's required, interval included.
+    if let Ok(reason) = response.string("failure reason") {
+        return Err(format!("Tracker failure: {}", reason).into());
+    }
 
-    for peer_value in peers_data {
-        let peer_dict = peer_value.as_dict()?;
+    let interval = response.int("interval")? as u32;
+    let tracker_id = response.string("tracker id").ok().map(String::from);
+    let count = |key| response.int(key).ok().map(|n| n.max(0) as u32);
+    // Trackers may answer with a list of dicts or, compact or not, with a compact string.
+    // BEP 7 trackers add IPv6 peers as a compact "peers6" string.
+    let mut peers = match response.get("peers")? {
+        BencodeValue::List(list) => parse_peers(list)?,
+        _ => parse_compact_peers(response.bytes("peers")?, 4)?,
+    };
+    if let Ok(peers6) = response.bytes("peers6") {
+        peers.extend(parse_compact_peers(peers6, 16)?);
+    }
 
-        let peer_id = match get_bytes(peer_dict, "peer id") {
-            Ok(bytes) => Some(bytes.to_vec()),
-            Err(_) => None,
-        };
-        let ip_str = get_string(peer_dict, "ip")?;
-        let ip = ip_str.parse::<Ipv4Addr>()?;
+    Ok(TrackerResponse {
+        interval,
+        min_interval: count("min interval"),
+        tracker_id,
+        warning_message: response.string("warning message").ok().map(String::from),
+        complete: count("complete"),
+        incomplete: count("incomplete"),
+        peers,
+        external_ip: response.bytes("external ip").ok().and_then(parse_ip),
+    })
+}
+
+// The "files" dict of a scrape response is keyed by raw info hash, which is rarely valid
+// UTF-8 and so can't be a BencodeValue dictionary key. That one dict is walked by hand;
+// everything else goes through the parser as usual.
+pub fn parse_scrape_response(data: &[u8]) -> Result<ScrapeResponse, Box<dyn Error>> {
+    let parser = BencodeParser::new(ParseLimits::strict());
+    let mut rest = data
+        .strip_prefix(b"d")
+        .ok_or("Scrape response is not a dict")?;
+    let mut response = ScrapeResponse::default();
+
+    while !rest.starts_with(b"e") {
+        let (key, remaining) = parse_string(rest)?;
+        rest = remaining;
+        if raw_bytes(&key) != b"files" {
+            let (value, remaining) = parser.parse(rest)?;
+            if raw_bytes(&key) == b"failure reason" {
+                return Err(value.as_string().unwrap_or("Scrape failed").into());
+            }
+            rest = remaining;
+            continue;
+        }
+
+        rest = rest
+            .strip_prefix(b"d")
+            .ok_or("Scrape files is not a dict")?;
+        while !rest.starts_with(b"e") {
+            let (info_hash, remaining) = parse_string(rest)?;
+            let (stats, remaining) = parser.parse(remaining)?;
+            rest = remaining;
+
+            let Ok(info_hash) = raw_bytes(&info_hash).try_into() else {
+                continue;
+            };
+            let count = |key| stats.int(key).map(|n| n.max(0) as u32);
+            response.files.insert(
+                info_hash,
+                ScrapeStats {
+                    complete: count("complete")?,
+                    downloaded: count("downloaded")?,
+                    incomplete: count("incomplete")?,
+                },
+            );
+        }
+        rest = &rest[1..];
+    }
+
+    Ok(response)
+}
+
+// parse_string hands back UTF-8 strings as String and anything else as Bytes.
+fn raw_bytes(value: &BencodeValue) -> &[u8] {
+    match value {
+        BencodeValue::String(s) => s.as_bytes(),
+        BencodeValue::Bytes(b) => b,
+        _ => &[],
+    }
+}
+
+// Takes the peers of `extra` that `response` doesn't have yet. Counts and intervals come
+// from `response`, unless only `extra` has them.
+fn merge_responses(mut response: TrackerResponse, extra: TrackerResponse) -> TrackerResponse {
+    for peer in extra.peers {
+        let known = response
+            .peers
+            .iter()
+            .any(|p| p.ip == peer.ip && p.port == peer.port);
+        if !known {
+            response.peers.push(peer);
+        }
+    }
+    response.min_interval = response.min_interval.or(extra.min_interval);
+    response.tracker_id = response.tracker_id.or(extra.tracker_id);
+    response.warning_message = response.warning_message.or(extra.warning_message);
+    response.complete = response.complete.or(extra.complete);
+    response.incomplete = response.incomplete.or(extra.incomplete);
+    response.external_ip = response.external_ip.or(extra.external_ip);
+    response
+}
+
+fn parse_ip(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        16 => Some(IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => None,
+    }
+}
+
+// Each peer is its address, `ip_len` bytes, followed by a big-endian port.
+fn parse_compact_peers(data: &[u8], ip_len: usize) -> Result<Vec<Peer>, Box<dyn Error>> {
+    if !data.len().is_multiple_of(ip_len + 2) {
+        return Err(format!("Compact peers aren't a multiple of {} bytes", ip_len + 2).into());
+    }
+    Ok(data
+        .chunks_exact(ip_len + 2)
+        .map(|chunk| {
+            let (ip, port) = chunk.split_at(ip_len);
+            let ip = match ip_len {
+                4 => IpAddr::from(<[u8; 4]>::try_from(ip).unwrap()),
+                _ => IpAddr::from(<[u8; 16]>::try_from(ip).unwrap()),
+            };
+            Peer {
+                id: None,
+                ip,
+                port: u16::from_be_bytes([port[0], port[1]]),
+            }
+        })
+        .collect())
+}
+
+fn parse_peers(peers_data: &[BencodeValue]) -> Result<Vec<Peer>, Box<dyn Error>> {
+    let mut peers = Vec::new();
 
-        let port = get_int(peer_dict, "port")? as u16;
+    for peer in peers_data {
+        let peer_id = peer.bytes("peer id").ok().map(<[u8]>::to_vec);
+        let ip = peer.string("ip")?.parse::<IpAddr>()?;
+        let port = peer.int("port")? as u16;
 
         peers.push(Peer {
             id: peer_id,
@@ -0,0 +1,35 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum TrackerError {
+    // The tracker answered with something other than 2xx, e.g. 404 for an unknown path or
+    // 503 while overloaded. Redirects that were followed don't count.
+    HttpStatus(u16),
+    TooManyRedirects(usize),
+    Timeout,
+    InvalidUrl(String),
+    // A tracker URL with a scheme TrackerClient doesn't speak, e.g. "udp".
+    UnsupportedScheme(String),
+    // The tracker's host is ruled out by the session's TrackerFilter.
+    Blocked(String),
+}
+
+impl fmt::Display for TrackerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrackerError::HttpStatus(status) => write!(f, "Tracker answered HTTP {}", status),
+            TrackerError::TooManyRedirects(limit) => {
+                write!(f, "Tracker redirected more than {} times", limit)
+            }
+            TrackerError::Timeout => write!(f, "Timed out waiting for tracker"),
+            TrackerError::InvalidUrl(url) => write!(f, "Invalid tracker URL {}", url),
+            TrackerError::UnsupportedScheme(scheme) => {
+                write!(f, "Unsupported tracker scheme {}://", scheme)
+            }
+            TrackerError::Blocked(host) => write!(f, "Tracker {} is filtered out", host),
+        }
+    }
+}
+
+impl Error for TrackerError {}
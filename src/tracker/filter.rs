@@ -0,0 +1,65 @@
+use super::error::TrackerError;
+
+// Schemes TrackerClient can announce over. Others, like udp:// trackers in magnet links,
+// or WebTorrent's ws:// and wss:// without the webtorrent feature, are left out rather
+// than tried and failed.
+#[cfg(not(feature = "webtorrent"))]
+pub const SUPPORTED_SCHEMES: &[&str] = &["http", "https"];
+#[cfg(feature = "webtorrent")]
+pub const SUPPORTED_SCHEMES: &[&str] = &["http", "https", "ws", "wss"];
+
+// Which trackers a session talks to. An entry matches its domain and every subdomain,
+// so "example.org" covers "tracker.example.org" too. Denied domains are never announced
+// to; with an allow list, nothing outside it is either.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackerFilter {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl TrackerFilter {
+    pub fn new() -> TrackerFilter {
+        TrackerFilter::default()
+    }
+
+    pub fn allow(mut self, domain: &str) -> TrackerFilter {
+        self.allow.push(domain.to_ascii_lowercase());
+        self
+    }
+
+    pub fn deny(mut self, domain: &str) -> TrackerFilter {
+        self.deny.push(domain.to_ascii_lowercase());
+        self
+    }
+
+    // Ok if `url` is a tracker we can and may announce to.
+    pub fn check(&self, url: &str) -> Result<(), TrackerError> {
+        let parsed =
+            reqwest::Url::parse(url).map_err(|_| TrackerError::InvalidUrl(url.to_string()))?;
+        if !SUPPORTED_SCHEMES.contains(&parsed.scheme()) {
+            return Err(TrackerError::UnsupportedScheme(parsed.scheme().to_string()));
+        }
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| TrackerError::InvalidUrl(url.to_string()))?
+            .to_ascii_lowercase();
+        let listed = |domains: &[String]| domains.iter().any(|domain| covers(domain, &host));
+        if listed(&self.deny) || (!self.allow.is_empty() && !listed(&self.allow)) {
+            return Err(TrackerError::Blocked(host));
+        }
+        Ok(())
+    }
+}
+
+// WebTorrent trackers, announced to over a WebSocket.
+pub fn is_web_tracker(url: &str) -> bool {
+    url.starts_with("ws://") || url.starts_with("wss://")
+}
+
+fn covers(domain: &str, host: &str) -> bool {
+    let domain = domain.trim_start_matches('.');
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
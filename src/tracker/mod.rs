@@ -1,2 +1,6 @@
 pub mod client;
+pub mod error;
+pub mod filter;
 pub mod value;
+#[cfg(feature = "webtorrent")]
+pub mod websocket;
@@ -1,6 +1,8 @@
+use std::collections::HashMap;
 use std::net;
+use std::time::Duration;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Event {
     Started,
     Completed,
@@ -28,7 +30,13 @@ pub struct TrackerRequest {
     pub downloaded: u64,
     pub left: u64,
     pub compact: bool,
+    pub no_peer_id: bool,
     pub event: Option<Event>,
+    pub numwant: Option<u32>,
+    // Random value that lets the tracker recognise us if our IP changes.
+    pub key: Option<String>,
+    // Echoed back from a previous response's "tracker id".
+    pub tracker_id: Option<String>,
 }
 
 impl TrackerRequest {
@@ -37,55 +45,165 @@ impl TrackerRequest {
         let mut peer_id = *b"-RS0001-000000000000"; // Your client prefix
         let mut rng = rand::rng();
 
-        for i in 8..20 {
-            peer_id[i] = rng.random::<u8>();
+        for byte in peer_id.iter_mut().skip(8) {
+            *byte = rng.random::<u8>();
         }
 
         peer_id
     }
-    fn url_encode_bytes(bytes: &[u8]) -> String {
-        bytes.iter().map(|&b| format!("%{:02X}", b)).collect()
+
+    // Entirely random, so the peer id doesn't name the client either.
+    pub fn generate_anonymous_peer_id() -> [u8; 20] {
+        rand::random()
+    }
+
+    pub fn generate_key() -> String {
+        format!("{:08X}", rand::random::<u32>())
+    }
+
+    // Carries state the tracker asked us to send back on the next announce.
+    pub fn update_from_response(&mut self, response: &TrackerResponse) {
+        if let Some(tracker_id) = &response.tracker_id {
+            self.tracker_id = Some(tracker_id.clone());
+        }
+    }
+
+    // Percent-encodes everything except the RFC 3986 unreserved characters, which trackers
+    // expect to see literally.
+    pub fn url_encode_bytes(bytes: &[u8]) -> String {
+        bytes
+            .iter()
+            .map(|&b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{:02X}", b),
+            })
+            .collect()
     }
 
     pub fn build_url(&self) -> String {
-        let mut url = self.announce_url.clone();
-        url.push('?');
+        let mut params = vec![
+            format!("info_hash={}", Self::url_encode_bytes(&self.info_hash)),
+            format!("peer_id={}", Self::url_encode_bytes(&self.peer_id)),
+            format!("port={}", self.port),
+            format!("uploaded={}", self.uploaded),
+            format!("downloaded={}", self.downloaded),
+            format!("left={}", self.left),
+            format!("compact={}", if self.compact { 1 } else { 0 }),
+        ];
 
-        url.push_str(&format!(
-            "info_hash={}",
-            Self::url_encode_bytes(&self.info_hash)
-        ));
-        url.push_str(&format!(
-            "&peer_id={}",
-            Self::url_encode_bytes(&self.peer_id)
-        ));
-        url.push_str(&format!("&port={}", self.port));
-        url.push_str(&format!("&uploaded={}", self.uploaded));
-        url.push_str(&format!("&downloaded={}", self.downloaded));
-        url.push_str(&format!("&left={}", self.left));
-        url.push_str(&format!("&compact={}", if self.compact { 1 } else { 0 }));
+        if self.no_peer_id {
+            params.push("no_peer_id=1".to_string());
+        }
 
         if let Some(event) = &self.event {
-            url.push_str(&format!("&event={}", event.as_str()));
+            params.push(format!("event={}", event.as_str()));
         }
 
         if let Some(ip) = &self.ip {
-            url.push_str(&format!("&ip={}", ip));
+            params.push(format!("ip={}", ip));
+        }
+
+        if let Some(numwant) = self.numwant {
+            params.push(format!("numwant={}", numwant));
+        }
+
+        if let Some(key) = &self.key {
+            params.push(format!("key={}", Self::url_encode_bytes(key.as_bytes())));
+        }
+
+        if let Some(tracker_id) = &self.tracker_id {
+            params.push(format!(
+                "trackerid={}",
+                Self::url_encode_bytes(tracker_id.as_bytes())
+            ));
         }
 
-        url
+        append_query(&self.announce_url, &params)
     }
 }
 
+// Appends `params` to the query string of `base`. Private trackers and seeds often carry a
+// passkey in the URL's own query string (announce.php?passkey=...), so only start a new
+// query when there is none yet. A fragment, if any, has to stay at the very end.
+pub fn append_query(base: &str, params: &[String]) -> String {
+    let (base, fragment) = match base.split_once('#') {
+        Some((base, fragment)) => (base, Some(fragment)),
+        None => (base, None),
+    };
+
+    let mut url = base.to_string();
+    if !url.contains('?') {
+        url.push('?');
+    } else if !url.ends_with('?') && !url.ends_with('&') {
+        url.push('&');
+    }
+    url.push_str(&params.join("&"));
+
+    if let Some(fragment) = fragment {
+        url.push('#');
+        url.push_str(fragment);
+    }
+
+    url
+}
+
 #[derive(Debug, Clone)]
 pub struct Peer {
     pub id: Option<Vec<u8>>,
-    pub ip: net::Ipv4Addr,
+    pub ip: net::IpAddr,
     pub port: u16,
 }
 
 #[derive(Debug)]
 pub struct TrackerResponse {
     pub interval: u32,
+    // Announcing again sooner than this is asking to be ignored.
+    pub min_interval: Option<u32>,
+    pub tracker_id: Option<String>,
+    // Something the tracker wants the user to see, though the announce went through.
+    pub warning_message: Option<String>,
+    // Seeders and leechers in the swarm, when the tracker reports them.
+    pub complete: Option<u32>,
+    pub incomplete: Option<u32>,
     pub peers: Vec<Peer>,
+    // Our address as the tracker saw it (BEP 24).
+    pub external_ip: Option<net::IpAddr>,
+}
+
+// How long each step of an announce took, to tell a slow resolver from a slow tracker.
+// Steps that weren't reached or timed are None. `response` runs from sending the request
+// to having the whole body, so for HTTPS it includes the TLS handshake. `total` is set
+// whenever the announce ran to success or failure.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AnnounceTimings {
+    pub total: Option<Duration>,
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub response: Option<Duration>,
+}
+
+// A torrent's counts from a scrape (BEP 48). `downloaded` is how many times the tracker saw
+// the torrent completed, i.e. its snatch count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScrapeStats {
+    pub complete: u32,
+    pub downloaded: u32,
+    pub incomplete: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct ScrapeResponse {
+    pub files: HashMap<[u8; 20], ScrapeStats>,
+}
+
+// Trackers that support scraping have a scrape URL derived from the announce URL: the last
+// path component has to start with "announce", which is replaced by "scrape". Returns
+// None for trackers that can't be scraped.
+pub fn scrape_url(announce_url: &str) -> Option<String> {
+    let slash = announce_url.rfind('/')?;
+    let last = &announce_url[slash + 1..];
+    let rest = last.strip_prefix("announce")?;
+    Some(format!("{}/scrape{}", &announce_url[..slash], rest))
 }
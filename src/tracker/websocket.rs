@@ -0,0 +1,177 @@
+use super::error::TrackerError;
+use super::value::{TrackerRequest, TrackerResponse};
+use serde_json::{Map, Value, json};
+use std::error::Error;
+use std::io::ErrorKind;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+// An offer another peer sent through the tracker. Answering it takes WebRTC, which this
+// crate doesn't speak; it's returned so callers can see who is in the web swarm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebOffer {
+    pub peer_id: Vec<u8>,
+    pub offer_id: Vec<u8>,
+    pub sdp: String,
+}
+
+#[derive(Debug)]
+pub struct WebAnnounce {
+    // Web peers have no address to dial, so the response's peer list stays empty.
+    pub response: TrackerResponse,
+    pub offers: Vec<WebOffer>,
+}
+
+// Announces to ws:// and wss:// trackers with the WebTorrent JSON protocol: one text
+// message each way over a WebSocket, with binary fields such as the info hash sent as
+// strings of chars U+0000 to U+00FF. We send no offers of our own.
+pub struct WebTrackerClient;
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+impl WebTrackerClient {
+    // Offers arriving within `wait` of the tracker's answer are collected too. The whole
+    // exchange, connecting included, is bounded by `timeout`.
+    pub fn announce(
+        request: &TrackerRequest,
+        timeout: Duration,
+        wait: Duration,
+    ) -> Result<WebAnnounce, Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+        let mut socket = connect(&request.announce_url, timeout)?;
+        socket.send(Message::text(announce_message(request).to_string()))?;
+
+        let mut response = None;
+        let mut offers = Vec::new();
+        let mut until = deadline;
+        while let Some(left) = until.checked_duration_since(Instant::now()) {
+            stream(&socket).set_read_timeout(Some(left.max(Duration::from_millis(1))))?;
+            let message = match socket.read() {
+                Ok(message) => message,
+                Err(tungstenite::Error::Io(err))
+                    if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    break;
+                }
+                Err(err) => return Err(err.into()),
+            };
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let Ok(Value::Object(reply)) = serde_json::from_str::<Value>(&text) else {
+                continue;
+            };
+            if let Some(reason) = reply.get("failure reason").and_then(Value::as_str) {
+                return Err(format!("Tracker failure: {}", reason).into());
+            }
+            if reply.get("action").and_then(Value::as_str) != Some("announce")
+                || binary(&reply, "info_hash").as_deref() != Some(&request.info_hash[..])
+            {
+                continue;
+            }
+            if let Some(offer) = parse_offer(&reply) {
+                offers.push(offer);
+            } else if response.is_none() {
+                response = Some(parse_response(&reply));
+                until = deadline.min(Instant::now() + wait);
+            }
+        }
+        let _ = socket.close(None);
+
+        match response {
+            Some(response) => Ok(WebAnnounce { response, offers }),
+            None => Err(TrackerError::Timeout.into()),
+        }
+    }
+}
+
+fn connect(url: &str, timeout: Duration) -> Result<Socket, Box<dyn Error>> {
+    let parsed = reqwest::Url::parse(url)?;
+    let host = parsed.host_str().ok_or("Tracker URL has no host")?;
+    let port = parsed
+        .port_or_known_default()
+        .ok_or("Tracker URL has no port")?;
+    let addr = (host.trim_start_matches('[').trim_end_matches(']'), port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or("Tracker host didn't resolve")?;
+    let stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    // The handshake error holds the stream, so only its message comes back.
+    let (socket, _) = tungstenite::client_tls(url, stream).map_err(|err| err.to_string())?;
+    Ok(socket)
+}
+
+fn stream(socket: &Socket) -> &TcpStream {
+    match socket.get_ref() {
+        MaybeTlsStream::NativeTls(tls) => tls.get_ref(),
+        MaybeTlsStream::Plain(stream) => stream,
+        _ => unreachable!("only native-tls is enabled"),
+    }
+}
+
+fn announce_message(request: &TrackerRequest) -> Value {
+    let mut message = json!({
+        "action": "announce",
+        "info_hash": text(&request.info_hash),
+        "peer_id": text(&request.peer_id),
+        "uploaded": request.uploaded,
+        "downloaded": request.downloaded,
+        "left": request.left,
+        "numwant": request.numwant.unwrap_or(50),
+        "offers": [],
+    });
+    if let Some(event) = &request.event {
+        message["event"] = event.as_str().into();
+    }
+    if let Some(tracker_id) = &request.tracker_id {
+        message["trackerid"] = tracker_id.as_str().into();
+    }
+    message
+}
+
+fn parse_offer(reply: &Map<String, Value>) -> Option<WebOffer> {
+    let sdp = reply.get("offer")?.get("sdp")?.as_str()?;
+    Some(WebOffer {
+        peer_id: binary(reply, "peer_id")?,
+        offer_id: binary(reply, "offer_id")?,
+        sdp: sdp.to_string(),
+    })
+}
+
+fn parse_response(reply: &Map<String, Value>) -> TrackerResponse {
+    let count = |key: &str| {
+        reply
+            .get(key)
+            .and_then(Value::as_u64)
+            .map(|n| n.min(u32::MAX as u64) as u32)
+    };
+    let string = |key: &str| reply.get(key).and_then(Value::as_str).map(String::from);
+    TrackerResponse {
+        interval: count("interval").unwrap_or(120),
+        min_interval: count("min interval"),
+        tracker_id: string("tracker id"),
+        warning_message: string("warning message"),
+        complete: count("complete"),
+        incomplete: count("incomplete"),
+        peers: Vec::new(),
+        external_ip: None,
+    }
+}
+
+fn text(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+// The reverse of text(); None for chars past U+00FF.
+fn binary(reply: &Map<String, Value>, key: &str) -> Option<Vec<u8>> {
+    reply
+        .get(key)?
+        .as_str()?
+        .chars()
+        .map(|c| u8::try_from(c).ok())
+        .collect()
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Sub};
+use std::str::FromStr;
+
+// A number of bytes. Parses strings like "512", "16KiB", "1.5 GB" and displays itself
+// with binary units, so sizes, piece counts and rates can't be mixed up by accident.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+// Bytes per second. Parses "500KiB/s" and the like.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rate(pub u64);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitParseError(pub String);
+
+impl fmt::Display for UnitParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid unit value: {}", self.0)
+    }
+}
+
+impl Error for UnitParseError {}
+
+const UNITS: [(&str, u64); 9] = [
+    ("B", 1),
+    ("KB", 1000),
+    ("KIB", 1 << 10),
+    ("MB", 1000 * 1000),
+    ("MIB", 1 << 20),
+    ("GB", 1000 * 1000 * 1000),
+    ("GIB", 1 << 30),
+    ("TB", 1000 * 1000 * 1000 * 1000),
+    ("TIB", 1 << 40),
+];
+
+impl ByteSize {
+    pub const fn kib(n: u64) -> ByteSize {
+        ByteSize(n << 10)
+    }
+
+    pub const fn mib(n: u64) -> ByteSize {
+        ByteSize(n << 20)
+    }
+
+    pub const fn gib(n: u64) -> ByteSize {
+        ByteSize(n << 30)
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = UnitParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let split = trimmed
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(split);
+
+        let number: f64 = number.parse().map_err(|_| UnitParseError(s.to_string()))?;
+        let unit = unit.trim().to_ascii_uppercase();
+        let multiplier = if unit.is_empty() {
+            1
+        } else {
+            UNITS
+                .iter()
+                .find(|(name, _)| *name == unit)
+                .map(|(_, multiplier)| *multiplier)
+                .ok_or_else(|| UnitParseError(s.to_string()))?
+        };
+
+        Ok(ByteSize((number * multiplier as f64).round() as u64))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const NAMES: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+        let mut value = self.0 as f64;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < NAMES.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+
+        if unit == 0 {
+            write!(f, "{} B", self.0)
+        } else {
+            write!(f, "{:.2} {}", value, NAMES[unit])
+        }
+    }
+}
+
+impl From<u64> for ByteSize {
+    fn from(bytes: u64) -> Self {
+        ByteSize(bytes)
+    }
+}
+
+impl Add for ByteSize {
+    type Output = ByteSize;
+
+    fn add(self, other: ByteSize) -> ByteSize {
+        ByteSize(self.0.saturating_add(other.0))
+    }
+}
+
+impl AddAssign for ByteSize {
+    fn add_assign(&mut self, other: ByteSize) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for ByteSize {
+    type Output = ByteSize;
+
+    fn sub(self, other: ByteSize) -> ByteSize {
+        ByteSize(self.0.saturating_sub(other.0))
+    }
+}
+
+impl Sum for ByteSize {
+    fn sum<I: Iterator<Item = ByteSize>>(iter: I) -> ByteSize {
+        iter.fold(ByteSize(0), Add::add)
+    }
+}
+
+impl Rate {
+    pub fn bytes_per_second(&self) -> u64 {
+        self.0
+    }
+
+    pub fn per_second(size: ByteSize) -> Rate {
+        Rate(size.0)
+    }
+}
+
+impl FromStr for Rate {
+    type Err = UnitParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let size = trimmed
+            .strip_suffix("/s")
+            .or_else(|| trimmed.strip_suffix("ps"))
+            .unwrap_or(trimmed);
+        let size: ByteSize = size.parse().map_err(|_| UnitParseError(s.to_string()))?;
+        Ok(Rate(size.0))
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/s", ByteSize(self.0))
+    }
+}
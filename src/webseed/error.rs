@@ -0,0 +1,53 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum WebSeedError {
+    Http(reqwest::Error),
+    IOError(std::io::Error),
+    HttpStatus(u16),
+    // The seed is busy and asked us to come back later (BEP 17 503 response).
+    RetryAfter(Duration),
+    LengthMismatch { expected: usize, found: usize },
+    // All of the seed's connection slots are taken.
+    TooManyConnections,
+    // Turned off after sending too many pieces that failed verification.
+    Disabled,
+}
+
+impl fmt::Display for WebSeedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "{}", e),
+            Self::IOError(e) => write!(f, "{}", e),
+            Self::HttpStatus(status) => write!(f, "Web seed returned HTTP {}", status),
+            Self::RetryAfter(delay) => {
+                write!(f, "Web seed busy, retry in {}s", delay.as_secs())
+            }
+            Self::LengthMismatch { expected, found } => {
+                write!(
+                    f,
+                    "Expected {} bytes from web seed, got {}",
+                    expected, found
+                )
+            }
+            Self::TooManyConnections => write!(f, "Web seed connection limit reached"),
+            Self::Disabled => write!(f, "Web seed disabled after sending corrupt data"),
+        }
+    }
+}
+
+impl Error for WebSeedError {}
+
+impl From<reqwest::Error> for WebSeedError {
+    fn from(err: reqwest::Error) -> Self {
+        WebSeedError::Http(err)
+    }
+}
+
+impl From<std::io::Error> for WebSeedError {
+    fn from(err: std::io::Error) -> Self {
+        WebSeedError::IOError(err)
+    }
+}
@@ -0,0 +1,97 @@
+use super::error::WebSeedError;
+use crate::peer::socks::Socks5Proxy;
+use crate::session::torrent::Torrent;
+use crate::tracker::client::http_client;
+use crate::tracker::value::{TrackerRequest, append_query};
+use std::time::Duration;
+
+// BEP 17 seed: a script that serves whole pieces (or ranges within a piece) for
+// GET <url>?info_hash=<hash>&piece=<index>[&ranges=<start>-<end>,...].
+// A busy seed answers 503 with the number of seconds to wait as the body.
+pub struct HttpSeed {
+    url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl HttpSeed {
+    pub fn new(url: &str) -> Result<HttpSeed, WebSeedError> {
+        Self::with_proxy(url, None)
+    }
+
+    // Fetches through `proxy` when given, as trackers do.
+    pub fn with_proxy(url: &str, proxy: Option<&Socks5Proxy>) -> Result<HttpSeed, WebSeedError> {
+        let client = http_client(proxy, Duration::from_secs(60))?;
+
+        Ok(HttpSeed {
+            url: url.to_string(),
+            client,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // `ranges` are inclusive byte ranges relative to the start of the piece.
+    pub fn build_url(&self, info_hash: &[u8; 20], piece: usize, ranges: &[(u64, u64)]) -> String {
+        let mut params = vec![
+            format!("info_hash={}", TrackerRequest::url_encode_bytes(info_hash)),
+            format!("piece={}", piece),
+        ];
+
+        if !ranges.is_empty() {
+            let ranges: Vec<String> = ranges
+                .iter()
+                .map(|(start, end)| format!("{}-{}", start, end))
+                .collect();
+            params.push(format!("ranges={}", ranges.join(",")));
+        }
+
+        append_query(&self.url, &params)
+    }
+
+    pub fn fetch(
+        &self,
+        info_hash: &[u8; 20],
+        piece: usize,
+        ranges: &[(u64, u64)],
+        expected_len: usize,
+    ) -> Result<Vec<u8>, WebSeedError> {
+        let response = self
+            .client
+            .get(self.build_url(info_hash, piece, ranges))
+            .send()?;
+
+        let status = response.status().as_u16();
+        if status == 503 {
+            let body = response.text().unwrap_or_default();
+            let seconds = body.trim().parse().unwrap_or(60);
+            return Err(WebSeedError::RetryAfter(Duration::from_secs(seconds)));
+        }
+        if status != 200 {
+            return Err(WebSeedError::HttpStatus(status));
+        }
+
+        let body = response.bytes()?.to_vec();
+        if body.len() != expected_len {
+            return Err(WebSeedError::LengthMismatch {
+                expected: expected_len,
+                found: body.len(),
+            });
+        }
+
+        Ok(body)
+    }
+
+    // Downloads a whole piece, verifies it and stores it in the torrent.
+    // Returns false if the seed sent data that failed the hash check.
+    pub fn download_piece(&self, torrent: &Torrent, index: usize) -> Result<bool, WebSeedError> {
+        let size = torrent.meta().piece_size(index);
+        let data = self.fetch(&torrent.info_hash(), index, &[], size)?;
+        let stored = torrent.store_piece(index, &data)?;
+        if stored {
+            torrent.add_web_seed_downloaded(data.len() as u64);
+        }
+        Ok(stored)
+    }
+}
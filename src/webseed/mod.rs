@@ -0,0 +1,4 @@
+pub mod error;
+pub mod http_seed;
+pub mod policy;
+pub mod url_seed;
@@ -0,0 +1,28 @@
+use crate::units::Rate;
+
+// When the session downloads from a torrent's web seeds. Web seeds cost the server
+// owner bandwidth and usually serve slower than a healthy swarm, so by default they only
+// step in while peers can't keep up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebSeedPolicy {
+    Never,
+    Always,
+    // Use web seeds while the download rate from peers is below the given rate.
+    WhenSlow(Rate),
+}
+
+impl Default for WebSeedPolicy {
+    fn default() -> Self {
+        WebSeedPolicy::WhenSlow(Rate(100 << 10))
+    }
+}
+
+impl WebSeedPolicy {
+    pub fn should_use(&self, peer_rate: Rate) -> bool {
+        match self {
+            WebSeedPolicy::Never => false,
+            WebSeedPolicy::Always => true,
+            WebSeedPolicy::WhenSlow(threshold) => peer_rate < *threshold,
+        }
+    }
+}
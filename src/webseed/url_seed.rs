@@ -0,0 +1,299 @@
+use super::error::WebSeedError;
+use crate::peer::socks::Socks5Proxy;
+use crate::retry::RetryPolicy;
+use crate::session::torrent::Torrent;
+use crate::storage::file_storage::FileEntry;
+use crate::tracker::client::http_client;
+use crate::tracker::value::TrackerRequest;
+use reqwest::header::RANGE;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct UrlSeedLimits {
+    // HTTP requests allowed in flight against this server at once.
+    pub max_connections: usize,
+    // Upper bound on the bytes asked for in one run of adjacent pieces.
+    pub max_request: u64,
+    // Delay after consecutive failures; max_attempts is ignored, a seed is never given up.
+    pub backoff: RetryPolicy,
+    // Pieces failing their hash check before the seed is disabled for good.
+    pub max_corrupt_pieces: u32,
+}
+
+impl Default for UrlSeedLimits {
+    fn default() -> Self {
+        UrlSeedLimits {
+            max_connections: 2,
+            max_request: 4 << 20,
+            backoff: RetryPolicy {
+                max_attempts: u32::MAX,
+                initial_backoff: Duration::from_secs(5),
+                max_backoff: Duration::from_secs(300),
+                jitter: 0.0,
+            },
+            max_corrupt_pieces: 3,
+        }
+    }
+}
+
+// A run of adjacent pieces fetched together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PieceRun {
+    pub first: usize,
+    pub count: usize,
+}
+
+#[derive(Debug, Default)]
+struct SeedState {
+    in_flight: usize,
+    failures: u32,
+    retry_at: Option<Instant>,
+    corrupt_pieces: u32,
+}
+
+// BEP 19 (GetRight-style) seed: a plain HTTP server with the torrent's files below the
+// URL. Requests per 16KiB block would be far too slow over HTTP, so adjacent missing
+// pieces are merged into a few large Range requests and the responses cut back into
+// pieces for verification.
+pub struct UrlSeed {
+    url: String,
+    client: reqwest::blocking::Client,
+    limits: UrlSeedLimits,
+    state: Mutex<SeedState>,
+}
+
+// Holds one of the seed's connection slots for the duration of a run.
+struct ConnectionSlot<'a>(&'a Mutex<SeedState>);
+
+impl Drop for ConnectionSlot<'_> {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().in_flight -= 1;
+    }
+}
+
+impl UrlSeed {
+    pub fn new(url: &str, limits: UrlSeedLimits) -> Result<UrlSeed, WebSeedError> {
+        Self::with_proxy(url, limits, None)
+    }
+
+    // Fetches through `proxy` when given, as trackers do.
+    pub fn with_proxy(
+        url: &str,
+        limits: UrlSeedLimits,
+        proxy: Option<&Socks5Proxy>,
+    ) -> Result<UrlSeed, WebSeedError> {
+        let client = http_client(proxy, Duration::from_secs(120))?;
+
+        Ok(UrlSeed {
+            url: url.to_string(),
+            client,
+            limits,
+            state: Mutex::new(SeedState::default()),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // A URL ending in '/' is a directory the torrent's name is appended to; otherwise, for
+    // single-file torrents, it points at the file itself.
+    pub fn file_url(&self, torrent: &Torrent, entry: &FileEntry) -> String {
+        let single_file = torrent.storage().files().len() == 1;
+        if single_file && !self.url.ends_with('/') {
+            return self.url.clone();
+        }
+
+        let mut url = self.url.clone();
+        for segment in entry.path.iter() {
+            if !url.ends_with('/') {
+                url.push('/');
+            }
+            let segment = segment.to_string_lossy();
+            url.push_str(&TrackerRequest::url_encode_bytes(segment.as_bytes()));
+        }
+        url
+    }
+
+    // Groups the given pieces into runs of adjacent ones no larger than max_request. A
+    // single piece bigger than max_request still gets a run of its own.
+    pub fn plan(&self, torrent: &Torrent, pieces: &[usize]) -> Vec<PieceRun> {
+        let mut pieces = pieces.to_vec();
+        pieces.sort_unstable();
+        pieces.dedup();
+
+        let mut runs: Vec<PieceRun> = Vec::new();
+        let mut run_bytes = 0u64;
+        for index in pieces {
+            let size = torrent.meta().piece_size(index) as u64;
+            if let Some(run) = runs.last_mut()
+                && run.first + run.count == index
+                && run_bytes + size <= self.limits.max_request
+            {
+                run.count += 1;
+                run_bytes += size;
+                continue;
+            }
+            runs.push(PieceRun {
+                first: index,
+                count: 1,
+            });
+            run_bytes = size;
+        }
+        runs
+    }
+
+    // Whether another run may start now, i.e. not disabled, not backing off and below the
+    // connection limit.
+    pub fn is_available(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.corrupt_pieces < self.limits.max_corrupt_pieces
+            && state.in_flight < self.limits.max_connections
+            && state.retry_at.is_none_or(|at| Instant::now() >= at)
+    }
+
+    // Set once the seed has sent max_corrupt_pieces pieces that failed verification.
+    pub fn is_disabled(&self) -> bool {
+        self.state.lock().unwrap().corrupt_pieces >= self.limits.max_corrupt_pieces
+    }
+
+    // How many pieces of this torrent fit in one request.
+    pub fn max_run_pieces(&self, torrent: &Torrent) -> usize {
+        let piece_length = torrent.meta().info.piece_length as u64;
+        (self.limits.max_request / piece_length.max(1)).max(1) as usize
+    }
+
+    // Fetches the pieces we're missing, run by run. Returns how many were stored; stops at
+    // the first failure, which also puts the seed into backoff.
+    pub fn download_missing(&self, torrent: &Torrent) -> Result<usize, WebSeedError> {
+        let missing: Vec<usize> = (0..torrent.meta().num_pieces())
+            .filter(|&index| !torrent.has_piece(index))
+            .collect();
+
+        let mut stored = 0;
+        for run in self.plan(torrent, &missing) {
+            stored += self.download_run(torrent, &run)?;
+        }
+        Ok(stored)
+    }
+
+    // Downloads a run of pieces, verifies each and stores the good ones. Returns how many
+    // passed the hash check. Pieces that didn't make it are handed back to the picker.
+    pub fn download_run(&self, torrent: &Torrent, run: &PieceRun) -> Result<usize, WebSeedError> {
+        let result = self.fetch_and_store(torrent, run);
+        for index in run.first..run.first + run.count {
+            if !torrent.has_piece(index) {
+                torrent.picker().abandon(index);
+            }
+        }
+        result
+    }
+
+    fn fetch_and_store(&self, torrent: &Torrent, run: &PieceRun) -> Result<usize, WebSeedError> {
+        let _slot = self.acquire()?;
+
+        let data = match self.fetch_run(torrent, run) {
+            Ok(data) => data,
+            Err(err) => {
+                self.record(false);
+                return Err(err);
+            }
+        };
+        self.record(true);
+
+        let mut stored = 0;
+        let mut offset = 0;
+        for index in run.first..run.first + run.count {
+            let size = torrent.meta().piece_size(index);
+            if torrent.store_piece(index, &data[offset..offset + size])? {
+                torrent.add_web_seed_downloaded(size as u64);
+                stored += 1;
+            } else {
+                self.state.lock().unwrap().corrupt_pieces += 1;
+            }
+            offset += size;
+        }
+        Ok(stored)
+    }
+
+    fn acquire(&self) -> Result<ConnectionSlot<'_>, WebSeedError> {
+        let mut state = self.state.lock().unwrap();
+        if state.corrupt_pieces >= self.limits.max_corrupt_pieces {
+            return Err(WebSeedError::Disabled);
+        }
+        if let Some(at) = state.retry_at {
+            let now = Instant::now();
+            if now < at {
+                return Err(WebSeedError::RetryAfter(at - now));
+            }
+        }
+        if state.in_flight >= self.limits.max_connections {
+            return Err(WebSeedError::TooManyConnections);
+        }
+        state.in_flight += 1;
+        Ok(ConnectionSlot(&self.state))
+    }
+
+    fn record(&self, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        if success {
+            state.failures = 0;
+            state.retry_at = None;
+        } else {
+            state.failures += 1;
+            state.retry_at = Some(Instant::now() + self.limits.backoff.backoff(state.failures));
+        }
+    }
+
+    // One Range request per file the run touches, concatenated in torrent order.
+    fn fetch_run(&self, torrent: &Torrent, run: &PieceRun) -> Result<Vec<u8>, WebSeedError> {
+        let piece_length = torrent.meta().info.piece_length as u64;
+        let offset = run.first as u64 * piece_length;
+        let length: u64 = (run.first..run.first + run.count)
+            .map(|index| torrent.meta().piece_size(index) as u64)
+            .sum();
+
+        let mut data = Vec::with_capacity(length as usize);
+        for (entry, file_offset, range) in torrent.storage().spans(offset, length) {
+            // Servers don't host pad files; they're zeros by definition.
+            if entry.pad {
+                data.resize(data.len() + range, 0);
+                continue;
+            }
+            let url = self.file_url(torrent, entry);
+            data.extend(self.fetch_range(&url, file_offset, range)?);
+        }
+        Ok(data)
+    }
+
+    fn fetch_range(&self, url: &str, start: u64, length: usize) -> Result<Vec<u8>, WebSeedError> {
+        let end = start + length as u64 - 1;
+        let response = self
+            .client
+            .get(url)
+            .header(RANGE, format!("bytes={}-{}", start, end))
+            .send()?;
+
+        let status = response.status().as_u16();
+        if status == 503 {
+            return Err(WebSeedError::RetryAfter(
+                self.limits.backoff.initial_backoff,
+            ));
+        }
+        // 200 means the server ignored the range; the length check below then only passes
+        // when the range covered the whole file.
+        if status != 206 && status != 200 {
+            return Err(WebSeedError::HttpStatus(status));
+        }
+
+        let body = response.bytes()?.to_vec();
+        if body.len() != length {
+            return Err(WebSeedError::LengthMismatch {
+                expected: length,
+                found: body.len(),
+            });
+        }
+        Ok(body)
+    }
+}
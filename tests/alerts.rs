@@ -0,0 +1,75 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::thread;
+
+use bittorrent_client::session::alert::{AlertLog, Severity};
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+fn meta(name: &str, announce: String) -> TorrentMetaInfo {
+    common::unverified(announce, name)
+}
+
+#[test]
+fn the_log_keeps_the_latest_alerts() {
+    let log = AlertLog::new(3);
+    log.push(Severity::Info, None, "one".to_string());
+    log.push(Severity::Error, None, "two".to_string());
+    log.push(Severity::Warning, None, "three".to_string());
+    log.push(Severity::Info, None, "four".to_string());
+
+    let all = log.alerts(Severity::Info);
+    let messages: Vec<_> = all.iter().map(|alert| alert.message.as_str()).collect();
+    assert_eq!(messages, ["two", "three", "four"]);
+    assert_eq!(all[0].id, 1);
+
+    let serious: Vec<_> = log.alerts(Severity::Warning);
+    assert_eq!(serious.len(), 2);
+    assert_eq!(log.alerts_since(2).len(), 1);
+
+    log.clear();
+    log.push(Severity::Info, None, "five".to_string());
+    let left = log.alerts(Severity::Info);
+    assert_eq!(left.last().unwrap().id, 4);
+    assert_eq!(log.alerts_since(3).len(), 1);
+}
+
+#[test]
+fn tracker_trouble_is_kept_for_later() {
+    let body: &[u8] = b"d8:intervali900e5:peersle15:warning message10:slow down!e";
+    let tracker = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let announce = format!("http://{}/announce", tracker.local_addr().unwrap());
+    thread::spawn(move || {
+        let (mut stream, _) = tracker.accept().unwrap();
+        let mut request = [0u8; 4096];
+        let _ = stream.read(&mut request);
+        let header = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(header.as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+    });
+
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-alerts-{}", std::process::id()));
+    let warned = session
+        .add_torrent(meta("warned.bin", announce), &dir)
+        .unwrap();
+    session.announce(&warned, None).unwrap();
+
+    let failing = meta("failing.bin", "http://127.0.0.1:1/announce".to_string());
+    let failing = session.add_torrent(failing, &dir).unwrap();
+    assert!(session.announce(&failing, None).is_err());
+
+    let alerts = session.alerts(Severity::Warning);
+    assert_eq!(alerts.len(), 2);
+    assert_eq!(alerts[0].info_hash, Some(warned.info_hash()));
+    assert!(alerts[0].message.contains("slow down!"));
+    assert_eq!(alerts[1].info_hash, Some(failing.info_hash()));
+    assert!(alerts[1].message.contains("127.0.0.1:1"));
+    assert!(session.alerts(Severity::Error).is_empty());
+    assert_eq!(session.alerts_since(alerts[0].id), alerts[1..]);
+}
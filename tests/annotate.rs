@@ -0,0 +1,102 @@
+mod common;
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::Handshake;
+use bittorrent_client::session::annotate::{NoAnnotations, PeerAnnotator, PeerLabels};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::snapshot::PeerInfo;
+use bittorrent_client::session::torrent::Torrent;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+// Stands in for a GeoIP database: loopback is "Localland".
+#[derive(Default)]
+struct FakeGeo {
+    lookups: AtomicUsize,
+}
+
+impl PeerAnnotator for FakeGeo {
+    fn annotate(&self, ip: IpAddr) -> PeerLabels {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        let mut labels = PeerLabels::new();
+        if ip.is_loopback() {
+            labels.insert("country".to_string(), "Localland".to_string());
+            labels.insert("asn".to_string(), "AS0".to_string());
+        }
+        labels
+    }
+}
+
+fn meta(name: &str, data: &[u8]) -> TorrentMetaInfo {
+    common::single_file(name, data.len(), data)
+}
+
+fn connect(session: &Session, torrent: &Torrent) -> (TcpStream, PeerInfo) {
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    Handshake::perform_handshake(
+        &mut stream,
+        &torrent.info_hash(),
+        b"-XX0001-annotateme00",
+        ReservedBits::empty(),
+        Duration::from_secs(10),
+    )
+    .unwrap();
+    let addr = stream.local_addr().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(peer) = torrent.peers().into_iter().find(|peer| peer.addr == addr) {
+            return (stream, peer);
+        }
+        assert!(Instant::now() < deadline, "peer never showed up");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn annotator_labels_new_connections() {
+    let dir = std::env::temp_dir().join(format!("bt-annotate-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let data = vec![3u8; 1000];
+    std::fs::write(dir.join("annotate.bin"), &data).unwrap();
+
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        tick_interval: Duration::from_secs(3600),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let torrent = session
+        .add_torrent(meta("annotate.bin", &data), &dir)
+        .unwrap();
+
+    // Nothing is plugged in yet.
+    let (_first, peer) = connect(&session, &torrent);
+    assert!(peer.labels.is_empty());
+
+    let geo = Arc::new(FakeGeo::default());
+    session.set_peer_annotator(geo.clone());
+    let (_second, peer) = connect(&session, &torrent);
+    assert_eq!(peer.labels["country"], "Localland");
+    assert_eq!(peer.labels["asn"], "AS0");
+    assert_eq!(geo.lookups.load(Ordering::Relaxed), 1);
+
+    // The earlier connection keeps what it had.
+    let unlabelled = torrent
+        .peers()
+        .iter()
+        .filter(|peer| peer.labels.is_empty())
+        .count();
+    assert_eq!(unlabelled, 1);
+
+    session.set_peer_annotator(Arc::new(NoAnnotations));
+    let (_third, peer) = connect(&session, &torrent);
+    assert!(peer.labels.is_empty());
+    assert_eq!(geo.lookups.load(Ordering::Relaxed), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::lifecycle::AnnounceLifecycle;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+use bittorrent_client::tracker::value::Event;
+
+fn session(stopped_announce_timeout: Duration) -> Session {
+    Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        stopped_announce_timeout,
+        ..SessionConfig::default()
+    })
+    .unwrap()
+}
+
+fn meta(name: &str, announce: String, data: &[u8]) -> TorrentMetaInfo {
+    TorrentMetaInfo {
+        announce,
+        ..common::single_file(name, data.len(), data)
+    }
+}
+
+// An HTTP tracker that answers everything and passes on each request line.
+fn tracker() -> (String, Receiver<String>) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let announce = format!("http://{}/announce", listener.local_addr().unwrap());
+    let (requests, received) = mpsc::channel();
+    thread::spawn(move || {
+        let body: &[u8] = b"d8:intervali900e5:peerslee";
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = [0u8; 4096];
+            let read = stream.read(&mut request).unwrap();
+            let line = String::from_utf8_lossy(&request[..read]);
+            let _ = requests.send(line.lines().next().unwrap_or_default().to_string());
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(body).unwrap();
+        }
+    });
+    (announce, received)
+}
+
+fn seeded(name: &str) -> (std::path::PathBuf, Vec<u8>) {
+    let dir = std::env::temp_dir().join(format!("bt-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let data = vec![3u8; 100];
+    std::fs::write(dir.join(name), &data).unwrap();
+    (dir, data)
+}
+
+#[test]
+fn completed_goes_out_once_and_only_after_a_download() {
+    let mut lifecycle = AnnounceLifecycle::default();
+    assert_eq!(lifecycle.next_event(), Some(Event::Started));
+    lifecycle.on_announced(Some(Event::Started), false);
+    assert_eq!(lifecycle.next_event(), Some(Event::Started));
+    lifecycle.on_announced(Some(Event::Started), true);
+    assert_eq!(lifecycle.next_event(), None);
+
+    lifecycle.on_download_complete();
+    assert_eq!(lifecycle.next_event(), Some(Event::Completed));
+    lifecycle.on_announced(Some(Event::Completed), false);
+    assert_eq!(lifecycle.next_event(), Some(Event::Completed));
+    lifecycle.on_announced(Some(Event::Completed), true);
+    assert!(lifecycle.completed_sent());
+    assert_eq!(lifecycle.next_event(), None);
+
+    // A recheck that finishes again doesn't owe the trackers another one.
+    lifecycle.on_download_complete();
+    assert_eq!(lifecycle.next_event(), None);
+}
+
+#[test]
+fn stopped_is_sent_once_per_start() {
+    let mut lifecycle = AnnounceLifecycle::default();
+    assert!(!lifecycle.begin_stop());
+    lifecycle.on_announced(Some(Event::Started), true);
+    assert!(lifecycle.begin_stop());
+    assert!(!lifecycle.begin_stop());
+    lifecycle.on_announced(Some(Event::Stopped), false);
+    assert!(!lifecycle.is_started());
+    assert_eq!(lifecycle.next_event(), Some(Event::Started));
+}
+
+#[test]
+fn seeds_start_without_completing_and_stop_when_paused() {
+    let (announce, received) = tracker();
+    let (dir, data) = seeded("lifecycle-pause");
+    let session = session(Duration::from_secs(5));
+    let torrent = session
+        .add_torrent(meta("lifecycle-pause", announce, &data), &dir)
+        .unwrap();
+    assert!(torrent.is_complete());
+
+    session.announce(&torrent, None).unwrap();
+    let started = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(started.contains("event=started"), "{}", started);
+    session.announce(&torrent, None).unwrap();
+    let regular = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(!regular.contains("event="), "{}", regular);
+    assert!(!torrent.announce_lifecycle().completed_sent());
+
+    torrent.pause();
+    session.tick();
+    let stopped = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(stopped.contains("event=stopped"), "{}", stopped);
+    session.tick();
+    assert!(received.recv_timeout(Duration::from_millis(300)).is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn dropping_the_session_sends_stopped() {
+    let (announce, received) = tracker();
+    let (dir, data) = seeded("lifecycle-drop");
+    let session = session(Duration::from_secs(5));
+    let torrent = session
+        .add_torrent(meta("lifecycle-drop", announce, &data), &dir)
+        .unwrap();
+    session.announce(&torrent, None).unwrap();
+    received.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    drop(torrent);
+    drop(session);
+    // Sent before drop returned, not left to a thread that may not get to run.
+    let stopped = received.try_recv().unwrap();
+    assert!(stopped.contains("event=stopped"), "{}", stopped);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn an_unresponsive_tracker_does_not_hold_up_shutdown() {
+    // Answers the first announce, then accepts connections and never replies.
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let announce = format!("http://{}/announce", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let mut held = Vec::new();
+        for (n, stream) in listener.incoming().enumerate() {
+            let mut stream = stream.unwrap();
+            if n == 0 {
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request).unwrap();
+                let body: &[u8] = b"d8:intervali900e5:peerslee";
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(header.as_bytes()).unwrap();
+                stream.write_all(body).unwrap();
+            } else {
+                held.push(stream);
+            }
+        }
+    });
+    let (dir, data) = seeded("lifecycle-silent");
+    let session = session(Duration::from_millis(300));
+    let torrent = session
+        .add_torrent(meta("lifecycle-silent", announce, &data), &dir)
+        .unwrap();
+    session.announce(&torrent, None).unwrap();
+
+    drop(torrent);
+    let start = Instant::now();
+    drop(session);
+    assert!(
+        start.elapsed() < Duration::from_secs(3),
+        "{:?}",
+        start.elapsed()
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
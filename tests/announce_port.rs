@@ -0,0 +1,81 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::peer::extension::{EXTENDED_HANDSHAKE_ID, ExtendedHandshake};
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+
+#[test]
+fn announce_port_overrides_the_bound_port() {
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        announce_port: Some(40_000),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    assert_ne!(session.local_addr().port(), 40_000);
+
+    let dir = std::env::temp_dir().join(format!("bt-announce-port-{}", std::process::id()));
+    let meta = common::unverified("http://127.0.0.1:1/announce".to_string(), "missing.bin");
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+    assert_eq!(session.tracker_request(&torrent, None).port, 40_000);
+
+    let encoded = session.extended_handshake().to_bencode().encode();
+    let (value, _) = parse_value(&encoded).unwrap();
+    let handshake = ExtendedHandshake::from_bencode(&value).unwrap();
+    assert_eq!(handshake.port, Some(40_000));
+}
+
+// Connects as a peer that speaks the extension protocol and returns the extended handshake
+// the session sends it.
+fn wire_handshake(privacy_mode: bool) -> ExtendedHandshake {
+    let session = Session::new(SessionConfig {
+        announce_port: Some(40_000),
+        privacy_mode,
+        ..common::local_config()
+    })
+    .unwrap();
+    let dir = common::temp_dir(&format!("announce-port-wire-{privacy_mode}"));
+    let meta = common::unverified("http://127.0.0.1:1/announce".to_string(), "missing.bin");
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let theirs = Handshake::perform_handshake(
+        &mut stream,
+        &torrent.info_hash(),
+        b"-FAKE0-extextextexte",
+        ReservedBits::FAST | ReservedBits::EXTENSION_PROTOCOL,
+        Duration::from_secs(5),
+    )
+    .unwrap();
+    assert!(theirs.supports_extension_protocol());
+    loop {
+        match PeerMessage::read_peer_message(&mut stream).unwrap() {
+            PeerMessage::Extended { id, payload } => {
+                assert_eq!(id, EXTENDED_HANDSHAKE_ID);
+                return ExtendedHandshake::from_payload(&payload).unwrap();
+            }
+            PeerMessage::HaveNone | PeerMessage::KeepAlive => {}
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn peers_get_the_extended_handshake_over_the_wire() {
+    let open = wire_handshake(false);
+    assert_eq!(open.port, Some(40_000));
+    assert!(open.client.is_some());
+
+    let private = wire_handshake(true);
+    assert_eq!(private.port, Some(40_000));
+    assert_eq!(private.client, None);
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::thread;
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::snapshot::ANNOUNCE_HISTORY;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+fn meta(announce: String) -> TorrentMetaInfo {
+    common::unverified(announce, "timings.bin")
+}
+
+#[test]
+fn announces_record_each_step() {
+    // Only listening on IPv4, so if localhost resolves to ::1 first that attempt fails and
+    // the IPv4 address has to be tried next.
+    let tracker = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let port = tracker.local_addr().unwrap().port();
+    thread::spawn(move || {
+        // The first connection only measures connecting and is closed right away.
+        for mut stream in tracker.incoming().flatten() {
+            let mut request = [0u8; 4096];
+            if !matches!(stream.read(&mut request), Ok(n) if n > 0) {
+                continue;
+            }
+            let body = b"d8:intervali1800e5:peerslee";
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(header.as_bytes());
+            let _ = stream.write_all(body);
+        }
+    });
+
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        time_announces: true,
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-announce-timings-{}", std::process::id()));
+    let torrent = session
+        .add_torrent(meta(format!("http://localhost:{}/announce", port)), &dir)
+        .unwrap();
+    let unreachable = "http://localhost:1/announce";
+    assert!(torrent.add_tracker(unreachable, "dead"));
+
+    session.announce(&torrent, None).unwrap();
+    let trackers = torrent.trackers();
+
+    let history = &trackers[0].history;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].error, None);
+    let timings = history[0].timings;
+    assert!(timings.dns.is_some() && timings.connect.is_some() && timings.response.is_some());
+    assert!(timings.total >= timings.response);
+
+    let history = &trackers[1].history;
+    assert_eq!(history.len(), 1);
+    assert!(history[0].error.is_some());
+    assert!(history[0].timings.dns.is_some());
+    assert_eq!(history[0].timings.connect, None);
+    assert_eq!(history[0].timings.response, None);
+
+    torrent.set_tracker_group_enabled("dead", false);
+    for _ in 0..ANNOUNCE_HISTORY + 2 {
+        session.announce(&torrent, None).unwrap();
+    }
+    assert_eq!(torrent.trackers()[0].history.len(), ANNOUNCE_HISTORY);
+}
+
+#[test]
+fn untimed_announces_keep_the_total() {
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-announce-total-{}", std::process::id()));
+    let torrent = session
+        .add_torrent(meta("http://127.0.0.1:1/announce".to_string()), &dir)
+        .unwrap();
+    assert!(session.announce(&torrent, None).is_err());
+
+    let record = &torrent.trackers()[0].history[0];
+    assert!(record.error.is_some());
+    assert!(record.timings.total.is_some());
+    assert_eq!(record.timings.dns, None);
+}
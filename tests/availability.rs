@@ -0,0 +1,144 @@
+mod common;
+
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::piece::availability::Availability;
+use bittorrent_client::piece::bitfield::Bitfield;
+use bittorrent_client::piece::picker::{PickerStrictness, PiecePicker};
+use bittorrent_client::session::engine::Session;
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn bitfield(num_pieces: usize, pieces: &[usize]) -> Bitfield {
+    let mut bitfield = Bitfield::new(num_pieces);
+    for &index in pieces {
+        bitfield.set(index);
+    }
+    bitfield
+}
+
+#[test]
+fn distributed_copies_counts_full_copies_plus_fraction() {
+    let mut availability = Availability::new(4);
+    assert_eq!(availability.distributed_copies(), 0.0);
+
+    availability.add_bitfield(&Bitfield::full(4));
+    availability.add_bitfield(&bitfield(4, &[0, 1, 2]));
+    assert_eq!(availability.seeds(), 1);
+    assert_eq!(availability.counts(), &[2, 2, 2, 1]);
+    assert!((availability.distributed_copies() - 1.75).abs() < 1e-9);
+
+    let mut peer = bitfield(4, &[0, 1, 2]);
+    peer.set(3);
+    availability.add_have(3, &peer);
+    assert_eq!(availability.seeds(), 2);
+    assert_eq!(availability.distributed_copies(), 2.0);
+
+    availability.remove_bitfield(&Bitfield::full(4));
+    availability.remove_bitfield(&peer);
+    assert_eq!(availability.counts(), &[0, 0, 0, 0]);
+    assert_eq!(availability.seeds(), 0);
+}
+
+#[test]
+fn rarest_first_uses_availability() {
+    let mut picker = PiecePicker::new(3);
+    picker.set_strictness(PickerStrictness::RarestFirst);
+    picker.add_peer_bitfield(&bitfield(3, &[0, 1, 2]));
+    picker.add_peer_bitfield(&bitfield(3, &[0, 2]));
+    picker.add_peer_bitfield(&bitfield(3, &[2]));
+
+    let peer = SocketAddr::from((Ipv4Addr::LOCALHOST, 1));
+    let all = Bitfield::full(3);
+    assert_eq!(picker.availability().counts(), &[2, 1, 3]);
+    assert_eq!(picker.pick(peer, &all), Some(1));
+    assert_eq!(picker.pick(peer, &all), Some(0));
+    assert_eq!(picker.pick(peer, &all), Some(2));
+}
+
+#[test]
+fn have_past_the_last_piece_is_ignored() {
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = common::temp_dir("have-bounds");
+    let torrent = session
+        .add_torrent(
+            common::unverified(common::ANNOUNCE.to_string(), "have.bin"),
+            &dir,
+        )
+        .unwrap();
+
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    Handshake::perform_handshake(
+        &mut stream,
+        &torrent.info_hash(),
+        b"-FAKE0-havehavehave0",
+        ReservedBits::FAST,
+        Duration::from_secs(10),
+    )
+    .unwrap();
+    PeerMessage::HaveAll
+        .write_peer_message(&mut stream)
+        .unwrap();
+    for piece_index in [1, 7, u32::MAX] {
+        PeerMessage::Have { piece_index }
+            .write_peer_message(&mut stream)
+            .unwrap();
+    }
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while torrent.stats().seeds == 0 {
+        assert!(Instant::now() < deadline, "HaveAll was not counted");
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // Once the peer leaves, nothing it sent may still count.
+    drop(stream);
+    while torrent.stats().seeds != 0 {
+        assert!(
+            Instant::now() < deadline,
+            "seeds left over: {}",
+            torrent.stats().seeds
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_repeated_bitfield_replaces_the_first() {
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = common::temp_dir("bitfield-again");
+    let meta = common::single_file("again.bin", 16384, &vec![5u8; 3 * 16384]);
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    Handshake::perform_handshake(
+        &mut stream,
+        &torrent.info_hash(),
+        b"-FAKE0-againagainaga",
+        ReservedBits::FAST,
+        Duration::from_secs(10),
+    )
+    .unwrap();
+    let messages = [
+        PeerMessage::Bitfield(bitfield(3, &[0, 1]).as_bytes().to_vec().into()),
+        PeerMessage::HaveAll,
+        PeerMessage::Bitfield(bitfield(3, &[2]).as_bytes().to_vec().into()),
+        PeerMessage::Have { piece_index: 0 },
+    ];
+    for message in messages {
+        message.write_peer_message(&mut stream).unwrap();
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while torrent.stats().piece_availability != [1, 0, 1] {
+        assert!(
+            Instant::now() < deadline,
+            "availability: {:?}",
+            torrent.stats().piece_availability
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(torrent.stats().seeds, 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,122 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bittorrent_client::session::bandwidth::{
+    BandwidthSchedule, RateLimiter, RateLimits, ScheduleRule, Weekday,
+};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::units::Rate;
+
+const WORK: RateLimits = RateLimits {
+    download: Some(Rate(1 << 20)),
+    upload: Some(Rate(1 << 20)),
+};
+const DEFAULT: RateLimits = RateLimits {
+    download: Some(Rate(4 << 20)),
+    upload: Some(Rate(256 << 10)),
+};
+
+fn schedule() -> BandwidthSchedule {
+    BandwidthSchedule {
+        utc_offset_minutes: 0,
+        rules: vec![
+            ScheduleRule {
+                days: vec![
+                    Weekday::Monday,
+                    Weekday::Tuesday,
+                    Weekday::Wednesday,
+                    Weekday::Thursday,
+                    Weekday::Friday,
+                ],
+                start_hour: 9,
+                end_hour: 17,
+                limits: WORK,
+            },
+            ScheduleRule {
+                days: vec![Weekday::Friday],
+                start_hour: 22,
+                end_hour: 6,
+                limits: RateLimits::default(),
+            },
+        ],
+    }
+}
+
+// Hours into 1970-01-01, a Thursday, UTC.
+fn at(day: u64, hour: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs((day * 24 + hour) * 3600)
+}
+
+#[test]
+fn rules_pick_limits_by_day_and_hour() {
+    let schedule = schedule();
+    assert_eq!(schedule.limits_at(at(0, 10), DEFAULT), WORK);
+    assert_eq!(schedule.limits_at(at(0, 17), DEFAULT), DEFAULT);
+    assert_eq!(schedule.limits_at(at(0, 23), DEFAULT), DEFAULT);
+    // Friday night runs into Saturday morning, but Saturday's own night isn't covered.
+    assert_eq!(
+        schedule.limits_at(at(1, 23), DEFAULT),
+        RateLimits::default()
+    );
+    assert_eq!(schedule.limits_at(at(2, 5), DEFAULT), RateLimits::default());
+    assert_eq!(schedule.limits_at(at(2, 10), DEFAULT), DEFAULT);
+    assert_eq!(schedule.limits_at(at(2, 23), DEFAULT), DEFAULT);
+
+    // 8:00 UTC is 10:00 two hours east of it.
+    let shifted = BandwidthSchedule {
+        utc_offset_minutes: 120,
+        ..schedule
+    };
+    assert_eq!(shifted.limits_at(at(0, 8), DEFAULT), WORK);
+    assert_eq!(shifted.limits_at(at(0, 15), DEFAULT), DEFAULT);
+}
+
+#[test]
+fn the_session_applies_the_schedule() {
+    let all_day = ScheduleRule {
+        days: Vec::new(),
+        start_hour: 0,
+        end_hour: 0,
+        limits: WORK,
+    };
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        rate_limits: DEFAULT,
+        bandwidth_schedule: BandwidthSchedule {
+            utc_offset_minutes: 0,
+            rules: vec![all_day],
+        },
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    assert_eq!(session.rate_limits(), WORK);
+    session.tick();
+    assert_eq!(session.rate_limits(), WORK);
+
+    let unscheduled = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        rate_limits: DEFAULT,
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    assert_eq!(unscheduled.rate_limits(), DEFAULT);
+}
+
+#[test]
+fn the_limiter_holds_transfers_to_its_rate() {
+    let limiter = RateLimiter::new(Some(100_000));
+    let start = Instant::now();
+    // The full bucket goes to the first, the second runs it into debt and the third
+    // waits that out.
+    for _ in 0..3 {
+        limiter.acquire(100_000);
+    }
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(900), "{elapsed:?}");
+
+    limiter.set_rate(None);
+    let start = Instant::now();
+    limiter.acquire(usize::MAX);
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
@@ -0,0 +1,58 @@
+use bittorrent_client::bencode::errors::{BencodeError, Position};
+use bittorrent_client::bencode::parser::{parse_int, parse_value};
+
+fn position(input: &[u8]) -> Position {
+    parse_value(input).unwrap_err().position().unwrap().clone()
+}
+
+#[test]
+fn errors_point_at_the_failing_token() {
+    // The integer at byte 14 isn't a number.
+    let at = position(b"d3:cowi1e3:fooi1x3ee");
+    assert_eq!(at.offset, 14);
+    assert_eq!(at.context, "i1x3ee");
+
+    // Unknown type byte inside a list.
+    let error = parse_value(b"l4:spamxe").unwrap_err();
+    assert!(matches!(error, BencodeError::UnexpectedByte(b'x', _)));
+    assert_eq!(error.position().unwrap().offset, 7);
+
+    // Unterminated containers fail at the end of input.
+    assert_eq!(position(b"li1ei2e").offset, 7);
+    assert_eq!(position(b"").offset, 0);
+}
+
+#[test]
+fn missing_colon_is_reported_where_it_belongs() {
+    let input = b"l4:spam12eggs5:later";
+    let error = parse_value(input).unwrap_err();
+    assert!(matches!(error, BencodeError::InvalidString(_, _)));
+    assert_eq!(error.position().unwrap().offset, 7);
+}
+
+#[test]
+fn context_is_short_and_escaped() {
+    let mut input = b"d4:name".to_vec();
+    // Declares more than there is, with megabytes of binary after it.
+    input.extend(b"9999999:");
+    input.extend(vec![0xffu8; 4 << 20]);
+
+    let error = parse_value(&input).unwrap_err();
+    let at = error.position().unwrap();
+    assert_eq!(at.offset, 7);
+    assert_eq!(
+        at.context,
+        "9999999:\\xff\\xff\\xff\\xff\\xff\\xff\\xff\\xff..."
+    );
+
+    let message = error.to_string();
+    assert!(message.len() < 200, "{}", message);
+    assert!(message.contains("at byte 7"), "{}", message);
+}
+
+#[test]
+fn standalone_parsers_report_offsets_too() {
+    let error = parse_int(b"i12x").unwrap_err();
+    assert_eq!(error.position().unwrap().offset, 0);
+    assert_eq!(Position::in_input(b"abc", 10).offset, 3);
+}
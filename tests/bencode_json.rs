@@ -0,0 +1,68 @@
+use bittorrent_client::bencode::json::{BytesEncoding, from_json, to_json};
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::bencode::value::BencodeValue;
+use serde_json::json;
+
+const TORRENT: &[u8] =
+    b"d8:announce9:http://tr4:infod6:lengthi5e4:name3:one12:piece lengthi16384e6:pieces4:\x00\xab\xcd\xffee";
+
+#[test]
+fn bytes_are_tagged_in_the_chosen_encoding() {
+    let (value, _) = parse_value(TORRENT).unwrap();
+    let expected = |bytes: serde_json::Value| {
+        json!({
+            "announce": "http://tr",
+            "info": {
+                "length": 5,
+                "name": "one",
+                "piece length": 16384,
+                "pieces": bytes,
+            },
+        })
+    };
+    assert_eq!(
+        to_json(&value, BytesEncoding::Base64),
+        expected(json!({"$base64": "AKvN/w=="}))
+    );
+    assert_eq!(
+        value.to_json(BytesEncoding::Hex),
+        expected(json!({"$hex": "00abcdff"}))
+    );
+}
+
+#[test]
+fn round_trips_to_the_same_bytes() {
+    let (value, _) = parse_value(TORRENT).unwrap();
+    for encoding in [BytesEncoding::Base64, BytesEncoding::Hex] {
+        let text = to_json(&value, encoding).to_string();
+        let back = from_json(&serde_json::from_str(&text).unwrap()).unwrap();
+        assert_eq!(back, value);
+        assert_eq!(back.encode(), TORRENT);
+    }
+}
+
+#[test]
+fn json_without_a_bencode_form_is_refused() {
+    for bad in [
+        json!(1.5),
+        json!(true),
+        json!(null),
+        json!([1, {"a": null}]),
+        json!({"$hex": "abc"}),
+        json!({"$hex": "zz"}),
+        json!({"$base64": "!!"}),
+    ] {
+        assert!(BencodeValue::try_from(&bad).is_err(), "{}", bad);
+    }
+    // Only a lone, string-valued tag is read as bytes.
+    assert_eq!(
+        from_json(&json!({"$hex": "00", "x": 1})).unwrap(),
+        BencodeValue::Dictionary(
+            [
+                ("$hex".to_string(), BencodeValue::String("00".into())),
+                ("x".to_string(), BencodeValue::Integer(1)),
+            ]
+            .into()
+        )
+    );
+}
@@ -0,0 +1,62 @@
+use bittorrent_client::bencode::errors::BencodeError;
+use bittorrent_client::bencode::limits::ParseLimits;
+use bittorrent_client::bencode::parser::{BencodeParser, parse_value};
+use bittorrent_client::bencode::value::BencodeValue;
+
+fn nested_lists(depth: usize) -> Vec<u8> {
+    let mut input = vec![b'l'; depth];
+    input.extend(vec![b'e'; depth]);
+    input
+}
+
+#[test]
+fn rejects_nesting_deeper_than_limit() {
+    let parser = BencodeParser::new(ParseLimits {
+        max_depth: 4,
+        ..ParseLimits::default()
+    });
+
+    assert!(parser.parse(&nested_lists(4)).is_ok());
+    assert!(matches!(
+        parser.parse(&nested_lists(5)),
+        Err(BencodeError::LimitExceeded(_, _))
+    ));
+    assert!(matches!(
+        parser.parse(b"d1:ad1:ad1:ad1:ad1:ai1eeeeee"),
+        Err(BencodeError::LimitExceeded(_, _))
+    ));
+}
+
+#[test]
+fn rejects_declared_string_length_before_reading_it() {
+    let parser = BencodeParser::new(ParseLimits::strict());
+
+    // The declared length alone trips the limit; the input is nowhere near that long.
+    assert!(matches!(
+        parser.parse(b"99999999999:x"),
+        Err(BencodeError::LimitExceeded(_, _))
+    ));
+    assert!(parser.parse(b"4:spam").is_ok());
+}
+
+#[test]
+fn rejects_total_decoded_size_over_limit() {
+    let parser = BencodeParser::new(ParseLimits {
+        max_total_size: 10,
+        ..ParseLimits::default()
+    });
+
+    assert!(parser.parse(b"l4:spam4:eggse").is_ok());
+    assert!(matches!(
+        parser.parse(b"l4:spam4:eggs4:hame"),
+        Err(BencodeError::LimitExceeded(_, _))
+    ));
+}
+
+#[test]
+fn default_limits_parse_ordinary_input() {
+    let (value, rest) = parse_value(b"d3:cow3:moo4:spaml1:a1:bee").unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(value.string("cow").unwrap(), "moo");
+    assert!(matches!(value.get("spam").unwrap(), BencodeValue::List(l) if l.len() == 2));
+}
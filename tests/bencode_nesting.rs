@@ -0,0 +1,73 @@
+use bittorrent_client::bencode::errors::BencodeError;
+use bittorrent_client::bencode::limits::ParseLimits;
+use bittorrent_client::bencode::parser::{BencodeParser, parse_value};
+use bittorrent_client::bencode::value::BencodeValue;
+
+const DEEP: usize = 100_000;
+
+fn unlimited() -> BencodeParser {
+    BencodeParser::new(ParseLimits {
+        max_depth: usize::MAX,
+        ..ParseLimits::default()
+    })
+}
+
+fn depth(mut value: &BencodeValue) -> usize {
+    let mut depth = 0;
+    while let BencodeValue::List(values) = value {
+        depth += 1;
+        match values.first() {
+            Some(inner) => value = inner,
+            None => break,
+        }
+    }
+    depth
+}
+
+#[test]
+fn deeply_nested_lists_do_not_overflow_the_stack() {
+    let mut input = vec![b'l'; DEEP];
+    input.extend(vec![b'e'; DEEP]);
+
+    assert!(matches!(
+        parse_value(&input),
+        Err(BencodeError::LimitExceeded(_, _))
+    ));
+
+    let (value, rest) = unlimited().parse(&input).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(depth(&value), DEEP);
+}
+
+#[test]
+fn deeply_nested_dicts_do_not_overflow_the_stack() {
+    let mut input = b"d1:a".repeat(DEEP);
+    input.extend(b"i1e");
+    input.extend(vec![b'e'; DEEP]);
+
+    let (_, rest) = unlimited().parse(&input).unwrap();
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn unterminated_deep_nesting_is_an_error() {
+    let input = vec![b'l'; DEEP];
+    assert!(matches!(
+        unlimited().parse(&input),
+        Err(BencodeError::InvalidList(_, _))
+    ));
+
+    // A dict can't close between a key and its value.
+    assert!(unlimited().parse(b"d1:ae").is_err());
+}
+
+#[test]
+fn iterative_parse_matches_nested_structure() {
+    let (value, rest) = parse_value(b"d4:listli1eli2eee4:spamd3:cow3:mooee5:extra").unwrap();
+    assert_eq!(rest, b"5:extra");
+    assert_eq!(value.get_path("list[1][0]").unwrap().as_int().unwrap(), &2);
+    assert_eq!(
+        value.get_path("spam.cow").unwrap().as_string().unwrap(),
+        "moo"
+    );
+}
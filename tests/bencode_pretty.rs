@@ -0,0 +1,42 @@
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::bencode::pretty::{PrettyOptions, pretty};
+
+#[test]
+fn nested_values_are_indented_with_sorted_keys() {
+    let (value, _) =
+        parse_value(b"d4:infod6:lengthi100e6:pieces4:\x00\x01\xfe\xffe8:announce3:url5:emptylee")
+            .unwrap();
+    assert_eq!(
+        value.pretty(),
+        "{\n  \"announce\": \"url\",\n  \"empty\": [],\n  \"info\": {\n    \"length\": 100,\n    \"pieces\": <4 bytes 0001feff>,\n  },\n}"
+    );
+}
+
+#[test]
+fn long_values_are_cut_short() {
+    let (value, _) = parse_value(b"l5:hello4:\xff\xff\xff\xffi1ei2ei3ee").unwrap();
+    let options = PrettyOptions {
+        byte_preview: 2,
+        max_string_length: 3,
+        max_items: 4,
+        ..PrettyOptions::default()
+    };
+    assert_eq!(
+        pretty(&value, &options),
+        "[\n  \"hel\"... (2 more chars),\n  <4 bytes ffff...>,\n  1,\n  2,\n  ... 1 more\n]"
+    );
+    assert!(pretty(&value, &PrettyOptions::full()).contains("<4 bytes ffffffff>"));
+}
+
+#[test]
+fn deep_nesting_is_collapsed() {
+    let (value, _) = parse_value(b"lllleeee").unwrap();
+    let options = PrettyOptions {
+        max_depth: 2,
+        ..PrettyOptions::default()
+    };
+    assert_eq!(
+        pretty(&value, &options),
+        "[\n  [\n    [... 1 items],\n  ],\n]"
+    );
+}
@@ -0,0 +1,70 @@
+use bittorrent_client::bencode::errors::BencodeError;
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::bencode::value::BencodeValue;
+
+fn sample() -> BencodeValue {
+    let data = b"d4:infod5:filesld6:lengthi10e4:pathl1:aeed6:lengthi20e4:pathl1:b1:ceee4:name4:demoe6:nestedlli1ei2eeee";
+    parse_value(data).unwrap().0
+}
+
+#[test]
+fn path_reaches_nested_values() {
+    let value = sample();
+    assert_eq!(
+        *value
+            .get_path("info.files[1].length")
+            .unwrap()
+            .as_int()
+            .unwrap(),
+        20
+    );
+    assert_eq!(
+        value
+            .get_path("info.files[1].path[1]")
+            .unwrap()
+            .as_string()
+            .unwrap(),
+        "c"
+    );
+    assert_eq!(
+        *value.get_path("nested[0][1]").unwrap().as_int().unwrap(),
+        2
+    );
+    assert_eq!(
+        value.get_path("info.name").unwrap().as_string().unwrap(),
+        "demo"
+    );
+}
+
+#[test]
+fn typed_accessors_chain() {
+    let value = sample();
+    let length = value
+        .dict("info")
+        .and_then(|info| info.list("files"))
+        .and_then(|files| files.index(0))
+        .and_then(|file| file.int("length"))
+        .unwrap();
+    assert_eq!(length, 10);
+}
+
+#[test]
+fn path_errors_name_the_problem() {
+    let value = sample();
+    assert!(matches!(
+        value.get_path("info.missing"),
+        Err(BencodeError::MissingKey(key)) if key == "missing"
+    ));
+    assert!(matches!(
+        value.get_path("info.files[5]"),
+        Err(BencodeError::MissingIndex(5))
+    ));
+    assert!(matches!(
+        value.get_path("info.files[x]"),
+        Err(BencodeError::InvalidPath(_))
+    ));
+    assert!(matches!(
+        value.get_path("info.name.first"),
+        Err(BencodeError::WrongType { .. })
+    ));
+}
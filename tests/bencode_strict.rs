@@ -0,0 +1,91 @@
+use bittorrent_client::bencode::errors::BencodeError;
+use bittorrent_client::bencode::limits::ParseLimits;
+use bittorrent_client::bencode::options::ParserOptions;
+use bittorrent_client::bencode::parser::{BencodeParser, parse_value};
+use bittorrent_client::torrent::parser::{parse_torrent_file, parse_torrent_file_with};
+use sha1::{Digest, Sha1};
+
+fn strict() -> BencodeParser {
+    BencodeParser::with_options(ParserOptions::strict())
+}
+
+#[test]
+fn lenient_parsing_accepts_sloppy_encodings() {
+    let (value, _) = parse_value(b"li03ei-0ei+7e03:abce").unwrap();
+    let list = value.as_list().unwrap();
+    assert_eq!(list[0].as_int().unwrap(), &3);
+    assert_eq!(list[1].as_int().unwrap(), &0);
+    assert_eq!(list[2].as_int().unwrap(), &7);
+    assert_eq!(list[3].as_string().unwrap(), "abc");
+
+    // Unsorted keys are fine and a repeated key keeps its last value.
+    let (value, _) = parse_value(b"d1:bi1e1:ai2e1:bi3ee").unwrap();
+    assert_eq!(value.int("a").unwrap(), 2);
+    assert_eq!(value.int("b").unwrap(), 3);
+}
+
+#[test]
+fn strict_parsing_rejects_them() {
+    for input in [&b"i03e"[..], b"i-0e", b"i+7e", b"03:abc"] {
+        assert!(strict().parse(input).is_err(), "{:?}", input);
+    }
+    assert!(matches!(
+        strict().parse(b"d1:bi1e1:ai2ee"),
+        Err(BencodeError::InvalidDict(_, Some(at))) if at.offset == 7
+    ));
+    assert!(matches!(
+        strict().parse(b"d1:ai1e1:ai2ee"),
+        Err(BencodeError::InvalidDict(_, _))
+    ));
+
+    // Order is by raw bytes and checked per dict.
+    let (value, _) = strict().parse(b"d1:Ai0e1:ad1:zi1e2:zzi2ee1:bi3ee").unwrap();
+    assert_eq!(value.get_path("a.zz").unwrap().as_int().unwrap(), &2);
+    assert!(strict().parse(b"i0e").is_ok());
+    assert!(strict().parse(b"i-12e").is_ok());
+    assert!(strict().parse(b"0:").is_ok());
+}
+
+#[test]
+fn options_carry_limits() {
+    let parser = BencodeParser::with_options(ParserOptions::strict().limits(ParseLimits::strict()));
+    assert!(parser.options().strict);
+    assert_eq!(parser.limits(), &ParseLimits::strict());
+    assert!(!BencodeParser::new(ParseLimits::strict()).options().strict);
+}
+
+#[test]
+fn torrent_files_can_be_checked_strictly() {
+    let path = std::env::temp_dir().join(format!("bt-strict-{}.torrent", std::process::id()));
+    // "name" comes before "length": out of order.
+    let mut data =
+        b"d8:announce3:url4:infod4:name1:a6:lengthi3e12:piece lengthi16384e6:pieces20:".to_vec();
+    data.extend([7u8; 20]);
+    data.extend(b"ee");
+    std::fs::write(&path, data).unwrap();
+    let file = path.to_str().unwrap();
+
+    assert_eq!(parse_torrent_file(file).unwrap().info.name, "a");
+    assert!(parse_torrent_file_with(file, ParserOptions::strict()).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn lenient_download_path_hashes_the_info_dict_as_written() {
+    let path = std::env::temp_dir().join(format!("bt-lenient-{}.torrent", std::process::id()));
+    // A leading zero in the length: re-encoding would write i5e and join another swarm.
+    let mut info = b"d6:lengthi05e4:name1:a12:piece lengthi16384e6:pieces20:".to_vec();
+    info.extend([7u8; 20]);
+    info.push(b'e');
+    let mut data = b"d8:announce3:url4:info".to_vec();
+    data.extend(&info);
+    data.push(b'e');
+    std::fs::write(&path, data).unwrap();
+    let file = path.to_str().unwrap();
+
+    let meta = parse_torrent_file(file).unwrap();
+    assert_eq!(meta.total_size(), 5);
+    assert_eq!(meta.info_hash(), <[u8; 20]>::from(Sha1::digest(&info)));
+    assert!(parse_torrent_file_with(file, ParserOptions::strict()).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
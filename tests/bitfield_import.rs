@@ -0,0 +1,51 @@
+use bittorrent_client::piece::bitfield::Bitfield;
+use bittorrent_client::piece::hash::Sha1Mode;
+use bittorrent_client::session::torrent::Torrent;
+use bittorrent_client::torrent::value::{FilesInfo, Info, TorrentMetaInfo};
+use std::collections::HashMap;
+
+fn torrent() -> Torrent {
+    let meta = TorrentMetaInfo::new(
+        "http://127.0.0.1:1/announce".to_string(),
+        Info {
+            name: "missing.bin".to_string(),
+            piece_length: 16384,
+            pieces: vec![[0u8; 20]; 10],
+            files_info: FilesInfo::SingleFile {
+                length: 10 * 16384,
+                md5sum: None,
+            },
+            extras: HashMap::new(),
+        },
+    );
+    let dir = std::env::temp_dir().join(format!("bt-bitfield-import-{}", std::process::id()));
+    Torrent::new(meta, &dir, Sha1Mode::Fast)
+}
+
+#[test]
+fn exported_bitfield_imports_into_a_fresh_torrent() {
+    let source = torrent();
+    let mut have = Bitfield::new(10);
+    have.set(0);
+    have.set(7);
+    assert!(source.import_bitfield(&have));
+
+    let exported = source.bitfield();
+    let copy = torrent();
+    assert!(copy.import_bitfield(&Bitfield::from_bytes(exported.as_bytes(), 10)));
+    assert_eq!(copy.bitfield(), exported);
+    assert!(copy.has_piece(7));
+    assert!(!copy.has_piece(1));
+    assert!(!copy.is_complete());
+
+    assert!(copy.import_bitfield(&Bitfield::full(10)));
+    assert!(copy.is_complete());
+    assert!(copy.seeding_time().is_some());
+}
+
+#[test]
+fn bitfield_of_the_wrong_size_is_refused() {
+    let torrent = torrent();
+    assert!(!torrent.import_bitfield(&Bitfield::full(11)));
+    assert_eq!(torrent.bitfield().count(), 0);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::path::PathBuf;
+
+use bittorrent_client::session::bundle::TorrentBundle;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::error::SessionError;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+fn seeded_torrent(label: &str) -> (TorrentMetaInfo, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("bt-bundle-{}-{}", label, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let data: Vec<u8> = (0..40_000).map(|i| (i % 251) as u8).collect();
+    std::fs::write(dir.join("bundle.bin"), &data).unwrap();
+    let meta = common::single_file("bundle.bin", 16384, &data);
+    (meta, dir)
+}
+
+fn session() -> Session {
+    Session::new(common::local_config()).unwrap()
+}
+
+#[test]
+fn exported_torrent_imports_into_another_session() {
+    let (meta, dir) = seeded_torrent("roundtrip");
+    let bundle_dir = dir.join("export");
+    let source = session();
+    let torrent = source.add_torrent(meta, &dir).unwrap();
+    assert!(torrent.is_complete());
+    source
+        .export_torrent(&torrent.info_hash(), &bundle_dir)
+        .unwrap();
+
+    let mut bundle = TorrentBundle::load(&bundle_dir).unwrap();
+    assert_eq!(bundle.meta.info_hash(), torrent.info_hash());
+    assert_eq!(bundle.resume.pieces, torrent.bitfield().as_bytes());
+    bundle.downloaded = 1000;
+    bundle.uploaded = 5000;
+    bundle.save(&bundle_dir).unwrap();
+
+    let target = session();
+    let imported = target.import_torrent(&bundle_dir, &dir).unwrap();
+    assert_eq!(imported.info_hash(), torrent.info_hash());
+    assert!(imported.is_complete());
+    assert_eq!(imported.downloaded().as_u64(), 1000);
+    assert_eq!(imported.uploaded().as_u64(), 5000);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn bundle_with_foreign_resume_data_is_rejected() {
+    let (meta, dir) = seeded_torrent("foreign");
+    let bundle_dir = dir.join("export");
+    let source = session();
+    let torrent = source.add_torrent(meta, &dir).unwrap();
+
+    let mut bundle = TorrentBundle {
+        meta: torrent.meta().clone(),
+        resume: torrent.resume_data(std::time::Duration::ZERO),
+        downloaded: 0,
+        uploaded: 0,
+    };
+    bundle.resume.info_hash = [9u8; 20];
+    bundle.save(&bundle_dir).unwrap();
+
+    assert!(matches!(
+        session().import_torrent(&bundle_dir, &dir),
+        Err(SessionError::InvalidBundle(_))
+    ));
+    assert!(matches!(
+        session().import_torrent(&dir.join("missing"), &dir),
+        Err(SessionError::IOError(_))
+    ));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::torrent::Torrent;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+const BLOCK: usize = 16 * 1024;
+
+fn content() -> Vec<u8> {
+    (0..3 * BLOCK).map(|i| (i * 31) as u8).collect()
+}
+
+fn meta(name: &str, data: &[u8]) -> TorrentMetaInfo {
+    common::single_file(name, data.len(), data)
+}
+
+// Accepts the session's connection and answers its handshake as a fast extension seed.
+fn accept(listener: &TcpListener, info_hash: [u8; 20]) -> TcpStream {
+    let (mut stream, _) = listener.accept().unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut handshake = [0u8; 68];
+    std::io::Read::read_exact(&mut stream, &mut handshake).unwrap();
+    std::io::Write::write_all(
+        &mut stream,
+        &Handshake::new(
+            info_hash,
+            *b"-FAKE0-seedseedseeds",
+            ReservedBits::FAST | ReservedBits::V2,
+        )
+        .to_bytes(),
+    )
+    .unwrap();
+    stream
+}
+
+// Reads until the session has asked for `count` blocks, returning their offsets.
+fn requests(stream: &mut TcpStream, count: usize) -> Vec<u32> {
+    let mut offsets = Vec::new();
+    while offsets.len() < count {
+        if let PeerMessage::Request { begin, .. } = PeerMessage::read_peer_message(stream).unwrap()
+        {
+            offsets.push(begin);
+        }
+    }
+    offsets
+}
+
+fn send_block(stream: &mut TcpStream, data: &[u8], begin: u32) {
+    let start = begin as usize;
+    PeerMessage::Piece {
+        index: 0,
+        begin,
+        block: data[start..start + BLOCK].to_vec().into(),
+    }
+    .write_peer_message(stream)
+    .unwrap();
+}
+
+fn wait_complete(torrent: &Torrent) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !torrent.is_complete() {
+        assert!(Instant::now() < deadline, "download didn't finish");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn start(name: &str, data: &[u8]) -> (Session, std::sync::Arc<Torrent>, TcpListener) {
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let torrent = session.add_torrent(meta(name, data), &dir).unwrap();
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    session
+        .add_peer(&torrent.info_hash(), listener.local_addr().unwrap())
+        .unwrap();
+    (session, torrent, listener)
+}
+
+#[test]
+fn choke_keeps_received_blocks() {
+    let data = content();
+    let (_session, torrent, listener) = start("choke", &data);
+    let mut stream = accept(&listener, torrent.info_hash());
+
+    PeerMessage::HaveAll
+        .write_peer_message(&mut stream)
+        .unwrap();
+    PeerMessage::Unchoke
+        .write_peer_message(&mut stream)
+        .unwrap();
+    assert_eq!(requests(&mut stream, 3), vec![0, 16384, 32768]);
+
+    send_block(&mut stream, &data, 0);
+    PeerMessage::Choke.write_peer_message(&mut stream).unwrap();
+    for begin in [16384, 32768] {
+        PeerMessage::RejectRequest {
+            index: 0,
+            begin,
+            length: BLOCK as u32,
+        }
+        .write_peer_message(&mut stream)
+        .unwrap();
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while torrent.parked_bytes(0) != Some(BLOCK) {
+        assert!(Instant::now() < deadline, "block 0 wasn't kept");
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    PeerMessage::Unchoke
+        .write_peer_message(&mut stream)
+        .unwrap();
+    let mut offsets = requests(&mut stream, 2);
+    offsets.sort();
+    assert_eq!(offsets, vec![16384, 32768]);
+    for begin in offsets {
+        send_block(&mut stream, &data, begin);
+    }
+
+    wait_complete(&torrent);
+    assert_eq!(torrent.parked_bytes(0), None);
+}
+
+#[test]
+fn allowed_fast_pieces_download_while_choked() {
+    let data = content();
+    let (_session, torrent, listener) = start("allowed-fast", &data);
+    let mut stream = accept(&listener, torrent.info_hash());
+
+    PeerMessage::HaveAll
+        .write_peer_message(&mut stream)
+        .unwrap();
+    PeerMessage::AllowedFast { piece_index: 0 }
+        .write_peer_message(&mut stream)
+        .unwrap();
+    for begin in requests(&mut stream, 3) {
+        send_block(&mut stream, &data, begin);
+    }
+
+    wait_complete(&torrent);
+}
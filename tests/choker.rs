@@ -0,0 +1,140 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::session::choker::{Choker, SeedChoker};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+
+fn peer(n: u8) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::new(10, 0, 0, n), 6881))
+}
+
+fn interested_peers(count: u8) -> Choker {
+    let mut choker = Choker::default();
+    for n in 1..=count {
+        choker.add_peer(peer(n));
+        choker.set_interested(peer(n), true);
+    }
+    choker
+}
+
+#[test]
+fn leeching_reciprocates_with_one_optimistic_slot() {
+    let mut choker = interested_peers(4);
+    choker.add_peer(peer(5));
+
+    for round in 0..2 {
+        choker.record_download(peer(1), 300);
+        choker.record_download(peer(2), 200);
+        choker.record_download(peer(3), 100);
+        choker.rechoke(Instant::now(), 3, false, SeedChoker::RoundRobin);
+        // The optimistic slot moves on to whoever hasn't had one; peer 5 isn't interested.
+        let optimistic = if round == 0 { peer(3) } else { peer(4) };
+        assert_eq!(choker.unchoked(), vec![peer(1), peer(2), optimistic]);
+    }
+}
+
+#[test]
+fn seed_chokers_pick_by_upload_or_in_turn() {
+    let mut choker = interested_peers(4);
+    choker.record_upload(peer(4), 500);
+    choker.record_upload(peer(3), 400);
+    choker.record_upload(peer(1), 100);
+    choker.rechoke(Instant::now(), 3, true, SeedChoker::FastestUpload);
+    assert_eq!(choker.unchoked(), vec![peer(1), peer(3), peer(4)]);
+
+    let mut choker = interested_peers(5);
+    let mut rounds = Vec::new();
+    for _ in 0..3 {
+        choker.record_upload(peer(1), 1000);
+        choker.rechoke(Instant::now(), 2, true, SeedChoker::RoundRobin);
+        rounds.push(choker.unchoked());
+    }
+    assert_eq!(
+        rounds,
+        vec![
+            vec![peer(1), peer(2)],
+            vec![peer(3), peer(4)],
+            vec![peer(1), peer(5)],
+        ]
+    );
+
+    choker.set_interested(peer(1), false);
+    choker.remove_peer(peer(5));
+    choker.rechoke(Instant::now(), 2, true, SeedChoker::RoundRobin);
+    assert_eq!(choker.unchoked(), vec![peer(2), peer(3)]);
+    assert!(!choker.is_due(Instant::now(), Duration::from_secs(10)));
+}
+
+#[test]
+fn seeding_session_unchokes_only_its_slots() {
+    let data: Vec<u8> = (0..16384).map(|i| (i % 251) as u8).collect();
+    let dir = std::env::temp_dir().join(format!("bt-choker-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("choker.bin"), &data).unwrap();
+    let meta = common::single_file("choker.bin", 16384, &data);
+
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        upload_slots: Some(1),
+        seed_choker: SeedChoker::RoundRobin,
+        choke_interval: Duration::ZERO,
+        tick_interval: Duration::from_secs(3600),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+    assert!(torrent.is_complete());
+
+    let leechers: Vec<TcpStream> = [*b"-FAKE0-leecher1leech", *b"-FAKE0-leecher2leech"]
+        .iter()
+        .map(|peer_id| {
+            let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(10)))
+                .unwrap();
+            Handshake::perform_handshake(
+                &mut stream,
+                &torrent.info_hash(),
+                peer_id,
+                ReservedBits::FAST | ReservedBits::V2,
+                Duration::from_secs(10),
+            )
+            .unwrap();
+            PeerMessage::Interested
+                .write_peer_message(&mut stream)
+                .unwrap();
+            stream
+        })
+        .collect();
+
+    // Every tick is a round; the first one to find an interested peer unchokes it.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut unchoked = Vec::new();
+    while unchoked.len() != 1 {
+        assert!(Instant::now() < deadline, "no peer was unchoked");
+        std::thread::sleep(Duration::from_millis(50));
+        session.tick();
+        unchoked = torrent.unchoked_peers();
+    }
+
+    let chosen = leechers
+        .iter()
+        .position(|stream| stream.local_addr().unwrap() == unchoked[0])
+        .unwrap();
+    let mut stream = &leechers[chosen];
+    loop {
+        match PeerMessage::read_peer_message(&mut stream).unwrap() {
+            PeerMessage::Unchoke => break,
+            PeerMessage::Choke => panic!("the chosen peer was choked"),
+            _ => {}
+        }
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
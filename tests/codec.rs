@@ -0,0 +1,68 @@
+use bittorrent_client::peer::codec::PeerMessageCodec;
+use bittorrent_client::peer::error::PeerMessageError;
+use bittorrent_client::peer::value::PeerMessage;
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+fn messages() -> Vec<PeerMessage> {
+    vec![
+        PeerMessage::Interested,
+        PeerMessage::Request {
+            index: 3,
+            begin: 0,
+            length: 16384,
+        },
+        PeerMessage::Piece {
+            index: 3,
+            begin: 0,
+            block: vec![9; 16384].into(),
+        },
+        PeerMessage::KeepAlive,
+        PeerMessage::Bitfield(vec![0xf0, 0x01].into()),
+        PeerMessage::Extended {
+            id: 0,
+            payload: b"d1:pi6881ee".to_vec().into(),
+        },
+    ]
+}
+
+#[test]
+fn encodes_what_to_bytes_would() {
+    let mut codec = PeerMessageCodec;
+    for message in messages() {
+        let mut dst = BytesMut::new();
+        codec.encode(message.clone(), &mut dst).unwrap();
+        assert_eq!(&dst[..], &message.to_bytes()[..]);
+    }
+}
+
+#[test]
+fn decodes_across_partial_buffers() {
+    let mut codec = PeerMessageCodec;
+    let mut wire = BytesMut::new();
+    for message in messages() {
+        codec.encode(message, &mut wire).unwrap();
+    }
+
+    let mut src = BytesMut::new();
+    let mut decoded = Vec::new();
+    for chunk in wire.chunks(1000) {
+        src.extend_from_slice(chunk);
+        while let Some(message) = codec.decode(&mut src).unwrap() {
+            decoded.push(message);
+        }
+    }
+    assert_eq!(decoded, messages());
+    assert!(codec.decode_eof(&mut src).unwrap().is_none());
+}
+
+#[test]
+fn truncated_stream_is_an_error_at_eof() {
+    let mut codec = PeerMessageCodec;
+    let mut src = BytesMut::from(&PeerMessage::Have { piece_index: 2 }.to_bytes()[..5]);
+    assert!(codec.decode(&mut src).unwrap().is_none());
+    assert!(matches!(
+        codec.decode_eof(&mut src),
+        Err(PeerMessageError::IOError(_))
+    ));
+}
@@ -0,0 +1,99 @@
+// Fixtures shared by the integration tests. Every test binary compiles this module and
+// uses only part of it.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::torrent::value::{File, FilesInfo, Info, TorrentMetaInfo};
+use sha1::{Digest, Sha1};
+
+// Nothing listens here, so announces fail fast.
+pub const ANNOUNCE: &str = "http://127.0.0.1:1/announce";
+
+pub fn piece_hashes(data: &[u8], piece_length: usize) -> Vec<[u8; 20]> {
+    data.chunks(piece_length)
+        .map(|piece| Sha1::digest(piece).into())
+        .collect()
+}
+
+// A single-file torrent of `data`.
+pub fn single_file(name: &str, piece_length: usize, data: &[u8]) -> TorrentMetaInfo {
+    TorrentMetaInfo::new(
+        ANNOUNCE.to_string(),
+        Info {
+            name: name.to_string(),
+            piece_length,
+            pieces: piece_hashes(data, piece_length),
+            files_info: FilesInfo::SingleFile {
+                length: data.len(),
+                md5sum: None,
+            },
+            extras: HashMap::new(),
+        },
+    )
+}
+
+// A torrent whose `files` together hold `data`.
+pub fn multi_file(
+    name: &str,
+    piece_length: usize,
+    files: Vec<File>,
+    data: &[u8],
+) -> TorrentMetaInfo {
+    TorrentMetaInfo::new(
+        ANNOUNCE.to_string(),
+        Info {
+            name: name.to_string(),
+            piece_length,
+            pieces: piece_hashes(data, piece_length),
+            files_info: FilesInfo::MultiFile { files },
+            extras: HashMap::new(),
+        },
+    )
+}
+
+pub fn file(path: &[&str], length: usize) -> File {
+    File {
+        length,
+        path: path.iter().map(|part| part.to_string()).collect(),
+        md5sum: None,
+        extras: HashMap::new(),
+    }
+}
+
+// A 100-byte torrent whose one piece no data matches, for tests about everything but the
+// download itself.
+pub fn unverified(announce: String, name: &str) -> TorrentMetaInfo {
+    TorrentMetaInfo::new(
+        announce,
+        Info {
+            name: name.to_string(),
+            piece_length: 16384,
+            pieces: vec![[0u8; 20]],
+            files_info: FilesInfo::SingleFile {
+                length: 100,
+                md5sum: None,
+            },
+            extras: HashMap::new(),
+        },
+    )
+}
+
+// An empty directory of its own below the system temp dir.
+pub fn temp_dir(label: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bt-{}-{}", label, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// A session listening on a free loopback port.
+pub fn local_config() -> SessionConfig {
+    SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        ..SessionConfig::default()
+    }
+}
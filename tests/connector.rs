@@ -0,0 +1,160 @@
+// Outgoing dials against local listeners that answer the handshake themselves.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use bittorrent_client::peer::connector::{DialTarget, PeerConnector};
+use bittorrent_client::peer::pex::PexFlags;
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerTimeouts};
+use bittorrent_client::retry::RetryPolicy;
+
+const INFO_HASH: [u8; 20] = [7; 20];
+const OWN_ID: [u8; 20] = *b"-RS0001-connector000";
+
+fn connector(max_attempts: u32) -> PeerConnector {
+    PeerConnector::new(
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(300),
+            max_backoff: Duration::from_millis(300),
+            jitter: 0.0,
+        },
+        PeerTimeouts {
+            connect: Duration::from_secs(2),
+            handshake: Duration::from_secs(2),
+            ..PeerTimeouts::default()
+        },
+    )
+}
+
+fn target(port: u16, obfuscated_port: Option<u16>) -> DialTarget {
+    DialTarget {
+        ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        port,
+        obfuscated_port,
+        flags: PexFlags::default(),
+    }
+}
+
+// A loopback port nothing listens on.
+fn closed_port() -> u16 {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+// Answers one handshake as `peer_id` for `info_hash`, holding it for `hold` first.
+// `in_flight` tracks how many handshakes all such listeners are holding at once, and
+// `most` the highest that ever got.
+fn handshaking_peer(
+    info_hash: [u8; 20],
+    peer_id: [u8; 20],
+    hold: Duration,
+    in_flight: Arc<AtomicUsize>,
+    most: Arc<AtomicUsize>,
+) -> u16 {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0u8; 68];
+        stream.read_exact(&mut request).unwrap();
+        let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        most.fetch_max(now, Ordering::SeqCst);
+        thread::sleep(hold);
+        in_flight.fetch_sub(1, Ordering::SeqCst);
+        let reply = Handshake::new(info_hash, peer_id, ReservedBits::FAST);
+        let _ = stream.write_all(&reply.to_bytes());
+        // Keep the connection up until the dialer is done with it.
+        let _ = stream.read(&mut request);
+    });
+    port
+}
+
+#[test]
+fn every_port_of_a_peer_is_dialed() {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let live = listener.local_addr().unwrap();
+
+    // The advertised port is dead; the obfuscated one answers.
+    let stream = connector(1)
+        .connect(&target(closed_port(), Some(live.port())))
+        .unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), live);
+    assert!(stream.read_timeout().unwrap().is_some());
+
+    assert!(
+        connector(1)
+            .connect(&target(closed_port(), Some(closed_port())))
+            .is_err()
+    );
+}
+
+#[test]
+fn failed_dials_are_retried_after_a_backoff() {
+    let port = closed_port();
+    assert!(connector(1).connect(&target(port, None)).is_err());
+
+    // The peer only starts listening after the first attempt was refused.
+    let late = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+        listener.accept().unwrap().1
+    });
+    let stream = connector(3).connect(&target(port, None)).unwrap();
+    assert_eq!(
+        stream.peer_addr().unwrap(),
+        SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+    );
+    assert_eq!(late.join().unwrap(), stream.local_addr().unwrap());
+}
+
+#[test]
+fn dial_all_keeps_to_the_parallel_limit() {
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let most = Arc::new(AtomicUsize::new(0));
+    let hold = Duration::from_millis(150);
+
+    let mut targets = Vec::new();
+    let mut expected = Vec::new();
+    for n in 0..4u8 {
+        let peer_id = [b'a' + n; 20];
+        let port = handshaking_peer(
+            INFO_HASH,
+            peer_id,
+            hold,
+            Arc::clone(&in_flight),
+            Arc::clone(&most),
+        );
+        targets.push(target(port, None));
+        expected.push(peer_id);
+    }
+    // Neither a dead peer nor one on another torrent comes back as a connection.
+    targets.push(target(closed_port(), None));
+    let other = handshaking_peer(
+        [8; 20],
+        [b'z'; 20],
+        Duration::ZERO,
+        Arc::clone(&in_flight),
+        Arc::new(AtomicUsize::new(0)),
+    );
+    targets.push(target(other, None));
+
+    let connections: Vec<_> = connector(1)
+        .dial_all(targets, INFO_HASH, OWN_ID, 2)
+        .into_iter()
+        .collect();
+
+    let mut got: Vec<[u8; 20]> = connections.iter().map(|c| c.remote.peer_id).collect();
+    got.sort();
+    assert_eq!(got, expected);
+    assert!(connections.iter().all(|c| !c.incoming && c.am_choking));
+    assert_eq!(most.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,37 @@
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::torrent::parser::torrent_from_bencode;
+use bittorrent_client::torrent::value::ToBencode;
+
+const INFO: &str =
+    "4:infod6:lengthi5e4:name5:a.txt12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+
+#[test]
+fn parses_creation_metadata() {
+    let bytes = format!(
+        "d8:announce14:http://tracker7:comment11:Hello there10:created by13:mktorrent 1.113:creation datei1700000000e8:encoding5:UTF-8{}e",
+        INFO
+    );
+    let (value, _) = parse_value(bytes.as_bytes()).unwrap();
+    let meta = torrent_from_bencode(&value).unwrap();
+    assert_eq!(meta.comment.as_deref(), Some("Hello there"));
+    assert_eq!(meta.created_by.as_deref(), Some("mktorrent 1.1"));
+    assert_eq!(meta.creation_date, Some(1_700_000_000));
+    assert_eq!(meta.encoding.as_deref(), Some("UTF-8"));
+
+    let reparsed = torrent_from_bencode(&meta.to_bencode_value()).unwrap();
+    assert_eq!(reparsed.comment, meta.comment);
+    assert_eq!(reparsed.created_by, meta.created_by);
+    assert_eq!(reparsed.creation_date, meta.creation_date);
+    assert_eq!(reparsed.encoding, meta.encoding);
+}
+
+#[test]
+fn creation_metadata_is_optional() {
+    let bytes = format!("d8:announce14:http://tracker7:commenti5e{}e", INFO);
+    let (value, _) = parse_value(bytes.as_bytes()).unwrap();
+    let meta = torrent_from_bencode(&value).unwrap();
+    assert_eq!(meta.comment, None);
+    assert_eq!(meta.created_by, None);
+    assert_eq!(meta.creation_date, None);
+    assert_eq!(meta.encoding, None);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::slots::SlotKind;
+
+#[test]
+fn dump_covers_torrents_peers_and_events() {
+    let dir = std::env::temp_dir().join(format!("bt-debug-dump-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let data: Vec<u8> = (0..16384).map(|i| (i % 233) as u8).collect();
+    std::fs::write(dir.join("dump.bin"), &data).unwrap();
+    let meta = common::single_file("dump.bin", 16384, &data);
+
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        tick_interval: Duration::from_secs(3600),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    Handshake::perform_handshake(
+        &mut stream,
+        &torrent.info_hash(),
+        b"-FAKE0-dumpdumpdump0",
+        ReservedBits::empty(),
+        Duration::from_secs(10),
+    )
+    .unwrap();
+    PeerMessage::Interested
+        .write_peer_message(&mut stream)
+        .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !torrent
+        .peer_states()
+        .first()
+        .is_some_and(|peer| peer.peer_interested)
+    {
+        assert!(Instant::now() < deadline, "peer never showed up");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let peer = &torrent.peer_states()[0];
+    assert_eq!(peer.addr, stream.local_addr().unwrap());
+    assert_eq!(&peer.peer_id, b"-FAKE0-dumpdumpdump0");
+    assert_eq!(peer.slot, SlotKind::Active);
+
+    let path = dir.join("dump.txt");
+    session.write_debug_dump(&path).unwrap();
+    let dump = std::fs::read_to_string(&path).unwrap();
+    let hex: String = torrent
+        .info_hash()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    assert!(dump.contains(&format!("== torrent {} dump.bin", hex)));
+    assert!(dump.contains("state: Seeding"));
+    assert!(dump.contains("-FAKE0-dumpdumpdump0 Active"));
+    assert!(dump.contains("http://127.0.0.1:1/announce group default"));
+    assert!(dump.contains("max_active_peers"));
+    assert!(dump.contains("TorrentAdded"));
+
+    drop(stream);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !torrent.peer_states().is_empty() {
+        assert!(Instant::now() < deadline, "peer was never forgotten");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
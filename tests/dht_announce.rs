@@ -0,0 +1,103 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::bencode::value::BencodeValue;
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::dht::DhtClient;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::peer_pool::PeerSource;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// Hands out one peer per lookup and writes down what it was asked.
+#[derive(Default)]
+struct RecordingDht {
+    bootstrapped: Mutex<Option<([u8; 20], Vec<SocketAddr>)>>,
+    lookups: Mutex<Vec<[u8; 20]>>,
+    announces: Mutex<Vec<([u8; 20], u16)>>,
+}
+
+impl DhtClient for RecordingDht {
+    fn bootstrap(&self, node_id: [u8; 20], nodes: &[SocketAddr]) {
+        *self.bootstrapped.lock().unwrap() = Some((node_id, nodes.to_vec()));
+    }
+
+    fn get_peers(&self, info_hash: &[u8; 20]) -> Vec<SocketAddr> {
+        self.lookups.lock().unwrap().push(*info_hash);
+        vec![SocketAddr::from((Ipv4Addr::LOCALHOST, 1))]
+    }
+
+    fn announce_peer(&self, info_hash: &[u8; 20], port: u16) {
+        self.announces.lock().unwrap().push((*info_hash, port));
+    }
+}
+
+impl RecordingDht {
+    fn wait_for_announces(&self, count: usize) {
+        let deadline = Instant::now() + TIMEOUT;
+        while self.announces.lock().unwrap().len() < count {
+            assert!(Instant::now() < deadline, "no DHT announce");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+fn session() -> Session {
+    Session::new(SessionConfig {
+        tick_interval: Duration::from_millis(20),
+        announce_port: Some(6999),
+        ..common::local_config()
+    })
+    .unwrap()
+}
+
+#[test]
+fn public_torrents_are_looked_up_and_announced() {
+    let session = session();
+    let dht = Arc::new(RecordingDht::default());
+    let node = SocketAddr::from((Ipv4Addr::new(10, 0, 0, 9), 6881));
+    session.add_dht_nodes([node]);
+    session.set_dht_client(dht.clone());
+    assert_eq!(
+        *dht.bootstrapped.lock().unwrap(),
+        Some((session.dht_node_id(), vec![node]))
+    );
+
+    let dir = common::temp_dir("dht-announce");
+    let mut private = common::unverified(common::ANNOUNCE.to_string(), "private.bin");
+    private
+        .info
+        .extras
+        .insert("private".to_string(), BencodeValue::Integer(1));
+    let private = session.add_torrent(private, &dir).unwrap();
+    let meta = common::unverified(common::ANNOUNCE.to_string(), "public.bin");
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    dht.wait_for_announces(1);
+    assert_eq!(
+        *dht.announces.lock().unwrap(),
+        vec![(torrent.info_hash(), 6999)]
+    );
+    let deadline = Instant::now() + TIMEOUT;
+    while torrent.peer_source_counts().get(&PeerSource::Dht) != Some(&1) {
+        assert!(Instant::now() < deadline, "DHT peer not pooled");
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // Within the interval nothing is repeated, and the private torrent never shows up.
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(*dht.lookups.lock().unwrap(), vec![torrent.info_hash()]);
+    assert!(private.peer_source_counts().is_empty());
+
+    // A resumed torrent looks itself up again straight away.
+    torrent.pause();
+    thread::sleep(Duration::from_millis(100));
+    torrent.resume();
+    dht.wait_for_announces(2);
+    assert_eq!(dht.lookups.lock().unwrap().len(), 2);
+    let _ = std::fs::remove_dir_all(&dir);
+}
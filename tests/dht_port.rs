@@ -0,0 +1,78 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+
+#[test]
+fn port_message_roundtrip() {
+    let message = PeerMessage::Port { listen_port: 6881 };
+    let bytes = message.to_bytes();
+    assert_eq!(bytes, vec![0, 0, 0, 3, 9, 0x1a, 0xe1]);
+    assert_eq!(
+        PeerMessage::read_peer_message(&mut bytes.as_slice()).unwrap(),
+        message
+    );
+    assert!(PeerMessage::from_frame(9, &[1]).is_err());
+}
+
+#[test]
+fn dht_ports_are_exchanged_with_dht_peers() {
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        dht_port: Some(7000),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-dht-port-{}", std::process::id()));
+    let meta = common::unverified("http://127.0.0.1:1/announce".to_string(), "dht.bin");
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    session
+        .add_peer(&torrent.info_hash(), listener.local_addr().unwrap())
+        .unwrap();
+    let (mut stream, _) = listener.accept().unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut handshake = [0u8; 68];
+    stream.read_exact(&mut handshake).unwrap();
+    // With a DHT node to point peers at, the session says so.
+    let theirs =
+        Handshake::from_bytes(&handshake, &torrent.info_hash(), b"-FAKE0-dhtdhtdhtdhtd").unwrap();
+    assert!(theirs.supports_dht());
+    assert!(theirs.supports_extension_protocol());
+    let mut ours = Handshake::new(
+        torrent.info_hash(),
+        *b"-FAKE0-dhtdhtdhtdhtd",
+        ReservedBits::FAST | ReservedBits::V2,
+    );
+    ours.reserved.insert(ReservedBits::DHT);
+    stream.write_all(&ours.to_bytes()).unwrap();
+
+    PeerMessage::Port { listen_port: 7001 }
+        .write_peer_message(&mut stream)
+        .unwrap();
+    loop {
+        if let PeerMessage::Port { listen_port } =
+            PeerMessage::read_peer_message(&mut stream).unwrap()
+        {
+            assert_eq!(listen_port, 7000);
+            break;
+        }
+    }
+
+    let expected = SocketAddr::from((Ipv4Addr::LOCALHOST, 7001));
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while torrent.dht_nodes() != vec![expected] {
+        assert!(Instant::now() < deadline, "DHT port wasn't recorded");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,108 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::dht_state::{DhtNode, DhtState};
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::resume::unix_time;
+
+fn state_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bt-dht-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn session(state_dir: &Path, dht_port: Option<u16>) -> Session {
+    Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        state_dir: Some(state_dir.to_path_buf()),
+        dht_port,
+        ..SessionConfig::default()
+    })
+    .unwrap()
+}
+
+fn node(addr: &str, last_seen: u64) -> DhtNode {
+    DhtNode {
+        addr: addr.parse().unwrap(),
+        last_seen,
+        id: None,
+    }
+}
+
+#[test]
+fn merge_keeps_latest_sighting_and_prune_drops_old_nodes() {
+    let mut state = DhtState::new();
+    state.merge([node("10.0.0.1:6881", 100), node("10.0.0.2:6881", 300)]);
+    state.merge([node("10.0.0.1:6881", 500), node("[::1]:6881", 50)]);
+    assert_eq!(
+        state.nodes,
+        vec![
+            node("10.0.0.1:6881", 500),
+            node("10.0.0.2:6881", 300),
+            node("[::1]:6881", 50),
+        ]
+    );
+
+    let decoded = DhtState::from_bencode(&state.to_bencode()).unwrap();
+    assert_eq!(decoded, state);
+
+    let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+    state.prune(now, Duration::from_secs(800));
+    assert_eq!(
+        state.nodes,
+        vec![node("10.0.0.1:6881", 500), node("10.0.0.2:6881", 300)]
+    );
+}
+
+#[test]
+fn node_id_and_nodes_survive_a_restart() {
+    let dir = state_dir("restart");
+    let first = session(&dir, Some(6881));
+    let node_id = first.dht_node_id();
+    let addr: SocketAddr = "192.0.2.7:6881".parse().unwrap();
+    first.add_dht_nodes([addr]);
+    assert_eq!(first.dht_nodes(), vec![addr]);
+    drop(first);
+    assert!(dir.join("dht.state").exists());
+
+    let second = session(&dir, Some(6881));
+    assert_eq!(second.dht_node_id(), node_id);
+    assert_eq!(second.dht_nodes(), vec![addr]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn stale_nodes_are_dropped_on_load_but_the_id_stays() {
+    let dir = state_dir("stale");
+    std::fs::create_dir_all(&dir).unwrap();
+    let now = unix_time(SystemTime::now());
+    let saved = DhtState {
+        node_id: [9; 20],
+        saved_at: now - 3 * 24 * 60 * 60,
+        nodes: vec![
+            node("192.0.2.1:6881", now - 60),
+            node("192.0.2.2:6881", now - 3 * 24 * 60 * 60),
+        ],
+    };
+    saved.save(&dir.join("dht.state")).unwrap();
+
+    let session = session(&dir, Some(6881));
+    assert_eq!(session.dht_node_id(), [9; 20]);
+    assert_eq!(
+        session.dht_nodes(),
+        vec!["192.0.2.1:6881".parse::<SocketAddr>().unwrap()]
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn nothing_is_saved_without_a_dht_port() {
+    let dir = state_dir("no-port");
+    let session = session(&dir, None);
+    session.add_dht_nodes(["192.0.2.3:6881".parse().unwrap()]);
+    drop(session);
+    assert!(!dir.join("dht.state").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::handle::TorrentState;
+use bittorrent_client::session::history::StateChange;
+use bittorrent_client::session::priority::FilePriority;
+use bittorrent_client::storage::disk_space::available_space;
+use bittorrent_client::torrent::value::{File, FilesInfo, Info, TorrentMetaInfo};
+
+const PIECE: usize = 16 * 1024 * 1024;
+
+// A small file followed by one bigger than the free space at `dir`.
+fn meta(dir: &Path) -> TorrentMetaInfo {
+    let too_big = (available_space(dir).unwrap() as usize).next_multiple_of(PIECE) + PIECE;
+    let file = |name: &str, length| File {
+        length,
+        path: vec![name.to_string()],
+        md5sum: None,
+        extras: HashMap::new(),
+    };
+    TorrentMetaInfo::new(
+        "http://127.0.0.1:1/announce".to_string(),
+        Info {
+            name: "big".to_string(),
+            piece_length: PIECE,
+            pieces: vec![[0u8; 20]; (100 + too_big).div_ceil(PIECE)],
+            files_info: FilesInfo::MultiFile {
+                files: vec![file("small.bin", 100), file("huge.bin", too_big)],
+            },
+            extras: HashMap::new(),
+        },
+    )
+}
+
+#[test]
+fn missing_directories_report_their_parents_space() {
+    let dir = std::env::temp_dir();
+    assert!(available_space(&dir).unwrap() > 0);
+    assert!(available_space(&dir.join("not/created/yet")).unwrap() > 0);
+}
+
+#[test]
+fn downloads_wait_for_enough_space() {
+    let dir = std::env::temp_dir().join(format!("bt-disk-space-{}", std::process::id()));
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        space_check_interval: Duration::ZERO,
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let torrent = session.add_torrent(meta(&dir), &dir).unwrap();
+    assert!(matches!(
+        torrent.state(),
+        TorrentState::InsufficientSpace { needed, available } if needed > available
+    ));
+    assert!(!torrent.is_active());
+    assert!(
+        torrent
+            .history()
+            .iter()
+            .any(|entry| matches!(entry.change, StateChange::InsufficientSpace { .. }))
+    );
+
+    // Still no room: resuming and the periodic check leave it paused.
+    torrent.resume();
+    session.tick();
+    assert!(matches!(
+        torrent.state(),
+        TorrentState::InsufficientSpace { .. }
+    ));
+
+    // Pausing by hand takes it out of the session's hands.
+    torrent.pause();
+    assert_eq!(torrent.state(), TorrentState::Paused);
+    torrent.resume();
+    assert!(matches!(
+        torrent.state(),
+        TorrentState::InsufficientSpace { .. }
+    ));
+
+    // Skipping the big file makes room, and the next check starts the download.
+    torrent.set_file_priority(1, FilePriority::Skip).unwrap();
+    session.tick();
+    assert_eq!(torrent.state(), TorrentState::Downloading);
+    let _ = std::fs::remove_dir_all(&dir);
+}
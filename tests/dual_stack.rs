@@ -0,0 +1,109 @@
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener};
+use std::thread;
+
+use bittorrent_client::peer::connector::FamilyPreference;
+use bittorrent_client::tracker::client::{TrackerClient, parse_tracker_response};
+use bittorrent_client::tracker::value::TrackerRequest;
+
+// One IPv4 peer in "peers" and one IPv6 peer, [2001:db8::1]:6882, in "peers6".
+const COMPACT: &[u8] = b"d8:intervali900e5:peers6:\x0a\x00\x00\x01\x1a\xe16:peers618:\
+\x20\x01\x0d\xb8\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x1a\xe2e";
+
+fn addrs(body: &[u8]) -> Vec<SocketAddr> {
+    let response = parse_tracker_response(body).unwrap();
+    response
+        .peers
+        .iter()
+        .map(|peer| SocketAddr::new(peer.ip, peer.port))
+        .collect()
+}
+
+#[test]
+fn peers6_are_added_to_compact_peers() {
+    assert_eq!(
+        addrs(COMPACT),
+        vec![
+            SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), 6881)),
+            SocketAddr::from((Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 6882)),
+        ]
+    );
+}
+
+#[test]
+fn dict_peers_may_have_ipv6_addresses() {
+    let body = b"d8:intervali900e5:peersld2:ip7:2001::54:porti51413eeee";
+    assert_eq!(
+        addrs(body),
+        vec![SocketAddr::new("2001::5".parse().unwrap(), 51413)]
+    );
+}
+
+#[test]
+fn truncated_compact_peers_are_refused() {
+    assert!(parse_tracker_response(b"d8:intervali900e5:peers5:\x0a\x00\x00\x01\x1ae").is_err());
+    assert!(
+        parse_tracker_response(b"d8:intervali900e5:peers0:6:peers64:\x20\x01\x0d\xb8e").is_err()
+    );
+}
+
+#[test]
+fn preferred_family_is_dialed_first() {
+    let v4 = |n| SocketAddr::from((Ipv4Addr::new(10, 0, 0, n), 6881));
+    let v6 = |n| SocketAddr::from((Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, n), 6881));
+    let discovered = vec![v4(1), v6(1), v4(2), v6(2)];
+
+    let mut addrs = discovered.clone();
+    FamilyPreference::Ipv6.sort(&mut addrs);
+    assert_eq!(addrs, vec![v6(1), v6(2), v4(1), v4(2)]);
+
+    let mut addrs = discovered.clone();
+    FamilyPreference::Ipv4.sort(&mut addrs);
+    assert_eq!(addrs, vec![v4(1), v4(2), v6(1), v6(2)]);
+
+    let mut addrs = discovered.clone();
+    FamilyPreference::Either.sort(&mut addrs);
+    assert_eq!(addrs, discovered);
+    assert!(FamilyPreference::Either.prefers(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+}
+
+// A tracker given as an IP literal has one family only, so there's a single announce.
+#[test]
+fn single_family_trackers_get_one_announce() {
+    let tracker = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let announce = format!("http://{}/announce", tracker.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        let (mut stream, _) = tracker.accept().unwrap();
+        let mut request = [0u8; 4096];
+        let _ = stream.read(&mut request);
+        let header = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            COMPACT.len()
+        );
+        stream.write_all(header.as_bytes()).unwrap();
+        stream.write_all(COMPACT).unwrap();
+        drop(stream);
+        tracker.set_nonblocking(true).unwrap();
+        tracker.accept().is_ok()
+    });
+
+    let request = TrackerRequest {
+        announce_url: announce,
+        info_hash: [1; 20],
+        peer_id: *b"-RS0001-abcdefghijkl",
+        ip: None,
+        port: 6881,
+        uploaded: 0,
+        downloaded: 0,
+        left: 100,
+        compact: true,
+        no_peer_id: true,
+        event: None,
+        numwant: None,
+        key: None,
+        tracker_id: None,
+    };
+    let response = TrackerClient::query_tracker_dual(&request).unwrap();
+    assert_eq!(response.peers.len(), 2);
+    assert!(!server.join().unwrap());
+}
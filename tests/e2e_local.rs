@@ -0,0 +1,216 @@
+// End-to-end harness against a real tracker and seeder running as local child processes.
+//
+// Ignored by default since it needs external binaries. Run with:
+//
+//   BT_E2E_TRACKER="opentracker -i 127.0.0.1 -p {port}" \
+//   BT_E2E_SEEDER="transmission-cli -w {dir} {torrent}" \
+//   cargo test --test e2e_local -- --ignored
+//
+// `{port}`, `{dir}` and `{torrent}` are substituted before the commands are spawned.
+// The test generates a small payload, writes a .torrent for it, starts the tracker and
+// the seeder, then announces, handshakes with the seeder and downloads every piece,
+// checking the result against the original payload.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::bencode::encoder::encode;
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerTimeouts};
+use bittorrent_client::torrent::parser::parse_torrent_file;
+use bittorrent_client::torrent::value::{FilesInfo, Info, ToBencode, TorrentMetaInfo};
+use bittorrent_client::tracker::client::TrackerClient;
+use bittorrent_client::tracker::value::{Event, TrackerRequest};
+use sha1::{Digest, Sha1};
+
+const TRACKER_PORT: u16 = 16969;
+const PIECE_LENGTH: usize = 16 * 1024;
+const PAYLOAD_LENGTH: usize = 5 * PIECE_LENGTH + 1234;
+
+struct ChildGuard(Child);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn spawn_from_env(var: &str, substitutions: &[(&str, String)]) -> ChildGuard {
+    let template = std::env::var(var).unwrap_or_else(|_| panic!("{} is not set", var));
+    let mut command_line = template;
+    for (key, value) in substitutions {
+        command_line = command_line.replace(key, value);
+    }
+
+    let mut parts = command_line.split_whitespace();
+    let program = parts.next().expect("empty command");
+    let child = Command::new(program)
+        .args(parts)
+        .spawn()
+        .unwrap_or_else(|e| panic!("failed to spawn {}: {}", program, e));
+
+    ChildGuard(child)
+}
+
+fn generate_payload() -> Vec<u8> {
+    (0..PAYLOAD_LENGTH).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+fn write_torrent(dir: &Path, payload: &[u8]) -> PathBuf {
+    let name = "e2e-payload.bin".to_string();
+    std::fs::write(dir.join(&name), payload).unwrap();
+
+    let pieces = payload
+        .chunks(PIECE_LENGTH)
+        .map(|chunk| Sha1::digest(chunk).into())
+        .collect();
+
+    let torrent = TorrentMetaInfo::new(
+        format!("http://127.0.0.1:{}/announce", TRACKER_PORT),
+        Info {
+            name,
+            piece_length: PIECE_LENGTH,
+            pieces,
+            files_info: FilesInfo::SingleFile {
+                length: payload.len(),
+                md5sum: None,
+            },
+            extras: HashMap::new(),
+        },
+    );
+
+    let path = dir.join("e2e.torrent");
+    std::fs::write(&path, encode(&torrent.to_bencode_value())).unwrap();
+    path
+}
+
+fn send_message(stream: &mut TcpStream, id: u8, payload: &[u8]) {
+    let len = (payload.len() + 1) as u32;
+    stream.write_all(&len.to_be_bytes()).unwrap();
+    stream.write_all(&[id]).unwrap();
+    stream.write_all(payload).unwrap();
+}
+
+fn read_message(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+    let mut len_bytes = [0u8; 4];
+    stream.read_exact(&mut len_bytes).unwrap();
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    if len == 0 {
+        return None;
+    }
+
+    let mut buffer = vec![0u8; len];
+    stream.read_exact(&mut buffer).unwrap();
+    Some((buffer[0], buffer[1..].to_vec()))
+}
+
+fn wait_for_message(stream: &mut TcpStream, wanted: u8) -> Vec<u8> {
+    loop {
+        if let Some((id, payload)) = read_message(stream)
+            && id == wanted
+        {
+            return payload;
+        }
+    }
+}
+
+#[test]
+#[ignore]
+fn downloads_generated_torrent_from_local_seeder() {
+    let dir = std::env::temp_dir().join(format!("bt-e2e-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let payload = generate_payload();
+    let torrent_path = write_torrent(&dir, &payload);
+
+    let _tracker = spawn_from_env("BT_E2E_TRACKER", &[("{port}", TRACKER_PORT.to_string())]);
+    let _seeder = spawn_from_env(
+        "BT_E2E_SEEDER",
+        &[
+            ("{dir}", dir.display().to_string()),
+            ("{torrent}", torrent_path.display().to_string()),
+        ],
+    );
+
+    let torrent = parse_torrent_file(torrent_path.to_str().unwrap()).unwrap();
+    let info_hash = torrent.info_hash();
+    let peer_id = TrackerRequest::generate_peer_id();
+
+    let request = TrackerRequest {
+        announce_url: torrent.announce.clone(),
+        info_hash,
+        peer_id,
+        ip: None,
+        port: 6881,
+        uploaded: 0,
+        downloaded: 0,
+        left: torrent.total_size() as u64,
+        compact: false,
+        no_peer_id: false,
+        event: Some(Event::Started),
+        numwant: Some(10),
+        key: Some(TrackerRequest::generate_key()),
+        tracker_id: None,
+    };
+
+    // The seeder needs a moment to start up and announce itself.
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let seeder = loop {
+        if let Ok(response) = TrackerClient::query_tracker(&request)
+            && let Some(peer) = response.peers.into_iter().next()
+        {
+            break peer;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "seeder never showed up at the tracker"
+        );
+        thread::sleep(Duration::from_secs(1));
+    };
+
+    let timeouts = PeerTimeouts {
+        read: Duration::from_secs(10),
+        ..PeerTimeouts::default()
+    };
+    let mut stream = Handshake::connect_to_peer(&seeder, &timeouts).unwrap();
+    Handshake::perform_handshake(
+        &mut stream,
+        &info_hash,
+        &peer_id,
+        ReservedBits::FAST | ReservedBits::V2,
+        timeouts.handshake,
+    )
+    .unwrap();
+
+    // Interested, then wait for Unchoke.
+    send_message(&mut stream, 2, &[]);
+    wait_for_message(&mut stream, 1);
+
+    let mut downloaded = Vec::with_capacity(payload.len());
+    for (index, expected_hash) in torrent.info.pieces.iter().enumerate() {
+        let begin = index * PIECE_LENGTH;
+        let length = PIECE_LENGTH.min(payload.len() - begin);
+
+        let mut request = Vec::with_capacity(12);
+        request.extend_from_slice(&(index as u32).to_be_bytes());
+        request.extend_from_slice(&0u32.to_be_bytes());
+        request.extend_from_slice(&(length as u32).to_be_bytes());
+        send_message(&mut stream, 6, &request);
+
+        let piece = wait_for_message(&mut stream, 7);
+        let block = &piece[8..];
+        let hash: [u8; 20] = Sha1::digest(block).into();
+        assert_eq!(&hash, expected_hash, "piece {} failed verification", index);
+
+        downloaded.extend_from_slice(block);
+    }
+
+    assert_eq!(downloaded, payload);
+    let _ = std::fs::remove_dir_all(&dir);
+}
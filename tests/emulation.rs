@@ -0,0 +1,131 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bittorrent_client::peer::extension::ExtendedHandshake;
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::emulation::ClientPreset;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+const PIECE: usize = 16384;
+
+fn seeded_torrent(label: &str) -> (TorrentMetaInfo, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("bt-emulation-{}-{}", label, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let data: Vec<u8> = (0..8 * PIECE).map(|i| (i / 11) as u8).collect();
+    std::fs::write(dir.join(label), &data).unwrap();
+    let meta = common::single_file(label, PIECE, &data);
+    (meta, dir)
+}
+
+fn session(client_preset: ClientPreset) -> Session {
+    Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        client_preset,
+        ..SessionConfig::default()
+    })
+    .unwrap()
+}
+
+// Connects to the session as a leecher and returns the first message it sends.
+fn opening_message(session: &Session, info_hash: [u8; 20]) -> PeerMessage {
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    Handshake::perform_handshake(
+        &mut stream,
+        &info_hash,
+        b"-FAKE0-leechleechlee",
+        ReservedBits::FAST | ReservedBits::V2,
+        Duration::from_secs(10),
+    )
+    .unwrap();
+    PeerMessage::read_peer_message(&mut stream).unwrap()
+}
+
+#[test]
+fn presets_shape_the_peer_id_and_extended_handshake() {
+    let transmission = session(ClientPreset::Transmission);
+    let peer_id = transmission.peer_id();
+    assert_eq!(&peer_id[..8], b"-TR4050-");
+    assert!(
+        peer_id[8..]
+            .iter()
+            .all(|b| b.is_ascii_digit() || b.is_ascii_lowercase())
+    );
+
+    let encoded = transmission.extended_handshake().to_bencode();
+    let decoded = ExtendedHandshake::from_bencode(&encoded).unwrap();
+    assert_eq!(decoded.client.as_deref(), Some("Transmission 4.0.5"));
+    assert_eq!(decoded.reqq, Some(512));
+
+    let utorrent = session(ClientPreset::UTorrent);
+    assert_eq!(&utorrent.peer_id()[..8], b"-UT355S-");
+    assert_eq!(utorrent.extended_handshake().reqq, Some(255));
+
+    let native = session(ClientPreset::Native);
+    assert_eq!(&native.peer_id()[..8], b"-RS0001-");
+    assert_eq!(native.extended_handshake().reqq, None);
+}
+
+#[test]
+fn lazy_bitfields_follow_the_preset() {
+    let utorrent = session(ClientPreset::UTorrent);
+    let (meta, dir) = seeded_torrent("utorrent");
+    let torrent = utorrent.add_torrent(meta, &dir).unwrap();
+    assert!(torrent.is_complete());
+    let PeerMessage::Bitfield(bytes) = opening_message(&utorrent, torrent.info_hash()) else {
+        panic!("expected a bitfield");
+    };
+    assert_eq!(bytes[0].count_ones(), 4);
+
+    let transmission = session(ClientPreset::Transmission);
+    let (meta, dir) = seeded_torrent("transmission");
+    let torrent = transmission.add_torrent(meta, &dir).unwrap();
+    assert_eq!(
+        opening_message(&transmission, torrent.info_hash()),
+        PeerMessage::HaveAll
+    );
+}
+
+#[test]
+fn active_connections_get_keep_alives() {
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        keep_alive_interval: Duration::from_millis(200),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let (meta, dir) = seeded_torrent("keep-alive");
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    Handshake::perform_handshake(
+        &mut stream,
+        &torrent.info_hash(),
+        b"-FAKE0-leechleechlee",
+        ReservedBits::default(),
+        Duration::from_secs(5),
+    )
+    .unwrap();
+    // The connection gets an active slot straight away, so the keep-alive has to come
+    // from the active loop while we stay quiet.
+    loop {
+        match PeerMessage::read_peer_message(&mut stream).unwrap() {
+            PeerMessage::KeepAlive => break,
+            PeerMessage::Bitfield(_) | PeerMessage::Have { .. } => {}
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::piece::bitfield::Bitfield;
+use bittorrent_client::piece::picker::PiecePicker;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::torrent::Torrent;
+
+#[test]
+fn endgame_waits_until_everything_is_claimed() {
+    let mut picker = PiecePicker::new(3);
+    let mut rare = Bitfield::new(3);
+    rare.set(1);
+    picker.add_peer_bitfield(&Bitfield::full(3));
+    picker.add_peer_bitfield(&rare);
+    let first = SocketAddr::from((Ipv4Addr::LOCALHOST, 1));
+    let second = SocketAddr::from((Ipv4Addr::LOCALHOST, 2));
+    let all = Bitfield::full(3);
+
+    assert_eq!(picker.pick(first, &all), Some(0));
+    assert_eq!(picker.pick_endgame(second, &all), None);
+    assert_eq!(picker.pick(first, &all), Some(2));
+    assert_eq!(picker.pick(first, &all), Some(1));
+    assert_eq!(picker.pick(second, &all), None);
+    // The least available of the claimed pieces first; pieces we have are out.
+    picker.on_piece_done(0);
+    assert_eq!(picker.pick_endgame(second, &all), Some(2));
+    picker.avoid(2, [second]);
+    assert_eq!(picker.pick_endgame(second, &all), Some(1));
+    assert_eq!(picker.pick_endgame(second, &rare), Some(1));
+}
+
+// Connects a fake seed to the session and returns it once it has been asked for blocks.
+fn seed(session: &Session, torrent: &Torrent, peer_id: &[u8; 20]) -> (TcpStream, Vec<u32>) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    session
+        .add_peer(&torrent.info_hash(), listener.local_addr().unwrap())
+        .unwrap();
+    let (mut stream, _) = listener.accept().unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut handshake = [0u8; 68];
+    stream.read_exact(&mut handshake).unwrap();
+    let ours = Handshake::new(torrent.info_hash(), *peer_id, ReservedBits::FAST);
+    stream.write_all(&ours.to_bytes()).unwrap();
+    PeerMessage::HaveAll
+        .write_peer_message(&mut stream)
+        .unwrap();
+    PeerMessage::Unchoke
+        .write_peer_message(&mut stream)
+        .unwrap();
+
+    let mut requested = Vec::new();
+    while requested.len() < 3 {
+        if let PeerMessage::Request { index, begin, .. } =
+            PeerMessage::read_peer_message(&mut stream).unwrap()
+        {
+            assert_eq!(index, 0);
+            requested.push(begin);
+        }
+    }
+    (stream, requested)
+}
+
+#[test]
+fn slower_peer_gets_cancels_once_the_piece_is_in() {
+    let data: Vec<u8> = (0..40_000).map(|i| (i * 11) as u8).collect();
+    let meta = common::single_file("endgame", data.len(), &data);
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-endgame-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    // The slow seed claims the only piece and never answers; the fast one gets it anyway.
+    let (mut slow, slow_requests) = seed(&session, &torrent, b"-FAKE0-slowslowslows");
+    let (mut fast, fast_requests) = seed(&session, &torrent, b"-FAKE0-fastfastfastf");
+    for begin in fast_requests {
+        let start = begin as usize;
+        let end = (start + 16 * 1024).min(data.len());
+        PeerMessage::Piece {
+            index: 0,
+            begin,
+            block: data[start..end].to_vec().into(),
+        }
+        .write_peer_message(&mut fast)
+        .unwrap();
+    }
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !torrent.is_complete() {
+        assert!(Instant::now() < deadline, "download didn't finish");
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    // The slow seed's task notices as soon as it hears from it.
+    PeerMessage::KeepAlive
+        .write_peer_message(&mut slow)
+        .unwrap();
+    let mut cancelled = HashSet::new();
+    while cancelled.len() < slow_requests.len() {
+        if let PeerMessage::Cancel { index, begin, .. } =
+            PeerMessage::read_peer_message(&mut slow).unwrap()
+        {
+            assert_eq!(index, 0);
+            cancelled.insert(begin);
+        }
+    }
+    assert_eq!(cancelled, slow_requests.into_iter().collect());
+    assert_eq!(torrent.cancelled_requests(), 3);
+}
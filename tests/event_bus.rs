@@ -0,0 +1,125 @@
+use std::sync::Arc;
+use std::sync::mpsc::{RecvError, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use bittorrent_client::session::event::{EventBus, OverflowPolicy, SessionEvent};
+use bittorrent_client::units::ByteSize;
+
+fn added(n: u8) -> SessionEvent {
+    SessionEvent::TorrentAdded { info_hash: [n; 20] }
+}
+
+fn progress(n: u8, downloaded: u64) -> SessionEvent {
+    SessionEvent::Progress {
+        info_hash: [n; 20],
+        downloaded: ByteSize(downloaded),
+        uploaded: ByteSize(0),
+        progress: 0.5,
+    }
+}
+
+#[test]
+fn full_queues_lose_their_oldest_events() {
+    let bus = EventBus::new(2, OverflowPolicy::DropOldest);
+    let events = bus.subscribe();
+    for n in 1..=3 {
+        bus.emit(added(n));
+    }
+    assert_eq!(
+        events.try_iter().collect::<Vec<_>>(),
+        vec![added(2), added(3)]
+    );
+    assert_eq!(events.dropped(), 1);
+}
+
+#[test]
+fn progress_events_coalesce_per_torrent() {
+    let bus = EventBus::new(3, OverflowPolicy::Coalesce);
+    let events = bus.subscribe();
+    bus.emit(progress(1, 100));
+    bus.emit(added(2));
+    bus.emit(progress(1, 200));
+    bus.emit(progress(2, 50));
+    bus.emit(progress(1, 300));
+    assert_eq!(
+        events.try_iter().collect::<Vec<_>>(),
+        vec![progress(1, 300), added(2), progress(2, 50)]
+    );
+    assert_eq!(events.dropped(), 0);
+
+    // Once full, anything else still pushes out the oldest.
+    for n in 3..=6 {
+        bus.emit(added(n));
+    }
+    assert_eq!(events.try_iter().count(), 3);
+    assert_eq!(events.dropped(), 1);
+}
+
+#[test]
+fn blocking_policy_waits_for_the_subscriber() {
+    let bus = Arc::new(EventBus::new(1, OverflowPolicy::Block));
+    let events = bus.subscribe();
+    bus.emit(added(1));
+
+    let producer = {
+        let bus = bus.clone();
+        thread::spawn(move || bus.emit(added(2)))
+    };
+    thread::sleep(Duration::from_millis(100));
+    assert!(!producer.is_finished());
+
+    assert_eq!(events.recv().unwrap(), added(1));
+    producer.join().unwrap();
+    assert_eq!(events.recv().unwrap(), added(2));
+    assert_eq!(events.dropped(), 0);
+
+    // A subscriber that goes away doesn't leave producers stuck.
+    bus.emit(added(3));
+    drop(events);
+    bus.emit(added(4));
+}
+
+#[test]
+fn blocked_emit_leaves_the_bus_usable() {
+    let bus = Arc::new(EventBus::new(1, OverflowPolicy::Block));
+    let events = bus.subscribe();
+    bus.emit(added(1));
+    let producer = {
+        let bus = bus.clone();
+        thread::spawn(move || bus.emit(added(2)))
+    };
+    thread::sleep(Duration::from_millis(50));
+
+    // Subscribing doesn't wait for the stuck producer.
+    let (done, subscribed) = std::sync::mpsc::channel();
+    {
+        let bus = bus.clone();
+        thread::spawn(move || done.send(bus.subscribe()).unwrap());
+    }
+    let late = subscribed.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(!producer.is_finished());
+
+    assert_eq!(events.recv().unwrap(), added(1));
+    producer.join().unwrap();
+    assert_eq!(events.recv().unwrap(), added(2));
+    bus.emit(added(3));
+    assert_eq!(late.recv().unwrap(), added(3));
+}
+
+#[test]
+fn receivers_drain_before_reporting_the_bus_gone() {
+    let bus = EventBus::new(8, OverflowPolicy::DropOldest);
+    let events = bus.subscribe();
+    bus.emit(added(1));
+    assert_eq!(events.recv_timeout(Duration::from_millis(10)), Ok(added(1)));
+    assert_eq!(
+        events.recv_timeout(Duration::from_millis(10)),
+        Err(RecvTimeoutError::Timeout)
+    );
+
+    bus.emit(added(2));
+    drop(bus);
+    assert_eq!(events.recv(), Ok(added(2)));
+    assert_eq!(events.recv(), Err(RecvError));
+}
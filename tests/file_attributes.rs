@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::bencode::value::BencodeValue;
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::torrent::parser::torrent_from_bencode;
+use bittorrent_client::torrent::value::{File, FileAttributes, FilesInfo, Info, TorrentMetaInfo};
+use sha1::{Digest, Sha1};
+
+fn file(path: &[&str], length: usize, attr: &str) -> File {
+    let mut extras = HashMap::new();
+    if !attr.is_empty() {
+        extras.insert("attr".to_string(), BencodeValue::String(attr.to_string()));
+    }
+    File {
+        length,
+        path: path.iter().map(|part| part.to_string()).collect(),
+        md5sum: None,
+        extras,
+    }
+}
+
+fn link(path: &[&str], target: &[&str]) -> File {
+    let mut link = file(path, 0, "l");
+    let target = target
+        .iter()
+        .map(|part| BencodeValue::String(part.to_string()))
+        .collect();
+    link.extras
+        .insert("symlink path".to_string(), BencodeValue::List(target));
+    link
+}
+
+#[test]
+fn attr_letters_and_symlink_paths_are_parsed() {
+    assert_eq!(
+        FileAttributes::parse(b"xh"),
+        FileAttributes {
+            padding: false,
+            symlink: false,
+            executable: true,
+            hidden: true,
+        }
+    );
+    assert_eq!(FileAttributes::parse(b"?"), FileAttributes::default());
+
+    let bytes = b"d8:announce3:url4:infod5:filesld4:attr1:l6:lengthi0e4:pathl6:lateste12:symlink pathl3:v1.5:a.bineed4:attr1:x6:lengthi3e4:pathl3:v1.5:a.bineee4:name3:dir12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee";
+    let (value, _) = parse_value(bytes).unwrap();
+    let meta = torrent_from_bencode(&value).unwrap();
+    let FilesInfo::MultiFile { files } = &meta.info.files_info else {
+        panic!("expected a multi-file torrent");
+    };
+    assert!(files[0].attributes().symlink);
+    assert_eq!(
+        files[0].symlink_path(),
+        Some(vec!["v1.".to_string(), "a.bin".to_string()])
+    );
+    assert!(files[1].attributes().executable);
+    assert_eq!(files[1].symlink_path(), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn downloads_get_executables_and_symlinks() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = b"#!/bin/sh\necho hello\n".to_vec();
+    let notes = b"release notes".to_vec();
+    let data = [script.as_slice(), notes.as_slice()].concat();
+    let meta = TorrentMetaInfo::new(
+        "http://127.0.0.1:1/announce".to_string(),
+        Info {
+            name: "distro".to_string(),
+            piece_length: 16384,
+            pieces: vec![Sha1::digest(&data).into()],
+            files_info: FilesInfo::MultiFile {
+                files: vec![
+                    file(&["bin", "run.sh"], script.len(), "x"),
+                    file(&["notes.txt"], notes.len(), ""),
+                    link(&["usr", "local", "run"], &["bin", "run.sh"]),
+                    link(&["escape"], &["..", "elsewhere"]),
+                ],
+            },
+            extras: HashMap::new(),
+        },
+    );
+
+    let dir = std::env::temp_dir().join(format!("bt-attributes-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+    assert!(torrent.store_piece(0, &data).unwrap());
+    assert!(torrent.is_complete());
+
+    let root = dir.join("distro");
+    let mode = std::fs::metadata(root.join("bin/run.sh"))
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o100, 0o100, "{:o}", mode);
+    let mode = std::fs::metadata(root.join("notes.txt"))
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o111, 0, "{:o}", mode);
+
+    let run = root.join("usr/local/run");
+    assert_eq!(
+        std::fs::read_link(&run).unwrap(),
+        std::path::Path::new("../../bin/run.sh")
+    );
+    assert_eq!(std::fs::read(&run).unwrap(), script);
+    assert!(std::fs::symlink_metadata(root.join("escape")).is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
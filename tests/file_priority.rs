@@ -0,0 +1,176 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::piece::bitfield::Bitfield;
+use bittorrent_client::piece::hash::Sha1Mode;
+use bittorrent_client::piece::picker::PiecePicker;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::priority::{FilePriority, wanted_pieces};
+use bittorrent_client::session::torrent::Torrent;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+const BLOCK: usize = 16 * 1024;
+const PIECE: usize = 3 * BLOCK;
+
+fn content() -> Vec<u8> {
+    (0..3 * PIECE).map(|i| (i * 13 / 5) as u8).collect()
+}
+
+// Three files of one piece each.
+fn meta(name: &str, data: &[u8]) -> TorrentMetaInfo {
+    let files = ["a.bin", "b.bin", "c.bin"]
+        .iter()
+        .map(|path| common::file(&[path], PIECE))
+        .collect();
+    common::multi_file(name, PIECE, files, data)
+}
+
+// Reads the next `count` Request and Cancel messages, skipping everything else.
+fn requests_and_cancels(stream: &mut TcpStream, count: usize) -> Vec<PeerMessage> {
+    let mut messages = Vec::new();
+    while messages.len() < count {
+        let message = PeerMessage::read_peer_message(stream).unwrap();
+        if matches!(
+            message,
+            PeerMessage::Request { .. } | PeerMessage::Cancel { .. }
+        ) {
+            messages.push(message);
+        }
+    }
+    messages
+}
+
+#[test]
+fn skipped_files_leave_the_picker_and_the_disk() {
+    let data = content();
+    let dir = common::temp_dir("priority-skip");
+    let torrent = Torrent::new(meta("skip", &data), &dir, Sha1Mode::Fast);
+    assert!(torrent.store_piece(0, &data[..PIECE]).unwrap());
+    std::fs::write(dir.join("skip").join("b.bin"), b"partial").unwrap();
+    assert_eq!(torrent.left().as_u64(), 2 * PIECE as u64);
+
+    torrent.set_file_priority(1, FilePriority::Skip).unwrap();
+    torrent.set_file_priority(0, FilePriority::Skip).unwrap();
+    assert_eq!(
+        torrent.file_priorities(),
+        vec![FilePriority::Skip, FilePriority::Skip, FilePriority::Normal]
+    );
+    assert!(!torrent.is_wanted(1));
+    assert_eq!(torrent.left().as_u64(), PIECE as u64);
+    // Nothing of b.bin was complete, so it's gone; a.bin holds a finished piece and stays.
+    assert!(!dir.join("skip").join("b.bin").exists());
+    assert!(dir.join("skip").join("a.bin").exists());
+
+    let peer = SocketAddr::from((Ipv4Addr::LOCALHOST, 6881));
+    let all = Bitfield::full(3);
+    let mut picker = PiecePicker::new(3);
+    picker.set_wanted(wanted_pieces(torrent.storage(), &torrent.file_priorities()));
+    assert_eq!(picker.pick(peer, &all), Some(2));
+    assert_eq!(picker.pick(peer, &all), None);
+
+    torrent.set_file_priority(1, FilePriority::Normal).unwrap();
+    assert!(torrent.is_wanted(1));
+    picker.set_wanted(wanted_pieces(torrent.storage(), &torrent.file_priorities()));
+    assert_eq!(picker.pick(peer, &all), Some(1));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn skipping_a_file_cancels_its_requests() {
+    let data = content();
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = common::temp_dir("priority-cancel");
+    let torrent = session.add_torrent(meta("cancel", &data), &dir).unwrap();
+    torrent.set_file_priority(2, FilePriority::Skip).unwrap();
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    session
+        .add_peer(&torrent.info_hash(), listener.local_addr().unwrap())
+        .unwrap();
+    let (mut stream, _) = listener.accept().unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut handshake = [0u8; 68];
+    std::io::Read::read_exact(&mut stream, &mut handshake).unwrap();
+    std::io::Write::write_all(
+        &mut stream,
+        &Handshake::new(
+            torrent.info_hash(),
+            *b"-FAKE0-seedseedseeds",
+            ReservedBits::FAST | ReservedBits::V2,
+        )
+        .to_bytes(),
+    )
+    .unwrap();
+    PeerMessage::HaveAll
+        .write_peer_message(&mut stream)
+        .unwrap();
+    PeerMessage::Unchoke
+        .write_peer_message(&mut stream)
+        .unwrap();
+
+    let requested = requests_and_cancels(&mut stream, 3);
+    let expected: Vec<_> = [0, 16384, 32768]
+        .into_iter()
+        .map(|begin| PeerMessage::Request {
+            index: 0,
+            begin,
+            length: BLOCK as u32,
+        })
+        .collect();
+    assert_eq!(requested, expected);
+
+    torrent.set_file_priority(0, FilePriority::Skip).unwrap();
+    PeerMessage::Piece {
+        index: 0,
+        begin: 0,
+        block: data[..BLOCK].to_vec().into(),
+    }
+    .write_peer_message(&mut stream)
+    .unwrap();
+
+    // The skip may land before or after block 0 is in, so it may be cancelled as well.
+    let mut messages = Vec::new();
+    while messages
+        .iter()
+        .filter(|message| matches!(message, PeerMessage::Request { index: 1, .. }))
+        .count()
+        < 3
+    {
+        messages.extend(requests_and_cancels(&mut stream, 1));
+    }
+    for begin in [16384, 32768] {
+        assert!(messages.contains(&PeerMessage::Cancel {
+            index: 0,
+            begin,
+            length: BLOCK as u32,
+        }));
+    }
+    for begin in [0, 16384, 32768] {
+        let start = PIECE + begin as usize;
+        PeerMessage::Piece {
+            index: 1,
+            begin,
+            block: data[start..start + BLOCK].to_vec().into(),
+        }
+        .write_peer_message(&mut stream)
+        .unwrap();
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while torrent.left().as_u64() != 0 {
+        assert!(Instant::now() < deadline, "piece 1 didn't arrive");
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert!(torrent.has_piece(1));
+    assert!(!torrent.has_piece(0));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
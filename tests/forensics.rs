@@ -0,0 +1,67 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use bittorrent_client::piece::hash::Sha1Mode;
+use bittorrent_client::session::torrent::Torrent;
+use sha1::{Digest, Sha1};
+
+const BLOCK: usize = 16 * 1024;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn failed_pieces_are_written_out_with_their_senders() {
+    let good: Vec<u8> = (0..2 * BLOCK).map(|i| (i * 3) as u8).collect();
+    let meta = common::single_file("forensics.bin", good.len(), &good);
+    let root = std::env::temp_dir().join(format!("bt-forensics-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let torrent = Torrent::new(meta, &root.join("data"), Sha1Mode::Fast);
+
+    let mut bad = good.clone();
+    bad[BLOCK + 1] ^= 0xff;
+    let sources = [
+        SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), 6881)),
+        SocketAddr::from((Ipv4Addr::new(10, 0, 0, 2), 6881)),
+    ];
+
+    // Off unless asked for.
+    assert!(!torrent.store_piece_from(0, &bad, &sources).unwrap());
+    assert!(!root.join("forensics").exists());
+
+    torrent.set_forensics_dir(Some(root.join("forensics")));
+    assert!(!torrent.store_piece_from(0, &bad, &sources).unwrap());
+
+    let dumps: Vec<_> = std::fs::read_dir(root.join("forensics").join(hex(&torrent.info_hash())))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(dumps.len(), 1);
+    let dump = &dumps[0];
+    assert!(
+        dump.file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("piece-0-")
+    );
+    assert_eq!(
+        std::fs::read(dump.join("block-0.bin")).unwrap(),
+        bad[..BLOCK]
+    );
+    assert_eq!(
+        std::fs::read(dump.join("block-1.bin")).unwrap(),
+        bad[BLOCK..]
+    );
+
+    let manifest = std::fs::read_to_string(dump.join("manifest.txt")).unwrap();
+    assert!(manifest.contains(&format!("expected sha1: {}", hex(&Sha1::digest(&good)))));
+    assert!(manifest.contains(&format!("actual sha1: {}", hex(&Sha1::digest(&bad)))));
+    assert!(manifest.contains(&format!(
+        "16384 16384 {} 10.0.0.2:6881 unknown",
+        hex(&Sha1::digest(&bad[BLOCK..]))
+    )));
+
+    let _ = std::fs::remove_dir_all(&root);
+}
@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read};
+
+use bittorrent_client::peer::error::PeerMessageError;
+use bittorrent_client::peer::framing::MessageDecoder;
+use bittorrent_client::peer::value::PeerMessage;
+
+fn messages() -> Vec<PeerMessage> {
+    vec![
+        PeerMessage::Unchoke,
+        PeerMessage::KeepAlive,
+        PeerMessage::Have { piece_index: 7 },
+        PeerMessage::Piece {
+            index: 1,
+            begin: 16384,
+            block: vec![5; 3000].into(),
+        },
+    ]
+}
+
+fn wire() -> Vec<u8> {
+    messages().iter().flat_map(PeerMessage::to_bytes).collect()
+}
+
+// Hands out the chunks it was given, one per read, with a WouldBlock in between.
+struct Trickle {
+    chunks: VecDeque<Vec<u8>>,
+    ready: bool,
+}
+
+impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.ready {
+            self.ready = true;
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        self.ready = false;
+        let Some(chunk) = self.chunks.pop_front() else {
+            return Ok(0);
+        };
+        buf[..chunk.len()].copy_from_slice(&chunk);
+        Ok(chunk.len())
+    }
+}
+
+#[test]
+fn messages_fed_a_byte_at_a_time() {
+    let mut decoder = MessageDecoder::new();
+    let mut decoded = Vec::new();
+    for byte in wire() {
+        decoder.feed(&[byte]);
+        while let Some(message) = decoder.decode().unwrap() {
+            decoded.push(message);
+        }
+    }
+    assert_eq!(decoded, messages());
+    assert_eq!(decoder.buffered(), 0);
+}
+
+#[test]
+fn several_messages_in_one_read() {
+    let mut decoder = MessageDecoder::new();
+    decoder.feed(&wire());
+    let mut decoded = Vec::new();
+    while let Some(message) = decoder.decode().unwrap() {
+        decoded.push(message);
+    }
+    assert_eq!(decoded, messages());
+}
+
+#[test]
+fn non_blocking_reads_keep_partial_messages() {
+    let mut reader = Trickle {
+        chunks: wire().chunks(7).map(<[u8]>::to_vec).collect(),
+        ready: false,
+    };
+    let mut decoder = MessageDecoder::new();
+    let mut decoded = Vec::new();
+    let mut would_block = 0;
+    while decoded.len() < messages().len() {
+        match decoder.read_message(&mut reader).unwrap() {
+            Some(message) => decoded.push(message),
+            None => would_block += 1,
+        }
+    }
+    assert_eq!(decoded, messages());
+    assert!(would_block > 1);
+
+    // The reader is done; closing between messages is still an error to the caller.
+    let err = loop {
+        match decoder.read_message(&mut reader) {
+            Ok(None) => continue,
+            Ok(Some(message)) => panic!("unexpected {:?}", message),
+            Err(err) => break err,
+        }
+    };
+    assert!(matches!(err, PeerMessageError::IOError(e) if e.kind() == ErrorKind::UnexpectedEof));
+}
+
+#[test]
+fn oversized_prefix_is_refused_early() {
+    let mut decoder = MessageDecoder::new();
+    decoder.feed(&u32::MAX.to_be_bytes());
+    assert!(matches!(
+        decoder.decode(),
+        Err(PeerMessageError::MessageTooLong(u32::MAX))
+    ));
+}
+
+#[test]
+fn closing_mid_message_is_unexpected_eof() {
+    let bytes = PeerMessage::Have { piece_index: 1 }.to_bytes();
+    let mut decoder = MessageDecoder::new();
+    let err = decoder.read_message(&mut &bytes[..6]).unwrap_err();
+    assert!(matches!(err, PeerMessageError::IOError(e) if e.kind() == ErrorKind::UnexpectedEof));
+    assert_eq!(decoder.buffered(), 6);
+}
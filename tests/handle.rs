@@ -0,0 +1,98 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::error::SessionError;
+use bittorrent_client::session::handle::TorrentState;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+const PIECE: usize = 16384;
+
+// Two pieces; with `seeded` both are already on disk.
+fn torrent(label: &str, seeded: bool) -> (TorrentMetaInfo, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("bt-handle-{}-{}", label, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let data: Vec<u8> = (0..2 * PIECE).map(|i| (i / 3) as u8).collect();
+    if seeded {
+        std::fs::write(dir.join(label), &data).unwrap();
+    }
+    let meta = common::single_file(label, PIECE, &data);
+    (meta, dir)
+}
+
+#[test]
+fn handles_report_state_and_progress() {
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        max_active_downloads: Some(1),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let (seed, seed_dir) = torrent("seed", true);
+    let (first, first_dir) = torrent("first", false);
+    let (second, second_dir) = torrent("second", false);
+    let seed = session.add_torrent(seed, &seed_dir).unwrap();
+    let first = session.add_torrent(first, &first_dir).unwrap();
+    let second = session.add_torrent(second, &second_dir).unwrap();
+
+    let seed = session.handle(&seed.info_hash()).unwrap();
+    assert_eq!(seed.name(), "seed");
+    assert_eq!(seed.state(), TorrentState::Seeding);
+    assert_eq!(seed.progress(), 1.0);
+
+    let first = session.handle(&first.info_hash()).unwrap();
+    let second = session.handle(&second.info_hash()).unwrap();
+    assert_eq!(first.state(), TorrentState::Downloading);
+    assert_eq!(second.state(), TorrentState::Queued);
+    assert_eq!(second.queue_position(), Some(2));
+
+    std::fs::write(
+        first_dir.join("first"),
+        (0..PIECE).map(|i| (i / 3) as u8).collect::<Vec<_>>(),
+    )
+    .unwrap();
+    first.force_recheck();
+    assert_eq!(first.progress(), 0.5);
+
+    first.pause();
+    assert_eq!(first.state(), TorrentState::Paused);
+    first.resume();
+    assert_eq!(first.state(), TorrentState::Downloading);
+    assert_eq!(session.handles().len(), 3);
+
+    let info_hash = first.info_hash();
+    first.remove(false).unwrap();
+    assert!(session.torrent(&info_hash).is_none());
+
+    for dir in [seed_dir, first_dir, second_dir] {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
+
+#[test]
+fn handles_outlive_their_session() {
+    let session = Session::new(common::local_config()).unwrap();
+    let (meta, dir) = torrent("orphan", true);
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+    let handle = session.handle(&torrent.info_hash()).unwrap();
+    drop(session);
+
+    assert_eq!(handle.state(), TorrentState::Seeding);
+    assert!(matches!(
+        handle.add_peer(SocketAddr::from((Ipv4Addr::LOCALHOST, 6881))),
+        Err(SessionError::SessionClosed)
+    ));
+    assert!(handle.announce().is_err());
+    assert_eq!(handle.queue_position(), None);
+    assert!(matches!(
+        handle.remove(false),
+        Err(SessionError::SessionClosed)
+    ));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
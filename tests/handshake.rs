@@ -0,0 +1,122 @@
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bittorrent_client::peer::error::{HandshakeError, PeerHandshakeError};
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::transport::PeerStream;
+use bittorrent_client::peer::value::Handshake;
+
+// Plays back a handshake, for another torrent when we dial, and refuses any timeout
+// change after the first one of each kind.
+#[derive(Debug)]
+struct WrongTorrent {
+    reply: io::Cursor<Vec<u8>>,
+    timeout_changes: Mutex<(u32, u32)>,
+}
+
+impl Read for WrongTorrent {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reply.read(buf)
+    }
+}
+
+impl Write for WrongTorrent {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn allow_once(count: &mut u32) -> io::Result<()> {
+    *count += 1;
+    if *count > 1 {
+        return Err(io::Error::other("socket gone"));
+    }
+    Ok(())
+}
+
+impl PeerStream for WrongTorrent {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    fn set_read_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+        allow_once(&mut self.timeout_changes.lock().unwrap().0)
+    }
+
+    fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    fn set_write_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+        allow_once(&mut self.timeout_changes.lock().unwrap().1)
+    }
+
+    fn peek(&self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+#[test]
+fn failed_timeout_restore_keeps_the_handshake_error() {
+    let reply = Handshake::new([2; 20], *b"-FAKE0-otherother000", ReservedBits::empty());
+    let mut stream = WrongTorrent {
+        reply: io::Cursor::new(reply.to_bytes().to_vec()),
+        timeout_changes: Mutex::new((0, 0)),
+    };
+    let result = Handshake::perform_handshake(
+        &mut stream,
+        &[1; 20],
+        b"-RS0001-handshake000",
+        ReservedBits::empty(),
+        Duration::from_secs(1),
+    );
+    assert!(matches!(
+        result,
+        Err(PeerHandshakeError::HandshakeError(
+            HandshakeError::InfoHashMismatch
+        ))
+    ));
+    // The restore was still attempted.
+    assert_eq!(*stream.timeout_changes.lock().unwrap(), (2, 2));
+}
+
+#[test]
+fn accepted_handshake_survives_a_failed_timeout_restore() {
+    let request = Handshake::new([2; 20], *b"-FAKE0-otherother000", ReservedBits::empty());
+    let incoming = || WrongTorrent {
+        reply: io::Cursor::new(request.to_bytes().to_vec()),
+        timeout_changes: Mutex::new((0, 0)),
+    };
+
+    let mut stream = incoming();
+    let remote = Handshake::accept_handshake(
+        &mut stream,
+        |_| Some(*b"-RS0001-handshake000"),
+        ReservedBits::empty(),
+        Duration::from_secs(1),
+    )
+    .unwrap();
+    assert_eq!(remote.peer_id, *b"-FAKE0-otherother000");
+    assert_eq!(*stream.timeout_changes.lock().unwrap(), (2, 2));
+
+    // Turning the torrent down restores the timeouts just the same.
+    let mut stream = incoming();
+    let result = Handshake::accept_handshake(
+        &mut stream,
+        |_| None,
+        ReservedBits::empty(),
+        Duration::from_secs(1),
+    );
+    assert!(matches!(
+        result,
+        Err(PeerHandshakeError::HandshakeError(
+            HandshakeError::InfoHashMismatch
+        ))
+    ));
+    assert_eq!(*stream.timeout_changes.lock().unwrap(), (2, 2));
+}
@@ -0,0 +1,303 @@
+mod common;
+
+// BEP 52 piece layers fetched from peers with hash requests and checked against the
+// pieces root.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::bencode::options::ParserOptions;
+use bittorrent_client::bencode::value::BencodeValue;
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::piece::layers::{HashRun, PieceLayers};
+use bittorrent_client::piece::merkle::{block_hashes, proof, root, verify_proof};
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::torrent::parser::parse_torrent_bytes;
+use bittorrent_client::torrent::value::{ToBencode, TorrentMetaInfo, V2File};
+
+const PIECE_LENGTH: usize = 32 * 1024;
+
+// Five pieces and a bit: a tree 16 leaves wide, with a piece layer 8 wide.
+fn content() -> Vec<u8> {
+    (0..5 * PIECE_LENGTH + 1000)
+        .map(|i| (i as u8).wrapping_mul(13))
+        .collect()
+}
+
+fn meta(data: &[u8], with_layer: bool) -> TorrentMetaInfo {
+    let piece_layer: Vec<[u8; 32]> = data
+        .chunks(PIECE_LENGTH)
+        .map(|piece| root(&block_hashes(piece), 2))
+        .collect();
+    TorrentMetaInfo {
+        v2_files: vec![V2File {
+            offset: 0,
+            length: data.len(),
+            pieces_root: root(&block_hashes(data), 16),
+            piece_layer: if with_layer { piece_layer } else { Vec::new() },
+        }],
+        ..common::single_file("layers", PIECE_LENGTH, data)
+    }
+}
+
+#[test]
+fn proofs_check_runs_against_the_root() {
+    let leaves: Vec<[u8; 32]> = (0..8u8).map(|n| [n; 32]).collect();
+    let tree_root = root(&leaves, 8);
+    for length in [1, 2, 4, 8] {
+        for index in (0..8).step_by(length) {
+            let uncles = proof(&leaves, 8, [0; 32], index, length);
+            let run = &leaves[index..index + length];
+            assert!(verify_proof(&tree_root, 8, index, run, &uncles));
+            assert_eq!(uncles.len(), (8 / length).trailing_zeros() as usize);
+            if let Some((_, short)) = uncles.split_first() {
+                assert!(!verify_proof(&tree_root, 8, index, run, short));
+            }
+        }
+    }
+    let uncles = proof(&leaves, 8, [0; 32], 2, 2);
+    assert!(!verify_proof(
+        &tree_root,
+        8,
+        2,
+        &[[9; 32], leaves[3]],
+        &uncles
+    ));
+}
+
+#[test]
+fn missing_layers_are_filled_from_answers() {
+    let data = content();
+    let seed = PieceLayers::new(&meta(&data, true));
+    let mut layers = PieceLayers::new(&meta(&data, false));
+    let pieces_root = root(&block_hashes(&data), 16);
+    assert_eq!(seed.missing(), 0);
+    assert_eq!(layers.missing(), 1);
+    assert_eq!(layers.hash(&pieces_root, 0), None);
+
+    let run = layers.next_request().unwrap();
+    assert_eq!(
+        run,
+        HashRun {
+            pieces_root,
+            base_layer: 1,
+            index: 0,
+            length: 8,
+            proof_layers: 3,
+        }
+    );
+    assert_eq!(layers.next_request(), None);
+
+    let mut hashes = seed.answer(run).unwrap();
+    assert_eq!(hashes.len(), 8);
+    hashes[2][0] ^= 1;
+    assert!(!layers.add_hashes(run, &hashes));
+    assert_eq!(layers.missing(), 1);
+
+    let run = layers.next_request().unwrap();
+    assert!(layers.add_hashes(run, &seed.answer(run).unwrap()));
+    assert_eq!(layers.missing(), 0);
+    assert_eq!(
+        layers.hash(&pieces_root, 1),
+        Some(root(
+            &block_hashes(&data[PIECE_LENGTH..2 * PIECE_LENGTH]),
+            2
+        ))
+    );
+    // Nothing to answer for half a run.
+    assert_eq!(layers.answer(HashRun { index: 1, ..run }), None);
+}
+
+#[test]
+fn hash_messages_round_trip() {
+    let header = ([7; 32], 1, 4, 2, 3);
+    let messages = [
+        PeerMessage::HashRequest {
+            pieces_root: header.0,
+            base_layer: header.1,
+            index: header.2,
+            length: header.3,
+            proof_layers: header.4,
+        },
+        PeerMessage::Hashes {
+            pieces_root: header.0,
+            base_layer: header.1,
+            index: header.2,
+            length: header.3,
+            proof_layers: header.4,
+            hashes: vec![[1; 32], [2; 32], [3; 32]],
+        },
+        PeerMessage::HashReject {
+            pieces_root: header.0,
+            base_layer: header.1,
+            index: header.2,
+            length: header.3,
+            proof_layers: header.4,
+        },
+    ];
+    for message in messages {
+        let bytes = message.to_bytes();
+        let parsed = PeerMessage::read_peer_message(&mut &bytes[..]).unwrap();
+        assert_eq!(parsed, message);
+    }
+    assert!(PeerMessage::from_frame(22, &[0; 50]).is_err());
+}
+
+#[test]
+fn session_fetches_missing_layers_from_peers() {
+    let data = content();
+    let seed = PieceLayers::new(&meta(&data, true));
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-hash-layers-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let torrent = session.add_torrent(meta(&data, false), &dir).unwrap();
+    assert_eq!(torrent.missing_piece_layers(), 1);
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    session
+        .add_peer(&torrent.info_hash(), listener.local_addr().unwrap())
+        .unwrap();
+    let (mut stream, _) = listener.accept().unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut handshake = [0u8; 68];
+    stream.read_exact(&mut handshake).unwrap();
+    let ours = Handshake::new(
+        torrent.info_hash(),
+        *b"-FAKE0-seedseedseeds",
+        ReservedBits::FAST | ReservedBits::V2,
+    );
+    assert!(ours.supports_v2());
+    stream.write_all(&ours.to_bytes()).unwrap();
+
+    loop {
+        if let PeerMessage::HashRequest {
+            pieces_root,
+            base_layer,
+            index,
+            length,
+            proof_layers,
+        } = PeerMessage::read_peer_message(&mut stream).unwrap()
+        {
+            let run = HashRun {
+                pieces_root,
+                base_layer,
+                index,
+                length,
+                proof_layers,
+            };
+            PeerMessage::Hashes {
+                pieces_root,
+                base_layer,
+                index,
+                length,
+                proof_layers,
+                hashes: seed.answer(run).unwrap(),
+            }
+            .write_peer_message(&mut stream)
+            .unwrap();
+            break;
+        }
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while torrent.missing_piece_layers() > 0 {
+        assert!(Instant::now() < deadline, "layer wasn't taken");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+// The .torrent for content() as a hybrid file would have it, piece layers included.
+fn torrent_file(data: &[u8]) -> Vec<u8> {
+    let with_layer = meta(data, true);
+    let file = &with_layer.v2_files[0];
+
+    let mut node = HashMap::new();
+    node.insert(
+        "length".to_string(),
+        BencodeValue::Integer(data.len() as i64),
+    );
+    node.insert(
+        "pieces root".to_string(),
+        BencodeValue::Bytes(file.pieces_root.to_vec()),
+    );
+    let mut leaf = HashMap::new();
+    leaf.insert(String::new(), BencodeValue::Dictionary(node));
+    let mut tree = HashMap::new();
+    tree.insert("layers".to_string(), BencodeValue::Dictionary(leaf));
+
+    let mut value = meta(data, false).to_bencode_value();
+    let BencodeValue::Dictionary(top) = &mut value else {
+        unreachable!()
+    };
+    let Some(BencodeValue::Dictionary(info)) = top.get_mut("info") else {
+        unreachable!()
+    };
+    info.insert("file tree".to_string(), BencodeValue::Dictionary(tree));
+
+    let layer = file.piece_layer.as_flattened();
+    let mut encoded = value.encode();
+    encoded.pop();
+    encoded.extend_from_slice(b"12:piece layersd32:");
+    encoded.extend_from_slice(&file.pieces_root);
+    encoded.extend_from_slice(format!("{}:", layer.len()).as_bytes());
+    encoded.extend_from_slice(layer);
+    encoded.extend_from_slice(b"ee");
+    encoded
+}
+
+#[test]
+fn seeds_serve_layers_from_the_torrent_file() {
+    let data = content();
+    let dir = common::temp_dir("hash-layers-seed");
+    std::fs::write(dir.join("layers"), &data).unwrap();
+    let parsed = parse_torrent_bytes(&torrent_file(&data), ParserOptions::lenient()).unwrap();
+    let pieces_root = parsed.v2_files[0].pieces_root;
+
+    let session = Session::new(common::local_config()).unwrap();
+    let torrent = session.add_torrent(parsed, &dir).unwrap();
+    assert!(torrent.is_complete());
+    assert_eq!(torrent.missing_piece_layers(), 0);
+
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    Handshake::perform_handshake(
+        &mut stream,
+        &torrent.info_hash(),
+        b"-FAKE0-leechleechlee",
+        ReservedBits::FAST | ReservedBits::V2,
+        Duration::from_secs(10),
+    )
+    .unwrap();
+    PeerMessage::HashRequest {
+        pieces_root,
+        base_layer: 1,
+        index: 4,
+        length: 4,
+        proof_layers: 3,
+    }
+    .write_peer_message(&mut stream)
+    .unwrap();
+
+    loop {
+        match PeerMessage::read_peer_message(&mut stream).unwrap() {
+            PeerMessage::Hashes { index, hashes, .. } => {
+                assert_eq!(index, 4);
+                let (run, uncles) = hashes.split_at(4);
+                assert!(verify_proof(&pieces_root, 8, 4, run, uncles));
+                break;
+            }
+            PeerMessage::HashReject { .. } => panic!("the seed had no layer to answer from"),
+            _ => {}
+        }
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
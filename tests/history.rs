@@ -0,0 +1,54 @@
+mod common;
+
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::history::{StateChange, TORRENT_HISTORY};
+
+#[test]
+fn torrents_log_their_state_changes() {
+    let dir = std::env::temp_dir().join(format!("bt-history-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let data = vec![9u8; 16384];
+    let meta = common::single_file("history.bin", 16384, &data);
+
+    let session = Session::new(common::local_config()).unwrap();
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+    let handle = session.handle(&torrent.info_hash()).unwrap();
+
+    torrent.pause();
+    torrent.pause();
+    torrent.resume();
+    assert!(torrent.store_piece(0, &data).unwrap());
+    assert!(session.announce(&torrent, None).is_err());
+
+    let changes: Vec<StateChange> = handle
+        .history()
+        .into_iter()
+        .map(|entry| entry.change)
+        .collect();
+    assert_eq!(
+        changes[..5],
+        [
+            StateChange::Added,
+            StateChange::Checked { have: 0, pieces: 1 },
+            StateChange::Stopped,
+            StateChange::Started,
+            StateChange::Completed,
+        ]
+    );
+    assert!(matches!(
+        &changes[5],
+        StateChange::TrackerError { url, .. } if url == "http://127.0.0.1:1/announce"
+    ));
+    let history = handle.history();
+    assert!(history.windows(2).all(|pair| pair[0].at <= pair[1].at));
+
+    for _ in 0..TORRENT_HISTORY {
+        torrent.pause();
+        torrent.resume();
+    }
+    let history = torrent.history();
+    assert_eq!(history.len(), TORRENT_HISTORY);
+    assert_eq!(history.last().unwrap().change, StateChange::Started);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,123 @@
+mod common;
+
+// BEP 17 seed against a server that answers each request with the next scripted reply.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::webseed::error::WebSeedError;
+use bittorrent_client::webseed::http_seed::HttpSeed;
+
+// Answers with `replies` (status, body) in order and sends back each request target.
+fn serve(replies: Vec<(u16, Vec<u8>)>) -> (String, Receiver<String>) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let url = format!("http://{}/seed", listener.local_addr().unwrap());
+    let (targets, received) = mpsc::channel();
+
+    thread::spawn(move || {
+        for (status, body) in replies {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                if let Some(rest) = line.strip_prefix("GET ") {
+                    let _ = targets.send(rest.split(' ').next().unwrap().to_string());
+                }
+                line.clear();
+            }
+            write!(
+                stream,
+                "HTTP/1.1 {} Scripted\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    });
+
+    (url, received)
+}
+
+#[test]
+fn urls_name_the_piece_and_its_ranges() {
+    let seed = HttpSeed::new("http://seed.example/get?key=1").unwrap();
+    let info_hash = *b"\x12\x34abcdefghijklmnop~ ";
+
+    assert_eq!(
+        seed.build_url(&info_hash, 7, &[]),
+        "http://seed.example/get?key=1&info_hash=%124abcdefghijklmnop~%20&piece=7"
+    );
+    assert_eq!(
+        seed.build_url(&info_hash, 0, &[(0, 16383), (32768, 49151)]),
+        "http://seed.example/get?key=1&info_hash=%124abcdefghijklmnop~%20&piece=0&ranges=0-16383,32768-49151"
+    );
+}
+
+#[test]
+fn fetch_returns_the_body_or_the_reason_it_failed() {
+    let (url, targets) = serve(vec![
+        (200, b"block".to_vec()),
+        (503, b" 30\n".to_vec()),
+        (503, b"soon".to_vec()),
+        (200, b"short".to_vec()),
+        (404, Vec::new()),
+    ]);
+    let seed = HttpSeed::new(&url).unwrap();
+    let info_hash = [0xaa; 20];
+
+    assert_eq!(seed.fetch(&info_hash, 3, &[(0, 4)], 5).unwrap(), b"block");
+    assert_eq!(
+        targets.recv().unwrap(),
+        format!("/seed?info_hash={}&piece=3&ranges=0-4", "%AA".repeat(20))
+    );
+
+    // A busy seed says how long to wait; an unreadable delay falls back to a minute.
+    assert!(matches!(
+        seed.fetch(&info_hash, 3, &[], 5),
+        Err(WebSeedError::RetryAfter(delay)) if delay == Duration::from_secs(30)
+    ));
+    assert!(matches!(
+        seed.fetch(&info_hash, 3, &[], 5),
+        Err(WebSeedError::RetryAfter(delay)) if delay == Duration::from_secs(60)
+    ));
+    assert!(matches!(
+        seed.fetch(&info_hash, 3, &[], 16384),
+        Err(WebSeedError::LengthMismatch {
+            expected: 16384,
+            found: 5
+        })
+    ));
+    assert!(matches!(
+        seed.fetch(&info_hash, 3, &[], 5),
+        Err(WebSeedError::HttpStatus(404))
+    ));
+}
+
+#[test]
+fn downloaded_pieces_are_verified_before_they_are_stored() {
+    let data: Vec<u8> = (0..20_000).map(|i| (i % 251) as u8).collect();
+    let (url, _) = serve(vec![
+        (200, vec![0; 16384]),
+        (200, data[..16384].to_vec()),
+        (200, data[16384..].to_vec()),
+    ]);
+    let dir = common::temp_dir("http-seed");
+    let session = Session::new(common::local_config()).unwrap();
+    let torrent = session
+        .add_torrent(common::single_file("seeded.bin", 16384, &data), &dir)
+        .unwrap();
+    let seed = HttpSeed::new(&url).unwrap();
+
+    assert!(!seed.download_piece(&torrent, 0).unwrap());
+    assert_eq!(torrent.web_seed_downloaded().0, 0);
+    assert!(seed.download_piece(&torrent, 0).unwrap());
+    assert!(seed.download_piece(&torrent, 1).unwrap());
+    assert!(torrent.is_complete());
+    assert_eq!(torrent.web_seed_downloaded().0, data.len() as u64);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,210 @@
+mod common;
+
+// Hybrid v1/v2 torrents: pieces with a v2 tree are checked against it, the rest fall
+// back to SHA-1, and each piece remembers which one it passed.
+
+use std::collections::HashMap;
+
+use bittorrent_client::bencode::options::ParserOptions;
+use bittorrent_client::bencode::value::BencodeValue;
+use bittorrent_client::piece::hash::{HashVersion, Sha1Mode};
+use bittorrent_client::piece::merkle::{block_hashes, root};
+use bittorrent_client::session::torrent::Torrent;
+use bittorrent_client::torrent::parser::{parse_torrent_bytes, torrent_from_bencode};
+use bittorrent_client::torrent::value::{ToBencode, TorrentMetaInfo, V2File};
+
+const PIECE_LENGTH: usize = 32 * 1024;
+
+fn bytes(len: usize, seed: u8) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i as u8).wrapping_mul(31) ^ seed)
+        .collect()
+}
+
+fn tree_leaf(length: usize, root: [u8; 32]) -> BencodeValue {
+    let mut node = HashMap::new();
+    node.insert("length".to_string(), BencodeValue::Integer(length as i64));
+    node.insert(
+        "pieces root".to_string(),
+        BencodeValue::Bytes(root.to_vec()),
+    );
+    let mut entry = HashMap::new();
+    entry.insert(String::new(), BencodeValue::Dictionary(node));
+    BencodeValue::Dictionary(entry)
+}
+
+fn with_file_tree(meta: &TorrentMetaInfo, tree: HashMap<String, BencodeValue>) -> BencodeValue {
+    let mut value = meta.to_bencode_value();
+    let BencodeValue::Dictionary(top) = &mut value else {
+        unreachable!()
+    };
+    let Some(BencodeValue::Dictionary(info)) = top.get_mut("info") else {
+        unreachable!()
+    };
+    info.insert("file tree".to_string(), BencodeValue::Dictionary(tree));
+    value
+}
+
+// a (one piece, padded), d (two and a bit pieces, padded), c (v1 only).
+fn hybrid(dir: &std::path::Path) -> (TorrentMetaInfo, Vec<u8>, Vec<u8>) {
+    let a = bytes(20_000, 1);
+    let d = bytes(40_000, 2);
+    let c = bytes(100, 3);
+    let files = vec![
+        common::file(&["a.bin"], a.len()),
+        common::file(&[".pad", "12768"], 12_768),
+        common::file(&["d.bin"], d.len()),
+        common::file(&[".pad", "25536"], 25_536),
+        common::file(&["c.bin"], c.len()),
+    ];
+    let payload = [
+        a.clone(),
+        vec![0; 12_768],
+        d.clone(),
+        vec![0; 25_536],
+        c.clone(),
+    ]
+    .concat();
+
+    let root_dir = dir.join("hybrid");
+    std::fs::create_dir_all(root_dir.join(".pad")).unwrap();
+    std::fs::write(root_dir.join("a.bin"), &a).unwrap();
+    std::fs::write(root_dir.join(".pad/12768"), vec![0; 12_768]).unwrap();
+    std::fs::write(root_dir.join("d.bin"), &d).unwrap();
+    std::fs::write(root_dir.join(".pad/25536"), vec![0; 25_536]).unwrap();
+    std::fs::write(root_dir.join("c.bin"), &c).unwrap();
+
+    let meta = TorrentMetaInfo {
+        v2_files: vec![
+            V2File {
+                offset: 0,
+                length: a.len(),
+                pieces_root: root(&block_hashes(&a), 2),
+                piece_layer: Vec::new(),
+            },
+            V2File {
+                offset: PIECE_LENGTH,
+                length: d.len(),
+                pieces_root: [0; 32],
+                piece_layer: d
+                    .chunks(PIECE_LENGTH)
+                    .map(|piece| root(&block_hashes(piece), 2))
+                    .collect(),
+            },
+        ],
+        ..common::multi_file("hybrid", PIECE_LENGTH, files, &payload)
+    };
+    (meta, a, d)
+}
+
+#[test]
+fn pieces_record_which_hash_verified_them() {
+    let dir = common::temp_dir("hybrid-paths");
+    let (meta, _, _) = hybrid(&dir);
+    assert_eq!(meta.num_pieces(), 4);
+    assert_eq!(meta.v2_piece(2).unwrap().length, 40_000 - PIECE_LENGTH);
+    assert!(meta.v2_piece(3).is_none());
+
+    let torrent = Torrent::new(meta, &dir, Sha1Mode::Fast);
+    torrent.check_files();
+
+    assert!(torrent.is_complete());
+    assert_eq!(torrent.verified_with(0), Some(HashVersion::V2));
+    assert_eq!(torrent.verified_with(1), Some(HashVersion::V2));
+    assert_eq!(torrent.verified_with(2), Some(HashVersion::V2));
+    assert_eq!(torrent.verified_with(3), Some(HashVersion::V1));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn v2_mismatch_is_not_rescued_by_v1() {
+    let dir = common::temp_dir("hybrid-mismatch");
+    let (mut meta, _, _) = hybrid(&dir);
+    meta.v2_files[0].pieces_root = [0xaa; 32];
+
+    let torrent = Torrent::new(meta, &dir, Sha1Mode::Fast);
+    torrent.check_files();
+
+    // The SHA-1 of piece 0 still matches, but its v2 root doesn't.
+    assert!(!torrent.has_piece(0));
+    assert_eq!(torrent.verified_with(0), None);
+    assert!(torrent.has_piece(1));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn file_tree_roots_are_parsed_at_v1_offsets() {
+    let dir = common::temp_dir("hybrid-parse");
+    let (meta, a, _) = hybrid(&dir);
+    let a_root = root(&block_hashes(&a), 2);
+
+    let mut tree = HashMap::new();
+    tree.insert("a.bin".to_string(), tree_leaf(a.len(), a_root));
+    // Wrong length: not matched up with the v1 file.
+    tree.insert("d.bin".to_string(), tree_leaf(1, [7; 32]));
+
+    let parsed = torrent_from_bencode(&with_file_tree(&meta, tree)).unwrap();
+    assert_eq!(parsed.v2_files.len(), 1);
+    assert_eq!(parsed.v2_files[0].offset, 0);
+    assert_eq!(parsed.v2_files[0].pieces_root, a_root);
+    assert_eq!(parsed.v2_piece(0).unwrap().leaves, 2);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// The bytes of a hybrid .torrent with both files in its file tree, and with "piece layers"
+// carrying `d_layer` for d.bin if one is given.
+fn hybrid_bytes(meta: &TorrentMetaInfo, a: &[u8], d: &[u8], d_layer: Option<&[u8]>) -> Vec<u8> {
+    let d_root = root(&block_hashes(d), 4);
+    let mut tree = HashMap::new();
+    tree.insert(
+        "a.bin".to_string(),
+        tree_leaf(a.len(), root(&block_hashes(a), 2)),
+    );
+    tree.insert("d.bin".to_string(), tree_leaf(d.len(), d_root));
+
+    let mut encoded = with_file_tree(meta, tree).encode();
+    let Some(d_layer) = d_layer else {
+        return encoded;
+    };
+    assert_eq!(encoded.pop(), Some(b'e'));
+    encoded.extend_from_slice(b"12:piece layersd32:");
+    encoded.extend_from_slice(&d_root);
+    encoded.extend_from_slice(format!("{}:", d_layer.len()).as_bytes());
+    encoded.extend_from_slice(d_layer);
+    encoded.extend_from_slice(b"ee");
+    encoded
+}
+
+#[test]
+fn piece_layers_are_read_from_torrent_files() {
+    let dir = common::temp_dir("hybrid-layers");
+    let (meta, a, d) = hybrid(&dir);
+    // Raw roots as keys are what the bencode parser turns down.
+    assert!(std::str::from_utf8(&root(&block_hashes(&d), 4)).is_err());
+    let d_layer: Vec<[u8; 32]> = d
+        .chunks(PIECE_LENGTH)
+        .map(|piece| root(&block_hashes(piece), 2))
+        .collect();
+
+    let encoded = hybrid_bytes(&meta, &a, &d, Some(d_layer.as_flattened()));
+    let parsed = parse_torrent_bytes(&encoded, ParserOptions::lenient()).unwrap();
+    assert_eq!(parsed.v2_files.len(), 2);
+    assert!(parsed.v2_files[0].piece_layer.is_empty());
+    assert_eq!(parsed.v2_files[1].piece_layer, d_layer);
+    assert_eq!(parsed.v2_piece(2).unwrap().hash, d_layer[1]);
+    // "piece layers" sits outside the info dict, so the info hash doesn't change.
+    let plain = parse_torrent_bytes(&hybrid_bytes(&meta, &a, &d, None), ParserOptions::lenient());
+    assert_eq!(parsed.info_hash(), plain.unwrap().info_hash());
+
+    // A layer that doesn't hash up to the root is dropped rather than trusted.
+    let mut wrong = d_layer.clone();
+    wrong[1] = [9; 32];
+    let encoded = hybrid_bytes(&meta, &a, &d, Some(wrong.as_flattened()));
+    let parsed = parse_torrent_bytes(&encoded, ParserOptions::lenient()).unwrap();
+    assert!(parsed.v2_files[1].piece_layer.is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+// A leecher staging its download in an incomplete directory: files carry the ".!bt"
+// suffix there and land in the save directory once the torrent completes.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::event::SessionEvent;
+
+const PIECE_LENGTH: usize = 16 * 1024;
+
+fn config(incomplete_dir: Option<PathBuf>) -> SessionConfig {
+    SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        incomplete_dir,
+        part_suffix: true,
+        tick_interval: Duration::from_millis(20),
+        ..SessionConfig::default()
+    }
+}
+
+#[test]
+fn completed_download_is_moved_out_of_the_incomplete_dir() {
+    let seed_dir = common::temp_dir("incomplete-seed");
+    let save_dir = common::temp_dir("incomplete-save");
+    let incomplete = common::temp_dir("incomplete-staging");
+
+    let data: Vec<u8> = (0..50_000).map(|i| (i % 251) as u8).collect();
+    std::fs::write(seed_dir.join("payload.bin"), &data).unwrap();
+    let meta = common::single_file("payload.bin", PIECE_LENGTH, &data);
+
+    // The seeder also has an incomplete dir configured but already holds everything.
+    let seeder = Session::new(config(Some(incomplete.join("unused")))).unwrap();
+    let seed_torrent = seeder.add_torrent(meta.clone(), &seed_dir).unwrap();
+    assert!(!seed_torrent.storage().is_staged());
+
+    let leecher = Session::new(config(Some(incomplete.clone()))).unwrap();
+    let events = leecher.subscribe();
+    let torrent = leecher.add_torrent(meta, &save_dir).unwrap();
+    assert!(torrent.storage().is_staged());
+
+    leecher
+        .add_peer(&torrent.info_hash(), seeder.local_addr())
+        .unwrap();
+
+    let moved = loop {
+        match events.recv_timeout(Duration::from_secs(30)).unwrap() {
+            SessionEvent::Moved { from, to, .. } => break (from, to),
+            _ => continue,
+        }
+    };
+    assert_eq!(moved, (incomplete.clone(), save_dir.clone()));
+    assert!(torrent.is_complete());
+    assert!(!torrent.storage().is_staged());
+
+    assert_eq!(std::fs::read(save_dir.join("payload.bin")).unwrap(), data);
+    assert!(!incomplete.join("payload.bin.!bt").exists());
+
+    for dir in [seed_dir, save_dir, incomplete] {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
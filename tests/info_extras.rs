@@ -0,0 +1,71 @@
+use bittorrent_client::bencode::options::ParserOptions;
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::bencode::value::BencodeValue;
+use bittorrent_client::torrent::edit::TorrentEditor;
+use bittorrent_client::torrent::parser::{parse_torrent_bytes, torrent_from_bencode};
+use bittorrent_client::torrent::value::FilesInfo;
+use sha1::{Digest, Sha1};
+
+fn parse(info: &str) -> bittorrent_client::torrent::value::TorrentMetaInfo {
+    let bytes = format!("d8:announce14:http://tracker4:info{}e", info);
+    let (value, _) = parse_value(bytes.as_bytes()).unwrap();
+    torrent_from_bencode(&value).unwrap()
+}
+
+#[test]
+fn unknown_info_keys_keep_the_info_hash() {
+    let info = "d6:lengthi5e4:name5:a.txt12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaa7:privatei1e6:source3:RED12:x_cross_seed8:deadbeefe";
+    let meta = parse(info);
+    assert_eq!(meta.info_hash(), <[u8; 20]>::from(Sha1::digest(info)));
+    assert_eq!(
+        meta.info.extras.get("source"),
+        Some(&BencodeValue::String("RED".to_string()))
+    );
+    assert_eq!(
+        meta.info.extras.get("private"),
+        Some(&BencodeValue::Integer(1))
+    );
+    assert_eq!(meta.info.extras.len(), 3);
+}
+
+#[test]
+fn unknown_file_keys_keep_the_info_hash() {
+    let info = "d5:filesld4:attr1:x6:lengthi3e4:pathl5:a.bineed4:attr1:p6:lengthi2e4:pathl4:.pad1:2eee4:name3:dir12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+    let meta = parse(info);
+    assert_eq!(meta.info_hash(), <[u8; 20]>::from(Sha1::digest(info)));
+    assert!(meta.info.extras.is_empty());
+    let FilesInfo::MultiFile { files } = &meta.info.files_info else {
+        panic!("expected a multi-file torrent");
+    };
+    assert_eq!(
+        files[1].extras.get("attr"),
+        Some(&BencodeValue::String("p".to_string()))
+    );
+}
+
+#[test]
+fn info_hash_covers_the_bytes_as_read() {
+    // Keys out of order: re-encoding would sort them and change the hash.
+    let info = "d4:name5:a.txt6:lengthi5e6:source3:RED12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+    let bytes = format!("d8:announce14:http://tracker4:info{}e", info);
+    let meta = parse_torrent_bytes(bytes.as_bytes(), ParserOptions::lenient()).unwrap();
+    let expected = <[u8; 20]>::from(Sha1::digest(info));
+    assert_eq!(meta.info_hash(), expected);
+    assert_eq!(
+        TorrentEditor::from_bytes(bytes.as_bytes())
+            .unwrap()
+            .info_hash(),
+        expected
+    );
+    assert_ne!(parse(info).info_hash(), expected);
+
+    // Written back out, the info dict is left as it was.
+    let written = meta.to_bytes();
+    assert!(
+        written
+            .windows(info.len())
+            .any(|window| window == info.as_bytes())
+    );
+    let again = parse_torrent_bytes(&written, ParserOptions::lenient()).unwrap();
+    assert_eq!(again.info_hash(), expected);
+}
@@ -0,0 +1,43 @@
+use std::net::IpAddr;
+
+use bittorrent_client::peer::lan::is_lan;
+use bittorrent_client::session::slots::{PeerSlots, SlotKind};
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn private_and_link_local_addresses_are_lan() {
+    for lan in [
+        "10.1.2.3",
+        "172.16.0.1",
+        "172.31.255.254",
+        "192.168.1.10",
+        "169.254.3.4",
+        "fe80::1",
+        "fd12:3456::1",
+        "::ffff:192.168.0.2",
+    ] {
+        assert!(is_lan(ip(lan)), "{} should be LAN", lan);
+    }
+    for wan in ["8.8.8.8", "172.32.0.1", "127.0.0.1", "::1", "2001:db8::1"] {
+        assert!(!is_lan(ip(wan)), "{} should not be LAN", wan);
+    }
+}
+
+#[test]
+fn lan_slots_bypass_the_limits() {
+    let mut slots = PeerSlots::default();
+    assert_eq!(slots.acquire(1, 0), Some(SlotKind::Active));
+    assert_eq!(slots.acquire(1, 0), None);
+
+    let lan = slots.acquire_lan();
+    assert!(lan.transfers());
+    assert_eq!((slots.active(), slots.lan()), (1, 1));
+    assert!(!slots.promote(1));
+
+    slots.release(lan);
+    assert_eq!(slots.lan(), 0);
+    assert!(!SlotKind::Standby.transfers());
+}
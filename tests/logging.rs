@@ -0,0 +1,62 @@
+mod common;
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use bittorrent_client::session::engine::Session;
+use tracing_subscriber::EnvFilter;
+
+// Collects everything the subscriber writes.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn events_carry_the_torrent_span_and_follow_the_filter() {
+    let output = Output::default();
+    let writer = output.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_env_filter(EnvFilter::new("bittorrent_client::session::torrent=debug"))
+        .with_writer(move || writer.clone())
+        .finish();
+
+    let data = vec![7u8; 100];
+    let meta = common::single_file("logged.bin", 16384, &data);
+    let dir = std::env::temp_dir().join(format!("bt-logging-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let session = Session::new(common::local_config()).unwrap();
+        let torrent = session.add_torrent(meta, &dir).unwrap();
+
+        let span = torrent.span().clone();
+        span.in_scope(|| {
+            assert!(!torrent.store_piece(0, &[0u8; 100]).unwrap());
+            assert!(torrent.store_piece(0, &data).unwrap());
+        });
+        assert!(session.announce(&torrent, None).is_err());
+    });
+
+    let logged = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    assert!(
+        logged.contains("torrent{name=logged.bin info_hash="),
+        "{logged}"
+    );
+    assert!(logged.contains("piece failed its hash check"), "{logged}");
+    assert!(logged.contains("piece verified index=0"), "{logged}");
+    // The announce is logged by the engine, which the filter leaves out.
+    assert!(!logged.contains("announce failed"), "{logged}");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use bittorrent_client::torrent::magnet::{MagnetLink, MagnetVersion};
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+const BTIH: &str = "c12fe1c06bba254a9dc9f519b335aa7c1367a88a";
+const BTMH: &str = "1220caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e";
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn meta() -> TorrentMetaInfo {
+    common::unverified("http://127.0.0.1:1/announce".to_string(), "magnet.bin")
+}
+
+#[test]
+fn v1_magnet_with_hex_or_base32_btih() {
+    let uri = format!(
+        "magnet:?xt=urn:btih:{}&dn=Some+Name%21&tr=http%3A%2F%2Ft.example%2Fannounce&xl=1234&x.pe=10.0.0.1:6881",
+        BTIH
+    );
+    let magnet = MagnetLink::parse(&uri).unwrap();
+    assert_eq!(magnet.version(), MagnetVersion::V1);
+    assert_eq!(hex(&magnet.info_hash.unwrap()), BTIH);
+    assert_eq!(magnet.info_hash_v2, None);
+    assert_eq!(magnet.display_name.as_deref(), Some("Some Name!"));
+    assert_eq!(magnet.trackers, ["http://t.example/announce"]);
+    assert_eq!(magnet.peers, ["10.0.0.1:6881"]);
+    assert_eq!(magnet.length, Some(1234));
+    assert_eq!(hex(&magnet.swarm_hash()), BTIH);
+
+    let base32 = MagnetLink::parse("magnet:?xt=urn:btih:YEX6DQDLXISUVHOJ6UM3GNNKPQJWPKEK").unwrap();
+    assert_eq!(base32.info_hash, magnet.info_hash);
+}
+
+#[test]
+fn v2_and_hybrid_magnets() {
+    let v2 = MagnetLink::parse(&format!("magnet:?xt=urn:btmh:{}", BTMH)).unwrap();
+    assert_eq!(v2.version(), MagnetVersion::V2);
+    assert_eq!(v2.info_hash, None);
+    assert_eq!(hex(&v2.info_hash_v2.unwrap()), BTMH[4..]);
+    // Peers and trackers know a v2 swarm by the truncated hash.
+    assert_eq!(hex(&v2.swarm_hash()), BTMH[4..44]);
+
+    let hybrid = MagnetLink::parse(&format!(
+        "magnet:?xt=urn:btih:{}&xt=urn:btmh:{}&dn=hybrid",
+        BTIH, BTMH
+    ))
+    .unwrap();
+    assert_eq!(hybrid.version(), MagnetVersion::Hybrid);
+    assert_eq!(hex(&hybrid.swarm_hash()), BTIH);
+    assert_eq!(MagnetLink::parse(&hybrid.to_uri()).unwrap(), hybrid);
+}
+
+#[test]
+fn round_trips_through_to_uri() {
+    let magnet = MagnetLink {
+        info_hash: Some([7; 20]),
+        info_hash_v2: None,
+        display_name: Some("a b/c&d".to_string()),
+        trackers: vec!["udp://t.example:80".to_string()],
+        web_seeds: vec!["http://w.example/files/".to_string()],
+        peers: vec!["[::1]:6881".to_string()],
+        length: Some(99),
+    };
+    assert_eq!(MagnetLink::parse(&magnet.to_uri()).unwrap(), magnet);
+}
+
+#[test]
+fn refuses_what_it_cannot_use() {
+    assert!(MagnetLink::parse("http://example.com/?xt=urn:btih:00").is_err());
+    assert!(MagnetLink::parse("magnet:?dn=nothing").is_err());
+    assert!(MagnetLink::parse("magnet:?xt=urn:btih:abcd").is_err());
+    // A SHA-1 multihash (0x11) isn't a v2 info hash.
+    let sha1 = format!("magnet:?xt=urn:btmh:1114{}", BTIH);
+    assert!(MagnetLink::parse(&sha1).is_err());
+    assert!(MagnetLink::parse(&format!("magnet:?xt=urn:btih:{}&dn=%zz", BTIH)).is_err());
+    // Unknown urns are skipped rather than refused, as long as one is ours.
+    let mixed = format!("magnet:?xt=urn:ed2k:abc&xt=urn:btih:{}", BTIH);
+    assert!(MagnetLink::parse(&mixed).is_ok());
+}
+
+#[test]
+fn fetched_metainfo_has_to_match_every_hash() {
+    let meta = meta();
+    let v1 = MagnetLink {
+        info_hash: Some(meta.info_hash()),
+        ..MagnetLink::default()
+    };
+    let v2 = MagnetLink {
+        info_hash_v2: Some(meta.info_hash_v2()),
+        ..MagnetLink::default()
+    };
+    let hybrid = MagnetLink {
+        info_hash: Some(meta.info_hash()),
+        info_hash_v2: Some(meta.info_hash_v2()),
+        ..MagnetLink::default()
+    };
+    assert!(v1.matches(&meta) && v2.matches(&meta) && hybrid.matches(&meta));
+
+    let wrong_v2 = MagnetLink {
+        info_hash_v2: Some([0; 32]),
+        ..hybrid.clone()
+    };
+    assert!(!wrong_v2.matches(&meta));
+}
@@ -0,0 +1,85 @@
+use std::io::Read;
+
+use bittorrent_client::peer::error::PeerMessageError;
+use bittorrent_client::peer::extension::ExtendedHandshake;
+use bittorrent_client::peer::value::{MAX_MESSAGE_LENGTH, PeerMessage};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Claims a huge message and then never sends it, counting how much was asked for.
+struct Lying {
+    prefix: [u8; 4],
+    position: usize,
+    requested: usize,
+}
+
+impl Read for Lying {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.requested += buf.len();
+        let rest = &self.prefix[self.position.min(4)..];
+        let n = rest.len().min(buf.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+#[test]
+fn length_prefixes_do_not_drive_allocation() {
+    let mut stream = Lying {
+        prefix: u32::MAX.to_be_bytes(),
+        position: 0,
+        requested: 0,
+    };
+    assert!(matches!(
+        PeerMessage::read_peer_message(&mut stream),
+        Err(PeerMessageError::MessageTooLong(u32::MAX))
+    ));
+
+    let mut stream = Lying {
+        prefix: MAX_MESSAGE_LENGTH.to_be_bytes(),
+        position: 0,
+        requested: 0,
+    };
+    assert!(matches!(
+        PeerMessage::read_peer_message(&mut stream),
+        Err(PeerMessageError::IOError(_))
+    ));
+    assert!(stream.requested < MAX_MESSAGE_LENGTH as usize);
+}
+
+// Same loop as the fuzz target, over seeded random input.
+#[test]
+fn random_streams_never_panic() {
+    let mut rng = StdRng::seed_from_u64(0x5eed);
+    for _ in 0..2000 {
+        let mut data = vec![0u8; rng.random_range(0..256)];
+        rng.fill(&mut data[..]);
+        // Mostly short prefixes, so that whole messages come out of the noise.
+        let mut at = 0;
+        while at + 5 <= data.len() {
+            let length = rng.random_range(0..24u32);
+            data[at..at + 4].copy_from_slice(&length.to_be_bytes());
+            data[at + 4] = rng.random_range(0..22);
+            at += 4 + length as usize;
+        }
+
+        let mut stream = data.as_slice();
+        while let Ok(message) = PeerMessage::read_peer_message(&mut stream) {
+            let bytes = message.to_bytes();
+            assert_eq!(
+                PeerMessage::read_peer_message(&mut bytes.as_slice()).unwrap(),
+                message
+            );
+        }
+        let _ = ExtendedHandshake::from_payload(&data);
+    }
+}
+
+#[test]
+fn hostile_extended_handshakes_are_rejected() {
+    assert!(ExtendedHandshake::from_payload(b"d1:md6:ut_pexi1eee1:pi6881ee").is_ok());
+    assert!(ExtendedHandshake::from_payload(&[b'l'; 4096]).is_err());
+    assert!(ExtendedHandshake::from_payload(b"d1:v99999999999:xe").is_err());
+    assert!(ExtendedHandshake::from_payload(b"d1:mi1ee").is_err());
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+use bittorrent_client::bencode::encoder::encode;
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::torrent::parser::torrent_from_bencode;
+use bittorrent_client::torrent::value::{File, FilesInfo, Info, ToBencode, TorrentMetaInfo};
+use md5::{Digest, Md5};
+use sha1::Sha1;
+
+fn md5_hex(data: &[u8]) -> String {
+    Md5::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn two_file_torrent(good: &[u8], bad: &[u8]) -> TorrentMetaInfo {
+    let payload = [good, bad].concat();
+    TorrentMetaInfo::new(
+        "http://127.0.0.1:1/announce".to_string(),
+        Info {
+            name: "sums".to_string(),
+            piece_length: 16384,
+            pieces: vec![Sha1::digest(&payload).into()],
+            files_info: FilesInfo::MultiFile {
+                files: vec![
+                    File {
+                        length: good.len(),
+                        path: vec!["good.iso".to_string()],
+                        md5sum: Some(md5_hex(good).to_uppercase()),
+                        extras: HashMap::new(),
+                    },
+                    File {
+                        length: bad.len(),
+                        path: vec!["bad.iso".to_string()],
+                        md5sum: Some(md5_hex(b"something else")),
+                        extras: HashMap::new(),
+                    },
+                ],
+            },
+            extras: HashMap::new(),
+        },
+    )
+}
+
+#[test]
+fn md5sum_survives_a_round_trip_and_keeps_the_info_hash() {
+    let meta = two_file_torrent(b"good", b"bad");
+    let encoded = encode(&meta.to_bencode_value());
+    let (value, _) = parse_value(&encoded).unwrap();
+    let parsed = torrent_from_bencode(&value).unwrap();
+
+    assert_eq!(parsed.info_hash(), meta.info_hash());
+    let FilesInfo::MultiFile { files } = parsed.info.files_info else {
+        panic!("expected a multi-file torrent");
+    };
+    assert_eq!(files[0].md5sum, Some(md5_hex(b"good").to_uppercase()));
+}
+
+#[test]
+fn mismatching_files_are_reported() {
+    let dir: PathBuf = std::env::temp_dir().join(format!("bt-md5-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("sums")).unwrap();
+    std::fs::write(dir.join("sums/good.iso"), b"good").unwrap();
+    std::fs::write(dir.join("sums/bad.iso"), b"bad").unwrap();
+
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let torrent = session
+        .add_torrent(two_file_torrent(b"good", b"bad"), &dir)
+        .unwrap();
+
+    assert!(torrent.is_complete());
+    assert_eq!(torrent.check_md5sums(), vec![PathBuf::from("sums/bad.iso")]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
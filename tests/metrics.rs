@@ -0,0 +1,39 @@
+#![cfg(feature = "metrics")]
+
+mod common;
+
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::metrics::{ANNOUNCE_ERRORS, HASH_FAILURES, PIECES_VERIFIED};
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+#[test]
+fn verification_and_announce_errors_are_counted() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+
+    let data = vec![3u8; 100];
+    let meta = common::single_file("metrics.bin", 16384, &data);
+    let dir = std::env::temp_dir().join(format!("bt-metrics-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    metrics::with_local_recorder(&recorder, || {
+        let session = Session::new(common::local_config()).unwrap();
+        let torrent = session.add_torrent(meta, &dir).unwrap();
+        assert!(!torrent.store_piece(0, &[0u8; 100]).unwrap());
+        assert!(!torrent.store_piece(0, &[1u8; 100]).unwrap());
+        assert!(torrent.store_piece(0, &data).unwrap());
+        assert!(session.announce(&torrent, None).is_err());
+    });
+
+    let counters: Vec<_> = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| (key.key().name().to_string(), value))
+        .collect();
+    assert!(counters.contains(&(HASH_FAILURES.to_string(), DebugValue::Counter(2))));
+    assert!(counters.contains(&(PIECES_VERIFIED.to_string(), DebugValue::Counter(1))));
+    assert!(counters.contains(&(ANNOUNCE_ERRORS.to_string(), DebugValue::Counter(1))));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,164 @@
+#![cfg(feature = "sim")]
+
+mod common;
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::snapshot::TrackerStatus;
+use bittorrent_client::sim::tracker::{MockReply, MockTracker};
+use bittorrent_client::tracker::client::TrackerClient;
+use bittorrent_client::tracker::error::TrackerError;
+use bittorrent_client::tracker::value::{Event, TrackerRequest};
+
+fn request(url: &str, compact: bool) -> TrackerRequest {
+    TrackerRequest {
+        announce_url: url.to_string(),
+        info_hash: [0xab; 20],
+        peer_id: *b"-RS0001-mocktracker0",
+        ip: None,
+        port: 6881,
+        uploaded: 10,
+        downloaded: 20,
+        left: 30,
+        compact,
+        no_peer_id: false,
+        event: Some(Event::Started),
+        numwant: Some(25),
+        key: None,
+        tracker_id: None,
+    }
+}
+
+fn peers() -> Vec<SocketAddr> {
+    vec![
+        SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), 6881)),
+        SocketAddr::from((Ipv6Addr::LOCALHOST, 6882)),
+    ]
+}
+
+#[test]
+fn client_gets_the_scripted_reply() {
+    let tracker = MockTracker::with_reply(MockReply {
+        interval: 900,
+        min_interval: Some(60),
+        complete: Some(4),
+        incomplete: Some(2),
+        peers: peers(),
+        tracker_id: Some("mock-1".to_string()),
+        warning: Some("be nice".to_string()),
+        ..MockReply::default()
+    })
+    .unwrap();
+
+    for compact in [true, false] {
+        let response =
+            TrackerClient::query_tracker(&request(&tracker.announce_url(), compact)).unwrap();
+        assert_eq!(response.interval, 900);
+        assert_eq!(response.min_interval, Some(60));
+        assert_eq!((response.complete, response.incomplete), (Some(4), Some(2)));
+        assert_eq!(response.tracker_id.as_deref(), Some("mock-1"));
+        assert_eq!(response.warning_message.as_deref(), Some("be nice"));
+        let got: Vec<SocketAddr> = response
+            .peers
+            .iter()
+            .map(|peer| SocketAddr::new(peer.ip, peer.port))
+            .collect();
+        assert_eq!(got, peers());
+    }
+
+    let announces = tracker.announces();
+    assert_eq!(announces.len(), 2);
+    assert_eq!(announces[0].info_hash, vec![0xab; 20]);
+    assert_eq!(announces[0].peer_id, b"-RS0001-mocktracker0");
+    assert_eq!(announces[0].event.as_deref(), Some("started"));
+    assert_eq!(
+        (
+            announces[0].uploaded,
+            announces[0].downloaded,
+            announces[0].left
+        ),
+        (10, 20, 30)
+    );
+    assert_eq!((announces[0].port, announces[0].numwant), (6881, Some(25)));
+    assert!(announces[0].compact && !announces[1].compact);
+
+    let stats = TrackerClient::scrape(&tracker.announce_url(), &[0xab; 20]).unwrap();
+    assert_eq!((stats.complete, stats.incomplete), (4, 2));
+    assert_eq!(tracker.scrapes(), 1);
+}
+
+#[test]
+fn failures_come_back_as_errors() {
+    let tracker = MockTracker::start().unwrap();
+    let url = tracker.announce_url();
+
+    tracker.update(|reply| reply.failure = Some("unregistered torrent".to_string()));
+    let error = TrackerClient::query_tracker(&request(&url, true)).unwrap_err();
+    assert!(
+        error.to_string().contains("unregistered torrent"),
+        "{}",
+        error
+    );
+    assert!(TrackerClient::scrape(&url, &[0xab; 20]).is_err());
+
+    tracker.update(|reply| {
+        reply.failure = None;
+        reply.status = Some(503);
+    });
+    let error = TrackerClient::query_tracker(&request(&url, true)).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<TrackerError>(),
+        Some(TrackerError::HttpStatus(503))
+    ));
+
+    tracker.update(|reply| {
+        reply.status = None;
+        reply.delay = Duration::from_secs(2);
+    });
+    let error =
+        TrackerClient::query_tracker_within(&request(&url, true), None, Duration::from_millis(300))
+            .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<TrackerError>(),
+        Some(TrackerError::Timeout)
+    ));
+    assert_eq!(tracker.announces().len(), 3);
+}
+
+#[test]
+fn session_announces_follow_the_lifecycle() {
+    let tracker = MockTracker::with_reply(MockReply {
+        interval: 120,
+        tracker_id: Some("abc".to_string()),
+        peers: vec![SocketAddr::from((Ipv4Addr::LOCALHOST, 1))],
+        ..MockReply::default()
+    })
+    .unwrap();
+    let session = Session::new(common::local_config()).unwrap();
+    let meta = common::unverified(tracker.announce_url(), "mock-tracker.bin");
+    let dir = std::env::temp_dir().join(format!("bt-mock-tracker-{}", std::process::id()));
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    session.announce(&torrent, None).unwrap();
+    session.announce(&torrent, None).unwrap();
+    let announces = tracker.announces();
+    assert_eq!(announces[0].event.as_deref(), Some("started"));
+    assert_eq!(announces[0].tracker_id, None);
+    assert_eq!(announces[0].left, 100);
+    assert_eq!(announces[0].port, session.local_addr().port());
+    // Regular announces carry no event and echo the tracker id.
+    assert_eq!(announces[1].event, None);
+    assert_eq!(announces[1].tracker_id.as_deref(), Some("abc"));
+    assert!(torrent.known_peers() >= 1);
+
+    let snapshot = &torrent.trackers()[0];
+    assert_eq!(snapshot.status, TrackerStatus::Working);
+    assert_eq!(snapshot.interval, Some(120));
+
+    tracker.update(|reply| reply.failure = Some("banned".to_string()));
+    assert!(session.announce(&torrent, None).is_err());
+    assert_eq!(torrent.trackers()[0].status, TrackerStatus::Failing);
+    let _ = std::fs::remove_dir_all(&dir);
+}
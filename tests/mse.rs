@@ -0,0 +1,67 @@
+mod common;
+
+use bittorrent_client::peer::mse::{req2_hash, req3_hash, unmask_req2};
+use bittorrent_client::session::engine::Session;
+use sha1::{Digest, Sha1};
+
+fn xor(a: &[u8; 20], b: &[u8; 20]) -> [u8; 20] {
+    std::array::from_fn(|i| a[i] ^ b[i])
+}
+
+#[test]
+fn req2_is_unmasked_with_the_shared_secret() {
+    let info_hash = [0x5a; 20];
+    let expected: [u8; 20] = Sha1::new()
+        .chain_update(b"req2")
+        .chain_update(info_hash)
+        .finalize()
+        .into();
+    assert_eq!(req2_hash(&info_hash), expected);
+
+    // What an initiator puts on the wire.
+    let secret = b"diffie-hellman secret";
+    let sent = xor(&req2_hash(&info_hash), &req3_hash(secret));
+    assert_ne!(sent, req2_hash(&info_hash));
+    assert_eq!(unmask_req2(&sent, secret), req2_hash(&info_hash));
+    assert_ne!(unmask_req2(&sent, b"another secret"), req2_hash(&info_hash));
+}
+
+#[test]
+fn encrypted_peers_are_routed_to_their_torrent() {
+    let dir = common::temp_dir("mse");
+    let session = Session::new(common::local_config()).unwrap();
+    let first = session
+        .add_torrent(
+            common::unverified(common::ANNOUNCE.to_string(), "first.bin"),
+            &dir,
+        )
+        .unwrap();
+    let second = session
+        .add_torrent(
+            common::unverified(common::ANNOUNCE.to_string(), "second.bin"),
+            &dir,
+        )
+        .unwrap();
+
+    let secret = b"shared";
+    let route = |info_hash: [u8; 20]| {
+        let sent = xor(&req2_hash(&info_hash), &req3_hash(secret));
+        session
+            .torrent_by_obfuscated_hash(&unmask_req2(&sent, secret))
+            .map(|torrent| torrent.info_hash())
+    };
+    assert_eq!(route(first.info_hash()), Some(first.info_hash()));
+    assert_eq!(route(second.info_hash()), Some(second.info_hash()));
+    assert_eq!(route([9; 20]), None);
+    // The plain info hash is no key: only its req2 hash is.
+    assert!(
+        session
+            .torrent_by_obfuscated_hash(&first.info_hash())
+            .is_none()
+    );
+
+    session.remove_torrent(&first.info_hash(), false).unwrap();
+    assert_eq!(route(first.info_hash()), None);
+    assert_eq!(route(second.info_hash()), Some(second.info_hash()));
+    let _ = std::fs::remove_dir_all(&dir);
+}
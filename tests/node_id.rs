@@ -0,0 +1,119 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use bittorrent_client::session::dht_state::{DhtNode, DhtState};
+use bittorrent_client::session::node_id::{
+    NodeIdCheck, check_node_id, is_secure_node_id, secure_node_id,
+};
+
+fn hex(text: &str) -> [u8; 20] {
+    let bytes: Vec<u8> = (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+        .collect();
+    bytes.try_into().unwrap()
+}
+
+// The examples from BEP 42.
+const VECTORS: [(&str, &str); 5] = [
+    ("124.31.75.21", "5fbfbff10c5d6a4ec8a88e4c6ab4c28b95eee401"),
+    ("21.75.31.124", "5a3ce9c14e7a08645677bbd1cfe7d8f956d53256"),
+    ("65.23.51.170", "a5d43220bc8f112a3d426c84764f8c2a1150e616"),
+    ("84.124.73.14", "1b0321dd1bb1fe518101ceef99462b947a01ff41"),
+    ("43.213.53.83", "e56f6cbf5b7c4be0237986d5243b87aa6d51305a"),
+];
+
+#[test]
+fn bep_42_examples_check_out() {
+    for (ip, id) in VECTORS {
+        let ip: IpAddr = ip.parse().unwrap();
+        assert!(is_secure_node_id(&hex(id), ip), "{}", ip);
+        assert_eq!(check_node_id(Some(&hex(id)), ip), NodeIdCheck::Valid);
+    }
+    // Only the masked bits of the address count: 120 and 124 share their low two bits.
+    let other = IpAddr::from([120, 31, 75, 21]);
+    let mut id = hex(VECTORS[0].1);
+    assert!(is_secure_node_id(&id, other));
+    id[0] ^= 1;
+    assert!(!is_secure_node_id(&id, other));
+    // Anywhere else it fails.
+    assert_eq!(
+        check_node_id(Some(&hex(VECTORS[0].1)), IpAddr::from([8, 8, 8, 8])),
+        NodeIdCheck::Invalid
+    );
+}
+
+#[test]
+fn generated_ids_fit_their_address() {
+    let ips: [IpAddr; 3] = [
+        IpAddr::from([203, 0, 113, 9]),
+        IpAddr::from([198, 51, 100, 200]),
+        "2001:db8:1234::1".parse().unwrap(),
+    ];
+    for ip in ips {
+        for _ in 0..20 {
+            assert!(is_secure_node_id(&secure_node_id(ip), ip));
+        }
+    }
+}
+
+#[test]
+fn local_addresses_are_exempt() {
+    let id = [0u8; 20];
+    for ip in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "192.168.0.5",
+        "172.20.0.1",
+        "fd00::1",
+    ] {
+        let ip: IpAddr = ip.parse().unwrap();
+        assert_eq!(check_node_id(Some(&id), ip), NodeIdCheck::Exempt);
+    }
+    assert_eq!(
+        check_node_id(None, IpAddr::from([8, 8, 8, 8])),
+        NodeIdCheck::Unknown
+    );
+}
+
+#[test]
+fn our_id_follows_the_external_address() {
+    let mut state = DhtState::new();
+    state.node_id = [0; 20];
+    assert!(!state.secure_for(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2))));
+    assert_eq!(state.node_id, [0; 20]);
+
+    let ip = IpAddr::from([203, 0, 113, 9]);
+    assert!(state.secure_for(ip));
+    assert!(is_secure_node_id(&state.node_id, ip));
+    let id = state.node_id;
+    assert!(!state.secure_for(ip));
+    assert_eq!(state.node_id, id);
+}
+
+#[test]
+fn nodes_with_bad_ids_rank_last() {
+    let valid_ip = VECTORS[1].0;
+    let node = |ip: &str, last_seen, id: Option<[u8; 20]>| DhtNode {
+        addr: (ip.parse::<IpAddr>().unwrap(), 6881).into(),
+        last_seen,
+        id,
+    };
+    let mut state = DhtState::new();
+    state.merge([
+        node("8.8.8.8", 300, Some([1; 20])),
+        node("9.9.9.9", 200, None),
+        node(valid_ip, 100, Some(hex(VECTORS[1].1))),
+        node("1.1.1.1", 250, None),
+    ]);
+    let order: Vec<String> = state
+        .nodes
+        .iter()
+        .map(|node| node.addr.ip().to_string())
+        .collect();
+    assert_eq!(order, [valid_ip, "1.1.1.1", "9.9.9.9", "8.8.8.8"]);
+
+    // A later sighting without an id keeps the id we had.
+    state.merge([node(valid_ip, 400, None)]);
+    assert_eq!(state.nodes[0].id, Some(hex(VECTORS[1].1)));
+    assert_eq!(state.nodes[0].last_seen, 400);
+}
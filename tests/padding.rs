@@ -0,0 +1,132 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::priority::FilePriority;
+use bittorrent_client::storage::file_storage::FileStorage;
+use bittorrent_client::torrent::builder::TorrentBuilder;
+use bittorrent_client::torrent::parser::torrent_from_bencode;
+use bittorrent_client::torrent::value::{File, FilesInfo, TorrentMetaInfo};
+
+const PIECE_LENGTH: usize = 16 * 1024;
+
+fn session() -> Session {
+    Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        ..SessionConfig::default()
+    })
+    .unwrap()
+}
+
+// Two files that each end mid-piece, made into a torrent with pad files.
+fn padded(label: &str) -> (PathBuf, TorrentMetaInfo, Vec<u8>, Vec<u8>) {
+    let dir = std::env::temp_dir().join(format!("bt-padding-{}-{}", label, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let root = dir.join("padded");
+    std::fs::create_dir_all(&root).unwrap();
+    let a: Vec<u8> = (0..20_000).map(|i| (i % 251) as u8).collect();
+    let b: Vec<u8> = (0..5_000).map(|i| (i % 7) as u8).collect();
+    std::fs::write(root.join("a.bin"), &a).unwrap();
+    std::fs::write(root.join("b.bin"), &b).unwrap();
+    let meta = TorrentBuilder::new(&root)
+        .announce("http://127.0.0.1:1/announce")
+        .piece_length(PIECE_LENGTH)
+        .pad_files(true)
+        .build()
+        .unwrap();
+    (dir, meta, a, b)
+}
+
+#[test]
+fn attr_p_marks_pad_files() {
+    let bytes = b"d8:announce3:url4:infod5:filesld6:lengthi3e4:pathl5:a.bineed4:attr1:p6:lengthi16381e4:pathl4:.pad5:16381eed6:lengthi2e4:pathl5:b.bineee4:name3:dir12:piece lengthi16384e6:pieces40:aaaaaaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbbbbbee";
+    let (value, _) = parse_value(bytes).unwrap();
+    let meta = torrent_from_bencode(&value).unwrap();
+    let FilesInfo::MultiFile { files } = &meta.info.files_info else {
+        panic!("expected a multi-file torrent");
+    };
+    let pads: Vec<bool> = files.iter().map(File::is_padding).collect();
+    assert_eq!(pads, [false, true, false]);
+
+    let storage = FileStorage::new(&std::env::temp_dir(), &meta);
+    let entries = storage.files();
+    assert!(entries[1].pad);
+    assert_eq!(entries[2].offset, PIECE_LENGTH as u64);
+    assert_eq!(storage.pieces_of(&entries[1]), 0..0);
+    assert_eq!(storage.pieces_of(&entries[2]), 1..2);
+}
+
+#[test]
+fn builder_pads_files_onto_piece_boundaries() {
+    let (dir, meta, _, _) = padded("builder");
+    let FilesInfo::MultiFile { files } = &meta.info.files_info else {
+        panic!("expected a multi-file torrent");
+    };
+    let layout: Vec<_> = files
+        .iter()
+        .map(|file| (file.path.join("/"), file.length, file.is_padding()))
+        .collect();
+    let gap = 2 * PIECE_LENGTH - 20_000;
+    assert_eq!(
+        layout,
+        [
+            ("a.bin".to_string(), 20_000, false),
+            (format!(".pad/{}", gap), gap, true),
+            ("b.bin".to_string(), 5_000, false),
+        ]
+    );
+    assert_eq!(meta.info.pieces.len(), 3);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn pad_files_never_reach_the_disk() {
+    let (dir, meta, a, b) = padded("disk");
+
+    // Seeding from where the torrent was made: the pad reads as zeros.
+    let session = session();
+    let seeding = session.add_torrent(meta.clone(), &dir).unwrap();
+    assert!(seeding.is_complete());
+    let pieces: Vec<Vec<u8>> = (0..3)
+        .map(|index| {
+            let size = meta.piece_size(index);
+            seeding
+                .storage()
+                .read(index as u64 * PIECE_LENGTH as u64, size)
+                .unwrap()
+        })
+        .collect();
+    assert!(
+        pieces[1][20_000 - PIECE_LENGTH..]
+            .iter()
+            .all(|&byte| byte == 0)
+    );
+    session.remove_torrent(&meta.info_hash(), false).unwrap();
+
+    // Downloading into an empty directory: only the real files appear.
+    let target = dir.join("download");
+    let torrent = session.add_torrent(meta, &target).unwrap();
+    for (index, piece) in pieces.iter().enumerate() {
+        assert!(torrent.store_piece(index, piece).unwrap());
+    }
+    assert!(torrent.is_complete());
+    let root = target.join("padded");
+    assert_eq!(std::fs::read(root.join("a.bin")).unwrap(), a);
+    assert_eq!(std::fs::read(root.join("b.bin")).unwrap(), b);
+    assert!(!root.join(".pad").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn pad_files_alone_want_no_pieces() {
+    let (dir, meta, _, _) = padded("priority");
+    let session = session();
+    let torrent = session.add_torrent(meta, &dir.join("empty")).unwrap();
+    torrent.set_file_priority(0, FilePriority::Skip).unwrap();
+    torrent.set_file_priority(2, FilePriority::Skip).unwrap();
+    assert!((0..3).all(|index| !torrent.is_wanted(index)));
+    assert_eq!(torrent.left().as_u64(), 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
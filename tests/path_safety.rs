@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::error::SessionError;
+use bittorrent_client::torrent::parser::torrent_from_bencode;
+use bittorrent_client::torrent::value::{File, FilesInfo, TorrentMetaInfo};
+
+fn bencode_str(s: &str) -> String {
+    format!("{}:{}", s.len(), s)
+}
+
+fn torrent(name: &str, path: &[&str]) -> Result<TorrentMetaInfo, String> {
+    let path: String = path.iter().map(|part| bencode_str(part)).collect();
+    let bytes = format!(
+        "d8:announce14:http://tracker4:infod5:filesld6:lengthi3e4:pathl{}eee4:name{}12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee",
+        path,
+        bencode_str(name)
+    );
+    let (value, _) = parse_value(bytes.as_bytes()).unwrap();
+    torrent_from_bencode(&value).map_err(|e| e.to_string())
+}
+
+#[test]
+fn parser_refuses_paths_leaving_the_save_dir() {
+    assert!(torrent("dir", &["sub", "a.bin"]).is_ok());
+
+    let escapes: [(&str, &[&str]); 7] = [
+        ("dir", &["..", "..", ".bashrc"]),
+        ("dir", &["/etc/cron.d/x"]),
+        ("dir", &["sub/../../x"]),
+        ("dir", &["a\\..\\b"]),
+        ("dir", &["", "a.bin"]),
+        ("dir", &[]),
+        ("..", &["a.bin"]),
+    ];
+    for (name, path) in escapes {
+        let error = torrent(name, path).unwrap_err();
+        assert!(error.contains("path"), "{:?}: {}", path, error);
+    }
+    assert!(torrent("/tmp", &["a.bin"]).is_err());
+    assert!(torrent(".", &["a.bin"]).is_err());
+}
+
+#[test]
+fn session_refuses_a_built_torrent_with_an_unsafe_path() {
+    let mut meta = torrent("dir", &["a.bin"]).unwrap();
+    meta.info.files_info = FilesInfo::MultiFile {
+        files: vec![File {
+            length: 3,
+            path: vec!["..".to_string(), "escaped.bin".to_string()],
+            md5sum: None,
+            extras: HashMap::new(),
+        }],
+    };
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-path-safety-{}", std::process::id()));
+    let result = session.add_torrent(meta, &dir);
+    assert!(matches!(result, Err(SessionError::UnsafePath(_))));
+    assert!(!dir.join("escaped.bin").exists());
+    assert!(session.torrents().is_empty());
+}
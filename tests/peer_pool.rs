@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV6, TcpListener};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::peer_pool::{PeerPool, PeerSource};
+use bittorrent_client::torrent::value::{FilesInfo, Info, TorrentMetaInfo};
+
+fn addr(n: u8) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::new(10, 0, 0, n), 6881))
+}
+
+#[test]
+fn pool_merges_sources_per_address() {
+    let mut pool = PeerPool::default();
+    assert!(pool.add(addr(1), PeerSource::Tracker));
+    assert!(!pool.add(addr(1), PeerSource::Pex));
+    assert!(!pool.add(addr(1), PeerSource::Tracker));
+    assert!(pool.add(addr(2), PeerSource::Dht));
+    let mapped = SocketAddrV6::new(Ipv4Addr::new(10, 0, 0, 2).to_ipv6_mapped(), 6881, 0, 0);
+    assert!(!pool.add(mapped.into(), PeerSource::Lsd));
+
+    assert_eq!(pool.len(), 2);
+    assert_eq!(
+        pool.sources(addr(1)),
+        vec![PeerSource::Tracker, PeerSource::Pex]
+    );
+    let counts = pool.source_counts();
+    assert_eq!(counts[&PeerSource::Tracker], 1);
+    assert_eq!(counts[&PeerSource::Dht], 1);
+    assert_eq!(counts.get(&PeerSource::Manual), None);
+}
+
+#[test]
+fn pool_holds_back_reconnects() {
+    let mut pool = PeerPool::default();
+    let interval = Duration::from_secs(60);
+    let now = Instant::now();
+    assert!(!pool.try_dial(addr(1), now, interval));
+
+    pool.add(addr(1), PeerSource::Tracker);
+    assert!(pool.try_dial(addr(1), now, interval));
+    assert!(!pool.try_dial(addr(1), now + Duration::from_secs(30), interval));
+    assert!(pool.try_dial(addr(1), now + interval, interval));
+}
+
+#[test]
+fn repeated_announces_dial_a_peer_once() {
+    // A peer that counts connection attempts and never answers the handshake.
+    let peer = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let peer_port = peer.local_addr().unwrap().port();
+    let dials = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&dials);
+    thread::spawn(move || {
+        let mut streams = Vec::new();
+        for stream in peer.incoming().flatten() {
+            counter.fetch_add(1, Ordering::SeqCst);
+            streams.push(stream);
+        }
+    });
+
+    // Lists the same peer twice in every response.
+    let tracker = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let url = format!("http://{}/announce", tracker.local_addr().unwrap());
+    thread::spawn(move || {
+        for mut stream in tracker.incoming().flatten() {
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let entry = format!("d2:ip9:127.0.0.14:porti{}ee", peer_port);
+            let body = format!("d8:intervali1800e5:peersl{}{}ee", entry, entry);
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(header.as_bytes());
+            let _ = stream.write_all(body.as_bytes());
+        }
+    });
+
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-peer-pool-{}", std::process::id()));
+    let meta = TorrentMetaInfo::new(
+        url,
+        Info {
+            name: "pool.bin".to_string(),
+            piece_length: 16384,
+            pieces: vec![[0; 20]],
+            files_info: FilesInfo::SingleFile {
+                length: 16384,
+                md5sum: None,
+            },
+            extras: HashMap::new(),
+        },
+    );
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    session.announce(&torrent, None).unwrap();
+    session.announce(&torrent, None).unwrap();
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(dials.load(Ordering::SeqCst), 1);
+    assert_eq!(torrent.known_peers(), 1);
+    assert_eq!(torrent.peer_source_counts()[&PeerSource::Tracker], 1);
+
+    // Asking for the peer explicitly dials it anyway.
+    session
+        .add_peer(
+            &torrent.info_hash(),
+            SocketAddr::from((Ipv4Addr::LOCALHOST, peer_port)),
+        )
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while dials.load(Ordering::SeqCst) < 2 {
+        assert!(Instant::now() < deadline, "add_peer didn't dial");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(torrent.peer_source_counts()[&PeerSource::Manual], 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
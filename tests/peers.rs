@@ -0,0 +1,135 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use bittorrent_client::peer::client_id::client_name;
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::snapshot::PeerInfo;
+use bittorrent_client::session::torrent::Torrent;
+use bittorrent_client::units::ByteSize;
+
+const PIECE: usize = 16384;
+
+fn wait_for(torrent: &Torrent, what: &str, done: impl Fn(&PeerInfo) -> bool) -> PeerInfo {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(peer) = torrent.peers().into_iter().find(|peer| done(peer)) {
+            return peer;
+        }
+        assert!(Instant::now() < deadline, "{}", what);
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn client_names_from_peer_ids() {
+    assert_eq!(
+        client_name(b"-qB4630-abcdefghijkl").as_deref(),
+        Some("qBittorrent 4.6.3.0")
+    );
+    assert_eq!(
+        client_name(b"-XX1A00-abcdefghijkl").as_deref(),
+        Some("XX 1.10.0.0")
+    );
+    assert_eq!(
+        client_name(b"M4-20-8--abcdefghijk").as_deref(),
+        Some("Mainline 4.20.8")
+    );
+    assert_eq!(client_name(&[7u8; 20]), None);
+}
+
+#[test]
+fn peers_lists_each_connection_with_its_traffic() {
+    let dir = std::env::temp_dir().join(format!("bt-peers-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let data: Vec<u8> = (0..2 * PIECE).map(|i| (i % 239) as u8).collect();
+    std::fs::write(dir.join("peers.bin"), &data).unwrap();
+    let meta = common::single_file("peers.bin", PIECE, &data);
+
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        tick_interval: Duration::from_secs(3600),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    Handshake::perform_handshake(
+        &mut stream,
+        &torrent.info_hash(),
+        b"-TR4050-peerspeers00",
+        ReservedBits::empty(),
+        Duration::from_secs(10),
+    )
+    .unwrap();
+    PeerMessage::Have { piece_index: 1 }
+        .write_peer_message(&mut stream)
+        .unwrap();
+    PeerMessage::Interested
+        .write_peer_message(&mut stream)
+        .unwrap();
+
+    let peer = wait_for(&torrent, "peer never showed up", |peer| {
+        peer.flags.peer_interested && peer.progress > 0.0
+    });
+    assert_eq!(peer.addr, stream.local_addr().unwrap());
+    assert_eq!(peer.client.as_deref(), Some("Transmission 4.0.5.0"));
+    assert!(peer.flags.incoming);
+    assert!(!peer.flags.encrypted);
+    assert!(!peer.flags.fast);
+    assert_eq!(peer.progress, 0.5);
+    assert_eq!(peer.uploaded, ByteSize(0));
+    assert_eq!(peer.requests, 0);
+    torrent.sample_peer_rate();
+
+    loop {
+        match PeerMessage::read_peer_message(&mut stream).unwrap() {
+            PeerMessage::Unchoke => break,
+            _ => continue,
+        }
+    }
+    PeerMessage::Request {
+        index: 0,
+        begin: 0,
+        length: PIECE as u32,
+    }
+    .write_peer_message(&mut stream)
+    .unwrap();
+    loop {
+        if let PeerMessage::Piece { block, .. } =
+            PeerMessage::read_peer_message(&mut stream).unwrap()
+        {
+            assert_eq!(&block[..], &data[..PIECE]);
+            break;
+        }
+    }
+    // The state is published when the task looks at the next message.
+    PeerMessage::KeepAlive
+        .write_peer_message(&mut stream)
+        .unwrap();
+    let peer = wait_for(&torrent, "upload never counted", |peer| {
+        peer.uploaded == ByteSize(PIECE as u64)
+    });
+    assert!(!peer.flags.am_choking);
+    assert_eq!(peer.downloaded, ByteSize(0));
+
+    std::thread::sleep(Duration::from_millis(10));
+    torrent.sample_peer_rate();
+    let peer = &torrent.peers()[0];
+    assert!(peer.upload_rate.bytes_per_second() > 0);
+    assert_eq!(peer.download_rate.bytes_per_second(), 0);
+
+    drop(stream);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !torrent.peers().is_empty() {
+        assert!(Instant::now() < deadline, "peer was never forgotten");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
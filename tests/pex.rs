@@ -0,0 +1,59 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4};
+
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::peer::connector::{DialTarget, prioritize};
+use bittorrent_client::peer::pex::{PexFlags, PexMessage, PexPeer};
+
+fn last_octet(ip: IpAddr) -> u8 {
+    match ip {
+        IpAddr::V4(ip) => ip.octets()[3],
+        IpAddr::V6(ip) => ip.octets()[15],
+    }
+}
+
+fn pex_peer(last_octet: u8, flags: u8) -> PexPeer {
+    PexPeer {
+        addr: SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, last_octet), 6881),
+        flags: PexFlags(flags),
+    }
+}
+
+#[test]
+fn pex_message_round_trips_flags() {
+    let message = PexMessage {
+        added: vec![pex_peer(1, 0x12), pex_peer(2, 0x01)],
+        dropped: vec![SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 3), 51413)],
+    };
+
+    let encoded = message.to_bencode().encode();
+    let (decoded, rest) = parse_value(&encoded).unwrap();
+    assert!(rest.is_empty());
+
+    let decoded = PexMessage::from_bencode(&decoded).unwrap();
+    assert_eq!(decoded, message);
+    assert!(decoded.added[0].flags.contains(PexFlags::SEED));
+    assert!(decoded.added[0].flags.contains(PexFlags::REACHABLE));
+    assert!(!decoded.added[1].flags.contains(PexFlags::SEED));
+}
+
+#[test]
+fn missing_flags_decode_as_empty() {
+    let (value, _) = parse_value(b"d5:added6:\x0a\x00\x00\x01\x1a\xe1e").unwrap();
+    let message = PexMessage::from_bencode(&value).unwrap();
+    assert_eq!(message.added, vec![pex_peer(1, 0)]);
+    assert!(message.dropped.is_empty());
+}
+
+#[test]
+fn dial_candidates_are_ordered_by_hints() {
+    let peers = [pex_peer(1, 0x00), pex_peer(2, 0x02), pex_peer(3, 0x12)];
+    let mut targets: Vec<DialTarget> = peers.iter().map(DialTarget::from).collect();
+
+    prioritize(&mut targets, false);
+    let order: Vec<u8> = targets.iter().map(|t| last_octet(t.ip)).collect();
+    assert_eq!(order, vec![3, 2, 1]);
+
+    prioritize(&mut targets, true);
+    let order: Vec<u8> = targets.iter().map(|t| last_octet(t.ip)).collect();
+    assert_eq!(order, vec![1, 3, 2]);
+}
@@ -0,0 +1,123 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use bittorrent_client::piece::bitfield::Bitfield;
+use bittorrent_client::piece::picker::PiecePicker;
+use bittorrent_client::piece::strategy::{PickStrategy, PickView, Random, RarestFirst, Sequential};
+
+fn peer(last: u8) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::new(127, 0, 0, last), 6881))
+}
+
+fn bitfield(len: usize, pieces: &[usize]) -> Bitfield {
+    let mut bitfield = Bitfield::new(len);
+    for &index in pieces {
+        bitfield.set(index);
+    }
+    bitfield
+}
+
+#[test]
+fn shipped_strategies_order_pieces() {
+    let mut picker = PiecePicker::new(6);
+    picker.add_peer_bitfield(&Bitfield::full(6));
+    picker.add_peer_bitfield(&bitfield(6, &[0, 1, 2, 3]));
+    picker.add_peer_bitfield(&bitfield(6, &[0, 1]));
+    picker.set_strategy(Box::new(RarestFirst));
+    assert_eq!(
+        picker.pick_up_to(peer(1), &Bitfield::full(6), 3),
+        vec![4, 5, 2]
+    );
+
+    let mut picker = PiecePicker::new(6);
+    picker.set_strategy(Box::new(Sequential::default()));
+    picker.on_piece_done(1);
+    assert_eq!(
+        picker.pick_up_to(peer(1), &Bitfield::full(6), 2),
+        vec![0, 2]
+    );
+    // Each peer carries on where it left off, wrapping around.
+    assert_eq!(
+        picker.pick_up_to(peer(1), &bitfield(6, &[0, 3, 5]), 5),
+        vec![3, 5]
+    );
+
+    let mut picker = PiecePicker::new(50);
+    picker.set_strategy(Box::new(Random));
+    let peer_has = bitfield(50, &[3, 9, 17, 30, 41]);
+    let mut picked = picker.pick_up_to(peer(1), &peer_has, 10);
+    picked.sort();
+    assert_eq!(picked, vec![3, 9, 17, 30, 41]);
+    assert!(picker.pick(peer(1), &peer_has).is_none());
+}
+
+// Newest-first, recording the events it's told about.
+#[derive(Default)]
+struct Deadline {
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+impl PickStrategy for Deadline {
+    fn next_pieces(
+        &mut self,
+        view: &PickView,
+        _peer: SocketAddr,
+        candidates: &Bitfield,
+        n: usize,
+    ) -> Vec<usize> {
+        // Also asks for a piece we already have, and one twice; the picker drops both.
+        let mut pieces: Vec<usize> = (0..candidates.len())
+            .rev()
+            .filter(|&index| candidates.has(index))
+            .take(n)
+            .collect();
+        if let Some(have) = (0..view.have.len()).find(|&index| view.have.has(index)) {
+            pieces.insert(0, have);
+        }
+        if let Some(&first) = pieces.last() {
+            pieces.push(first);
+        }
+        pieces
+    }
+
+    fn on_have(&mut self, index: usize) {
+        self.events.lock().unwrap().push(format!("have {}", index));
+    }
+
+    fn on_piece_done(&mut self, index: usize) {
+        self.events.lock().unwrap().push(format!("done {}", index));
+    }
+
+    fn on_choke(&mut self, peer: SocketAddr) {
+        self.events.lock().unwrap().push(format!("choke {}", peer));
+    }
+}
+
+#[test]
+fn custom_strategies_plug_in() {
+    let strategy = Deadline::default();
+    let events = Arc::clone(&strategy.events);
+    let mut picker = PiecePicker::new(5);
+    picker.set_strategy(Box::new(strategy));
+    assert!(picker.has_custom_strategy());
+
+    picker.on_piece_done(0);
+    let all = Bitfield::full(5);
+    assert_eq!(picker.pick_up_to(peer(1), &all, 2), vec![4, 3]);
+
+    // Pieces the peer got wrong are held back while there's anything else.
+    picker.avoid(2, [peer(1)]);
+    assert_eq!(picker.pick(peer(1), &all), Some(1));
+    assert_eq!(picker.pick(peer(1), &all), Some(2));
+    assert_eq!(picker.pick(peer(1), &all), None);
+
+    picker.add_have(4, &bitfield(5, &[4]));
+    picker.on_choke(peer(1));
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec!["done 0", "have 4", "choke 127.0.0.1:6881"]
+    );
+
+    picker.clear_strategy();
+    assert!(!picker.has_custom_strategy());
+}
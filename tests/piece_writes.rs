@@ -0,0 +1,63 @@
+use std::io::{IoSlice, Write};
+
+use bittorrent_client::peer::value::{PeerMessage, write_piece};
+
+// Takes at most `limit` bytes per call and counts the calls.
+struct Trickle {
+    data: Vec<u8>,
+    limit: usize,
+    calls: usize,
+}
+
+impl Write for Trickle {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_vectored(&[IoSlice::new(buf)])
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        self.calls += 1;
+        let mut taken = 0;
+        for buf in bufs {
+            let take = buf.len().min(self.limit - taken);
+            self.data.extend_from_slice(&buf[..take]);
+            taken += take;
+        }
+        Ok(taken)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn piece_writes_match_the_encoded_message() {
+    let block: Vec<u8> = (0..16384).map(|i| (i % 199) as u8).collect();
+    let message = PeerMessage::Piece {
+        index: 7,
+        begin: 16384,
+        block: block.clone().into(),
+    };
+
+    let mut whole = Trickle {
+        data: Vec::new(),
+        limit: usize::MAX,
+        calls: 0,
+    };
+    message.write_peer_message(&mut whole).unwrap();
+    assert_eq!(whole.data, message.to_bytes());
+    // Header and block in a single call.
+    assert_eq!(whole.calls, 1);
+
+    let mut trickle = Trickle {
+        data: Vec::new(),
+        limit: 5,
+        calls: 0,
+    };
+    write_piece(&mut trickle, 7, 16384, &block).unwrap();
+    assert_eq!(trickle.data, message.to_bytes());
+    assert_eq!(
+        PeerMessage::read_peer_message(&mut trickle.data.as_slice()).unwrap(),
+        message
+    );
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::pipeline::{PipelineDepth, PipelineEstimator};
+
+const ADAPTIVE: PipelineDepth = PipelineDepth::Adaptive { min: 4, max: 64 };
+
+#[test]
+fn depth_starts_at_the_minimum() {
+    let estimator = PipelineEstimator::default();
+    assert_eq!(estimator.depth(ADAPTIVE, 16 * 1024), 4);
+    assert_eq!(estimator.depth(PipelineDepth::Fixed(7), 16 * 1024), 7);
+}
+
+#[test]
+fn depth_covers_twice_the_bandwidth_delay_product() {
+    let mut estimator = PipelineEstimator::default();
+    let start = Instant::now();
+    // A 16 KiB block every 25ms, each back 100ms after its request. The first second,
+    // counted from the first request, sees 37 of them.
+    for n in 0..37 {
+        let now = start + Duration::from_millis(100 + 25 * n);
+        estimator.on_block(16 * 1024, now - Duration::from_millis(100), now);
+    }
+    assert_eq!(estimator.min_rtt(), Some(Duration::from_millis(100)));
+    assert!((estimator.rate() - 37.0 * 16.0 * 1024.0).abs() < 1.0);
+    // 3.7 blocks per round trip.
+    assert_eq!(estimator.depth(ADAPTIVE, 16 * 1024), 8);
+    assert_eq!(estimator.depth(ADAPTIVE, 8 * 1024), 15);
+    assert_eq!(
+        estimator.depth(PipelineDepth::Adaptive { min: 4, max: 6 }, 16 * 1024),
+        6
+    );
+    assert_eq!(estimator.depth(PipelineDepth::Fixed(3), 16 * 1024), 3);
+}
+
+#[test]
+fn requests_use_the_configured_block_size() {
+    let data: Vec<u8> = (0..20_000).map(|i| (i * 7) as u8).collect();
+    let meta = common::single_file("block-size", data.len(), &data);
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        block_size: 8 * 1024,
+        pipeline_depth: PipelineDepth::Fixed(2),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-block-size-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+    assert_eq!(torrent.block_size(), 8 * 1024);
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    session
+        .add_peer(&torrent.info_hash(), listener.local_addr().unwrap())
+        .unwrap();
+    let (mut stream, _) = listener.accept().unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut handshake = [0u8; 68];
+    std::io::Read::read_exact(&mut stream, &mut handshake).unwrap();
+    std::io::Write::write_all(
+        &mut stream,
+        &Handshake::new(
+            torrent.info_hash(),
+            *b"-FAKE0-seedseedseeds",
+            ReservedBits::FAST | ReservedBits::V2,
+        )
+        .to_bytes(),
+    )
+    .unwrap();
+    PeerMessage::Bitfield(vec![0x80].into())
+        .write_peer_message(&mut stream)
+        .unwrap();
+    PeerMessage::Unchoke
+        .write_peer_message(&mut stream)
+        .unwrap();
+
+    // Never more than two requests outstanding, each 8 KiB but the last.
+    let mut requests = Vec::new();
+    let mut outstanding = Vec::new();
+    while requests.len() < 3 {
+        match PeerMessage::read_peer_message(&mut stream).unwrap() {
+            PeerMessage::Request { begin, length, .. } => {
+                requests.push((begin, length));
+                outstanding.push((begin, length));
+                assert!(outstanding.len() <= 2);
+            }
+            _ => continue,
+        }
+        if outstanding.len() == 2 || requests.len() == 3 {
+            let (begin, length) = outstanding.remove(0);
+            let start = begin as usize;
+            PeerMessage::Piece {
+                index: 0,
+                begin,
+                block: data[start..start + length as usize].to_vec().into(),
+            }
+            .write_peer_message(&mut stream)
+            .unwrap();
+        }
+    }
+    assert_eq!(requests, vec![(0, 8192), (8192, 8192), (16384, 3616)]);
+    for (begin, length) in outstanding {
+        let start = begin as usize;
+        PeerMessage::Piece {
+            index: 0,
+            begin,
+            block: data[start..start + length as usize].to_vec().into(),
+        }
+        .write_peer_message(&mut stream)
+        .unwrap();
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !torrent.is_complete() {
+        assert!(Instant::now() < deadline, "download didn't finish");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
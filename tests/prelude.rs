@@ -0,0 +1,31 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use bittorrent_client::prelude::*;
+
+#[test]
+fn prelude_is_enough_to_run_a_torrent() {
+    let dir = std::env::temp_dir().join(format!("bt-prelude-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let data = vec![7u8; 16384];
+    std::fs::write(dir.join("prelude.bin"), &data).unwrap();
+    let meta = common::single_file("prelude.bin", 16384, &data);
+
+    let session = Session::new(common::local_config()).unwrap();
+    let torrent: std::sync::Arc<Torrent> = session.add_torrent(meta, &dir).unwrap();
+    let hex: String = (torrent.info_hash().iter())
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let magnet = MagnetLink::parse(&format!("magnet:?xt=urn:btih:{}", hex)).unwrap();
+    assert!(magnet.matches(torrent.meta()));
+    let handle: TorrentHandle = session.handle(&torrent.info_hash()).unwrap();
+    assert_eq!(handle.state(), TorrentState::Seeding);
+    assert!(matches!(
+        session.add_peer(&[0; 20], SocketAddr::from((Ipv4Addr::LOCALHOST, 1))),
+        Err(SessionError::UnknownTorrent(_))
+    ));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
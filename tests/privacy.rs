@@ -0,0 +1,93 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+const PIECE: usize = 16384;
+
+fn seeded_torrent(label: &str, pieces: usize) -> (TorrentMetaInfo, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("bt-privacy-{}-{}", label, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let data: Vec<u8> = (0..pieces * PIECE).map(|i| (i / 7) as u8).collect();
+    std::fs::write(dir.join(label), &data).unwrap();
+    let meta = common::single_file(label, PIECE, &data);
+    (meta, dir)
+}
+
+fn session(privacy_mode: bool) -> Session {
+    Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        privacy_mode,
+        ..SessionConfig::default()
+    })
+    .unwrap()
+}
+
+#[test]
+fn privacy_mode_hides_client_identity() {
+    let session = session(true);
+    let (first, first_dir) = seeded_torrent("first", 1);
+    let (second, second_dir) = seeded_torrent("second", 1);
+    let first = session.add_torrent(first, &first_dir).unwrap();
+    let second = session.add_torrent(second, &second_dir).unwrap();
+
+    let first_id = session.peer_id_for(&first.info_hash());
+    assert_eq!(first_id, session.peer_id_for(&first.info_hash()));
+    assert_ne!(first_id, session.peer_id_for(&second.info_hash()));
+    assert_ne!(first_id, session.peer_id());
+
+    let request = session.tracker_request(&first, None);
+    assert_eq!(request.peer_id, first_id);
+    assert_eq!(request.ip, None);
+    assert_eq!(session.extended_handshake().client, None);
+
+    let open = self::session(false);
+    assert_eq!(open.peer_id_for(&first.info_hash()), open.peer_id());
+    assert!(open.extended_handshake().client.is_some());
+}
+
+#[test]
+fn privacy_mode_sends_a_lazy_bitfield() {
+    let session = session(true);
+    let (meta, dir) = seeded_torrent("lazy", 8);
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+    assert!(torrent.is_complete());
+
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let remote = Handshake::perform_handshake(
+        &mut stream,
+        &torrent.info_hash(),
+        b"-FAKE0-leechleechlee",
+        ReservedBits::FAST | ReservedBits::V2,
+        Duration::from_secs(10),
+    )
+    .unwrap();
+    assert_eq!(remote.peer_id, session.peer_id_for(&torrent.info_hash()));
+
+    let PeerMessage::Bitfield(bytes) = PeerMessage::read_peer_message(&mut stream).unwrap() else {
+        panic!("expected a bitfield");
+    };
+    let mut have = bytes[0];
+    assert_eq!(have.count_ones(), 4);
+    for _ in 0..4 {
+        let PeerMessage::Have { piece_index } =
+            PeerMessage::read_peer_message(&mut stream).unwrap()
+        else {
+            panic!("expected a have");
+        };
+        have |= 0x80 >> piece_index;
+    }
+    assert_eq!(have, 0xff);
+}
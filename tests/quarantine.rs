@@ -0,0 +1,86 @@
+#![cfg(feature = "sim")]
+
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use bittorrent_client::piece::bitfield::Bitfield;
+use bittorrent_client::piece::hash::Sha1Mode;
+use bittorrent_client::piece::picker::{PickerStrictness, PiecePicker};
+use bittorrent_client::session::torrent::Torrent;
+use bittorrent_client::sim::internals::Quarantine;
+
+const BLOCK: usize = 16 * 1024;
+
+fn peer(last: u8) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::new(127, 0, 0, last), 6881))
+}
+
+#[test]
+fn only_senders_of_differing_blocks_are_blamed() {
+    let good: Vec<u8> = (0..3 * BLOCK).map(|i| i as u8).collect();
+    let mut bad = good.clone();
+    bad[BLOCK + 5] ^= 1;
+
+    let mut quarantine = Quarantine::default();
+    quarantine.add_failed(0, &bad, &[peer(1), peer(2), peer(3)]);
+    assert!(quarantine.contains(0));
+    assert_eq!(quarantine.suspects(0).len(), 3);
+
+    let offenders = quarantine.resolve(0, &good);
+    assert_eq!(offenders.into_iter().collect::<Vec<_>>(), vec![peer(2)]);
+    assert!(!quarantine.contains(0));
+}
+
+#[test]
+fn bad_piece_is_quarantined_until_a_good_copy_arrives() {
+    let good: Vec<u8> = (0..2 * BLOCK).map(|i| (i * 7) as u8).collect();
+    let meta = common::single_file("quarantine.bin", good.len(), &good);
+    let dir = std::env::temp_dir().join(format!("bt-quarantine-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let torrent = Torrent::new(meta, &dir, Sha1Mode::Fast);
+
+    let mut bad = good.clone();
+    bad[0] ^= 0xff;
+    assert!(
+        !torrent
+            .store_piece_from(0, &bad, &[peer(1), peer(1)])
+            .unwrap()
+    );
+    assert!(torrent.is_quarantined(0));
+    assert_eq!(torrent.hash_failures(peer(1).ip()), 0);
+
+    assert!(
+        torrent
+            .store_piece_from(0, &good, &[peer(2), peer(2)])
+            .unwrap()
+    );
+    assert!(!torrent.is_quarantined(0));
+    assert_eq!(torrent.hash_failures(peer(1).ip()), 1);
+    assert_eq!(torrent.hash_failures(peer(2).ip()), 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn failed_piece_is_picked_from_someone_else_first() {
+    let all = Bitfield::full(2);
+    for strictness in [
+        PickerStrictness::RarestFirst,
+        PickerStrictness::SeedFriendly,
+    ] {
+        let mut picker = PiecePicker::new(2);
+        picker.set_strictness(strictness);
+        picker.add_peer_bitfield(&all);
+        picker.avoid(0, [peer(1)]);
+
+        assert_eq!(picker.pick(peer(1), &all), Some(1));
+        // With nothing else left, the same peer may try again.
+        assert_eq!(picker.pick(peer(1), &all), Some(0));
+
+        let mut picker = PiecePicker::new(2);
+        picker.set_strictness(strictness);
+        picker.avoid(0, [peer(1)]);
+        assert_eq!(picker.pick(peer(2), &all), Some(0));
+    }
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+// A complete single-file torrent on disk; `fill` makes each info hash distinct.
+fn seeded_torrent(dir: &Path, fill: u8) -> TorrentMetaInfo {
+    let name = format!("queued-{}.bin", fill);
+    let data = vec![fill; 500];
+    std::fs::write(dir.join(&name), &data).unwrap();
+    common::single_file(&name, 16384, &data)
+}
+
+#[test]
+fn seeds_beyond_the_limit_wait_and_are_promoted() {
+    let dir = std::env::temp_dir().join(format!("bt-queue-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        max_active_seeds: Some(1),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+
+    let first = session.add_torrent(seeded_torrent(&dir, 1), &dir).unwrap();
+    let second = session.add_torrent(seeded_torrent(&dir, 2), &dir).unwrap();
+    let third = session.add_torrent(seeded_torrent(&dir, 3), &dir).unwrap();
+    assert!(first.is_active());
+    assert!(second.is_queued());
+    assert!(third.is_queued());
+
+    assert!(session.set_queue_position(&third.info_hash(), 0));
+    assert_eq!(session.queue_position(&third.info_hash()), Some(0));
+    assert!(third.is_active());
+    assert!(first.is_queued());
+
+    third.pause();
+    session.update_queue();
+    assert!(first.is_active());
+    assert!(second.is_queued());
+
+    session.remove_torrent(&first.info_hash(), false).unwrap();
+    assert!(second.is_active());
+    assert_eq!(session.queue_position(&second.info_hash()), Some(1));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
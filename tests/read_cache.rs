@@ -0,0 +1,180 @@
+mod common;
+
+use std::cell::Cell;
+use std::net::TcpStream;
+use std::time::Duration;
+
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::storage::read_cache::{CacheStats, ReadCache};
+use bittorrent_client::units::ByteSize;
+
+const PIECE: usize = 32 * 1024;
+
+#[test]
+fn least_recently_used_pieces_go_first() {
+    let cache = ReadCache::new(250);
+    let loads = Cell::new(0);
+    let get = |index: usize| {
+        cache
+            .get_or_load([1; 20], index, || {
+                loads.set(loads.get() + 1);
+                Ok(vec![index as u8; 100])
+            })
+            .unwrap()
+    };
+
+    assert_eq!(get(0)[0], 0);
+    get(1);
+    get(0);
+    // Only two pieces fit; piece 1 was used least recently.
+    get(2);
+    assert_eq!(loads.get(), 3);
+    get(0);
+    get(2);
+    assert_eq!(loads.get(), 3);
+    get(1);
+    assert_eq!(loads.get(), 4);
+    assert_eq!(
+        cache.stats(),
+        CacheStats {
+            hits: 3,
+            misses: 4,
+            cached_bytes: 200,
+        }
+    );
+
+    cache.remove_torrent(&[1; 20]);
+    assert_eq!(cache.stats().cached_bytes, 0);
+    assert!(
+        cache
+            .get_or_load([2; 20], 0, || Err(std::io::ErrorKind::NotFound.into()))
+            .is_err()
+    );
+}
+
+#[test]
+fn uploads_read_each_piece_once() {
+    let dir = std::env::temp_dir().join(format!("bt-read-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let data: Vec<u8> = (0..2 * PIECE).map(|i| (i % 241) as u8).collect();
+    std::fs::write(dir.join("cache.bin"), &data).unwrap();
+    let meta = common::single_file("cache.bin", PIECE, &data);
+
+    let session = Session::new(common::local_config()).unwrap();
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    Handshake::perform_handshake(
+        &mut stream,
+        &torrent.info_hash(),
+        b"-FAKE0-cachecachecac",
+        ReservedBits::FAST | ReservedBits::V2,
+        Duration::from_secs(10),
+    )
+    .unwrap();
+    PeerMessage::Interested
+        .write_peer_message(&mut stream)
+        .unwrap();
+    loop {
+        if PeerMessage::read_peer_message(&mut stream).unwrap() == PeerMessage::Unchoke {
+            break;
+        }
+    }
+
+    // Both blocks of piece 1, then the first again.
+    for begin in [0, 16384, 0] {
+        PeerMessage::Request {
+            index: 1,
+            begin,
+            length: 16384,
+        }
+        .write_peer_message(&mut stream)
+        .unwrap();
+        let PeerMessage::Piece { block, .. } = PeerMessage::read_peer_message(&mut stream).unwrap()
+        else {
+            panic!("expected a piece");
+        };
+        let start = PIECE + begin as usize;
+        assert_eq!(block, data[start..start + 16384]);
+    }
+
+    assert_eq!(
+        session.read_cache_stats(),
+        CacheStats {
+            hits: 2,
+            misses: 1,
+            cached_bytes: PIECE as u64,
+        }
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// Whether the peer answers a request with the block or a reject.
+fn answer(stream: &mut TcpStream, index: u32, begin: u32, length: u32) -> Option<Vec<u8>> {
+    PeerMessage::Request {
+        index,
+        begin,
+        length,
+    }
+    .write_peer_message(stream)
+    .unwrap();
+    loop {
+        match PeerMessage::read_peer_message(stream).unwrap() {
+            PeerMessage::Piece { block, .. } => return Some(block.to_vec()),
+            PeerMessage::RejectRequest { .. } => return None,
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn requests_past_the_piece_end_are_rejected() {
+    let data: Vec<u8> = (0..2 * PIECE - 1000).map(|i| (i % 239) as u8).collect();
+    for cache_size in [0, 1 << 20] {
+        let dir = common::temp_dir(&format!("read-bounds-{}", cache_size));
+        std::fs::write(dir.join("bounds.bin"), &data).unwrap();
+        let meta = common::single_file("bounds.bin", PIECE, &data);
+        let mut config = common::local_config();
+        config.read_cache_size = ByteSize(cache_size);
+        let session = Session::new(config).unwrap();
+        let torrent = session.add_torrent(meta, &dir).unwrap();
+
+        let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        Handshake::perform_handshake(
+            &mut stream,
+            &torrent.info_hash(),
+            b"-FAKE0-boundsbounds0",
+            ReservedBits::FAST,
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        PeerMessage::Interested
+            .write_peer_message(&mut stream)
+            .unwrap();
+        loop {
+            if PeerMessage::read_peer_message(&mut stream).unwrap() == PeerMessage::Unchoke {
+                break;
+            }
+        }
+
+        // Runs from the end of piece 0 into piece 1.
+        let half = PIECE as u32 - 8192;
+        assert_eq!(answer(&mut stream, 0, half, 16384), None);
+        // Runs past the end of the short last piece.
+        assert_eq!(answer(&mut stream, 1, PIECE as u32 - 16384, 16384), None);
+        assert_eq!(
+            answer(&mut stream, 0, half, 8192).unwrap(),
+            data[half as usize..PIECE]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::path::PathBuf;
+
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+const PIECE: usize = 100;
+
+fn session() -> Session {
+    Session::new(common::local_config()).unwrap()
+}
+
+// Three files of 150, 100 and 250 bytes over five 100-byte pieces: a.bin is in pieces
+// 0-1, b.bin in 1-2 and c.bin in 2-4.
+fn meta(name: &str, data: &[u8]) -> TorrentMetaInfo {
+    let files = vec![
+        common::file(&["a.bin"], 150),
+        common::file(&["b.bin"], 100),
+        common::file(&["c.bin"], 250),
+    ];
+    common::multi_file(name, PIECE, files, data)
+}
+
+fn setup(name: &str) -> (PathBuf, Vec<u8>) {
+    let dir = std::env::temp_dir().join(format!("bt-recheck-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join(name)).unwrap();
+    let data: Vec<u8> = (0..500).map(|i| (i * 7) as u8).collect();
+    std::fs::write(dir.join(name).join("a.bin"), &data[..150]).unwrap();
+    std::fs::write(dir.join(name).join("b.bin"), &data[150..250]).unwrap();
+    std::fs::write(dir.join(name).join("c.bin"), &data[250..]).unwrap();
+    (dir, data)
+}
+
+#[test]
+fn edited_file_loses_only_its_bad_pieces() {
+    let (dir, data) = setup("recheck-edit");
+    let session = session();
+    let torrent = session
+        .add_torrent(meta("recheck-edit", &data), &dir)
+        .unwrap();
+    assert!(torrent.is_complete());
+
+    // Only the tail of c.bin changes, which sits in piece 4.
+    let mut edited = data[250..].to_vec();
+    edited[200] ^= 0xff;
+    let path = dir.join("recheck-edit").join("c.bin");
+    std::fs::write(&path, &edited).unwrap();
+
+    assert_eq!(torrent.recheck_file(2).unwrap(), vec![4]);
+    assert!(!torrent.is_complete());
+    assert!(torrent.seeding_time().is_none());
+    assert!((0..4).all(|index| torrent.has_piece(index)));
+    assert!(!torrent.has_piece(4));
+
+    // A file that's still fine costs nothing.
+    assert!(torrent.recheck_file(0).unwrap().is_empty());
+    assert!(!torrent.has_piece(4));
+
+    // Putting the original back is picked up by the next recheck.
+    std::fs::write(&path, &data[250..]).unwrap();
+    assert!(torrent.recheck_file(2).unwrap().is_empty());
+    assert!(torrent.is_complete());
+    assert!(torrent.seeding_time().is_some());
+}
+
+#[test]
+fn replaced_file_shared_pieces_go_too() {
+    let (dir, data) = setup("recheck-shared");
+    let session = session();
+    let torrent = session
+        .add_torrent(meta("recheck-shared", &data), &dir)
+        .unwrap();
+
+    std::fs::write(dir.join("recheck-shared").join("b.bin"), [0u8; 100]).unwrap();
+    assert_eq!(torrent.recheck_file(1).unwrap(), vec![1, 2]);
+    assert_eq!((0..5).filter(|&index| torrent.has_piece(index)).count(), 3);
+    assert!(torrent.recheck_file(3).is_err());
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::error::SessionError;
+use bittorrent_client::session::event::SessionEvent;
+use bittorrent_client::torrent::value::{File, TorrentMetaInfo};
+use bittorrent_client::tracker::value::Event;
+
+fn session() -> Session {
+    Session::new(common::local_config()).unwrap()
+}
+
+fn meta(name: &str, announce: String, files: Vec<File>, data: &[u8]) -> TorrentMetaInfo {
+    TorrentMetaInfo {
+        announce,
+        ..common::multi_file(name, data.len(), files, data)
+    }
+}
+
+#[test]
+fn deleting_data_leaves_other_files_alone() {
+    let dir = std::env::temp_dir().join(format!("bt-remove-data-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let root = dir.join("remove-data");
+    std::fs::create_dir_all(root.join("sub")).unwrap();
+    std::fs::create_dir_all(root.join("extra")).unwrap();
+    let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+    std::fs::write(root.join("a.bin"), &data[..100]).unwrap();
+    std::fs::write(root.join("sub").join("b.bin"), &data[100..]).unwrap();
+    std::fs::write(root.join("extra").join("notes.txt"), b"mine").unwrap();
+    // Next to the torrent's directory but not part of it.
+    std::fs::write(dir.join("outside.bin"), b"keep").unwrap();
+
+    let session = session();
+    let events = session.subscribe();
+    let files = vec![
+        common::file(&["a.bin"], 100),
+        common::file(&["sub", "b.bin"], 200),
+    ];
+    let torrent = session
+        .add_torrent(
+            meta("remove-data", "http://127.0.0.1:1/a".into(), files, &data),
+            &dir,
+        )
+        .unwrap();
+    assert!(torrent.is_complete());
+
+    session.remove_torrent(&torrent.info_hash(), true).unwrap();
+    assert!(session.torrent(&torrent.info_hash()).is_none());
+    assert!(!root.join("a.bin").exists());
+    assert!(!root.join("sub").exists());
+    assert!(root.join("extra").join("notes.txt").exists());
+    assert!(dir.join("outside.bin").exists());
+    let removed = std::iter::from_fn(|| events.recv_timeout(Duration::from_secs(5)).ok())
+        .find(|event| matches!(event, SessionEvent::TorrentRemoved { .. }));
+    assert_eq!(
+        removed,
+        Some(SessionEvent::TorrentRemoved {
+            info_hash: torrent.info_hash(),
+            data_deleted: true,
+        })
+    );
+
+    assert!(matches!(
+        session.remove_torrent(&torrent.info_hash(), true),
+        Err(SessionError::UnknownTorrent(_))
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn trackers_hear_the_torrent_stopped() {
+    let tracker = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let announce = format!("http://{}/announce", tracker.local_addr().unwrap());
+    let (requests, received) = mpsc::channel();
+    thread::spawn(move || {
+        let body: &[u8] = b"d8:intervali900e5:peerslee";
+        for stream in tracker.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = [0u8; 4096];
+            let read = stream.read(&mut request).unwrap();
+            let line = String::from_utf8_lossy(&request[..read]);
+            let _ = requests.send(line.lines().next().unwrap_or_default().to_string());
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(body).unwrap();
+        }
+    });
+
+    let dir = std::env::temp_dir().join(format!("bt-remove-stopped-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let data = vec![7u8; 100];
+    let session = session();
+    let torrent = session
+        .add_torrent(
+            meta(
+                "remove-stopped",
+                announce,
+                vec![common::file(&["x"], 100)],
+                &data,
+            ),
+            &dir,
+        )
+        .unwrap();
+    session.announce(&torrent, Some(Event::Started)).unwrap();
+    let started = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(started.contains("event=started"), "{}", started);
+
+    session.remove_torrent(&torrent.info_hash(), false).unwrap();
+    let stopped = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(stopped.contains("event=stopped"), "{}", stopped);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::resume::ResumeData;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+fn session() -> Session {
+    Session::new(common::local_config()).unwrap()
+}
+
+fn meta(name: &str, data: &[u8]) -> TorrentMetaInfo {
+    let files = vec![
+        common::file(&["a.bin"], 100),
+        common::file(&["sub", "b.bin"], 200),
+    ];
+    common::multi_file(name, data.len(), files, data)
+}
+
+// A download directory holding the complete torrent `name`.
+fn setup(test: &str, name: &str) -> (PathBuf, Vec<u8>) {
+    let dir = std::env::temp_dir().join(format!("bt-rename-{}-{}", test, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join(name).join("sub")).unwrap();
+    let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+    std::fs::write(dir.join(name).join("a.bin"), &data[..100]).unwrap();
+    std::fs::write(dir.join(name).join("sub").join("b.bin"), &data[100..]).unwrap();
+    (dir, data)
+}
+
+fn read(path: &Path) -> Vec<u8> {
+    std::fs::read(path).unwrap()
+}
+
+#[test]
+fn renamed_files_move_on_disk_and_keep_serving() {
+    let (dir, data) = setup("move", "show-raw");
+    let session = session();
+    let torrent = session.add_torrent(meta("show-raw", &data), &dir).unwrap();
+    assert!(torrent.is_complete());
+
+    torrent
+        .rename_file(1, Path::new("show-raw/Season 1/Episode 1.bin"))
+        .unwrap();
+    assert!(!dir.join("show-raw").join("sub").exists());
+    assert_eq!(
+        read(&dir.join("show-raw").join("Season 1").join("Episode 1.bin")),
+        &data[100..]
+    );
+    assert_eq!(torrent.storage().read(0, 300).unwrap(), data);
+
+    torrent.rename_root("Show").unwrap();
+    assert!(!dir.join("show-raw").exists());
+    assert_eq!(read(&dir.join("Show").join("a.bin")), &data[..100]);
+    assert_eq!(
+        read(&dir.join("Show").join("Season 1").join("Episode 1.bin")),
+        &data[100..]
+    );
+    assert_eq!(torrent.storage().read(0, 300).unwrap(), data);
+    assert_eq!(torrent.storage().root_name(), PathBuf::from("Show"));
+}
+
+#[test]
+fn unsafe_or_taken_paths_are_refused() {
+    let (dir, data) = setup("refused", "refused");
+    std::fs::write(dir.join("mine.txt"), b"keep").unwrap();
+    let session = session();
+    let torrent = session.add_torrent(meta("refused", &data), &dir).unwrap();
+
+    assert!(torrent.rename_file(0, Path::new("../a.bin")).is_err());
+    assert!(torrent.rename_file(0, Path::new("/tmp/a.bin")).is_err());
+    assert!(
+        torrent
+            .rename_file(0, Path::new("refused/sub/b.bin"))
+            .is_err()
+    );
+    assert!(torrent.rename_file(0, Path::new("mine.txt")).is_err());
+    assert!(torrent.rename_file(5, Path::new("x.bin")).is_err());
+    assert!(torrent.rename_root("a/b").is_err());
+    assert!(torrent.rename_root("..").is_err());
+
+    assert_eq!(read(&dir.join("mine.txt")), b"keep");
+    assert_eq!(read(&dir.join("refused").join("a.bin")), &data[..100]);
+    assert_eq!(torrent.storage().read(0, 300).unwrap(), data);
+}
+
+#[test]
+fn names_survive_in_resume_data() {
+    let (dir, data) = setup("resume", "resume-raw");
+    let session = session();
+    let torrent = session
+        .add_torrent(meta("resume-raw", &data), &dir)
+        .unwrap();
+    torrent.rename_root("Album").unwrap();
+    torrent
+        .rename_file(0, Path::new("Album/01 Intro.bin"))
+        .unwrap();
+
+    let resume = torrent.resume_data(Duration::from_secs(30));
+    assert_eq!(resume.root_name, Some(PathBuf::from("Album")));
+    assert_eq!(resume.renamed.len(), 2);
+    let decoded = ResumeData::from_bencode(&resume.to_bencode()).unwrap();
+    assert_eq!(decoded, resume);
+    drop(torrent);
+    drop(session);
+
+    // A fresh session finds nothing at the torrent's own paths until the names come back.
+    let session = self::session();
+    let torrent = session
+        .add_torrent(meta("resume-raw", &data), &dir)
+        .unwrap();
+    assert!(!torrent.is_complete());
+    assert!(torrent.restore(&decoded, false, Duration::from_secs(30)));
+    assert!(torrent.is_complete());
+    assert_eq!(torrent.storage().read(0, 300).unwrap(), data);
+    assert_eq!(read(&dir.join("Album").join("01 Intro.bin")), &data[..100]);
+}
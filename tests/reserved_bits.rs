@@ -0,0 +1,45 @@
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::Handshake;
+
+#[test]
+fn named_flags_sit_where_the_beps_put_them() {
+    assert_eq!(
+        ReservedBits::EXTENSION_PROTOCOL.to_bytes(),
+        [0, 0, 0, 0, 0, 0x10, 0, 0]
+    );
+    assert_eq!(ReservedBits::FAST.to_bytes(), [0, 0, 0, 0, 0, 0, 0, 0x04]);
+    assert_eq!(ReservedBits::DHT.to_bytes(), [0, 0, 0, 0, 0, 0, 0, 0x01]);
+    assert_eq!(ReservedBits::V2.to_bytes(), [0, 0, 0, 0, 0, 0, 0, 0x10]);
+}
+
+#[test]
+fn sets_combine_and_test_by_flag() {
+    let mut bits = ReservedBits::FAST | ReservedBits::DHT;
+    assert!(bits.contains(ReservedBits::FAST));
+    assert!(!bits.contains(ReservedBits::FAST | ReservedBits::V2));
+    bits.insert(ReservedBits::EXTENSION_PROTOCOL);
+    bits.remove(ReservedBits::DHT);
+    assert!(bits.supports_extension_protocol() && bits.supports_fast());
+    assert!(!bits.supports_dht() && !bits.supports_v2());
+    assert_eq!(bits & ReservedBits::FAST, ReservedBits::FAST);
+    assert_eq!(ReservedBits::default(), ReservedBits::empty());
+}
+
+#[test]
+fn remote_capabilities_come_out_of_from_bytes() {
+    let info_hash = [3; 20];
+    let mut raw = [0u8; 8];
+    raw[0] = 0x80;
+    let sent = ReservedBits::from_bytes(raw) | ReservedBits::EXTENSION_PROTOCOL | ReservedBits::V2;
+    let bytes = Handshake::new(info_hash, *b"-FAKE0-remoteremote0", sent).to_bytes();
+    let remote = Handshake::from_bytes(&bytes, &info_hash, b"-FAKE0-ourselvesours").unwrap();
+
+    assert_eq!(remote.reserved, sent);
+    assert!(remote.supports_extension_protocol() && remote.supports_v2());
+    assert!(!remote.supports_fast() && !remote.supports_dht());
+    assert_eq!(remote.reserved.unknown().to_bytes(), raw);
+    assert_eq!(
+        format!("{:?}", remote.reserved),
+        "ReservedBits(extension protocol | v2 | 8000000000000000)"
+    );
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use bittorrent_client::retry::{BreakerState, CircuitBreaker, RetryPolicy};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+
+fn policy(jitter: f64) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_secs(2),
+        max_backoff: Duration::from_secs(60),
+        jitter,
+    }
+}
+
+#[test]
+fn jitter_only_ever_shortens_the_delay() {
+    let jittery = policy(0.5);
+    assert_eq!(jittery.backoff(1), Duration::from_secs(2));
+    assert_eq!(jittery.backoff(3), Duration::from_secs(8));
+    assert_eq!(jittery.backoff(10), Duration::from_secs(60));
+    for retry in 1..8 {
+        let delay = jittery.jittered(retry);
+        assert!(delay <= jittery.backoff(retry), "{:?}", delay);
+        assert!(delay >= jittery.backoff(retry) / 2, "{:?}", delay);
+    }
+    assert_eq!(policy(0.0).jittered(2), Duration::from_secs(4));
+}
+
+#[test]
+fn breaker_opens_after_max_attempts_and_half_opens_later() {
+    let policy = policy(0.0);
+    let now = Instant::now();
+    let mut breaker = CircuitBreaker::new();
+    assert!(breaker.allows(now));
+
+    breaker.on_failure(&policy, now);
+    assert_eq!(breaker.state(&policy, now), BreakerState::Closed);
+    assert!(!breaker.allows(now + Duration::from_secs(1)));
+    assert!(breaker.allows(now + Duration::from_secs(2)));
+
+    breaker.on_failure(&policy, now);
+    assert_eq!(breaker.retry_at(), Some(now + Duration::from_secs(4)));
+    breaker.on_failure(&policy, now);
+    assert_eq!(breaker.failures(), 3);
+    assert_eq!(breaker.state(&policy, now), BreakerState::Open);
+    assert_eq!(
+        breaker.state(&policy, now + Duration::from_secs(60)),
+        BreakerState::HalfOpen
+    );
+
+    breaker.on_success();
+    assert_eq!(breaker.failures(), 0);
+    assert_eq!(breaker.state(&policy, now), BreakerState::Closed);
+    assert!(breaker.allows(now));
+}
+
+#[test]
+fn failing_trackers_are_skipped_until_their_backoff_ends() {
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        tracker_retry: RetryPolicy {
+            max_attempts: 2,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::from_secs(3600),
+            jitter: 0.0,
+        },
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-retry-{}", std::process::id()));
+    let meta = common::unverified(common::ANNOUNCE.to_string(), "retry.bin");
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    // The first failure only delays the next try, here not at all.
+    assert!(session.announce(&torrent, None).is_err());
+    assert_eq!(torrent.trackers()[0].breaker.failures(), 1);
+    assert!(session.announce(&torrent, None).is_err());
+    assert_eq!(torrent.trackers()[0].history.len(), 2);
+
+    // Two in a row open the breaker: the tracker isn't contacted at all.
+    let err = session.announce(&torrent, None).unwrap_err();
+    assert!(err.to_string().contains("backing off"), "{}", err);
+    assert_eq!(torrent.trackers()[0].history.len(), 2);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::event::SessionEvent;
+use bittorrent_client::session::resume::ResumeData;
+use bittorrent_client::session::schedule::{ScheduledAction, TorrentSchedule};
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+fn setup(label: &str) -> (PathBuf, TorrentMetaInfo) {
+    let root = std::env::temp_dir().join(format!("bt-schedule-{}-{}", label, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("data")).unwrap();
+
+    let data: Vec<u8> = (0..16384).map(|i| (i % 239) as u8).collect();
+    std::fs::write(root.join("data").join("schedule.bin"), &data).unwrap();
+    let meta = common::single_file("schedule.bin", 16384, &data);
+    (root, meta)
+}
+
+fn session(state_dir: Option<&Path>) -> Session {
+    Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        state_dir: state_dir.map(Path::to_path_buf),
+        tick_interval: Duration::from_secs(3600),
+        ..SessionConfig::default()
+    })
+    .unwrap()
+}
+
+#[test]
+fn due_times_are_taken_once() {
+    let now = SystemTime::now();
+    let mut schedule = TorrentSchedule {
+        start_at: Some(now - Duration::from_secs(1)),
+        stop_at: Some(now + Duration::from_secs(60)),
+    };
+    assert_eq!(schedule.take_due(now), vec![ScheduledAction::Start]);
+    assert_eq!(schedule.take_due(now), vec![]);
+    assert_eq!(
+        schedule.take_due(now + Duration::from_secs(60)),
+        vec![ScheduledAction::Stop]
+    );
+    assert!(schedule.is_empty());
+}
+
+#[test]
+fn tick_starts_and_stops_scheduled_torrents() {
+    let (root, meta) = setup("tick");
+    let session = session(None);
+    let events = session.subscribe();
+    let torrent = session.add_torrent(meta, &root.join("data")).unwrap();
+
+    torrent.schedule_start(SystemTime::now() + Duration::from_secs(3600));
+    assert!(torrent.is_paused());
+    session.tick();
+    assert!(torrent.is_paused());
+
+    torrent.schedule_start(SystemTime::now() - Duration::from_secs(1));
+    torrent.schedule_stop_after(Duration::from_secs(3600));
+    session.tick();
+    assert!(!torrent.is_paused());
+    assert_eq!(torrent.schedule().start_at, None);
+    assert!(torrent.schedule().stop_at.is_some());
+
+    torrent.schedule_stop(SystemTime::now() - Duration::from_secs(1));
+    session.tick();
+    assert!(torrent.is_paused());
+    assert!(torrent.schedule().is_empty());
+
+    let actions: Vec<ScheduledAction> = events
+        .try_iter()
+        .filter_map(|event| match event {
+            SessionEvent::Scheduled { action, .. } => Some(action),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(actions, vec![ScheduledAction::Start, ScheduledAction::Stop]);
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn schedule_survives_a_restart() {
+    let (root, meta) = setup("restart");
+    let state_dir = root.join("state");
+    let start_at = UNIX_EPOCH + Duration::from_secs(4_000_000_000);
+
+    let first = session(Some(&state_dir));
+    let torrent = first.add_torrent(meta.clone(), &root.join("data")).unwrap();
+    torrent.schedule_start(start_at);
+    let resume = torrent.resume_data(Duration::from_secs(30));
+    assert_eq!(
+        ResumeData::from_bencode(&resume.to_bencode()).unwrap(),
+        resume
+    );
+    drop(torrent);
+    drop(first);
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while state_dir.join("session.lock").exists() {
+        assert!(Instant::now() < deadline, "lock file was not released");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let second = session(Some(&state_dir));
+    let torrent = second.add_torrent(meta, &root.join("data")).unwrap();
+    assert_eq!(torrent.schedule().start_at, Some(start_at));
+    assert!(torrent.is_paused());
+
+    let _ = std::fs::remove_dir_all(&root);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::thread;
+
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::tracker::client::parse_scrape_response;
+use bittorrent_client::tracker::value::{ScrapeStats, scrape_url};
+
+fn scrape_body(info_hash: &[u8; 20]) -> Vec<u8> {
+    let mut body = b"d5:filesd20:".to_vec();
+    body.extend_from_slice(info_hash);
+    body.extend_from_slice(b"d8:completei5e10:downloadedi50e10:incompletei10eeee");
+    body
+}
+
+#[test]
+fn scrape_urls_follow_the_announce_url() {
+    assert_eq!(
+        scrape_url("http://example.com/announce").as_deref(),
+        Some("http://example.com/scrape")
+    );
+    assert_eq!(
+        scrape_url("http://example.com/x/announce.php?passkey=1").as_deref(),
+        Some("http://example.com/x/scrape.php?passkey=1")
+    );
+    assert_eq!(scrape_url("http://example.com/a"), None);
+    assert_eq!(scrape_url("http://example.com/announce/x"), None);
+}
+
+#[test]
+fn binary_info_hashes_are_parsed() {
+    let info_hash = [0xffu8; 20];
+    let response = parse_scrape_response(&scrape_body(&info_hash)).unwrap();
+    assert_eq!(
+        response.files[&info_hash],
+        ScrapeStats {
+            complete: 5,
+            downloaded: 50,
+            incomplete: 10,
+        }
+    );
+
+    let failure = parse_scrape_response(b"d14:failure reason6:no wayee").unwrap_err();
+    assert_eq!(failure.to_string(), "no way");
+    assert!(parse_scrape_response(b"d5:filesd").is_err());
+}
+
+#[test]
+fn snatch_count_shows_up_per_tracker() {
+    let meta = |announce: String| common::unverified(announce, "scrape.bin");
+
+    let tracker = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let announce = format!("http://{}/announce", tracker.local_addr().unwrap());
+    let body = scrape_body(&meta(announce.clone()).info_hash());
+    let server = thread::spawn(move || {
+        let (mut stream, _) = tracker.accept().unwrap();
+        let mut request = [0u8; 4096];
+        let read = stream.read(&mut request).unwrap();
+        let header = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(header.as_bytes()).unwrap();
+        stream.write_all(&body).unwrap();
+        String::from_utf8_lossy(&request[..read]).into_owned()
+    });
+
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-scrape-{}", std::process::id()));
+    let torrent = session.add_torrent(meta(announce), &dir).unwrap();
+
+    let stats = session.scrape(&torrent).unwrap();
+    assert_eq!(stats.downloaded, 50);
+    assert!(server.join().unwrap().starts_with("GET /scrape?info_hash="));
+
+    let tracker = &torrent.trackers()[0];
+    assert_eq!(tracker.downloaded, Some(50));
+    assert_eq!(tracker.seeders, Some(5));
+    assert_eq!(tracker.leechers, Some(10));
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::event::SessionEvent;
+use bittorrent_client::session::seeding::{GoalAction, SeedingGoal, SeedingGoals};
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+fn seeded_torrent(label: &str) -> (TorrentMetaInfo, std::path::PathBuf) {
+    let dir = std::env::temp_dir().join(format!("bt-seeding-{}-{}", label, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let data = vec![7u8; 1000];
+    std::fs::write(dir.join("seed.bin"), &data).unwrap();
+    let meta = common::single_file("seed.bin", 16384, &data);
+    (meta, dir)
+}
+
+fn session(goals: SeedingGoals) -> Session {
+    Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        seeding_goals: goals,
+        tick_interval: Duration::from_secs(3600),
+        ..SessionConfig::default()
+    })
+    .unwrap()
+}
+
+#[test]
+fn seed_time_goal_pauses_torrent() {
+    let (meta, dir) = seeded_torrent("pause");
+    let session = session(SeedingGoals {
+        seed_time: Some(Duration::ZERO),
+        ..SeedingGoals::default()
+    });
+    let events = session.subscribe();
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    session.tick();
+
+    assert!(torrent.is_paused());
+    assert!(session.torrent(&torrent.info_hash()).is_some());
+    let events: Vec<_> = events.try_iter().collect();
+    assert_eq!(
+        events,
+        vec![
+            SessionEvent::TorrentAdded {
+                info_hash: torrent.info_hash()
+            },
+            SessionEvent::SeedingGoalReached {
+                info_hash: torrent.info_hash(),
+                goal: SeedingGoal::SeedTime
+            },
+        ]
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn per_torrent_goal_overrides_session_and_removes() {
+    let (meta, dir) = seeded_torrent("remove");
+    let session = session(SeedingGoals::default());
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    session.tick();
+    assert!(!torrent.is_paused());
+
+    torrent.set_seeding_goals(Some(SeedingGoals {
+        idle_time: Some(Duration::ZERO),
+        action: GoalAction::Remove,
+        ..SeedingGoals::default()
+    }));
+    session.tick();
+
+    assert!(session.torrent(&torrent.info_hash()).is_none());
+    let _ = std::fs::remove_dir_all(&dir);
+}
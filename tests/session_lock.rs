@@ -0,0 +1,113 @@
+#![cfg(feature = "sim")]
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::error::SessionError;
+use bittorrent_client::sim::internals::SessionLock;
+
+fn state_dir(label: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bt-lock-{}-{}", label, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn session(state_dir: &Path) -> Result<Session, SessionError> {
+    Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        state_dir: Some(state_dir.to_path_buf()),
+        ..SessionConfig::default()
+    })
+}
+
+#[test]
+fn second_session_on_same_state_dir_is_refused() {
+    let dir = state_dir("second");
+    let first = session(&dir).unwrap();
+
+    let Err(err) = session(&dir) else {
+        panic!("second session acquired a held lock");
+    };
+    match &err {
+        SessionError::Locked { pid, .. } => assert_eq!(*pid, Some(std::process::id())),
+        other => panic!("unexpected error: {}", other),
+    }
+    assert!(err.to_string().contains(&std::process::id().to_string()));
+
+    // The tick thread may still hold the session for a moment after the drop.
+    drop(first);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while dir.join("session.lock").exists() {
+        assert!(Instant::now() < deadline, "lock file was not released");
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert!(session(&dir).is_ok());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn lock_of_dead_owner_is_stale() {
+    let dir = state_dir("stale");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    std::fs::write(dir.join("session.lock"), "999999999\n").unwrap();
+    let (lock, stale) = SessionLock::acquire(&dir).unwrap();
+    assert!(stale);
+    assert_eq!(SessionLock::owner(lock.path()), Some(std::process::id()));
+    drop(lock);
+    assert!(!dir.join("session.lock").exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn lock_without_an_owner_is_held() {
+    let dir = state_dir("empty");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // What a reader would see of a lock whose owner hasn't written its PID yet.
+    std::fs::write(dir.join("session.lock"), "").unwrap();
+    let Err(err) = SessionLock::acquire(&dir) else {
+        panic!("took a lock whose owner is unknown");
+    };
+    assert!(matches!(err, SessionError::Locked { pid: None, .. }));
+    assert!(dir.join("session.lock").exists());
+    // Only the lock file is left behind.
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    std::fs::remove_file(dir.join("session.lock")).unwrap();
+    let (lock, stale) = SessionLock::acquire(&dir).unwrap();
+    assert!(!stale);
+    assert_eq!(SessionLock::owner(lock.path()), Some(std::process::id()));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn racing_acquirers_get_one_lock() {
+    let dir = state_dir("race");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("session.lock"), "999999999\n").unwrap();
+
+    let acquirers: Vec<_> = (0..8)
+        .map(|_| {
+            let dir = dir.clone();
+            thread::spawn(move || SessionLock::acquire(&dir))
+        })
+        .collect();
+    let results: Vec<_> = acquirers.into_iter().map(|t| t.join().unwrap()).collect();
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+    for result in &results {
+        if let Err(err) = result {
+            assert!(matches!(err, SessionError::Locked { .. }), "{}", err);
+        }
+    }
+
+    drop(results);
+    let _ = std::fs::remove_dir_all(&dir);
+}
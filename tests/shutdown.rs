@@ -0,0 +1,22 @@
+mod common;
+
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::session::engine::Session;
+
+#[test]
+fn dropping_the_session_frees_the_listening_port() {
+    let session = Session::new(common::local_config()).unwrap();
+    let addr = session.local_addr();
+    assert!(TcpListener::bind(addr).is_err());
+
+    // The tick thread may still hold the session for a moment after the drop.
+    drop(session);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while TcpListener::bind(addr).is_err() {
+        assert!(Instant::now() < deadline, "listening socket was not closed");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
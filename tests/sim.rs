@@ -0,0 +1,206 @@
+#![cfg(feature = "sim")]
+
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bittorrent_client::peer::value::PeerTimeouts;
+use bittorrent_client::piece::strategy::Sequential;
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::torrent::Torrent;
+use bittorrent_client::sim::network::SimNetwork;
+use bittorrent_client::sim::peer::FakePeer;
+use bittorrent_client::sim::wait;
+use bittorrent_client::storage::backend::{MemoryStorage, StorageBackend};
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+const PIECE_LENGTH: usize = 32 * 1024;
+const PIECES: usize = 6;
+// Every wait ends on the event it waits for; this bound only matters when a test fails.
+const WAIT: Duration = Duration::from_secs(20);
+
+fn payload() -> Vec<u8> {
+    (0..PIECES * PIECE_LENGTH)
+        .map(|i| (i * 13 % 241) as u8)
+        .collect()
+}
+
+fn meta(payload: &[u8]) -> TorrentMetaInfo {
+    common::single_file("sim.bin", PIECE_LENGTH, payload)
+}
+
+fn session(config: SessionConfig) -> Session {
+    Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        ..config
+    })
+    .unwrap()
+}
+
+// An empty torrent in memory that fetches pieces in index order.
+fn leech(session: &Session, meta: &TorrentMetaInfo) -> Arc<Torrent> {
+    let torrent = session
+        .add_torrent_with_backend(meta.clone(), Box::new(MemoryStorage::new(meta)))
+        .unwrap();
+    torrent.set_pick_strategy(Box::new(Sequential::default()));
+    torrent
+}
+
+fn wait_complete(torrent: &Torrent) {
+    assert!(
+        wait::until(torrent, WAIT, Torrent::is_complete),
+        "download did not finish"
+    );
+}
+
+fn contents(torrent: &Torrent) -> Vec<u8> {
+    let size = torrent.meta().total_size() as u32;
+    torrent.backend().read_block(0, 0, size).unwrap()
+}
+
+#[test]
+fn pieces_are_gathered_from_partial_seeds() {
+    let payload = payload();
+    let meta = meta(&payload);
+    let session = session(SessionConfig::default());
+    let torrent = leech(&session, &meta);
+    let mut network = SimNetwork::new(&session);
+
+    let low = FakePeer::seed(&meta, &payload).only(0..3);
+    let high = FakePeer::seed(&meta, &payload).only(3..PIECES);
+    let low = network.connect(&torrent.info_hash(), low).unwrap();
+    let high = network.connect(&torrent.info_hash(), high).unwrap();
+    wait_complete(&torrent);
+
+    assert_eq!(contents(&torrent), payload);
+    // Each was only asked for what it has: two blocks for each of its three pieces.
+    assert_eq!(low.log().blocks_sent, 6);
+    assert_eq!(high.log().blocks_sent, 6);
+    assert!(low.log().requests.iter().all(|&(index, _, _)| index < 3));
+    assert_ne!(low.addr(), high.addr());
+}
+
+#[test]
+fn corrupt_peer_is_banned_and_its_piece_fetched_elsewhere() {
+    let payload = payload();
+    let meta = meta(&payload);
+    let session = session(SessionConfig {
+        max_hash_failures: Some(1),
+        ..SessionConfig::default()
+    });
+    let torrent = leech(&session, &meta);
+    let mut network = SimNetwork::new(&session);
+
+    let liar = FakePeer::seed(&meta, &payload)
+        .corrupt(0)
+        .keep_alive(Duration::from_millis(50));
+    let liar = network.connect(&torrent.info_hash(), liar).unwrap();
+    let others = |torrent: &Torrent| (1..PIECES).all(|index| torrent.has_piece(index));
+    assert!(
+        wait::until(&torrent, WAIT, others),
+        "other pieces did not arrive"
+    );
+    // Nobody is blamed until a good copy shows which blocks were bad.
+    assert!(!torrent.has_piece(0));
+    assert_eq!(torrent.hash_failures(liar.addr().ip()), 0);
+
+    let honest = network
+        .connect(&torrent.info_hash(), FakePeer::seed(&meta, &payload))
+        .unwrap();
+    wait_complete(&torrent);
+    assert_eq!(contents(&torrent), payload);
+    assert!(liar.wait_for(WAIT, |log| log.closed));
+    assert_eq!(torrent.hash_failures(liar.addr().ip()), 1);
+    assert!(
+        honest
+            .log()
+            .requests
+            .iter()
+            .any(|&(index, _, _)| index == 0)
+    );
+}
+
+#[test]
+fn choked_download_is_finished_by_another_peer() {
+    let payload = payload();
+    let meta = meta(&payload);
+    let session = session(SessionConfig::default());
+    let torrent = leech(&session, &meta);
+    let mut network = SimNetwork::new(&session);
+
+    // Sends the first block of piece 0, then chokes for good.
+    let choker = FakePeer::seed(&meta, &payload).choke_after(1);
+    let choker = network.connect(&torrent.info_hash(), choker).unwrap();
+    // The second block of piece 0 never comes from it.
+    assert!(choker.wait_for(WAIT, |log| log.blocks_sent == 1));
+    assert!(!torrent.has_piece(0));
+
+    let other = network
+        .connect(&torrent.info_hash(), FakePeer::seed(&meta, &payload))
+        .unwrap();
+    wait_complete(&torrent);
+    assert_eq!(contents(&torrent), payload);
+    assert_eq!(choker.disconnect().blocks_sent, 1);
+    // The parked half of piece 0 wasn't asked for again.
+    let requested = other.log().requests;
+    assert_eq!(
+        requested
+            .iter()
+            .filter(|&&(index, _, _)| index == 0)
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn stalled_peer_times_out() {
+    let payload = payload();
+    let meta = meta(&payload);
+    let session = session(SessionConfig {
+        timeouts: PeerTimeouts {
+            read: Duration::from_millis(500),
+            ..PeerTimeouts::default()
+        },
+        ..SessionConfig::default()
+    });
+    let torrent = leech(&session, &meta);
+    let mut network = SimNetwork::new(&session);
+
+    let staller = FakePeer::seed(&meta, &payload).stall_after(3);
+    let staller = network.connect(&torrent.info_hash(), staller).unwrap();
+    assert!(staller.wait_for(WAIT, |log| log.closed));
+    assert_eq!(staller.log().blocks_sent, 3);
+    assert!(torrent.has_piece(0));
+
+    // Held on to: dropping a handle hangs up.
+    let _honest = network
+        .connect(&torrent.info_hash(), FakePeer::seed(&meta, &payload))
+        .unwrap();
+    wait_complete(&torrent);
+    assert_eq!(contents(&torrent), payload);
+}
+
+#[test]
+fn seed_serves_a_fake_leech() {
+    let payload = payload();
+    let meta = meta(&payload);
+    let session = session(SessionConfig::default());
+    let memory = MemoryStorage::new(&meta);
+    memory.write_block(0, 0, &payload).unwrap();
+    let torrent = session
+        .add_torrent_with_backend(meta.clone(), Box::new(memory))
+        .unwrap();
+    assert!(torrent.is_complete());
+
+    let mut network = SimNetwork::new(&session);
+    let leech = network
+        .connect(&torrent.info_hash(), FakePeer::leech(&meta))
+        .unwrap();
+    assert!(leech.wait_for(WAIT, |log| log.blocks_received == 2 * PIECES));
+    let log = leech.disconnect();
+    assert!(log.unchokes >= 1);
+    assert!(log.requests.is_empty());
+}
@@ -0,0 +1,49 @@
+use std::net::Ipv4Addr;
+
+use bittorrent_client::session::snapshot::{PeerSnapshot, SnapshotDiffer, diff};
+use bittorrent_client::units::ByteSize;
+
+fn peer(last_octet: u8, downloaded: u64) -> PeerSnapshot {
+    PeerSnapshot {
+        ip: Ipv4Addr::new(10, 0, 0, last_octet),
+        port: 6881,
+        id: None,
+        choked: true,
+        interested: false,
+        downloaded: ByteSize(downloaded),
+        uploaded: ByteSize(0),
+    }
+}
+
+#[test]
+fn polls_report_only_what_moved() {
+    let mut differ = SnapshotDiffer::new();
+
+    let first = differ.poll(vec![peer(1, 0), peer(2, 0)]);
+    assert_eq!(first.added, vec![peer(1, 0), peer(2, 0)]);
+    assert!(first.removed.is_empty() && first.changed.is_empty());
+
+    assert!(differ.poll(vec![peer(1, 0), peer(2, 0)]).is_empty());
+
+    // Peer 1 made progress, peer 2 left and peer 3 arrived.
+    let next = differ.poll(vec![peer(1, 16384), peer(3, 0)]);
+    assert_eq!(next.added, vec![peer(3, 0)]);
+    assert_eq!(next.removed, vec![(Ipv4Addr::new(10, 0, 0, 2), 6881)]);
+    assert_eq!(next.changed, vec![peer(1, 16384)]);
+
+    // A reconnecting UI starts over from the full list.
+    differ.reset();
+    let full = differ.poll(vec![peer(1, 16384), peer(3, 0)]);
+    assert_eq!(full.added.len(), 2);
+    assert!(full.removed.is_empty() && full.changed.is_empty());
+}
+
+#[test]
+fn peers_are_keyed_by_address_and_port() {
+    let mut moved = peer(1, 0);
+    moved.port = 6882;
+    let changes = diff(&[peer(1, 0)], &[moved.clone()]);
+    assert_eq!(changes.added, vec![moved]);
+    assert_eq!(changes.removed, vec![(Ipv4Addr::new(10, 0, 0, 1), 6881)]);
+    assert!(changes.changed.is_empty());
+}
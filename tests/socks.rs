@@ -0,0 +1,219 @@
+mod common;
+
+// Outgoing connections through a SOCKS5 proxy. The fake proxy here plays the destination
+// itself once it has agreed to connect, and reports where it was asked to connect to.
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::peer::socks::Socks5Proxy;
+use bittorrent_client::peer::value::Handshake;
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::tracker::client::TrackerClient;
+use bittorrent_client::tracker::value::TrackerRequest;
+use bittorrent_client::webseed::http_seed::HttpSeed;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn read_bytes(stream: &mut TcpStream, count: usize) -> Vec<u8> {
+    let mut buf = vec![0u8; count];
+    stream.read_exact(&mut buf).unwrap();
+    buf
+}
+
+// Speaks the proxy's side of one SOCKS5 negotiation. Returns the stream and the CONNECT
+// destination as host:port, or None if the credentials were refused.
+fn accept(
+    listener: &TcpListener,
+    credentials: Option<(&str, &str)>,
+) -> Option<(TcpStream, String)> {
+    let (mut stream, _) = listener.accept().unwrap();
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    let greeting = read_bytes(&mut stream, 2);
+    assert_eq!(greeting[0], 5);
+    let methods = read_bytes(&mut stream, greeting[1] as usize);
+
+    match credentials {
+        Some((username, password)) => {
+            assert!(methods.contains(&2));
+            stream.write_all(&[5, 2]).unwrap();
+            let header = read_bytes(&mut stream, 2);
+            let given_username = read_bytes(&mut stream, header[1] as usize);
+            let length = read_bytes(&mut stream, 1)[0];
+            let given_password = read_bytes(&mut stream, length as usize);
+            if given_username != username.as_bytes() || given_password != password.as_bytes() {
+                stream.write_all(&[1, 1]).unwrap();
+                return None;
+            }
+            stream.write_all(&[1, 0]).unwrap();
+        }
+        None => {
+            assert!(methods.contains(&0));
+            stream.write_all(&[5, 0]).unwrap();
+        }
+    }
+
+    let request = read_bytes(&mut stream, 4);
+    assert_eq!(&request[..3], &[5, 1, 0]);
+    let host = match request[3] {
+        1 => Ipv4Addr::from(<[u8; 4]>::try_from(read_bytes(&mut stream, 4)).unwrap()).to_string(),
+        3 => {
+            let length = read_bytes(&mut stream, 1)[0];
+            String::from_utf8(read_bytes(&mut stream, length as usize)).unwrap()
+        }
+        other => panic!("unexpected address type {}", other),
+    };
+    let port = u16::from_be_bytes(read_bytes(&mut stream, 2).try_into().unwrap());
+    stream
+        .write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0x1a, 0xe1])
+        .unwrap();
+    Some((stream, format!("{}:{}", host, port)))
+}
+
+fn proxy() -> (TcpListener, Socks5Proxy) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let proxy = Socks5Proxy::new(listener.local_addr().unwrap().to_string());
+    (listener, proxy)
+}
+
+#[test]
+fn connects_with_credentials() {
+    let (listener, proxy) = proxy();
+    let proxy = proxy.with_credentials("alice", "s3cret");
+    let server = thread::spawn(move || {
+        let (mut stream, destination) = accept(&listener, Some(("alice", "s3cret"))).unwrap();
+        stream.write_all(b"hello").unwrap();
+        destination
+    });
+
+    let target = SocketAddr::from((Ipv4Addr::new(10, 1, 2, 3), 51413));
+    let mut stream = proxy.connect(target, TIMEOUT).unwrap();
+    assert_eq!(read_bytes(&mut stream, 5), b"hello");
+    assert_eq!(server.join().unwrap(), "10.1.2.3:51413");
+}
+
+#[test]
+fn refused_credentials_are_an_error() {
+    let (listener, proxy) = proxy();
+    let proxy = proxy.with_credentials("alice", "wrong");
+    let server = thread::spawn(move || accept(&listener, Some(("alice", "s3cret"))).is_none());
+
+    let target = SocketAddr::from((Ipv4Addr::new(10, 1, 2, 3), 51413));
+    let err = proxy.connect(target, TIMEOUT).unwrap_err();
+    assert!(err.to_string().contains("credentials"));
+    assert!(server.join().unwrap());
+    assert!(!format!("{:?}", proxy).contains("wrong"));
+}
+
+#[test]
+fn tracker_hostnames_are_resolved_by_the_proxy() {
+    let (listener, proxy) = proxy();
+    let body: &[u8] = b"d8:intervali900e5:peers6:\x0a\x00\x00\x01\x1a\xe1e";
+    let server = thread::spawn(move || {
+        let (mut stream, destination) = accept(&listener, None).unwrap();
+        let mut request = [0u8; 4096];
+        let _ = stream.read(&mut request);
+        let header = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(header.as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+        destination
+    });
+
+    let request = TrackerRequest {
+        announce_url: "http://tracker.invalid:6969/announce".to_string(),
+        info_hash: [1; 20],
+        peer_id: *b"-RS0001-abcdefghijkl",
+        ip: None,
+        port: 6881,
+        uploaded: 0,
+        downloaded: 0,
+        left: 100,
+        compact: true,
+        no_peer_id: true,
+        event: None,
+        numwant: None,
+        key: None,
+        tracker_id: None,
+    };
+    let response = TrackerClient::query_tracker_via(&request, &proxy).unwrap();
+    assert_eq!(response.peers.len(), 1);
+    assert_eq!(server.join().unwrap(), "tracker.invalid:6969");
+}
+
+// Answers one HTTP request made through the proxy with `body`.
+fn serve_through(listener: TcpListener, body: &'static [u8]) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let (mut stream, destination) = accept(&listener, None).unwrap();
+        let mut request = [0u8; 4096];
+        let _ = stream.read(&mut request);
+        let header = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(header.as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+        destination
+    })
+}
+
+#[test]
+fn web_seeds_are_fetched_through_the_proxy() {
+    let (listener, seed_proxy) = proxy();
+    let server = serve_through(listener, b"piece");
+    let seed = HttpSeed::with_proxy("http://seed.invalid:8080/seed", Some(&seed_proxy)).unwrap();
+    assert_eq!(seed.fetch(&[1; 20], 0, &[], 5).unwrap(), b"piece");
+    assert_eq!(server.join().unwrap(), "seed.invalid:8080");
+
+    // The session hands its proxy to the torrent's url-list seeds.
+    let (listener, proxy) = proxy();
+    let server = serve_through(listener, b"data");
+    let dir = common::temp_dir("socks-url-seed");
+    let session = Session::new(SessionConfig {
+        proxy: Some(proxy),
+        tick_interval: Duration::from_millis(20),
+        ..common::local_config()
+    })
+    .unwrap();
+    let mut meta = common::single_file("seeded.bin", 16384, b"data");
+    meta.url_list = vec!["http://files.invalid/seeded.bin".to_string()];
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+    let deadline = Instant::now() + TIMEOUT;
+    while !torrent.is_complete() {
+        assert!(
+            Instant::now() < deadline,
+            "web seed download did not finish"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(server.join().unwrap(), "files.invalid:80");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn session_dials_peers_through_the_proxy() {
+    let (listener, proxy) = proxy();
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        proxy: Some(proxy),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-socks-{}", std::process::id()));
+    let meta = common::unverified("http://127.0.0.1:1/announce".to_string(), "proxied.bin");
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    // Nothing listens there; only the proxy can make the connection work.
+    let peer = SocketAddr::from((Ipv4Addr::new(192, 0, 2, 7), 6881));
+    session.add_peer(&torrent.info_hash(), peer).unwrap();
+    let (mut stream, destination) = accept(&listener, None).unwrap();
+    assert_eq!(destination, peer.to_string());
+
+    let handshake = read_bytes(&mut stream, 68);
+    Handshake::from_bytes(&handshake, &torrent.info_hash(), b"-FAKE0-seedseedseeds").unwrap();
+}
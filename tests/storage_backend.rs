@@ -0,0 +1,158 @@
+mod common;
+
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::piece::hash::Sha1Mode;
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::torrent::Torrent;
+use bittorrent_client::storage::backend::StorageBackend;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+const PIECE_LENGTH: usize = 16 * 1024;
+
+fn payload() -> Vec<u8> {
+    (0..70_000).map(|i| (i * 11 % 251) as u8).collect()
+}
+
+fn meta(payload: &[u8]) -> TorrentMetaInfo {
+    let files = [("a.bin", 30_000), ("b.bin", payload.len() - 30_000)]
+        .into_iter()
+        .map(|(name, length)| common::file(&[name], length))
+        .collect();
+    common::multi_file("backend-test", PIECE_LENGTH, files, payload)
+}
+
+fn config() -> SessionConfig {
+    common::local_config()
+}
+
+// Keeps the torrent in a buffer shared with the test and counts flushes.
+#[derive(Clone)]
+struct Buffer {
+    piece_length: usize,
+    data: Arc<Mutex<Vec<u8>>>,
+    flushes: Arc<AtomicUsize>,
+}
+
+impl Buffer {
+    fn new(meta: &TorrentMetaInfo) -> Buffer {
+        Buffer {
+            piece_length: meta.info.piece_length,
+            data: Arc::new(Mutex::new(vec![0; meta.total_size()])),
+            flushes: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn contents(&self) -> Vec<u8> {
+        self.data.lock().unwrap().clone()
+    }
+
+    fn range(&self, index: usize, begin: u32, length: usize) -> io::Result<Range<usize>> {
+        let start = index * self.piece_length + begin as usize;
+        if start + length > self.data.lock().unwrap().len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "out of range"));
+        }
+        Ok(start..start + length)
+    }
+}
+
+impl StorageBackend for Buffer {
+    fn read_block(&self, index: usize, begin: u32, length: u32) -> io::Result<Vec<u8>> {
+        let range = self.range(index, begin, length as usize)?;
+        Ok(self.data.lock().unwrap()[range].to_vec())
+    }
+
+    fn write_block(&self, index: usize, begin: u32, data: &[u8]) -> io::Result<()> {
+        let range = self.range(index, begin, data.len())?;
+        self.data.lock().unwrap()[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        self.data.lock().unwrap().len() as u64
+    }
+}
+
+#[test]
+fn custom_backend_takes_the_pieces() {
+    let payload = payload();
+    let meta = meta(&payload);
+    let torrent =
+        Torrent::with_backend(meta.clone(), Box::new(Buffer::new(&meta)), Sha1Mode::Fast).unwrap();
+    assert!(torrent.has_custom_backend());
+
+    for (index, piece) in payload.chunks(PIECE_LENGTH).enumerate() {
+        assert!(torrent.store_piece(index, piece).unwrap());
+    }
+    assert!(torrent.is_complete());
+    assert_eq!(
+        torrent.backend().read_block(1, 5, 10).unwrap(),
+        payload[PIECE_LENGTH + 5..][..10]
+    );
+    assert!(!Path::new("backend-test").exists());
+
+    // Renaming is about files, which this torrent doesn't have.
+    assert!(torrent.rename_root("other").is_err());
+}
+
+#[test]
+fn backend_must_match_the_torrent_size() {
+    let payload = payload();
+    let smaller = meta(&payload[..40_000]);
+    let result = Torrent::with_backend(
+        meta(&payload),
+        Box::new(Buffer::new(&smaller)),
+        Sha1Mode::Fast,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn sessions_seed_from_and_download_into_backends() {
+    let payload = payload();
+    let meta = meta(&payload);
+
+    let seed_memory = Buffer::new(&meta);
+    seed_memory.write_block(0, 0, &payload).unwrap();
+    let seeder = Session::new(config()).unwrap();
+    let seed = seeder
+        .add_torrent_with_backend(meta.clone(), Box::new(seed_memory))
+        .unwrap();
+    assert!(seed.is_complete());
+
+    let backend = Buffer::new(&meta);
+    let (memory, flushes) = (backend.clone(), Arc::clone(&backend.flushes));
+    let leecher = Session::new(config()).unwrap();
+    let torrent = leecher
+        .add_torrent_with_backend(meta, Box::new(backend))
+        .unwrap();
+    assert!(!torrent.is_complete());
+    leecher
+        .add_peer(&torrent.info_hash(), seeder.local_addr())
+        .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    // The flush follows the last piece being marked done.
+    while !torrent.is_complete() || flushes.load(Ordering::Relaxed) == 0 {
+        assert!(Instant::now() < deadline, "download did not finish in time");
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(memory.contents(), payload);
+    assert_eq!(flushes.load(Ordering::Relaxed), 1);
+
+    leecher.remove_torrent(&torrent.info_hash(), true).unwrap();
+    assert_eq!(flushes.load(Ordering::Relaxed), 2);
+    assert_eq!(memory.contents(), payload);
+}
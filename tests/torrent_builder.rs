@@ -0,0 +1,113 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::torrent::builder::{HashProgress, TorrentBuilder};
+use bittorrent_client::torrent::value::FilesInfo;
+use sha1::{Digest, Sha1};
+
+const PIECE_LENGTH: usize = 16 * 1024;
+
+// Files of awkward sizes, so pieces straddle file boundaries.
+fn content(label: &str) -> (PathBuf, Vec<u8>) {
+    let dir = std::env::temp_dir().join(format!("bt-builder-{}-{}", label, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let root = dir.join("album");
+    std::fs::create_dir_all(root.join("disc 2")).unwrap();
+    let a: Vec<u8> = (0..50_000).map(|i| (i % 251) as u8).collect();
+    let b: Vec<u8> = (0..7_000).map(|i| (i % 13) as u8).collect();
+    let c: Vec<u8> = (0..90_001).map(|i| (i % 197) as u8).collect();
+    std::fs::write(root.join("a.flac"), &a).unwrap();
+    std::fs::write(root.join("disc 2").join("b.flac"), &b).unwrap();
+    std::fs::write(root.join("z.cue"), &c).unwrap();
+    (dir, [a, b, c].concat())
+}
+
+#[test]
+fn pieces_match_a_plain_sequential_hash_whatever_the_thread_count() {
+    let (dir, payload) = content("threads");
+    let expected: Vec<[u8; 20]> = payload
+        .chunks(PIECE_LENGTH)
+        .map(|piece| Sha1::digest(piece).into())
+        .collect();
+
+    for threads in [1, 4] {
+        let meta = TorrentBuilder::new(dir.join("album"))
+            .announce("http://tracker.example/announce")
+            .piece_length(PIECE_LENGTH)
+            .threads(threads)
+            .build()
+            .unwrap();
+        assert_eq!(meta.info.name, "album");
+        assert_eq!(meta.info.pieces, expected);
+        let FilesInfo::MultiFile { files } = &meta.info.files_info else {
+            panic!("expected a multi-file torrent");
+        };
+        let paths: Vec<_> = files.iter().map(|file| file.path.join("/")).collect();
+        assert_eq!(paths, ["a.flac", "disc 2/b.flac", "z.cue"]);
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn progress_counts_up_to_the_totals() {
+    let (dir, payload) = content("progress");
+    let mut reports: Vec<HashProgress> = Vec::new();
+    let meta = TorrentBuilder::new(dir.join("album"))
+        .piece_length(PIECE_LENGTH)
+        .threads(3)
+        .build_with_progress(|progress| reports.push(progress))
+        .unwrap();
+
+    assert_eq!(reports.len(), meta.info.pieces.len());
+    assert!(
+        reports
+            .windows(2)
+            .all(|pair| pair[0].bytes_hashed < pair[1].bytes_hashed)
+    );
+    let last = reports.last().unwrap();
+    assert_eq!(last.pieces_hashed, last.total_pieces);
+    assert_eq!(last.bytes_hashed.as_u64(), payload.len() as u64);
+    assert_eq!(last.total_bytes, last.bytes_hashed);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn built_torrent_is_complete_where_it_was_made() {
+    let (dir, _) = content("seed");
+    let meta = TorrentBuilder::new(dir.join("album").join("z.cue"))
+        .announce("http://127.0.0.1:1/announce")
+        .piece_length(PIECE_LENGTH)
+        .private(true)
+        .comment("made in a test")
+        .build()
+        .unwrap();
+    assert!(matches!(
+        meta.info.files_info,
+        FilesInfo::SingleFile { length: 90_001, .. }
+    ));
+    assert!(meta.info.is_private());
+
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let torrent = session.add_torrent(meta, &dir.join("album")).unwrap();
+    assert!(torrent.is_complete());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn odd_piece_lengths_and_missing_content_are_refused() {
+    let (dir, _) = content("refused");
+    assert!(
+        TorrentBuilder::new(dir.join("album"))
+            .piece_length(20_000)
+            .build()
+            .is_err()
+    );
+    assert!(TorrentBuilder::new(dir.join("missing")).build().is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
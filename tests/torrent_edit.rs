@@ -0,0 +1,82 @@
+use bittorrent_client::bencode::parser::parse_value;
+use bittorrent_client::bencode::value::BencodeValue;
+use bittorrent_client::torrent::edit::TorrentEditor;
+use bittorrent_client::torrent::parser::torrent_from_bencode;
+use sha1::{Digest, Sha1};
+
+// An info dict with a key this client doesn't model, so re-encoding it would change the
+// info hash.
+const INFO: &[u8] = b"d6:lengthi5e4:name5:a.txt12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaa6:source4:TEST7:privatei1ee";
+
+fn torrent() -> Vec<u8> {
+    let mut bytes = b"d8:announce22:http://old.example/ann7:comment5:hello4:info".to_vec();
+    bytes.extend_from_slice(INFO);
+    bytes.extend_from_slice(b"5:x-tag3:abce");
+    bytes
+}
+
+#[test]
+fn untouched_torrent_round_trips() {
+    let editor = TorrentEditor::from_bytes(&torrent()).unwrap();
+    assert_eq!(editor.to_bytes(), torrent());
+    assert_eq!(editor.info_hash(), <[u8; 20]>::from(Sha1::digest(INFO)));
+    assert_eq!(editor.announce(), Some("http://old.example/ann"));
+    assert_eq!(editor.comment(), Some("hello"));
+}
+
+#[test]
+fn edits_keep_the_info_hash() {
+    let mut editor = TorrentEditor::from_bytes(&torrent()).unwrap();
+    editor.replace_trackers(&["http://new.example/announce"]);
+    editor.add_tracker("udp://backup.example:6969");
+    editor.set_comment(None);
+    editor.set_created_by(Some("retagger"));
+    editor.set_creation_date(Some(1_700_000_000));
+    editor.set_url_list(vec!["http://mirror.example/".to_string()]);
+    assert!(editor.remove("x-tag").is_some());
+    assert!(editor.set("info", BencodeValue::Integer(1)).is_err());
+
+    let bytes = editor.to_bytes();
+    let reopened = TorrentEditor::from_bytes(&bytes).unwrap();
+    assert_eq!(reopened.info_hash(), <[u8; 20]>::from(Sha1::digest(INFO)));
+    assert_eq!(reopened.announce(), Some("http://new.example/announce"));
+    assert_eq!(
+        reopened.announce_list(),
+        vec![
+            vec!["http://new.example/announce".to_string()],
+            vec!["udp://backup.example:6969".to_string()],
+        ]
+    );
+    assert_eq!(reopened.comment(), None);
+    assert_eq!(reopened.created_by(), Some("retagger"));
+    assert_eq!(reopened.creation_date(), Some(1_700_000_000));
+    assert!(reopened.get("x-tag").is_none());
+
+    let (value, rest) = parse_value(&bytes).unwrap();
+    assert!(rest.is_empty());
+    let meta = torrent_from_bencode(&value).unwrap();
+    assert_eq!(meta.announce, "http://new.example/announce");
+    assert_eq!(meta.url_list, vec!["http://mirror.example/".to_string()]);
+    assert!(bytes.windows(INFO.len()).any(|window| window == INFO));
+}
+
+#[test]
+fn removing_the_announce_url_promotes_the_next_tracker() {
+    let mut editor = TorrentEditor::from_bytes(&torrent()).unwrap();
+    editor.add_tracker("http://second.example/ann");
+    editor.add_tracker("http://second.example/ann");
+    assert_eq!(editor.announce_list().len(), 2);
+
+    editor.remove_tracker("http://old.example/ann");
+    assert_eq!(editor.announce(), Some("http://second.example/ann"));
+    assert_eq!(
+        editor.announce_list(),
+        vec![vec!["http://second.example/ann".to_string()]]
+    );
+}
+
+#[test]
+fn rejects_torrents_without_info() {
+    assert!(TorrentEditor::from_bytes(b"d8:announce3:urle").is_err());
+    assert!(TorrentEditor::from_bytes(b"l4:infoe").is_err());
+}
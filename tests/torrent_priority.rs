@@ -0,0 +1,66 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use bittorrent_client::session::bandwidth::{RateLimits, TorrentPriority, weighted_shares};
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+use bittorrent_client::units::Rate;
+
+use TorrentPriority::{High, Low, Normal};
+
+fn meta(name: &str) -> TorrentMetaInfo {
+    common::unverified("http://127.0.0.1:1/announce".to_string(), name)
+}
+
+#[test]
+fn busy_torrents_split_by_weight() {
+    let shares = weighted_shares(700, &[(High, 700), (Normal, 700), (Low, 700)]);
+    assert_eq!(shares, vec![400, 200, 100]);
+}
+
+#[test]
+fn idle_share_goes_to_the_busy_torrents() {
+    // The high priority torrent only uses 50 of its 400, leaving 650 for the other two.
+    let shares = weighted_shares(700, &[(High, 50), (Normal, 700), (Low, 700)]);
+    assert_eq!(shares[1], 433);
+    assert_eq!(shares[2], 216);
+    // It can still speed up to its own share right away.
+    assert_eq!(shares[0], 400);
+}
+
+#[test]
+fn a_torrent_alone_gets_the_whole_rate() {
+    assert_eq!(weighted_shares(1000, &[(Low, 10)]), vec![1000]);
+    assert!(weighted_shares(1000, &[]).is_empty());
+}
+
+#[test]
+fn the_session_splits_its_limits_each_tick() {
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        rate_limits: RateLimits {
+            download: Some(Rate(600_000)),
+            upload: None,
+        },
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-torrent-priority-{}", std::process::id()));
+    let wanted = session.add_torrent(meta("wanted.iso"), &dir).unwrap();
+    let other = session.add_torrent(meta("other.iso"), &dir).unwrap();
+    assert_eq!(wanted.priority(), Normal);
+    assert_eq!(wanted.rate_share(), RateLimits::default());
+
+    session.tick();
+    assert_eq!(wanted.rate_share().download, Some(Rate(300_000)));
+    assert_eq!(other.rate_share().download, Some(Rate(300_000)));
+
+    wanted.set_priority(High);
+    other.set_priority(Low);
+    session.tick();
+    assert_eq!(wanted.rate_share().download, Some(Rate(480_000)));
+    assert_eq!(other.rate_share().download, Some(Rate(120_000)));
+    assert_eq!(wanted.rate_share().upload, None);
+}
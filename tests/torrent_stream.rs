@@ -0,0 +1,132 @@
+mod common;
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use bittorrent_client::piece::hash::Sha1Mode;
+use bittorrent_client::session::stream::TorrentStream;
+use bittorrent_client::session::torrent::Torrent;
+use bittorrent_client::storage::backend::MemoryStorage;
+use tokio::io::{AsyncRead, ReadBuf};
+
+const PIECE_LENGTH: usize = 16 * 1024;
+
+fn payload() -> Vec<u8> {
+    (0..5 * PIECE_LENGTH + 1234)
+        .map(|i| (i * 31 % 253) as u8)
+        .collect()
+}
+
+fn torrent(payload: &[u8]) -> Arc<Torrent> {
+    let meta = common::single_file("stream.bin", PIECE_LENGTH, payload);
+    let backend = Box::new(MemoryStorage::new(&meta));
+    Arc::new(Torrent::with_backend(meta, backend, Sha1Mode::Fast).unwrap())
+}
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+struct Flag(AtomicBool);
+
+impl Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+// Polls the stream to its end on this thread, parking while it waits for pieces.
+fn read_to_end(stream: &mut TorrentStream) -> Vec<u8> {
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 5000];
+    loop {
+        assert!(Instant::now() < deadline, "stream stalled");
+        let mut buf = ReadBuf::new(&mut chunk);
+        match Pin::new(&mut *stream).poll_read(&mut cx, &mut buf) {
+            Poll::Ready(result) => {
+                result.unwrap();
+                if buf.filled().is_empty() {
+                    return data;
+                }
+                data.extend_from_slice(buf.filled());
+            }
+            Poll::Pending => thread::park_timeout(Duration::from_millis(500)),
+        }
+    }
+}
+
+#[test]
+fn stream_follows_the_download() {
+    let payload = payload();
+    let torrent = torrent(&payload);
+
+    // Pieces arrive last to first, so the reader waits for nearly all of them.
+    let writer = {
+        let torrent = Arc::clone(&torrent);
+        let payload = payload.clone();
+        thread::spawn(move || {
+            let pieces: Vec<&[u8]> = payload.chunks(PIECE_LENGTH).collect();
+            for (index, piece) in pieces.iter().enumerate().rev() {
+                thread::sleep(Duration::from_millis(10));
+                assert!(torrent.store_piece(index, piece).unwrap());
+            }
+        })
+    };
+
+    let mut stream = TorrentStream::new(Arc::clone(&torrent));
+    assert_eq!(read_to_end(&mut stream), payload);
+    assert_eq!(stream.remaining(), 0);
+    writer.join().unwrap();
+}
+
+#[test]
+fn stream_waits_for_missing_pieces_only() {
+    let payload = payload();
+    let torrent = torrent(&payload);
+    let piece = |index: usize| &payload[index * PIECE_LENGTH..(index + 1) * PIECE_LENGTH];
+    torrent.store_piece(1, piece(1)).unwrap();
+
+    // A range inside piece 1 reads right away.
+    let start = PIECE_LENGTH as u64 + 100;
+    let mut stream = TorrentStream::range(Arc::clone(&torrent), start..start + 300);
+    assert_eq!(read_to_end(&mut stream), piece(1)[100..400]);
+
+    // Across into piece 2, which isn't there: the first half comes, then it waits.
+    let end = 2 * PIECE_LENGTH as u64 + 50;
+    let mut stream = TorrentStream::range(Arc::clone(&torrent), start..end);
+    let flag = Arc::new(Flag(AtomicBool::new(false)));
+    let waker = Waker::from(Arc::clone(&flag));
+    let mut cx = Context::from_waker(&waker);
+    let mut chunk = vec![0u8; PIECE_LENGTH * 2];
+    let mut buf = ReadBuf::new(&mut chunk);
+    assert!(
+        Pin::new(&mut stream)
+            .poll_read(&mut cx, &mut buf)
+            .is_ready()
+    );
+    assert_eq!(buf.filled(), &piece(1)[100..]);
+    assert!(
+        Pin::new(&mut stream)
+            .poll_read(&mut cx, &mut buf)
+            .is_pending()
+    );
+    assert!(!flag.0.load(Ordering::Relaxed));
+
+    // A bad copy isn't a new piece.
+    torrent.store_piece(2, &vec![0u8; PIECE_LENGTH]).unwrap();
+    assert!(!flag.0.load(Ordering::Relaxed));
+    torrent.store_piece(2, piece(2)).unwrap();
+    assert!(flag.0.load(Ordering::Relaxed));
+    assert_eq!(read_to_end(&mut stream), piece(2)[..50]);
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::thread;
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::snapshot::TrackerStatus;
+use bittorrent_client::tracker::error::TrackerError;
+use bittorrent_client::tracker::filter::TrackerFilter;
+
+fn tracker() -> String {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let url = format!("http://{}/announce", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let body = "d8:completei3e8:intervali1800e5:peerslee";
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(header.as_bytes());
+            let _ = stream.write_all(body.as_bytes());
+        }
+    });
+    url
+}
+
+#[test]
+fn filter_checks_scheme_and_domain() {
+    let filter = TrackerFilter::new().deny("Tracking.example");
+    assert!(filter.check("https://tracker.example.org/announce").is_ok());
+    // WebTorrent trackers need the webtorrent feature.
+    let web = filter.check("wss://tracker.example.org");
+    if cfg!(feature = "webtorrent") {
+        assert!(web.is_ok());
+    } else {
+        assert!(matches!(web, Err(TrackerError::UnsupportedScheme(scheme)) if scheme == "wss"));
+    }
+    assert!(matches!(
+        filter.check("udp://tracker.example.org:1337/announce"),
+        Err(TrackerError::UnsupportedScheme(_))
+    ));
+    assert!(matches!(
+        filter.check("not a url"),
+        Err(TrackerError::InvalidUrl(_))
+    ));
+    assert!(matches!(
+        filter.check("http://a.TRACKING.example/announce"),
+        Err(TrackerError::Blocked(host)) if host == "a.tracking.example"
+    ));
+    // Only whole labels match.
+    assert!(filter.check("http://nottracking.example/announce").is_ok());
+
+    let allow = TrackerFilter::new()
+        .allow("example.org")
+        .deny("bad.example.org");
+    assert!(allow.check("http://example.org/announce").is_ok());
+    assert!(allow.check("http://t.example.org/announce").is_ok());
+    assert!(allow.check("http://bad.example.org/announce").is_err());
+    assert!(allow.check("http://example.com/announce").is_err());
+}
+
+#[test]
+fn skipped_trackers_get_a_status_and_dont_fail_the_announce() {
+    let working = tracker();
+    let session = Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        tracker_filter: TrackerFilter::new().deny("dead.invalid"),
+        ..SessionConfig::default()
+    })
+    .unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-tracker-filter-{}", std::process::id()));
+    let meta = common::unverified(
+        "udp://tracker.example.org:1337/announce".to_string(),
+        "filter.bin",
+    );
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+    torrent.add_tracker("http://dead.invalid/announce", "extra");
+    torrent.add_tracker(&working, "extra");
+
+    let response = session.announce(&torrent, None).unwrap();
+    assert_eq!(response.complete, Some(3));
+
+    let status = |url: &str| {
+        let trackers = torrent.trackers();
+        trackers.iter().find(|t| t.url == url).unwrap().status
+    };
+    assert_eq!(
+        status("udp://tracker.example.org:1337/announce"),
+        TrackerStatus::Unsupported
+    );
+    assert_eq!(
+        status("http://dead.invalid/announce"),
+        TrackerStatus::Blocked
+    );
+    assert_eq!(status(&working), TrackerStatus::Working);
+    // Skipping isn't failing: no backoff and nothing in the history.
+    for tracker in torrent.trackers() {
+        assert_eq!(tracker.breaker.failures(), 0);
+        if tracker.url != working {
+            assert!(tracker.history.is_empty());
+        }
+    }
+    assert!(torrent.error().is_none());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::snapshot::DEFAULT_TRACKER_GROUP;
+
+// Answers every announce with `seeders` and counts how many it got.
+fn tracker(seeders: u32) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let url = format!("http://{}/announce", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&hits);
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            counter.fetch_add(1, Ordering::SeqCst);
+            let body = format!("d8:completei{}e8:intervali1800e5:peerslee", seeders);
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(header.as_bytes());
+            let _ = stream.write_all(body.as_bytes());
+        }
+    });
+    (url, hits)
+}
+
+#[test]
+fn disabled_groups_are_not_announced_to() {
+    let (public, public_hits) = tracker(5);
+    let (private, private_hits) = tracker(9);
+
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-tracker-groups-{}", std::process::id()));
+    let meta = common::unverified(public.clone(), "groups.bin");
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+    assert!(torrent.add_tracker(&private, "private-x"));
+    assert!(!torrent.add_tracker(&private, "other"));
+
+    torrent.set_tracker_group_enabled("private-x", false);
+    session.announce(&torrent, None).unwrap();
+    assert_eq!(public_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(private_hits.load(Ordering::SeqCst), 0);
+
+    torrent.set_tracker_group_enabled(DEFAULT_TRACKER_GROUP, false);
+    torrent.set_tracker_group_enabled("private-x", true);
+    let response = session.announce(&torrent, None).unwrap();
+    assert_eq!(response.complete, Some(9));
+    assert_eq!(public_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(private_hits.load(Ordering::SeqCst), 1);
+
+    let trackers = torrent.trackers();
+    assert_eq!(trackers.len(), 2);
+    assert_eq!(trackers[0].group, DEFAULT_TRACKER_GROUP);
+    assert!(!trackers[0].enabled);
+    assert_eq!(trackers[0].seeders, Some(5));
+    assert_eq!(trackers[1].group, "private-x");
+    assert!(trackers[1].enabled);
+    assert_eq!(trackers[1].seeders, Some(9));
+
+    torrent.set_tracker_group_enabled("private-x", false);
+    assert!(session.announce(&torrent, None).is_err());
+    assert!(torrent.add_tracker("http://127.0.0.1:1/announce", "private-x"));
+    assert!(!torrent.trackers()[2].enabled);
+}
@@ -0,0 +1,146 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bittorrent_client::tracker::client::{MAX_REDIRECTS, TrackerClient};
+use bittorrent_client::tracker::error::TrackerError;
+use bittorrent_client::tracker::value::TrackerRequest;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+const BODY: &[u8] = b"d8:intervali900e5:peers6:\x0a\x00\x00\x01\x1a\xe1e";
+
+// Runs `answer` on the path of every request and writes back what it returns.
+fn serve(answer: fn(&str, u16) -> Vec<u8>) -> String {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let path = request_path(&stream);
+            let _ = stream.write_all(&answer(&path, port));
+        }
+    });
+    format!("http://127.0.0.1:{}/announce", port)
+}
+
+fn request_path(stream: &TcpStream) -> String {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut path = String::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+        if let Some(rest) = line.strip_prefix("GET ") {
+            path = rest.split(' ').next().unwrap().to_string();
+        }
+        line.clear();
+    }
+    path
+}
+
+fn response(status: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+    let mut out = format!(
+        "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        headers,
+        body.len()
+    )
+    .into_bytes();
+    out.extend_from_slice(body);
+    out
+}
+
+fn request(announce_url: String) -> TrackerRequest {
+    TrackerRequest {
+        announce_url,
+        info_hash: [1; 20],
+        peer_id: *b"-RS0001-abcdefghijkl",
+        ip: None,
+        port: 6881,
+        uploaded: 0,
+        downloaded: 0,
+        left: 100,
+        compact: true,
+        no_peer_id: true,
+        event: None,
+        numwant: None,
+        key: None,
+        tracker_id: None,
+    }
+}
+
+fn tracker_error(err: Box<dyn std::error::Error>) -> TrackerError {
+    *err.downcast::<TrackerError>().unwrap()
+}
+
+#[test]
+fn redirects_are_followed_with_the_query() {
+    let announce = serve(|path, port| {
+        if path.starts_with("/announce?") {
+            let query = path.split_once('?').unwrap().1;
+            let location = format!("Location: http://127.0.0.1:{}/moved?{}\r\n", port, query);
+            response("301 Moved Permanently", &location, b"")
+        } else if path.starts_with("/moved?") && path.contains("info_hash=") {
+            response("200 OK", "", BODY)
+        } else {
+            response("404 Not Found", "", b"")
+        }
+    });
+    let response = TrackerClient::query_tracker(&request(announce)).unwrap();
+    assert_eq!(response.interval, 900);
+    assert_eq!(response.peers.len(), 1);
+}
+
+#[test]
+fn redirect_loops_are_cut_off() {
+    let announce = serve(|_, port| {
+        let location = format!("Location: http://127.0.0.1:{}/announce\r\n", port);
+        response("302 Found", &location, b"")
+    });
+    let err = TrackerClient::query_tracker(&request(announce)).unwrap_err();
+    assert!(matches!(
+        tracker_error(err),
+        TrackerError::TooManyRedirects(MAX_REDIRECTS)
+    ));
+}
+
+#[test]
+fn gzipped_responses_are_unpacked() {
+    let announce = serve(|_, _| {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(BODY).unwrap();
+        response(
+            "200 OK",
+            "Content-Encoding: gzip\r\n",
+            &encoder.finish().unwrap(),
+        )
+    });
+    let response = TrackerClient::query_tracker(&request(announce)).unwrap();
+    assert_eq!(response.peers.len(), 1);
+}
+
+#[test]
+fn error_statuses_are_reported_as_such() {
+    let announce = serve(|_, _| response("503 Service Unavailable", "", b"busy"));
+    let err = TrackerClient::query_tracker(&request(announce)).unwrap_err();
+    assert!(matches!(tracker_error(err), TrackerError::HttpStatus(503)));
+
+    let err = TrackerClient::scrape(&serve(|_, _| response("404 Not Found", "", b"")), &[1; 20])
+        .unwrap_err();
+    assert!(matches!(tracker_error(err), TrackerError::HttpStatus(404)));
+}
+
+#[test]
+fn silent_trackers_time_out() {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let announce = format!("http://{}/announce", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let held: Vec<_> = listener.incoming().collect();
+        drop(held);
+    });
+    let started = Instant::now();
+    let err =
+        TrackerClient::query_tracker_within(&request(announce), None, Duration::from_millis(300))
+            .unwrap_err();
+    assert!(matches!(tracker_error(err), TrackerError::Timeout));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::thread;
+
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::tracker::client::parse_tracker_response;
+
+const FULL: &[u8] =
+    b"d8:completei12e10:incompletei3e8:intervali1800e12:min intervali60e5:peersle10:tracker id3:abce";
+
+#[test]
+fn swarm_counts_and_intervals_are_parsed() {
+    let response = parse_tracker_response(FULL).unwrap();
+    assert_eq!(response.interval, 1800);
+    assert_eq!(response.min_interval, Some(60));
+    assert_eq!(response.complete, Some(12));
+    assert_eq!(response.incomplete, Some(3));
+    assert_eq!(response.tracker_id.as_deref(), Some("abc"));
+}
+
+#[test]
+fn optional_keys_may_be_missing() {
+    let response = parse_tracker_response(b"d8:intervali900e5:peerslee").unwrap();
+    assert_eq!(response.interval, 900);
+    assert_eq!(response.min_interval, None);
+    assert_eq!(response.complete, None);
+    assert_eq!(response.incomplete, None);
+    assert_eq!(response.tracker_id, None);
+}
+
+#[test]
+fn announce_results_show_up_in_the_snapshot() {
+    let tracker = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let announce = format!("http://{}/announce", tracker.local_addr().unwrap());
+    thread::spawn(move || {
+        let (mut stream, _) = tracker.accept().unwrap();
+        let mut request = [0u8; 4096];
+        let _ = stream.read(&mut request);
+        let header = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            FULL.len()
+        );
+        stream.write_all(header.as_bytes()).unwrap();
+        stream.write_all(FULL).unwrap();
+    });
+
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-tracker-response-{}", std::process::id()));
+    let meta = common::unverified(announce.clone(), "missing.bin");
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    let before = &torrent.snapshot().trackers[0];
+    assert_eq!(before.url, announce);
+    assert_eq!(before.seeders, None);
+
+    session.announce(&torrent, None).unwrap();
+    let trackers = torrent.snapshot().trackers;
+    assert_eq!(trackers.len(), 1);
+    assert_eq!(trackers[0].seeders, Some(12));
+    assert_eq!(trackers[0].leechers, Some(3));
+    assert_eq!(trackers[0].interval, Some(1800));
+    assert_eq!(trackers[0].min_interval, Some(60));
+    assert_eq!(trackers[0].tracker_id.as_deref(), Some("abc"));
+}
+
+#[test]
+fn external_ip_is_read_in_either_family() {
+    let mut v4 = b"d11:external ip4:".to_vec();
+    v4.extend([203, 0, 113, 9]);
+    v4.extend(b"8:intervali1800e5:peers0:e");
+    let response = parse_tracker_response(&v4).unwrap();
+    assert_eq!(
+        response.external_ip,
+        Some(std::net::IpAddr::from([203, 0, 113, 9]))
+    );
+
+    let mut v6 = b"d11:external ip16:".to_vec();
+    v6.extend([0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    v6.extend(b"8:intervali1800e5:peers0:e");
+    let response = parse_tracker_response(&v6).unwrap();
+    assert_eq!(response.external_ip, Some("2001:db8::1".parse().unwrap()));
+
+    let response = parse_tracker_response(b"d8:intervali1800e5:peers0:e").unwrap();
+    assert_eq!(response.external_ip, None);
+}
@@ -0,0 +1,87 @@
+use bittorrent_client::tracker::value::{Event, TrackerRequest};
+
+fn request(announce_url: &str) -> TrackerRequest {
+    TrackerRequest {
+        announce_url: announce_url.to_string(),
+        info_hash: *b"\x12\x34abcXYZ-._~ /?&=%\xff\x00",
+        peer_id: *b"-RS0001-abcdefghijkl",
+        ip: None,
+        port: 6881,
+        uploaded: 0,
+        downloaded: 10,
+        left: 1000,
+        compact: true,
+        no_peer_id: false,
+        event: Some(Event::Started),
+        numwant: None,
+        key: None,
+        tracker_id: None,
+    }
+}
+
+const QUERY: &str = "info_hash=%124abcXYZ-._~%20%2F%3F%26%3D%25%FF%00\
+&peer_id=-RS0001-abcdefghijkl&port=6881&uploaded=0&downloaded=10&left=1000\
+&compact=1&event=started";
+
+#[test]
+fn plain_announce_url_starts_a_query() {
+    let url = request("http://tracker.example.org:6969/announce").build_url();
+    assert_eq!(
+        url,
+        format!("http://tracker.example.org:6969/announce?{}", QUERY)
+    );
+}
+
+#[test]
+fn passkey_query_is_extended_with_ampersand() {
+    let url = request("https://tracker.example.org/announce.php?passkey=0123abcd").build_url();
+    assert_eq!(
+        url,
+        format!(
+            "https://tracker.example.org/announce.php?passkey=0123abcd&{}",
+            QUERY
+        )
+    );
+}
+
+#[test]
+fn passkey_in_path_is_left_alone() {
+    let url = request("https://tracker.example.org/0123abcd/announce").build_url();
+    assert_eq!(
+        url,
+        format!("https://tracker.example.org/0123abcd/announce?{}", QUERY)
+    );
+}
+
+#[test]
+fn trailing_separator_is_not_doubled() {
+    let url = request("http://tracker.example.org/announce.php?uk=xyz&").build_url();
+    assert_eq!(
+        url,
+        format!("http://tracker.example.org/announce.php?uk=xyz&{}", QUERY)
+    );
+
+    let url = request("http://tracker.example.org/announce?").build_url();
+    assert_eq!(
+        url,
+        format!("http://tracker.example.org/announce?{}", QUERY)
+    );
+}
+
+#[test]
+fn fragment_stays_at_the_end() {
+    let url = request("http://tracker.example.org/announce#frag").build_url();
+    assert_eq!(
+        url,
+        format!("http://tracker.example.org/announce?{}#frag", QUERY)
+    );
+}
+
+#[test]
+fn unreserved_bytes_are_not_encoded() {
+    assert_eq!(
+        TrackerRequest::url_encode_bytes(b"Az09-._~"),
+        "Az09-._~".to_string()
+    );
+    assert_eq!(TrackerRequest::url_encode_bytes(b"+ :"), "%2B%20%3A");
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use bittorrent_client::session::bundle::TorrentBundle;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+use bittorrent_client::units::ByteSize;
+use bittorrent_client::webseed::url_seed::{UrlSeed, UrlSeedLimits};
+
+const PIECE_LENGTH: usize = 16 * 1024;
+
+fn session() -> Session {
+    Session::new(common::local_config()).unwrap()
+}
+
+fn meta(name: &str, announce: String, data: &[u8]) -> TorrentMetaInfo {
+    TorrentMetaInfo {
+        announce,
+        ..common::single_file(name, PIECE_LENGTH, data)
+    }
+}
+
+// An HTTP tracker that answers everything and passes on the query of each announce.
+fn tracker() -> (String, Receiver<HashMap<String, String>>) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let announce = format!("http://{}/announce", listener.local_addr().unwrap());
+    let (requests, received) = mpsc::channel();
+    thread::spawn(move || {
+        let body: &[u8] = b"d8:intervali900e5:peerslee";
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = [0u8; 4096];
+            let read = stream.read(&mut request).unwrap();
+            let text = String::from_utf8_lossy(&request[..read]);
+            let target = text.split(' ').nth(1).unwrap_or_default();
+            let query = target.split_once('?').map(|(_, q)| q).unwrap_or_default();
+            let params = query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            let _ = requests.send(params);
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(body).unwrap();
+        }
+    });
+    (announce, received)
+}
+
+// Serves `data` at every path, honouring Range requests.
+fn web_seed(data: Vec<u8>) -> String {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let base = format!("http://{}/transfer.bin", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut range = (0, data.len() - 1);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                if let Some(rest) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                    let (start, end) = rest.trim().split_once('-').unwrap();
+                    range = (start.parse().unwrap(), end.parse().unwrap());
+                }
+                line.clear();
+            }
+            let part = &data[range.0..=range.1];
+            write!(
+                stream,
+                "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                part.len()
+            )
+            .unwrap();
+            stream.write_all(part).unwrap();
+        }
+    });
+    base
+}
+
+#[test]
+fn announces_report_payload_moved_since_started() {
+    let data: Vec<u8> = (0..40_000).map(|i| (i % 233) as u8).collect();
+    let (announce, received) = tracker();
+    let dir = std::env::temp_dir().join(format!("bt-transfer-live-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let session = session();
+    let torrent = session
+        .add_torrent(meta("transfer.bin", announce, &data), &dir)
+        .unwrap();
+
+    session.announce(&torrent, None).unwrap();
+    let started = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(started["event"], "started");
+    assert_eq!(started["downloaded"], "0");
+    assert_eq!(started["left"], data.len().to_string());
+
+    let seed = UrlSeed::new(&web_seed(data.clone()), UrlSeedLimits::default()).unwrap();
+    seed.download_missing(&torrent).unwrap();
+    assert!(torrent.is_complete());
+    assert_eq!(
+        torrent.transfer().since_started().downloaded,
+        ByteSize(40_000)
+    );
+
+    session.announce(&torrent, None).unwrap();
+    let completed = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(completed["event"], "completed");
+    assert_eq!(completed["downloaded"], data.len().to_string());
+    assert_eq!(completed["uploaded"], "0");
+    assert_eq!(completed["left"], "0");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn totals_from_earlier_sessions_are_not_announced_again() {
+    let data: Vec<u8> = (0..20_000).map(|i| (i % 229) as u8).collect();
+    let (announce, received) = tracker();
+    let dir = std::env::temp_dir().join(format!("bt-transfer-import-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("imported.bin"), &data).unwrap();
+
+    let source = session();
+    let torrent = source
+        .add_torrent(meta("imported.bin", announce, &data), &dir)
+        .unwrap();
+    let bundle_dir = dir.join("export");
+    source
+        .export_torrent(&torrent.info_hash(), &bundle_dir)
+        .unwrap();
+    let mut bundle = TorrentBundle::load(&bundle_dir).unwrap();
+    bundle.downloaded = 1000;
+    bundle.uploaded = 5000;
+    bundle.save(&bundle_dir).unwrap();
+
+    let target = session();
+    let imported = target.import_torrent(&bundle_dir, &dir).unwrap();
+    assert_eq!(imported.transfer().totals().uploaded, ByteSize(5000));
+    target.announce(&imported, None).unwrap();
+    let started = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(started["event"], "started");
+    assert_eq!(started["uploaded"], "0");
+    assert_eq!(started["downloaded"], "0");
+    assert_eq!(started["left"], "0");
+    let _ = std::fs::remove_dir_all(&dir);
+}
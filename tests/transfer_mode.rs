@@ -0,0 +1,203 @@
+mod common;
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use bittorrent_client::bencode::value::BencodeValue;
+use bittorrent_client::peer::reserved::ReservedBits;
+use bittorrent_client::peer::value::{Handshake, PeerMessage};
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::error::SessionError;
+use bittorrent_client::session::handle::TransferMode;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+
+fn content() -> Vec<u8> {
+    (0..20_000).map(|i| (i * 13) as u8).collect()
+}
+
+fn meta(name: &str, data: &[u8], private: bool) -> TorrentMetaInfo {
+    let mut extras = HashMap::new();
+    if private {
+        extras.insert("private".to_string(), BencodeValue::Integer(1));
+    }
+    let mut meta = common::single_file(name, data.len(), data);
+    meta.info.extras = extras;
+    meta
+}
+
+fn session() -> Session {
+    Session::new(common::local_config()).unwrap()
+}
+
+fn handshake(stream: &mut TcpStream, info_hash: [u8; 20]) {
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    Handshake::perform_handshake(
+        stream,
+        &info_hash,
+        b"-FAKE0-modemodemode0",
+        ReservedBits::FAST,
+        Duration::from_secs(10),
+    )
+    .unwrap();
+}
+
+// Messages the peer gets within `wait`.
+fn received(stream: &mut TcpStream, wait: Duration) -> Vec<PeerMessage> {
+    stream.set_read_timeout(Some(wait)).unwrap();
+    let mut messages = Vec::new();
+    let deadline = Instant::now() + wait;
+    while Instant::now() < deadline {
+        match PeerMessage::read_peer_message(stream) {
+            Ok(message) => messages.push(message),
+            Err(_) => break,
+        }
+    }
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    messages
+}
+
+#[test]
+fn private_torrents_cannot_stop_uploading() {
+    let data = content();
+    let session = session();
+    let private = session
+        .add_torrent(
+            meta("mode-private", &data, true),
+            &common::temp_dir("mode-private"),
+        )
+        .unwrap();
+    assert!(private.meta().info.is_private());
+    assert!(matches!(
+        private.set_transfer_mode(TransferMode::DownloadOnly),
+        Err(SessionError::PrivateTorrent(_))
+    ));
+    assert_eq!(private.transfer_mode(), TransferMode::Normal);
+    private.set_transfer_mode(TransferMode::UploadOnly).unwrap();
+
+    let public = session
+        .add_torrent(
+            meta("mode-public", &data, false),
+            &common::temp_dir("mode-public"),
+        )
+        .unwrap();
+    public
+        .set_transfer_mode(TransferMode::DownloadOnly)
+        .unwrap();
+    assert_eq!(public.transfer_mode(), TransferMode::DownloadOnly);
+}
+
+#[test]
+fn download_only_refuses_requests_until_switched_back() {
+    let data = content();
+    let dir = common::temp_dir("mode-seed");
+    std::fs::write(dir.join("mode-seed"), &data).unwrap();
+    let session = session();
+    let torrent = session
+        .add_torrent(meta("mode-seed", &data, false), &dir)
+        .unwrap();
+    assert!(torrent.is_complete());
+    torrent
+        .set_transfer_mode(TransferMode::DownloadOnly)
+        .unwrap();
+
+    let mut stream = TcpStream::connect(session.local_addr()).unwrap();
+    handshake(&mut stream, torrent.info_hash());
+    PeerMessage::HaveNone
+        .write_peer_message(&mut stream)
+        .unwrap();
+    PeerMessage::Interested
+        .write_peer_message(&mut stream)
+        .unwrap();
+    let request = PeerMessage::Request {
+        index: 0,
+        begin: 0,
+        length: 16384,
+    };
+    request.write_peer_message(&mut stream).unwrap();
+    let messages = received(&mut stream, Duration::from_millis(500));
+    assert!(!messages.contains(&PeerMessage::Unchoke), "{:?}", messages);
+    assert!(messages.contains(&PeerMessage::RejectRequest {
+        index: 0,
+        begin: 0,
+        length: 16384,
+    }));
+
+    torrent.set_transfer_mode(TransferMode::Normal).unwrap();
+    PeerMessage::KeepAlive
+        .write_peer_message(&mut stream)
+        .unwrap();
+    loop {
+        if PeerMessage::read_peer_message(&mut stream).unwrap() == PeerMessage::Unchoke {
+            break;
+        }
+    }
+    request.write_peer_message(&mut stream).unwrap();
+    loop {
+        if let PeerMessage::Piece { block, .. } =
+            PeerMessage::read_peer_message(&mut stream).unwrap()
+        {
+            assert_eq!(block, data[..16384]);
+            break;
+        }
+    }
+}
+
+#[test]
+fn upload_only_never_asks_for_pieces() {
+    let data = content();
+    let dir = common::temp_dir("mode-leech");
+    let session = session();
+    let torrent = session
+        .add_torrent(meta("mode-leech", &data, false), &dir)
+        .unwrap();
+    torrent.set_transfer_mode(TransferMode::UploadOnly).unwrap();
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    session
+        .add_peer(&torrent.info_hash(), listener.local_addr().unwrap())
+        .unwrap();
+    let (mut stream, _) = listener.accept().unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut theirs = [0u8; 68];
+    std::io::Read::read_exact(&mut stream, &mut theirs).unwrap();
+    let ours = Handshake::new(
+        torrent.info_hash(),
+        *b"-FAKE0-seedseedseeds",
+        ReservedBits::FAST,
+    );
+    std::io::Write::write_all(&mut stream, &ours.to_bytes()).unwrap();
+    PeerMessage::HaveAll
+        .write_peer_message(&mut stream)
+        .unwrap();
+    PeerMessage::Unchoke
+        .write_peer_message(&mut stream)
+        .unwrap();
+    let messages = received(&mut stream, Duration::from_millis(500));
+    assert!(
+        !messages
+            .iter()
+            .any(|m| matches!(m, PeerMessage::Interested | PeerMessage::Request { .. })),
+        "{:?}",
+        messages
+    );
+
+    torrent.set_transfer_mode(TransferMode::Normal).unwrap();
+    PeerMessage::KeepAlive
+        .write_peer_message(&mut stream)
+        .unwrap();
+    loop {
+        if let PeerMessage::Request { index, .. } =
+            PeerMessage::read_peer_message(&mut stream).unwrap()
+        {
+            assert_eq!(index, 0);
+            break;
+        }
+    }
+}
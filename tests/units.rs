@@ -0,0 +1,26 @@
+use bittorrent_client::units::{ByteSize, Rate};
+
+#[test]
+fn parses_sizes_with_decimal_and_binary_units() {
+    assert_eq!("512".parse::<ByteSize>().unwrap(), ByteSize(512));
+    assert_eq!("16KiB".parse::<ByteSize>().unwrap(), ByteSize::kib(16));
+    assert_eq!("1 MB".parse::<ByteSize>().unwrap(), ByteSize(1_000_000));
+    assert_eq!("1.5gib".parse::<ByteSize>().unwrap(), ByteSize(3 << 29));
+    assert!("12 parsecs".parse::<ByteSize>().is_err());
+    assert!("MiB".parse::<ByteSize>().is_err());
+}
+
+#[test]
+fn parses_rates() {
+    assert_eq!("500KiB/s".parse::<Rate>().unwrap(), Rate(500 * 1024));
+    assert_eq!("1MBps".parse::<Rate>().unwrap(), Rate(1_000_000));
+    assert!("fast".parse::<Rate>().is_err());
+}
+
+#[test]
+fn displays_human_readable_values() {
+    assert_eq!(ByteSize(100).to_string(), "100 B");
+    assert_eq!(ByteSize::mib(1).to_string(), "1.00 MiB");
+    assert_eq!(ByteSize(1536).to_string(), "1.50 KiB");
+    assert_eq!(Rate(2048).to_string(), "2.00 KiB/s");
+}
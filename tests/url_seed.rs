@@ -0,0 +1,200 @@
+mod common;
+
+// BEP 19 web seed against a minimal HTTP server that understands Range requests.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use bittorrent_client::session::config::SessionConfig;
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::event::SessionEvent;
+use bittorrent_client::torrent::value::TorrentMetaInfo;
+use bittorrent_client::units::ByteSize;
+use bittorrent_client::webseed::policy::WebSeedPolicy;
+use bittorrent_client::webseed::url_seed::{PieceRun, UrlSeed, UrlSeedLimits};
+use std::time::Duration;
+
+const PIECE_LENGTH: usize = 16 * 1024;
+
+// Serves `files` (URL path -> contents) and counts the requests it answered.
+fn serve(files: HashMap<String, Vec<u8>>) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let base = format!("http://{}/", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&requests);
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut path = String::new();
+            let mut range = None;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                if let Some(rest) = line.strip_prefix("GET ") {
+                    path = rest.split(' ').next().unwrap().to_string();
+                } else if let Some(rest) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                    let (start, end) = rest.trim().split_once('-').unwrap();
+                    range = Some((
+                        start.parse::<usize>().unwrap(),
+                        end.parse::<usize>().unwrap(),
+                    ));
+                }
+                line.clear();
+            }
+
+            let body = &files[&path];
+            let (start, end) = range.unwrap_or((0, body.len() - 1));
+            let part = &body[start..=end];
+            write!(
+                stream,
+                "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                part.len()
+            )
+            .unwrap();
+            stream.write_all(part).unwrap();
+        }
+    });
+
+    (base, requests)
+}
+
+#[test]
+fn adjacent_pieces_are_fetched_in_few_range_requests() {
+    let a: Vec<u8> = (0..40_000).map(|i| (i % 253) as u8).collect();
+    let b: Vec<u8> = (0..60_000).map(|i| (i % 241) as u8).collect();
+    let payload = [a.as_slice(), b.as_slice()].concat();
+
+    let files = vec![
+        common::file(&["a.bin"], a.len()),
+        common::file(&["b.bin"], b.len()),
+    ];
+    let meta = common::multi_file("web seeded", PIECE_LENGTH, files, &payload);
+
+    let mut files = HashMap::new();
+    files.insert("/web%20seeded/a.bin".to_string(), a);
+    files.insert("/web%20seeded/b.bin".to_string(), b);
+    let (base, requests) = serve(files);
+
+    let dir = std::env::temp_dir().join(format!("bt-url-seed-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let session = Session::new(common::local_config()).unwrap();
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    let seed = UrlSeed::new(
+        &base,
+        UrlSeedLimits {
+            max_request: 4 * PIECE_LENGTH as u64,
+            ..UrlSeedLimits::default()
+        },
+    )
+    .unwrap();
+
+    // Seven pieces in total; runs are capped at four.
+    let runs = seed.plan(&torrent, &[6, 0, 1, 2, 3, 4, 5]);
+    assert_eq!(
+        runs,
+        vec![
+            PieceRun { first: 0, count: 4 },
+            PieceRun { first: 4, count: 3 }
+        ]
+    );
+
+    assert_eq!(seed.download_missing(&torrent).unwrap(), 7);
+    assert!(torrent.is_complete());
+    // The first run spans both files, the second only b.bin.
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn single_file_torrent(data: &[u8], url: &str) -> TorrentMetaInfo {
+    TorrentMetaInfo {
+        url_list: vec![url.to_string()],
+        ..common::single_file("single.bin", PIECE_LENGTH, data)
+    }
+}
+
+fn web_seeding_session(max_corrupt_pieces: u32) -> Session {
+    Session::new(SessionConfig {
+        listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        web_seeds: WebSeedPolicy::Always,
+        web_seed_limits: UrlSeedLimits {
+            max_corrupt_pieces,
+            ..UrlSeedLimits::default()
+        },
+        tick_interval: Duration::from_millis(20),
+        ..SessionConfig::default()
+    })
+    .unwrap()
+}
+
+#[test]
+fn session_downloads_from_url_list_and_accounts_it_separately() {
+    let data: Vec<u8> = (0..70_000).map(|i| (i % 239) as u8).collect();
+    let mut files = HashMap::new();
+    files.insert("/single.bin".to_string(), data.clone());
+    let (base, _) = serve(files);
+
+    let dir = std::env::temp_dir().join(format!("bt-url-list-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let session = web_seeding_session(3);
+    let torrent = session
+        .add_torrent(
+            single_file_torrent(&data, &format!("{}single.bin", base)),
+            &dir,
+        )
+        .unwrap();
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(30);
+    while !torrent.is_complete() {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "web seed download stalled"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let snapshot = torrent.snapshot();
+    assert_eq!(snapshot.web_seed_downloaded, ByteSize(data.len() as u64));
+    assert_eq!(snapshot.downloaded, snapshot.web_seed_downloaded);
+    assert_eq!(torrent.peer_downloaded(), ByteSize(0));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn corrupt_web_seed_is_disabled() {
+    let data = vec![1u8; 3 * PIECE_LENGTH];
+    let mut files = HashMap::new();
+    files.insert("/single.bin".to_string(), vec![2u8; data.len()]);
+    let (base, _) = serve(files);
+
+    let dir = std::env::temp_dir().join(format!("bt-corrupt-seed-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let session = web_seeding_session(2);
+    let events = session.subscribe();
+    let torrent = session
+        .add_torrent(
+            single_file_torrent(&data, &format!("{}single.bin", base)),
+            &dir,
+        )
+        .unwrap();
+
+    let url = loop {
+        match events.recv_timeout(Duration::from_secs(30)).unwrap() {
+            SessionEvent::WebSeedDisabled { url, .. } => break url,
+            _ => continue,
+        }
+    };
+    assert!(url.ends_with("/single.bin"));
+    assert!(torrent.web_seeds()[0].is_disabled());
+    assert!(!torrent.is_complete());
+    assert_eq!(torrent.web_seed_downloaded(), ByteSize(0));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
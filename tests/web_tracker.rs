@@ -0,0 +1,143 @@
+#![cfg(feature = "webtorrent")]
+
+mod common;
+
+use std::net::{Ipv4Addr, TcpListener};
+use std::thread;
+use std::time::Duration;
+
+use bittorrent_client::session::engine::Session;
+use bittorrent_client::session::snapshot::TrackerStatus;
+use bittorrent_client::tracker::value::{Event, TrackerRequest};
+use bittorrent_client::tracker::websocket::WebTrackerClient;
+use serde_json::{Value, json};
+use tungstenite::Message;
+
+const INFO_HASH: [u8; 20] = [0xe5; 20];
+
+fn text(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+// A WebTorrent tracker answering each announce with `replies`, and passing on what it
+// received. Replies without an info hash get the announce's.
+fn tracker(replies: Vec<Value>) -> (String, std::sync::mpsc::Receiver<Value>) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let url = format!("ws://{}/", listener.local_addr().unwrap());
+    let (sent, received) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let Ok(mut socket) = tungstenite::accept(stream) else {
+                continue;
+            };
+            let Ok(Message::Text(announce)) = socket.read() else {
+                continue;
+            };
+            let announce: Value = serde_json::from_str(&announce).unwrap();
+            for reply in &replies {
+                let mut reply = reply.clone();
+                if reply.get("info_hash").is_none() {
+                    reply["info_hash"] = announce["info_hash"].clone();
+                }
+                let _ = socket.send(Message::text(reply.to_string()));
+            }
+            let _ = sent.send(announce);
+            // Hold the connection open until the client closes it.
+            while socket.read().is_ok() {}
+        }
+    });
+    (url, received)
+}
+
+fn request(url: &str) -> TrackerRequest {
+    TrackerRequest {
+        announce_url: url.to_string(),
+        info_hash: INFO_HASH,
+        peer_id: *b"-RS0001-abcdefghijkl",
+        ip: None,
+        port: 6881,
+        uploaded: 0,
+        downloaded: 0,
+        left: 100,
+        compact: true,
+        no_peer_id: false,
+        event: Some(Event::Started),
+        numwant: Some(10),
+        key: None,
+        tracker_id: None,
+    }
+}
+
+#[test]
+fn announces_and_collects_offers() {
+    let (url, received) = tracker(vec![
+        json!({"action": "announce", "info_hash": text(&[1; 20]), "interval": 5}),
+        json!({
+            "action": "announce",
+            "info_hash": text(&INFO_HASH),
+            "interval": 120,
+            "complete": 4,
+            "incomplete": 7,
+        }),
+        json!({
+            "action": "announce",
+            "info_hash": text(&INFO_HASH),
+            "peer_id": text(&[0xaa; 20]),
+            "offer_id": text(&[0xbb; 20]),
+            "offer": {"type": "offer", "sdp": "v=0"},
+        }),
+    ]);
+
+    let announce = WebTrackerClient::announce(
+        &request(&url),
+        Duration::from_secs(5),
+        Duration::from_millis(300),
+    )
+    .unwrap();
+    assert_eq!(announce.response.interval, 120);
+    assert_eq!(announce.response.complete, Some(4));
+    assert_eq!(announce.response.incomplete, Some(7));
+    assert!(announce.response.peers.is_empty());
+    assert_eq!(announce.offers.len(), 1);
+    assert_eq!(announce.offers[0].peer_id, vec![0xaa; 20]);
+    assert_eq!(announce.offers[0].offer_id, vec![0xbb; 20]);
+    assert_eq!(announce.offers[0].sdp, "v=0");
+
+    let sent = received.recv().unwrap();
+    assert_eq!(sent["action"], "announce");
+    assert_eq!(sent["event"], "started");
+    assert_eq!(sent["info_hash"], text(&INFO_HASH));
+    assert_eq!(sent["numwant"], 10);
+    assert_eq!(sent["left"], 100);
+}
+
+#[test]
+fn failures_and_silence_are_errors() {
+    let (url, _received) = tracker(vec![json!({"failure reason": "unregistered torrent"})]);
+    let err = WebTrackerClient::announce(&request(&url), Duration::from_secs(5), Duration::ZERO)
+        .unwrap_err();
+    assert!(err.to_string().contains("unregistered torrent"), "{}", err);
+
+    let (url, _received) = tracker(Vec::new());
+    assert!(
+        WebTrackerClient::announce(&request(&url), Duration::from_millis(300), Duration::ZERO)
+            .is_err()
+    );
+}
+
+#[test]
+fn session_announces_to_web_trackers() {
+    let (url, received) = tracker(vec![json!({"action": "announce", "complete": 2})]);
+    let session = Session::new(common::local_config()).unwrap();
+    let dir = std::env::temp_dir().join(format!("bt-web-tracker-{}", std::process::id()));
+    let meta = common::unverified(url.clone(), "web.bin");
+    let torrent = session.add_torrent(meta, &dir).unwrap();
+
+    let response = session.announce(&torrent, None).unwrap();
+    assert_eq!(response.complete, Some(2));
+    assert_eq!(received.recv().unwrap()["event"], "started");
+    let trackers = torrent.trackers();
+    assert_eq!(trackers[0].status, TrackerStatus::Working);
+    assert_eq!(trackers[0].seeders, Some(2));
+    let _ = std::fs::remove_dir_all(&dir);
+}